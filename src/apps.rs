@@ -0,0 +1,276 @@
+use crate::import::{self, Cell, MappedRow};
+use crate::recipes::Recipe;
+use serde_json::Value;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Meal-planning apps whose exports `mealplan import --from` reads
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum App {
+    PlanToEat,
+    Anylist,
+}
+
+impl fmt::Display for App {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            App::PlanToEat => write!(f, "Plan to Eat"),
+            App::Anylist => write!(f, "AnyList"),
+        }
+    }
+}
+
+/// The recipes and planned meals read from another app's export
+#[derive(Debug, Default)]
+pub struct AppExport {
+    pub recipes: Vec<Recipe>,
+    pub meals: Vec<MappedRow>,
+}
+
+// Column headings each field goes by, compared without case, spaces or punctuation
+const TITLE: &[&str] = &["title", "name", "recipe", "recipename", "recipetitle"];
+const DATE: &[&str] = &["date", "day", "planneddate", "scheduledon"];
+const MEAL_TYPE: &[&str] = &["course", "mealtype", "type", "label", "labelname", "meal"];
+const INGREDIENTS: &[&str] = &["ingredients"];
+const STEPS: &[&str] = &["directions", "instructions", "steps", "preparationsteps", "method"];
+const TAGS: &[&str] = &["tags", "categories", "category"];
+const LINK: &[&str] = &["url", "originalurl", "sourceurl", "source"];
+const SERVINGS: &[&str] = &["servings", "yield"];
+const PHOTO: &[&str] = &["photourl", "photo", "imageurl", "image"];
+
+/// Reads an app's export: a CSV of recipes or of planned meals, or, from AnyList, a JSON
+/// file holding either or both
+pub fn read_export(app: App, path: &Path) -> io::Result<AppExport> {
+    let is_json = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    if is_json {
+        let json: Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        return from_json(app, &json);
+    }
+    from_rows(app, import::read_rows(path)?)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn normalize(heading: &str) -> String {
+    heading.chars().filter(|c| c.is_alphanumeric()).collect::<String>().to_lowercase()
+}
+
+/// Reads the rows of a CSV export, telling a planner export from a recipe export by its
+/// column headings
+fn from_rows(app: App, rows: Vec<Vec<Cell>>) -> io::Result<AppExport> {
+    let mut rows = rows.into_iter();
+    let headings: Vec<String> = rows.next().unwrap_or_default().iter().map(|cell| normalize(&cell.text())).collect();
+    let column = |names: &[&str]| names.iter().find_map(|name| headings.iter().position(|h| h == name));
+    let title = column(TITLE)
+        .ok_or_else(|| invalid(format!("This doesn't look like a {} export: no column holds the recipe's title.", app)))?;
+    let cell = |row: &[Cell], index: Option<usize>| index.and_then(|i| row.get(i)).map(Cell::text).unwrap_or_default();
+    let rows = rows.enumerate().filter(|(_, row)| row.iter().any(|c| !c.text().is_empty()));
+
+    let mut export = AppExport::default();
+    if let Some(date) = column(DATE) {
+        let meal_type = column(MEAL_TYPE).filter(|index| *index != title);
+        export.meals = rows
+            .map(|(n, row)| MappedRow {
+                row: n + 2,
+                day: row.get(date).cloned().unwrap_or(Cell::Empty),
+                meal_type: meal_type_or_dinner(&cell(&row, meal_type)),
+                description: cell(&row, Some(title)),
+                cook: String::new(),
+            })
+            .collect();
+    } else if column(INGREDIENTS).is_some() || column(STEPS).is_some() {
+        let (ingredients, steps, tags) = (column(INGREDIENTS), column(STEPS), column(TAGS));
+        let (link, servings, photo) = (column(LINK), column(SERVINGS), column(PHOTO));
+        export.recipes = rows
+            .map(|(_, row)| Recipe {
+                name: cell(&row, Some(title)),
+                tags: split_list(&cell(&row, tags)),
+                ingredients: lines(&cell(&row, ingredients)),
+                link: Some(cell(&row, link)).filter(|link| !link.is_empty()),
+                servings: first_number(&cell(&row, servings)),
+                photo: Some(cell(&row, photo)).filter(|photo| !photo.is_empty()),
+                steps: lines(&cell(&row, steps)),
+                equipment: Vec::new(),
+            })
+            .filter(|recipe| !recipe.name.is_empty())
+            .collect();
+    } else {
+        return Err(invalid(format!(
+            "This doesn't look like a {} export: it needs a date column for planned meals, or ingredients or directions for recipes.",
+            app
+        )));
+    }
+    Ok(export)
+}
+
+/// Reads a JSON export: an object with `recipes` and `mealPlanEvents` (or `meals`), or a
+/// list of either
+fn from_json(app: App, json: &Value) -> io::Result<AppExport> {
+    let (recipes, events) = match json {
+        Value::Object(object) => (
+            object.get("recipes").and_then(Value::as_array).cloned().unwrap_or_default(),
+            ["mealPlanEvents", "meal_plan_events", "events", "meals"]
+                .iter()
+                .find_map(|key| object.get(*key).and_then(Value::as_array))
+                .cloned()
+                .unwrap_or_default(),
+        ),
+        // A list of entries with dates is a meal plan, and otherwise recipes
+        Value::Array(entries) if entries.iter().any(|entry| entry.get("date").is_some()) => (Vec::new(), entries.clone()),
+        Value::Array(entries) => (entries.clone(), Vec::new()),
+        _ => return Err(invalid(format!("This doesn't look like a {} export.", app))),
+    };
+    if recipes.is_empty() && events.is_empty() {
+        return Err(invalid(format!("The {} export has no recipes or meal plan events.", app)));
+    }
+
+    let text = |value: &Value, keys: &[&str]| {
+        keys.iter().find_map(|key| value.get(*key).and_then(Value::as_str)).unwrap_or_default().trim().to_string()
+    };
+    let list = |value: &Value, key: &str| -> Vec<String> {
+        match value.get(key) {
+            Some(Value::Array(items)) => items
+                .iter()
+                .map(|item| match item {
+                    Value::String(text) => text.trim().to_string(),
+                    // AnyList ingredients have their quantity apart from the name
+                    item => [text(item, &["quantity"]), text(item, &["name", "text"]), text(item, &["note"])]
+                        .into_iter()
+                        .filter(|part| !part.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                })
+                .filter(|item| !item.is_empty())
+                .collect(),
+            Some(Value::String(text)) => lines(text),
+            _ => Vec::new(),
+        }
+    };
+
+    let recipes = recipes
+        .iter()
+        .map(|recipe| Recipe {
+            name: text(recipe, &["name", "title"]),
+            tags: list(recipe, "tags"),
+            ingredients: list(recipe, "ingredients"),
+            link: Some(text(recipe, &["sourceUrl", "source_url", "url"])).filter(|link| !link.is_empty()),
+            servings: first_number(&match recipe.get("servings") {
+                Some(Value::Number(number)) => number.to_string(),
+                _ => text(recipe, &["servings", "yield"]),
+            }),
+            photo: Some(text(recipe, &["photoUrl", "photo_url", "imageUrl"])).filter(|photo| !photo.is_empty()),
+            steps: ["preparationSteps", "steps", "directions"].iter().map(|key| list(recipe, key)).find(|steps| !steps.is_empty()).unwrap_or_default(),
+            equipment: Vec::new(),
+        })
+        .filter(|recipe| !recipe.name.is_empty())
+        .collect();
+    let meals = events
+        .iter()
+        .enumerate()
+        .map(|(n, event)| {
+            let date = text(event, &["date"]);
+            MappedRow {
+                row: n + 1,
+                day: if date.is_empty() { Cell::Empty } else { Cell::Text(date) },
+                meal_type: meal_type_or_dinner(&text(event, &["labelName", "label", "mealType", "course"])),
+                description: text(event, &["title", "recipeName", "name"]),
+                cook: String::new(),
+            }
+        })
+        .collect();
+    Ok(AppExport { recipes, meals })
+}
+
+/// Meals the app didn't give a meal type are taken as dinners
+fn meal_type_or_dinner(meal_type: &str) -> String {
+    if meal_type.trim().is_empty() {
+        "dinner".to_string()
+    } else {
+        meal_type.trim().to_string()
+    }
+}
+
+fn lines(text: &str) -> Vec<String> {
+    text.lines().map(str::trim).filter(|line| !line.is_empty()).map(str::to_string).collect()
+}
+
+fn split_list(text: &str) -> Vec<String> {
+    text.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect()
+}
+
+/// The first whole number in text such as "4 servings" or "Serves 6-8"
+fn first_number(text: &str) -> Option<u32> {
+    let digits: String = text.chars().skip_while(|c| !c.is_ascii_digit()).take_while(char::is_ascii_digit).collect();
+    digits.parse().ok().filter(|servings| *servings > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_plan_to_eat_csv() {
+        let dir = tempdir().unwrap();
+        let recipes = dir.path().join("recipes.csv");
+        std::fs::write(
+            &recipes,
+            "Title,Source,Url,Servings,Ingredients,Directions,Tags\n\
+             Chili,Example,https://example.com/chili,6 servings,\"1 lb beef\n\n2 cans beans\",\"Brown the beef.\nSimmer.\",\"beans, slow cooker\"\n\
+             ,,,,,,\n",
+        )
+        .unwrap();
+        let export = read_export(App::PlanToEat, &recipes).unwrap();
+        assert!(export.meals.is_empty());
+        assert_eq!(export.recipes.len(), 1);
+        let chili = &export.recipes[0];
+        assert_eq!(chili.ingredients, vec!["1 lb beef".to_string(), "2 cans beans".to_string()]);
+        assert_eq!(chili.steps.len(), 2);
+        assert_eq!(chili.tags, vec!["beans".to_string(), "slow cooker".to_string()]);
+        assert_eq!((chili.link.as_deref(), chili.servings), (Some("https://example.com/chili"), Some(6)));
+
+        let planner = dir.path().join("planner.csv");
+        std::fs::write(&planner, "Date,Course,Title\n2023-01-02,Breakfast,Oatmeal\n2023-01-03,,Chili\n").unwrap();
+        let export = read_export(App::PlanToEat, &planner).unwrap();
+        assert_eq!(export.meals.len(), 2);
+        assert_eq!((export.meals[0].row, export.meals[0].meal_type.as_str()), (2, "Breakfast"));
+        assert_eq!((export.meals[1].day.text().as_str(), export.meals[1].meal_type.as_str()), ("2023-01-03", "dinner"));
+
+        let other = dir.path().join("other.csv");
+        std::fs::write(&other, "Title,Notes\nChili,Spicy\n").unwrap();
+        assert!(read_export(App::PlanToEat, &other).unwrap_err().to_string().contains("Plan to Eat"));
+    }
+
+    #[test]
+    fn test_anylist_json() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("anylist.json");
+        let json = serde_json::json!({
+            "recipes": [{
+                "name": "Pancakes",
+                "servings": "Serves 4",
+                "sourceUrl": "https://example.com/pancakes",
+                "ingredients": [{"quantity": "2 cups", "name": "flour"}, {"name": "milk", "note": "warm"}],
+                "preparationSteps": ["Mix.", "Fry."],
+            }],
+            "mealPlanEvents": [
+                {"date": "2023-01-07", "labelName": "Breakfast", "recipeName": "Pancakes"},
+                {"date": "2023-01-07", "title": "Takeout"},
+            ],
+        });
+        std::fs::write(&path, json.to_string()).unwrap();
+
+        let export = read_export(App::Anylist, &path).unwrap();
+        let pancakes = &export.recipes[0];
+        assert_eq!(pancakes.ingredients, vec!["2 cups flour".to_string(), "milk warm".to_string()]);
+        assert_eq!((pancakes.servings, pancakes.steps.len()), (Some(4), 2));
+        assert_eq!(export.meals[0].description, "Pancakes");
+        assert_eq!((export.meals[1].row, export.meals[1].meal_type.as_str()), (2, "dinner"));
+
+        std::fs::write(&path, "{}").unwrap();
+        assert!(read_export(App::Anylist, &path).is_err());
+    }
+}
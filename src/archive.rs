@@ -0,0 +1,433 @@
+use crate::grocery::GroceryList;
+use crate::history::{self, DishSummary};
+use crate::models::MealPlan;
+use crate::search::SearchIndex;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::RangeBounds;
+use std::path::{Path, PathBuf};
+
+/// Directory under the storage path that holds archived weeks
+pub const ARCHIVE_DIR: &str = "archive";
+
+/// File in the archive directory that summarizes every archived week
+pub const ARCHIVE_INDEX: &str = "index.json";
+
+/// File in the archive directory that holds the full-text search index of archived meals
+pub const SEARCH_INDEX: &str = "search_index.json";
+
+/// Directory in the archive that holds each archived week's grocery list
+pub const GROCERY_DIR: &str = "groceries";
+
+/// Extension of archived weeks compressed with zstd
+pub const COMPRESSED_EXTENSION: &str = "json.zst";
+
+/// zstd level used when compacting; archived weeks are written once and rarely read
+const COMPRESSION_LEVEL: i32 = 19;
+
+/// What compacting the archive did
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CompactSummary {
+    pub weeks: usize,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+/// What's in an archived week, so queries can skip loading weeks they don't need
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeekSummary {
+    /// File name of the week within the archive directory
+    pub file: String,
+    pub meal_count: usize,
+    pub dishes: Vec<DishSummary>,
+}
+
+impl WeekSummary {
+    fn of(meal_plan: &MealPlan, file: String) -> Self {
+        Self {
+            file,
+            meal_count: meal_plan.meals().len(),
+            dishes: history::summarize_dishes(meal_plan),
+        }
+    }
+}
+
+/// Summaries of every archived week, keyed by week start date
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveIndex {
+    pub weeks: BTreeMap<NaiveDate, WeekSummary>,
+}
+
+/// Stores past weeks' meal plans, one JSON file per week named by its start date
+pub struct Archive {
+    dir: PathBuf,
+}
+
+impl Archive {
+    /// Opens the archive inside the given storage directory
+    pub fn new(storage_path: &Path) -> Self {
+        Self {
+            dir: storage_path.join(ARCHIVE_DIR),
+        }
+    }
+
+    /// Returns the uncompressed file an archived week is saved to
+    pub fn week_path(&self, week_start_date: NaiveDate) -> PathBuf {
+        self.dir.join(format!("{}.json", week_start_date.format("%Y-%m-%d")))
+    }
+
+    /// Returns the compressed file an archived week is compacted into
+    pub fn compressed_week_path(&self, week_start_date: NaiveDate) -> PathBuf {
+        self.dir.join(format!("{}.{}", week_start_date.format("%Y-%m-%d"), COMPRESSED_EXTENSION))
+    }
+
+    /// Returns whichever file the week is currently stored in, preferring the uncompressed one
+    fn stored_week_path(&self, week_start_date: NaiveDate) -> PathBuf {
+        let path = self.week_path(week_start_date);
+        if path.exists() {
+            path
+        } else {
+            self.compressed_week_path(week_start_date)
+        }
+    }
+
+    /// Saves a week's plan to the archive, replacing any earlier copy of that week
+    pub fn save_week(&self, meal_plan: &MealPlan) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.week_path(meal_plan.week_start_date);
+        meal_plan.save_to_json(&path)?;
+
+        let compressed = self.compressed_week_path(meal_plan.week_start_date);
+        if compressed.exists() {
+            std::fs::remove_file(compressed)?;
+        }
+
+        let mut index = self.index()?;
+        index.weeks.insert(meal_plan.week_start_date, WeekSummary::of(meal_plan, file_name(&path)));
+        self.save_index(&index)?;
+
+        let mut search_index = self.read_search_index()?;
+        search_index.add_week(meal_plan);
+        self.save_search_index(&search_index)?;
+        Ok(path)
+    }
+
+    /// Loads the index of archived weeks. Weeks archived before the index existed, or
+    /// copied in by hand, are summarized and added to it; removed weeks are dropped.
+    pub fn index(&self) -> std::io::Result<ArchiveIndex> {
+        let index_path = self.dir.join(ARCHIVE_INDEX);
+        let mut index: ArchiveIndex = match std::fs::read_to_string(&index_path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_default(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => ArchiveIndex::default(),
+            Err(e) => return Err(e),
+        };
+
+        let weeks = self.weeks()?;
+        let before = index.weeks.len();
+        index.weeks.retain(|week, summary| weeks.contains(week) && self.dir.join(&summary.file).exists());
+        let mut changed = index.weeks.len() != before;
+
+        for week in &weeks {
+            if !index.weeks.contains_key(week) {
+                let path = self.stored_week_path(*week);
+                let summary = WeekSummary::of(&read_week_file(&path)?, file_name(&path));
+                index.weeks.insert(*week, summary);
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.save_index(&index)?;
+        }
+        Ok(index)
+    }
+
+    fn save_index(&self, index: &ArchiveIndex) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.dir.join(ARCHIVE_INDEX), serde_json::to_string_pretty(index)?)
+    }
+
+    /// Loads the full-text search index. Like the archive index, weeks missing from it are
+    /// read and indexed, and weeks no longer in the archive are dropped.
+    pub fn search_index(&self) -> std::io::Result<SearchIndex> {
+        let mut search_index = self.read_search_index()?;
+        let weeks = self.weeks()?;
+
+        let removed: Vec<NaiveDate> = search_index.weeks.keys().filter(|week| !weeks.contains(week)).copied().collect();
+        let mut changed = !removed.is_empty();
+        for week in removed {
+            search_index.remove_week(week);
+        }
+        for week in &weeks {
+            if !search_index.weeks.contains_key(week) {
+                search_index.add_week(&self.load_week(*week)?);
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.save_search_index(&search_index)?;
+        }
+        Ok(search_index)
+    }
+
+    fn read_search_index(&self) -> std::io::Result<SearchIndex> {
+        match std::fs::read_to_string(self.dir.join(SEARCH_INDEX)) {
+            Ok(json) => Ok(serde_json::from_str(&json).unwrap_or_default()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SearchIndex::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save_search_index(&self, search_index: &SearchIndex) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(self.dir.join(SEARCH_INDEX), serde_json::to_string(search_index)?)
+    }
+
+    /// Loads only the archived weeks starting within the range, oldest first
+    pub fn load_weeks<R: RangeBounds<NaiveDate>>(&self, range: R) -> std::io::Result<Vec<MealPlan>> {
+        self.index()?
+            .weeks
+            .iter()
+            .filter(|(week, _)| range.contains(week))
+            .map(|(_, summary)| read_week_file(&self.dir.join(&summary.file)))
+            .collect()
+    }
+
+    /// Compresses the uncompressed weeks starting before `before` with zstd. Each week is
+    /// checked to read back the same before its uncompressed file is removed.
+    pub fn compact(&self, before: NaiveDate) -> std::io::Result<CompactSummary> {
+        let mut index = self.index()?;
+        let mut summary = CompactSummary::default();
+
+        for week in self.weeks()? {
+            let path = self.week_path(week);
+            if week >= before || !path.exists() {
+                continue;
+            }
+
+            let json = std::fs::read(&path)?;
+            let compressed = zstd::encode_all(json.as_slice(), COMPRESSION_LEVEL)?;
+            if zstd::decode_all(compressed.as_slice())? != json {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Compressed copy of {} did not match the original", file_name(&path)),
+                ));
+            }
+
+            let compressed_path = self.compressed_week_path(week);
+            std::fs::write(&compressed_path, &compressed)?;
+            std::fs::remove_file(&path)?;
+
+            if let Some(week_summary) = index.weeks.get_mut(&week) {
+                week_summary.file = file_name(&compressed_path);
+            }
+            summary.weeks += 1;
+            summary.bytes_before += json.len() as u64;
+            summary.bytes_after += compressed.len() as u64;
+        }
+
+        if summary.weeks > 0 {
+            self.save_index(&index)?;
+        }
+        Ok(summary)
+    }
+
+    /// Loads an archived week, whether or not it has been compressed
+    pub fn load_week(&self, week_start_date: NaiveDate) -> std::io::Result<MealPlan> {
+        read_week_file(&self.stored_week_path(week_start_date))
+    }
+
+    /// Saves a week's grocery list alongside the archived plan, so reports can include spending
+    pub fn save_groceries(&self, grocery_list: &GroceryList) -> std::io::Result<PathBuf> {
+        let dir = self.dir.join(GROCERY_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.json", grocery_list.week_start_date.format("%Y-%m-%d")));
+        grocery_list.save_to_json(&path)?;
+        Ok(path)
+    }
+
+    /// Loads an archived week's grocery list, or None if none was archived for that week
+    pub fn load_groceries(&self, week_start_date: NaiveDate) -> std::io::Result<Option<GroceryList>> {
+        let path = self.dir.join(GROCERY_DIR).join(format!("{}.json", week_start_date.format("%Y-%m-%d")));
+        match GroceryList::load_from_json(&path) {
+            Ok(list) => Ok(Some(list)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Lists the start dates of all archived weeks, oldest first
+    pub fn weeks(&self) -> std::io::Result<Vec<NaiveDate>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut weeks = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let name = file_name(&entry?.path());
+            let date = name
+                .strip_suffix(".json")
+                .or_else(|| name.strip_suffix(&format!(".{}", COMPRESSED_EXTENSION)))
+                .and_then(|stem| NaiveDate::parse_from_str(stem, "%Y-%m-%d").ok());
+            if let Some(date) = date {
+                weeks.push(date);
+            }
+        }
+        weeks.sort();
+        weeks.dedup();
+        Ok(weeks)
+    }
+}
+
+/// Reads an archived week, decompressing it if it was compacted
+fn read_week_file(path: &Path) -> std::io::Result<MealPlan> {
+    if file_name(path).ends_with(COMPRESSED_EXTENSION) {
+        let json = zstd::decode_all(std::fs::File::open(path)?)?;
+        Ok(serde_json::from_slice(&json)?)
+    } else {
+        MealPlan::load_from_json(path)
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, Meal, MealType};
+    use chrono::Weekday;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_archive_weeks() {
+        let temp_dir = tempdir().unwrap();
+        let archive = Archive::new(temp_dir.path());
+        assert!(archive.weeks().unwrap().is_empty());
+
+        let first = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let second = NaiveDate::from_ymd_opt(2023, 1, 9).unwrap();
+
+        let mut plan = MealPlan::new(second);
+        plan.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Mon),
+            "Alice".to_string(),
+            "Chili".to_string(),
+        ));
+        archive.save_week(&plan).unwrap();
+        archive.save_week(&MealPlan::new(first)).unwrap();
+
+        // Unrelated files in the archive directory are ignored
+        std::fs::write(temp_dir.path().join(ARCHIVE_DIR).join("notes.txt"), "hi").unwrap();
+
+        assert_eq!(archive.weeks().unwrap(), vec![first, second]);
+        let loaded = archive.load_week(second).unwrap();
+        assert_eq!(loaded.meals()[0].description, "Chili");
+
+        // Grocery lists are kept apart from the weeks
+        let mut groceries = GroceryList::new(second);
+        groceries.add_item("Beans", None);
+        archive.save_groceries(&groceries).unwrap();
+        assert_eq!(archive.weeks().unwrap(), vec![first, second]);
+        assert_eq!(archive.load_groceries(second).unwrap().unwrap().items.len(), 1);
+        assert!(archive.load_groceries(first).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_search_index_follows_archive() {
+        let temp_dir = tempdir().unwrap();
+        let archive = Archive::new(temp_dir.path());
+        let week = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut plan = MealPlan::new(week);
+        plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Chili".to_string()));
+        archive.save_week(&plan).unwrap();
+
+        let query = crate::search::parse_query("chili");
+        assert_eq!(archive.search_index().unwrap().search(&query).len(), 1);
+
+        // A lost index is rebuilt from the archived weeks, and removed weeks are dropped
+        std::fs::remove_file(temp_dir.path().join(ARCHIVE_DIR).join(SEARCH_INDEX)).unwrap();
+        assert_eq!(archive.search_index().unwrap().search(&query).len(), 1);
+        std::fs::remove_file(archive.week_path(week)).unwrap();
+        assert!(archive.search_index().unwrap().search(&query).is_empty());
+    }
+
+    #[test]
+    fn test_archive_index() {
+        let temp_dir = tempdir().unwrap();
+        let archive = Archive::new(temp_dir.path());
+
+        let weeks: Vec<NaiveDate> = (0..4)
+            .map(|n| NaiveDate::from_ymd_opt(2023, 1, 2).unwrap() + chrono::Duration::weeks(n))
+            .collect();
+        for week in &weeks {
+            let mut plan = MealPlan::new(*week);
+            plan.add_meal(Meal::new(
+                MealType::Dinner,
+                Day::Weekday(Weekday::Mon),
+                "Alice".to_string(),
+                format!("Dish {}", week),
+            ));
+            archive.save_week(&plan).unwrap();
+        }
+
+        let index = archive.index().unwrap();
+        assert_eq!(index.weeks.len(), 4);
+        assert_eq!(index.weeks[&weeks[0]].file, "2023-01-02.json");
+        assert_eq!(index.weeks[&weeks[0]].dishes[0].description, "Dish 2023-01-02");
+
+        // Only the requested weeks are loaded; a damaged week outside the range is never read
+        std::fs::write(archive.week_path(weeks[0]), "not json").unwrap();
+        let loaded = archive.load_weeks(weeks[2]..).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].week_start_date, weeks[2]);
+
+        // Weeks missing from the index are picked up, deleted ones dropped
+        std::fs::remove_file(temp_dir.path().join(ARCHIVE_DIR).join(ARCHIVE_INDEX)).unwrap();
+        std::fs::remove_file(archive.week_path(weeks[0])).unwrap();
+        let index = archive.index().unwrap();
+        assert_eq!(index.weeks.keys().copied().collect::<Vec<_>>(), weeks[1..].to_vec());
+        std::fs::remove_file(archive.week_path(weeks[1])).unwrap();
+        assert_eq!(archive.index().unwrap().weeks.len(), 2);
+    }
+
+    #[test]
+    fn test_compact() {
+        let temp_dir = tempdir().unwrap();
+        let archive = Archive::new(temp_dir.path());
+
+        let weeks: Vec<NaiveDate> = (0..3)
+            .map(|n| NaiveDate::from_ymd_opt(2023, 1, 2).unwrap() + chrono::Duration::weeks(n))
+            .collect();
+        for week in &weeks {
+            let mut plan = MealPlan::new(*week);
+            for day in [Weekday::Mon, Weekday::Tue, Weekday::Wed] {
+                plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(day), "Alice".to_string(), "Chili".to_string()));
+            }
+            archive.save_week(&plan).unwrap();
+        }
+
+        let summary = archive.compact(weeks[2]).unwrap();
+        assert_eq!(summary.weeks, 2);
+        assert!(summary.bytes_after < summary.bytes_before);
+        assert!(!archive.week_path(weeks[0]).exists());
+        assert!(archive.compressed_week_path(weeks[0]).exists());
+        assert!(archive.week_path(weeks[2]).exists());
+
+        // Compressed weeks read back transparently
+        assert_eq!(archive.weeks().unwrap(), weeks);
+        assert_eq!(archive.load_week(weeks[0]).unwrap().meals().len(), 3);
+        assert_eq!(archive.load_weeks(..).unwrap().len(), 3);
+        assert_eq!(archive.index().unwrap().weeks[&weeks[1]].file, "2023-01-09.json.zst");
+
+        // Nothing left to compact, and re-archiving a week replaces its compressed copy
+        assert_eq!(archive.compact(weeks[2]).unwrap(), CompactSummary::default());
+        archive.save_week(&MealPlan::new(weeks[0])).unwrap();
+        assert!(!archive.compressed_week_path(weeks[0]).exists());
+        assert!(archive.load_week(weeks[0]).unwrap().meals().is_empty());
+    }
+}
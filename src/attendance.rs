@@ -0,0 +1,258 @@
+use crate::lint::{self, Finding, Severity};
+use crate::models::{Meal, MealPlan, MealType};
+use crate::recipes::RecipeBook;
+use crate::spreadsheet::GRID_MEAL_TYPES;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// File in the storage path with the household's members and who's away
+pub const ATTENDANCE_FILE: &str = "attendance.json";
+
+/// Someone in the household who eats the meals, with what they can't eat
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Member {
+    pub name: String,
+    /// Ingredients, tags or words of dishes the member mustn't eat, e.g. peanut
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allergies: Vec<String>,
+}
+
+/// A member missing the meals of a date, or only one meal of it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Absence {
+    pub member: String,
+    pub date: NaiveDate,
+    /// The meal missed; every meal that day when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meal_type: Option<MealType>,
+}
+
+/// Who's in the household and who's eating which meals, kept in attendance.json in the
+/// storage path. Without members everyone is taken to eat every meal.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Attendance {
+    #[serde(default)]
+    pub members: Vec<Member>,
+    #[serde(default)]
+    pub away: Vec<Absence>,
+}
+
+impl Attendance {
+    /// Finds a member by name, ignoring case
+    pub fn member(&self, name: &str) -> Option<&Member> {
+        self.members.iter().find(|member| member.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Adds a member, or more allergies to one already in the household. Returns whether
+    /// the member is new.
+    pub fn set_member(&mut self, name: &str, allergies: &[String]) -> Result<bool, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("The member needs a name.".to_string());
+        }
+        let added = self.member(name).is_none();
+        if added {
+            self.members.push(Member { name: name.to_string(), allergies: Vec::new() });
+        }
+        let member = self.members.iter_mut().find(|member| member.name.eq_ignore_ascii_case(name)).expect("member was just found or added");
+        for allergy in allergies.iter().map(|allergy| allergy.trim().to_lowercase()).filter(|allergy| !allergy.is_empty()) {
+            if !member.allergies.contains(&allergy) {
+                member.allergies.push(allergy);
+            }
+        }
+        Ok(added)
+    }
+
+    /// Takes a member out of the household along with their absences, returning false if
+    /// there is nobody by that name
+    pub fn remove_member(&mut self, name: &str) -> bool {
+        let Some(name) = self.member(name).map(|member| member.name.clone()) else {
+            return false;
+        };
+        self.members.retain(|member| member.name != name);
+        self.away.retain(|absence| absence.member != name);
+        true
+    }
+
+    /// Whether a member misses a meal
+    pub fn is_away(&self, name: &str, date: NaiveDate, meal_type: &MealType) -> bool {
+        self.away.iter().any(|absence| {
+            absence.member.eq_ignore_ascii_case(name) && absence.date == date && absence.meal_type.as_ref().is_none_or(|t| t == meal_type)
+        })
+    }
+
+    /// Marks a member away for a date's meals, or only `meal_type`. Returns false if they
+    /// already were.
+    pub fn set_away(&mut self, name: &str, date: NaiveDate, meal_type: Option<MealType>) -> Result<bool, String> {
+        let name = self.member(name).map(|member| member.name.clone()).ok_or_else(|| unknown_member(name))?;
+        match &meal_type {
+            Some(meal_type) if self.is_away(&name, date, meal_type) => return Ok(false),
+            Some(_) => {}
+            None if GRID_MEAL_TYPES.iter().all(|t| self.is_away(&name, date, t)) => return Ok(false),
+            // A day away covers the meals already marked
+            None => self.away.retain(|absence| absence.member != name || absence.date != date),
+        }
+        self.away.push(Absence { member: name, date, meal_type });
+        self.away.sort_by_key(|absence| absence.date);
+        Ok(true)
+    }
+
+    /// Marks a member eating a date's meals again, or only `meal_type`. Returns false if
+    /// they weren't away.
+    pub fn set_back(&mut self, name: &str, date: NaiveDate, meal_type: Option<MealType>) -> Result<bool, String> {
+        let name = self.member(name).map(|member| member.name.clone()).ok_or_else(|| unknown_member(name))?;
+        let before = self.away.clone();
+        let on_date = |absence: &Absence| absence.member == name && absence.date == date;
+        match meal_type {
+            None => self.away.retain(|absence| !on_date(absence)),
+            Some(meal_type) => {
+                let whole_day = self.away.iter().any(|absence| on_date(absence) && absence.meal_type.is_none());
+                self.away.retain(|absence| !on_date(absence) || absence.meal_type.as_ref().is_some_and(|t| *t != meal_type));
+                // Back for one meal of a day away: still away for the others
+                if whole_day {
+                    for other in GRID_MEAL_TYPES.iter().filter(|t| **t != meal_type) {
+                        self.away.push(Absence { member: name.clone(), date, meal_type: Some(other.clone()) });
+                    }
+                    self.away.sort_by_key(|absence| absence.date);
+                }
+            }
+        }
+        Ok(self.away != before)
+    }
+
+    /// The members eating a meal
+    pub fn eating(&self, date: NaiveDate, meal_type: &MealType) -> Vec<&Member> {
+        self.members.iter().filter(|member| !self.is_away(&member.name, date, meal_type)).collect()
+    }
+
+    /// The share of the household eating a meal, from 0 to 1. Meals are for everyone when
+    /// no members are listed.
+    pub fn share(&self, meal_plan: &MealPlan, meal: &Meal) -> f64 {
+        if self.members.is_empty() {
+            return 1.0;
+        }
+        self.eating(meal_plan.date_of(&meal.day), &meal.meal_type).len() as f64 / self.members.len() as f64
+    }
+
+    /// Saves the attendance to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the attendance from a JSON file, which is empty until the first member is added
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+fn unknown_member(name: &str) -> String {
+    format!("\"{}\" isn't in the household. Add them with 'mealplan attendance member {}'.", name.trim(), name.trim())
+}
+
+/// Meals with something a member eating them is allergic to, found in their tags,
+/// descriptions or recipe ingredients
+pub fn allergy_findings(meal_plan: &MealPlan, book: &RecipeBook, attendance: &Attendance) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for meal in meal_plan.meals() {
+        let date = meal_plan.date_of(&meal.day);
+        for member in attendance.eating(date, &meal.meal_type) {
+            for allergy in member.allergies.iter().filter(|allergy| lint::mentions(meal, book, allergy)) {
+                findings.push(Finding {
+                    rule: "allergy".to_string(),
+                    severity: Severity::Error,
+                    date: Some(date),
+                    message: format!("{}: {} has {}, which {} is allergic to", meal.meal_type, meal.description, allergy, member.name),
+                });
+            }
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Day;
+    use chrono::Weekday;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_attendance() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(ATTENDANCE_FILE);
+        let mut attendance = Attendance::load_from_json(&path).unwrap();
+        let tuesday = NaiveDate::from_ymd_opt(2023, 1, 3).unwrap();
+        assert!(attendance.set_away("Sam", tuesday, None).is_err());
+
+        for name in ["Alex", "Jo", "Sam"] {
+            assert_eq!(attendance.set_member(name, &[]), Ok(true));
+        }
+        assert_eq!(attendance.set_member("sam", &["Peanut".to_string()]), Ok(false));
+        assert_eq!(attendance.member("SAM").unwrap().allergies, vec!["peanut"]);
+
+        // Away for the day, then back for dinner
+        assert_eq!(attendance.set_away("sam", tuesday, Some(MealType::Lunch)), Ok(true));
+        assert_eq!(attendance.set_away("sam", tuesday, None), Ok(true));
+        assert_eq!(attendance.set_away("sam", tuesday, Some(MealType::Lunch)), Ok(false));
+        assert_eq!(attendance.away.len(), 1);
+        assert_eq!(attendance.set_back("Sam", tuesday, Some(MealType::Dinner)), Ok(true));
+        assert!(attendance.is_away("Sam", tuesday, &MealType::Lunch));
+        assert!(!attendance.is_away("Sam", tuesday, &MealType::Dinner));
+        assert_eq!(attendance.eating(tuesday, &MealType::Lunch).len(), 2);
+
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Jo".to_string(), "Satay".to_string()));
+        let lunch = plan.meals()[0].clone();
+        assert!((attendance.share(&plan, &lunch) - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(Attendance::default().share(&plan, &lunch), 1.0);
+
+        attendance.save_to_json(&path).unwrap();
+        let mut attendance = Attendance::load_from_json(&path).unwrap();
+        assert_eq!(attendance.set_back("sam", tuesday, None), Ok(true));
+        assert!(attendance.away.is_empty());
+        assert!(attendance.remove_member("alex"));
+        assert!(!attendance.remove_member("alex"));
+    }
+
+    #[test]
+    fn test_allergy_findings() {
+        let mut attendance = Attendance::default();
+        attendance.set_member("Sam", &["peanut".to_string()]).unwrap();
+        attendance.set_member("Jo", &[]).unwrap();
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Jo".to_string(), "Peanut noodles".to_string()));
+        plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), "Jo".to_string(), "Satay".to_string()));
+        let mut book = RecipeBook::default();
+        book.add(crate::recipes::Recipe {
+            name: "Satay".to_string(),
+            tags: Vec::new(),
+            ingredients: vec!["3 tbsp peanut butter".to_string()],
+            link: None,
+            servings: None,
+            photo: None,
+            steps: Vec::new(),
+            equipment: Vec::new(),
+        });
+
+        let findings = allergy_findings(&plan, &book, &attendance);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].message, "Dinner: Peanut noodles has peanut, which Sam is allergic to");
+
+        // Nobody allergic is eating Tuesday's dinner
+        attendance.set_away("Sam", NaiveDate::from_ymd_opt(2023, 1, 3).unwrap(), Some(MealType::Dinner)).unwrap();
+        assert_eq!(allergy_findings(&plan, &book, &attendance).len(), 1);
+    }
+}
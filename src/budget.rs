@@ -0,0 +1,236 @@
+use crate::models::MealPlan;
+use crate::places::Places;
+use chrono::{Datelike, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// File in the storage path recording the budget alerts already sent
+pub const SENT_ALERTS_FILE: &str = "budget_alerts.json";
+
+/// Which configured budget an alert is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    /// `weekly_budget`, against the week's grocery list
+    WeeklyGroceries,
+    /// `monthly_takeout_budget`, against the meals from restaurants and takeout places
+    MonthlyTakeout,
+}
+
+/// Spending over a budget: what the plan is set to cost, or what was actually paid
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetAlert {
+    pub budget: Budget,
+    /// First day of the week or month the budget covers
+    pub period: NaiveDate,
+    /// Whether `spend` is an estimate of what's planned rather than what was paid
+    pub planned: bool,
+    pub spend: f64,
+    pub limit: f64,
+}
+
+impl BudgetAlert {
+    /// Identifies the alert so it is only sent once per period
+    pub fn key(&self) -> String {
+        let budget = match self.budget {
+            Budget::WeeklyGroceries => "groceries",
+            Budget::MonthlyTakeout => "takeout",
+        };
+        let kind = if self.planned { "planned" } else { "spent" };
+        format!("{}-{}-{}", budget, kind, self.period.format("%Y-%m-%d"))
+    }
+
+    pub fn over(&self) -> f64 {
+        self.spend - self.limit
+    }
+}
+
+impl fmt::Display for BudgetAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.budget, self.planned) {
+            (Budget::WeeklyGroceries, true) => write!(
+                f,
+                "The groceries for the week of {} are estimated at {:.2}",
+                self.period.format("%Y-%m-%d"),
+                self.spend
+            )?,
+            (Budget::WeeklyGroceries, false) => write!(
+                f,
+                "Spent {:.2} on groceries for the week of {}",
+                self.spend,
+                self.period.format("%Y-%m-%d")
+            )?,
+            (Budget::MonthlyTakeout, _) => {
+                write!(f, "Eating out in {} comes to about {:.2}", self.period.format("%B %Y"), self.spend)?
+            }
+        }
+        let period = match self.budget {
+            Budget::WeeklyGroceries => "weekly",
+            Budget::MonthlyTakeout => "monthly takeout",
+        };
+        write!(f, ", {:.2} over the {:.2} {} budget.", self.over(), self.limit, period)
+    }
+}
+
+/// Alerts for a week's groceries: one when the list's estimate goes over the budget, and
+/// one when what was paid does
+pub fn grocery_alerts(week_start: NaiveDate, estimated: f64, spent: f64, limit: Option<f64>) -> Vec<BudgetAlert> {
+    let Some(limit) = limit else {
+        return Vec::new();
+    };
+    [(true, estimated), (false, spent)]
+        .into_iter()
+        .filter(|(_, spend)| *spend > limit)
+        .map(|(planned, spend)| BudgetAlert { budget: Budget::WeeklyGroceries, period: week_start, planned, spend, limit })
+        .collect()
+}
+
+/// First day of the month a date falls in
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+/// What the meals from places in a month usually cost, across the plans given. A week
+/// found more than once, such as the current plan and its archived copy, is counted once.
+pub fn takeout_spending(plans: &[&MealPlan], places: &Places, month: NaiveDate) -> f64 {
+    let start = month_start(month);
+    let end = start.checked_add_months(Months::new(1)).unwrap_or(start);
+    let mut weeks = Vec::new();
+    let mut total = 0.0;
+    for plan in plans {
+        if weeks.contains(&plan.week_start_date) {
+            continue;
+        }
+        weeks.push(plan.week_start_date);
+        total += plan
+            .meals()
+            .iter()
+            .filter(|meal| (start..end).contains(&plan.date_of(&meal.day)))
+            .filter_map(|meal| meal.place.as_deref())
+            .filter_map(|name| places.find(name).and_then(|place| place.typical_cost))
+            .sum::<f64>();
+    }
+    total
+}
+
+/// The alert for a month's takeout, if it goes over the budget
+pub fn takeout_alert(month: NaiveDate, spend: f64, limit: Option<f64>) -> Option<BudgetAlert> {
+    let limit = limit?;
+    (spend > limit).then(|| BudgetAlert { budget: Budget::MonthlyTakeout, period: month_start(month), planned: true, spend, limit })
+}
+
+/// The budget alerts already sent through the notification backends
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SentAlerts {
+    pub sent: Vec<String>,
+}
+
+impl SentAlerts {
+    /// Picks out the alerts not sent before, recording them as sent
+    pub fn take_new<'a>(&mut self, alerts: &'a [BudgetAlert]) -> Vec<&'a BudgetAlert> {
+        alerts
+            .iter()
+            .filter(|alert| {
+                let key = alert.key();
+                if self.sent.contains(&key) {
+                    return false;
+                }
+                self.sent.push(key);
+                true
+            })
+            .collect()
+    }
+
+    /// Forgets an alert, so it is sent again next time
+    pub fn forget(&mut self, alert: &BudgetAlert) {
+        let key = alert.key();
+        self.sent.retain(|sent| *sent != key);
+    }
+
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the sent alerts from a JSON file, which is empty until the first is sent
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, Meal, MealType};
+    use crate::places::Place;
+    use chrono::Weekday;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_grocery_alerts() {
+        assert!(grocery_alerts(date(1, 2), 150.0, 150.0, None).is_empty());
+        assert!(grocery_alerts(date(1, 2), 100.0, 80.0, Some(100.0)).is_empty());
+
+        let alerts = grocery_alerts(date(1, 2), 120.0, 90.0, Some(100.0));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].key(), "groceries-planned-2023-01-02");
+        assert_eq!(
+            alerts[0].to_string(),
+            "The groceries for the week of 2023-01-02 are estimated at 120.00, 20.00 over the 100.00 weekly budget."
+        );
+        let alerts = grocery_alerts(date(1, 2), 120.0, 130.0, Some(100.0));
+        assert_eq!(alerts[1].to_string(), "Spent 130.00 on groceries for the week of 2023-01-02, 30.00 over the 100.00 weekly budget.");
+    }
+
+    #[test]
+    fn test_takeout_spending() {
+        let mut places = Places::default();
+        places.add(Place { name: "Pizza Place".to_string(), cuisine: None, contact: None, typical_cost: Some(30.0) });
+
+        // The week of Jan 30 runs into February
+        let mut meal_plan = MealPlan::new(date(1, 30));
+        for weekday in [Weekday::Mon, Weekday::Tue, Weekday::Fri] {
+            let mut meal = Meal::new(MealType::Dinner, Day::Weekday(weekday), "John".to_string(), "Pizza".to_string());
+            meal.place = Some("pizza place".to_string());
+            meal_plan.add_meal(meal);
+        }
+        assert_eq!(takeout_spending(&[&meal_plan, &meal_plan], &places, date(1, 15)), 60.0);
+        assert_eq!(takeout_spending(&[&meal_plan], &places, date(2, 1)), 30.0);
+
+        assert!(takeout_alert(date(1, 15), 60.0, Some(80.0)).is_none());
+        let alert = takeout_alert(date(1, 15), 60.0, Some(50.0)).unwrap();
+        assert_eq!(alert.period, date(1, 1));
+        assert_eq!(alert.to_string(), "Eating out in January 2023 comes to about 60.00, 10.00 over the 50.00 monthly takeout budget.");
+    }
+
+    #[test]
+    fn test_sent_alerts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SENT_ALERTS_FILE);
+        let alerts = grocery_alerts(date(1, 2), 120.0, 130.0, Some(100.0));
+
+        let mut sent = SentAlerts::load_from_json(&path).unwrap();
+        assert_eq!(sent.take_new(&alerts[..1]).len(), 1);
+        sent.save_to_json(&path).unwrap();
+
+        let mut sent = SentAlerts::load_from_json(&path).unwrap();
+        let new = sent.take_new(&alerts);
+        assert_eq!(new, vec![&alerts[1]]);
+        sent.forget(&alerts[1]);
+        assert_eq!(sent.take_new(&alerts).len(), 1);
+    }
+}
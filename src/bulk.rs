@@ -0,0 +1,94 @@
+use serde::Deserialize;
+
+/// One change to the plan, read from a line of newline-delimited JSON such as
+/// `{"op": "add", "meal_type": "dinner", "day": "monday", "cook": "Alice", "description": "Tacos"}`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase", deny_unknown_fields)]
+pub enum BulkOp {
+    /// Adds a meal. The description may be a meal alias, and the cook may be "auto".
+    Add {
+        meal_type: String,
+        day: String,
+        #[serde(default)]
+        cook: String,
+        description: String,
+        /// Replace a meal already planned in the slot instead of failing
+        #[serde(default)]
+        replace: bool,
+    },
+    /// Changes the cook, the description or both of a planned meal
+    Edit {
+        meal_type: String,
+        day: String,
+        #[serde(default)]
+        cook: Option<String>,
+        #[serde(default)]
+        description: Option<String>,
+    },
+    /// Moves a meal to the trash
+    Remove { meal_type: String, day: String },
+}
+
+/// Parses newline-delimited operations, skipping blank lines. Each operation comes with its
+/// line number. Fails on the first line that isn't a valid operation.
+pub fn parse_ops(input: &str) -> Result<Vec<(usize, BulkOp)>, String> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            serde_json::from_str(line)
+                .map(|op| (n + 1, op))
+                .map_err(|e| format!("Line {}: {}", n + 1, e))
+        })
+        .collect()
+}
+
+/// What applying a batch of operations did
+#[derive(Debug, Default, PartialEq)]
+pub struct BulkSummary {
+    pub added: usize,
+    pub edited: usize,
+    pub removed: usize,
+    /// Problems that didn't stop an operation, such as a cook being unavailable, by line
+    pub warnings: Vec<(usize, String)>,
+}
+
+impl BulkSummary {
+    pub fn total(&self) -> usize {
+        self.added + self.edited + self.removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ops() {
+        let input = r#"{"op": "add", "meal_type": "dinner", "day": "monday", "cook": "Al", "description": "Tacos"}
+
+{"op": "edit", "meal_type": "dinner", "day": "monday", "description": "Fish tacos"}
+{"op": "remove", "meal_type": "lunch", "day": "2023-01-03"}
+"#;
+        let ops = parse_ops(input).unwrap();
+        assert_eq!(ops.len(), 3);
+        assert_eq!(ops[1], (3, BulkOp::Edit {
+            meal_type: "dinner".to_string(),
+            day: "monday".to_string(),
+            cook: None,
+            description: Some("Fish tacos".to_string()),
+        }));
+        assert!(matches!(&ops[0].1, BulkOp::Add { replace: false, .. }));
+        assert_eq!(ops[2].0, 4);
+    }
+
+    #[test]
+    fn test_parse_ops_errors() {
+        let error = parse_ops("{\"op\": \"remove\", \"meal_type\": \"lunch\", \"day\": \"monday\"}\n{\"op\": \"cook\"}").unwrap_err();
+        assert!(error.starts_with("Line 2: unknown variant `cook`"), "{}", error);
+        assert!(parse_ops("{\"op\": \"remove\", \"meal_type\": \"lunch\"}").unwrap_err().contains("missing field `day`"));
+        assert!(parse_ops("{\"op\": \"remove\", \"meal_type\": \"lunch\", \"day\": \"mon\", \"when\": 1}").is_err());
+        assert!(parse_ops("not json").is_err());
+    }
+}
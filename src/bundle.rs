@@ -0,0 +1,263 @@
+use crate::duplicates::{self, Collision, Outcome, Strategy};
+use crate::models::{Meal, MealPlan};
+use crate::recipes::{Recipe, RecipeBook};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Version of the bundle layout, bumped when the format changes incompatibly
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+/// A self-contained, shareable copy of a week's meal plan and the recipes it uses
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanBundle {
+    pub format_version: u32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub exported_at: DateTime<Utc>,
+    pub meal_plan: MealPlan,
+    /// Recipes the bundled meals are made from. Older bundles have none, and older versions
+    /// of mealplan skip them, so adding them left the format version as it was.
+    #[serde(default)]
+    pub recipes: Vec<Recipe>,
+}
+
+/// Counts of what happened when a bundle was merged into a plan
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MergeSummary {
+    pub added: usize,
+    pub replaced: usize,
+    /// Existing meals the bundled dish was added to
+    pub combined: usize,
+    pub unchanged: usize,
+    pub skipped: usize,
+}
+
+impl PlanBundle {
+    /// Creates a bundle from a meal plan, taking along the recipes its meals and their dishes are made from
+    pub fn new(meal_plan: MealPlan, book: &RecipeBook) -> Self {
+        let mut recipes: Vec<Recipe> = Vec::new();
+        for meal in meal_plan.meals() {
+            let dishes = meal.description.split(" + ").filter_map(|dish| book.find(dish.trim()));
+            for recipe in book.for_meal(meal).into_iter().chain(dishes) {
+                if !recipes.iter().any(|r| r.name.eq_ignore_ascii_case(&recipe.name)) {
+                    recipes.push(recipe.clone());
+                }
+            }
+        }
+
+        Self {
+            format_version: BUNDLE_FORMAT_VERSION,
+            exported_at: Utc::now(),
+            meal_plan,
+            recipes,
+        }
+    }
+
+    /// Merges the bundled meals into a plan. `decide` is called for each meal that
+    /// collides with a different existing meal on the same date and says what to do with it.
+    pub fn merge_into<F>(&self, meal_plan: &mut MealPlan, mut decide: F) -> MergeSummary
+    where
+        F: FnMut(&Meal, &Meal, Collision) -> Strategy,
+    {
+        let mut summary = MergeSummary::default();
+
+        for meal in self.meal_plan.meals() {
+            match duplicates::resolve(meal_plan, meal.clone(), &mut decide) {
+                Outcome::Added => summary.added += 1,
+                Outcome::Unchanged => summary.unchanged += 1,
+                Outcome::Replaced => summary.replaced += 1,
+                Outcome::Combined => summary.combined += 1,
+                Outcome::Skipped(_) => summary.skipped += 1,
+            }
+        }
+
+        summary
+    }
+
+    /// Adds the bundled recipes missing from a recipe book, returning how many were added.
+    /// A recipe the book already has under the same name is left as it is.
+    pub fn merge_recipes_into(&self, book: &mut RecipeBook) -> usize {
+        let mut added = 0;
+        for recipe in &self.recipes {
+            if book.find(&recipe.name).is_none() {
+                book.add(recipe.clone());
+                added += 1;
+            }
+        }
+        added
+    }
+
+    /// Saves the bundle to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads a bundle from a file, rejecting bundles written by a newer format version
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let bundle: PlanBundle = serde_json::from_str(&contents)?;
+
+        if bundle.format_version > BUNDLE_FORMAT_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Bundle format version {} is newer than the supported version {}",
+                    bundle.format_version, BUNDLE_FORMAT_VERSION
+                ),
+            ));
+        }
+
+        Ok(bundle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, MealType};
+    use chrono::{NaiveDate, Weekday};
+    use tempfile::tempdir;
+
+    fn sample_plan() -> MealPlan {
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Mon),
+            "Alice".to_string(),
+            "Lasagna".to_string(),
+        ));
+        plan.add_meal(Meal::new(
+            MealType::Lunch,
+            Day::Weekday(Weekday::Tue),
+            "Bob".to_string(),
+            "Soup".to_string(),
+        ));
+        plan
+    }
+
+    fn recipe(name: &str) -> Recipe {
+        Recipe {
+            name: name.to_string(),
+            tags: Vec::new(),
+            ingredients: Vec::new(),
+            link: None,
+            servings: None,
+            photo: None,
+            steps: Vec::new(),
+            equipment: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_bundle_roundtrip() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("week.mpb");
+
+        PlanBundle::new(sample_plan(), &RecipeBook::default()).save(&file_path).unwrap();
+        let loaded = PlanBundle::load(&file_path).unwrap();
+
+        assert_eq!(loaded.format_version, BUNDLE_FORMAT_VERSION);
+        assert_eq!(loaded.meal_plan.meals().len(), 2);
+    }
+
+    #[test]
+    fn test_bundle_takes_referenced_recipes() {
+        let mut book = RecipeBook::default();
+        for name in ["Lasagna", "Garlic Bread", "Soup", "Curry"] {
+            book.add(recipe(name));
+        }
+        let mut plan = sample_plan();
+        plan.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Wed),
+            "Alice".to_string(),
+            "lasagna + Garlic Bread".to_string(),
+        ));
+
+        // Each recipe is taken once, and recipes no meal uses stay behind
+        let bundle = PlanBundle::new(plan, &book);
+        let names: Vec<&str> = bundle.recipes.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["Lasagna", "Soup", "Garlic Bread"]);
+    }
+
+    #[test]
+    fn test_bundle_without_recipes_loads() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("week.mpb");
+
+        let mut value = serde_json::to_value(PlanBundle::new(sample_plan(), &RecipeBook::default())).unwrap();
+        value.as_object_mut().unwrap().remove("recipes");
+        std::fs::write(&file_path, value.to_string()).unwrap();
+
+        let loaded = PlanBundle::load(&file_path).unwrap();
+        assert_eq!(loaded.format_version, 1);
+        assert!(loaded.recipes.is_empty());
+    }
+
+    #[test]
+    fn test_merge_recipes_into() {
+        let mut source = RecipeBook::default();
+        let mut lasagna = recipe("Lasagna");
+        lasagna.ingredients = vec!["noodles".to_string()];
+        source.add(lasagna);
+        source.add(recipe("Soup"));
+        let bundle = PlanBundle::new(sample_plan(), &source);
+
+        // The household's own lasagna is kept; only the soup is new
+        let mut book = RecipeBook::default();
+        book.add(recipe("Lasagna"));
+        assert_eq!(bundle.merge_recipes_into(&mut book), 1);
+        assert!(book.find("Lasagna").unwrap().ingredients.is_empty());
+        assert!(book.find("Soup").is_some());
+        assert_eq!(bundle.merge_recipes_into(&mut book), 0);
+    }
+
+    #[test]
+    fn test_bundle_rejects_newer_format() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("week.mpb");
+
+        let mut bundle = PlanBundle::new(sample_plan(), &RecipeBook::default());
+        bundle.format_version = BUNDLE_FORMAT_VERSION + 1;
+        bundle.save(&file_path).unwrap();
+
+        let err = PlanBundle::load(&file_path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_merge_into() {
+        let bundle = PlanBundle::new(sample_plan(), &RecipeBook::default());
+
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Mon),
+            "Carol".to_string(),
+            "Tacos".to_string(),
+        ));
+
+        // Skipping the collision keeps the existing meal
+        let summary = bundle.merge_into(&mut plan, |_, _, _| Strategy::Skip);
+        assert_eq!(summary, MergeSummary { added: 1, replaced: 0, combined: 0, unchanged: 0, skipped: 1 });
+        let dinner = plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap();
+        assert_eq!(dinner.description, "Tacos");
+
+        // Replacing it puts the bundled meal in its place; the lunch is already present
+        let summary = bundle.merge_into(&mut plan, |_, _, collision| {
+            assert_eq!(collision, Collision::Conflict);
+            Strategy::Replace
+        });
+        assert_eq!(summary, MergeSummary { added: 0, replaced: 1, combined: 0, unchanged: 1, skipped: 0 });
+        let dinner = plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap();
+        assert_eq!(dinner.description, "Lasagna");
+        assert_eq!(plan.meals().len(), 2);
+    }
+}
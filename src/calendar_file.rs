@@ -0,0 +1,140 @@
+use icalendar::{Calendar, CalendarComponent};
+
+/// Suffix of the UIDs of every event and to-do mealplan writes
+pub const MEALPLAN_UID_SUFFIX: &str = "@mealplan";
+
+/// A calendar file split into the text of its components, so the ones mealplan didn't
+/// write can be put back exactly as they were, alarms and time zones included
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarFile {
+    /// BEGIN:VCALENDAR and the calendar's properties, up to its first component
+    pub head: String,
+    /// Each top-level component's lines, with any nested in it. Properties of the calendar
+    /// that come after a component are kept here too, in their place.
+    pub components: Vec<String>,
+    /// END:VCALENDAR and anything after it
+    pub tail: String,
+}
+
+impl CalendarFile {
+    /// Splits a calendar's text at its components, keeping every line as it is
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut file = CalendarFile { head: String::new(), components: Vec::new(), tail: String::new() };
+        let mut started = false;
+        let mut depth = 0;
+        let mut current = String::new();
+        let mut lines = text.split_inclusive('\n');
+        for line in lines.by_ref() {
+            let content = line.trim_end_matches(['\r', '\n']).to_ascii_uppercase();
+            if !started {
+                file.head.push_str(line);
+                started = content == "BEGIN:VCALENDAR";
+                continue;
+            }
+            if depth == 0 && content == "END:VCALENDAR" {
+                file.tail.push_str(line);
+                break;
+            }
+            if content.starts_with("BEGIN:") {
+                depth += 1;
+            }
+            match depth {
+                0 if file.components.is_empty() => file.head.push_str(line),
+                0 => file.components.push(line.to_string()),
+                _ => current.push_str(line),
+            }
+            if depth > 0 && content.starts_with("END:") {
+                depth -= 1;
+                if depth == 0 {
+                    file.components.push(std::mem::take(&mut current));
+                }
+            }
+        }
+        if !started || file.tail.is_empty() {
+            return Err("not an iCalendar file: no BEGIN:VCALENDAR ... END:VCALENDAR".to_string());
+        }
+        file.tail.extend(lines);
+        Ok(file)
+    }
+
+    /// The file's text, with its components in order
+    pub fn to_text(&self) -> String {
+        let mut text = self.head.clone();
+        for component in &self.components {
+            text.push_str(component);
+        }
+        text.push_str(&self.tail);
+        text
+    }
+}
+
+/// The UID of a component's text, not looking into the components nested in it
+pub fn component_uid(text: &str) -> Option<String> {
+    let unfolded = text.replace("\r\n ", "").replace("\r\n\t", "").replace("\n ", "").replace("\n\t", "");
+    let mut depth = 0;
+    for line in unfolded.lines() {
+        let upper = line.to_ascii_uppercase();
+        if upper.starts_with("BEGIN:") {
+            depth += 1;
+        } else if upper.starts_with("END:") {
+            depth -= 1;
+        } else if depth == 1 && (upper.starts_with("UID:") || upper.starts_with("UID;")) {
+            return line.split_once(':').map(|(_, uid)| uid.trim().to_string());
+        }
+    }
+    None
+}
+
+/// Reads a component's text, or None if it can't be read
+pub fn parse_component(text: &str) -> Option<CalendarComponent> {
+    let calendar: Calendar = format!("BEGIN:VCALENDAR\r\n{}END:VCALENDAR\r\n", text).parse().ok()?;
+    calendar.components.into_iter().next()
+}
+
+/// Writes a component as the text it has in a calendar file
+pub fn component_text(component: CalendarComponent) -> String {
+    let calendar = Calendar { properties: Vec::new(), components: vec![component] }.to_string();
+    calendar
+        .strip_prefix("BEGIN:VCALENDAR\r\n")
+        .and_then(|text| text.strip_suffix("END:VCALENDAR\r\n"))
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use icalendar::{Component, Event};
+
+    const FILE: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Family//EN\r\n\
+        BEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\nBEGIN:STANDARD\r\nDTSTART:19701025T030000\r\nTZOFFSETFROM:+0200\r\nTZOFFSETTO:+0100\r\nEND:STANDARD\r\nEND:VTIMEZONE\r\n\
+        BEGIN:VEVENT\r\nUID:dentist@example.com\r\nSUMMARY:Dentist\r\nBEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-PT15M\r\nUID:alarm@example.com\r\nEND:VALARM\r\nEND:VEVENT\r\n\
+        BEGIN:VEVENT\r\nUID:meal-dinner-20230102\r\n @mealplan\r\nSUMMARY:Dinner: Pasta\r\nEND:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    #[test]
+    fn test_split_and_join() {
+        let file = CalendarFile::parse(FILE).unwrap();
+        assert_eq!(file.head, "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Family//EN\r\n");
+        assert_eq!(file.components.len(), 3);
+        assert!(file.components[0].starts_with("BEGIN:VTIMEZONE") && file.components[0].ends_with("END:VTIMEZONE\r\n"));
+        assert_eq!(file.tail, "END:VCALENDAR\r\n");
+        assert_eq!(file.to_text(), FILE);
+
+        assert_eq!(component_uid(&file.components[0]), None);
+        assert_eq!(component_uid(&file.components[1]).as_deref(), Some("dentist@example.com"));
+        assert_eq!(component_uid(&file.components[2]).as_deref(), Some("meal-dinner-20230102@mealplan"));
+
+        assert!(CalendarFile::parse("BEGIN:VEVENT\r\nEND:VEVENT\r\n").is_err());
+        assert!(CalendarFile::parse("BEGIN:VCALENDAR\nBEGIN:VEVENT\n").is_err());
+    }
+
+    #[test]
+    fn test_component_roundtrip() {
+        let event = Event::new().uid("meal-lunch-20230103@mealplan").summary("Lunch: Soup").done();
+        let text = component_text(event.into());
+        assert!(text.starts_with("BEGIN:VEVENT\r\n") && text.ends_with("END:VEVENT\r\n"));
+        let parsed = parse_component(&text).unwrap();
+        assert_eq!(parsed.as_event().and_then(|e| e.get_summary()), Some("Lunch: Soup"));
+    }
+}
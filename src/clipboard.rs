@@ -0,0 +1,188 @@
+use crate::models::{Meal, MealPlan};
+use crate::report::escape_html;
+use crate::spreadsheet::GRID_MEAL_TYPES;
+use std::collections::BTreeMap;
+
+/// How a week is written out for pasting into a chat or an email
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFormat {
+    Markdown,
+    Plain,
+}
+
+impl TextFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(TextFormat::Markdown),
+            "plain" | "text" | "txt" => Ok(TextFormat::Plain),
+            _ => Err("Invalid format. Must be markdown or plain.".to_string()),
+        }
+    }
+}
+
+/// Renders the week's meals by day, leaving out the bookkeeping the Markdown sync file keeps
+pub fn render_week(meal_plan: &MealPlan, format: TextFormat) -> String {
+    let mut days: BTreeMap<chrono::NaiveDate, Vec<&Meal>> = BTreeMap::new();
+    for meal in meal_plan.meals() {
+        days.entry(meal_plan.date_of(&meal.day)).or_default().push(meal);
+    }
+
+    let title = format!("Meal plan for the week of {}", meal_plan.week_start_date.format("%B %-d, %Y"));
+    let mut text = match format {
+        TextFormat::Markdown => format!("**{}**\n", title),
+        TextFormat::Plain => format!("{}\n", title),
+    };
+    if days.is_empty() {
+        text.push_str("\nNothing planned yet.\n");
+        return text;
+    }
+
+    for (date, mut meals) in days {
+        meals.sort_by_key(|meal| GRID_MEAL_TYPES.iter().position(|t| *t == meal.meal_type));
+        let heading = date.format("%A, %b %-d");
+        match format {
+            TextFormat::Markdown => text.push_str(&format!("\n**{}**\n", heading)),
+            TextFormat::Plain => text.push_str(&format!("\n{}\n", heading)),
+        }
+        for meal in meals {
+            let bullet = match format {
+                TextFormat::Markdown => "-",
+                TextFormat::Plain => " ",
+            };
+            text.push_str(&format!("{} {}: {}", bullet, meal.meal_type, meal_plan.render_description(meal)));
+            if !meal.cook.trim().is_empty() {
+                text.push_str(&format!(" ({})", meal.cook));
+            }
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// Renders the week's meals by day as a web page, showing each meal's photo. Photos in the
+/// assets directory are linked relative to the page.
+pub fn render_week_html(meal_plan: &MealPlan) -> String {
+    let title = escape_html(&format!("Meal plan for the week of {}", meal_plan.week_start_date.format("%B %-d, %Y")));
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n"
+    );
+    let mut days: BTreeMap<chrono::NaiveDate, Vec<&Meal>> = BTreeMap::new();
+    for meal in meal_plan.meals() {
+        days.entry(meal_plan.date_of(&meal.day)).or_default().push(meal);
+    }
+    if days.is_empty() {
+        html.push_str("<p>Nothing planned yet.</p>\n");
+    }
+
+    for (date, mut meals) in days {
+        meals.sort_by_key(|meal| GRID_MEAL_TYPES.iter().position(|t| *t == meal.meal_type));
+        html.push_str(&format!("<h2>{}</h2>\n<ul>\n", date.format("%A, %b %-d")));
+        for meal in meals {
+            let description = escape_html(&meal_plan.render_description(meal));
+            html.push_str(&format!("<li>{}: {}", meal.meal_type, description));
+            if !meal.cook.trim().is_empty() {
+                html.push_str(&format!(" ({})", escape_html(&meal.cook)));
+            }
+            if let Some(photo) = &meal.photo {
+                html.push_str(&format!("<br><img src=\"{}\" alt=\"{}\" width=\"320\">", escape_html(photo), description));
+            }
+            html.push_str("</li>\n");
+        }
+        html.push_str("</ul>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Places text on the system clipboard
+#[cfg(not(target_os = "linux"))]
+pub fn copy_text(text: &str) -> Result<(), String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    clipboard.set_text(text).map_err(|e| e.to_string())
+}
+
+/// Places text on the system clipboard. On Linux the clipboard only holds what a program
+/// is still around to serve, so a copy of this program is left running in the background
+/// to hold it until something else is copied.
+#[cfg(target_os = "linux")]
+pub fn copy_text(text: &str) -> Result<(), String> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    // Fail here rather than in the background if there is no clipboard to use
+    arboard::Clipboard::new().map_err(|e| e.to_string())?;
+
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let mut child = Command::new(exe)
+        .args(["copy", "--hold"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let mut stdin = child.stdin.take().ok_or("Failed to pass the text to the clipboard")?;
+    stdin.write_all(text.as_bytes()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Places text on the clipboard and waits until something else replaces it
+#[cfg(target_os = "linux")]
+pub fn hold_text(text: &str) -> Result<(), String> {
+    use arboard::SetExtLinux;
+
+    let mut clipboard = arboard::Clipboard::new().map_err(|e| e.to_string())?;
+    clipboard.set().wait().text(text.to_string()).map_err(|e| e.to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn hold_text(text: &str) -> Result<(), String> {
+    copy_text(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, MealType};
+    use chrono::{NaiveDate, Weekday};
+
+    fn plan() -> MealPlan {
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), "Bob".to_string(), "Chili".to_string()));
+        plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Tacos".to_string()));
+        plan.add_meal(Meal::new(MealType::Breakfast, Day::Weekday(Weekday::Mon), String::new(), "Oatmeal".to_string()));
+        plan
+    }
+
+    #[test]
+    fn test_render_week() {
+        assert_eq!(
+            render_week(&plan(), TextFormat::Markdown),
+            "**Meal plan for the week of January 2, 2023**\n\n\
+             **Monday, Jan 2**\n- Breakfast: Oatmeal\n- Dinner: Tacos (Alice)\n\n\
+             **Tuesday, Jan 3**\n- Dinner: Chili (Bob)\n"
+        );
+        assert!(render_week(&plan(), TextFormat::Plain).contains("\nMonday, Jan 2\n  Breakfast: Oatmeal\n"));
+
+        let empty = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        assert!(render_week(&empty, TextFormat::Plain).ends_with("Nothing planned yet.\n"));
+    }
+
+    #[test]
+    fn test_render_week_html() {
+        let mut plan = plan();
+        plan.change_meal(&MealType::Dinner, &Day::Weekday(Weekday::Tue), |meal| meal.photo = Some("assets/chili.jpg".to_string()));
+        plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Mon), "Bob".to_string(), "Mac & cheese".to_string()));
+
+        let html = render_week_html(&plan);
+        assert!(html.contains("<h1>Meal plan for the week of January 2, 2023</h1>\n<h2>Monday, Jan 2</h2>\n"));
+        assert!(html.contains("<li>Lunch: Mac &amp; cheese (Bob)</li>\n"));
+        assert!(html.contains("<li>Dinner: Chili (Bob)<br><img src=\"assets/chili.jpg\" alt=\"Chili\" width=\"320\"></li>\n"));
+    }
+
+    #[test]
+    fn test_text_format_parse() {
+        assert_eq!(TextFormat::parse("MD").unwrap(), TextFormat::Markdown);
+        assert_eq!(TextFormat::parse("plain").unwrap(), TextFormat::Plain);
+        assert!(TextFormat::parse("html").is_err());
+    }
+}
@@ -0,0 +1,267 @@
+use crate::grocery::GroceryList;
+use crate::history::{dish_key, LEFTOVERS_PREFIX};
+use crate::models::{MealPlan, MealType};
+use chrono::{Datelike, NaiveDate, Weekday};
+use std::collections::BTreeMap;
+
+/// A dish served in both weeks, with how often in each
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepeatedDish {
+    pub description: String,
+    pub times: usize,
+    pub previous_times: usize,
+}
+
+/// A slot (a meal type on a weekday) cooked by someone else than in the other week
+#[derive(Debug, Clone, PartialEq)]
+pub struct CookChange {
+    pub weekday: Weekday,
+    pub meal_type: MealType,
+    pub previous: String,
+    pub current: String,
+}
+
+/// How many meals a cook has in each week
+#[derive(Debug, Clone, PartialEq)]
+pub struct CookCount {
+    pub cook: String,
+    pub meals: usize,
+    pub previous_meals: usize,
+}
+
+/// The differences between one week and another: dishes served in both, who cooks what
+/// now, and what the groceries cost
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeekComparison {
+    pub week: NaiveDate,
+    pub with: NaiveDate,
+    pub repeated: Vec<RepeatedDish>,
+    pub cook_changes: Vec<CookChange>,
+    pub cooks: Vec<CookCount>,
+    /// Grocery spending of each week, when a list with purchases or receipts was kept
+    pub spent: Option<f64>,
+    pub previous_spent: Option<f64>,
+}
+
+impl WeekComparison {
+    pub fn new(week: &MealPlan, groceries: Option<&GroceryList>, with: &MealPlan, previous_groceries: Option<&GroceryList>) -> Self {
+        let dishes = count_dishes(week);
+        let previous_dishes = count_dishes(with);
+        let mut repeated: Vec<RepeatedDish> = dishes
+            .iter()
+            .filter_map(|(key, (description, times))| {
+                let (_, previous_times) = previous_dishes.get(key)?;
+                Some(RepeatedDish { description: description.clone(), times: *times, previous_times: *previous_times })
+            })
+            .collect();
+        repeated.sort_by(|a, b| {
+            (b.times + b.previous_times).cmp(&(a.times + a.previous_times)).then_with(|| a.description.cmp(&b.description))
+        });
+
+        let previous_slots = slots(with);
+        let mut cook_changes: Vec<CookChange> = slots(week)
+            .into_iter()
+            .filter_map(|(key, (meal_type, current))| {
+                let (_, previous) = previous_slots.get(&key)?;
+                (!previous.eq_ignore_ascii_case(&current)).then(|| CookChange {
+                    weekday: Weekday::try_from(key.0).unwrap_or(Weekday::Mon),
+                    meal_type,
+                    previous: previous.clone(),
+                    current,
+                })
+            })
+            .collect();
+        cook_changes.sort_by_key(|change| {
+            let offset = (change.weekday.num_days_from_monday() + 7 - week.week_start_date.weekday().num_days_from_monday()) % 7;
+            (offset, meal_type_order(&change.meal_type))
+        });
+
+        let mut cooks: BTreeMap<String, CookCount> = BTreeMap::new();
+        for (plan, current) in [(week, true), (with, false)] {
+            for meal in plan.meals().iter().filter(|meal| !meal.cook.trim().is_empty()) {
+                let cook = meal.cook.trim();
+                let count = cooks.entry(cook.to_lowercase()).or_insert_with(|| CookCount {
+                    cook: cook.to_string(),
+                    meals: 0,
+                    previous_meals: 0,
+                });
+                if current {
+                    count.meals += 1;
+                } else {
+                    count.previous_meals += 1;
+                }
+            }
+        }
+
+        Self {
+            week: week.week_start_date,
+            with: with.week_start_date,
+            repeated,
+            cook_changes,
+            cooks: cooks.into_values().collect(),
+            spent: groceries.and_then(grocery_spending),
+            previous_spent: previous_groceries.and_then(grocery_spending),
+        }
+    }
+
+    /// Renders the comparison for the terminal
+    pub fn to_text(&self) -> String {
+        let mut out = format!(
+            "Week of {} compared with the week of {}\n",
+            self.week.format("%Y-%m-%d"),
+            self.with.format("%Y-%m-%d")
+        );
+
+        if self.repeated.is_empty() {
+            out.push_str("\nNo dishes are repeated.\n");
+        } else {
+            out.push_str("\nRepeated dishes:\n");
+            for dish in &self.repeated {
+                out.push_str(&format!("- {}", dish.description));
+                if dish.times > 1 || dish.previous_times > 1 {
+                    out.push_str(&format!(" ({}x, was {}x)", dish.times, dish.previous_times));
+                }
+                out.push('\n');
+            }
+        }
+
+        if !self.cook_changes.is_empty() {
+            out.push_str("\nCook changes:\n");
+            for change in &self.cook_changes {
+                out.push_str(&format!(
+                    "- {} {}: {} -> {}\n",
+                    change.weekday,
+                    change.meal_type.to_string().to_lowercase(),
+                    change.previous,
+                    change.current
+                ));
+            }
+        }
+
+        if !self.cooks.is_empty() {
+            out.push_str("\nMeals per cook:\n");
+            for count in &self.cooks {
+                out.push_str(&format!("- {}: {}", count.cook, count.meals));
+                if count.meals != count.previous_meals {
+                    out.push_str(&format!(" ({:+})", count.meals as i64 - count.previous_meals as i64));
+                }
+                out.push('\n');
+            }
+        }
+
+        out.push('\n');
+        match (self.spent, self.previous_spent) {
+            (Some(spent), Some(previous)) => {
+                out.push_str(&format!("Groceries: spent {:.2}, against {:.2}", spent, previous));
+                if previous > 0.0 {
+                    out.push_str(&format!(" ({:+.2}, {:+.0}%)", spent - previous, (spent - previous) / previous * 100.0));
+                } else {
+                    out.push_str(&format!(" ({:+.2})", spent - previous));
+                }
+                out.push_str(".\n");
+            }
+            (Some(spent), None) => {
+                out.push_str(&format!("Groceries: spent {:.2}; no spending was recorded for the other week.\n", spent))
+            }
+            (None, Some(previous)) => out.push_str(&format!(
+                "Groceries: no spending recorded yet, against {:.2} the other week.\n",
+                previous
+            )),
+            (None, None) => out.push_str("Groceries: no spending was recorded for either week.\n"),
+        }
+        out
+    }
+}
+
+/// What a week's groceries cost: the receipts when there are any, otherwise the prices
+/// recorded for purchased items
+fn grocery_spending(list: &GroceryList) -> Option<f64> {
+    if let Some(total) = list.receipt_total() {
+        return Some(total);
+    }
+    let (spent, unpriced) = list.spending();
+    (spent > 0.0 || unpriced > 0).then_some(spent)
+}
+
+/// Counts each dish of a week by its normalized description, skipping leftovers
+fn count_dishes(meal_plan: &MealPlan) -> BTreeMap<String, (String, usize)> {
+    let mut dishes: BTreeMap<String, (String, usize)> = BTreeMap::new();
+    for meal in meal_plan.meals() {
+        let description = meal_plan.render_description(meal);
+        let description = description.trim();
+        if description.is_empty() || description.starts_with(LEFTOVERS_PREFIX) {
+            continue;
+        }
+        dishes.entry(dish_key(description)).or_insert_with(|| (description.to_string(), 0)).1 += 1;
+    }
+    dishes
+}
+
+/// Maps each slot, keyed by weekday number from Monday and meal type order, to its meal
+/// type and cook
+fn slots(meal_plan: &MealPlan) -> BTreeMap<(u8, u8), (MealType, String)> {
+    meal_plan
+        .meals()
+        .iter()
+        .filter(|meal| !meal.cook.trim().is_empty())
+        .map(|meal| {
+            let weekday = meal_plan.date_of(&meal.day).weekday().num_days_from_monday() as u8;
+            ((weekday, meal_type_order(&meal.meal_type)), (meal.meal_type.clone(), meal.cook.trim().to_string()))
+        })
+        .collect()
+}
+
+fn meal_type_order(meal_type: &MealType) -> u8 {
+    match meal_type {
+        MealType::Breakfast => 0,
+        MealType::Lunch => 1,
+        MealType::Snack => 2,
+        MealType::Dinner => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grocery::GroceryItem;
+    use crate::models::{Day, Meal};
+
+    fn plan(week_start: NaiveDate, meals: &[(Weekday, &str, &str)]) -> MealPlan {
+        let mut meal_plan = MealPlan::new(week_start);
+        for (weekday, cook, description) in meals {
+            meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(*weekday), cook.to_string(), description.to_string()));
+        }
+        meal_plan
+    }
+
+    #[test]
+    fn test_compare_weeks() {
+        let last_week = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let this_week = NaiveDate::from_ymd_opt(2023, 1, 9).unwrap();
+        let previous = plan(last_week, &[(Weekday::Mon, "John", "Tacos"), (Weekday::Tue, "Jane", "Chili"), (Weekday::Wed, "John", "Soup")]);
+        let current = plan(this_week, &[(Weekday::Mon, "John", "tacos "), (Weekday::Tue, "John", "Chili"), (Weekday::Wed, "John", "Leftovers: Chili")]);
+
+        let mut groceries = GroceryList::new(this_week);
+        groceries.items.push(GroceryItem { purchased: true, price: Some(55.0), ..GroceryItem::new("Beans".to_string(), None) });
+        let mut previous_groceries = GroceryList::new(last_week);
+        previous_groceries.items.push(GroceryItem { purchased: true, price: Some(50.0), ..GroceryItem::new("Beans".to_string(), None) });
+
+        let comparison = WeekComparison::new(&current, Some(&groceries), &previous, Some(&previous_groceries));
+        let repeated: Vec<&str> = comparison.repeated.iter().map(|dish| dish.description.as_str()).collect();
+        assert_eq!(repeated, vec!["Chili", "tacos"]);
+        assert_eq!(
+            comparison.cook_changes,
+            vec![CookChange { weekday: Weekday::Tue, meal_type: MealType::Dinner, previous: "Jane".to_string(), current: "John".to_string() }]
+        );
+        assert_eq!(comparison.cooks[0], CookCount { cook: "Jane".to_string(), meals: 0, previous_meals: 1 });
+
+        let text = comparison.to_text();
+        assert!(text.starts_with("Week of 2023-01-09 compared with the week of 2023-01-02\n"));
+        assert!(text.contains("- Tue dinner: Jane -> John\n"));
+        assert!(text.contains("- John: 3 (+1)\n"));
+        assert!(text.contains("Groceries: spent 55.00, against 50.00 (+5.00, +10%).\n"));
+
+        let text = WeekComparison::new(&current, None, &previous, Some(&GroceryList::new(last_week))).to_text();
+        assert!(text.contains("Groceries: no spending was recorded for either week.\n"));
+    }
+}
@@ -0,0 +1,214 @@
+use crate::recipes::Recipe;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+/// Reads how long a step takes from its text, e.g. "Simmer for 20 minutes" or "Bake 1 hr
+/// 15 min". Amounts next to each other are added up. The first duration in the step is
+/// used; a range such as "10-12 minutes" counts as its upper end.
+pub fn step_duration(step: &str) -> Option<Duration> {
+    let words = tokens(step);
+    let mut total = 0.0;
+    let mut found = false;
+    let mut i = 0;
+    while i < words.len() {
+        let seconds = match (words[i].parse::<f64>(), words.get(i + 1).and_then(|unit| unit_seconds(unit))) {
+            (Ok(amount), Some(unit)) => Some(amount * unit),
+            _ => None,
+        };
+        match seconds {
+            Some(seconds) => {
+                total += seconds;
+                found = true;
+                i += 2;
+                if words.get(i).is_some_and(|word| word == "and") {
+                    i += 1;
+                }
+            }
+            None if found => break,
+            None => i += 1,
+        }
+    }
+    (found && total > 0.0).then(|| Duration::from_secs_f64(total))
+}
+
+/// Shows the time left as m:ss, or h:mm:ss from an hour up
+pub fn format_countdown(remaining: Duration) -> String {
+    let secs = remaining.as_secs();
+    match secs / 3600 {
+        0 => format!("{}:{:02}", secs / 60, secs % 60),
+        hours => format!("{}:{:02}:{:02}", hours, secs / 60 % 60, secs % 60),
+    }
+}
+
+/// Counts down in place on the terminal, then rings the bell
+pub fn run_timer<W: Write>(duration: Duration, out: &mut W) -> std::io::Result<()> {
+    let end = Instant::now() + duration;
+    loop {
+        let remaining = end.saturating_duration_since(Instant::now());
+        write!(out, "\r  {} left ", format_countdown(remaining + Duration::from_millis(999)))?;
+        out.flush()?;
+        if remaining.is_zero() {
+            break;
+        }
+        std::thread::sleep(remaining.min(Duration::from_millis(250)));
+    }
+    writeln!(out, "\r\x07  Time's up!      ")?;
+    Ok(())
+}
+
+/// Walks through a recipe's steps one at a time, reading what to do next from `input`.
+/// Steps with a duration offer to start a timer, run by `timer`. Returns whether every
+/// step was reached.
+pub fn walk_steps<R, W, T>(recipe: &Recipe, input: &mut R, out: &mut W, mut timer: T) -> std::io::Result<bool>
+where
+    R: BufRead,
+    W: Write,
+    T: FnMut(Duration, &mut W) -> std::io::Result<()>,
+{
+    let count = recipe.steps.len();
+    writeln!(out, "Cooking {}, {} step{}.", recipe.name, count, if count == 1 { "" } else { "s" })?;
+    if !recipe.ingredients.is_empty() {
+        writeln!(out, "You'll need: {}", recipe.ingredients.join(", "))?;
+    }
+
+    let mut index = 0;
+    while index < count {
+        let step = &recipe.steps[index];
+        let duration = step_duration(step);
+        writeln!(out, "\nStep {}/{}: {}", index + 1, count, step)?;
+        let next = if index + 1 == count { "finish" } else { "next step" };
+        match duration {
+            Some(duration) => write!(out, "[Enter] start the {} timer, [s] skip it, [b] back, [q] quit > ", format_countdown(duration))?,
+            None => write!(out, "[Enter] {}, [b] back, [q] quit > ", next)?,
+        }
+        out.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(out)?;
+            return Ok(false);
+        }
+        match line.trim().to_lowercase().as_str() {
+            "q" | "quit" => return Ok(false),
+            "b" | "back" => index = index.saturating_sub(1),
+            "s" | "skip" => index += 1,
+            _ => {
+                if let Some(duration) = duration {
+                    timer(duration, out)?;
+                }
+                index += 1;
+            }
+        }
+    }
+    writeln!(out, "\nAll done. Enjoy!")?;
+    Ok(true)
+}
+
+/// Splits text into lowercase words, with numbers and letters apart so "20min" and
+/// "1h30m" split up, and ranges such as "10-12" reduced to their upper end
+fn tokens(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut range = false;
+    let flush = |current: &mut String, words: &mut Vec<String>, range: &mut bool| {
+        if !current.is_empty() {
+            if *range && current.parse::<f64>().is_ok() && words.last().is_some_and(|w| w.parse::<f64>().is_ok()) {
+                words.pop();
+            }
+            words.push(std::mem::take(current));
+        }
+        *range = false;
+    };
+    for c in text.to_lowercase().chars() {
+        let numeric = c.is_ascii_digit() || (c == '.' && current.chars().next().is_some_and(|f| f.is_ascii_digit()));
+        let same_kind = current.chars().next().is_none_or(|first| first.is_ascii_digit() == numeric);
+        if c.is_alphanumeric() || numeric {
+            if !same_kind {
+                flush(&mut current, &mut words, &mut range);
+            }
+            current.push(c);
+        } else {
+            let is_range = (c == '-' || c == '–') && current.parse::<f64>().is_ok();
+            flush(&mut current, &mut words, &mut range);
+            range = is_range;
+        }
+    }
+    flush(&mut current, &mut words, &mut range);
+    words
+}
+
+fn unit_seconds(unit: &str) -> Option<f64> {
+    match unit {
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(1.0),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(60.0),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(3600.0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_step_duration() {
+        let minutes = |m: u64| Some(Duration::from_secs(m * 60));
+        assert_eq!(step_duration("Simmer for 20 minutes, stirring"), minutes(20));
+        assert_eq!(step_duration("Bake 1 hr 15 min until golden"), minutes(75));
+        assert_eq!(step_duration("Roast 1h30m"), minutes(90));
+        assert_eq!(step_duration("Rest for 1 hour and 10 minutes"), minutes(70));
+        assert_eq!(step_duration("Boil 10-12 min"), minutes(12));
+        assert_eq!(step_duration("Microwave 90 seconds"), Some(Duration::from_secs(90)));
+        assert_eq!(step_duration("Cook 2.5 minutes per side"), Some(Duration::from_secs(150)));
+        assert_eq!(step_duration("Add 2 cups of broth"), None);
+        assert_eq!(step_duration("Chop 3 onions"), None);
+    }
+
+    #[test]
+    fn test_format_countdown() {
+        assert_eq!(format_countdown(Duration::from_secs(65)), "1:05");
+        assert_eq!(format_countdown(Duration::from_secs(20 * 60)), "20:00");
+        assert_eq!(format_countdown(Duration::from_secs(3600 + 5)), "1:00:05");
+    }
+
+    #[test]
+    fn test_walk_steps() {
+        let recipe = Recipe {
+            name: "Chili".to_string(),
+            tags: Vec::new(),
+            ingredients: vec!["1 lb beef".to_string()],
+            link: None,
+            servings: None,
+            photo: None,
+            steps: vec!["Brown the beef".to_string(), "Simmer for 20 minutes".to_string(), "Serve".to_string()],
+            equipment: Vec::new(),
+        };
+
+        // Back from the second step, then start its timer
+        let mut timers = Vec::new();
+        let mut out = Vec::new();
+        let done = walk_steps(&recipe, &mut Cursor::new("\nb\n\n\n\n"), &mut out, |duration, _| {
+            timers.push(duration);
+            Ok(())
+        })
+        .unwrap();
+        assert!(done);
+        assert_eq!(timers, vec![Duration::from_secs(20 * 60)]);
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("Cooking Chili, 3 steps.\nYou'll need: 1 lb beef\n"));
+        assert_eq!(text.matches("Step 1/3: Brown the beef").count(), 2);
+        assert!(text.contains("[Enter] start the 20:00 timer, [s] skip it"));
+        assert!(text.ends_with("All done. Enjoy!\n"));
+
+        // Skipping the timer, then quitting
+        let mut timers = 0;
+        let done = walk_steps(&recipe, &mut Cursor::new("\ns\nq\n"), &mut Vec::new(), |_, _| {
+            timers += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert!(!done);
+        assert_eq!(timers, 0);
+    }
+}
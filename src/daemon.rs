@@ -0,0 +1,253 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Name of the status socket created in the storage directory
+pub const STATUS_SOCKET: &str = "daemon.sock";
+
+/// What the daemon reports over its status socket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub started_at: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_seconds_option")]
+    pub last_sync: Option<DateTime<Utc>>,
+    pub last_result: Option<String>,
+    pub sync_count: u64,
+}
+
+impl DaemonStatus {
+    /// Creates the status for a daemon that has just started
+    pub fn new() -> Self {
+        Self {
+            pid: std::process::id(),
+            started_at: Utc::now(),
+            last_sync: None,
+            last_result: None,
+            sync_count: 0,
+        }
+    }
+}
+
+/// Tracks modification times of a set of files to detect changes between polls
+pub struct FileWatcher {
+    last_seen: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl FileWatcher {
+    /// Starts watching the given files, treating their current state as unchanged
+    pub fn new(paths: &[PathBuf]) -> Self {
+        let last_seen = paths.iter().map(|p| (p.clone(), modified_time(p))).collect();
+        Self { last_seen }
+    }
+
+    /// Returns true if any watched file was created, modified, or deleted since the last call
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        for (path, seen) in self.last_seen.iter_mut() {
+            let current = modified_time(path);
+            if current != *seen {
+                *seen = current;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Watches the files and calls `sync` once changes have settled for `debounce`.
+/// Changes made by `sync` itself are not treated as new edits. `paths` is asked for the
+/// files to watch before every poll, so they can move when the configuration is reloaded.
+/// Runs until the process exits.
+pub fn watch<P, F>(mut paths: P, interval: Duration, debounce: Duration, status: Arc<Mutex<DaemonStatus>>, mut sync: F)
+where
+    P: FnMut() -> Vec<PathBuf>,
+    F: FnMut() -> Result<(), String>,
+{
+    let mut watching = paths();
+    let mut watcher = FileWatcher::new(&watching);
+
+    loop {
+        thread::sleep(interval);
+        let current = paths();
+        if current != watching {
+            println!("[{}] Now watching {:?}.", Utc::now().format("%Y-%m-%d %H:%M:%S"), current);
+            watcher = FileWatcher::new(&current);
+            watching = current;
+        }
+        if !watcher.poll() {
+            continue;
+        }
+
+        // Wait for a quiet period so editors that save in several steps are synced once
+        loop {
+            thread::sleep(debounce);
+            if !watcher.poll() {
+                break;
+            }
+        }
+
+        let result = sync();
+        match &result {
+            Ok(()) => println!("[{}] Synchronized meal plan files.", Utc::now().format("%Y-%m-%d %H:%M:%S")),
+            Err(e) => eprintln!("[{}] Sync failed: {}", Utc::now().format("%Y-%m-%d %H:%M:%S"), e),
+        }
+
+        if let Ok(mut status) = status.lock() {
+            status.last_sync = Some(Utc::now());
+            status.last_result = Some(result.err().unwrap_or_else(|| "ok".to_string()));
+            status.sync_count += 1;
+        }
+
+        // Don't react to the files we just rewrote
+        watcher.poll();
+    }
+}
+
+/// Serves the daemon status as a line of JSON to every client that connects to the socket
+#[cfg(unix)]
+pub fn serve_status(socket_path: &Path, status: Arc<Mutex<DaemonStatus>>) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    // A socket left behind by a previous run would make bind fail, but one that still
+    // answers belongs to a daemon that is running
+    if socket_path.exists() {
+        if UnixStream::connect(socket_path).is_ok() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                "another daemon is already running for this storage path",
+            ));
+        }
+        std::fs::remove_file(socket_path)?;
+    }
+    let listener = UnixListener::bind(socket_path)?;
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let json = match status.lock() {
+                Ok(status) => serde_json::to_string(&*status).unwrap_or_default(),
+                Err(_) => continue,
+            };
+            let _ = writeln!(stream, "{}", json);
+        }
+    });
+
+    Ok(())
+}
+
+/// Asks a running daemon for its status
+#[cfg(unix)]
+pub fn query_status(socket_path: &Path) -> std::io::Result<DaemonStatus> {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+
+    let stream = UnixStream::connect(socket_path)?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+/// Builds a systemd user unit that runs the daemon with the given arguments
+pub fn systemd_unit(executable: &Path, args: &[String]) -> String {
+    let mut exec_start = unit_word(&executable.display().to_string());
+    for arg in args {
+        exec_start.push(' ');
+        exec_start.push_str(&unit_word(arg));
+    }
+
+    format!(
+        "[Unit]\n\
+         Description=Meal plan sync daemon\n\
+         \n\
+         [Service]\n\
+         ExecStart={}\n\
+         Restart=on-failure\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        exec_start
+    )
+}
+
+/// Writes one word of a unit's command line: `%` is doubled so systemd doesn't expand it,
+/// and words with spaces, quotes or backslashes are quoted
+fn unit_word(word: &str) -> String {
+    let word = word.replace('%', "%%");
+    if word.is_empty() || word.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'' || c == '\\') {
+        format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_file_watcher_detects_changes() {
+        let temp_dir = tempdir().unwrap();
+        let json_path = temp_dir.path().join("meal_plan.json");
+        let markdown_path = temp_dir.path().join("meal_plan.md");
+        std::fs::write(&json_path, "{}").unwrap();
+
+        let mut watcher = FileWatcher::new(&[json_path.clone(), markdown_path.clone()]);
+        assert!(!watcher.poll());
+
+        // Creating a watched file counts as a change
+        std::fs::write(&markdown_path, "# Meal Plan").unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        // So does deleting one
+        std::fs::remove_file(&json_path).unwrap();
+        assert!(watcher.poll());
+    }
+
+    #[test]
+    fn test_systemd_unit() {
+        let unit = systemd_unit(
+            Path::new("/usr/local/bin/mealplan"),
+            &["--path".to_string(), "/home/me/Meal Plans".to_string(), "daemon".to_string()],
+        );
+        assert!(unit.contains("ExecStart=/usr/local/bin/mealplan --path \"/home/me/Meal Plans\" daemon\n"));
+        assert!(unit.contains("WantedBy=default.target"));
+
+        // The executable is quoted too, and systemd's specifiers are escaped
+        let unit = systemd_unit(Path::new("/home/me/My Apps/mealplan"), &["--path".to_string(), "/data/100%".to_string()]);
+        assert!(unit.contains("ExecStart=\"/home/me/My Apps/mealplan\" --path /data/100%%\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_status_socket() {
+        let temp_dir = tempdir().unwrap();
+        let socket_path = temp_dir.path().join(STATUS_SOCKET);
+
+        // A socket nobody answers on is left over from an earlier run and replaced
+        drop(std::os::unix::net::UnixListener::bind(&socket_path).unwrap());
+        let status = Arc::new(Mutex::new(DaemonStatus::new()));
+        status.lock().unwrap().sync_count = 3;
+        serve_status(&socket_path, Arc::clone(&status)).unwrap();
+
+        let reported = query_status(&socket_path).unwrap();
+        assert_eq!(reported.pid, std::process::id());
+        assert_eq!(reported.sync_count, 3);
+
+        // A second daemon doesn't take the socket from the running one
+        let error = serve_status(&socket_path, status).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AddrInUse);
+        assert_eq!(query_status(&socket_path).unwrap().sync_count, 3);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::models::MealPlan;
+use crate::places::Places;
+use crate::prices::PriceHistory;
+use crate::recipes::RecipeBook;
+use chrono::NaiveDate;
+use parquet::basic::Compression;
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int32Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Columns of the exported table, in order
+pub const COLUMNS: [&str; 8] = ["date", "slot", "dish", "cook", "tags", "rating", "cost", "cooked"];
+
+/// Separates a meal's tags within the tags column
+pub const TAG_SEPARATOR: &str = ";";
+
+const PARQUET_SCHEMA: &str = "
+    message meal {
+        REQUIRED INT32 date (DATE);
+        REQUIRED BYTE_ARRAY slot (UTF8);
+        REQUIRED BYTE_ARRAY dish (UTF8);
+        REQUIRED BYTE_ARRAY cook (UTF8);
+        REQUIRED BYTE_ARRAY tags (UTF8);
+        OPTIONAL INT32 rating;
+        OPTIONAL DOUBLE cost;
+        REQUIRED BOOLEAN cooked;
+    }
+";
+
+/// One planned meal, flattened for analysis
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryRow {
+    pub date: NaiveDate,
+    /// The meal type, in lowercase
+    pub slot: String,
+    pub dish: String,
+    pub cook: String,
+    pub tags: Vec<String>,
+    pub rating: Option<u8>,
+    /// The place's typical cost for meals eaten out, or else what the recipe's ingredients
+    /// cost at their latest prices, when every one has a price
+    pub cost: Option<f64>,
+    pub cooked: bool,
+}
+
+/// Flattens weeks of plans into one row per meal, in date and meal type order
+pub fn history_rows(plans: &[MealPlan], book: &RecipeBook, prices: &PriceHistory, places: &Places) -> Vec<HistoryRow> {
+    let mut rows: Vec<HistoryRow> = plans
+        .iter()
+        .flat_map(|plan| {
+            plan.meals().iter().map(move |meal| {
+                let cost = match &meal.place {
+                    Some(place) => places.find(place).and_then(|place| place.typical_cost),
+                    None => book
+                        .for_meal(meal)
+                        .filter(|recipe| !recipe.ingredients.is_empty())
+                        .map(|recipe| prices.estimate(&recipe.ingredients))
+                        .and_then(|(total, unpriced)| (unpriced == 0).then_some(total)),
+                };
+                HistoryRow {
+                    date: plan.date_of(&meal.day),
+                    slot: meal.meal_type.to_string().to_lowercase(),
+                    dish: meal.description.clone(),
+                    cook: meal.cook.clone(),
+                    tags: meal.tags.clone(),
+                    rating: meal.rating,
+                    cost,
+                    cooked: meal.cooked,
+                }
+            })
+        })
+        .collect();
+    let slot_order = |slot: &str| ["breakfast", "lunch", "snack", "dinner"].iter().position(|s| *s == slot);
+    rows.sort_by_key(|row| (row.date, slot_order(&row.slot)));
+    rows
+}
+
+/// Quotes a CSV field when it holds a comma, quote or line break
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+/// Writes the rows as CSV with a heading row. Missing ratings and costs are left empty.
+pub fn write_csv<W: Write>(rows: &[HistoryRow], mut out: W) -> io::Result<()> {
+    writeln!(out, "{}", COLUMNS.join(","))?;
+    for row in rows {
+        let fields = [
+            row.date.format("%Y-%m-%d").to_string(),
+            row.slot.clone(),
+            row.dish.clone(),
+            row.cook.clone(),
+            row.tags.join(TAG_SEPARATOR),
+            row.rating.map(|rating| rating.to_string()).unwrap_or_default(),
+            row.cost.map(|cost| format!("{:.2}", cost)).unwrap_or_default(),
+            row.cooked.to_string(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        writeln!(out, "{}", fields.join(","))?;
+    }
+    Ok(())
+}
+
+/// Writes the rows as a Parquet file with one row group, compressed with Snappy
+pub fn write_parquet(rows: &[HistoryRow], path: &Path) -> io::Result<()> {
+    let invalid = |e: parquet::errors::ParquetError| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+    let schema = Arc::new(parse_message_type(PARQUET_SCHEMA).map_err(invalid)?);
+    let properties = Arc::new(WriterProperties::builder().set_compression(Compression::SNAPPY).build());
+    let mut writer = SerializedFileWriter::new(File::create(path)?, schema, properties).map_err(invalid)?;
+    let mut row_group = writer.next_row_group().map_err(invalid)?;
+
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap_or_default();
+    let text = |field: fn(&HistoryRow) -> String| rows.iter().map(|row| ByteArray::from(field(row).as_str())).collect::<Vec<_>>();
+    // Optional columns hold only the values present, with a definition level of 1 for each
+    // row that has one and 0 for each that doesn't
+    let levels = |present: fn(&HistoryRow) -> bool| rows.iter().map(|row| i16::from(present(row))).collect::<Vec<_>>();
+
+    let mut column = 0;
+    while let Some(mut writer) = row_group.next_column().map_err(invalid)? {
+        match COLUMNS[column] {
+            "date" => {
+                let days: Vec<i32> = rows.iter().map(|row| (row.date - epoch).num_days() as i32).collect();
+                writer.typed::<Int32Type>().write_batch(&days, None, None)
+            }
+            "slot" => writer.typed::<ByteArrayType>().write_batch(&text(|row| row.slot.clone()), None, None),
+            "dish" => writer.typed::<ByteArrayType>().write_batch(&text(|row| row.dish.clone()), None, None),
+            "cook" => writer.typed::<ByteArrayType>().write_batch(&text(|row| row.cook.clone()), None, None),
+            "tags" => writer.typed::<ByteArrayType>().write_batch(&text(|row| row.tags.join(TAG_SEPARATOR)), None, None),
+            "rating" => {
+                let ratings: Vec<i32> = rows.iter().filter_map(|row| row.rating).map(i32::from).collect();
+                writer.typed::<Int32Type>().write_batch(&ratings, Some(&levels(|row| row.rating.is_some())), None)
+            }
+            "cost" => {
+                let costs: Vec<f64> = rows.iter().filter_map(|row| row.cost).collect();
+                writer.typed::<DoubleType>().write_batch(&costs, Some(&levels(|row| row.cost.is_some())), None)
+            }
+            _ => {
+                let cooked: Vec<bool> = rows.iter().map(|row| row.cooked).collect();
+                writer.typed::<BoolType>().write_batch(&cooked, None, None)
+            }
+        }
+        .map_err(invalid)?;
+        writer.close().map_err(invalid)?;
+        column += 1;
+    }
+    row_group.close().map_err(invalid)?;
+    writer.close().map_err(invalid)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, Meal, MealType};
+    use crate::places::Place;
+    use crate::recipes::Recipe;
+    use chrono::Weekday;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use tempfile::tempdir;
+
+    fn history() -> Vec<HistoryRow> {
+        let mut book = RecipeBook::default();
+        book.add(Recipe { name: "Chili".to_string(), tags: Vec::new(), ingredients: vec!["beans".to_string(), "beef".to_string()], link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() });
+        let mut prices = PriceHistory::default();
+        prices.record("beans", 2.0, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap()).unwrap();
+        prices.record("beef", 8.5, NaiveDate::from_ymd_opt(2023, 1, 1).unwrap()).unwrap();
+        let mut places = Places::default();
+        places.add(Place { name: "Thai Palace".to_string(), cuisine: None, contact: None, typical_cost: Some(30.0) });
+
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let mut chili = Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Chili".to_string());
+        chili.tags = vec!["beans".to_string(), "spicy".to_string()];
+        chili.rating = Some(4);
+        chili.cooked = true;
+        plan.add_meal(chili);
+        let mut takeout = Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), String::new(), "Pad Thai".to_string());
+        takeout.place = Some("Thai Palace".to_string());
+        plan.add_meal(takeout);
+        plan.add_meal(Meal::new(MealType::Breakfast, Day::Weekday(Weekday::Tue), "Bob".to_string(), "Toast, jam".to_string()));
+        history_rows(&[plan], &book, &prices, &places)
+    }
+
+    #[test]
+    fn test_history_csv() {
+        let rows = history();
+        assert_eq!(rows.iter().map(|row| row.dish.as_str()).collect::<Vec<_>>(), vec!["Chili", "Toast, jam", "Pad Thai"]);
+
+        let mut csv = Vec::new();
+        write_csv(&rows, &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "date,slot,dish,cook,tags,rating,cost,cooked\n\
+             2023-01-02,dinner,Chili,Alice,beans;spicy,4,10.50,true\n\
+             2023-01-03,breakfast,\"Toast, jam\",Bob,,,,false\n\
+             2023-01-03,dinner,Pad Thai,,,,30.00,false\n"
+        );
+    }
+
+    #[test]
+    fn test_history_parquet() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("history.parquet");
+        write_parquet(&history(), &path).unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+        let rows: Vec<String> = reader.get_row_iter(None).unwrap().map(|row| row.unwrap().to_string()).collect();
+        assert_eq!(
+            rows[0],
+            "{date: 2023-01-02, slot: \"dinner\", dish: \"Chili\", cook: \"Alice\", tags: \"beans;spicy\", rating: 4, cost: 10.5, cooked: true}"
+        );
+        assert!(rows[1].contains("rating: null, cost: null"), "{}", rows[1]);
+    }
+}
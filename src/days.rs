@@ -0,0 +1,167 @@
+use crate::plan::Day;
+use chrono::{NaiveDate, Weekday};
+
+/// Weekday names in the languages households have asked for, Monday first. Accents are
+/// optional when typing, so the names are stored without them.
+const LOCALIZED_NAMES: [[&str; 7]; 6] = [
+    // French
+    ["lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche"],
+    // German
+    ["montag", "dienstag", "mittwoch", "donnerstag", "freitag", "samstag", "sonntag"],
+    // Spanish
+    ["lunes", "martes", "miercoles", "jueves", "viernes", "sabado", "domingo"],
+    // Italian
+    ["lunedi", "martedi", "mercoledi", "giovedi", "venerdi", "sabato", "domenica"],
+    // Portuguese, which also writes "segunda-feira" and so on
+    ["segunda", "terca", "quarta", "quinta", "sexta", "sabado", "domingo"],
+    // Dutch
+    ["maandag", "dinsdag", "woensdag", "donderdag", "vrijdag", "zaterdag", "zondag"],
+];
+
+/// English abbreviations besides the first three letters of each name
+const ABBREVIATIONS: [(&str, Weekday); 11] = [
+    ("mo", Weekday::Mon),
+    ("tu", Weekday::Tue),
+    ("tues", Weekday::Tue),
+    ("we", Weekday::Wed),
+    ("weds", Weekday::Wed),
+    ("th", Weekday::Thu),
+    ("thur", Weekday::Thu),
+    ("thurs", Weekday::Thu),
+    ("fr", Weekday::Fri),
+    ("sa", Weekday::Sat),
+    ("su", Weekday::Sun),
+];
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// Parses a weekday name: English names and their abbreviations ("thu", "tues"), plurals
+/// ("fridays"), and French, German, Spanish, Italian, Portuguese and Dutch names. Case and
+/// accents don't matter.
+pub fn parse_weekday(text: &str) -> Option<Weekday> {
+    let name = fold_accents(&text.trim().to_lowercase());
+    let name = name.strip_suffix("-feira").unwrap_or(&name);
+    let name = name.strip_suffix('s').filter(|n| n.ends_with("day")).unwrap_or(name);
+
+    if let Ok(weekday) = name.parse::<Weekday>() {
+        return Some(weekday);
+    }
+    if let Some((_, weekday)) = ABBREVIATIONS.iter().find(|(abbreviation, _)| *abbreviation == name) {
+        return Some(*weekday);
+    }
+    LOCALIZED_NAMES
+        .iter()
+        .find_map(|names| names.iter().position(|n| *n == name))
+        .map(|n| WEEKDAYS[n])
+}
+
+/// Parses a day of the plan's week: a date as YYYY-MM-DD, a weekday name as
+/// [`parse_weekday`] accepts, or a number from 1 to 7 counting from the day the week starts
+pub fn parse_day(text: &str, week_start: Weekday) -> Result<Day, String> {
+    let text = text.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Ok(Day::Date(date));
+    }
+    if let Ok(number) = text.parse::<u8>() {
+        if (1..=7).contains(&number) {
+            let offset = (week_start.num_days_from_monday() as usize + number as usize - 1) % 7;
+            return Ok(Day::Weekday(WEEKDAYS[offset]));
+        }
+        return Err(format!("Invalid day number {}. Use 1 to 7, where 1 is the first day of the week.", number));
+    }
+    parse_weekday(text)
+        .map(Day::Weekday)
+        .ok_or_else(|| "Invalid day format. Use YYYY-MM-DD, a day name or a number from 1 to 7.".to_string())
+}
+
+/// The weekday's full English name, e.g. Friday
+pub fn weekday_name(weekday: Weekday) -> String {
+    // Any date falling on the weekday will do for formatting its full name
+    NaiveDate::from_isoywd_opt(2023, 1, weekday)
+        .map(|date| date.format("%A").to_string())
+        .unwrap_or_else(|| weekday.to_string())
+}
+
+/// Splits a list of days such as `mon,wed,fri` into single days, expanding `weekdays`,
+/// `weekend` and `everyday` into the days they stand for
+pub fn split_days(text: &str) -> Vec<String> {
+    let names = |days: &[&str]| days.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+    text.split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .flat_map(|part| match part.to_lowercase().as_str() {
+            "weekdays" => names(&["monday", "tuesday", "wednesday", "thursday", "friday"]),
+            "weekend" | "weekends" => names(&["saturday", "sunday"]),
+            "everyday" | "daily" => names(&["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"]),
+            _ => vec![part.to_string()],
+        })
+        .collect()
+}
+
+/// Replaces the accented letters used in weekday names with plain ones
+fn fold_accents(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ä' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'ö' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' => 'u',
+            'ç' => 'c',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_weekday() {
+        assert_eq!(parse_weekday("Thursday"), Some(Weekday::Thu));
+        assert_eq!(parse_weekday("thu"), Some(Weekday::Thu));
+        assert_eq!(parse_weekday("Tues"), Some(Weekday::Tue));
+        assert_eq!(parse_weekday("fridays"), Some(Weekday::Fri));
+        assert_eq!(parse_weekday("su"), Some(Weekday::Sun));
+        assert_eq!(parse_weekday("Miércoles"), Some(Weekday::Wed));
+        assert_eq!(parse_weekday("mercoledì"), Some(Weekday::Wed));
+        assert_eq!(parse_weekday("Samedi"), Some(Weekday::Sat));
+        assert_eq!(parse_weekday("terça-feira"), Some(Weekday::Tue));
+        assert_eq!(parse_weekday("donnerstag"), Some(Weekday::Thu));
+        assert_eq!(parse_weekday("zondag"), Some(Weekday::Sun));
+        assert_eq!(parse_weekday("someday"), None);
+        assert_eq!(parse_weekday("s"), None);
+    }
+
+    #[test]
+    fn test_parse_day() {
+        assert_eq!(parse_day("2023-05-01", Weekday::Mon), Ok(Day::Date(NaiveDate::from_ymd_opt(2023, 5, 1).unwrap())));
+        assert_eq!(parse_day(" wed ", Weekday::Mon), Ok(Day::Weekday(Weekday::Wed)));
+        assert_eq!(parse_day("1", Weekday::Mon), Ok(Day::Weekday(Weekday::Mon)));
+        assert_eq!(parse_day("7", Weekday::Mon), Ok(Day::Weekday(Weekday::Sun)));
+        // Numbers count from the day the plan's week starts
+        assert_eq!(parse_day("1", Weekday::Sat), Ok(Day::Weekday(Weekday::Sat)));
+        assert_eq!(parse_day("3", Weekday::Sat), Ok(Day::Weekday(Weekday::Mon)));
+        assert!(parse_day("8", Weekday::Mon).is_err());
+        assert!(parse_day("0", Weekday::Mon).is_err());
+        assert!(parse_day("2023-02-30", Weekday::Mon).is_err());
+    }
+
+    #[test]
+    fn test_split_days() {
+        assert_eq!(split_days("mon, wed,fri"), vec!["mon", "wed", "fri"]);
+        assert_eq!(split_days("Weekend"), vec!["saturday", "sunday"]);
+        assert_eq!(split_days("weekdays,sun").len(), 6);
+        assert_eq!(split_days("2023-01-02"), vec!["2023-01-02"]);
+        assert!(split_days(" , ").is_empty());
+    }
+}
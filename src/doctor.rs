@@ -0,0 +1,222 @@
+use crate::archive::Archive;
+use crate::daemon;
+use crate::events::{self, EventLog};
+use crate::models::MealPlan;
+use crate::settings::{self, Severity, ValidationContext};
+use crate::storage::{self, SyncState};
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// The result of one check, with what to do about it when it didn't pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, status: Status::Pass, detail: detail.into(), fix: None }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status: Status::Warn, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status: Status::Fail, detail: detail.into(), fix: Some(fix.into()) }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.status {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        };
+        write!(f, "[{}] {}: {}", label, self.name, self.detail)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n       Fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs every check on the configuration and the storage directory
+pub fn run_checks(config_path: &Path, storage_path: &Path, context: &ValidationContext) -> Vec<Check> {
+    vec![
+        check_config(config_path, context),
+        check_storage(storage_path),
+        check_plan(storage_path),
+        check_event_log(storage_path),
+        check_drift(storage_path),
+        check_archive(storage_path),
+        check_daemon_socket(storage_path),
+    ]
+}
+
+fn check_config(config_path: &Path, context: &ValidationContext) -> Check {
+    const NAME: &str = "Configuration";
+    let contents = match std::fs::read_to_string(config_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Check::warn(NAME, format!("No file at {:?}; defaults are used.", config_path), "Run 'mealplan config init'.");
+        }
+        Err(e) => return Check::fail(NAME, format!("{:?} can't be read: {}.", config_path, e), "Check the file's permissions."),
+    };
+    let issues = settings::validate_config(&contents, context);
+    let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+    match (errors, issues.len()) {
+        (0, 0) => Check::pass(NAME, format!("{:?} is valid.", config_path)),
+        (0, warnings) => Check::warn(NAME, format!("{} warning(s) in {:?}.", warnings, config_path), "Run 'mealplan config validate' for details."),
+        (errors, _) => Check::fail(NAME, format!("{} error(s) in {:?}.", errors, config_path), "Run 'mealplan config validate' for details."),
+    }
+}
+
+fn check_storage(storage_path: &Path) -> Check {
+    const NAME: &str = "Storage";
+    if !storage_path.is_dir() {
+        return Check::fail(NAME, format!("{:?} is not a directory.", storage_path), "Create it, or set meal_plan_storage_path to another directory.");
+    }
+    let probe = storage_path.join(format!(".mealplan-doctor-{}", std::process::id()));
+    match std::fs::write(&probe, b"").and_then(|_| std::fs::remove_file(&probe)) {
+        Ok(()) => Check::pass(NAME, format!("{:?} is writable.", storage_path)),
+        Err(e) => Check::fail(NAME, format!("{:?} can't be written to: {}.", storage_path, e), "Check the directory's owner and permissions."),
+    }
+}
+
+fn check_plan(storage_path: &Path) -> Check {
+    const NAME: &str = "Meal plan";
+    let path = storage_path.join("meal_plan.json");
+    if !path.exists() {
+        return Check::warn(NAME, "No meal plan has been saved yet.", "Add a meal with 'mealplan add'.");
+    }
+    if let Err(e) = MealPlan::load_from_json(&path) {
+        return Check::fail(NAME, format!("{:?} can't be read: {}.", path, e), "Run 'mealplan migrate' if it was written by an older version; otherwise fix it with 'mealplan open'.");
+    }
+    match storage::verify_checksum(&path) {
+        Ok(Some(false)) => Check::warn(NAME, format!("{:?} was changed outside mealplan.", path), "Run 'mealplan sync status' to see what changed."),
+        Err(e) => Check::warn(NAME, format!("The checksum of {:?} can't be read: {}.", path, e), "Save the plan again, e.g. with 'mealplan sync'."),
+        Ok(_) => Check::pass(NAME, format!("{:?} loads.", path)),
+    }
+}
+
+fn check_event_log(storage_path: &Path) -> Check {
+    const NAME: &str = "Change log";
+    let log = EventLog::new(storage_path);
+    match log.read() {
+        Ok(events) => {
+            let corrupt = storage_path.join(events::CORRUPT_LOG);
+            if corrupt.exists() {
+                Check::warn(
+                    NAME,
+                    format!("{} change(s) recorded; lines that couldn't be read were moved to {:?}.", events.len(), corrupt),
+                    "Look them over, then delete the file.",
+                )
+            } else {
+                Check::pass(NAME, format!("{} change(s) recorded.", events.len()))
+            }
+        }
+        Err(e) => Check::fail(NAME, format!("{:?} can't be read: {}.", log.path(), e), "Move it aside; the next save starts a new log from the JSON file."),
+    }
+}
+
+fn check_drift(storage_path: &Path) -> Check {
+    const NAME: &str = "JSON and Markdown";
+    let json_path = storage_path.join("meal_plan.json");
+    let markdown_path = storage_path.join("meal_plan.md");
+    if !json_path.exists() {
+        return Check::pass(NAME, "Nothing to compare yet.");
+    }
+    if !markdown_path.exists() {
+        return Check::warn(NAME, "The Markdown copy is missing.", "Run 'mealplan sync --source json'.");
+    }
+    match SyncState::load(storage_path).changes(&json_path, &markdown_path) {
+        Ok(changes) if changes.json && changes.markdown => {
+            Check::fail(NAME, "Both files changed since they were last in sync.", "Run 'mealplan sync status' to see what changed, then 'mealplan sync --source json' to keep the JSON.")
+        }
+        Ok(changes) if changes.json => Check::warn(NAME, "The JSON file changed since the last sync.", "Run 'mealplan sync'."),
+        Ok(changes) if changes.markdown => Check::warn(
+            NAME,
+            "The Markdown copy was edited since the last sync.",
+            "Run 'mealplan sync status', then 'mealplan sync --source json' once the edits are in the plan.",
+        ),
+        Ok(_) => Check::pass(NAME, "The files are in sync."),
+        Err(e) => Check::fail(NAME, format!("The files can't be read: {}.", e), "Check the storage directory's permissions."),
+    }
+}
+
+fn check_archive(storage_path: &Path) -> Check {
+    const NAME: &str = "Archive";
+    let archive = Archive::new(storage_path);
+    match archive.weeks() {
+        Ok(weeks) if weeks.is_empty() => Check::pass(NAME, "No weeks archived yet."),
+        Ok(weeks) => match archive.index() {
+            Ok(_) => Check::pass(NAME, format!("{} week(s) archived.", weeks.len())),
+            Err(e) => Check::fail(NAME, format!("The archive index can't be built: {}.", e), "Look for a damaged week file in the archive directory."),
+        },
+        Err(e) => Check::fail(NAME, format!("The archive can't be read: {}.", e), "Check the archive directory's permissions."),
+    }
+}
+
+/// A status socket nobody answers on is left over from a daemon that didn't exit cleanly
+fn check_daemon_socket(storage_path: &Path) -> Check {
+    const NAME: &str = "Daemon";
+    let socket = storage_path.join(daemon::STATUS_SOCKET);
+    if !socket.exists() {
+        return Check::pass(NAME, "Not running.");
+    }
+    match daemon::query_status(&socket) {
+        Ok(_) => Check::pass(NAME, "Running."),
+        Err(_) => Check::warn(NAME, format!("{:?} is left over from a daemon that is no longer running.", socket), format!("Delete {:?}.", socket)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use tempfile::tempdir;
+
+    fn context() -> ValidationContext {
+        ValidationContext { today: NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(), google_credentials: None }
+    }
+
+    fn statuses(checks: &[Check]) -> Vec<(&str, Status)> {
+        checks.iter().map(|c| (c.name, c.status)).collect()
+    }
+
+    #[test]
+    fn test_run_checks() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        let checks = run_checks(&config_path, dir.path(), &context());
+        assert_eq!(statuses(&checks), vec![
+            ("Configuration", Status::Warn),
+            ("Storage", Status::Pass),
+            ("Meal plan", Status::Warn),
+            ("Change log", Status::Pass),
+            ("JSON and Markdown", Status::Pass),
+            ("Archive", Status::Pass),
+            ("Daemon", Status::Pass),
+        ]);
+
+        std::fs::write(dir.path().join("meal_plan.json"), "{\"week_start_date\": ").unwrap();
+        std::fs::write(dir.path().join(daemon::STATUS_SOCKET), "").unwrap();
+        let checks = run_checks(&config_path, dir.path(), &context());
+        assert_eq!(checks[2].status, Status::Fail);
+        assert_eq!(checks[4].status, Status::Warn);
+        assert_eq!(checks[6].status, Status::Warn);
+        assert!(checks[6].to_string().contains("Fix: Delete"));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::models::{Meal, MealPlan, MealType};
+use chrono::NaiveDate;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Words that don't tell dishes apart
+const FILLER_WORDS: &[&str] = &["a", "an", "and", "the", "with", "of", "on", "in", "homemade", "easy"];
+
+/// How much two descriptions' words must overlap for the dishes to be taken as the same
+const SIMILARITY_THRESHOLD: f64 = 0.6;
+
+/// What to do with an imported meal for a slot that already has one
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Leave the meal already planned
+    Skip,
+    /// Put the imported meal in its place
+    Replace,
+    /// Keep both dishes in the slot, e.g. "Chili + Cornbread"
+    KeepBoth,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::Skip => write!(f, "skip"),
+            Strategy::Replace => write!(f, "replace"),
+            Strategy::KeepBoth => write!(f, "keep both"),
+        }
+    }
+}
+
+/// How an imported meal compares with the one already in its slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collision {
+    /// The same dish and cook, so there is nothing to import
+    Same,
+    /// A similar description, such as "Chili" and "chili con carne": most likely the same meal
+    Duplicate,
+    /// A different dish
+    Conflict,
+}
+
+/// The words of a description that tell the dish apart, in lowercase
+fn words(description: &str) -> BTreeSet<String> {
+    description
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !FILLER_WORDS.contains(word))
+        // "Tacos" and "taco" are the same dish
+        .map(|word| word.strip_suffix('s').filter(|stem| stem.len() > 2).unwrap_or(word).to_string())
+        .collect()
+}
+
+/// Whether two descriptions name the same dish: the same words apart from case, punctuation
+/// and filler, one's words all within the other's, or mostly the same words
+pub fn similar(a: &str, b: &str) -> bool {
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return a == b;
+    }
+    if a.is_subset(&b) || b.is_subset(&a) {
+        return true;
+    }
+    let shared = a.intersection(&b).count() as f64;
+    shared / a.union(&b).count() as f64 >= SIMILARITY_THRESHOLD
+}
+
+pub fn classify(existing: &Meal, incoming: &Meal) -> Collision {
+    if existing.description.trim() == incoming.description.trim() && existing.cook.trim() == incoming.cook.trim() {
+        Collision::Same
+    } else if similar(&existing.description, &incoming.description) {
+        Collision::Duplicate
+    } else {
+        Collision::Conflict
+    }
+}
+
+/// The meal planned for a meal type on a date, however its day is written
+pub fn find_at<'a>(meal_plan: &'a MealPlan, meal_type: &MealType, date: NaiveDate) -> Option<&'a Meal> {
+    meal_plan.meals().iter().find(|meal| meal.meal_type == *meal_type && meal_plan.date_of(&meal.day) == date)
+}
+
+/// The existing meal with the imported dish added to it, for keeping both. A dish it already
+/// has isn't added again, and the existing cook stays unless it had none.
+pub fn combine(existing: &Meal, incoming: &Meal) -> Meal {
+    let mut combined = existing.clone();
+    let has_dish = existing.description.split(" + ").any(|dish| similar(dish, &incoming.description));
+    if !has_dish {
+        combined.description = format!("{} + {}", existing.description.trim(), incoming.description.trim());
+    }
+    if combined.cook.trim().is_empty() {
+        combined.cook = incoming.cook.clone();
+    }
+    for tag in &incoming.tags {
+        if !combined.tags.contains(tag) {
+            combined.tags.push(tag.clone());
+        }
+    }
+    combined
+}
+
+/// What resolving one imported meal did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Added,
+    /// The slot already had the same meal
+    Unchanged,
+    Replaced,
+    Combined,
+    Skipped(Collision),
+}
+
+/// Puts an imported meal into a plan. When its slot already has a meal, `decide` is asked
+/// what to do, unless the meal is the same one.
+pub fn resolve<F>(meal_plan: &mut MealPlan, incoming: Meal, decide: F) -> Outcome
+where
+    F: FnOnce(&Meal, &Meal, Collision) -> Strategy,
+{
+    let date = meal_plan.date_of(&incoming.day);
+    let Some(existing) = find_at(meal_plan, &incoming.meal_type, date).cloned() else {
+        meal_plan.add_meal(incoming);
+        return Outcome::Added;
+    };
+    let collision = classify(&existing, &incoming);
+    if collision == Collision::Same {
+        return Outcome::Unchanged;
+    }
+    match decide(&existing, &incoming, collision) {
+        Strategy::Skip => Outcome::Skipped(collision),
+        Strategy::Replace => {
+            // The imported meal takes the existing one's day, so the slot isn't written twice
+            // as a weekday and as a date
+            meal_plan.remove_meal(&existing.meal_type, &existing.day);
+            meal_plan.add_meal(Meal { day: existing.day.clone(), ..incoming });
+            Outcome::Replaced
+        }
+        Strategy::KeepBoth => {
+            meal_plan.add_meal(combine(&existing, &incoming));
+            Outcome::Combined
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Day;
+    use chrono::Weekday;
+
+    fn meal(day: Day, description: &str) -> Meal {
+        Meal::new(MealType::Dinner, day, "Al".to_string(), description.to_string())
+    }
+
+    #[test]
+    fn test_similar() {
+        assert!(similar("Chili", "chili con carne"));
+        assert!(similar("Tacos with salsa", "taco and salsa"));
+        assert!(similar("Spaghetti bolognese!", "Spaghetti Bolognese"));
+        assert!(!similar("Chicken curry", "Beef stew"));
+        assert!(!similar("Chicken curry", "Chicken soup with rice"));
+        assert!(!similar("", "Soup"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(meal(Day::Weekday(Weekday::Mon), "Chili"));
+        let monday = Day::Date(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+
+        // The same date written as a date finds the meal planned for the weekday
+        assert_eq!(resolve(&mut plan, meal(monday.clone(), "Chili"), |_, _, _| Strategy::Replace), Outcome::Unchanged);
+        let mut seen = None;
+        let outcome = resolve(&mut plan, meal(monday.clone(), "chili con carne"), |_, _, collision| {
+            seen = Some(collision);
+            Strategy::Skip
+        });
+        assert_eq!((outcome, seen), (Outcome::Skipped(Collision::Duplicate), Some(Collision::Duplicate)));
+
+        assert_eq!(resolve(&mut plan, meal(monday.clone(), "Cornbread"), |_, _, _| Strategy::KeepBoth), Outcome::Combined);
+        assert_eq!(resolve(&mut plan, meal(monday.clone(), "cornbread"), |_, _, _| Strategy::KeepBoth), Outcome::Combined);
+        assert_eq!(plan.meals().len(), 1);
+        assert_eq!(plan.meals()[0].description, "Chili + Cornbread");
+
+        assert_eq!(resolve(&mut plan, meal(monday, "Stew"), |_, _, _| Strategy::Replace), Outcome::Replaced);
+        assert_eq!((plan.meals().len(), plan.meals()[0].description.as_str()), (1, "Stew"));
+        assert_eq!(plan.meals()[0].day, Day::Weekday(Weekday::Mon));
+    }
+}
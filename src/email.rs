@@ -0,0 +1,214 @@
+use crate::grocery::GroceryList;
+use crate::models::{CookProfile, Meal, MealPlan};
+use crate::recipes::RecipeBook;
+use crate::secrets::{self, Integration};
+use crate::spreadsheet::GRID_MEAL_TYPES;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How the connection to the mail server is secured
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Security {
+    /// Connect in plain text and switch to TLS, usually on port 587
+    #[default]
+    Starttls,
+    /// TLS from the start, usually on port 465
+    Tls,
+    /// No encryption, for a relay on the same machine
+    None,
+}
+
+/// The mail server the weekly digest is sent through, kept in the configuration's `email`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EmailSettings {
+    pub smtp_host: String,
+    /// Leave it out for the usual port of the security used
+    #[serde(default)]
+    pub smtp_port: Option<u16>,
+    #[serde(default)]
+    pub security: Security,
+    /// Account to sign in with; leave it out for a server that takes mail without signing in
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Leave it out to use the one stored with `mealplan auth set email`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub password: String,
+    /// Address the digest comes from, e.g. "Meal Plan <mealplan@example.com>"
+    pub from: String,
+}
+
+/// One cook's copy of the weekly digest
+#[derive(Debug, Clone, PartialEq)]
+pub struct Digest {
+    pub cook: String,
+    pub address: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// The week's meals cooked by someone, in order
+fn meals_for<'a>(meal_plan: &'a MealPlan, cook: &str) -> Vec<&'a Meal> {
+    let mut meals: Vec<&Meal> = meal_plan
+        .meals()
+        .iter()
+        .filter(|meal| meal.place.is_none() && meal.cook.trim().eq_ignore_ascii_case(cook.trim()))
+        .collect();
+    meals.sort_by_key(|meal| (meal_plan.date_of(&meal.day), GRID_MEAL_TYPES.iter().position(|t| *t == meal.meal_type)));
+    meals
+}
+
+/// A cook's digest: the meals they're cooking, the prep for those with a recipe, and what's
+/// left on the shared grocery list
+pub fn digest_body(meal_plan: &MealPlan, cook: &str, book: &RecipeBook, grocery_list: &GroceryList) -> String {
+    let meals = meals_for(meal_plan, cook);
+    let slot = |meal: &Meal| format!("{} {}", meal_plan.date_of(&meal.day).format("%A"), meal.meal_type.to_string().to_lowercase());
+
+    let mut body = format!("Hi {},\n\nHere's the plan for the week of {}.\n\n", cook.trim(), meal_plan.week_start_date.format("%a %b %-d"));
+    if meals.is_empty() {
+        body.push_str("You're not cooking this week.\n");
+    } else {
+        body.push_str("You're cooking:\n");
+        for meal in &meals {
+            body.push_str(&format!("  {}: {}\n", slot(meal), meal_plan.render_description(meal).trim()));
+        }
+    }
+
+    let prep: Vec<(&Meal, _)> = meals.iter().filter_map(|meal| book.for_meal(meal).map(|recipe| (*meal, recipe))).collect();
+    if !prep.is_empty() {
+        body.push_str("\nYour prep:\n");
+        for (meal, recipe) in prep {
+            body.push_str(&format!("  {}, for {}\n", recipe.name, slot(meal)));
+            for ingredient in &recipe.ingredients {
+                body.push_str(&format!("    - {}\n", ingredient));
+            }
+        }
+    }
+
+    let remaining = grocery_list.remaining();
+    if remaining.is_empty() {
+        body.push_str("\nNothing is left on the grocery list.\n");
+    } else {
+        body.push_str("\nShared grocery list:\n");
+        for item in remaining {
+            body.push_str(&format!("  - {}\n", item.name));
+        }
+    }
+    body
+}
+
+/// A digest for each cook with an email address, or only the one named
+pub fn digests(meal_plan: &MealPlan, cooks: &[CookProfile], only: Option<&str>, book: &RecipeBook, grocery_list: &GroceryList) -> Vec<Digest> {
+    let subject = format!("Meal plan for the week of {}", meal_plan.week_start_date.format("%b %-d"));
+    cooks
+        .iter()
+        .filter(|cook| only.is_none_or(|name| cook.name.eq_ignore_ascii_case(name.trim())))
+        .filter_map(|cook| {
+            let address = cook.email.as_deref()?.trim();
+            (!address.is_empty()).then(|| Digest {
+                cook: cook.name.clone(),
+                address: address.to_string(),
+                subject: subject.clone(),
+                body: digest_body(meal_plan, &cook.name, book, grocery_list),
+            })
+        })
+        .collect()
+}
+
+/// A connection to the mail server, for sending the digests
+pub struct Mailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+}
+
+impl Mailer {
+    pub fn new(settings: &EmailSettings, timeout: Duration) -> Result<Self, String> {
+        let from: Mailbox = settings.from.parse().map_err(|e| format!("The from address {:?} isn't valid: {}", settings.from, e))?;
+        let mut builder = match settings.security {
+            Security::Starttls => SmtpTransport::starttls_relay(&settings.smtp_host).map_err(|e| e.to_string())?,
+            Security::Tls => SmtpTransport::relay(&settings.smtp_host).map_err(|e| e.to_string())?,
+            Security::None => SmtpTransport::builder_dangerous(&settings.smtp_host),
+        };
+        if let Some(port) = settings.smtp_port {
+            builder = builder.port(port);
+        }
+        if let Some(username) = &settings.username {
+            let password = if settings.password.is_empty() {
+                secrets::lookup(Integration::Email).unwrap_or_default()
+            } else {
+                settings.password.clone()
+            };
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+        Ok(Self { transport: builder.timeout(Some(timeout)).build(), from })
+    }
+
+    pub fn send(&self, digest: &Digest) -> Result<(), String> {
+        let address = digest.address.parse().map_err(|e| format!("{:?} isn't a valid address: {}", digest.address, e))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(Mailbox::new(Some(digest.cook.clone()), address))
+            .subject(&digest.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(digest.body.clone())
+            .map_err(|e| e.to_string())?;
+        self.transport.send(&message).map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, MealType};
+    use crate::recipes::Recipe;
+    use chrono::{NaiveDate, Weekday};
+
+    #[test]
+    fn test_digests() {
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Wed), "Alice".to_string(), "Tacos".to_string()));
+        plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "alice".to_string(), "Chili".to_string()));
+        plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Mon), "Bob".to_string(), "Soup".to_string()));
+        let mut book = RecipeBook::default();
+        book.add(Recipe {
+            name: "Chili".to_string(),
+            tags: Vec::new(),
+            ingredients: vec!["1 lb ground beef".to_string(), "2 cans beans".to_string()],
+            link: None,
+            servings: None,
+            photo: None,
+            steps: Vec::new(),
+            equipment: Vec::new(),
+        });
+        let mut list = GroceryList::new(plan.week_start_date);
+        list.add_item("milk", None);
+        list.add_item("eggs", None);
+        list.set_purchased("eggs", true);
+
+        let mut alice = CookProfile::new("Alice".to_string());
+        alice.email = Some("alice@example.com".to_string());
+        let mut carol = CookProfile::new("Carol".to_string());
+        carol.email = Some("carol@example.com".to_string());
+        let cooks = vec![alice, CookProfile::new("Bob".to_string()), carol];
+
+        // Only cooks with an address get a digest
+        let digests = digests(&plan, &cooks, None, &book, &list);
+        assert_eq!(digests.iter().map(|d| d.cook.as_str()).collect::<Vec<_>>(), vec!["Alice", "Carol"]);
+        assert_eq!(digests[0].subject, "Meal plan for the week of Jan 2");
+        assert_eq!(
+            digests[0].body,
+            "Hi Alice,\n\nHere's the plan for the week of Mon Jan 2.\n\n\
+             You're cooking:\n  Monday dinner: Chili\n  Wednesday dinner: Tacos\n\n\
+             Your prep:\n  Chili, for Monday dinner\n    - 1 lb ground beef\n    - 2 cans beans\n\n\
+             Shared grocery list:\n  - milk\n"
+        );
+        assert!(digests[1].body.contains("You're not cooking this week.\n"));
+        assert!(!digests[1].body.contains("Your prep"));
+
+        assert_eq!(super::digests(&plan, &cooks, Some("carol"), &book, &list).len(), 1);
+    }
+}
@@ -0,0 +1,239 @@
+use crate::cooking::step_duration;
+use crate::lint::{Finding, Severity};
+use crate::models::MealPlan;
+use crate::recipes::{Recipe, RecipeBook};
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// An appliance a recipe keeps busy until the dish is ready, e.g. the oven at 200°C for
+/// 45 minutes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EquipmentUse {
+    pub appliance: String,
+    /// Degrees Celsius the appliance is set to, for ovens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<u32>,
+    pub minutes: u32,
+}
+
+impl EquipmentUse {
+    /// Parses an appliance, an optional temperature and how long it's needed, such as
+    /// "oven 200C 45 min", "oven 400°F for 1 hour" or "stove 20 minutes"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let example = "Give the appliance and how long it's needed, e.g. \"oven 200C 45 min\".";
+        let text = text.trim().to_lowercase();
+        let words = text.split(|c: char| c.is_ascii_digit()).next().unwrap_or_default();
+        let appliance = words.split_whitespace()
+            .filter(|word| !matches!(*word, "at" | "for"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if appliance.is_empty() {
+            return Err(example.to_string());
+        }
+        let minutes = step_duration(&text).ok_or_else(|| example.to_string())?.as_secs().div_ceil(60) as u32;
+        Ok(Self { appliance, temperature: temperature(&text), minutes })
+    }
+}
+
+impl std::fmt::Display for EquipmentUse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.appliance)?;
+        if let Some(temperature) = self.temperature {
+            write!(f, " at {}°C", temperature)?;
+        }
+        write!(f, " for {} min", self.minutes)
+    }
+}
+
+/// Finds a temperature such as 200C, 200 °C or 400F in lowercase text, in Celsius
+fn temperature(text: &str) -> Option<u32> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && chars[i].is_ascii_digit() {
+            i += 1;
+        }
+        let number: u32 = chars[start..i].iter().collect::<String>().parse().ok()?;
+        let mut unit = i;
+        while unit < chars.len() && matches!(chars[unit], ' ' | '°' | 'º') {
+            unit += 1;
+        }
+        let ends = chars.get(unit + 1).is_none_or(|c| !c.is_alphabetic());
+        match chars.get(unit) {
+            Some('c') if ends => return Some(number),
+            Some('f') if ends => return Some(((number as f64 - 32.0) * 5.0 / 9.0).round() as u32),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// When a dish keeps an appliance busy, in minutes relative to serving time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Booking<'a> {
+    need: &'a EquipmentUse,
+    start: i64,
+    end: i64,
+}
+
+/// Works out when to start each dish so they're all ready to serve without an appliance
+/// taking more dishes than `capacity` allows, or an oven two temperatures. Dishes are
+/// placed in order, each finishing as close to serving time as it can, so the first is
+/// served freshest. Returns each dish's start in minutes before serving, and whether any
+/// had to be started early to make room.
+fn stagger(dishes: &[&Recipe], capacity: &dyn Fn(&str) -> u32) -> (Vec<i64>, bool) {
+    let mut booked: Vec<Booking> = Vec::new();
+    let mut starts = Vec::new();
+    let mut staggered = false;
+    for recipe in dishes {
+        let fits = |end: i64| {
+            recipe.equipment.iter().all(|need| {
+                let start = end - need.minutes as i64;
+                let overlapping: Vec<&Booking> = booked.iter()
+                    .filter(|b| b.need.appliance == need.appliance && b.start < end && start < b.end)
+                    .collect();
+                let other_temperature = overlapping.iter()
+                    .any(|b| b.need.temperature.zip(need.temperature).is_some_and(|(a, b)| a != b));
+                (overlapping.len() as u32) < capacity(&need.appliance) && !other_temperature
+            })
+        };
+        // Finishing at serving time, or just as something already booked starts
+        let mut ends: Vec<i64> = booked.iter().map(|b| b.start).chain([0]).filter(|end| *end <= 0).collect();
+        ends.sort_unstable_by(|a, b| b.cmp(a));
+        ends.dedup();
+        let end = ends.into_iter().find(|end| fits(*end)).unwrap_or(0);
+        staggered |= end < 0;
+        for need in &recipe.equipment {
+            booked.push(Booking { need, start: end - need.minutes as i64, end });
+        }
+        starts.push(recipe.equipment.iter().map(|need| end - need.minutes as i64).min().unwrap_or(end));
+    }
+    (starts, staggered)
+}
+
+/// Meals whose dishes, joined with " + " as in "Roast Chicken + Apple Pie", need the same
+/// appliance at once, with start times that get everything ready by the meal. `capacities`
+/// says how many dishes an appliance takes at once; others take one.
+pub fn equipment_findings(meal_plan: &MealPlan, book: &RecipeBook, capacities: &BTreeMap<String, u32>) -> Vec<Finding> {
+    let capacity = |appliance: &str| {
+        capacities.iter()
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case(appliance))
+            .map_or(1, |(_, capacity)| *capacity)
+    };
+    let mut findings = Vec::new();
+    for meal in meal_plan.meals() {
+        let mut dishes: Vec<&Recipe> = meal.description.split(" + ").filter_map(|dish| book.find(dish.trim())).collect();
+        if dishes.is_empty() {
+            dishes.extend(book.for_meal(meal));
+        }
+        dishes.retain(|recipe| !recipe.equipment.is_empty());
+        if dishes.len() < 2 {
+            continue;
+        }
+        let (starts, staggered) = stagger(&dishes, &capacity);
+        if !staggered {
+            continue;
+        }
+
+        let date = meal_plan.date_of(&meal.day);
+        let serve = date.and_time(meal.meal_type.usual_time());
+        let mut appliances: Vec<&str> = Vec::new();
+        for need in dishes.iter().flat_map(|recipe| &recipe.equipment) {
+            let shared = dishes.iter().filter(|recipe| recipe.equipment.iter().any(|n| n.appliance == need.appliance)).count() > 1;
+            if shared && !appliances.contains(&need.appliance.as_str()) {
+                appliances.push(&need.appliance);
+            }
+        }
+        let mut order: Vec<(i64, &str)> = starts.iter().copied().zip(dishes.iter().map(|recipe| recipe.name.as_str())).collect();
+        order.sort();
+        let plan: Vec<String> = order.iter()
+            .map(|(start, name)| format!("{} at {}", name, (serve + Duration::minutes(*start)).format("%H:%M")))
+            .collect();
+        findings.push(Finding {
+            rule: "equipment".to_string(),
+            severity: Severity::Warning,
+            date: Some(date),
+            message: format!(
+                "{}: {} need the {} at the same time. Start {} to have everything ready by {}.",
+                meal.meal_type,
+                dishes.iter().map(|recipe| recipe.name.as_str()).collect::<Vec<_>>().join(" and "),
+                appliances.join(" and "),
+                plan.join(", then "),
+                serve.format("%H:%M")
+            ),
+        });
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, Meal, MealType};
+    use chrono::{NaiveDate, Weekday};
+
+    fn recipe(name: &str, equipment: &[&str]) -> Recipe {
+        Recipe {
+            name: name.to_string(),
+            tags: Vec::new(),
+            ingredients: Vec::new(),
+            link: None,
+            servings: None,
+            photo: None,
+            steps: Vec::new(),
+            equipment: equipment.iter().map(|text| EquipmentUse::parse(text).unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_equipment() {
+        let oven = EquipmentUse::parse("Oven 200°C for 45 min").unwrap();
+        assert_eq!(oven, EquipmentUse { appliance: "oven".to_string(), temperature: Some(200), minutes: 45 });
+        assert_eq!(oven.to_string(), "oven at 200°C for 45 min");
+        assert_eq!(EquipmentUse::parse("oven 400F 1 hour").unwrap().temperature, Some(204));
+        assert_eq!(EquipmentUse::parse("oven at 180 C 1h15m").unwrap().minutes, 75);
+        let stove = EquipmentUse::parse("stove 20 minutes").unwrap();
+        assert_eq!((stove.appliance.as_str(), stove.temperature), ("stove", None));
+        assert_eq!(EquipmentUse::parse("slow cooker 6 hours").unwrap().appliance, "slow cooker");
+        assert!(EquipmentUse::parse("oven 200C").is_err());
+        assert!(EquipmentUse::parse("45 min").is_err());
+    }
+
+    #[test]
+    fn test_equipment_findings() {
+        let mut book = RecipeBook::default();
+        book.add(recipe("Roast Chicken", &["oven 200C 60 min"]));
+        book.add(recipe("Apple Pie", &["oven 180C 45 min"]));
+        book.add(recipe("Potatoes", &["oven 200C 40 min"]));
+        book.add(recipe("Rice", &["stove 20 min"]));
+        book.add(recipe("Gravy", &["stove 10 min"]));
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        let mut add = |day: Weekday, description: &str| {
+            plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(day), "Al".to_string(), description.to_string()));
+        };
+        add(Weekday::Mon, "Roast Chicken + Apple Pie");
+        // The oven takes dishes at one temperature together, and the stove has burners enough
+        add(Weekday::Tue, "Roast Chicken + Potatoes");
+        add(Weekday::Wed, "Rice + Gravy");
+        add(Weekday::Thu, "Roast Chicken");
+        let capacities = BTreeMap::from([("oven".to_string(), 2), ("Stove".to_string(), 4)]);
+
+        let findings = equipment_findings(&plan, &book, &capacities);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].date, NaiveDate::from_ymd_opt(2024, 1, 1));
+        assert_eq!(
+            findings[0].message,
+            "Dinner: Roast Chicken and Apple Pie need the oven at the same time. \
+             Start Apple Pie at 16:15, then Roast Chicken at 17:00 to have everything ready by 18:00."
+        );
+
+        // With one burner, the gravy waits for the rice
+        assert_eq!(equipment_findings(&plan, &book, &BTreeMap::new()).len(), 3);
+    }
+}
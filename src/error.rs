@@ -0,0 +1,143 @@
+use serde::Serialize;
+
+/// Category of a failure. Each category exits with its own code so scripts can tell
+/// them apart without parsing the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Anything not covered by a more specific category
+    General,
+    /// An argument or value on the command line is invalid
+    Usage,
+    /// The meal, cook, file or other item asked for doesn't exist
+    NotFound,
+    /// The change clashes with the current state, such as a slot that is already planned
+    Conflict,
+    /// The user answered no to a confirmation prompt
+    Cancelled,
+    /// Reading or writing a file failed, e.g. because the disk is full
+    Io,
+    /// A file was read but its contents are damaged or in the wrong format
+    Data,
+}
+
+impl ErrorKind {
+    /// The process exit code for this category
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::General => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::NotFound => 3,
+            ErrorKind::Conflict => 4,
+            ErrorKind::Cancelled => 5,
+            ErrorKind::Io => 6,
+            ErrorKind::Data => 7,
+        }
+    }
+}
+
+/// An error reported to the user, with the category that decides the exit code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CliError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl CliError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn usage(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Usage, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::NotFound, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Conflict, message)
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Cancelled, message)
+    }
+
+    /// Wraps a file error with what was being done, categorizing it by the error's kind
+    pub fn io(context: &str, error: std::io::Error) -> Self {
+        let kind = match error.kind() {
+            std::io::ErrorKind::NotFound => ErrorKind::NotFound,
+            std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof => ErrorKind::Data,
+            _ => ErrorKind::Io,
+        };
+        Self::new(kind, format!("{}: {}", context, error))
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.kind.exit_code()
+    }
+
+    /// The error as a single line of JSON, for `--error-format json`
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "error": {
+                "kind": self.kind,
+                "code": self.exit_code(),
+                "message": self.message,
+            }
+        })
+        .to_string()
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorKind::General, message)
+    }
+}
+
+impl From<&str> for CliError {
+    fn from(message: &str) -> Self {
+        Self::new(ErrorKind::General, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_errors_are_categorized() {
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        assert_eq!(CliError::io("Failed to load", missing).kind, ErrorKind::NotFound);
+
+        let damaged = std::io::Error::new(std::io::ErrorKind::InvalidData, "expected value");
+        let error = CliError::io("Failed to load", damaged);
+        assert_eq!(error.kind, ErrorKind::Data);
+        assert_eq!(error.message, "Failed to load: expected value");
+
+        let full = std::io::Error::other("No space left on device");
+        assert_eq!(CliError::io("Failed to save", full).exit_code(), 6);
+    }
+
+    #[test]
+    fn test_json_format() {
+        let error = CliError::not_found("No Dinner meal found for Mon.");
+        assert_eq!(
+            error.to_json(),
+            r#"{"error":{"code":3,"kind":"not_found","message":"No Dinner meal found for Mon."}}"#
+        );
+    }
+}
@@ -0,0 +1,471 @@
+use crate::models::{Meal, MealPlan, Tombstone, TrashTombstone, TrashedMeal};
+use crate::storage;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the event log in the storage directory
+pub const EVENT_LOG: &str = "events.jsonl";
+
+/// Where lines of the log that can't be read are moved, beside the log
+pub const CORRUPT_LOG: &str = "events.jsonl.corrupt";
+
+/// Size past which the log is compacted into the events that rebuild the saved plan
+pub const COMPACT_THRESHOLD: u64 = 1024 * 1024;
+
+/// A single change to the meal plan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Event {
+    /// A new, empty week was started, replacing the previous week's plan
+    WeekStarted { week_start_date: NaiveDate },
+    /// A meal was put in its slot, replacing whatever was there
+    MealAdded { meal: Meal },
+    /// A meal slot was emptied
+    MealRemoved { tombstone: Tombstone },
+    /// A removed meal was kept in the trash
+    MealTrashed { entry: TrashedMeal },
+    /// A meal left the trash, either restored or deleted for good
+    MealTakenFromTrash { id: u32 },
+    /// A meal left the trash on another device, learned from a merge
+    TrashTombstoneAdded { tombstone: TrashTombstone },
+}
+
+/// An event as stored in the log, with the time it was recorded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggedEvent {
+    /// Position in the log, counting from 1. Events logged before there were sequence
+    /// numbers are numbered by where they are when read.
+    #[serde(default)]
+    pub seq: u64,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub recorded_at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Append-only log of every change made to the plan, one JSON event per line
+pub struct EventLog {
+    path: PathBuf,
+}
+
+impl EventLog {
+    /// Opens the event log inside the given storage directory
+    pub fn new(storage_path: &Path) -> Self {
+        Self {
+            path: storage_path.join(EVENT_LOG),
+        }
+    }
+
+    /// Returns the file the log is stored in
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends events to the end of the log, returning the sequence number of the last
+    /// event in it afterwards
+    pub fn append(&self, events: &[Event]) -> std::io::Result<u64> {
+        let last_seq = self.read()?.last().map_or(0, |logged| logged.seq);
+        if events.is_empty() {
+            return Ok(last_seq);
+        }
+
+        let recorded_at = Utc::now();
+        let mut lines = String::new();
+        for (seq, event) in (last_seq + 1..).zip(events) {
+            let logged = LoggedEvent {
+                seq,
+                recorded_at,
+                event: event.clone(),
+            };
+            lines.push_str(&serde_json::to_string(&logged)?);
+            lines.push('\n');
+        }
+
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(&self.path)?;
+        // A line cut short by a crash is ended first, so the new events don't run on from it
+        if file.seek(SeekFrom::End(0))? > 0 {
+            file.seek(SeekFrom::End(-1))?;
+            let mut last = [0u8];
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                lines.insert(0, '\n');
+            }
+        }
+        file.write_all(lines.as_bytes())?;
+        Ok(last_seq + events.len() as u64)
+    }
+
+    /// Reads every event in the log, oldest first. Lines that can't be parsed are moved to
+    /// `events.jsonl.corrupt` with a warning, so they are reported once and kept for a look,
+    /// and the log is rewritten without them. A last line without its line ending, such as
+    /// one cut short by a crash mid-write, is skipped until another event ends it.
+    pub fn read(&self) -> std::io::Result<Vec<LoggedEvent>> {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut events: Vec<LoggedEvent> = Vec::new();
+        let mut kept = String::new();
+        let mut corrupt = String::new();
+        for line in contents.split_inclusive('\n') {
+            match serde_json::from_str::<LoggedEvent>(line) {
+                Ok(mut event) => {
+                    let previous = events.last().map_or(0, |logged| logged.seq);
+                    event.seq = event.seq.max(previous + 1);
+                    events.push(event);
+                    kept.push_str(line);
+                }
+                Err(_) if line.ends_with('\n') && !line.trim().is_empty() => corrupt.push_str(line),
+                Err(_) => kept.push_str(line),
+            }
+        }
+
+        if !corrupt.is_empty() {
+            let corrupt_path = self.path.with_file_name(CORRUPT_LOG);
+            OpenOptions::new().create(true).append(true).open(&corrupt_path)?.write_all(corrupt.as_bytes())?;
+            storage::write_atomic(&self.path, kept.as_bytes())?;
+            eprintln!(
+                "Warning: Moved {} damaged line(s) of the change log to {:?}.",
+                corrupt.lines().count(),
+                corrupt_path
+            );
+        }
+        Ok(events)
+    }
+
+    /// Once the log has grown past `COMPACT_THRESHOLD`, rewrites it as the events that build
+    /// `plan`, the plan just saved, from nothing. The last event keeps the sequence number
+    /// the log had, so the snapshot and the log still agree. Returns whether it was compacted.
+    pub fn compact(&self, plan: &MealPlan) -> std::io::Result<bool> {
+        let size = std::fs::metadata(&self.path).map_or(0, |metadata| metadata.len());
+        if size <= COMPACT_THRESHOLD {
+            return Ok(false);
+        }
+
+        let events = diff(None, plan);
+        let first_seq = (plan.event_seq + 1).saturating_sub(events.len() as u64).max(1);
+        let mut lines = String::new();
+        for (seq, event) in (first_seq..).zip(events) {
+            let logged = LoggedEvent { seq, recorded_at: plan.last_modified, event };
+            lines.push_str(&serde_json::to_string(&logged)?);
+            lines.push('\n');
+        }
+        storage::write_atomic(&self.path, lines.as_bytes())?;
+        Ok(true)
+    }
+
+    /// Rebuilds the current plan by replaying the log, or returns None if nothing has been logged
+    pub fn project(&self) -> std::io::Result<Option<MealPlan>> {
+        Ok(project(&self.read()?))
+    }
+}
+
+/// Replays events into a plan. Returns None until a week has been started.
+pub fn project(events: &[LoggedEvent]) -> Option<MealPlan> {
+    let mut plan: Option<MealPlan> = None;
+
+    for logged in events {
+        if let Event::WeekStarted { week_start_date } = &logged.event {
+            plan = Some(MealPlan::new(*week_start_date));
+        }
+        let Some(plan) = plan.as_mut() else { continue };
+
+        match &logged.event {
+            Event::WeekStarted { .. } => {}
+            Event::MealAdded { meal } => plan.restore_meal(meal.clone()),
+            Event::MealRemoved { tombstone } => plan.restore_tombstone(tombstone.clone()),
+            Event::MealTrashed { entry } => {
+                plan.trash.retain(|t| t.id != entry.id);
+                plan.trash.push(entry.clone());
+            }
+            Event::MealTakenFromTrash { id } => {
+                plan.take_from_trash(*id);
+            }
+            Event::TrashTombstoneAdded { tombstone } => plan.add_trash_tombstone(tombstone.clone()),
+        }
+        plan.last_modified = logged.recorded_at;
+        plan.event_seq = logged.seq;
+    }
+
+    plan
+}
+
+/// Works out the events that turn `previous` into `current`
+pub fn diff(previous: Option<&MealPlan>, current: &MealPlan) -> Vec<Event> {
+    let mut events = Vec::new();
+
+    let previous = match previous {
+        Some(previous) if previous.week_start_date == current.week_start_date => previous,
+        _ => {
+            events.push(Event::WeekStarted {
+                week_start_date: current.week_start_date,
+            });
+            for tombstone in &current.tombstones {
+                events.push(Event::MealRemoved { tombstone: tombstone.clone() });
+            }
+            for meal in current.meals() {
+                events.push(Event::MealAdded { meal: meal.clone() });
+            }
+            for entry in &current.trash {
+                events.push(Event::MealTrashed { entry: entry.clone() });
+            }
+            for tombstone in &current.trash_tombstones {
+                events.push(Event::TrashTombstoneAdded { tombstone: tombstone.clone() });
+            }
+            return events;
+        }
+    };
+
+    // Slots emptied since the previous save
+    for old in previous.meals() {
+        if current.find_meal(&old.meal_type, &old.day).is_some() {
+            continue;
+        }
+        let tombstone = current
+            .tombstones
+            .iter()
+            .find(|t| t.meal_type == old.meal_type && t.day == old.day)
+            .cloned()
+            .unwrap_or_else(|| Tombstone {
+                meal_type: old.meal_type.clone(),
+                day: old.day.clone(),
+                clock: old.clock.clone(),
+                removed_at: None,
+                removed_by: None,
+            });
+        events.push(Event::MealRemoved { tombstone });
+    }
+
+    // Tombstones that arrived without the meal being in the previous plan, e.g. from a merge
+    for tombstone in &current.tombstones {
+        let known = previous.tombstones.contains(tombstone)
+            || previous.find_meal(&tombstone.meal_type, &tombstone.day).is_some();
+        if !known {
+            events.push(Event::MealRemoved { tombstone: tombstone.clone() });
+        }
+    }
+
+    // Meals that are new or changed
+    for meal in current.meals() {
+        if previous.find_meal(&meal.meal_type, &meal.day) != Some(meal) {
+            events.push(Event::MealAdded { meal: meal.clone() });
+        }
+    }
+
+    // Meals that left the trash, then ones that went in
+    for entry in &previous.trash {
+        // Compared whole, as an id freed by a restore can be given to the next meal trashed
+        if !current.trash.contains(entry) {
+            events.push(Event::MealTakenFromTrash { id: entry.id });
+        }
+    }
+    for entry in &current.trash {
+        if !previous.trash.contains(entry) {
+            events.push(Event::MealTrashed { entry: entry.clone() });
+        }
+    }
+
+    // Trash entries taken out elsewhere, which the trash events above don't account for
+    for tombstone in &current.trash_tombstones {
+        let known = previous.trash_tombstones.contains(tombstone)
+            || previous.trash.iter().any(|entry| tombstone.matches(entry));
+        if !known {
+            events.push(Event::TrashTombstoneAdded { tombstone: tombstone.clone() });
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, MealType};
+    use chrono::{Timelike, Weekday};
+    use tempfile::tempdir;
+
+    fn dinner(day: Weekday, description: &str) -> Meal {
+        Meal::new(MealType::Dinner, Day::Weekday(day), "Alice".to_string(), description.to_string())
+    }
+
+    fn replay(log: &EventLog) -> MealPlan {
+        log.project().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_log_replays_changes() {
+        let temp_dir = tempdir().unwrap();
+        let log = EventLog::new(temp_dir.path());
+        assert!(log.project().unwrap().is_none());
+
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut plan = MealPlan::new(week_start);
+        plan.add_meal(dinner(Weekday::Mon, "Chili"));
+        log.append(&diff(None, &plan)).unwrap();
+
+        // Replace Monday's dinner and add Tuesday's
+        let previous = replay(&log);
+        plan.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon));
+        plan.add_meal(dinner(Weekday::Mon, "Tacos"));
+        plan.add_meal(dinner(Weekday::Tue, "Soup"));
+        log.append(&diff(Some(&previous), &plan)).unwrap();
+
+        // Remove Tuesday's again
+        let previous = replay(&log);
+        plan.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Tue));
+        let events = diff(Some(&previous), &plan);
+        assert_eq!(events.len(), 1);
+        log.append(&events).unwrap();
+
+        let projected = replay(&log);
+        assert_eq!(projected.meals().len(), 1);
+        assert_eq!(projected.meals()[0].description, "Tacos");
+        assert_eq!(projected.meals()[0].clock, plan.meals()[0].clock);
+        assert_eq!(projected.tombstones.len(), 1);
+
+        // Saving an unchanged plan records nothing
+        assert!(diff(Some(&projected), &plan).is_empty());
+        assert_eq!(log.read().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_new_week_resets_projection() {
+        let temp_dir = tempdir().unwrap();
+        let log = EventLog::new(temp_dir.path());
+
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(dinner(Weekday::Mon, "Chili"));
+        log.append(&diff(None, &plan)).unwrap();
+
+        let next_week = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 9).unwrap());
+        let events = diff(Some(&replay(&log)), &next_week);
+        assert_eq!(events, vec![Event::WeekStarted { week_start_date: next_week.week_start_date }]);
+        log.append(&events).unwrap();
+
+        let projected = replay(&log);
+        assert_eq!(projected.week_start_date, next_week.week_start_date);
+        assert!(projected.meals().is_empty());
+    }
+
+    #[test]
+    fn test_log_replays_trash() {
+        let temp_dir = tempdir().unwrap();
+        let log = EventLog::new(temp_dir.path());
+
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(dinner(Weekday::Mon, "Chili"));
+        plan.add_meal(dinner(Weekday::Tue, "Soup"));
+        plan.trash_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon));
+        log.append(&diff(None, &plan)).unwrap();
+        assert_eq!(replay(&log).trash, plan.trash);
+
+        let previous = replay(&log);
+        plan.restore_from_trash(1).unwrap();
+        let id = plan.trash_meal(&MealType::Dinner, &Day::Weekday(Weekday::Tue)).unwrap();
+        log.append(&diff(Some(&previous), &plan)).unwrap();
+
+        let projected = replay(&log);
+        assert_eq!(projected.meals().len(), 1);
+        assert_eq!(projected.meals()[0].description, "Chili");
+        assert_eq!(projected.trash.len(), 1);
+        assert_eq!(projected.trash[0].id, id);
+        assert!(diff(Some(&projected), &plan).is_empty());
+
+        // A trash entry taken out on another device is logged too, so merges keep it out
+        let previous = replay(&log);
+        plan.add_trash_tombstone(TrashTombstone {
+            meal_type: MealType::Lunch,
+            day: Day::Weekday(Weekday::Wed),
+            removed_at: plan.last_modified.with_nanosecond(0).unwrap(),
+        });
+        log.append(&diff(Some(&previous), &plan)).unwrap();
+        assert_eq!(replay(&log).trash_tombstones, plan.trash_tombstones);
+        assert!(diff(Some(&replay(&log)), &plan).is_empty());
+    }
+
+    #[test]
+    fn test_read_skips_damaged_lines() {
+        let temp_dir = tempdir().unwrap();
+        let log = EventLog::new(temp_dir.path());
+
+        let plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        log.append(&diff(None, &plan)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(log.path()).unwrap();
+        file.write_all(b"{\"type\":\"MealAdd\n").unwrap();
+
+        assert_eq!(log.read().unwrap().len(), 1);
+        assert!(log.project().unwrap().is_some());
+
+        // The damaged line was moved out of the log, so it is only reported once
+        let corrupt = temp_dir.path().join(CORRUPT_LOG);
+        assert_eq!(std::fs::read_to_string(&corrupt).unwrap(), "{\"type\":\"MealAdd\n");
+        assert!(!std::fs::read_to_string(log.path()).unwrap().contains("MealAdd\n"));
+
+        // A line cut short at the end is left for now, and ended before the next events
+        let mut file = OpenOptions::new().append(true).open(log.path()).unwrap();
+        file.write_all(b"{\"seq\":2,\"type\":\"Mea").unwrap();
+        assert_eq!(log.read().unwrap().len(), 1);
+        let mut plan = plan.clone();
+        plan.add_meal(dinner(Weekday::Mon, "Chili"));
+        assert_eq!(log.append(&diff(Some(&replay(&log)), &plan)).unwrap(), 2);
+        assert_eq!(log.read().unwrap().len(), 2);
+        assert_eq!(replay(&log).meals().len(), 1);
+        assert!(std::fs::read_to_string(&corrupt).unwrap().ends_with("{\"seq\":2,\"type\":\"Mea\n"));
+    }
+
+    #[test]
+    fn test_events_are_numbered() {
+        let temp_dir = tempdir().unwrap();
+        let log = EventLog::new(temp_dir.path());
+
+        // Events logged before there were sequence numbers are numbered by position
+        let old = "{\"recorded_at\":1672617600,\"type\":\"WeekStarted\",\"week_start_date\":\"2023-01-02\"}\n";
+        std::fs::write(log.path(), old.repeat(2)).unwrap();
+        let seqs: Vec<u64> = log.read().unwrap().iter().map(|logged| logged.seq).collect();
+        assert_eq!(seqs, [1, 2]);
+
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(dinner(Weekday::Mon, "Chili"));
+        plan.add_meal(dinner(Weekday::Tue, "Soup"));
+        assert_eq!(log.append(&diff(Some(&replay(&log)), &plan)).unwrap(), 4);
+        assert_eq!(log.append(&[]).unwrap(), 4);
+        assert_eq!(replay(&log).event_seq, 4);
+    }
+
+    #[test]
+    fn test_large_log_is_compacted() {
+        let temp_dir = tempdir().unwrap();
+        let log = EventLog::new(temp_dir.path());
+
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        log.append(&diff(None, &plan)).unwrap();
+        assert!(!log.compact(&plan).unwrap());
+
+        let mut n = 0;
+        while std::fs::metadata(log.path()).unwrap().len() <= COMPACT_THRESHOLD {
+            let previous = replay(&log);
+            n += 1;
+            plan.add_meal(dinner(Weekday::Mon, &format!("Chili {}", n)));
+            let mut events = diff(Some(&previous), &plan);
+            // Many saves' worth of events at once, to keep the test quick
+            events = events.iter().cycle().take(events.len() * 500).cloned().collect();
+            plan.event_seq = log.append(&events).unwrap();
+        }
+        plan.add_meal(dinner(Weekday::Tue, "Soup"));
+        plan.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Tue));
+        plan.event_seq = log.append(&diff(Some(&replay(&log)), &plan)).unwrap();
+
+        assert!(log.compact(&plan).unwrap());
+        assert!(std::fs::metadata(log.path()).unwrap().len() < 4096);
+        let events = log.read().unwrap();
+        assert_eq!(events.last().unwrap().seq, plan.event_seq);
+        let projected = project(&events).unwrap();
+        assert!(diff(Some(&projected), &plan).is_empty());
+        assert_eq!(projected.event_seq, plan.event_seq);
+    }
+}
@@ -0,0 +1,92 @@
+/// A common workflow, as the commands to run for it in order. Each command is the words
+/// typed after `mealplan`; the tests parse every one, so they keep up with the CLI.
+pub struct Topic {
+    pub name: &'static str,
+    pub title: &'static str,
+    pub commands: &'static [&'static [&'static str]],
+}
+
+pub const TOPICS: &[Topic] = &[
+    Topic {
+        name: "week",
+        title: "Plan a week",
+        commands: &[
+            &["config", "init"],
+            &["cooks", "add", "Alice"],
+            &["cooks", "add", "Bob"],
+            &["add", "Tacos", "-t", "dinner", "-d", "monday", "-c", "Alice"],
+            &["add", "Chili", "-t", "dinner", "-d", "tue,thu", "-c", "auto"],
+            &["generate"],
+            &["check"],
+        ],
+    },
+    Topic {
+        name: "calendars",
+        title: "Share the plan as calendars",
+        commands: &[
+            &["export-ical", "--output", "meal_plan.ics"],
+            &["export-ical", "--merge", "family.ics", "--workflow"],
+            &["export-ical", "--output", "calendars", "--split", "weekly"],
+            &["digest", "--dry-run"],
+        ],
+    },
+    Topic {
+        name: "groceries",
+        title: "Generate the grocery list",
+        commands: &[
+            &["recipe", "add", "Chili", "--ingredient", "1 lb ground beef", "--ingredient", "2 cans beans", "--servings", "6"],
+            &["add", "Chili", "-t", "dinner", "-d", "wednesday", "-c", "Alice"],
+            &["attendance", "away", "Sam", "--day", "wednesday"],
+            &["grocery", "add", "milk"],
+            &["grocery", "--week"],
+            &["grocery", "check", "milk"],
+        ],
+    },
+    Topic {
+        name: "pantry",
+        title: "Cook from what's in the kitchen",
+        commands: &[
+            &["pantry", "add", "rice", "eggs", "soy sauce"],
+            &["whatcanimake", "--max-minutes", "30"],
+            &["snapshot", "create", "before changes"],
+            &["add", "Fried Rice", "-t", "dinner", "-d", "friday", "-c", "Bob"],
+        ],
+    },
+];
+
+/// Finds a topic by name, ignoring case
+pub fn find(name: &str) -> Option<&'static Topic> {
+    TOPICS.iter().find(|topic| topic.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// A word of a command as it's typed in a shell, quoted when it has spaces or characters
+/// the shell would treat specially
+pub fn shell_word(word: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./:,=@+".contains(c);
+    if !word.is_empty() && word.chars().all(plain) {
+        return word.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in word.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_word() {
+        assert_eq!(shell_word("tue,thu"), "tue,thu");
+        assert_eq!(shell_word("1 lb ground beef"), "\"1 lb ground beef\"");
+        assert_eq!(shell_word("say \"hi\" $HOME"), "\"say \\\"hi\\\" \\$HOME\"");
+        assert_eq!(shell_word(""), "\"\"");
+        assert_eq!(find("WEEK").map(|topic| topic.title), Some("Plan a week"));
+    }
+}
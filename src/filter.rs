@@ -0,0 +1,448 @@
+use crate::models::{Meal, MealPlan, MealType};
+use crate::days;
+use chrono::{Datelike, NaiveDate, Weekday};
+
+/// A meal field a filter condition can test
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Cook,
+    MealType,
+    Description,
+    Tag,
+    Day,
+    Date,
+    Cooked,
+    Rating,
+}
+
+impl Field {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "cook" => Ok(Field::Cook),
+            "meal_type" | "type" => Ok(Field::MealType),
+            "description" => Ok(Field::Description),
+            "tag" | "tags" => Ok(Field::Tag),
+            "day" => Ok(Field::Day),
+            "date" => Ok(Field::Date),
+            "cooked" => Ok(Field::Cooked),
+            "rating" => Ok(Field::Rating),
+            _ => Err(format!(
+                "Unknown field '{}'. Use cook, meal_type, description, tag, day, date, cooked or rating.",
+                name
+            )),
+        }
+    }
+}
+
+/// How a condition compares a field with its value. The negative forms are the opposite
+/// of the positive ones, so `tag != veg` matches meals without a `veg` tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Contains,
+    NotContains,
+    In,
+    NotIn,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A condition's value, checked against the field when the filter is parsed
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(String),
+    MealType(MealType),
+    Weekday(Weekday),
+    Date(NaiveDate),
+    Bool(bool),
+    Number(u8),
+}
+
+/// How deep parentheses and `!` may nest, so a hostile filter can't exhaust the stack
+const MAX_DEPTH: usize = 64;
+
+/// A parsed expression. Chains of `&&` and `||` are kept flat, so only parentheses and
+/// `!` make it deeper.
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Condition { field: Field, op: Op, values: Vec<Value> },
+}
+
+/// A parsed `--filter` expression such as
+/// `cook == "Alice" && meal_type in [dinner, lunch] && tag ~ "veg"`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0, depth: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(Self { expr }),
+            Some(token) => Err(format!("Unexpected {} after the end of the expression.", token)),
+        }
+    }
+
+    /// Whether a meal on the given date passes the filter
+    pub fn matches(&self, meal: &Meal, date: NaiveDate) -> bool {
+        evaluate(&self.expr, meal, date)
+    }
+
+    /// Keeps only the plan's meals that pass the filter
+    pub fn retain(&self, meal_plan: &mut MealPlan) {
+        let keep: Vec<bool> = meal_plan.meals().iter().map(|meal| self.matches(meal, meal_plan.date_of(&meal.day))).collect();
+        let mut keep = keep.into_iter();
+        meal_plan.retain_meals(|_| keep.next().unwrap_or(false));
+    }
+}
+
+fn evaluate(expr: &Expr, meal: &Meal, date: NaiveDate) -> bool {
+    match expr {
+        Expr::And(terms) => terms.iter().all(|term| evaluate(term, meal, date)),
+        Expr::Or(terms) => terms.iter().any(|term| evaluate(term, meal, date)),
+        Expr::Not(inner) => !evaluate(inner, meal, date),
+        Expr::Condition { field, op, values } => {
+            let positive = match op {
+                Op::Eq | Op::Ne => values.iter().any(|value| compare(*field, Op::Eq, value, meal, date)),
+                Op::Contains | Op::NotContains => values.iter().any(|value| compare(*field, Op::Contains, value, meal, date)),
+                Op::In | Op::NotIn => values.iter().any(|value| compare(*field, Op::Eq, value, meal, date)),
+                ordering => return values.iter().any(|value| compare(*field, *ordering, value, meal, date)),
+            };
+            match op {
+                Op::Ne | Op::NotContains | Op::NotIn => !positive,
+                _ => positive,
+            }
+        }
+    }
+}
+
+/// Tests one value with a positive operator
+fn compare(field: Field, op: Op, value: &Value, meal: &Meal, date: NaiveDate) -> bool {
+    let text_matches = |text: &str, value: &str| {
+        let text = text.trim().to_lowercase();
+        match op {
+            Op::Contains => text.contains(value),
+            _ => text == value,
+        }
+    };
+    match (field, value) {
+        (Field::Cook, Value::Text(value)) => text_matches(&meal.cook, value),
+        (Field::Description, Value::Text(value)) => text_matches(&meal.description, value),
+        (Field::Tag, Value::Text(value)) => meal.tags.iter().any(|tag| text_matches(tag, value)),
+        (Field::MealType, Value::MealType(meal_type)) => meal.meal_type == *meal_type,
+        (Field::Day, Value::Weekday(weekday)) => date.weekday() == *weekday,
+        (Field::Date, Value::Date(value)) => ordering_matches(op, date.cmp(value)),
+        (Field::Cooked, Value::Bool(cooked)) => meal.cooked == *cooked,
+        (Field::Rating, Value::Number(value)) => meal.rating.is_some_and(|rating| ordering_matches(op, rating.cmp(value))),
+        _ => false,
+    }
+}
+
+fn ordering_matches(op: Op, ordering: std::cmp::Ordering) -> bool {
+    use std::cmp::Ordering::*;
+    match op {
+        Op::Lt => ordering == Less,
+        Op::Le => ordering != Greater,
+        Op::Gt => ordering == Greater,
+        Op::Ge => ordering != Less,
+        _ => ordering == Equal,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Quoted(text) => write!(f, "\"{}\"", text),
+            Token::Op(op) => write!(f, "'{}'", op),
+        }
+    }
+}
+
+/// Symbols, longest first so `<=` isn't read as `<` then `=`
+const SYMBOLS: [&str; 15] = ["&&", "||", "==", "!=", "!~", "<=", ">=", "<", ">", "~", "!", "(", ")", "[", "]"];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while !rest.is_empty() {
+        if let Some(symbol) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+            tokens.push(Token::Op(symbol));
+            rest = &rest[symbol.len()..];
+        } else if let Some(stripped) = rest.strip_prefix(',') {
+            tokens.push(Token::Op(","));
+            rest = stripped;
+        } else if rest.starts_with('"') || rest.starts_with('\'') {
+            let quote = rest.chars().next().unwrap_or('"');
+            let end = rest[1..].find(quote).ok_or_else(|| format!("Missing closing {} quote.", quote))?;
+            tokens.push(Token::Quoted(rest[1..end + 1].to_string()));
+            rest = &rest[end + 2..];
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || "&|=!~<>()[],\"'".contains(c))
+                .unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("Unexpected character '{}'.", rest.chars().next().unwrap_or(' ')));
+            }
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser: `||` binds loosest, then `&&`, then `!`
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    /// How many parentheses and `!` enclose the current position
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: &'static str) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &'static str) -> Result<(), String> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(match self.peek() {
+                Some(token) => format!("Expected '{}' but found {}.", op, token),
+                None => format!("Expected '{}' at the end of the expression.", op),
+            })
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut terms = vec![self.and()?];
+        while self.eat("||") {
+            terms.push(self.and()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Expr::Or(terms) })
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut terms = vec![self.unary()?];
+        while self.eat("&&") {
+            terms.push(self.unary()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Expr::And(terms) })
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.peek() == Some(&Token::Op("!")) || self.peek() == Some(&Token::Op("(")) {
+            if self.depth == MAX_DEPTH {
+                return Err(format!("The expression nests more than {} levels deep.", MAX_DEPTH));
+            }
+            self.depth += 1;
+            let expr = if self.eat("!") {
+                self.unary().map(|inner| Expr::Not(Box::new(inner)))
+            } else {
+                self.eat("(");
+                self.or().and_then(|expr| self.expect(")").map(|_| expr))
+            };
+            self.depth -= 1;
+            return expr;
+        }
+        self.condition()
+    }
+
+    fn condition(&mut self) -> Result<Expr, String> {
+        let (name, field) = match self.next() {
+            Some(Token::Word(name)) => (name.clone(), Field::parse(&name)?),
+            Some(token) => return Err(format!("Expected a field name but found {}.", token)),
+            None => return Err("Expected a condition at the end of the expression.".to_string()),
+        };
+
+        let op_token = self.peek().cloned();
+        let op = match self.next() {
+            Some(Token::Op(op)) => match op {
+                "==" => Op::Eq,
+                "!=" => Op::Ne,
+                "~" => Op::Contains,
+                "!~" => Op::NotContains,
+                "<" => Op::Lt,
+                "<=" => Op::Le,
+                ">" => Op::Gt,
+                ">=" => Op::Ge,
+                other => return Err(format!("Expected a comparison but found '{}'.", other)),
+            },
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("in") => Op::In,
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case("not") => match self.next() {
+                Some(Token::Word(word)) if word.eq_ignore_ascii_case("in") => Op::NotIn,
+                _ => return Err("Expected 'in' after 'not'.".to_string()),
+            },
+            Some(token) => return Err(format!("Expected a comparison but found {}.", token)),
+            None => return Err("Expected a comparison at the end of the expression.".to_string()),
+        };
+
+        let allowed = match field {
+            Field::Cook | Field::Description | Field::Tag => !matches!(op, Op::Lt | Op::Le | Op::Gt | Op::Ge),
+            Field::MealType | Field::Day => matches!(op, Op::Eq | Op::Ne | Op::In | Op::NotIn),
+            Field::Date | Field::Rating => !matches!(op, Op::Contains | Op::NotContains),
+            Field::Cooked => matches!(op, Op::Eq | Op::Ne),
+        };
+        if !allowed {
+            let op = op_token.map(|token| token.to_string()).unwrap_or_default();
+            return Err(format!("The field '{}' can't be compared with {}.", name, op));
+        }
+
+        let raw_values = if matches!(op, Op::In | Op::NotIn) { self.list()? } else { vec![self.value()?] };
+        let values = raw_values.iter().map(|raw| parse_value(field, raw)).collect::<Result<Vec<Value>, String>>()?;
+        Ok(Expr::Condition { field, op, values })
+    }
+
+    fn value(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(word)) | Some(Token::Quoted(word)) => Ok(word),
+            Some(token) => Err(format!("Expected a value but found {}.", token)),
+            None => Err("Expected a value at the end of the expression.".to_string()),
+        }
+    }
+
+    fn list(&mut self) -> Result<Vec<String>, String> {
+        self.expect("[")?;
+        let mut values = vec![self.value()?];
+        while self.eat(",") {
+            values.push(self.value()?);
+        }
+        self.expect("]")?;
+        Ok(values)
+    }
+}
+
+fn parse_value(field: Field, raw: &str) -> Result<Value, String> {
+    let raw = raw.trim();
+    match field {
+        Field::Cook | Field::Description | Field::Tag => Ok(Value::Text(raw.to_lowercase())),
+        Field::MealType => match raw.to_lowercase().as_str() {
+            "breakfast" => Ok(Value::MealType(MealType::Breakfast)),
+            "lunch" => Ok(Value::MealType(MealType::Lunch)),
+            "dinner" => Ok(Value::MealType(MealType::Dinner)),
+            "snack" => Ok(Value::MealType(MealType::Snack)),
+            _ => Err(format!("Invalid meal type '{}'. Must be breakfast, lunch, dinner, or snack.", raw)),
+        },
+        Field::Day => days::parse_weekday(raw).map(Value::Weekday).ok_or_else(|| format!("Invalid day '{}'. Use a weekday such as monday.", raw)),
+        Field::Date => NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+            .map(Value::Date)
+            .map_err(|_| format!("Invalid date '{}'. Use YYYY-MM-DD.", raw)),
+        Field::Cooked => match raw.to_lowercase().as_str() {
+            "true" | "yes" => Ok(Value::Bool(true)),
+            "false" | "no" => Ok(Value::Bool(false)),
+            _ => Err(format!("Invalid value '{}' for cooked. Use true or false.", raw)),
+        },
+        Field::Rating => raw.parse().map(Value::Number).map_err(|_| format!("Invalid rating '{}'. Use a number from 1 to 5.", raw)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Day;
+
+    fn meal(meal_type: MealType, cook: &str, description: &str, tags: &[&str]) -> Meal {
+        let mut meal = Meal::new(meal_type, Day::Weekday(Weekday::Mon), cook.to_string(), description.to_string());
+        meal.tags = tags.iter().map(|t| t.to_string()).collect();
+        meal
+    }
+
+    fn monday() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 1, 2).unwrap()
+    }
+
+    #[test]
+    fn test_filter_conditions() {
+        let curry = meal(MealType::Dinner, "Alice", "Vegetable curry", &["Veggie", "spicy"]);
+        let eggs = meal(MealType::Breakfast, "Bob", "Eggs", &[]);
+        let check = |source: &str, meal: &Meal| Filter::parse(source).unwrap().matches(meal, monday());
+
+        let filter = r#"cook == "Alice" && meal_type in [dinner, lunch] && tag ~ "veg""#;
+        assert!(check(filter, &curry));
+        assert!(!check(filter, &eggs));
+
+        assert!(check("cook != alice", &eggs));
+        assert!(check("description ~ 'CURRY' || cook == bob", &eggs));
+        assert!(check("!(tag ~ veg)", &eggs));
+        assert!(check("tag !~ veg", &eggs));
+        assert!(check("type not in [dinner]", &eggs));
+        assert!(check("day == mon && date >= 2023-01-01 && date < 2023-01-03", &curry));
+        assert!(!check("day in [tue, wednesday]", &curry));
+        assert!(check("cooked == false", &curry));
+        // Meals without a rating fail every rating comparison
+        assert!(!check("rating < 3", &curry));
+        assert!(!check("rating >= 3", &curry));
+    }
+
+    #[test]
+    fn test_or_binds_looser_than_and() {
+        let eggs = meal(MealType::Breakfast, "Bob", "Eggs", &[]);
+        let check = |source: &str| Filter::parse(source).unwrap().matches(&eggs, monday());
+        assert!(check("cook == bob || cook == alice && type == dinner"));
+        assert!(!check("(cook == bob || cook == alice) && type == dinner"));
+    }
+
+    #[test]
+    fn test_filter_errors() {
+        assert!(Filter::parse("chef == Alice").unwrap_err().contains("Unknown field 'chef'"));
+        assert!(Filter::parse("meal_type == brunch").unwrap_err().contains("Invalid meal type"));
+        assert_eq!(Filter::parse("cook < Alice").unwrap_err(), "The field 'cook' can't be compared with '<'.");
+        assert!(Filter::parse("rating ~ 4").is_err());
+        assert!(Filter::parse("cook == \"Alice").unwrap_err().contains("Missing closing"));
+        assert!(Filter::parse("cook == Alice &&").is_err());
+        assert!(Filter::parse("(cook == Alice").unwrap_err().contains("Expected ')'"));
+        assert!(Filter::parse("type in [dinner lunch]").is_err());
+        assert!(Filter::parse("cook == Alice cook == Bob").unwrap_err().contains("Unexpected"));
+
+        let nested = format!("{}cook == Alice{}", "(".repeat(64), ")".repeat(64));
+        assert!(Filter::parse(&nested).is_ok());
+        let too_deep = format!("{}cook == Alice{}", "(".repeat(65), ")".repeat(65));
+        assert_eq!(Filter::parse(&too_deep).unwrap_err(), "The expression nests more than 64 levels deep.");
+        assert!(Filter::parse(&format!("{}cook == Alice", "!".repeat(100_000))).is_err());
+        let long_chain = vec!["cook == Alice"; 100_000].join(" && ");
+        assert!(Filter::parse(&long_chain).is_ok());
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut plan = MealPlan::new(monday());
+        plan.add_meal(meal(MealType::Dinner, "Alice", "Curry", &[]));
+        plan.add_meal(meal(MealType::Breakfast, "Bob", "Eggs", &[]));
+        Filter::parse("cook == alice").unwrap().retain(&mut plan);
+        assert_eq!(plan.meals().len(), 1);
+        assert_eq!(plan.meals()[0].description, "Curry");
+    }
+}
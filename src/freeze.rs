@@ -0,0 +1,135 @@
+use crate::models::{Meal, MealPlan};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// File in the storage path listing the frozen weeks
+pub const FROZEN_WEEKS_FILE: &str = "frozen_weeks.json";
+
+/// Weeks marked read-only, such as once the shopping is done, by the date each week starts
+/// and when it was frozen. Marking meals cooked, rating them and packing them is still
+/// allowed; changing what is planned, or who cooks it, is not.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FrozenWeeks {
+    pub weeks: BTreeMap<NaiveDate, DateTime<Utc>>,
+}
+
+impl FrozenWeeks {
+    /// Freezes a week, returning false if it was already frozen
+    pub fn freeze(&mut self, week_start_date: NaiveDate, at: DateTime<Utc>) -> bool {
+        if self.weeks.contains_key(&week_start_date) {
+            return false;
+        }
+        self.weeks.insert(week_start_date, at);
+        true
+    }
+
+    /// Unfreezes a week, returning false if it wasn't frozen
+    pub fn unfreeze(&mut self, week_start_date: NaiveDate) -> bool {
+        self.weeks.remove(&week_start_date).is_some()
+    }
+
+    /// When the week was frozen, if it is
+    pub fn frozen_at(&self, week_start_date: NaiveDate) -> Option<DateTime<Utc>> {
+        self.weeks.get(&week_start_date).copied()
+    }
+
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the frozen weeks from a JSON file, which is empty until a week is frozen
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+/// Describes the changes a freeze stops between two versions of a week: meals added or
+/// removed, and meals whose dish, cook, recipe, place or tags changed
+pub fn frozen_changes(before: &MealPlan, after: &MealPlan) -> Vec<String> {
+    let label = |meal: &Meal| format!("{} on {}", meal.meal_type, meal.day);
+    let mut changes = Vec::new();
+    for meal in after.meals() {
+        match before.find_meal(&meal.meal_type, &meal.day) {
+            None => changes.push(format!("{}: added {}", label(meal), meal.description)),
+            Some(old) if old.description != meal.description => {
+                changes.push(format!("{}: {} -> {}", label(meal), old.description, meal.description))
+            }
+            Some(old) if old.cook != meal.cook => changes.push(format!("{}: cook {} -> {}", label(meal), old.cook, meal.cook)),
+            Some(old) if old.recipe != meal.recipe || old.place != meal.place || old.tags != meal.tags => {
+                changes.push(format!("{}: details of {} changed", label(meal), meal.description))
+            }
+            Some(_) => {}
+        }
+    }
+    for meal in before.meals() {
+        if after.find_meal(&meal.meal_type, &meal.day).is_none() {
+            changes.push(format!("{}: removed {}", label(meal), meal.description));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, MealType};
+    use chrono::Weekday;
+
+    #[test]
+    fn test_frozen_weeks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(FROZEN_WEEKS_FILE);
+        let week = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let now = Utc::now();
+
+        let mut frozen = FrozenWeeks::load_from_json(&path).unwrap();
+        assert!(frozen.freeze(week, now));
+        assert!(!frozen.freeze(week, now));
+        frozen.save_to_json(&path).unwrap();
+
+        let mut frozen = FrozenWeeks::load_from_json(&path).unwrap();
+        assert_eq!(frozen.frozen_at(week).map(|at| at.timestamp()), Some(now.timestamp()));
+        assert!(frozen.frozen_at(week + chrono::Duration::days(7)).is_none());
+        assert!(frozen.unfreeze(week));
+        assert!(!frozen.unfreeze(week));
+    }
+
+    #[test]
+    fn test_frozen_changes() {
+        let mut before = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        before.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Chili".to_string()));
+        before.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), "Bob".to_string(), "Soup".to_string()));
+        before.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Bob".to_string(), "Salad".to_string()));
+
+        // Cooking and rating meals isn't a change to the plan
+        let mut after = before.clone();
+        after.change_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon), |meal| {
+            meal.cooked = true;
+            meal.rating = Some(4);
+        });
+        assert!(frozen_changes(&before, &after).is_empty());
+
+        after.change_meal(&MealType::Dinner, &Day::Weekday(Weekday::Tue), |meal| meal.cook = "Alice".to_string());
+        after.remove_meal(&MealType::Lunch, &Day::Weekday(Weekday::Tue));
+        after.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Wed), "Bob".to_string(), "Tacos".to_string()));
+        assert_eq!(frozen_changes(&before, &after), vec![
+            "Dinner on Tue: cook Bob -> Alice".to_string(),
+            "Dinner on Wed: added Tacos".to_string(),
+            "Lunch on Tue: removed Salad".to_string(),
+        ]);
+    }
+}
@@ -0,0 +1,424 @@
+use crate::attendance::Attendance;
+use crate::models::MealPlan;
+use crate::recipes::RecipeBook;
+use crate::units::{self, Quantity};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Store heading used for items without a preferred store
+pub const ANY_STORE: &str = "Any store";
+
+/// Represents a single item on the grocery list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroceryItem {
+    pub name: String,
+    pub purchased: bool,
+    #[serde(default)]
+    pub category: Option<String>,
+    /// What was paid for the item, recorded when it is checked off
+    #[serde(default)]
+    pub price: Option<f64>,
+}
+
+impl GroceryItem {
+    /// Creates a new item that has not been purchased yet
+    pub fn new(name: String, category: Option<String>) -> Self {
+        Self {
+            name,
+            purchased: false,
+            category,
+            price: None,
+        }
+    }
+
+    /// Looks up the preferred store for this item, matching the item name first
+    /// and then its category
+    pub fn preferred_store<'a>(&self, store_preferences: &'a HashMap<String, String>) -> Option<&'a String> {
+        let lookup = |key: &str| {
+            store_preferences
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, store)| store)
+        };
+
+        lookup(&self.name).or_else(|| self.category.as_deref().and_then(lookup))
+    }
+}
+
+/// A grocery list line as online grocery stores import it, with the amount split out
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CartItem {
+    pub name: String,
+    pub quantity: f64,
+    /// None for things bought by count, like eggs
+    pub unit: Option<String>,
+    /// The item's category, shown to the shopper
+    pub note: Option<String>,
+}
+
+/// A receipt or bank statement imported against the list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImportedReceipt {
+    /// File the receipt was imported from
+    pub source: String,
+    /// Everything on the receipt, matched to the list or not
+    pub total: f64,
+}
+
+/// Represents the grocery list for a week's meal plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroceryList {
+    pub week_start_date: NaiveDate,
+    pub items: Vec<GroceryItem>,
+    /// Receipts imported for the week, whose totals are what was actually spent
+    #[serde(default)]
+    pub receipts: Vec<ImportedReceipt>,
+}
+
+impl GroceryList {
+    /// Creates a new empty grocery list
+    pub fn new(week_start_date: NaiveDate) -> Self {
+        Self {
+            week_start_date,
+            items: Vec::new(),
+            receipts: Vec::new(),
+        }
+    }
+
+    /// Adds an item to the list, returning false if it is already present
+    pub fn add_item(&mut self, name: &str, category: Option<String>) -> bool {
+        self.add(name, category).is_some()
+    }
+
+    /// Adds an item to the list, returning the line it is on, or None if it is already
+    /// present. An amount of an ingredient that is listed and not yet bought goes on that
+    /// line, so "2 tbsp butter" and "30 g butter" become "4.23 tbsp butter".
+    pub fn add(&mut self, name: &str, category: Option<String>) -> Option<&GroceryItem> {
+        let combined = Quantity::parse(name).and_then(|quantity| {
+            self.items.iter().enumerate().filter(|(_, item)| !item.purchased).find_map(|(index, item)| {
+                let listed = Quantity::parse(&item.name)?;
+                Some((index, listed.combine(&quantity)?))
+            })
+        });
+        match combined {
+            Some((index, quantity)) => {
+                let item = &mut self.items[index];
+                item.name = quantity.to_string();
+                item.category = item.category.take().or(category);
+                Some(item)
+            }
+            None if self.find_item(name).is_some() => None,
+            None => {
+                self.items.push(GroceryItem::new(name.to_string(), category));
+                self.items.last()
+            }
+        }
+    }
+
+    /// Finds an item by name, ignoring case
+    pub fn find_item(&self, name: &str) -> Option<&GroceryItem> {
+        self.items.iter().find(|i| i.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Marks an item as purchased or not, returning false if the item is not on the list
+    pub fn set_purchased(&mut self, name: &str, purchased: bool) -> bool {
+        match self.items.iter_mut().find(|i| i.name.eq_ignore_ascii_case(name)) {
+            Some(item) => {
+                item.purchased = purchased;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records what was paid for an item, returning false if the item is not on the list
+    pub fn set_price(&mut self, name: &str, price: f64) -> bool {
+        match self.items.iter_mut().find(|i| i.name.eq_ignore_ascii_case(name)) {
+            Some(item) => {
+                item.price = Some(price);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// What the imported receipts add up to, or None when none were imported
+    pub fn receipt_total(&self) -> Option<f64> {
+        (!self.receipts.is_empty()).then(|| self.receipts.iter().map(|r| r.total).sum())
+    }
+
+    /// Returns the total paid for purchased items and how many purchased items have no price
+    pub fn spending(&self) -> (f64, usize) {
+        let purchased = self.items.iter().filter(|i| i.purchased);
+        purchased.fold((0.0, 0), |(total, unpriced), item| match item.price {
+            Some(price) => (total + price, unpriced),
+            None => (total, unpriced + 1),
+        })
+    }
+
+    /// Returns the items that still need to be purchased
+    pub fn remaining(&self) -> Vec<&GroceryItem> {
+        self.items.iter().filter(|i| !i.purchased).collect()
+    }
+
+    /// The items still to buy as online cart lines. Items without an amount are bought once.
+    pub fn cart_items(&self) -> Vec<CartItem> {
+        self.remaining()
+            .into_iter()
+            .map(|item| {
+                let (name, quantity, unit) = match Quantity::parse(&item.name) {
+                    Some(quantity) => (quantity.ingredient, quantity.amount, quantity.unit.map(|u| u.symbol.to_string())),
+                    None => (item.name.clone(), 1.0, None),
+                };
+                CartItem { name, quantity, unit, note: item.category.clone() }
+            })
+            .collect()
+    }
+
+    /// Groups items by their preferred store, with unmapped items under `ANY_STORE`
+    pub fn group_by_store(&self, store_preferences: &HashMap<String, String>) -> BTreeMap<String, Vec<&GroceryItem>> {
+        let mut by_store: BTreeMap<String, Vec<&GroceryItem>> = BTreeMap::new();
+        for item in &self.items {
+            let store = item
+                .preferred_store(store_preferences)
+                .cloned()
+                .unwrap_or_else(|| ANY_STORE.to_string());
+            by_store.entry(store).or_default().push(item);
+        }
+        by_store
+    }
+
+    /// Saves the grocery list to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads a grocery list from a JSON file
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let list: GroceryList = serde_json::from_str(&contents)?;
+        Ok(list)
+    }
+}
+
+/// The dates shopping today has to cover: today up to the day before the next shopping
+/// day. None when the household has no shopping days.
+pub fn shopping_window(today: NaiveDate, shopping_days: &[Weekday]) -> Option<(NaiveDate, NaiveDate)> {
+    let next = (1..=7)
+        .map(|n| today + Duration::days(n))
+        .find(|date| shopping_days.contains(&date.weekday()))?;
+    Some((today, next - Duration::days(1)))
+}
+
+/// What the meals planned from `from` to `to` need that isn't on the grocery list, going by
+/// their recipes, with amounts of the same ingredient added together. Meals eaten out need
+/// nothing, and the amounts of meals some of the household is away for are cut to the
+/// share that's eating.
+pub fn ingredients_for_meals(grocery_list: &GroceryList, meal_plan: &MealPlan, book: &RecipeBook, attendance: &Attendance, from: NaiveDate, to: NaiveDate) -> Vec<String> {
+    let listed: Vec<String> = grocery_list.items.iter().map(|item| units::ingredient_name(&item.name)).collect();
+    let mut needed = GroceryList::new(meal_plan.week_start_date);
+    let meals = meal_plan
+        .meals()
+        .iter()
+        .filter(|meal| meal.place.is_none() && (from..=to).contains(&meal_plan.date_of(&meal.day)));
+    for (meal, recipe) in meals.filter_map(|meal| book.for_meal(meal).map(|recipe| (meal, recipe))) {
+        let share = attendance.share(meal_plan, meal);
+        if share == 0.0 {
+            continue;
+        }
+        let ingredients = if share < 1.0 { recipe.scaled_ingredients(share) } else { recipe.ingredients.clone() };
+        for ingredient in &ingredients {
+            if !listed.contains(&units::ingredient_name(ingredient)) {
+                needed.add_item(ingredient.trim(), None);
+            }
+        }
+    }
+    needed.items.into_iter().map(|item| item.name).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_add_combines_amounts() {
+        let mut list = GroceryList::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        list.add_item("2 tbsp butter", None);
+        list.add_item("2 cups flour", None);
+        assert_eq!(list.add("30 g Butter", Some("dairy".to_string())).unwrap().name, "4.23 tbsp butter");
+        assert_eq!(list.items[0].category.as_deref(), Some("dairy"));
+        assert_eq!(list.items.len(), 2);
+        assert_eq!(list.add("2 cups flour", None).unwrap().name, "4 cups flour");
+
+        // Amounts that can't be converted, or of bought items, get their own line
+        list.add_item("1 lb spinach", None);
+        assert_eq!(list.add("2 cups spinach", None).unwrap().name, "2 cups spinach");
+        list.set_purchased("4 cups flour", true);
+        list.add_item("1 cup flour", None);
+        assert_eq!(list.items.len(), 5);
+    }
+
+    #[test]
+    fn test_add_and_check_items() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut list = GroceryList::new(week_start);
+
+        assert!(list.add_item("Milk", None));
+        assert!(list.add_item("Eggs", None));
+        // Duplicates are ignored regardless of case
+        assert!(!list.add_item("milk", None));
+        assert_eq!(list.items.len(), 2);
+
+        assert!(list.set_purchased("MILK", true));
+        assert!(!list.set_purchased("Bread", true));
+
+        let remaining = list.remaining();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].name, "Eggs");
+
+        assert!(list.set_purchased("milk", false));
+        assert_eq!(list.remaining().len(), 2);
+    }
+
+    #[test]
+    fn test_spending() {
+        let mut list = GroceryList::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        list.add_item("Milk", None);
+        list.add_item("Bread", None);
+        list.add_item("Eggs", None);
+        list.set_purchased("Milk", true);
+        list.set_price("Milk", 2.5);
+        list.set_purchased("Bread", true);
+        list.set_price("Eggs", 4.0);
+
+        // Eggs have a price but haven't been bought yet
+        assert_eq!(list.spending(), (2.5, 1));
+        assert!(!list.set_price("Butter", 3.0));
+    }
+
+    #[test]
+    fn test_cart_items() {
+        let mut list = GroceryList::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        list.add_item("1 1/2 lb ground beef", Some("meat".to_string()));
+        list.add_item("6 eggs", None);
+        list.add_item("Paper towels", None);
+        list.add_item("Milk", None);
+        list.set_purchased("Milk", true);
+
+        let items = list.cart_items();
+        assert_eq!(items.len(), 3);
+        assert_eq!(
+            items[0],
+            CartItem { name: "ground beef".to_string(), quantity: 1.5, unit: Some("lb".to_string()), note: Some("meat".to_string()) }
+        );
+        assert_eq!((items[1].quantity, items[1].unit.as_deref()), (6.0, None));
+        assert_eq!((items[2].name.as_str(), items[2].quantity), ("Paper towels", 1.0));
+    }
+
+    #[test]
+    fn test_shopping_window() {
+        // Wednesday, with shopping on Wednesdays and Saturdays
+        let wednesday = NaiveDate::from_ymd_opt(2023, 1, 4).unwrap();
+        let days = [Weekday::Sat, Weekday::Wed];
+        assert_eq!(shopping_window(wednesday, &days), Some((wednesday, NaiveDate::from_ymd_opt(2023, 1, 6).unwrap())));
+        let saturday = NaiveDate::from_ymd_opt(2023, 1, 7).unwrap();
+        assert_eq!(shopping_window(saturday, &days), Some((saturday, NaiveDate::from_ymd_opt(2023, 1, 10).unwrap())));
+        // A single shopping day covers a full week
+        assert_eq!(shopping_window(wednesday, &[Weekday::Wed]), Some((wednesday, NaiveDate::from_ymd_opt(2023, 1, 10).unwrap())));
+        assert_eq!(shopping_window(wednesday, &[]), None);
+    }
+
+    #[test]
+    fn test_ingredients_for_meals() {
+        use crate::models::{Day, Meal, MealType};
+        use crate::recipes::Recipe;
+
+        let recipe = |name: &str, ingredients: &[&str]| Recipe {
+            name: name.to_string(),
+            tags: Vec::new(),
+            ingredients: ingredients.iter().map(|i| i.to_string()).collect(),
+            link: None,
+            servings: None,
+            photo: None,
+            steps: Vec::new(),
+            equipment: Vec::new(),
+        };
+        let mut book = RecipeBook::default();
+        book.add(recipe("Pancakes", &["2 cups flour", "2 tbsp butter", "milk"]));
+        book.add(recipe("Shortbread", &["1 cup flour", "100 g butter"]));
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let meal = |day: Weekday, description: &str| Meal::new(MealType::Dinner, Day::Weekday(day), "Al".to_string(), description.to_string());
+        plan.add_meal(meal(Weekday::Mon, "Pancakes"));
+        plan.add_meal(meal(Weekday::Tue, "Shortbread"));
+        plan.add_meal(meal(Weekday::Fri, "Shortbread"));
+        let mut list = GroceryList::new(plan.week_start_date);
+        list.add_item("Milk", None);
+
+        let monday = plan.week_start_date;
+        let tuesday = NaiveDate::from_ymd_opt(2023, 1, 3).unwrap();
+        let everyone = Attendance::default();
+        assert_eq!(ingredients_for_meals(&list, &plan, &book, &everyone, monday, tuesday), vec!["3 cups flour", "9.42 tbsp butter"]);
+        let sunday = NaiveDate::from_ymd_opt(2023, 1, 8).unwrap();
+        assert_eq!(ingredients_for_meals(&list, &plan, &book, &everyone, tuesday, sunday), vec!["2 cups flour", "200 g butter"]);
+
+        // Half the household is away Tuesday, and nobody eats Friday's dinner
+        let mut attendance = Attendance::default();
+        attendance.set_member("Al", &[]).unwrap();
+        attendance.set_member("Bo", &[]).unwrap();
+        attendance.set_away("Bo", tuesday, None).unwrap();
+        let friday = NaiveDate::from_ymd_opt(2023, 1, 6).unwrap();
+        attendance.set_away("Al", friday, Some(MealType::Dinner)).unwrap();
+        attendance.set_away("Bo", friday, Some(MealType::Dinner)).unwrap();
+        assert_eq!(ingredients_for_meals(&list, &plan, &book, &attendance, tuesday, sunday), vec!["8 tbsp flour", "50 g butter"]);
+    }
+
+    #[test]
+    fn test_grocery_list_json_roundtrip() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("grocery_list.json");
+
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut list = GroceryList::new(week_start);
+        list.add_item("Milk", Some("dairy".to_string()));
+        list.set_purchased("Milk", true);
+
+        list.save_to_json(&file_path).unwrap();
+        let loaded = GroceryList::load_from_json(&file_path).unwrap();
+
+        assert_eq!(loaded.week_start_date, week_start);
+        assert_eq!(loaded.items.len(), 1);
+        assert!(loaded.items[0].purchased);
+        assert_eq!(loaded.items[0].category.as_deref(), Some("dairy"));
+    }
+
+    #[test]
+    fn test_group_by_store() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut list = GroceryList::new(week_start);
+        list.add_item("Paper towels", None);
+        list.add_item("Milk", Some("dairy".to_string()));
+        list.add_item("Basil", Some("produce".to_string()));
+        list.add_item("Batteries", None);
+
+        let mut store_preferences = HashMap::new();
+        store_preferences.insert("paper towels".to_string(), "Costco".to_string());
+        store_preferences.insert("Dairy".to_string(), "Costco".to_string());
+        store_preferences.insert("produce".to_string(), "Corner Shop".to_string());
+
+        let by_store = list.group_by_store(&store_preferences);
+        assert_eq!(by_store.len(), 3);
+        assert_eq!(by_store["Costco"].len(), 2);
+        assert_eq!(by_store["Corner Shop"][0].name, "Basil");
+        assert_eq!(by_store[ANY_STORE][0].name, "Batteries");
+    }
+}
@@ -0,0 +1,355 @@
+use crate::archive::{Archive, ArchiveIndex};
+use crate::models::{MealPlan, MealType};
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Prefix the batch planner puts on leftover meals, which aren't dishes in their own right
+pub const LEFTOVERS_PREFIX: &str = "Leftovers:";
+
+/// A meal that was planned on a specific date
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub week_start_date: NaiveDate,
+    pub date: NaiveDate,
+    pub meal_type: MealType,
+    pub cook: String,
+    pub description: String,
+}
+
+/// A dish with how often and when it was last planned
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DishSummary {
+    pub description: String,
+    pub last_planned: NaiveDate,
+    pub times: usize,
+}
+
+/// How often a dish was served over the weeks in a variety report
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DishCount {
+    pub description: String,
+    pub times: usize,
+    /// Most consecutive weeks the dish was served in
+    pub longest_streak_weeks: usize,
+    pub last_planned: NaiveDate,
+}
+
+/// How repetitive the dinners over a range of weeks were
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VarietyReport {
+    pub first_week: NaiveDate,
+    pub weeks: usize,
+    pub total_dinners: usize,
+    pub unique_dishes: usize,
+    /// Unique dishes divided by total dinners: 1.0 means nothing was repeated
+    pub variety_score: f64,
+    /// Every dish served, most common first
+    pub dishes: Vec<DishCount>,
+}
+
+/// Every meal from the archived weeks and the current plan, oldest first
+#[derive(Debug, Default)]
+pub struct MealHistory {
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl MealHistory {
+    /// Builds the history from the given plans
+    pub fn from_plans<'a, I>(plans: I) -> Self
+    where
+        I: IntoIterator<Item = &'a MealPlan>,
+    {
+        let mut entries: Vec<HistoryEntry> = plans
+            .into_iter()
+            .flat_map(|plan| {
+                plan.meals().iter().map(move |meal| HistoryEntry {
+                    week_start_date: plan.week_start_date,
+                    date: plan.date_of(&meal.day),
+                    meal_type: meal.meal_type.clone(),
+                    cook: meal.cook.clone(),
+                    description: meal.description.clone(),
+                })
+            })
+            .collect();
+        entries.sort_by_key(|e| e.date);
+        Self { entries }
+    }
+
+    /// Loads the archived weeks starting on or after `first_week`, plus the current plan.
+    /// Older weeks are never read.
+    pub fn load_since(archive: &Archive, current: &MealPlan, first_week: NaiveDate) -> std::io::Result<Self> {
+        Ok(Self::from_plans(&load_plans_since(archive, current, first_week)?))
+    }
+
+    /// Returns each dish with the last time it was planned, ignoring leftovers.
+    /// Dishes are matched by description, ignoring case and surrounding whitespace.
+    pub fn last_eaten(&self) -> Vec<DishSummary> {
+        combine_dishes(self.entries.iter().map(|entry| DishSummary {
+            description: entry.description.clone(),
+            last_planned: entry.date,
+            times: 1,
+        }))
+    }
+
+    /// Reports on the variety of dinners over `weeks` weeks ending with the week starting `last_week`
+    pub fn variety(&self, last_week: NaiveDate, weeks: usize) -> VarietyReport {
+        let first_week = first_week_of(last_week, weeks).unwrap_or(NaiveDate::MIN);
+        let dinners: Vec<&HistoryEntry> = self
+            .entries
+            .iter()
+            .filter(|e| e.meal_type == MealType::Dinner)
+            .filter(|e| e.week_start_date >= first_week && e.week_start_date <= last_week)
+            .filter(|e| !e.description.trim().is_empty() && !e.description.trim().starts_with(LEFTOVERS_PREFIX))
+            .collect();
+
+        let mut by_dish: HashMap<String, (DishCount, BTreeSet<NaiveDate>)> = HashMap::new();
+        for dinner in &dinners {
+            let description = dinner.description.trim();
+            let (count, weeks) = by_dish.entry(dish_key(description)).or_insert_with(|| {
+                (
+                    DishCount {
+                        description: description.to_string(),
+                        times: 0,
+                        longest_streak_weeks: 0,
+                        last_planned: dinner.date,
+                    },
+                    BTreeSet::new(),
+                )
+            });
+            count.times += 1;
+            if dinner.date >= count.last_planned {
+                count.last_planned = dinner.date;
+                count.description = description.to_string();
+            }
+            weeks.insert(dinner.week_start_date);
+        }
+
+        let mut dishes: Vec<DishCount> = by_dish
+            .into_values()
+            .map(|(mut count, weeks)| {
+                count.longest_streak_weeks = longest_weekly_streak(&weeks);
+                count
+            })
+            .collect();
+        dishes.sort_by(|a, b| b.times.cmp(&a.times).then_with(|| a.description.cmp(&b.description)));
+
+        let total_dinners = dinners.len();
+        let unique_dishes = dishes.len();
+        VarietyReport {
+            first_week,
+            weeks,
+            total_dinners,
+            unique_dishes,
+            variety_score: if total_dinners == 0 {
+                0.0
+            } else {
+                unique_dishes as f64 / total_dinners as f64
+            },
+            dishes,
+        }
+    }
+}
+
+/// Loads the plans of the archived weeks starting on or after `first_week`, plus the
+/// current plan, oldest first
+pub fn load_plans_since(archive: &Archive, current: &MealPlan, first_week: NaiveDate) -> std::io::Result<Vec<MealPlan>> {
+    let mut plans: Vec<MealPlan> = archive
+        .load_weeks(first_week..)?
+        .into_iter()
+        .filter(|plan| plan.week_start_date != current.week_start_date)
+        .collect();
+    if current.week_start_date >= first_week {
+        plans.push(current.clone());
+    }
+    Ok(plans)
+}
+
+/// Returns the start of the first of `weeks` weeks ending with the week starting `last_week`,
+/// or None if that's further back than dates go
+pub fn first_week_of(last_week: NaiveDate, weeks: usize) -> Option<NaiveDate> {
+    let back = i64::try_from(weeks.saturating_sub(1)).ok().and_then(Duration::try_weeks)?;
+    last_week.checked_sub_signed(back)
+}
+
+/// Summarizes the dishes in one week's plan, for the archive index
+pub fn summarize_dishes(meal_plan: &MealPlan) -> Vec<DishSummary> {
+    MealHistory::from_plans([meal_plan]).last_eaten()
+}
+
+/// Returns the dishes that haven't been planned since `cutoff`, longest ago first.
+/// Archived weeks are read from the archive index rather than loaded.
+pub fn stale_dishes(index: &ArchiveIndex, current: &MealPlan, cutoff: NaiveDate) -> Vec<DishSummary> {
+    let archived = index
+        .weeks
+        .iter()
+        .filter(|(week, _)| **week != current.week_start_date)
+        .flat_map(|(_, summary)| summary.dishes.iter().cloned());
+
+    combine_dishes(archived.chain(summarize_dishes(current)))
+        .into_iter()
+        .filter(|dish| dish.last_planned < cutoff)
+        .collect()
+}
+
+/// Returns every dish planned in the archived weeks or the current plan, most often planned first
+pub fn known_dishes(index: &ArchiveIndex, current: &MealPlan) -> Vec<DishSummary> {
+    let archived = index
+        .weeks
+        .iter()
+        .filter(|(week, _)| **week != current.week_start_date)
+        .flat_map(|(_, summary)| summary.dishes.iter().cloned());
+
+    let mut dishes = combine_dishes(archived.chain(summarize_dishes(current)));
+    dishes.sort_by(|a, b| b.times.cmp(&a.times).then_with(|| b.last_planned.cmp(&a.last_planned)));
+    dishes
+}
+
+/// Merges summaries of the same dish, keeping the latest spelling, skipping leftovers,
+/// and sorting the result by when each dish was last planned
+fn combine_dishes<I: IntoIterator<Item = DishSummary>>(summaries: I) -> Vec<DishSummary> {
+    let mut dishes: HashMap<String, DishSummary> = HashMap::new();
+    for summary in summaries {
+        let description = summary.description.trim();
+        if description.is_empty() || description.starts_with(LEFTOVERS_PREFIX) {
+            continue;
+        }
+
+        let dish = dishes.entry(dish_key(description)).or_insert_with(|| DishSummary {
+            description: description.to_string(),
+            last_planned: summary.last_planned,
+            times: 0,
+        });
+        dish.times += summary.times;
+        if summary.last_planned >= dish.last_planned {
+            dish.last_planned = summary.last_planned;
+            dish.description = description.to_string();
+        }
+    }
+
+    let mut dishes: Vec<DishSummary> = dishes.into_values().collect();
+    dishes.sort_by(|a, b| a.last_planned.cmp(&b.last_planned).then_with(|| a.description.cmp(&b.description)));
+    dishes
+}
+
+/// Counts the longest run of weeks, each starting 7 days after the previous one
+fn longest_weekly_streak(weeks: &BTreeSet<NaiveDate>) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for week in weeks {
+        current = match previous {
+            Some(previous) if *week - previous == Duration::weeks(1) => current + 1,
+            _ => 1,
+        };
+        longest = longest.max(current);
+        previous = Some(*week);
+    }
+    longest
+}
+
+/// Normalizes a description so the same dish matches however it was typed
+pub fn dish_key(description: &str) -> String {
+    description.trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, Meal};
+    use chrono::Weekday;
+    use tempfile::tempdir;
+
+    fn plan(week_start: NaiveDate, dinners: &[(Weekday, &str)]) -> MealPlan {
+        let mut plan = MealPlan::new(week_start);
+        for (day, description) in dinners {
+            plan.add_meal(Meal::new(
+                MealType::Dinner,
+                Day::Weekday(*day),
+                "Alice".to_string(),
+                description.to_string(),
+            ));
+        }
+        plan
+    }
+
+    #[test]
+    fn test_stale_dishes() {
+        let temp_dir = tempdir().unwrap();
+        let archive = Archive::new(temp_dir.path());
+
+        // Weeks starting on Mondays
+        let first = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let second = NaiveDate::from_ymd_opt(2023, 1, 9).unwrap();
+        let current = NaiveDate::from_ymd_opt(2023, 3, 6).unwrap();
+        archive.save_week(&plan(first, &[(Weekday::Mon, "Chili"), (Weekday::Tue, "Lasagna")])).unwrap();
+        archive
+            .save_week(&plan(second, &[(Weekday::Wed, "chili "), (Weekday::Thu, "Leftovers: Chili")]))
+            .unwrap();
+        let current = plan(current, &[(Weekday::Mon, "Tacos")]);
+
+        let history = MealHistory::load_since(&archive, &current, first).unwrap();
+        assert_eq!(history.entries.len(), 5);
+        assert_eq!(MealHistory::load_since(&archive, &current, second).unwrap().entries.len(), 3);
+
+        let last_eaten = history.last_eaten();
+        assert_eq!(last_eaten.len(), 3);
+        assert_eq!(last_eaten[0], DishSummary {
+            description: "Lasagna".to_string(),
+            last_planned: NaiveDate::from_ymd_opt(2023, 1, 3).unwrap(),
+            times: 1,
+        });
+        assert_eq!(last_eaten[1].description, "chili");
+        assert_eq!(last_eaten[1].last_planned, NaiveDate::from_ymd_opt(2023, 1, 11).unwrap());
+        assert_eq!(last_eaten[1].times, 2);
+
+        // The index gives the same answer without loading the weeks
+        let index = archive.index().unwrap();
+        let stale = stale_dishes(&index, &current, NaiveDate::from_ymd_opt(2023, 1, 5).unwrap());
+        assert_eq!(stale, last_eaten[..1].to_vec());
+        let stale = stale_dishes(&index, &current, NaiveDate::from_ymd_opt(2023, 2, 1).unwrap());
+        assert_eq!(stale, last_eaten[..2].to_vec());
+
+        let known = known_dishes(&index, &current);
+        assert_eq!(known.len(), 3);
+        assert_eq!((known[0].description.as_str(), known[0].times), ("chili", 2));
+        assert_eq!(known[1].description, "Tacos");
+    }
+
+    #[test]
+    fn test_variety_report() {
+        let week = |n: i64| NaiveDate::from_ymd_opt(2023, 1, 2).unwrap() + Duration::weeks(n);
+        let mut lunch = MealPlan::new(week(1));
+        lunch.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Tacos".to_string()));
+
+        let plans = vec![
+            plan(week(0), &[(Weekday::Mon, "Pizza")]),
+            plan(week(1), &[(Weekday::Mon, "Tacos"), (Weekday::Tue, "Pizza")]),
+            plan(week(2), &[(Weekday::Mon, "tacos"), (Weekday::Wed, "Leftovers: Tacos")]),
+            plan(week(3), &[(Weekday::Mon, "Soup"), (Weekday::Fri, "Tacos")]),
+            lunch,
+        ];
+        let history = MealHistory::from_plans(&plans);
+
+        // The first week falls outside the last three weeks
+        let report = history.variety(week(3), 3);
+        assert_eq!(report.first_week, week(1));
+        assert_eq!(report.total_dinners, 5);
+        assert_eq!(report.unique_dishes, 3);
+        assert!((report.variety_score - 0.6).abs() < f64::EPSILON);
+        assert_eq!(report.dishes[0].description, "Tacos");
+        assert_eq!(report.dishes[0].times, 3);
+        assert_eq!(report.dishes[0].longest_streak_weeks, 3);
+        assert_eq!(report.dishes[1].description, "Pizza");
+        assert_eq!(report.dishes[1].longest_streak_weeks, 1);
+
+        let report = history.variety(week(3), 4);
+        assert_eq!(report.dishes[0].description, "Tacos");
+        assert_eq!(report.dishes[1].longest_streak_weeks, 2);
+        assert_eq!(history.variety(week(10), 2).variety_score, 0.0);
+
+        assert_eq!(first_week_of(week(3), 1), Some(week(3)));
+        assert_eq!(first_week_of(week(3), usize::MAX), None);
+        assert_eq!(history.variety(week(3), usize::MAX).total_dinners, 6);
+    }
+}
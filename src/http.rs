@@ -0,0 +1,420 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// File in the storage path holding pushes waiting for the network to come back
+pub const OUTBOX_FILE: &str = "outbox.json";
+
+/// Longest wait between two tries of a request, whatever the backoff or server asks for
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// How the integrations talk to the network, from the configuration's `http` section
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpSettings {
+    /// Seconds to wait for a server before giving up on a request
+    pub timeout_secs: u64,
+    /// How many more times a request is tried after failing for a reason that may pass,
+    /// such as the network being down or the server being busy
+    pub retries: u32,
+    /// Milliseconds to wait before the first retry, doubling for each one after
+    pub backoff_ms: u64,
+    /// Fewest milliseconds between two requests to the same server
+    pub min_interval_ms: u64,
+    /// Keep notifications and posts that couldn't be delivered, and send them later
+    pub queue_offline: bool,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self { timeout_secs: 15, retries: 2, backoff_ms: 500, min_interval_ms: 250, queue_offline: true }
+    }
+}
+
+/// What a request sends
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "content", rename_all = "snake_case")]
+pub enum Body {
+    #[default]
+    Empty,
+    Text(String),
+    Json(serde_json::Value),
+    Form(Vec<(String, String)>),
+}
+
+/// A request to a web service, which can be kept in the outbox and sent again later
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub query: Vec<(String, String)>,
+    #[serde(default)]
+    pub body: Body,
+    /// Added to the timeout, for requests the server holds open such as long polls
+    #[serde(skip)]
+    pub extra_wait: Duration,
+    /// Hands back redirects rather than following them, for callers that check each address
+    #[serde(skip)]
+    pub no_redirects: bool,
+    /// Connects only to these addresses instead of looking the host up, for callers that
+    /// checked where the host points and mustn't have it point elsewhere by the time it's sent
+    #[serde(skip)]
+    pub addresses: Vec<SocketAddr>,
+}
+
+impl Request {
+    pub fn new(method: &str, url: impl Into<String>) -> Self {
+        Self { method: method.to_string(), url: url.into(), headers: Vec::new(), query: Vec::new(), body: Body::Empty, extra_wait: Duration::ZERO, no_redirects: false, addresses: Vec::new() }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn query(mut self, name: &str, value: &str) -> Self {
+        self.query.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body(mut self, body: Body) -> Self {
+        self.body = body;
+        self
+    }
+
+    pub fn extra_wait(mut self, extra_wait: Duration) -> Self {
+        self.extra_wait = extra_wait;
+        self
+    }
+
+    pub fn no_redirects(mut self) -> Self {
+        self.no_redirects = true;
+        self
+    }
+
+    pub fn connect_to(mut self, addresses: Vec<SocketAddr>) -> Self {
+        self.addresses = addresses;
+        self
+    }
+
+    /// The server the request goes to, such as ntfy.sh or 127.0.0.1:8080
+    pub fn host(&self) -> &str {
+        let rest = self.url.split_once("://").map_or(self.url.as_str(), |(_, rest)| rest);
+        rest.split(['/', '?']).next().unwrap_or(rest)
+    }
+}
+
+/// Whether a failed request may go through if tried again: the server couldn't be
+/// reached, asked us to slow down, or had a passing problem of its own
+pub fn is_transient(error: &ureq::Error) -> bool {
+    match error {
+        ureq::Error::Transport(_) => true,
+        ureq::Error::Status(code, _) => matches!(code, 408 | 429 | 500 | 502 | 503 | 504),
+    }
+}
+
+// When the last request to each server was sent, shared by every client in the process
+static LAST_REQUEST: LazyLock<Mutex<HashMap<String, Instant>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Sends requests with the configured timeout, spacing and retries
+#[derive(Debug, Clone, Default)]
+pub struct Client {
+    pub settings: HttpSettings,
+}
+
+impl Client {
+    pub fn new(settings: HttpSettings) -> Self {
+        Self { settings }
+    }
+
+    /// Sends a request, trying again with a growing wait while it fails for a reason that
+    /// may pass. A server's Retry-After is waited out when it's longer.
+    pub fn send(&self, request: &Request) -> Result<ureq::Response, Box<ureq::Error>> {
+        let mut attempt = 0;
+        loop {
+            match self.send_once(request) {
+                Err(error) if attempt < self.settings.retries && is_transient(&error) => {
+                    let backoff = Duration::from_millis(self.settings.backoff_ms.saturating_mul(1 << attempt.min(16)));
+                    let asked = match error.as_ref() {
+                        ureq::Error::Status(_, response) => response.header("Retry-After").and_then(|secs| secs.trim().parse().ok()),
+                        ureq::Error::Transport(_) => None,
+                    };
+                    thread::sleep(backoff.max(Duration::from_secs(asked.unwrap_or(0))).min(MAX_BACKOFF));
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn send_once(&self, request: &Request) -> Result<ureq::Response, Box<ureq::Error>> {
+        self.wait_turn(request.host());
+        let timeout = Duration::from_secs(self.settings.timeout_secs.max(1)) + request.extra_wait;
+        let mut agent = ureq::AgentBuilder::new();
+        if request.no_redirects {
+            agent = agent.redirects(0);
+        }
+        if !request.addresses.is_empty() {
+            let addresses = request.addresses.clone();
+            agent = agent.resolver(move |_: &str| Ok::<_, io::Error>(addresses.clone()));
+        }
+        let agent = agent.build();
+        let mut call = agent.request(&request.method, &request.url).timeout(timeout);
+        for (name, value) in &request.headers {
+            call = call.set(name, value);
+        }
+        for (name, value) in &request.query {
+            call = call.query(name, value);
+        }
+        let result = match &request.body {
+            Body::Empty => call.call(),
+            Body::Text(text) => call.send_string(text),
+            Body::Json(json) => call.send_json(json),
+            Body::Form(fields) => {
+                let fields: Vec<(&str, &str)> = fields.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+                call.send_form(&fields)
+            }
+        };
+        result.map_err(Box::new)
+    }
+
+    /// Waits until the server hasn't been sent anything for the minimum interval
+    fn wait_turn(&self, host: &str) {
+        let interval = Duration::from_millis(self.settings.min_interval_ms);
+        let wait = {
+            let mut last = LAST_REQUEST.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let now = Instant::now();
+            let next = last.get(host).map_or(now, |sent| (*sent + interval).max(now));
+            last.insert(host.to_string(), next);
+            next - now
+        };
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
+
+// The settings and outbox the integrations use, set once the configuration is loaded
+static CONFIGURED: RwLock<Option<(HttpSettings, Option<PathBuf>)>> = RwLock::new(None);
+
+/// Sets how requests are sent from now on, and where undelivered pushes are kept
+pub fn configure(settings: HttpSettings, outbox: Option<PathBuf>) {
+    *CONFIGURED.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some((settings, outbox));
+}
+
+fn configured() -> (HttpSettings, Option<PathBuf>) {
+    CONFIGURED.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone().unwrap_or_default()
+}
+
+/// A client with the configured settings, or the defaults before any are set
+pub fn client() -> Client {
+    Client::new(configured().0)
+}
+
+/// Sends a request with the configured settings
+pub fn send(request: &Request) -> Result<ureq::Response, Box<ureq::Error>> {
+    client().send(request)
+}
+
+/// What became of a push
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    Sent,
+    /// The server couldn't be reached, so the push waits in the outbox
+    Queued,
+}
+
+/// Sends a notification or post, such as a reminder. One that still fails for a reason
+/// that may pass is put in the outbox, when there is one, rather than lost.
+pub fn push(request: Request, description: &str) -> Result<Delivery, Box<ureq::Error>> {
+    let (settings, outbox) = configured();
+    let error = match Client::new(settings.clone()).send(&request) {
+        Ok(_) => return Ok(Delivery::Sent),
+        Err(error) => error,
+    };
+    match outbox.filter(|_| settings.queue_offline && is_transient(&error)) {
+        Some(path) => {
+            // If even the outbox can't be written, the push fails as it would have anyway
+            let queued = Outbox::load_from_json(&path).and_then(|mut outbox| {
+                outbox.queue(request, description, Utc::now());
+                outbox.save_to_json(&path)
+            });
+            queued.map(|_| Delivery::Queued).map_err(|_| error)
+        }
+        None => Err(error),
+    }
+}
+
+/// A push waiting for the network to come back
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedPush {
+    pub request: Request,
+    /// What the push is, for listing, e.g. "ntfy topic dinner on https://ntfy.sh"
+    pub description: String,
+    pub queued_at: DateTime<Utc>,
+    /// Times sending it from the outbox has failed
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+/// What flushing the outbox did
+#[derive(Debug, Default, PartialEq)]
+pub struct FlushSummary {
+    pub sent: usize,
+    /// Pushes the server turned down for good, which were dropped, with why
+    pub rejected: Vec<String>,
+    pub remaining: usize,
+}
+
+/// Pushes waiting to be sent, oldest first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Outbox {
+    pub pending: Vec<QueuedPush>,
+}
+
+impl Outbox {
+    pub fn queue(&mut self, request: Request, description: &str, at: DateTime<Utc>) {
+        self.pending.push(QueuedPush { request, description: description.to_string(), queued_at: at, attempts: 0 });
+    }
+
+    /// Sends the waiting pushes in order, once each. Sending stops at the first that fails
+    /// for a reason that may pass, as the rest would most likely fail the same way.
+    pub fn flush(&mut self, client: &Client) -> FlushSummary {
+        let mut summary = FlushSummary::default();
+        let once = Client::new(HttpSettings { retries: 0, ..client.settings.clone() });
+        while let Some(push) = self.pending.first_mut() {
+            match once.send(&push.request) {
+                Ok(_) => summary.sent += 1,
+                Err(error) if is_transient(&error) => {
+                    push.attempts += 1;
+                    break;
+                }
+                Err(error) => summary.rejected.push(format!("{}: {}", push.description, error)),
+            }
+            self.pending.remove(0);
+        }
+        summary.remaining = self.pending.len();
+        summary
+    }
+
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the outbox from a JSON file, which is empty until a push is queued
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+/// Sends what is waiting in the outbox at `path` with the configured settings
+pub fn flush_outbox(path: &Path) -> io::Result<FlushSummary> {
+    let mut outbox = Outbox::load_from_json(path)?;
+    if outbox.pending.is_empty() {
+        return Ok(FlushSummary::default());
+    }
+    let summary = outbox.flush(&client());
+    outbox.save_to_json(path)?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    /// Answers each connection with the next status, returning the request lines seen
+    fn serve(statuses: Vec<u16>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/dinner", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut seen = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                seen.push(line.trim().to_string());
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 0 && !header.trim().is_empty() {
+                    header.clear();
+                }
+                let response = format!("HTTP/1.1 {} X\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+            seen
+        });
+        (url, handle)
+    }
+
+    fn quick(retries: u32) -> Client {
+        Client::new(HttpSettings { retries, backoff_ms: 1, min_interval_ms: 0, ..HttpSettings::default() })
+    }
+
+    #[test]
+    fn test_retries() {
+        let (url, server) = serve(vec![503, 429, 200]);
+        let request = Request::new("GET", url).query("day", "mon");
+        assert_eq!(request.host().split(':').next(), Some("127.0.0.1"));
+        assert_eq!(quick(2).send(&request).unwrap().status(), 200);
+        assert_eq!(server.join().unwrap(), vec!["GET /dinner?day=mon HTTP/1.1"; 3]);
+
+        // A request pinned to addresses goes there, whatever the host name resolves to
+        let (url, server) = serve(vec![200]);
+        let address = url.split('/').nth(2).unwrap().parse().unwrap();
+        let pinned = Request::new("GET", url.replace("127.0.0.1", "recipes.invalid")).connect_to(vec![address]);
+        assert_eq!(quick(0).send(&pinned).unwrap().status(), 200);
+        assert_eq!(server.join().unwrap(), vec!["GET /dinner HTTP/1.1"]);
+
+        // Client errors aren't tried again
+        let (url, server) = serve(vec![404]);
+        assert!(!is_transient(&quick(2).send(&Request::new("GET", url)).unwrap_err()));
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_outbox() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(OUTBOX_FILE);
+        // Nothing listens on a port that was just freed
+        let closed = format!("http://{}/dinner", TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+        let mut outbox = Outbox::default();
+        let at = Utc::now();
+        outbox.queue(Request::new("POST", &closed).body(Body::Text("Soup".to_string())), "ntfy", at);
+        outbox.save_to_json(&path).unwrap();
+
+        let mut outbox = Outbox::load_from_json(&path).unwrap();
+        assert_eq!(outbox.pending[0].request.body, Body::Text("Soup".to_string()));
+        assert_eq!(outbox.flush(&quick(2)), FlushSummary { sent: 0, rejected: Vec::new(), remaining: 1 });
+        assert_eq!(outbox.pending[0].attempts, 1);
+
+        let (url, server) = serve(vec![200, 400]);
+        outbox.pending[0].request.url = url.clone();
+        outbox.queue(Request::new("POST", url).body(Body::Json(serde_json::json!({"title": "Tea"}))), "gotify", at);
+        let summary = outbox.flush(&quick(2));
+        assert_eq!((summary.sent, summary.rejected.len(), summary.remaining), (1, 1, 0));
+        assert!(summary.rejected[0].starts_with("gotify: "), "{:?}", summary.rejected);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,239 @@
+use calamine::{open_workbook_auto, Data, DataType, Reader};
+use chrono::NaiveDate;
+use std::io;
+use std::path::Path;
+
+/// A cell read from a spreadsheet. Date cells are kept as dates so they don't depend on
+/// how the spreadsheet displays them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Text(String),
+    Date(NaiveDate),
+}
+
+impl Cell {
+    /// The cell as trimmed text, with dates written as YYYY-MM-DD
+    pub fn text(&self) -> String {
+        match self {
+            Cell::Empty => String::new(),
+            Cell::Text(text) => text.trim().to_string(),
+            Cell::Date(date) => date.format("%Y-%m-%d").to_string(),
+        }
+    }
+}
+
+/// Which spreadsheet column holds each field of a meal, as zero-based column indexes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMap {
+    pub day: usize,
+    pub meal_type: usize,
+    pub description: usize,
+    pub cook: Option<usize>,
+}
+
+impl ColumnMap {
+    /// Parses a mapping such as `A=day,B=meal_type,C=description,D=cook`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (mut day, mut meal_type, mut description, mut cook) = (None, None, None, None);
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (column, field) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid mapping '{}'. Use COLUMN=field, e.g. A=day.", entry))?;
+            let index = column_index(column.trim())
+                .ok_or_else(|| format!("Invalid column '{}'. Use a letter such as A or AB.", column.trim()))?;
+            let slot = match field.trim().to_lowercase().replace('-', "_").as_str() {
+                "day" | "date" => &mut day,
+                "meal_type" | "type" => &mut meal_type,
+                "description" | "meal" => &mut description,
+                "cook" => &mut cook,
+                other => return Err(format!("Unknown field '{}'. Use day, meal_type, description or cook.", other)),
+            };
+            if slot.replace(index).is_some() {
+                return Err(format!("The field '{}' is mapped more than once.", field.trim()));
+            }
+        }
+
+        let required = |index: Option<usize>, field: &str| index.ok_or_else(|| format!("The mapping needs a column for {}.", field));
+        Ok(Self {
+            day: required(day, "day")?,
+            meal_type: required(meal_type, "meal_type")?,
+            description: required(description, "description")?,
+            cook,
+        })
+    }
+}
+
+/// Converts a column letter (A, B, ..., Z, AA, ...) to a zero-based index
+fn column_index(column: &str) -> Option<usize> {
+    if column.is_empty() || !column.chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    column
+        .to_ascii_uppercase()
+        .bytes()
+        .try_fold(0usize, |index, b| index.checked_mul(26)?.checked_add((b - b'A' + 1) as usize))
+        .map(|index| index - 1)
+}
+
+/// One row of a spreadsheet, mapped to the fields of a meal
+#[derive(Debug, Clone, PartialEq)]
+pub struct MappedRow {
+    /// Row number as shown in the spreadsheet, starting at 1
+    pub row: usize,
+    pub day: Cell,
+    pub meal_type: String,
+    pub description: String,
+    pub cook: String,
+}
+
+/// Picks the mapped columns out of each row, skipping blank rows
+pub fn map_rows(rows: Vec<Vec<Cell>>, map: &ColumnMap, first_row: usize) -> Vec<MappedRow> {
+    let cell = |row: &[Cell], index: usize| row.get(index).cloned().unwrap_or(Cell::Empty);
+    rows.into_iter()
+        .enumerate()
+        .filter(|(_, row)| row.iter().any(|c| !c.text().is_empty()))
+        .map(|(n, row)| MappedRow {
+            row: first_row + n,
+            day: cell(&row, map.day),
+            meal_type: cell(&row, map.meal_type).text(),
+            description: cell(&row, map.description).text(),
+            cook: map.cook.map(|index| cell(&row, index).text()).unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Reads the rows of a spreadsheet: the first sheet of an xlsx, xls or ods workbook, or a CSV file
+pub fn read_rows(path: &Path) -> io::Result<Vec<Vec<Cell>>> {
+    let is_csv = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+    if is_csv {
+        return Ok(parse_csv(&std::fs::read_to_string(path)?));
+    }
+
+    let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+    let mut workbook = open_workbook_auto(path).map_err(|e| match e {
+        calamine::Error::Io(e) => e,
+        e => invalid(e.to_string()),
+    })?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| invalid("The workbook has no sheets.".to_string()))?
+        .map_err(|e| invalid(e.to_string()))?;
+
+    // Rows are numbered from the top of the sheet, even when it starts with blank rows
+    let leading_rows = range.start().map(|(row, _)| row as usize).unwrap_or(0);
+    let leading_columns = range.start().map(|(_, col)| col as usize).unwrap_or(0);
+    let mut rows = vec![Vec::new(); leading_rows];
+    rows.extend(range.rows().map(|row| {
+        let mut cells = vec![Cell::Empty; leading_columns];
+        cells.extend(row.iter().map(spreadsheet_cell));
+        cells
+    }));
+    Ok(rows)
+}
+
+fn spreadsheet_cell(data: &Data) -> Cell {
+    if let Some(date) = data.as_date().filter(|_| data.is_datetime() || data.is_datetime_iso()) {
+        return Cell::Date(date);
+    }
+    match data {
+        Data::Empty => Cell::Empty,
+        Data::String(text) => Cell::Text(text.clone()),
+        other => Cell::Text(other.to_string()),
+    }
+}
+
+/// Splits CSV text into rows of cells, handling quoted fields with commas, quotes and newlines
+fn parse_csv(contents: &str) -> Vec<Vec<Cell>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = contents.chars().peekable();
+
+    let end_field = |row: &mut Vec<Cell>, field: &mut String| {
+        let text = std::mem::take(field);
+        row.push(if text.is_empty() { Cell::Empty } else { Cell::Text(text) });
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => end_field(&mut row, &mut field),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                end_field(&mut row, &mut field);
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        end_field(&mut row, &mut field);
+        rows.push(row);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_column_map() {
+        let map = ColumnMap::parse("A=day, B=meal_type, C=description, D=cook").unwrap();
+        assert_eq!(map, ColumnMap { day: 0, meal_type: 1, description: 2, cook: Some(3) });
+
+        let map = ColumnMap::parse("AB=date,c=type,z=meal").unwrap();
+        assert_eq!((map.day, map.meal_type, map.description, map.cook), (27, 2, 25, None));
+
+        assert!(ColumnMap::parse("A=day,B=meal_type").unwrap_err().contains("description"));
+        assert!(ColumnMap::parse("A=day,B=day,C=description,D=meal_type").is_err());
+        assert!(ColumnMap::parse("1=day,B=meal_type,C=description").is_err());
+        assert!(ColumnMap::parse("A=day,B=meal_type,C=description,D=price").is_err());
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("plan.csv");
+        std::fs::write(&path, "Date,Type,Meal,Cook\r\n2023-01-02,Dinner,\"Mac, cheese\",Al\n\n2023-01-03,Lunch,\"Say \"\"hi\"\"\",\n").unwrap();
+
+        let rows = read_rows(&path).unwrap();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[1][2], Cell::Text("Mac, cheese".to_string()));
+        assert_eq!(rows[3][2].text(), "Say \"hi\"");
+
+        let map = ColumnMap::parse("A=day,B=meal_type,C=description,D=cook").unwrap();
+        let mapped = map_rows(rows.into_iter().skip(1).collect(), &map, 2);
+        assert_eq!(mapped.len(), 2);
+        assert_eq!(mapped[1].row, 4);
+        assert_eq!(mapped[1].cook, "");
+    }
+
+    #[test]
+    fn test_read_xlsx() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("plan.xlsx");
+        let mut workbook = rust_xlsxwriter::Workbook::new();
+        let sheet = workbook.add_worksheet();
+        let date_format = rust_xlsxwriter::Format::new().set_num_format("dd/mm/yyyy");
+        let date = rust_xlsxwriter::ExcelDateTime::from_ymd(2023, 1, 2).unwrap();
+        sheet.write_string(1, 1, "Day").unwrap();
+        sheet.write_datetime_with_format(2, 1, &date, &date_format).unwrap();
+        sheet.write_string(2, 2, "Dinner").unwrap();
+        sheet.write_number(2, 3, 42).unwrap();
+        workbook.save(&path).unwrap();
+
+        let rows = read_rows(&path).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[2][1], Cell::Date(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap()));
+        assert_eq!(rows[2][2].text(), "Dinner");
+        assert_eq!(rows[2][3].text(), "42");
+    }
+}
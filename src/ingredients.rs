@@ -0,0 +1,220 @@
+use crate::units;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::RwLock;
+
+/// File in the storage path with the ingredients merged with `ingredient merge`
+pub const INGREDIENTS_FILE: &str = "ingredients.json";
+
+/// Names that mean the same ingredient wherever the plan is used, as the name lists are
+/// given under and its other names
+const BUILT_IN: &[(&str, &[&str])] = &[
+    ("arugula", &["rocket"]),
+    ("baking soda", &["bicarbonate of soda", "bicarb"]),
+    ("bell pepper", &["capsicum", "sweet pepper"]),
+    ("chickpea", &["garbanzo bean", "garbanzo"]),
+    ("cilantro", &["coriander leaves", "fresh coriander"]),
+    ("eggplant", &["aubergine"]),
+    ("ground beef", &["minced beef", "beef mince"]),
+    ("heavy cream", &["double cream"]),
+    ("powdered sugar", &["icing sugar", "confectioners sugar", "confectioners' sugar"]),
+    ("scallion", &["green onion", "spring onion"]),
+    ("zucchini", &["courgette"]),
+];
+
+/// Other names for ingredients, so "scallions" and "green onion" are one line on the grocery
+/// list and share a price history. Names merged by the household are kept in
+/// ingredients.json in the storage path, on top of the built-in ones.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Registry {
+    /// Each ingredient's name with the names merged into it
+    #[serde(default)]
+    pub synonyms: BTreeMap<String, Vec<String>>,
+}
+
+impl Registry {
+    /// The household's ingredient a name is another name for
+    fn merged(&self, key: &str) -> Option<String> {
+        self.synonyms
+            .iter()
+            .find(|(name, synonyms)| units::fold_ingredient(name) == key || synonyms.iter().any(|synonym| units::fold_ingredient(synonym) == key))
+            .map(|(name, _)| units::fold_ingredient(name))
+    }
+
+    /// The key of the ingredient a folded name stands for: the household's own merges first,
+    /// then the built-in names, which may have been merged in turn
+    pub fn canonical(&self, key: &str) -> String {
+        if let Some(merged) = self.merged(key) {
+            return merged;
+        }
+        let built_in = BUILT_IN
+            .iter()
+            .find(|(name, synonyms)| units::fold_ingredient(name) == key || synonyms.iter().any(|synonym| units::fold_ingredient(synonym) == key))
+            .map(|(name, _)| units::fold_ingredient(name));
+        match built_in {
+            Some(name) => self.merged(&name).unwrap_or(name),
+            None => key.to_string(),
+        }
+    }
+
+    /// The name an ingredient key is shown and merged under
+    fn display_name(&self, key: &str) -> String {
+        let own = self.synonyms.keys().find(|name| units::fold_ingredient(name) == key).cloned();
+        let built_in = || BUILT_IN.iter().find(|(name, _)| units::fold_ingredient(name) == key).map(|(name, _)| name.to_string());
+        own.or_else(built_in).unwrap_or_else(|| key.to_string())
+    }
+
+    /// Treats `names` as `into` from now on, returning the names that weren't already. A name
+    /// that others were merged into brings them along, and merging an ingredient's own name
+    /// into one of its other names makes that the name it goes by.
+    pub fn merge(&mut self, names: &[String], into: &str) -> Result<Vec<String>, String> {
+        let into = into.trim().to_lowercase();
+        if into.is_empty() {
+            return Err("Name the ingredient to merge into.".to_string());
+        }
+        let into_key = units::fold_ingredient(&into);
+        let mut target = self.canonical(&into_key);
+        let mut merged = Vec::new();
+        for name in names {
+            let name = name.trim().to_lowercase();
+            if name.is_empty() {
+                continue;
+            }
+            let key = units::fold_ingredient(&name);
+            if key == into_key {
+                return Err(format!("Can't merge \"{}\" into itself.", name));
+            }
+            if self.canonical(&key) == target && key != target {
+                continue;
+            }
+            let group_name = if key == target {
+                target = into_key.clone();
+                into.clone()
+            } else {
+                self.display_name(&target)
+            };
+
+            let mut moved = vec![name.clone()];
+            if let Some(own) = self.synonyms.keys().find(|own| units::fold_ingredient(own) == key).cloned() {
+                moved.extend(self.synonyms.remove(&own).unwrap_or_default());
+            }
+            let group = self.synonyms.entry(group_name.clone()).or_default();
+            for synonym in moved {
+                if units::fold_ingredient(&synonym) != units::fold_ingredient(&group_name) && !group.contains(&synonym) {
+                    group.push(synonym);
+                }
+            }
+            merged.push(name);
+        }
+        Ok(merged)
+    }
+
+    /// Every ingredient with other names, built in or merged, with those names
+    pub fn groups(&self) -> BTreeMap<String, BTreeSet<String>> {
+        let mut names: Vec<&str> = Vec::new();
+        for (name, synonyms) in BUILT_IN {
+            names.push(name);
+            names.extend(synonyms.iter());
+        }
+        for (name, synonyms) in &self.synonyms {
+            names.push(name);
+            names.extend(synonyms.iter().map(String::as_str));
+        }
+
+        let mut groups: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for name in names {
+            let canonical = self.canonical(&units::fold_ingredient(name));
+            if units::fold_ingredient(name) != canonical {
+                groups.entry(self.display_name(&canonical)).or_default().insert(name.to_string());
+            }
+        }
+        groups
+    }
+
+    /// Saves the merged names to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the merged names from a JSON file, which is empty until the first merge
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+// The names ingredients are matched up by, set once the storage path is known
+static REGISTRY: RwLock<Option<Registry>> = RwLock::new(None);
+
+/// Sets the registry ingredient names are matched up with from now on
+pub fn configure(registry: Registry) {
+    *REGISTRY.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(registry);
+}
+
+/// The key of the ingredient a folded name stands for, going by the configured registry, or
+/// by the built-in names before one is configured
+pub fn canonical(key: &str) -> String {
+    match &*REGISTRY.read().unwrap_or_else(|poisoned| poisoned.into_inner()) {
+        Some(registry) => registry.canonical(key),
+        None => Registry::default().canonical(key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn key(name: &str) -> String {
+        units::fold_ingredient(name)
+    }
+
+    #[test]
+    fn test_canonical() {
+        let registry = Registry::default();
+        assert_eq!(registry.canonical(&key("Green Onions")), "scallion");
+        assert_eq!(registry.canonical(&key("scallions")), "scallion");
+        assert_eq!(registry.canonical(&key("courgette")), "zucchini");
+        assert_eq!(registry.canonical(&key("onion")), "onion");
+    }
+
+    #[test]
+    fn test_merge() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(INGREDIENTS_FILE);
+        let mut registry = Registry::load_from_json(&path).unwrap();
+
+        let merged = registry.merge(&["Cheddar".to_string(), "sharp cheddar".to_string()], "cheddar cheese").unwrap();
+        assert_eq!(merged, vec!["cheddar", "sharp cheddar"]);
+        assert_eq!(registry.canonical(&key("Sharp Cheddar")), "cheddar cheese");
+        // Merging again changes nothing
+        assert!(registry.merge(&["cheddar".to_string()], "Cheddar Cheese").unwrap().is_empty());
+
+        // A merged ingredient brings its names along, and built-in names can be merged too
+        registry.merge(&["cheddar cheese".to_string()], "cheese").unwrap();
+        assert_eq!(registry.canonical(&key("sharp cheddar")), "cheese");
+        registry.merge(&["scallion".to_string()], "green onion").unwrap();
+        assert_eq!(registry.canonical(&key("spring onions")), "green onion");
+        assert!(registry.merge(&["Cheese".to_string()], "cheese").is_err());
+
+        let groups = registry.groups();
+        assert_eq!(groups["cheese"], BTreeSet::from(["cheddar".to_string(), "cheddar cheese".to_string(), "sharp cheddar".to_string()]));
+        assert!(groups["green onion"].contains("spring onion"));
+        assert!(!groups.contains_key("scallion"));
+
+        registry.save_to_json(&path).unwrap();
+        assert_eq!(Registry::load_from_json(&path).unwrap(), registry);
+    }
+}
@@ -0,0 +1,10 @@
+//! The meal plan and the rules for changing and merging it, shared by the command line
+//! and a browser build. Everything touching the filesystem is left out on wasm32, where
+//! plans are kept through a [`store::Storage`] instead.
+
+pub mod days;
+pub mod photos;
+pub mod plan;
+pub mod store;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod storage;
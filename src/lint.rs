@@ -0,0 +1,324 @@
+use crate::filter::Filter;
+use crate::models::{Meal, MealPlan, MealType};
+use crate::recipes::RecipeBook;
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// Name of the rules file `mealplan check` reads from the storage path
+pub const RULES_FILE: &str = "check_rules.yaml";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+fn default_severity() -> Severity {
+    Severity::Warning
+}
+
+/// What a rule looks for in the plan
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum RuleCheck {
+    /// Meals of the same type on consecutive days share one of `values`, found in their
+    /// tags, descriptions or recipe ingredients, or are the same dish when `values` is empty
+    InARow {
+        #[serde(default)]
+        values: Vec<String>,
+    },
+    /// A day has fewer or more meals than this
+    MealsPerDay {
+        #[serde(default)]
+        min: Option<usize>,
+        #[serde(default)]
+        max: Option<usize>,
+    },
+    /// A cook has more meals than this in the week
+    MaxPerCook { max: usize },
+    /// A meal matches a filter expression, such as `tag ~ fried && day == mon`
+    Matches { filter: String },
+}
+
+/// One rule of the rules file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LintRule {
+    /// Name shown with what the rule finds; defaults to the kind of check
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default = "default_severity")]
+    pub severity: Severity,
+    /// Only look at meals of this type
+    #[serde(default)]
+    pub meal_type: Option<String>,
+    /// Said instead of the rule's own description of what it found
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(flatten)]
+    pub check: RuleCheck,
+}
+
+#[derive(Debug, Deserialize)]
+struct RulesFile {
+    #[serde(default)]
+    rules: Vec<LintRule>,
+}
+
+/// Something a rule found in the plan
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    pub rule: String,
+    pub severity: Severity,
+    /// Day the finding is about, if it is about one
+    pub date: Option<NaiveDate>,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}]", self.severity, self.rule)?;
+        if let Some(date) = self.date {
+            write!(f, " {}", date.format("%a %Y-%m-%d"))?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Reads the rules from YAML, checking their meal types and filters
+pub fn parse_rules(yaml: &str) -> Result<Vec<LintRule>, String> {
+    if yaml.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let file: RulesFile = serde_yaml::from_str(yaml).map_err(|e| e.to_string())?;
+    for (n, rule) in file.rules.iter().enumerate() {
+        let context = |e: String| format!("Rule {} ({}): {}", n + 1, rule.name(), e);
+        rule.meal_type().map_err(context)?;
+        if let RuleCheck::Matches { filter } = &rule.check {
+            Filter::parse(filter).map_err(context)?;
+        }
+    }
+    Ok(file.rules)
+}
+
+/// Loads the rules file, which is optional: without one there are no rules
+pub fn load_rules(path: &Path) -> Result<Vec<LintRule>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(yaml) => parse_rules(&yaml).map_err(|e| format!("{:?}: {}", path, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read {:?}: {}", path, e)),
+    }
+}
+
+impl LintRule {
+    pub fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            match self.check {
+                RuleCheck::InARow { .. } => "in_a_row",
+                RuleCheck::MealsPerDay { .. } => "meals_per_day",
+                RuleCheck::MaxPerCook { .. } => "max_per_cook",
+                RuleCheck::Matches { .. } => "matches",
+            }
+            .to_string()
+        })
+    }
+
+    fn meal_type(&self) -> Result<Option<MealType>, String> {
+        let Some(meal_type) = &self.meal_type else {
+            return Ok(None);
+        };
+        match meal_type.to_lowercase().as_str() {
+            "breakfast" => Ok(Some(MealType::Breakfast)),
+            "lunch" => Ok(Some(MealType::Lunch)),
+            "dinner" => Ok(Some(MealType::Dinner)),
+            "snack" => Ok(Some(MealType::Snack)),
+            _ => Err(format!("Unknown meal type {:?}. Use breakfast, lunch, dinner or snack.", meal_type)),
+        }
+    }
+
+    /// Checks the plan against the rule
+    pub fn check(&self, meal_plan: &MealPlan, book: &RecipeBook) -> Vec<Finding> {
+        let meal_type = self.meal_type().ok().flatten();
+        let meals: Vec<(&Meal, NaiveDate)> = meal_plan
+            .meals()
+            .iter()
+            .filter(|meal| meal_type.as_ref().is_none_or(|t| meal.meal_type == *t))
+            .map(|meal| (meal, meal_plan.date_of(&meal.day)))
+            .collect();
+        let kind = meal_type.as_ref().map_or("meal".to_string(), |t| t.to_string().to_lowercase());
+
+        let mut found: Vec<(Option<NaiveDate>, String)> = Vec::new();
+        match &self.check {
+            RuleCheck::InARow { values } => {
+                for (meal, date) in &meals {
+                    let Some((next, _)) = meals.iter().find(|(other, other_date)| {
+                        other.meal_type == meal.meal_type && *other_date == *date + Duration::days(1)
+                    }) else {
+                        continue;
+                    };
+                    let repeated: Vec<String> = if values.is_empty() {
+                        let same = meal.description.trim().eq_ignore_ascii_case(next.description.trim());
+                        if same { vec![meal.description.trim().to_string()] } else { Vec::new() }
+                    } else {
+                        values
+                            .iter()
+                            .filter(|value| mentions(meal, book, value) && mentions(next, book, value))
+                            .cloned()
+                            .collect()
+                    };
+                    for value in repeated {
+                        let message = format!(
+                            "{} for {} two days in a row ({} and {})",
+                            value,
+                            meal.meal_type.to_string().to_lowercase(),
+                            date.format("%A"),
+                            (*date + Duration::days(1)).format("%A")
+                        );
+                        found.push((Some(*date + Duration::days(1)), message));
+                    }
+                }
+            }
+            RuleCheck::MealsPerDay { min, max } => {
+                for date in (0..7).map(|n| meal_plan.week_start_date + Duration::days(n)) {
+                    let count = meals.iter().filter(|(_, meal_date)| *meal_date == date).count();
+                    if min.is_some_and(|min| count < min) || max.is_some_and(|max| count > max) {
+                        let message = match count {
+                            0 => format!("no {}s planned", kind),
+                            _ => format!("{} {}(s) planned", count, kind),
+                        };
+                        found.push((Some(date), message));
+                    }
+                }
+            }
+            RuleCheck::MaxPerCook { max } => {
+                let mut counts: BTreeMap<String, (String, usize)> = BTreeMap::new();
+                for (meal, _) in meals.iter().filter(|(meal, _)| !meal.cook.trim().is_empty()) {
+                    counts.entry(meal.cook.trim().to_lowercase()).or_insert_with(|| (meal.cook.trim().to_string(), 0)).1 += 1;
+                }
+                for (cook, count) in counts.into_values().filter(|(_, count)| count > max) {
+                    found.push((None, format!("{} cooks {} {}s, more than {}", cook, count, kind, max)));
+                }
+            }
+            RuleCheck::Matches { filter } => {
+                let Ok(filter) = Filter::parse(filter) else { return Vec::new() };
+                for (meal, date) in meals.iter().filter(|(meal, date)| filter.matches(meal, *date)) {
+                    let message = format!("{}: {}", meal.meal_type, meal_plan.render_description(meal));
+                    found.push((Some(*date), message));
+                }
+            }
+        }
+
+        found
+            .into_iter()
+            .map(|(date, message)| Finding {
+                rule: self.name(),
+                severity: self.severity,
+                date,
+                message: self.message.clone().unwrap_or(message),
+            })
+            .collect()
+    }
+}
+
+/// Whether a meal's tags, description or recipe ingredients mention a value
+pub fn mentions(meal: &Meal, book: &RecipeBook, value: &str) -> bool {
+    let value = value.to_lowercase();
+    let ingredients = book.for_meal(meal).map(|recipe| recipe.ingredients.as_slice()).unwrap_or_default();
+    meal.tags.iter().any(|tag| tag.to_lowercase() == value)
+        || std::iter::once(&meal.description).chain(ingredients).any(|text| text.to_lowercase().contains(&value))
+}
+
+/// Checks the plan against every rule, most severe findings first and then by day
+pub fn check_plan(meal_plan: &MealPlan, book: &RecipeBook, rules: &[LintRule]) -> Vec<Finding> {
+    let mut findings: Vec<Finding> = rules.iter().flat_map(|rule| rule.check(meal_plan, book)).collect();
+    findings.sort_by_key(|finding| (finding.severity, finding.date));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Day;
+    use crate::recipes::Recipe;
+    use chrono::Weekday;
+
+    const RULES: &str = r#"
+rules:
+  # Proteins are found in tags, descriptions and recipes
+  - name: protein-repeat
+    check: in_a_row
+    meal_type: dinner
+    values: [chicken, beef, fish]
+  - name: empty-day
+    severity: error
+    check: meals_per_day
+    min: 1
+  - check: max_per_cook
+    meal_type: dinner
+    max: 2
+  - name: no-fried-mondays
+    severity: info
+    check: matches
+    filter: "tag ~ fried && day == mon"
+    message: Go easy on Mondays
+"#;
+
+    fn meal(meal_type: MealType, day: Weekday, cook: &str, description: &str) -> Meal {
+        Meal::new(meal_type, Day::Weekday(day), cook.to_string(), description.to_string())
+    }
+
+    #[test]
+    fn test_check_plan() {
+        let rules = parse_rules(RULES).unwrap();
+        assert_eq!(rules.len(), 4);
+        assert_eq!(rules[2].name(), "max_per_cook");
+
+        // 2023-01-02 is a Monday
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let mut fried = meal(MealType::Dinner, Weekday::Mon, "Alice", "Chicken tenders");
+        fried.tags.push("fried".to_string());
+        plan.add_meal(fried);
+        plan.add_meal(meal(MealType::Dinner, Weekday::Tue, "Alice", "Curry"));
+        plan.add_meal(meal(MealType::Dinner, Weekday::Wed, "Alice", "Tacos"));
+        plan.add_meal(meal(MealType::Dinner, Weekday::Thu, "Bob", "Fish tacos"));
+        for day in [Weekday::Fri, Weekday::Sat, Weekday::Sun] {
+            plan.add_meal(meal(MealType::Lunch, day, "Bob", "Sandwiches"));
+        }
+        let mut book = RecipeBook::default();
+        book.add(Recipe { name: "Curry".to_string(), tags: Vec::new(), ingredients: vec!["2 chicken thighs".to_string()], link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() });
+
+        let findings: Vec<String> = check_plan(&plan, &book, &rules).iter().map(Finding::to_string).collect();
+        assert_eq!(findings, vec![
+            "warning [max_per_cook]: Alice cooks 3 dinners, more than 2",
+            "warning [protein-repeat] Tue 2023-01-03: chicken for dinner two days in a row (Monday and Tuesday)",
+            "info [no-fried-mondays] Mon 2023-01-02: Go easy on Mondays",
+        ]);
+
+        plan.remove_meal(&MealType::Lunch, &Day::Weekday(Weekday::Sat));
+        let findings = check_plan(&plan, &book, &rules);
+        assert_eq!(findings[0].to_string(), "error [empty-day] Sat 2023-01-07: no meals planned");
+    }
+
+    #[test]
+    fn test_parse_rules_errors() {
+        assert!(parse_rules("rules:\n  - check: in_a_row\n    meal_type: supper\n").unwrap_err().contains("Unknown meal type"));
+        assert!(parse_rules("rules:\n  - check: matches\n    filter: \"cook ==\"\n").unwrap_err().starts_with("Rule 1 (matches)"));
+        assert!(parse_rules("rules:\n  - check: max_per_cook\n").is_err());
+        assert!(parse_rules("rules:\n  - check: sometimes\n").is_err());
+        assert!(parse_rules("# No rules yet\n").unwrap().is_empty());
+    }
+}
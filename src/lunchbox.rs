@@ -0,0 +1,356 @@
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Where an item goes in a lunchbox; each box gets one of each
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Part {
+    Main,
+    Fruit,
+    Snack,
+}
+
+impl Part {
+    pub const ALL: [Part; 3] = [Part::Main, Part::Fruit, Part::Snack];
+
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "main" | "mains" => Some(Part::Main),
+            "fruit" | "fruits" => Some(Part::Fruit),
+            "snack" | "snacks" => Some(Part::Snack),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Part {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Part::Main => write!(f, "main"),
+            Part::Fruit => write!(f, "fruit"),
+            Part::Snack => write!(f, "snack"),
+        }
+    }
+}
+
+/// An approved lunchbox item
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Component {
+    pub name: String,
+    pub part: Part,
+    /// What's in it, such as nuts or dairy, for children's exclusions to match
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// What to put on the grocery list for each box, when not the name itself
+    #[serde(default)]
+    pub buy: Option<String>,
+}
+
+/// A child lunches are packed for, with what they mustn't or won't have
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Child {
+    pub name: String,
+    /// Tags or words of items to leave out, e.g. nuts or banana
+    #[serde(default)]
+    pub exclusions: Vec<String>,
+}
+
+impl Child {
+    /// Returns the exclusion an item falls under: one of its tags, or words in its name
+    pub fn excluded_by(&self, component: &Component) -> Option<&str> {
+        let name_words = words(&component.name);
+        self.exclusions.iter().map(|e| e.trim()).filter(|e| !e.is_empty()).find(|exclusion| {
+            let exclusion_words = words(exclusion);
+            component.tags.iter().any(|tag| tag.trim().eq_ignore_ascii_case(exclusion))
+                || name_words.windows(exclusion_words.len()).any(|window| window == exclusion_words.as_slice())
+        })
+    }
+}
+
+/// One child's lunchbox for a day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackedLunch {
+    pub date: NaiveDate,
+    pub child: String,
+    /// The item for each part, missing when every item for it is excluded
+    pub items: BTreeMap<Part, String>,
+    /// Whether its items were put on the grocery list
+    #[serde(default)]
+    pub listed: bool,
+}
+
+/// The lunchbox planner, kept in lunchbox.json in the storage path: the approved items,
+/// the children, and the lunches last generated
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lunchbox {
+    #[serde(default)]
+    pub components: Vec<Component>,
+    #[serde(default)]
+    pub children: Vec<Child>,
+    #[serde(default)]
+    pub lunches: Vec<PackedLunch>,
+}
+
+impl Lunchbox {
+    /// Adds an item to the pool, replacing one with the same name. Returns true if it is new.
+    pub fn add_component(&mut self, component: Component) -> bool {
+        match self.components.iter_mut().find(|c| c.name.eq_ignore_ascii_case(&component.name)) {
+            Some(existing) => {
+                *existing = component;
+                false
+            }
+            None => {
+                self.components.push(component);
+                true
+            }
+        }
+    }
+
+    /// Removes an item by name, ignoring case, returning false if there was none
+    pub fn remove_component(&mut self, name: &str) -> bool {
+        let before = self.components.len();
+        self.components.retain(|c| !c.name.eq_ignore_ascii_case(name.trim()));
+        self.components.len() != before
+    }
+
+    /// Adds a child, or replaces the exclusions of one already added. Returns true if new.
+    pub fn set_child(&mut self, name: &str, exclusions: Vec<String>) -> bool {
+        match self.children.iter_mut().find(|c| c.name.eq_ignore_ascii_case(name.trim())) {
+            Some(child) => {
+                child.exclusions = exclusions;
+                false
+            }
+            None => {
+                self.children.push(Child { name: name.trim().to_string(), exclusions });
+                true
+            }
+        }
+    }
+
+    /// Removes a child by name, ignoring case, returning false if there was none
+    pub fn remove_child(&mut self, name: &str) -> bool {
+        let before = self.children.len();
+        self.children.retain(|c| !c.name.eq_ignore_ascii_case(name.trim()));
+        self.children.len() != before
+    }
+
+    /// Packs a lunch for each child on each date, replacing lunches already planned for
+    /// those dates. Each child gets the items they've had least this week, skipping what
+    /// they had the day before when there's another choice; where the week's order starts
+    /// moves on from week to week.
+    pub fn generate(&mut self, dates: &[NaiveDate]) -> &[PackedLunch] {
+        let mut dates = dates.to_vec();
+        dates.sort();
+        dates.dedup();
+        self.lunches.retain(|lunch| !dates.contains(&lunch.date));
+
+        let mut packed = Vec::new();
+        for (child_index, child) in self.children.iter().enumerate() {
+            let mut used: BTreeMap<String, usize> = BTreeMap::new();
+            let mut yesterday: BTreeMap<Part, String> = BTreeMap::new();
+            for &date in &dates {
+                let mut items = BTreeMap::new();
+                for part in Part::ALL {
+                    let allowed: Vec<&Component> = self
+                        .components
+                        .iter()
+                        .filter(|c| c.part == part && child.excluded_by(c).is_none())
+                        .collect();
+                    if allowed.is_empty() {
+                        continue;
+                    }
+                    let start = (date.iso_week().week() as usize + child_index) % allowed.len();
+                    let chosen = (0..allowed.len())
+                        .map(|offset| allowed[(start + offset) % allowed.len()])
+                        .min_by_key(|c| {
+                            let repeat = allowed.len() > 1 && yesterday.get(&part) == Some(&c.name);
+                            (repeat, used.get(&c.name.to_lowercase()).copied().unwrap_or(0))
+                        })
+                        .map(|c| c.name.clone())
+                        .unwrap_or_default();
+                    *used.entry(chosen.to_lowercase()).or_default() += 1;
+                    items.insert(part, chosen);
+                }
+                yesterday = items.clone();
+                packed.push(PackedLunch { date, child: child.name.clone(), items, listed: false });
+            }
+        }
+
+        self.lunches.extend(packed);
+        self.lunches.sort_by_key(|lunch| lunch.date);
+        &self.lunches
+    }
+
+    /// Drops lunches from before the given date
+    pub fn forget_before(&mut self, date: NaiveDate) {
+        self.lunches.retain(|lunch| lunch.date >= date);
+    }
+
+    /// What the lunches between two dates need from the shops, with how many of each,
+    /// e.g. "3 apples". Lunches already put on the grocery list are left out, and the rest
+    /// are marked as put on it.
+    pub fn list_groceries(&mut self, from: NaiveDate, to: NaiveDate) -> Vec<String> {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        let components = &self.components;
+        for lunch in self.lunches.iter_mut().filter(|lunch| lunch.date >= from && lunch.date <= to && !lunch.listed) {
+            lunch.listed = true;
+            for name in lunch.items.values() {
+                let item = components
+                    .iter()
+                    .find(|c| c.name.eq_ignore_ascii_case(name))
+                    .and_then(|c| c.buy.clone())
+                    .unwrap_or_else(|| name.clone());
+                match counts.iter_mut().find(|(listed, _)| listed.eq_ignore_ascii_case(&item)) {
+                    Some((_, count)) => *count += 1,
+                    None => counts.push((item, 1)),
+                }
+            }
+        }
+        counts.into_iter().map(|(item, count)| format!("{} {}", count, item)).collect()
+    }
+
+    /// Lists the lunches between two dates, a line per child under each day
+    pub fn to_text(&self, from: NaiveDate, to: NaiveDate) -> String {
+        let mut out = String::new();
+        let mut day = None;
+        for lunch in self.lunches.iter().filter(|lunch| lunch.date >= from && lunch.date <= to) {
+            if day != Some(lunch.date) {
+                out.push_str(&format!("{}\n", lunch.date.format("%a %Y-%m-%d")));
+                day = Some(lunch.date);
+            }
+            let items: Vec<String> = Part::ALL
+                .iter()
+                .map(|part| lunch.items.get(part).cloned().unwrap_or_else(|| format!("(no {})", part)))
+                .collect();
+            out.push_str(&format!("  {}: {}\n", lunch.child, items.join(", ")));
+        }
+        out
+    }
+
+    /// Saves the lunchbox planner to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the lunchbox planner from a JSON file, which is empty until the first item is added
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn component(name: &str, part: Part, tags: &[&str]) -> Component {
+        Component { name: name.to_string(), part, tags: tags.iter().map(|t| t.to_string()).collect(), buy: None }
+    }
+
+    fn lunchbox() -> Lunchbox {
+        let mut lunchbox = Lunchbox::default();
+        lunchbox.add_component(component("Ham sandwich", Part::Main, &[]));
+        lunchbox.add_component(component("Peanut butter sandwich", Part::Main, &["nuts"]));
+        lunchbox.add_component(component("Pasta salad", Part::Main, &[]));
+        lunchbox.add_component(Component { buy: Some("apples".to_string()), ..component("Apple", Part::Fruit, &[]) });
+        lunchbox.add_component(component("Banana", Part::Fruit, &[]));
+        lunchbox.add_component(component("Crackers", Part::Snack, &[]));
+        lunchbox.set_child("Sam", vec!["nuts".to_string()]);
+        lunchbox.set_child("Ella", vec!["banana".to_string()]);
+        lunchbox
+    }
+
+    fn school_week() -> Vec<NaiveDate> {
+        (2..=6).map(|day| NaiveDate::from_ymd_opt(2023, 1, day).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_exclusions() {
+        let lunchbox = lunchbox();
+        let sam = &lunchbox.children[0];
+        assert_eq!(sam.excluded_by(&lunchbox.components[1]), Some("nuts"));
+        assert_eq!(sam.excluded_by(&lunchbox.components[0]), None);
+        let picky = Child { name: "Max".to_string(), exclusions: vec!["Butter Sandwich".to_string()] };
+        assert!(picky.excluded_by(&lunchbox.components[1]).is_some());
+        assert!(picky.excluded_by(&lunchbox.components[0]).is_none());
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut lunchbox = lunchbox();
+        let week = school_week();
+        let lunches = lunchbox.generate(&week).to_vec();
+        assert_eq!(lunches.len(), 10);
+        for lunch in &lunches {
+            assert_eq!(lunch.items.len(), 3);
+            if lunch.child == "Sam" {
+                assert_ne!(lunch.items[&Part::Main], "Peanut butter sandwich");
+            } else {
+                assert_eq!(lunch.items[&Part::Fruit], "Apple");
+            }
+        }
+        // Sam's mains alternate, and his fruit too
+        let sam: Vec<&PackedLunch> = lunches.iter().filter(|lunch| lunch.child == "Sam").collect();
+        for pair in sam.windows(2) {
+            assert_ne!(pair[0].items[&Part::Main], pair[1].items[&Part::Main]);
+            assert_ne!(pair[0].items[&Part::Fruit], pair[1].items[&Part::Fruit]);
+        }
+        // Ella gets all three mains before any comes round again
+        let ella: Vec<&str> = lunches.iter().filter(|l| l.child == "Ella").take(3).map(|l| l.items[&Part::Main].as_str()).collect();
+        assert_eq!(ella.iter().collect::<std::collections::BTreeSet<_>>().len(), 3);
+
+        // Regenerating a day replaces its lunches
+        assert_eq!(lunchbox.generate(&week[..1]).len(), 10);
+
+        let items = lunchbox.list_groceries(week[0], week[4]);
+        assert!(items.contains(&"10 Crackers".to_string()));
+        let apples = items.iter().find(|item| item.ends_with(" apples")).unwrap();
+        assert!(apples.starts_with('7') || apples.starts_with('8'));
+        // Only lunches packed since are listed again
+        assert!(lunchbox.list_groceries(week[0], week[4]).is_empty());
+        lunchbox.generate(&week[..1]);
+        assert!(lunchbox.list_groceries(week[0], week[4]).contains(&"2 Crackers".to_string()));
+
+        let text = lunchbox.to_text(week[0], week[0]);
+        assert!(text.starts_with("Mon 2023-01-02\n  Sam: "));
+        lunchbox.remove_component("crackers");
+        lunchbox.generate(&week[..1]);
+        assert!(lunchbox.to_text(week[0], week[0]).contains("(no snack)"));
+    }
+
+    #[test]
+    fn test_lunchbox_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("lunchbox.json");
+        assert!(Lunchbox::load_from_json(&path).unwrap().components.is_empty());
+        let mut lunchbox = lunchbox();
+        lunchbox.generate(&school_week());
+        lunchbox.save_to_json(&path).unwrap();
+        let loaded = Lunchbox::load_from_json(&path).unwrap();
+        assert_eq!(loaded.components, lunchbox.components);
+        assert_eq!(loaded.lunches, lunchbox.lunches);
+        assert!(std::fs::read_to_string(&path).unwrap().contains("\"part\": \"fruit\""));
+    }
+}
@@ -1,18 +1,111 @@
 #![allow(dead_code)]
 
+mod apps;
+mod archive;
+mod attendance;
+mod budget;
+mod bulk;
+mod bundle;
+mod calendar_file;
+mod clipboard;
+mod compare;
+mod cooking;
+mod daemon;
+mod dataset;
+mod doctor;
+mod duplicates;
+mod email;
+mod equipment;
+mod error;
+mod events;
+mod examples;
+mod filter;
+mod freeze;
+mod grocery;
+mod history;
+mod http;
+mod import;
+mod ingredients;
+mod lint;
+mod lunchbox;
+mod matrix;
+mod migrate;
 mod models;
+mod motd;
+mod notify;
+mod packing;
+mod packs;
+mod pantry;
+mod peer;
+mod places;
+mod prices;
+mod prompt;
+mod receipt;
+mod recipes;
+mod reload;
+mod report;
+mod review;
+mod rotation;
+mod scraper;
+mod search;
+mod secrets;
+mod server;
+mod settings;
+mod shell;
+mod snapshots;
+mod solver;
+mod sheets;
+mod spreadsheet;
+mod swaps;
+mod units;
+mod waste;
+mod webapp;
 
-use clap::{Parser, Subcommand};
-use models::{Config, MealPlan, Meal, MealType, Day};
-use std::path::PathBuf;
+use archive::Archive;
+use attendance::Attendance;
+use bulk::{BulkOp, BulkSummary};
+use bundle::PlanBundle;
+use calendar_file::{component_text, component_uid, CalendarFile};
+use clipboard::{render_week, render_week_html, TextFormat};
+use compare::WeekComparison;
+use days::weekday_name;
+use mealplan::{days, photos, storage};
+use error::CliError;
+use equipment::EquipmentUse;
+use events::EventLog;
+use filter::Filter;
+use clap::{CommandFactory, Parser, Subcommand};
+use grocery::{CartItem, GroceryItem, GroceryList, ImportedReceipt};
+use history::{DishSummary, MealHistory, VarietyReport};
+use lunchbox::Lunchbox;
+use models::{Config, CookProfile, MealAlias, MealPlan, Meal, MealType, Day};
+use pantry::Pantry;
+use report::{PeriodReport, ReportPeriod};
+use prompt::{Completions, Prompter, Scripted, Terminal};
+use receipt::ReceiptLine;
+use recipes::{Recipe, RecipeBook, RecipeFilter};
+use places::{Place, Places};
+use prices::PriceHistory;
+use review::WeeklyReview;
+use rotation::{Rotation, Rotations};
+use search::IndexedMeal;
+use shell::{ShellEditor, ShellInput};
+use waste::{WasteEntry, WasteLog, WasteReport};
+use solver::{Candidate, CookOption, Placed, Problem};
+use std::path::{Path, PathBuf};
 use chrono::{NaiveDate, Weekday, Local, Datelike};
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Read};
 use icalendar::{Calendar, Component, Event, EventLike, Property};
-use chrono::{Duration, TimeZone, Utc};
-use std::collections::HashMap;
+use image::Luma;
+use qrcode::render::unicode;
+use qrcode::QrCode;
+use storage::SyncState;
+use chrono::{DateTime, Duration, SubsecRound, TimeZone, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, after_help = "Run 'mealplan examples' for the commands of common workflows.")]
 struct Args {
     #[command(subcommand)]
     command: Option<Commands>,
@@ -20,21 +113,91 @@ struct Args {
     /// Optional custom path for config and data files
     #[arg(short, long, global = true)]
     path: Option<PathBuf>,
+
+    /// Named configuration profile to use, e.g. for a second household
+    #[arg(long, global = true, env = "MEALPLAN_PROFILE")]
+    profile: Option<String>,
+
+    /// Configuration file to use instead of looking one up; takes precedence over --profile
+    #[arg(long, global = true, env = "MEALPLAN_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// How to print errors: text, or json for scripts
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+
+    /// Change a frozen week anyway, or roll over before the week has ended
+    #[arg(long, global = true)]
+    force: bool,
+
+    /// Answer the command's questions from a file, one answer per line, instead of asking
+    #[arg(long, global = true)]
+    answers_file: Option<PathBuf>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ErrorFormat {
+    Text,
+    Json,
+}
+
+/// Which side wins when a meal changed both in the plan and in the calendar
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CalendarPreference {
+    Plan,
+    Calendar,
+}
+
+/// How `export-ical --split` divides the plan into calendar files
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum CalendarSplit {
+    /// One file per week, named by the week's first day
+    Weekly,
+    /// One file per month
+    Monthly,
+}
+
+/// File format of `export-history`
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum HistoryFormat {
+    Csv,
+    Parquet,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Add a new meal to the plan
     Add {
-        /// Description of the meal
-        description: String,
+        /// Description of the meal, or the name of a meal alias; defaults to the place's
+        /// name with --eat-out
+        #[arg(required_unless_present = "eat_out")]
+        description: Option<String>,
         
         #[arg(short = 't', long)]
         meal_type: String,
+        /// Day to plan the meal on, or several separated by commas, e.g. mon,wed,fri,
+        /// weekdays or weekend
+        #[arg(short, long, visible_alias = "days", required_unless_present = "from", conflicts_with = "from")]
+        day: Option<String>,
+        /// First date of a range to plan the meal on every day of (YYYY-MM-DD)
+        #[arg(long, requires = "to")]
+        from: Option<String>,
+        /// Last date of the range, included (YYYY-MM-DD)
+        #[arg(long, requires = "from")]
+        to: Option<String>,
+        /// Cook for the meal, or "auto" to pick the next cook in the rotation. Defaults to
+        /// the meal type's cook in default_cooks.
         #[arg(short, long)]
-        day: String,
-        #[arg(short, long)]
-        cook: String,
+        cook: Option<String>,
+        /// Packed to take along, like a school lunch; shows up in `packing-list`
+        #[arg(long)]
+        packed: bool,
+        /// Eat out or order in from a place in the places list
+        #[arg(long, value_name = "PLACE")]
+        eat_out: Option<String>,
+        /// Photo of the dish: an image file, copied into the assets directory, or a web address
+        #[arg(long)]
+        photo: Option<String>,
     },
     /// Edit an existing meal in the plan
     Edit {
@@ -47,843 +210,9942 @@ enum Commands {
         day: String,
         #[arg(short, long)]
         cook: Option<String>,
+        /// Mark the meal as packed to take along, or not with --packed false
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        packed: Option<bool>,
+        /// Photo of the dish: an image file, copied into the assets directory, or a web
+        /// address; an empty value removes it
+        #[arg(long)]
+        photo: Option<String>,
     },
     /// Remove a meal from the plan
     Remove {
+        /// Meal type to remove; with --all, only meals of this type
+        #[arg(short, long, required_unless_present = "all")]
+        meal_type: Option<String>,
+        /// Day to remove the meal from; with --all, only meals on this day
+        #[arg(short, long, required_unless_present = "all")]
+        day: Option<String>,
+        /// Remove every meal matching the other options after one confirmation
+        #[arg(long)]
+        all: bool,
+        /// With --all, only meals this cook is cooking
+        #[arg(short, long, requires = "all")]
+        cook: Option<String>,
+        /// With --all, only meals matching an expression, e.g. 'cook == "Alice" && tag ~ veg'
+        #[arg(long, requires = "all")]
+        filter: Option<String>,
+        /// With --all, only meals of a week: this, next, or a date in it
+        #[arg(long, requires = "all")]
+        week: Option<String>,
+        /// With --all, remove without asking first
+        #[arg(short, long, requires = "all")]
+        yes: bool,
+    },
+    /// Copy a meal to other days, keeping its description, cook, tags and recipe
+    Clone {
         #[arg(short, long)]
         meal_type: String,
+        /// Day of the meal to copy
         #[arg(short, long)]
         day: String,
+        /// Day to copy it to, or several separated by commas, e.g. wed,fri
+        #[arg(long)]
+        to: String,
     },
     /// Export the meal plan to iCal format
     ExportIcal {
-        #[arg(short, long)]
-        output: PathBuf,
+        /// File to write; defaults to the file given with --merge
+        #[arg(short, long, required_unless_present = "merge")]
+        output: Option<PathBuf>,
+        /// Only export meals added or edited since a date (YYYY-MM-DD) or period (e.g. 7d)
+        #[arg(long, conflicts_with = "merge")]
+        modified_since: Option<String>,
+        /// Update the plan's events in an existing calendar, keeping its other events
+        #[arg(long)]
+        merge: Option<PathBuf>,
+        /// With --merge, which side wins for meals changed in both the plan and the calendar
+        #[arg(long, value_enum, requires = "merge")]
+        prefer: Option<CalendarPreference>,
+        /// Only include meals matching an expression, e.g. 'cook == "Alice" && tag ~ veg'
+        #[arg(long)]
+        filter: Option<String>,
+        /// Also add shopping trips on the configured shopping days and prep to-dos for meals with a recipe
+        #[arg(long)]
+        workflow: bool,
+        /// Plan file (JSON) to export instead of the stored plan, or - to read it from stdin
+        #[arg(long)]
+        input: Option<PathBuf>,
+        /// Write one file per week or month into the --output directory, archived weeks included
+        #[arg(long, value_enum, conflicts_with_all = ["merge", "modified_since", "workflow"])]
+        split: Option<CalendarSplit>,
+        /// With --split, the number of weeks to export, counting back from the current one; defaults to all of them
+        #[arg(long, requires = "split")]
+        weeks: Option<usize>,
     },
     /// Export the meal plan to JSON format
     ExportJson {
         #[arg(short, long)]
         output: PathBuf,
+        /// Only export meals added or edited since a date (YYYY-MM-DD) or period (e.g. 7d)
+        #[arg(long)]
+        modified_since: Option<String>,
+        /// Only include meals matching an expression, e.g. 'cook == "Alice" && tag ~ veg'
+        #[arg(long)]
+        filter: Option<String>,
+        /// Plan file (JSON) to export instead of the stored plan, or - to read it from stdin
+        #[arg(long)]
+        input: Option<PathBuf>,
+    },
+    /// Export the week as a web page, with the photos of the meals
+    ExportHtml {
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Only include meals matching an expression, e.g. 'cook == "Alice" && tag ~ veg'
+        #[arg(long)]
+        filter: Option<String>,
+        /// Plan file (JSON) to export instead of the stored plan, or - to read it from stdin
+        #[arg(long)]
+        input: Option<PathBuf>,
+    },
+    /// Export the plan as a spreadsheet with one sheet per week, colored by cook
+    ExportXlsx {
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Number of weeks to include, counting back from the current one
+        #[arg(short, long, default_value_t = 1)]
+        weeks: usize,
+        /// Only include meals matching an expression, e.g. 'cook == "Alice" && tag ~ veg'
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Export every archived meal as one table (date, slot, dish, cook, tags, rating, cost),
+    /// for analysis in pandas, R or a spreadsheet
+    ExportHistory {
+        #[arg(long, value_enum, default_value_t = HistoryFormat::Csv)]
+        format: HistoryFormat,
+        /// File to write; CSV is printed when not given
+        #[arg(short, long, required_if_eq("format", "parquet"))]
+        output: Option<PathBuf>,
+        /// Only include meals matching an expression, e.g. 'cook == "Alice" && tag ~ veg'
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Export the meal plan as a QR code, rendered in the terminal or saved as a PNG
+    ExportQr {
+        /// PNG file to write instead of rendering in the terminal
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Encode a link to the live plan instead of the plan itself
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// List the packed meals to assemble for a day, such as tomorrow's school lunches
+    PackingList {
+        /// Day to pack for: today, tomorrow, a date, a day name or a number
+        #[arg(short, long, default_value = "tomorrow")]
+        day: String,
+        /// Write a Markdown checklist instead of plain text
+        #[arg(long)]
+        checklist: bool,
+        /// Also send the list as a notification, e.g. from an evening cron job
+        #[arg(long)]
+        notify: bool,
+    },
+    /// Walk through the recipe of a planned meal step by step, with timers for timed steps
+    Cook {
+        /// Day of the meal: today, tomorrow, a date, a day name or a number
+        #[arg(short, long, default_value = "today")]
+        day: String,
+        #[arg(short = 't', long, default_value = "dinner")]
+        meal_type: String,
+    },
+    /// Print today's and tomorrow's meals as a compact snippet for /etc/motd, tmux or conky
+    Motd {
+        /// Widest a line may be, in characters; longer lines are cut short
+        #[arg(short, long, default_value_t = 40)]
+        width: usize,
+        /// What to show of each meal, in order: type, dish and cook
+        #[arg(long, default_value = "type,dish,cook")]
+        fields: String,
+        /// Put both days on a single line, e.g. for a status bar
+        #[arg(long)]
+        line: bool,
+    },
+    /// Email each cook the week's plan: the meals they're cooking, their prep and the
+    /// shared grocery list
+    Digest {
+        /// Send only this cook's digest
+        #[arg(long)]
+        cook: Option<String>,
+        /// Print the emails instead of sending them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Serve the plan over HTTP, e.g. `/today/speech` for a voice assistant to read out
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// Turn down edits from the web app, only showing the plan
+        #[arg(long)]
+        read_only: bool,
+    },
+    /// Post the daily menu to a Matrix room and answer !meal commands there
+    Matrix {
+        #[command(subcommand)]
+        action: MatrixAction,
+    },
+    /// Send a reminder to the configured push services or the desktop, now or at a time.
+    /// Without a message, lists the reminders waiting to be sent.
+    Remind {
+        /// What to be reminded of, e.g. "Defrost the chicken"
+        message: Option<String>,
+        /// When to send it: a time such as 20:00 or 8pm, or a date and time (YYYY-MM-DD HH:MM).
+        /// Timed reminders are sent by the daemon, or by `remind --send-due` from cron.
+        #[arg(long, requires = "message")]
+        at: Option<String>,
+        /// Send the reminders that have come due
+        #[arg(long, conflicts_with = "message")]
+        send_due: bool,
+    },
+    /// List the notifications and messages waiting for the network to come back, or send them
+    Outbox {
+        /// Send what is waiting now rather than listing it
+        #[arg(long)]
+        flush: bool,
+    },
+    /// Copy the week's plan to the clipboard for pasting into a chat or an email
+    Copy {
+        /// Text format (markdown or plain)
+        #[arg(short, long, default_value = "markdown")]
+        format: String,
+        /// Hold text read from stdin on the clipboard until something else is copied
+        #[arg(long, hide = true)]
+        hold: bool,
     },
     /// Sync the meal plan between JSON and Markdown formats
     Sync {
         /// Source format to sync from (json, markdown, or auto)
         #[arg(short, long, default_value = "auto")]
         source: String,
+
+        #[command(subcommand)]
+        action: Option<SyncAction>,
+    },
+    /// Open the meal plan or configuration in $EDITOR, or in the default app if it isn't set
+    Open {
+        /// File to open (json, md or config)
+        #[arg(default_value = "json")]
+        file: String,
+        /// Print the file's path instead of opening it
+        #[arg(long)]
+        print: bool,
     },
     /// Initialize or update the configuration
     Config {
         #[command(subcommand)]
         action: ConfigAction,
     },
+    /// Keep the tokens integrations sign in with in the system keyring, out of the configuration
+    Auth {
+        #[command(subcommand)]
+        action: AuthAction,
+    },
+    /// Track the grocery list for the current week
+    Grocery {
+        /// Split the list into separate sections per preferred store
+        #[arg(long)]
+        by_store: bool,
+        /// Cover the whole week's meals, not just those until the next shopping day
+        #[arg(long)]
+        week: bool,
+
+        #[command(subcommand)]
+        action: Option<GroceryAction>,
+    },
+    /// Import meals from a spreadsheet (xlsx, xls, ods or csv), into this week and the archive,
+    /// or recipes and past plans from another meal-planning app's export
+    Import {
+        #[arg(short, long)]
+        file: PathBuf,
+        /// Which column holds each field, e.g. "A=day,B=meal_type,C=description,D=cook"
+        #[arg(short, long, required_unless_present = "from", conflicts_with = "from")]
+        map: Option<String>,
+        /// Read the file as an export from this app, with its recipes going into the recipe book
+        #[arg(long, value_enum)]
+        from: Option<apps::App>,
+        /// The first row holds meals rather than column headings
+        #[arg(long, conflicts_with = "from")]
+        no_header: bool,
+        /// Check every row and report problems without importing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// What to do with a meal for a slot that's already planned; asks when not given and
+        /// run at a terminal, and skips otherwise
+        #[arg(long, value_enum)]
+        on_duplicate: Option<duplicates::Strategy>,
+    },
+    /// Apply add, edit and remove operations written as newline-delimited JSON, all or nothing
+    Apply {
+        /// File of operations, or - to read them from stdin
+        file: PathBuf,
+        /// Check the operations and show what they would do without saving
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Plan interactively at a prompt, with the plan loaded once and saved on :w or when leaving
+    Shell,
+    /// Check the configuration and storage for problems and suggest fixes
+    Doctor,
+    /// Show the commands of a common workflow, such as planning a week, ready to copy
+    Examples {
+        /// Workflow to show; lists them when left out
+        topic: Option<String>,
+    },
+    /// Upgrade plan files written by older versions to the current format, keeping a backup
+    Migrate {
+        /// Report what would change without touching any files
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Share a week's plan with another household as a single bundle file
+    Bundle {
+        #[command(subcommand)]
+        action: BundleAction,
+    },
+    /// Treat different names for an ingredient as one on grocery lists, prices and receipts
+    Ingredient {
+        #[command(subcommand)]
+        action: IngredientAction,
+    },
+    /// Keep a recipe book and search it
+    Recipe {
+        #[command(subcommand)]
+        action: RecipeAction,
+    },
+    /// Install template packs of recipes, meal aliases and week templates, such as "Summer
+    /// grilling"
+    Pack {
+        #[command(subcommand)]
+        action: PackAction,
+    },
+    /// Cycle staple recipes through the weeks, one recipe per week on the same day
+    Rotation {
+        #[command(subcommand)]
+        action: RotationAction,
+    },
+    /// Plan the children's school lunchboxes from a pool of approved mains, fruit and snacks
+    Lunchbox {
+        #[command(subcommand)]
+        action: LunchboxAction,
+    },
+    /// Keep a list of restaurants and takeout places
+    #[command(alias = "place")]
+    Places {
+        #[command(subcommand)]
+        action: PlaceAction,
+    },
+    /// Compare what was spent on groceries with what was expected
+    Budget {
+        #[command(subcommand)]
+        action: BudgetAction,
+    },
+    /// Log food that goes to waste and report on it by month
+    Waste {
+        #[command(subcommand)]
+        action: WasteAction,
+    },
+    /// Keep track of what's in the kitchen
+    Pantry {
+        #[command(subcommand)]
+        action: PantryAction,
+    },
+    /// Record who's in the household and who's away for which meals, for grocery amounts
+    /// and allergy checks
+    Attendance {
+        #[command(subcommand)]
+        action: AttendanceAction,
+    },
+    /// Rank recipes by how much of them the pantry already has, for when the plan falls apart
+    #[command(name = "whatcanimake")]
+    WhatCanIMake {
+        /// Leave out recipes whose steps take longer than this many minutes
+        #[arg(long)]
+        max_minutes: Option<u32>,
+        /// How many recipes to show
+        #[arg(short = 'n', long, default_value_t = 10)]
+        limit: usize,
+    },
+    /// Manage shortcuts that expand to a full meal description, tags and recipe
+    Alias {
+        #[command(subcommand)]
+        action: AliasAction,
+    },
+    /// Manage the household's cooks and when they are available
+    Cooks {
+        #[command(subcommand)]
+        action: CookAction,
+    },
+    /// Check the plan for meals assigned to cooks who are unavailable that day, against the
+    /// rules in check_rules.yaml, and against the weekly and monthly takeout budgets
+    Check {
+        /// Rules file (YAML) to check against instead of check_rules.yaml in the storage path
+        #[arg(long)]
+        rules: Option<PathBuf>,
+        /// Output format (text or json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+        /// Also send budget alerts through the notification backends, each one only once
+        #[arg(long)]
+        notify: bool,
+    },
+    /// Fill the week's empty slots from the recipe book, following the configured plan_rules
+    Generate {
+        #[arg(short = 't', long, default_value = "dinner")]
+        meal_type: String,
+        /// Only fill these days, separated by commas, e.g. mon,wed or weekdays
+        #[arg(short, long, visible_alias = "days")]
+        day: Option<String>,
+        /// Show the plan that would be made without saving it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Cook once, eat twice: plan a meal and its leftovers later in the week
+    Batch {
+        /// Description of the meal
+        #[arg(required_unless_present = "suggest")]
+        description: Option<String>,
+
+        #[arg(short = 't', long, required_unless_present = "suggest")]
+        meal_type: Option<String>,
+        /// Day the meal is cooked
+        #[arg(short, long, required_unless_present = "suggest")]
+        day: Option<String>,
+        /// Day the leftovers are eaten
+        #[arg(short, long, required_unless_present = "suggest")]
+        leftovers: Option<String>,
+        /// Meal type for the leftovers (defaults to the same meal type)
+        #[arg(long)]
+        leftovers_meal_type: Option<String>,
+        /// Cook for the meal, or "auto" to pick the next cook in the rotation
+        #[arg(short, long, required_unless_present = "suggest")]
+        cook: Option<String>,
+        /// Ingredient to add to the grocery list for the double batch (repeatable)
+        #[arg(short, long)]
+        ingredient: Vec<String>,
+        /// List the recipes tagged batch-friendly or freezes-well instead of planning a meal
+        #[arg(long, conflicts_with_all = ["description", "meal_type", "day", "leftovers", "leftovers_meal_type", "cook", "ingredient"])]
+        suggest: bool,
+    },
+    /// Search every meal planned, this week and archived, by description, cook or tag
+    Search {
+        /// Words to find; quote a phrase with "..." and end a word with * to match its start
+        query: String,
+        /// Show at most this many meals
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// List dishes from past weeks that haven't been planned in a while
+    Stale {
+        /// How far back counts as recent, e.g. 60d or 8w
+        #[arg(long, default_value = "60d")]
+        since: String,
+    },
+    /// Report on past weeks' meals
+    Stats {
+        #[command(subcommand)]
+        action: StatsAction,
+    },
+    /// Send the week's plan to an online service
+    Push {
+        #[command(subcommand)]
+        target: PushTarget,
+    },
+    /// Summarize a month or year of meals from the archive
+    Report {
+        /// Month to report on, as YYYY-MM
+        #[arg(long, conflicts_with = "year", required_unless_present = "year")]
+        month: Option<String>,
+        /// Year to report on
+        #[arg(long)]
+        year: Option<i32>,
+        /// Output format (markdown or html)
+        #[arg(short, long, default_value = "markdown")]
+        format: String,
+        /// File to write the report to instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Only include meals matching an expression, e.g. 'cook == "Alice" && tag ~ veg'
+        #[arg(long)]
+        filter: Option<String>,
+    },
+    /// Archive the finished week and start the next one
+    Rollover {
+        /// Only roll over once the week has ended, catching up on missed weeks (for cron jobs).
+        /// Use --force instead to roll over before then.
+        #[arg(long)]
+        auto: bool,
+    },
+    /// Mark a week read-only, e.g. once the shopping is done. Changing its meals then needs
+    /// --force; marking them cooked and rating them doesn't.
+    Freeze {
+        /// Week to freeze: this, next, last, a number of weeks ago, or a date in the week
+        #[arg(long, default_value = "this")]
+        week: String,
+    },
+    /// Make a frozen week editable again
+    Unfreeze {
+        /// Week to unfreeze, in the same forms as for freeze
+        #[arg(long, default_value = "this")]
+        week: String,
+    },
+    /// Keep named copies of the week to try out other plans and go back to
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Ask another cook to take over a meal. They're notified, and the meal is theirs once
+    /// they run swap-accept.
+    SwapRequest {
+        #[arg(short = 't', long)]
+        meal_type: String,
+        #[arg(short, long)]
+        day: String,
+        /// Cook to hand the meal to
+        #[arg(long)]
+        to: String,
+    },
+    /// Take over a meal another cook asked you to cook. Without --day or --meal-type, the
+    /// only request waiting for you is accepted.
+    SwapAccept {
+        #[arg(short = 't', long)]
+        meal_type: Option<String>,
+        #[arg(short, long)]
+        day: Option<String>,
+        /// Cook accepting, when it isn't the configured user_name
+        #[arg(long = "as", value_name = "COOK")]
+        cook: Option<String>,
+    },
+    /// Turn down a meal another cook asked you to cook
+    SwapDecline {
+        #[arg(short = 't', long)]
+        meal_type: Option<String>,
+        #[arg(short, long)]
+        day: Option<String>,
+        /// Cook declining, when it isn't the configured user_name
+        #[arg(long = "as", value_name = "COOK")]
+        cook: Option<String>,
+    },
+    /// List the swap requests waiting to be accepted
+    Swaps,
+    /// Record that a meal was cooked, optionally rating it
+    Cooked {
+        #[arg(short, long)]
+        meal_type: String,
+        #[arg(short, long)]
+        day: String,
+        /// How it turned out, from 1 to 5
+        #[arg(short, long, value_parser = clap::value_parser!(u8).range(1..=5))]
+        rating: Option<u8>,
+    },
+    /// Summarize the week: meals planned vs. cooked, spending vs. budget, ratings and leftovers
+    Review {
+        /// Also append the summary to a journal file (defaults to review_journal.md in the storage path)
+        #[arg(long, num_args = 0..=1, value_name = "FILE")]
+        journal: Option<Option<PathBuf>>,
+    },
+    /// Compare two weeks: dishes served in both, cook changes and grocery spending
+    Compare {
+        /// Week to look at: this, last, a number of weeks ago, or a date in the week
+        #[arg(long, default_value = "this")]
+        week: String,
+        /// Week to compare it with, in the same forms
+        #[arg(long, default_value = "last")]
+        with: String,
+    },
+    /// List or restore meals removed this week
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+    /// Show the latest changes recorded in the change log, and who made them
+    History {
+        /// Number of changes to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Maintain the archive of past weeks
+    Archive {
+        #[command(subcommand)]
+        action: ArchiveAction,
+    },
+    /// Keep the JSON and Markdown files in sync continuously
+    Daemon {
+        /// Seconds between checks for changed files
+        #[arg(long, default_value_t = 1)]
+        interval: u64,
+        /// Seconds to wait for changes to settle before syncing
+        #[arg(long, default_value_t = 2)]
+        debounce: u64,
+        /// A copy of the plan kept elsewhere, such as in a shared folder, to merge with and
+        /// keep up to date. Can be given more than once.
+        #[arg(long = "remote", value_name = "FILE")]
+        remotes: Vec<PathBuf>,
+
+        #[command(subcommand)]
+        action: Option<DaemonAction>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AuthAction {
+    /// Store a token, typed at the prompt or piped in. For google, pipe in the service
+    /// account key file. A copy left in the configuration file is taken out.
+    Set {
+        integration: secrets::Integration,
+    },
+    /// Forget a stored token
+    Remove {
+        integration: secrets::Integration,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum IngredientAction {
+    /// List the ingredients that go by other names
+    List,
+    /// Treat ingredients as another one from now on, e.g. `merge scallion "spring onion" --into "green onion"`
+    Merge {
+        #[arg(required = true)]
+        names: Vec<String>,
+        #[arg(long)]
+        into: String,
+    },
 }
 
 #[derive(Subcommand, Debug)]
 enum ConfigAction {
     /// Initialize the configuration
     Init,
+    /// Check the configuration file for mistakes, such as unknown keys or a storage path that can't be written
+    Validate,
 }
 
-fn main() -> Result<(), String> {
-    // Set up panic handler for unexpected errors
-    std::panic::set_hook(Box::new(|panic_info| {
-        eprintln!("An unexpected error occurred: {}", panic_info);
-        eprintln!("Please report this issue to the developers.");
-    }));
+#[derive(Subcommand, Debug)]
+enum SyncAction {
+    /// Exchange the plan with another machine on the local network
+    Peer {
+        /// Address to wait on for a peer, e.g. 0.0.0.0:7878
+        #[arg(long, conflicts_with = "connect", required_unless_present = "connect")]
+        listen: Option<String>,
+        /// Address of a listening peer, e.g. kitchen-pi.local:7878
+        #[arg(long)]
+        connect: Option<String>,
+        /// What to do with a slot both machines planned with different meals; asks when not
+        /// given and run at a terminal, and keeps the more recently edited meal otherwise
+        #[arg(long, value_enum)]
+        on_duplicate: Option<duplicates::Strategy>,
+    },
+    /// Merge another copy of the plan file, e.g. a conflicted copy from Dropbox or Syncthing
+    Merge {
+        file: PathBuf,
+        /// What to do with a slot both copies planned with different meals; asks when not
+        /// given and run at a terminal, and keeps the more recently edited meal otherwise
+        #[arg(long, value_enum)]
+        on_duplicate: Option<duplicates::Strategy>,
+    },
+    /// Show whether the JSON and Markdown files are in sync and what a sync would do
+    Status,
+}
 
-    if let Err(e) = run() {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
-    }
-    
-    Ok(())
+#[derive(Subcommand, Debug)]
+enum DaemonAction {
+    /// Write a systemd user unit that starts the daemon at login
+    Install,
+    /// Show the status of the running daemon
+    Status,
 }
 
-/// Main application logic, separated to allow for proper error handling
-fn run() -> Result<(), String> {
-    let args = Args::parse();
+#[derive(Subcommand, Debug)]
+enum GroceryAction {
+    /// Add an item to the grocery list
+    Add {
+        item: String,
 
-    // Load configuration
-    let config_dir = dirs::home_dir()
-        .ok_or_else(|| "Could not determine home directory".to_string())?
-        .join(".config")
-        .join("mealplan");
-    
-    let config_path = config_dir.join("config.json");
-    
-    // Try to load config or create default
-    let config = if config_path.exists() {
-        match Config::load(&config_path) {
-            Ok(config) => config,
-            Err(e) => {
-                eprintln!("Warning: Failed to load configuration: {}", e);
-                eprintln!("Using default configuration instead.");
-                Config::new()
-            }
-        }
-    } else {
-        if args.command.as_ref().map_or(false, |cmd| {
-            matches!(cmd, Commands::Config { action: ConfigAction::Init })
-        }) {
-            // Don't show warning if user is running config init
-        } else {
-            eprintln!("Warning: No configuration file found at {:?}", config_path);
-            eprintln!("Using default configuration. Run 'mealplan config init' to create a configuration file.");
-        }
-        Config::new()
-    };
+        /// Category used to look up the preferred store (e.g. dairy, produce)
+        #[arg(short, long)]
+        category: Option<String>,
+    },
+    /// Mark an item as purchased
+    Check {
+        item: String,
+        /// What you paid, counted against the weekly budget in `mealplan review`
+        #[arg(long, value_parser = parse_price)]
+        price: Option<f64>,
+    },
+    /// Mark an item as not purchased
+    Uncheck {
+        item: String,
+    },
+    /// Show the items that still need to be purchased
+    Status,
+    /// Record what items cost and see how their prices have moved
+    Cost {
+        #[command(subcommand)]
+        action: CostAction,
+    },
+    /// Export the items still to buy for an online grocery cart
+    Export {
+        /// Output format: csv or json with name, quantity, unit and note columns, or lines
+        /// with one item per line for pasting into a store's app
+        #[arg(short, long, default_value = "csv")]
+        format: String,
+        /// File to write the export to instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
 
-    // Determine storage path (from args or config)
-    let storage_path = match &args.path {
-        Some(path) => path.clone(),
-        None => config.meal_plan_storage_path.clone(),
-    };
-    
-    // Ensure storage directory exists
-    if !storage_path.exists() {
-        std::fs::create_dir_all(&storage_path)
-            .map_err(|e| format!("Failed to create storage directory: {}", e))?;
-    }
+#[derive(Subcommand, Debug)]
+enum CostAction {
+    /// Record what an item cost today, setting its price on the list too
+    Set {
+        item: String,
+        #[arg(value_parser = parse_price)]
+        price: f64,
+    },
+    /// Show the prices recorded for an item, or the latest price of every item
+    History {
+        item: Option<String>,
+    },
+}
 
-    let meal_plan_path = storage_path.join("meal_plan.json");
+#[derive(Subcommand, Debug)]
+enum RecipeAction {
+    /// Add a recipe, or replace it if one with the same name exists
+    Add {
+        name: String,
+        /// Tags for the recipe, comma separated or repeated
+        #[arg(long, value_delimiter = ',')]
+        tags: Vec<String>,
+        /// An ingredient of the recipe (repeatable)
+        #[arg(short, long)]
+        ingredient: Vec<String>,
+        /// Where the full recipe can be found
+        #[arg(short, long)]
+        link: Option<String>,
+        /// How many servings the recipe makes, for planning its leftovers
+        #[arg(short, long)]
+        servings: Option<u32>,
+        /// Photo of the dish: an image file, copied into the assets directory, or a web address
+        #[arg(long)]
+        photo: Option<String>,
+        /// A step of the method, in order (repeatable); durations such as "simmer 20 minutes"
+        /// get a timer in `mealplan cook`
+        #[arg(long)]
+        step: Vec<String>,
+        /// An appliance the dish keeps busy and for how long, e.g. "oven 200C 45 min"
+        /// (repeatable)
+        #[arg(long)]
+        equipment: Vec<String>,
+    },
+    /// Import a recipe from a web page, from its recipe data or with an adapter for the site
+    Import {
+        /// Address of the recipe page
+        url: String,
+        /// Show the recipe that was read without saving it
+        #[arg(long)]
+        dry_run: bool,
+        /// Fetch the page again even if a copy fetched in the last week is cached
+        #[arg(long)]
+        refresh: bool,
+    },
+    /// Remove a recipe
+    Remove {
+        name: String,
+    },
+    /// List the recipes
+    List,
+    /// Find recipes by fuzzy matching words against their names, tags and ingredients
+    Search {
+        /// Words to look for, such as "chick parm"
+        query: Option<String>,
+        /// Only recipes with an ingredient containing this (repeatable)
+        #[arg(short, long)]
+        ingredient: Vec<String>,
+        /// Only recipes with this tag (repeatable)
+        #[arg(long)]
+        tag: Vec<String>,
+    },
+    /// Show a recipe's ingredients scaled up or down, converting units where it helps
+    Scale {
+        name: String,
+        /// How much to make, such as 2 for a double batch or 0.5 for half
+        factor: f64,
+    },
+}
 
-    // Load or create a new meal plan
-    let mut meal_plan = match MealPlan::load_from_json(&meal_plan_path) {
-        Ok(plan) => plan,
-        Err(e) => {
-            if meal_plan_path.exists() {
-                eprintln!("Warning: Failed to load meal plan: {}", e);
-                eprintln!("Creating a new meal plan instead.");
-            } else {
-                println!("No existing meal plan found. Creating a new one.");
-            }
-            MealPlan::new(Local::now().date_naive())
-        }
-    };
+#[derive(Subcommand, Debug)]
+enum PackAction {
+    /// List the packs that come with mealplan and the ones installed
+    List,
+    /// Install a pack: the name of one that comes with mealplan, a file or a web address
+    Install {
+        source: String,
+        /// Overwrite recipes, aliases and template meals the household already has
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Take out what an installed pack added, leaving anything changed since
+    Remove {
+        name: String,
+    },
+}
 
-    match args.command {
-        Some(Commands::Add { description, meal_type, day, cook}) => {
-            add_meal(&mut meal_plan, meal_type, day, cook, description)?;
-            println!("Meal added successfully.");
-            
-            // Save the updated meal plan
-            meal_plan.save_to_json(&meal_plan_path)
-                .map_err(|e| format!("Failed to save meal plan: {}", e))?;
-            
-            // Also update markdown for consistency
-            let markdown_path = storage_path.join("meal_plan.md");
-            if let Err(e) = meal_plan.save_to_markdown(&markdown_path) {
-                eprintln!("Warning: Failed to update markdown file: {}", e);
-            }
-        }
-        Some(Commands::Edit { description, meal_type, day, cook }) => {
-            edit_meal(&mut meal_plan, meal_type, day, cook, description)?;
-            println!("Meal updated successfully.");
-            
-            // Save the updated meal plan
-            meal_plan.save_to_json(&meal_plan_path)
-                .map_err(|e| format!("Failed to save meal plan: {}", e))?;
-            
-            // Also update markdown for consistency
-            let markdown_path = storage_path.join("meal_plan.md");
-            if let Err(e) = meal_plan.save_to_markdown(&markdown_path) {
-                eprintln!("Warning: Failed to update markdown file: {}", e);
-            }
-        }
-        Some(Commands::Remove { meal_type, day }) => {
-            remove_meal(&mut meal_plan, meal_type, day)?;
-            println!("Meal removed successfully.");
-            
-            // Save the updated meal plan
-            meal_plan.save_to_json(&meal_plan_path)
-                .map_err(|e| format!("Failed to save meal plan: {}", e))?;
-            
-            // Also update markdown for consistency
-            let markdown_path = storage_path.join("meal_plan.md");
-            if let Err(e) = meal_plan.save_to_markdown(&markdown_path) {
-                eprintln!("Warning: Failed to update markdown file: {}", e);
-            }
-        }
-        Some(Commands::ExportIcal { output }) => {
-            export_ical(&meal_plan, &output)?;
-            println!("Meal plan exported to iCal successfully: {:?}", output);
-        }
-        Some(Commands::ExportJson { output }) => {
-            export_json(&meal_plan, &output)?;
-            println!("Meal plan exported to JSON successfully: {:?}", output);
-        }
-        Some(Commands::Sync { source }) => {
-            let config_with_storage = Config {
-                meal_plan_storage_path: storage_path.clone(),
-                current_week_start_date: config.current_week_start_date,
+#[derive(Subcommand, Debug)]
+enum LunchboxAction {
+    /// Add an approved item to the pool, or replace one with the same name
+    Add {
+        name: String,
+        /// Where it goes in the box: main, fruit or snack
+        #[arg(long)]
+        part: String,
+        /// What's in it, such as nuts or dairy, for exclusions to match (repeatable)
+        #[arg(long)]
+        tag: Vec<String>,
+        /// What to put on the grocery list for each box, e.g. apples; defaults to the name
+        #[arg(long)]
+        buy: Option<String>,
+    },
+    /// Remove an item from the pool
+    Remove {
+        name: String,
+    },
+    /// Add a child to pack lunches for, or replace their exclusions
+    Child {
+        name: String,
+        /// Tags or words of items to leave out of their box, e.g. nuts (repeatable)
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// Stop packing lunches for the child
+        #[arg(long, conflicts_with = "exclude")]
+        remove: bool,
+    },
+    /// List the pool of items and the children
+    List,
+    /// Pack a lunch for each child on this week's school days
+    Generate {
+        /// School days, separated by commas
+        #[arg(short, long, visible_alias = "days", default_value = "weekdays")]
+        day: String,
+        /// Also add what the lunches need to the grocery list
+        #[arg(long)]
+        grocery: bool,
+    },
+    /// Show this week's lunches
+    Show,
+}
+
+#[derive(Subcommand, Debug)]
+enum RotationAction {
+    /// Create a rotation from recipes in the recipe book, or replace one with the same name
+    Create {
+        name: String,
+        /// Recipes in the order they take turns
+        #[arg(required = true)]
+        recipes: Vec<String>,
+        #[arg(short = 't', long, default_value = "dinner")]
+        meal_type: String,
+        /// Day of the week the recipes are planned on
+        #[arg(short, long)]
+        day: String,
+        /// Cook for the planned meals; defaults to the next cook in the cook rotation
+        #[arg(short, long)]
+        cook: Option<String>,
+    },
+    /// Remove a rotation
+    Remove {
+        name: String,
+    },
+    /// List the rotations and the recipe each gives next
+    List,
+    /// Plan this week's recipe from a rotation, or from every rotation if none is named
+    Apply {
+        name: Option<String>,
+        /// Cook for this week's meals instead of the rotation's
+        #[arg(short, long)]
+        cook: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PlaceAction {
+    /// Add a place, or replace it if one with the same name exists
+    Add {
+        name: String,
+        #[arg(long)]
+        cuisine: Option<String>,
+        /// Phone number or website for ordering
+        #[arg(long)]
+        contact: Option<String>,
+        /// What a meal from the place usually costs
+        #[arg(long)]
+        cost: Option<f64>,
+    },
+    /// Remove a place
+    Remove {
+        name: String,
+    },
+    /// List the places
+    List,
+    /// Suggest places not ordered from lately
+    Suggest {
+        /// How many weeks back counts as lately
+        #[arg(short, long, default_value_t = 4)]
+        weeks: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BudgetAction {
+    /// Import a receipt or bank statement (csv, xlsx, xls or ods) as the week's actual spending,
+    /// pricing the grocery list items its lines match
+    ImportReceipt {
+        file: PathBuf,
+        /// Show what would be matched without saving anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SnapshotAction {
+    /// Save the week's meals as they are now, e.g. `snapshot create "before guests"`
+    Create {
+        name: String,
+        /// Take a snapshot that already has this name again
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Put the week's meals back the way they were in a snapshot
+    Restore {
+        name: String,
+    },
+    /// List the snapshots
+    List,
+    /// Delete a snapshot
+    Delete {
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PantryAction {
+    /// Note that the kitchen has these ingredients
+    Add {
+        #[arg(required = true)]
+        items: Vec<String>,
+    },
+    /// Note that these ingredients have run out
+    Remove {
+        #[arg(required = true)]
+        items: Vec<String>,
+    },
+    /// List what's in the pantry
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+enum AttendanceAction {
+    /// Add someone to the household, or allergies to someone already in it
+    Member {
+        name: String,
+        /// Something they're allergic to, such as peanut; may be given more than once
+        #[arg(long = "allergy")]
+        allergies: Vec<String>,
+    },
+    /// Take someone out of the household
+    RemoveMember {
+        name: String,
+    },
+    /// Mark someone away for a day's meals, e.g. `attendance away Sam --day tue`
+    Away {
+        name: String,
+        #[arg(short, long)]
+        day: String,
+        /// Only the one meal; every meal that day when left out
+        #[arg(short = 't', long)]
+        meal_type: Option<String>,
+    },
+    /// Mark someone eating a day's meals again
+    Back {
+        name: String,
+        #[arg(short, long)]
+        day: String,
+        /// Only the one meal; every meal that day when left out
+        #[arg(short = 't', long)]
+        meal_type: Option<String>,
+    },
+    /// Show the household and who's missing which of the week's meals
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+enum WasteAction {
+    /// Record food that was thrown away
+    Log {
+        /// What was thrown away, such as "half a bag of spinach"
+        item: String,
+        /// Recipe from the recipe book the food was bought or cooked for
+        #[arg(short, long)]
+        recipe: Option<String>,
+        /// Day it was thrown away, as a weekday of this week or a date (defaults to today)
+        #[arg(short, long)]
+        day: Option<String>,
+    },
+    /// List everything logged
+    List,
+    /// Report on a month's waste, with the recipes behind it
+    Report {
+        /// Month to report on, as YYYY-MM (defaults to this month)
+        #[arg(long)]
+        month: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AliasAction {
+    /// Add an alias, or replace it if it already exists
+    Add {
+        name: String,
+        /// Full description the alias stands for
+        description: String,
+        /// Tags for the meal, comma separated or repeated
+        #[arg(long, value_delimiter = ',')]
+        tags: Vec<String>,
+        /// Recipe name or link
+        #[arg(long)]
+        recipe: Option<String>,
+    },
+    /// Remove an alias
+    Remove {
+        name: String,
+    },
+    /// List the aliases
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+enum CookAction {
+    /// Add a cook to the roster, or update their details if they are already on it
+    Add {
+        name: String,
+        /// Email address, used to invite the cook to exported calendar events
+        #[arg(short, long)]
+        email: Option<String>,
+        /// Dietary needs or favourite dishes (repeatable)
+        #[arg(long)]
+        preference: Vec<String>,
+    },
+    /// Remove a cook from the roster and the rotation
+    Remove {
+        name: String,
+    },
+    /// Mark days a cook can't cook, e.g. `cook unavailable Alice fridays`
+    Unavailable {
+        name: String,
+        #[arg(required = true)]
+        days: Vec<String>,
+    },
+    /// Make a cook available again on the given days
+    Available {
+        name: String,
+        #[arg(required = true)]
+        days: Vec<String>,
+    },
+    /// Show the roster with each cook's availability and meals this week
+    List,
+    /// Set the order cooks take turns in for `--cook auto`, or show it when no names are given
+    Rotation {
+        names: Vec<String>,
+    },
+    /// Show or edit what a cook takes on when cooks are picked automatically
+    Prefs {
+        #[command(subcommand)]
+        action: CookPrefsAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CookPrefsAction {
+    /// Show a cook's preferences
+    Show {
+        name: String,
+    },
+    /// Edit a cook's preferences as YAML in $EDITOR
+    Edit {
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TrashAction {
+    /// List removed meals that can still be restored
+    List,
+    /// Put a removed meal back in the plan
+    Restore {
+        /// Id shown by `trash list`
+        id: u32,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ArchiveAction {
+    /// Compress archived weeks with zstd to save space
+    Compact {
+        /// Only compress weeks that started longer ago than this, e.g. 12w or 90d
+        #[arg(long, default_value = "12w")]
+        older_than: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum StatsAction {
+    /// Show how repetitive dinners have been and which dishes come up most
+    Variety {
+        /// Number of weeks to look back over, including the current one
+        #[arg(short, long, default_value_t = 4)]
+        weeks: usize,
+        /// Output format (text, csv, or json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+        /// File to write the report to instead of printing it
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Only include meals matching an expression, e.g. 'cook == "Alice" && tag ~ veg'
+        #[arg(long)]
+        filter: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum MatrixAction {
+    /// Post a day's menu to the room once, e.g. from cron
+    Post {
+        /// Day to post: today, tomorrow, a date, a day name or a number
+        #[arg(short, long, default_value = "today")]
+        day: String,
+    },
+    /// Stay connected, posting the menu every morning and answering commands
+    Run,
+}
+
+#[derive(Subcommand, Debug)]
+enum PushTarget {
+    /// Write the week to a Google Sheet, always to the same block of cells (A1:H5)
+    Sheets {
+        /// ID of the spreadsheet, from its URL
+        #[arg(long)]
+        spreadsheet_id: String,
+        /// Name of the sheet (tab) to write to
+        #[arg(long, default_value = "Meal Plan")]
+        sheet: String,
+        /// Service account key file. Defaults to $GOOGLE_APPLICATION_CREDENTIALS, then the key
+        /// stored with `mealplan auth set google`, then an OAuth access token from
+        /// $GOOGLE_OAUTH_ACCESS_TOKEN.
+        #[arg(long)]
+        credentials: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BundleAction {
+    /// Package the current week into a bundle file
+    Export {
+        file: PathBuf,
+    },
+    /// Merge a bundle file into the current week, prompting on collisions
+    Import {
+        file: PathBuf,
+        /// What to do with a meal for a slot that's already planned, instead of asking
+        #[arg(long, value_enum)]
+        on_duplicate: Option<duplicates::Strategy>,
+    },
+}
+
+fn main() {
+    // Set up panic handler for unexpected errors
+    std::panic::set_hook(Box::new(|panic_info| {
+        eprintln!("An unexpected error occurred: {}", panic_info);
+        eprintln!("Please report this issue to the developers.");
+    }));
+
+    let args = Args::parse();
+    let error_format = args.error_format;
+    let result = match args.answers_file.clone() {
+        Some(path) => match Scripted::from_file(&path) {
+            Ok(mut answers) => {
+                let result = run(args, &mut answers);
+                if answers.remaining() > 0 {
+                    eprintln!("Warning: {} answer(s) in {:?} weren't asked for.", answers.remaining(), path);
+                }
+                result
+            }
+            Err(e) => Err(CliError::io("Failed to read the answers file", e)),
+        },
+        None => run(args, &mut Terminal),
+    };
+    if let Err(e) = result {
+        match error_format {
+            ErrorFormat::Text => eprintln!("Error: {}", e),
+            ErrorFormat::Json => eprintln!("{}", e.to_json()),
+        }
+        std::process::exit(e.exit_code());
+    }
+}
+
+/// Main application logic, separated to allow for proper error handling
+fn run(args: Args, prompter: &mut dyn Prompter) -> Result<(), CliError> {
+    // Examples only describe the CLI, so they need no configuration or plan
+    if let Some(Commands::Examples { topic }) = &args.command {
+        print!("{}", format_examples(topic.as_deref())?);
+        return Ok(());
+    }
+
+    // Load configuration
+    let config_path = match &args.config {
+        Some(path) => path.clone(),
+        None => settings::config_dir(args.profile.as_deref()).map_err(CliError::usage)?.join("config.json"),
+    };
+    let config_dir = settings::storage_dir_for(&config_path);
+    secrets::configure(secrets::SecretStore::new(config_dir.clone()));
+
+    // Try to load config or create default. The plan is kept next to the config by default.
+    let default_config = || Config {
+        meal_plan_storage_path: config_dir.clone(),
+        ..Config::new()
+    };
+    // Why the configuration file couldn't be read, if it couldn't. The defaults used instead
+    // must never be saved over the user's file.
+    let mut config_error = None;
+    let file_config = if config_path.exists() {
+        match Config::load(&config_path) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Warning: Failed to load configuration: {}", e);
+                eprintln!("Using default configuration instead.");
+                config_error = Some(e.to_string());
+                default_config()
+            }
+        }
+    } else {
+        if args.command.as_ref().is_some_and(|cmd| {
+            matches!(cmd, Commands::Config { action: ConfigAction::Init })
+        }) {
+            // Don't show warning if user is running config init
+        } else {
+            eprintln!("Warning: No configuration file found at {:?}", config_path);
+            eprintln!("Using default configuration. Run 'mealplan config init' to create a configuration file.");
+        }
+        default_config()
+    };
+    // Environment overrides apply to this run only; changes to the configuration are made
+    // to `file_config` so they never end up saved
+    let config = settings::apply_env_overrides(&file_config, std::env::vars())
+        .map_err(|e| CliError::usage(format!("Invalid environment override: {}", e)))?;
+
+    // Determine storage path (from args or config)
+    let storage_path = match &args.path {
+        Some(path) => path.clone(),
+        None => config.meal_plan_storage_path.clone(),
+    };
+    
+    // Ensure storage directory exists
+    if !storage_path.exists() {
+        std::fs::create_dir_all(&storage_path)
+            .map_err(|e| CliError::io("Failed to create storage directory", e))?;
+    }
+    http::configure(config.http.clone(), Some(storage_path.join(http::OUTBOX_FILE)));
+    ingredients::configure(load_ingredients(&storage_path));
+
+    let meal_plan_path = storage_path.join("meal_plan.json");
+
+    // Old plan files don't load, so they are upgraded before the plan is loaded
+    if let Some(Commands::Migrate { dry_run }) = args.command {
+        return migrate_storage(&storage_path, config.current_week_start_date, dry_run, config.backup_count);
+    }
+
+    // Load or create a new meal plan
+    let mut meal_plan = match load_meal_plan(&storage_path) {
+        Ok(plan) => plan,
+        Err(e) => {
+            if meal_plan_path.exists() {
+                eprintln!("Warning: Failed to load meal plan: {}", e);
+                eprintln!("If it was written by an older version, run 'mealplan migrate' to upgrade it.");
+                match keep_damaged_plan(&meal_plan_path) {
+                    Ok(damaged) => eprintln!("The damaged plan was copied to {:?}.", damaged),
+                    Err(e) => eprintln!("Warning: Failed to keep a copy of the damaged plan: {}", e),
+                }
+                eprintln!("Creating a new meal plan instead.");
+            } else {
+                println!("No existing meal plan found. Creating a new one.");
+            }
+            MealPlan::new(Local::now().date_naive())
+        }
+    };
+    meal_plan.replica_id = current_replica(&config);
+    meal_plan.user = Some(current_user(&config));
+    meal_plan.allow_frozen = args.force;
+    // A retention too long to count back from now keeps everything; `config validate` flags it
+    if let Some(cutoff) = Duration::try_days(config.trash_retention_days as i64).and_then(|days| Utc::now().checked_sub_signed(days)) {
+        meal_plan.purge_trash(cutoff);
+    }
+
+    match args.command {
+        Some(Commands::Add { description, meal_type, day, from, to, cook, packed, eat_out, photo }) => {
+            let day = add_days(&meal_plan, day, from, to)?;
+            let (description, extras) = meal_extras(&storage_path, description, packed, eat_out, photo)?;
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            add_command(&config, &mut meal_plan, &book, description, meal_type, day, cook.unwrap_or_default(), extras, prompter)?;
+            save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+        }
+        Some(Commands::Edit { description, meal_type, day, cook, packed, photo }) => {
+            edit_command(&config, &mut meal_plan, &storage_path, description, meal_type, day, cook, packed, photo, prompter)?;
+            save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+        }
+        Some(Commands::Remove { meal_type, day, all: false, .. }) => {
+            let id = remove_meal(&mut meal_plan, meal_type.unwrap_or_default(), day.unwrap_or_default(), prompter)?;
+            save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+            println!("Meal moved to the trash. Restore it with `mealplan trash restore {}`.", id);
+        }
+        Some(Commands::Remove { meal_type, day, all: true, cook, filter, week, yes }) => {
+            let matching = MealSelection { meal_type, day, cook, filter, week };
+            if remove_matching(&mut meal_plan, &matching, yes, prompter)? > 0 {
+                save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+            }
+        }
+        Some(Commands::Clone { meal_type, day, to }) => {
+            clone_meal(&mut meal_plan, &meal_type, &day, &to, &current_user(&config), prompter)?;
+            save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+        }
+        Some(Commands::Doctor) => {
+            let checks = doctor::run_checks(&config_path, &storage_path, &settings::ValidationContext::current());
+            for check in &checks {
+                println!("{}", check);
+            }
+            let failed = checks.iter().filter(|c| c.status == doctor::Status::Fail).count();
+            let warned = checks.iter().filter(|c| c.status == doctor::Status::Warn).count();
+            println!("{} passed, {} warning(s), {} failed.", checks.len() - failed - warned, warned, failed);
+            if failed > 0 {
+                return Err(CliError::new(error::ErrorKind::General, format!("{} check(s) failed.", failed)));
+            }
+        }
+        Some(Commands::Migrate { .. }) => unreachable!("handled before the plan is loaded"),
+        Some(Commands::Examples { .. }) => unreachable!("handled before the configuration is loaded"),
+        Some(Commands::Shell) => {
+            run_shell(&config, &mut meal_plan, &storage_path, prompter)?;
+        }
+        Some(Commands::ExportIcal { output, merge: Some(existing), prefer, filter, workflow, input, .. }) => {
+            let exported = filtered_plan(&input_plan(&meal_plan, input.as_deref())?, filter.as_deref())?;
+            let output = output.unwrap_or_else(|| existing.clone());
+            let (book, grocery_list, attendance) = workflow_sources(&storage_path, &exported, workflow)?;
+            let workflow = book.as_ref().map(|book| CalendarWorkflow { book, grocery_list: &grocery_list, attendance: &attendance, shopping_days: &config.shopping_days });
+            let summary = export_ical_merged(&exported, &config.cooks, workflow.as_ref(), &existing, &output, prefer)?;
+            println!(
+                "Merged the meal plan into {:?}: {} updated, {} added, {} removed.",
+                output, summary.updated, summary.added, summary.removed
+            );
+
+            // Edits made in the calendar go into the stored plan, unless another plan was exported
+            if !summary.pulled.is_empty() && input.is_none() {
+                for pulled in &summary.pulled {
+                    let Some(day) = meal_plan.meals().iter()
+                        .find(|meal| meal.meal_type == pulled.meal_type && meal_plan.date_of(&meal.day) == pulled.date)
+                        .map(|meal| meal.day.clone())
+                    else { continue };
+                    meal_plan.change_meal(&pulled.meal_type, &day, |meal| {
+                        meal.description = pulled.description.clone();
+                        if let Some(cook) = &pulled.cook {
+                            meal.cook = cook.clone();
+                        }
+                    });
+                    println!("Took {} on {} from the calendar: {}", pulled.meal_type, pulled.date.format("%a %Y-%m-%d"), pulled.description);
+                }
+                save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+            }
+
+            if !summary.conflicts.is_empty() {
+                for conflict in &summary.conflicts {
+                    println!(
+                        "Conflict: {} on {} changed in both; the plan has \"{}\", the calendar has \"{}\".",
+                        conflict.meal_type, conflict.date.format("%a %Y-%m-%d"), conflict.plan, conflict.calendar
+                    );
+                }
+                return Err(CliError::conflict(format!(
+                    "{} meal(s) changed in both the plan and the calendar; the calendar's versions were kept. Rerun with --prefer plan or --prefer calendar.",
+                    summary.conflicts.len()
+                )));
+            }
+        }
+        Some(Commands::ExportIcal { output: Some(output), split: Some(split), weeks, filter, input, .. }) => {
+            let plans = match (input.as_deref(), weeks) {
+                (Some(_), _) => vec![input_plan(&meal_plan, input.as_deref())?],
+                (None, Some(0)) => return Err(CliError::usage("The number of weeks must be at least 1.")),
+                (None, Some(weeks)) => recent_weeks(&Archive::new(&storage_path), &meal_plan, weeks)?,
+                (None, None) => {
+                    let mut plans = Archive::new(&storage_path).load_weeks(..meal_plan.week_start_date)
+                        .map_err(|e| CliError::io("Failed to load archived weeks", e))?;
+                    plans.push(meal_plan.clone());
+                    plans
+                }
             };
-            sync_meal_plan(&config_with_storage, &source)?;
-            println!("Meal plan synchronized successfully.");
+            let plans = plans.iter().map(|plan| filtered_plan(plan, filter.as_deref())).collect::<Result<Vec<_>, _>>()?;
+            let calendars = split_calendars(&plans, &config.cooks, split);
+            std::fs::create_dir_all(&output)
+                .map_err(|e| CliError::io("Failed to create the output directory", e))?;
+            for (name, calendar) in &calendars {
+                std::fs::write(output.join(format!("{}.ics", name)), calendar.to_string())
+                    .map_err(|e| CliError::io("Failed to write iCal file", e))?;
+            }
+            println!("Meal plan exported to {} iCal file(s) in {:?}", calendars.len(), output);
         }
-        Some(Commands::Config { action: ConfigAction::Init }) => {
-            config_init(&config)?;
-            println!("Configuration initialized successfully.");
+        Some(Commands::ExportIcal { output: Some(output), modified_since, merge: None, filter, workflow, input, .. }) => {
+            let meal_plan = filtered_plan(&input_plan(&meal_plan, input.as_deref())?, filter.as_deref())?;
+            let (meal_plan, next_since) = filter_modified_since(&meal_plan, modified_since.as_deref())?;
+            let (book, grocery_list, attendance) = workflow_sources(&storage_path, &meal_plan, workflow)?;
+            let workflow = book.as_ref().map(|book| CalendarWorkflow { book, grocery_list: &grocery_list, attendance: &attendance, shopping_days: &config.shopping_days });
+            export_ical(&meal_plan, &config.cooks, workflow.as_ref(), &output, next_since.is_some())?;
+            println!("Meal plan exported to iCal successfully: {:?}", output);
+            print_incremental_summary(&meal_plan, next_since);
         }
-        None => {
-            println!("Welcome to the Meal Plan CLI Tool!");
-            println!("This tool helps you organize and manage your weekly meal plans.");
-            println!("Use --help to see available commands.");
-            
-            // Show a summary of the current meal plan if it exists
-            if !meal_plan.meals.is_empty() {
-                println!("\nCurrent Meal Plan Summary:");
-                println!("Week starting: {}", meal_plan.week_start_date.format("%Y-%m-%d"));
-                println!("Total meals: {}", meal_plan.meals.len());
-                println!("Last modified: {}", meal_plan.last_modified.format("%Y-%m-%d %H:%M:%S"));
-                
-                // Group meals by day for a cleaner display
-                let mut meals_by_day: HashMap<String, Vec<&Meal>> = HashMap::new();
-                for meal in &meal_plan.meals {
-                    let day_str = format!("{}", meal.day);
-                    meals_by_day.entry(day_str).or_default().push(meal);
+        Some(Commands::ExportIcal { output: None, merge: None, .. }) => {
+            return Err(CliError::usage("Specify --output or --merge."));
+        }
+        Some(Commands::ExportJson { output, modified_since, filter, input }) => {
+            let meal_plan = filtered_plan(&input_plan(&meal_plan, input.as_deref())?, filter.as_deref())?;
+            let (meal_plan, next_since) = filter_modified_since(&meal_plan, modified_since.as_deref())?;
+            export_json(&meal_plan, &output)?;
+            println!("Meal plan exported to JSON successfully: {:?}", output);
+            print_incremental_summary(&meal_plan, next_since);
+        }
+        Some(Commands::ExportHtml { output, filter, input }) => {
+            let meal_plan = filtered_plan(&input_plan(&meal_plan, input.as_deref())?, filter.as_deref())?;
+            let dir = output.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            let missing = photos::copy_photos(&storage_path, &meal_plan, dir)
+                .map_err(|e| CliError::io("Failed to copy photos", e))?;
+            for photo in missing {
+                eprintln!("Warning: the photo {} is missing from the storage path.", photo);
+            }
+            std::fs::write(&output, render_week_html(&meal_plan))
+                .map_err(|e| CliError::io("Failed to write HTML file", e))?;
+            println!("Meal plan exported to HTML successfully: {:?}", output);
+        }
+        Some(Commands::ExportXlsx { output, weeks, filter }) => {
+            if weeks == 0 {
+                return Err(CliError::usage("The number of weeks must be at least 1."));
+            }
+            let filter = parse_filter(filter.as_deref())?;
+            let mut plans = recent_weeks(&Archive::new(&storage_path), &meal_plan, weeks)?;
+            if let Some(filter) = &filter {
+                plans.iter_mut().for_each(|plan| filter.retain(plan));
+            }
+            let roster: Vec<String> = config.cooks.iter().map(|c| c.name.clone()).collect();
+            spreadsheet::write_xlsx(&plans, &roster, &output)
+                .map_err(|e| CliError::io("Failed to write spreadsheet", e))?;
+            println!("Meal plan exported to XLSX successfully: {:?}", output);
+        }
+        Some(Commands::ExportHistory { format, output, filter }) => {
+            let filter = parse_filter(filter.as_deref())?;
+            let mut plans = Archive::new(&storage_path).load_weeks(..)
+                .map_err(|e| CliError::io("Failed to load archived weeks", e))?;
+            if let Some(filter) = &filter {
+                plans.iter_mut().for_each(|plan| filter.retain(plan));
+            }
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            let prices = PriceHistory::load_from_json(storage_path.join("prices.json"))
+                .map_err(|e| CliError::io("Failed to load price history", e))?;
+            let places = Places::load_from_json(storage_path.join("places.json"))
+                .map_err(|e| CliError::io("Failed to load places", e))?;
+            let rows = dataset::history_rows(&plans, &book, &prices, &places);
+            match (format, &output) {
+                (HistoryFormat::Csv, None) => {
+                    dataset::write_csv(&rows, io::stdout().lock())
+                        .map_err(|e| CliError::io("Failed to write history", e))?;
+                    // Nothing else is printed, so the output can be piped straight to a file
+                    return Ok(());
                 }
-                
-                for (day, meals) in meals_by_day {
-                    println!("\n{}:", day);
-                    for meal in meals {
-                        println!("  {}: {} (Cook: {})", meal.meal_type, meal.description, meal.cook);
-                    }
+                (HistoryFormat::Csv, Some(output)) => {
+                    let file = std::fs::File::create(output)
+                        .map_err(|e| CliError::io(&format!("Failed to create {:?}", output), e))?;
+                    dataset::write_csv(&rows, io::BufWriter::new(file))
+                        .map_err(|e| CliError::io("Failed to write history", e))?;
                 }
+                (HistoryFormat::Parquet, output) => {
+                    let output = output.as_deref().ok_or_else(|| CliError::usage("Parquet needs a file; pass --output."))?;
+                    dataset::write_parquet(&rows, output)
+                        .map_err(|e| CliError::io("Failed to write history", e))?;
+                }
+            }
+            if let Some(output) = output {
+                println!("Exported {} meal(s) from {} week(s) to {:?}", rows.len(), plans.len(), output);
+            }
+        }
+        Some(Commands::ExportQr { output, url }) => {
+            let contents = url.unwrap_or_else(|| compact_plan_text(&meal_plan));
+            export_qr(&contents, output.as_ref())?;
+            if let Some(output) = output {
+                println!("Meal plan exported to QR code successfully: {:?}", output);
             }
         }
+        Some(Commands::Open { file, print }) => {
+            let path = match file.to_lowercase().as_str() {
+                "json" => meal_plan_path.clone(),
+                "md" | "markdown" => storage_path.join("meal_plan.md"),
+                "config" => config_path.clone(),
+                _ => return Err(CliError::usage("Invalid file. Must be json, md or config.")),
+            };
+            if !path.exists() {
+                if path == config_path {
+                    return Err(CliError::not_found("No configuration file yet. Run 'mealplan config init' to create one."));
+                }
+                save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+            }
+            if print {
+                println!("{}", path.display());
+                return Ok(());
+            }
+            open_file(&path)?;
+            // An edit made here is saved properly, so its checksum matches and it isn't taken for damage
+            if path == meal_plan_path && storage::verify_checksum(&path).ok().flatten() == Some(false) {
+                let edited = MealPlan::load_from_json(&path)
+                    .map_err(|e| CliError::io("The edited meal plan doesn't load", e))?;
+                save_meal_plan(&edited, &storage_path, config.backup_count)?;
+            }
+        }
+        Some(Commands::Motd { width, fields, line }) => {
+            if width < motd::MIN_WIDTH {
+                return Err(CliError::usage(format!("The width must be at least {} characters.", motd::MIN_WIDTH)));
+            }
+            let fields = motd::parse_fields(&fields).map_err(CliError::usage)?;
+            let today = Local::now().date_naive();
+            if line {
+                println!("{}", motd::motd_line(&meal_plan, today, width, &fields));
+            } else {
+                println!("{}", motd::motd(&meal_plan, today, width, &fields));
+            }
+            // Nothing else is printed, so the snippet can go straight into a status line
+            return Ok(());
+        }
+        Some(Commands::PackingList { day, checklist, notify }) => {
+            let date = packing_date(&meal_plan, &day, Local::now().date_naive())?;
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            let items = packing::packing_list(&meal_plan, &book, date);
+            print!("{}", packing::render(&items, date, checklist));
+            if notify && !items.is_empty() {
+                let title = format!("Pack for {}", date.format("%A"));
+                notify::send_all(&notify::notifiers(&config.notify), &title, &packing::summary(&items))
+                    .map_err(|e| CliError::new(error::ErrorKind::General, e))?;
+            }
+        }
+        Some(Commands::Cook { day, meal_type }) => {
+            let date = packing_date(&meal_plan, &day, Local::now().date_naive())?;
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            let recipe = recipe_to_cook(&meal_plan, &book, &parse_meal_type(&meal_type)?, date)?;
+            let stdin = io::stdin();
+            cooking::walk_steps(recipe, &mut stdin.lock(), &mut io::stdout(), cooking::run_timer)
+                .map_err(|e| CliError::io("Failed to read input", e))?;
+        }
+        Some(Commands::Matrix { action: MatrixAction::Post { day } }) => {
+            let matrix = matrix_config(&config)?;
+            let date = packing_date(&meal_plan, &day, Local::now().date_naive())?;
+            matrix::MatrixClient::new(matrix).send_text(&format_daily_menu(&meal_plan, date))
+                .map_err(|e| CliError::io("Failed to post to Matrix", e))?;
+            println!("Posted the menu for {} to {}.", date.format("%A"), matrix.room_id);
+        }
+        Some(Commands::Digest { cook, dry_run }) => {
+            if let Some(cook) = &cook {
+                if config.find_cook(cook).is_none() {
+                    return Err(CliError::not_found(format!("Cook '{}' not found.", cook)));
+                }
+            }
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            let grocery_list = load_grocery_list(&storage_path.join("grocery_list.json"), meal_plan.week_start_date);
+            let digests = email::digests(&meal_plan, &config.cooks, cook.as_deref(), &book, &grocery_list);
+            if digests.is_empty() {
+                return Err(CliError::not_found("No cook has an email address. Add one with `mealplan cooks add NAME --email ADDRESS`."));
+            }
+            if dry_run {
+                for digest in &digests {
+                    println!("To: {} <{}>\nSubject: {}\n\n{}", digest.cook, digest.address, digest.subject, digest.body);
+                }
+            } else {
+                let settings = config.email.as_ref().ok_or_else(|| {
+                    CliError::not_found("No mail server configured. Add an \"email\" section with smtp_host, username and from to the configuration.")
+                })?;
+                let mailer = email::Mailer::new(settings, std::time::Duration::from_secs(config.http.timeout_secs))
+                    .map_err(CliError::usage)?;
+                let mut failed = 0;
+                for digest in &digests {
+                    match mailer.send(digest) {
+                        Ok(()) => println!("Sent the digest to {} <{}>.", digest.cook, digest.address),
+                        Err(e) => {
+                            eprintln!("Failed to send the digest to {}: {}", digest.cook, e);
+                            failed += 1;
+                        }
+                    }
+                }
+                if failed > 0 {
+                    return Err(CliError::new(error::ErrorKind::General, format!("{} of {} digests weren't sent.", failed, digests.len())));
+                }
+            }
+        }
+        Some(Commands::Serve { addr, read_only }) => {
+            let listener = std::net::TcpListener::bind(&addr)
+                .map_err(|e| CliError::io(&format!("Failed to listen on {}", addr), e))?;
+            let shared: reload::SharedConfig = Arc::new(RwLock::new(Config {
+                meal_plan_storage_path: storage_path.clone(),
+                ..config.clone()
+            }));
+            let storage_override = args.path.clone();
+            reload::watch_config(config_path.clone(), Arc::clone(&shared), move || reload_config(&config_path, storage_override.as_deref()));
+            println!("Serving the meal plan on http://{}. Press Ctrl+C to stop.", addr);
+            server::serve(listener, |request| {
+                let config = reload::current(&shared);
+                serve_request(&config, &config.meal_plan_storage_path, request, Local::now().date_naive(), read_only)
+            })
+            .map_err(|e| CliError::io("Failed to serve", e))?;
+        }
+        Some(Commands::Matrix { action: MatrixAction::Run }) => {
+            run_matrix_bot(&config, &storage_path)?;
+        }
+        Some(Commands::Remind { message: Some(message), at: None, .. }) => {
+            notify::send_all(&notify::notifiers(&config.notify), REMINDER_TITLE, &message)
+                .map_err(|e| CliError::new(error::ErrorKind::General, e))?;
+            println!("Sent the reminder.");
+        }
+        Some(Commands::Remind { message: Some(message), at: Some(at), .. }) => {
+            let now = Local::now().naive_local();
+            let at = notify::parse_reminder_time(&at, now).map_err(CliError::usage)?;
+            let path = storage_path.join("reminders.json");
+            let mut reminders = notify::Reminders::load_from_json(&path)
+                .map_err(|e| CliError::io("Failed to load reminders", e))?;
+            reminders.add(notify::Reminder { at, message: message.clone() });
+            reminders.save_to_json(&path)
+                .map_err(|e| CliError::io("Failed to save reminders", e))?;
+            println!("Reminder set for {}: {}", notify::describe_time(at, now.date()), message);
+        }
+        Some(Commands::Remind { message: None, send_due: true, .. }) => {
+            let sent = send_due_reminders(&config.notify, &storage_path, Local::now().naive_local())?;
+            println!("Sent {} reminder(s).", sent);
+        }
+        Some(Commands::Remind { message: None, .. }) => {
+            let reminders = notify::Reminders::load_from_json(storage_path.join("reminders.json"))
+                .map_err(|e| CliError::io("Failed to load reminders", e))?;
+            print!("{}", format_reminders(&reminders, Local::now().date_naive()));
+        }
+        Some(Commands::Outbox { flush: true }) => {
+            let summary = http::flush_outbox(&storage_path.join(http::OUTBOX_FILE))
+                .map_err(|e| CliError::io("Failed to update the outbox", e))?;
+            for rejected in &summary.rejected {
+                eprintln!("Dropped {}", rejected);
+            }
+            println!("Sent {} waiting push(es); {} still waiting.", summary.sent, summary.remaining);
+        }
+        Some(Commands::Outbox { flush: false }) => {
+            let outbox = http::Outbox::load_from_json(storage_path.join(http::OUTBOX_FILE))
+                .map_err(|e| CliError::io("Failed to load the outbox", e))?;
+            print!("{}", format_outbox(&outbox));
+        }
+        Some(Commands::Copy { hold: true, .. }) => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)
+                .map_err(|e| CliError::io("Failed to read the text to copy", e))?;
+            clipboard::hold_text(&text).map_err(|e| CliError::new(error::ErrorKind::General, format!("Failed to copy to the clipboard: {}", e)))?;
+            return Ok(());
+        }
+        Some(Commands::Copy { format, hold: false }) => {
+            let format = TextFormat::parse(&format).map_err(CliError::usage)?;
+            clipboard::copy_text(&render_week(&meal_plan, format))
+                .map_err(|e| CliError::new(error::ErrorKind::General, format!("Failed to copy to the clipboard: {}", e)))?;
+            println!("Copied the week of {} to the clipboard.", meal_plan.week_start_date.format("%Y-%m-%d"));
+        }
+        Some(Commands::Sync { action: Some(SyncAction::Peer { listen, connect, on_duplicate }), .. }) => {
+            sync_peer(&mut meal_plan, listen, connect, merge_decision(on_duplicate, prompter))?;
+            save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+            println!("Meal plan synchronized with peer successfully.");
+        }
+        Some(Commands::Sync { action: Some(SyncAction::Merge { file, on_duplicate }), .. }) => {
+            sync_merge_file(&mut meal_plan, &file, merge_decision(on_duplicate, prompter))?;
+            save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+            println!("Meal plan merged successfully.");
+        }
+        Some(Commands::Sync { source, action: Some(SyncAction::Status) }) => {
+            print!("{}", sync_status(&storage_path, &source)?);
+        }
+        Some(Commands::Sync { source, action: None }) => {
+            let config_with_storage = Config {
+                meal_plan_storage_path: storage_path.clone(),
+                ..config.clone()
+            };
+            sync_meal_plan(&config_with_storage, &source)?;
+            println!("Meal plan synchronized successfully.");
+        }
+        Some(Commands::Config { action: ConfigAction::Init }) => {
+            config_init(&config_path, prompter)?;
+            println!("Configuration initialized successfully.");
+        }
+        Some(Commands::Config { action: ConfigAction::Validate }) => {
+            config_validate(&config_path)?;
+        }
+        Some(Commands::Auth { action: AuthAction::Set { integration } }) => {
+            let secret = read_secret(integration)?;
+            let store = secrets::SecretStore::new(config_dir.clone());
+            match store.set(integration, &secret).map_err(|e| CliError::io("Failed to store the token", e))? {
+                secrets::Location::Keyring => println!("Stored the {} token in the system keyring.", integration),
+                secrets::Location::File { path, reason } => {
+                    println!("Stored the {} token encrypted in {:?}, as the system keyring can't be used: {}", integration, path, reason);
+                }
+            }
+            let mut updated_config = file_config.clone();
+            if config_path.exists() && remove_plaintext_token(&mut updated_config, integration, &secret) {
+                save_config(&updated_config, &config_path, config_error.as_deref())?;
+                println!("Took the token out of {:?}.", config_path);
+            }
+        }
+        Some(Commands::Auth { action: AuthAction::Remove { integration } }) => {
+            let store = secrets::SecretStore::new(config_dir.clone());
+            if !store.remove(integration).map_err(|e| CliError::io("Failed to remove the token", e))? {
+                return Err(CliError::not_found(format!("No {} token is stored.", integration)));
+            }
+            println!("Removed the stored {} token.", integration);
+        }
+        Some(Commands::Ingredient { action: IngredientAction::List }) => {
+            let registry = load_ingredients(&storage_path);
+            for (ingredient, names) in registry.groups() {
+                println!("{}: {}", ingredient, names.into_iter().collect::<Vec<_>>().join(", "));
+            }
+        }
+        Some(Commands::Ingredient { action: IngredientAction::Merge { names, into } }) => {
+            let registry_path = storage_path.join(ingredients::INGREDIENTS_FILE);
+            let mut registry = ingredients::Registry::load_from_json(&registry_path)
+                .map_err(|e| CliError::io("Failed to load merged ingredients", e))?;
+            let merged = registry.merge(&names, &into).map_err(CliError::usage)?;
+            if merged.is_empty() {
+                println!("{} already go by {}.", names.join(", "), into);
+                return Ok(());
+            }
+            registry.save_to_json(&registry_path)
+                .map_err(|e| CliError::io("Failed to save merged ingredients", e))?;
+            ingredients::configure(registry);
+            println!("Merged {} into {}.", merged.join(", "), into.trim().to_lowercase());
+
+            // Prices recorded under the merged names become one history
+            let prices_path = storage_path.join("prices.json");
+            if prices_path.exists() {
+                let prices = PriceHistory::load_from_json(&prices_path)
+                    .map_err(|e| CliError::io("Failed to load prices", e))?;
+                prices.save_to_json(&prices_path)
+                    .map_err(|e| CliError::io("Failed to save prices", e))?;
+            }
+        }
+        Some(Commands::Recipe { action }) => {
+            let recipes_path = storage_path.join("recipes.json");
+            let mut book = RecipeBook::load_from_json(&recipes_path)
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            match action {
+                RecipeAction::Add { name, tags, ingredient, link, servings, photo, step, equipment } => {
+                    let name = name.trim().to_string();
+                    if name.is_empty() {
+                        return Err(CliError::usage("The recipe name can't be empty."));
+                    }
+                    let recipe = Recipe {
+                        name: name.clone(),
+                        tags: clean_list(tags),
+                        ingredients: clean_list(ingredient),
+                        link,
+                        servings,
+                        photo: photo.map(|photo| import_photo(&storage_path, &photo)).transpose()?,
+                        steps: clean_list(step),
+                        equipment: clean_list(equipment).iter()
+                            .map(|text| EquipmentUse::parse(text))
+                            .collect::<Result<_, _>>()
+                            .map_err(CliError::usage)?,
+                    };
+                    let added = book.add(recipe);
+                    book.save_to_json(&recipes_path)
+                        .map_err(|e| CliError::io("Failed to save recipes", e))?;
+                    println!("{} the recipe {}.", if added { "Added" } else { "Updated" }, name);
+                }
+                RecipeAction::Import { url, dry_run, refresh } => {
+                    let mut fetcher = scraper::Fetcher::new(&storage_path);
+                    fetcher.refresh = refresh;
+                    let scraped = scraper::scrape(&fetcher, url.trim()).map_err(|e| match e.kind() {
+                        std::io::ErrorKind::InvalidInput | std::io::ErrorKind::PermissionDenied => CliError::usage(e.to_string()),
+                        std::io::ErrorKind::InvalidData => CliError::not_found(e.to_string()),
+                        _ => CliError::io("Failed to import the recipe", e),
+                    })?;
+                    // Old pages are cleared out now and then; one that can't be is only kept longer
+                    let _ = fetcher.prune();
+                    print!("{}", format_recipe(&scraped.recipe));
+                    match scraped.source.as_str() {
+                        "recipe data" => println!("Read from the page's recipe data{}.", if scraped.from_cache { ", cached" } else { "" }),
+                        adapter => println!("Read with the {} adapter{}.", adapter, if scraped.from_cache { ", cached" } else { "" }),
+                    }
+                    if dry_run {
+                        println!("Dry run: nothing was saved.");
+                    } else {
+                        let name = scraped.recipe.name.clone();
+                        let added = book.add(scraped.recipe);
+                        book.save_to_json(&recipes_path)
+                            .map_err(|e| CliError::io("Failed to save recipes", e))?;
+                        println!("{} the recipe {}.", if added { "Added" } else { "Updated" }, name);
+                    }
+                }
+                RecipeAction::Remove { name } => {
+                    if !book.remove(&name) {
+                        return Err(CliError::not_found(format!("No recipe named {}.", name)));
+                    }
+                    book.save_to_json(&recipes_path)
+                        .map_err(|e| CliError::io("Failed to save recipes", e))?;
+                    println!("Removed the recipe {}.", name.trim());
+                }
+                RecipeAction::List => {
+                    if book.recipes.is_empty() {
+                        println!("No recipes yet. Add one with 'mealplan recipe add <name> --ingredient <ingredient>'.");
+                    }
+                    let mut recipes: Vec<&Recipe> = book.recipes.iter().collect();
+                    recipes.sort_by_key(|r| r.name.to_lowercase());
+                    for recipe in recipes {
+                        print!("{}", format_recipe(recipe));
+                    }
+                }
+                RecipeAction::Search { query, ingredient, tag } => {
+                    let query = query.unwrap_or_default();
+                    if query.trim().is_empty() && ingredient.is_empty() && tag.is_empty() {
+                        return Err(CliError::usage("Give words to search for, or --ingredient or --tag."));
+                    }
+                    let filter = RecipeFilter { ingredients: ingredient, tags: tag };
+                    let results = book.search(&query, &filter);
+                    if results.is_empty() {
+                        println!("No recipes match.");
+                    }
+                    for result in results {
+                        print!("{}", format_recipe(result.recipe));
+                    }
+                }
+                RecipeAction::Scale { name, factor } => {
+                    if !(factor.is_finite() && factor > 0.0) {
+                        return Err(CliError::usage("The scale factor must be a number above zero."));
+                    }
+                    let recipe = book.find(&name)
+                        .ok_or_else(|| CliError::not_found(format!("No recipe named {}.", name.trim())))?;
+                    print!("{}", format_scaled_recipe(recipe, factor));
+                }
+            }
+        }
+        Some(Commands::Pack { action }) => {
+            let packs_path = storage_path.join(packs::INSTALLED_PACKS_FILE);
+            let mut installed = packs::InstalledPacks::load_from_json(&packs_path)
+                .map_err(|e| CliError::io("Failed to load the installed packs", e))?;
+            match action {
+                PackAction::List => print!("{}", format_packs(&installed)),
+                PackAction::Install { source, replace } => {
+                    let pack = load_pack(&source)?;
+                    let recipes_path = storage_path.join("recipes.json");
+                    let mut book = RecipeBook::load_from_json(&recipes_path)
+                        .map_err(|e| CliError::io("Failed to load recipes", e))?;
+                    let mut updated_config = file_config.clone();
+                    let changes = installed.install(&pack, &mut book, &mut updated_config, replace).map_err(CliError::usage)?;
+                    book.save_to_json(&recipes_path)
+                        .map_err(|e| CliError::io("Failed to save recipes", e))?;
+                    save_config(&updated_config, &config_path, config_error.as_deref())?;
+                    installed.save_to_json(&packs_path)
+                        .map_err(|e| CliError::io("Failed to save the installed packs", e))?;
+                    println!("Installed {}.", pack.name);
+                    print!("{}", format_pack_changes(&changes));
+                    if !changes.kept.is_empty() {
+                        println!("Use --replace to overwrite what you already had.");
+                    }
+                }
+                PackAction::Remove { name } => {
+                    let recipes_path = storage_path.join("recipes.json");
+                    let mut book = RecipeBook::load_from_json(&recipes_path)
+                        .map_err(|e| CliError::io("Failed to load recipes", e))?;
+                    let mut updated_config = file_config.clone();
+                    let changes = installed.uninstall(&name, &mut book, &mut updated_config)
+                        .ok_or_else(|| CliError::not_found(format!("No installed pack named {}.", name.trim())))?;
+                    book.save_to_json(&recipes_path)
+                        .map_err(|e| CliError::io("Failed to save recipes", e))?;
+                    save_config(&updated_config, &config_path, config_error.as_deref())?;
+                    installed.save_to_json(&packs_path)
+                        .map_err(|e| CliError::io("Failed to save the installed packs", e))?;
+                    println!("Removed the pack {}.", name.trim());
+                    print!("{}", format_pack_changes(&changes));
+                }
+            }
+        }
+        Some(Commands::Generate { meal_type, day, dry_run }) => {
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            let prices = PriceHistory::load_from_json(storage_path.join("prices.json"))
+                .map_err(|e| CliError::io("Failed to load price history", e))?;
+            let added = generate_meals(&config, &mut meal_plan, &book, &prices, &meal_type, day.as_deref())?;
+            if dry_run {
+                println!("Dry run: nothing was saved.");
+            } else if added > 0 {
+                save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+            }
+        }
+        Some(Commands::Places { action }) => {
+            let places_path = storage_path.join("places.json");
+            let mut places = Places::load_from_json(&places_path)
+                .map_err(|e| CliError::io("Failed to load places", e))?;
+            match action {
+                PlaceAction::Add { name, cuisine, contact, cost } => {
+                    let name = name.trim().to_string();
+                    if name.is_empty() {
+                        return Err(CliError::usage("The place name can't be empty."));
+                    }
+                    if cost.is_some_and(|cost| cost < 0.0) {
+                        return Err(CliError::usage("The cost can't be negative."));
+                    }
+                    let added = places.add(Place { name: name.clone(), cuisine, contact, typical_cost: cost });
+                    places.save_to_json(&places_path)
+                        .map_err(|e| CliError::io("Failed to save places", e))?;
+                    println!("{} the place {}.", if added { "Added" } else { "Updated" }, name);
+                }
+                PlaceAction::Remove { name } => {
+                    if !places.remove(&name) {
+                        return Err(CliError::not_found(format!("No place named {}.", name.trim())));
+                    }
+                    places.save_to_json(&places_path)
+                        .map_err(|e| CliError::io("Failed to save places", e))?;
+                    println!("Removed the place {}.", name.trim());
+                }
+                PlaceAction::List => print!("{}", format_places(&places)),
+                PlaceAction::Suggest { weeks } => {
+                    let archive = Archive::new(&storage_path);
+                    let first_week = first_week(&meal_plan, weeks)?;
+                    let plans = history::load_plans_since(&archive, &meal_plan, NaiveDate::MIN)
+                        .map_err(|e| CliError::io("Failed to load the archive", e))?;
+                    print!("{}", format_place_suggestions(&places.not_ordered_since(&plans, first_week), weeks));
+                }
+            }
+        }
+        Some(Commands::Budget { action: BudgetAction::ImportReceipt { file, dry_run } }) => {
+            let rows = import::read_rows(&file)
+                .map_err(|e| CliError::io(&format!("Failed to read {:?}", file), e))?;
+            let lines = receipt::read_lines(&rows).map_err(|e| CliError::new(error::ErrorKind::Data, e))?;
+            let grocery_path = storage_path.join("grocery_list.json");
+            let mut grocery_list = load_grocery_list(&grocery_path, meal_plan.week_start_date);
+            let prices_path = storage_path.join("prices.json");
+            let mut prices = PriceHistory::load_from_json(&prices_path)
+                .map_err(|e| CliError::io("Failed to load price history", e))?;
+            let source = file.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            print!("{}", import_receipt(&mut grocery_list, &mut prices, &lines, &source, Local::now().date_naive()));
+            if dry_run {
+                println!("Dry run: nothing was saved.");
+            } else {
+                grocery_list.save_to_json(&grocery_path)
+                    .map_err(|e| CliError::io("Failed to save grocery list", e))?;
+                prices.save_to_json(&prices_path)
+                    .map_err(|e| CliError::io("Failed to save price history", e))?;
+            }
+        }
+        Some(Commands::Waste { action }) => {
+            let waste_path = storage_path.join("waste.json");
+            let mut log = WasteLog::load_from_json(&waste_path)
+                .map_err(|e| CliError::io("Failed to load the waste log", e))?;
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            match action {
+                WasteAction::Log { item, recipe, day } => {
+                    let entry = waste_entry(&meal_plan, &book, &item, recipe.as_deref(), day.as_deref(), Local::now().date_naive())?;
+                    println!("Logged {} as wasted on {}.", entry.item, entry.date.format("%a %Y-%m-%d"));
+                    log.log(entry);
+                    log.save_to_json(&waste_path)
+                        .map_err(|e| CliError::io("Failed to save the waste log", e))?;
+                }
+                WasteAction::List => print!("{}", format_waste_log(&log)),
+                WasteAction::Report { month } => {
+                    let month = month.unwrap_or_else(|| Local::now().date_naive().format("%Y-%m").to_string());
+                    let period = ReportPeriod::parse_month(&month)
+                        .ok_or_else(|| CliError::usage(format!("Invalid month: {}. Use YYYY-MM, e.g. 2025-02.", month)))?;
+                    print!("{}", format_waste_report(&WasteReport::new(&log, period), &book, config.household_size));
+                }
+            }
+        }
+        Some(Commands::Pantry { action }) => {
+            let pantry_path = storage_path.join("pantry.json");
+            let mut pantry = Pantry::load_from_json(&pantry_path)
+                .map_err(|e| CliError::io("Failed to load the pantry", e))?;
+            let mut not_found: Vec<String> = Vec::new();
+            match action {
+                PantryAction::Add { items } => {
+                    for item in &items {
+                        if pantry.add(item) {
+                            println!("Added {} to the pantry.", item.trim());
+                        } else {
+                            println!("The pantry already has {}.", item.trim());
+                        }
+                    }
+                }
+                PantryAction::Remove { items } => {
+                    for item in &items {
+                        if pantry.remove(item) {
+                            println!("Took {} out of the pantry.", item.trim());
+                        } else {
+                            not_found.push(item.trim().to_string());
+                        }
+                    }
+                }
+                PantryAction::List => print!("{}", format_pantry(&pantry)),
+            }
+            pantry.save_to_json(&pantry_path)
+                .map_err(|e| CliError::io("Failed to save the pantry", e))?;
+            if !not_found.is_empty() {
+                return Err(CliError::not_found(format!("The pantry has no {}.", not_found.join(", "))));
+            }
+        }
+        Some(Commands::Attendance { action }) => {
+            let attendance_path = storage_path.join(attendance::ATTENDANCE_FILE);
+            let mut attendance = Attendance::load_from_json(&attendance_path)
+                .map_err(|e| CliError::io("Failed to load attendance", e))?;
+            match action {
+                AttendanceAction::Member { name, allergies } => {
+                    let added = attendance.set_member(&name, &allergies).map_err(CliError::usage)?;
+                    let member = attendance.member(&name).expect("member was just set");
+                    match (added, member.allergies.is_empty()) {
+                        (true, true) => println!("Added {} to the household.", member.name),
+                        (true, false) => println!("Added {} to the household, allergic to {}.", member.name, member.allergies.join(", ")),
+                        (false, _) => println!("{} is allergic to {}.", member.name, member.allergies.join(", ")),
+                    }
+                }
+                AttendanceAction::RemoveMember { name } => {
+                    if !attendance.remove_member(&name) {
+                        return Err(CliError::not_found(format!("\"{}\" isn't in the household.", name.trim())));
+                    }
+                    println!("Took {} out of the household.", name.trim());
+                }
+                AttendanceAction::Away { name, day, meal_type } => {
+                    let date = meal_plan.date_of(&parse_day(&day, &meal_plan)?);
+                    let meal_type = meal_type.as_deref().map(parse_meal_type).transpose()?;
+                    let slot = attendance_slot(date, meal_type.as_ref());
+                    if attendance.set_away(&name, date, meal_type).map_err(CliError::not_found)? {
+                        println!("{} is away for {}.", name.trim(), slot);
+                    } else {
+                        println!("{} was already away for {}.", name.trim(), slot);
+                    }
+                }
+                AttendanceAction::Back { name, day, meal_type } => {
+                    let date = meal_plan.date_of(&parse_day(&day, &meal_plan)?);
+                    let meal_type = meal_type.as_deref().map(parse_meal_type).transpose()?;
+                    let slot = attendance_slot(date, meal_type.as_ref());
+                    if attendance.set_back(&name, date, meal_type).map_err(CliError::not_found)? {
+                        println!("{} is eating {} again.", name.trim(), slot);
+                    } else {
+                        println!("{} wasn't away for {}.", name.trim(), slot);
+                    }
+                }
+                AttendanceAction::List => print!("{}", format_attendance(&attendance, &meal_plan)),
+            }
+            attendance.save_to_json(&attendance_path)
+                .map_err(|e| CliError::io("Failed to save attendance", e))?;
+        }
+        Some(Commands::WhatCanIMake { max_minutes, limit }) => {
+            let pantry = Pantry::load_from_json(storage_path.join("pantry.json"))
+                .map_err(|e| CliError::io("Failed to load the pantry", e))?;
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            if pantry.items.is_empty() {
+                return Err(CliError::not_found("The pantry is empty. Add what's in the kitchen with 'mealplan pantry add <item>...'."));
+            }
+            let matches = pantry::what_can_i_make(&pantry, &book, max_minutes);
+            print!("{}", format_pantry_matches(&matches[..matches.len().min(limit)]));
+        }
+        Some(Commands::Lunchbox { action }) => {
+            let lunchbox_path = storage_path.join("lunchbox.json");
+            let mut lunchbox = Lunchbox::load_from_json(&lunchbox_path)
+                .map_err(|e| CliError::io("Failed to load the lunchbox planner", e))?;
+            let week_end = meal_plan.week_start_date + Duration::days(6);
+            match action {
+                LunchboxAction::Add { name, part, tag, buy } => {
+                    let name = name.trim().to_string();
+                    if name.is_empty() {
+                        return Err(CliError::usage("The item name can't be empty."));
+                    }
+                    let part = lunchbox::Part::parse(&part)
+                        .ok_or_else(|| CliError::usage(format!("Unknown part {}; use main, fruit or snack.", part.trim())))?;
+                    let buy = buy.map(|buy| buy.trim().to_string()).filter(|buy| !buy.is_empty());
+                    let added = lunchbox.add_component(lunchbox::Component { name: name.clone(), part, tags: tag, buy });
+                    println!("{} the {} {}.", if added { "Added" } else { "Updated" }, part, name);
+                }
+                LunchboxAction::Remove { name } => {
+                    if !lunchbox.remove_component(&name) {
+                        return Err(CliError::not_found(format!("No lunchbox item named {}.", name.trim())));
+                    }
+                    println!("Removed {} from the lunchbox items.", name.trim());
+                }
+                LunchboxAction::Child { name, exclude, remove } => {
+                    if remove {
+                        if !lunchbox.remove_child(&name) {
+                            return Err(CliError::not_found(format!("No child named {}.", name.trim())));
+                        }
+                        println!("Stopped packing lunches for {}.", name.trim());
+                    } else {
+                        if name.trim().is_empty() {
+                            return Err(CliError::usage("The child's name can't be empty."));
+                        }
+                        let added = lunchbox.set_child(&name, exclude);
+                        println!("{} {}.", if added { "Added" } else { "Updated" }, name.trim());
+                    }
+                }
+                LunchboxAction::List => print!("{}", format_lunchbox(&lunchbox)),
+                LunchboxAction::Generate { day, grocery } => {
+                    if lunchbox.children.is_empty() {
+                        return Err(CliError::not_found("No children to pack lunches for. Add one with 'mealplan lunchbox child <name>'."));
+                    }
+                    let dates = days::split_days(&day)
+                        .iter()
+                        .map(|day| parse_day(day, &meal_plan).map(|day| meal_plan.date_of(&day)))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if dates.is_empty() {
+                        return Err(CliError::usage("Give the school days to pack lunches for."));
+                    }
+                    lunchbox.forget_before(meal_plan.week_start_date);
+                    lunchbox.generate(&dates);
+                    print!("{}", lunchbox.to_text(meal_plan.week_start_date, week_end));
+                    let missing: Vec<String> = lunchbox::Part::ALL
+                        .iter()
+                        .filter(|part| lunchbox.lunches.iter().any(|lunch| dates.contains(&lunch.date) && !lunch.items.contains_key(part)))
+                        .map(|part| part.to_string())
+                        .collect();
+                    if !missing.is_empty() {
+                        println!("Some boxes have no {}: add items with 'mealplan lunchbox add <name> --part <part>'.", missing.join(" or "));
+                    }
+                    if grocery {
+                        let grocery_path = storage_path.join("grocery_list.json");
+                        let mut grocery_list = load_grocery_list(&grocery_path, meal_plan.week_start_date);
+                        let (from, to) = (dates.iter().min().copied().unwrap_or(week_end), dates.iter().max().copied().unwrap_or(week_end));
+                        let added = lunchbox.list_groceries(from, to)
+                            .iter()
+                            .filter(|item| grocery_list.add_item(item, Some("Lunchbox".to_string())))
+                            .count();
+                        grocery_list.save_to_json(&grocery_path)
+                            .map_err(|e| CliError::io("Failed to save grocery list", e))?;
+                        println!("Added {} item(s) to the grocery list.", added);
+                    }
+                }
+                LunchboxAction::Show => {
+                    let text = lunchbox.to_text(meal_plan.week_start_date, week_end);
+                    if text.is_empty() {
+                        println!("No lunches packed this week. Run 'mealplan lunchbox generate'.");
+                    } else {
+                        print!("{}", text);
+                    }
+                }
+            }
+            lunchbox.save_to_json(&lunchbox_path)
+                .map_err(|e| CliError::io("Failed to save the lunchbox planner", e))?;
+        }
+        Some(Commands::Rotation { action }) => {
+            let rotations_path = storage_path.join("rotations.json");
+            let mut rotations = Rotations::load_from_json(&rotations_path)
+                .map_err(|e| CliError::io("Failed to load rotations", e))?;
+            match action {
+                RotationAction::Create { name, recipes, meal_type, day, cook } => {
+                    let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                        .map_err(|e| CliError::io("Failed to load recipes", e))?;
+                    let rotation = create_rotation(&config, &book, name, &recipes, &meal_type, &day, cook)?;
+                    let message = format!("the rotation {}: {}", rotation.name, rotation.recipes.join(" -> "));
+                    let added = rotations.add(rotation);
+                    println!("{} {}.", if added { "Created" } else { "Replaced" }, message);
+                }
+                RotationAction::Remove { name } => {
+                    if !rotations.remove(&name) {
+                        return Err(CliError::not_found(format!("No rotation named {}.", name.trim())));
+                    }
+                    println!("Removed the rotation {}.", name.trim());
+                }
+                RotationAction::List => print!("{}", format_rotations(&rotations)),
+                RotationAction::Apply { name, cook } => {
+                    let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                        .map_err(|e| CliError::io("Failed to load recipes", e))?;
+                    apply_rotations(&config, &mut meal_plan, &mut rotations, &book, name.as_deref(), cook.as_deref(), prompter)?;
+                    save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+                }
+            }
+            rotations.save_to_json(&rotations_path)
+                .map_err(|e| CliError::io("Failed to save rotations", e))?;
+        }
+        Some(Commands::Alias { action: AliasAction::Add { name, description, tags, recipe } }) => {
+            let mut updated_config = file_config.clone();
+            alias_add(&mut updated_config, &name, description, tags, recipe)?;
+            save_config(&updated_config, &config_path, config_error.as_deref())?;
+            println!("Added the alias {}.", name.trim());
+        }
+        Some(Commands::Alias { action: AliasAction::Remove { name } }) => {
+            let mut updated_config = file_config.clone();
+            if updated_config.aliases.remove(&name.trim().to_lowercase()).is_none() {
+                return Err(CliError::not_found(format!("No alias named {}.", name)));
+            }
+            save_config(&updated_config, &config_path, config_error.as_deref())?;
+            println!("Removed the alias {}.", name.trim());
+        }
+        Some(Commands::Alias { action: AliasAction::List }) => {
+            print!("{}", format_aliases(&config));
+        }
+        Some(Commands::Cooks { action: CookAction::Add { name, email, preference } }) => {
+            let mut updated_config = file_config.clone();
+            let added = cooks_add(&mut updated_config, &name, email, preference);
+            save_config(&updated_config, &config_path, config_error.as_deref())?;
+            if added {
+                println!("Added {} to the cooks.", name);
+            } else {
+                println!("Updated {}.", name);
+            }
+        }
+        Some(Commands::Cooks { action: CookAction::Remove { name } }) => {
+            let mut updated_config = file_config.clone();
+            cooks_remove(&mut updated_config, &name)?;
+            save_config(&updated_config, &config_path, config_error.as_deref())?;
+            println!("Removed {} from the cooks.", name);
+        }
+        Some(Commands::Cooks { action: CookAction::List }) => {
+            print!("{}", format_cooks(&config, &meal_plan));
+        }
+        Some(Commands::Cooks { action: CookAction::Rotation { names } }) => {
+            if names.is_empty() {
+                let rotation = config.rotation();
+                if rotation.is_empty() {
+                    println!("No cook rotation configured.");
+                } else {
+                    println!("{}", rotation.join(" -> "));
+                }
+            } else {
+                if let Some(unknown) = names.iter().find(|name| !config.is_known_cook(name)) {
+                    return Err(unknown_cook_error(&config, unknown));
+                }
+                let updated_config = Config {
+                    cook_rotation: names.clone(),
+                    ..file_config.clone()
+                };
+                save_config(&updated_config, &config_path, config_error.as_deref())?;
+                println!("Cook rotation set to {}.", names.join(" -> "));
+            }
+        }
+        Some(Commands::Cooks { action: CookAction::Prefs { action: CookPrefsAction::Show { name } } }) => {
+            let cook = config.find_cook(&name).ok_or_else(|| unknown_cook_error(&config, &name))?;
+            print!("{}", format_cook_prefs(cook));
+        }
+        Some(Commands::Cooks { action: CookAction::Prefs { action: CookPrefsAction::Edit { name } } }) => {
+            let mut updated_config = file_config.clone();
+            let cook = updated_config.find_cook_mut(&name).ok_or_else(|| unknown_cook_error(&config, &name))?;
+            let path = std::env::temp_dir().join(format!("mealplan-prefs-{}.yaml", std::process::id()));
+            std::fs::write(&path, cook_prefs_yaml(cook))
+                .map_err(|e| CliError::io("Failed to write the preferences to edit", e))?;
+            let edited = open_file(&path).and_then(|_| {
+                std::fs::read_to_string(&path).map_err(|e| CliError::io("Failed to read the edited preferences", e))
+            });
+            let _ = std::fs::remove_file(&path);
+            cook.prefs = parse_cook_prefs(&edited?)
+                .map_err(|e| CliError::new(error::ErrorKind::Data, format!("Invalid preferences: {}", e)))?;
+            let summary = format_cook_prefs(cook);
+            save_config(&updated_config, &config_path, config_error.as_deref())?;
+            print!("{}", summary);
+        }
+        Some(Commands::Cooks { action: CookAction::Unavailable { name, days } }) => {
+            let mut updated_config = file_config.clone();
+            set_cook_availability(&mut updated_config, &name, &days, false)?;
+            save_config(&updated_config, &config_path, config_error.as_deref())?;
+            println!("{} marked unavailable on {}.", name, days.join(", "));
+        }
+        Some(Commands::Cooks { action: CookAction::Available { name, days } }) => {
+            let mut updated_config = file_config.clone();
+            set_cook_availability(&mut updated_config, &name, &days, true)?;
+            save_config(&updated_config, &config_path, config_error.as_deref())?;
+            println!("{} marked available on {}.", name, days.join(", "));
+        }
+        Some(Commands::Check { rules, format, notify }) => {
+            if notify && config.notify.is_empty() {
+                return Err(CliError::usage("No notification targets are configured. Add a \"notify\" section to the configuration."));
+            }
+            let rules_path = rules.unwrap_or_else(|| storage_path.join(lint::RULES_FILE));
+            let rules = lint::load_rules(&rules_path).map_err(|e| CliError::new(error::ErrorKind::Data, e))?;
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            let attendance = Attendance::load_from_json(storage_path.join(attendance::ATTENDANCE_FILE))
+                .map_err(|e| CliError::io("Failed to load attendance", e))?;
+            let alerts = budget_alerts(&config, &storage_path, &meal_plan)?;
+            let mut findings = check_findings(&config, &meal_plan, &book, &attendance, &rules);
+            findings.extend(alerts.iter().map(budget_finding));
+            findings.sort_by_key(|finding| (finding.severity, finding.date));
+            print!("{}", format_findings(&findings, &format)?);
+            if notify {
+                let sent = send_budget_alerts(&config.notify, &storage_path, &alerts)?;
+                if sent > 0 {
+                    eprintln!("Sent {} budget alert(s).", sent);
+                }
+            }
+            let errors = findings.iter().filter(|f| f.severity == lint::Severity::Error).count();
+            if errors > 0 {
+                return Err(CliError::conflict(format!("{} error(s) found.", errors)));
+            }
+        }
+        Some(Commands::Grocery { by_store, week, action }) => {
+            let grocery_path = storage_path.join("grocery_list.json");
+            let mut grocery_list = load_grocery_list(&grocery_path, meal_plan.week_start_date);
+
+            match action {
+                Some(GroceryAction::Add { item, category }) => {
+                    let line = grocery_add(&mut grocery_list, &item, category)?;
+                    println!("{}", grocery_added_message(&item, &line));
+                }
+                Some(GroceryAction::Check { item, price }) => {
+                    grocery_set_purchased(&mut grocery_list, &item, true)?;
+                    if let Some(price) = price {
+                        grocery_list.set_price(item.trim(), price);
+                        record_price(&storage_path, &item, price)?;
+                    }
+                    println!("Marked {} as purchased.", item);
+                }
+                Some(GroceryAction::Cost { action: CostAction::Set { item, price } }) => {
+                    if item.trim().is_empty() {
+                        return Err(CliError::usage("Grocery item cannot be empty."));
+                    }
+                    grocery_list.set_price(item.trim(), price);
+                    record_price(&storage_path, &item, price)?;
+                    println!("Recorded {:.2} for {}.", price, item.trim());
+                }
+                Some(GroceryAction::Cost { action: CostAction::History { item } }) => {
+                    let prices = PriceHistory::load_from_json(storage_path.join("prices.json"))
+                        .map_err(|e| CliError::io("Failed to load price history", e))?;
+                    print!("{}", format_price_history(&prices, item.as_deref())?);
+                }
+                Some(GroceryAction::Uncheck { item }) => {
+                    grocery_set_purchased(&mut grocery_list, &item, false)?;
+                    println!("Marked {} as not purchased.", item);
+                }
+                Some(GroceryAction::Status) => grocery_status(&grocery_list),
+                Some(GroceryAction::Export { format, output }) => {
+                    let contents = format_cart(&grocery_list.cart_items(), &format)?;
+                    match output {
+                        Some(output) => {
+                            std::fs::write(&output, contents)
+                                .map_err(|e| CliError::io("Failed to write grocery export", e))?;
+                            println!("Grocery export written to {:?}", output);
+                        }
+                        None => print!("{}", contents),
+                    }
+                }
+                None => {
+                    let store_preferences = if by_store {
+                        Some(&config.store_preferences)
+                    } else {
+                        None
+                    };
+                    print!("{}", format_grocery_list(&grocery_list, store_preferences));
+                    let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                        .map_err(|e| CliError::io("Failed to load recipes", e))?;
+                    let attendance = Attendance::load_from_json(storage_path.join(attendance::ATTENDANCE_FILE))
+                        .map_err(|e| CliError::io("Failed to load attendance", e))?;
+                    let shopping_days = if week { &[][..] } else { &config.shopping_days[..] };
+                    print!("{}", format_meal_ingredients(&grocery_list, &meal_plan, &book, &attendance, shopping_days, Local::now().date_naive()));
+                }
+            }
+
+            grocery_list.save_to_json(&grocery_path)
+                .map_err(|e| CliError::io("Failed to save grocery list", e))?;
+        }
+        Some(Commands::Batch { suggest: true, .. }) => {
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            print!("{}", format_batch_suggestions(&book));
+        }
+        Some(Commands::Batch {
+            description: Some(description),
+            meal_type: Some(meal_type),
+            day: Some(day),
+            leftovers: Some(leftovers),
+            leftovers_meal_type,
+            cook: Some(cook),
+            ingredient,
+            ..
+        }) => {
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            let cook = resolve_cook(&config, &meal_plan, &cook, &meal_type, &day, &dish(&description, &[], &book))?;
+            validate_cook(&config, &cook)?;
+            confirm_cook_available(&config, &meal_plan, &cook, &day, prompter)?;
+            batch_meal(
+                &mut meal_plan,
+                &meal_type,
+                &day,
+                leftovers_meal_type.as_deref().unwrap_or(&meal_type),
+                &leftovers,
+                &cook,
+                &description,
+                &current_user(&config),
+                prompter,
+            )?;
+            save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+            println!("Batch meal planned successfully.");
+
+            let recipe = book.find(&description);
+            if let Some(recipe) = recipe.filter(|recipe| !recipe.is_batch_friendly()) {
+                println!(
+                    "Note: {} isn't tagged {}; make sure it keeps until {}.",
+                    recipe.name,
+                    recipes::BATCH_FRIENDLY_TAGS.join(" or "),
+                    leftovers
+                );
+            }
+            let items = batch_groceries(recipe, &ingredient);
+            if !items.is_empty() {
+                let grocery_path = storage_path.join("grocery_list.json");
+                let mut grocery_list = load_grocery_list(&grocery_path, meal_plan.week_start_date);
+                for item in &items {
+                    if let Ok(line) = grocery_add(&mut grocery_list, item, None) {
+                        println!("{}", grocery_added_message(item, &line));
+                    }
+                }
+                grocery_list.save_to_json(&grocery_path)
+                    .map_err(|e| CliError::io("Failed to save grocery list", e))?;
+                match recipe {
+                    Some(recipe) if !recipe.ingredients.is_empty() => {
+                        println!("Added {}'s ingredients for a double batch.", recipe.name);
+                    }
+                    _ => println!("Remember to buy enough for a double batch."),
+                }
+            }
+        }
+        Some(Commands::Batch { .. }) => {
+            return Err(CliError::usage("Give the meal with --meal-type, --day, --leftovers and --cook, or use --suggest."));
+        }
+        Some(Commands::Search { query, limit }) => {
+            let terms = search::parse_query(&query);
+            if terms.is_empty() {
+                return Err(CliError::usage("Give words to search for."));
+            }
+            let mut index = Archive::new(&storage_path).search_index()
+                .map_err(|e| CliError::io("Failed to load the search index", e))?;
+            index.add_week(&meal_plan);
+            let mut meals = index.search(&terms);
+            meals.truncate(limit.unwrap_or(usize::MAX));
+            print!("{}", format_search_results(&meals));
+        }
+        Some(Commands::Stale { since }) => {
+            let cutoff = date_before(Local::now().date_naive(), parse_period(&since)?)?;
+            let index = Archive::new(&storage_path).index()
+                .map_err(|e| CliError::io("Failed to load archived weeks", e))?;
+            let stale = history::stale_dishes(&index, &meal_plan, cutoff);
+            print!("{}", format_stale(&stale, Local::now().date_naive()));
+        }
+        Some(Commands::Stats { action: StatsAction::Variety { weeks, format, output, filter } }) => {
+            if weeks == 0 {
+                return Err(CliError::usage("The number of weeks must be at least 1."));
+            }
+            let first_week = first_week(&meal_plan, weeks)?;
+            let history = filtered_history(&Archive::new(&storage_path), &meal_plan, first_week, filter.as_deref())?;
+            let report = history.variety(meal_plan.week_start_date, weeks);
+            let contents = match format.to_lowercase().as_str() {
+                "text" => format_variety(&report),
+                "csv" => variety_csv(&report),
+                "json" => serde_json::to_string_pretty(&report)
+                    .map(|json| json + "\n")
+                    .map_err(|e| format!("Failed to serialize report: {}", e))?,
+                _ => return Err(CliError::usage("Invalid format. Must be text, csv, or json.")),
+            };
+
+            match output {
+                Some(output) => {
+                    std::fs::write(&output, contents)
+                        .map_err(|e| CliError::io("Failed to write report", e))?;
+                    println!("Variety report written to {:?}", output);
+                }
+                None => print!("{}", contents),
+            }
+        }
+        Some(Commands::Cooked { meal_type, day, rating }) => {
+            mark_cooked(&mut meal_plan, &meal_type, &day, rating)?;
+            save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+            println!("Marked the meal as cooked.");
+        }
+        Some(Commands::Compare { week, with }) => {
+            let archive = Archive::new(&storage_path);
+            let (week, groceries) = load_compared_week(&archive, &meal_plan, &storage_path, &week)?;
+            let (with, previous_groceries) = load_compared_week(&archive, &meal_plan, &storage_path, &with)?;
+            let comparison = WeekComparison::new(&week, groceries.as_ref(), &with, previous_groceries.as_ref());
+            print!("{}", comparison.to_text());
+        }
+        Some(Commands::Review { journal }) => {
+            let grocery_list = load_grocery_list(&storage_path.join("grocery_list.json"), meal_plan.week_start_date);
+            let places = Places::load_from_json(storage_path.join("places.json"))
+                .map_err(|e| CliError::io("Failed to load places", e))?;
+            let prices = PriceHistory::load_from_json(storage_path.join("prices.json"))
+                .map_err(|e| CliError::io("Failed to load price history", e))?;
+            let review = WeeklyReview::new(&meal_plan, &grocery_list, &places, &prices, config.weekly_budget);
+            if !week_has_ended(&meal_plan, Local::now().date_naive()) {
+                println!("Note: the week of {} has not ended yet.\n", meal_plan.week_start_date.format("%Y-%m-%d"));
+            }
+            print!("{}", review.to_markdown());
+            let alerts = budget_alerts(&config, &storage_path, &meal_plan)?;
+            if !alerts.is_empty() {
+                println!("\n### Budget alerts\n");
+                for alert in &alerts {
+                    println!("- {}", alert);
+                }
+            }
+
+            if let Some(journal) = journal {
+                let journal = journal.unwrap_or_else(|| storage_path.join("review_journal.md"));
+                review.append_to_journal(&journal)
+                    .map_err(|e| CliError::io("Failed to write review journal", e))?;
+                println!("\nAppended the review to {:?}", journal);
+            }
+        }
+        Some(Commands::Freeze { week }) => {
+            let week = resolve_week(&week, meal_plan.week_start_date)?;
+            let path = storage_path.join(freeze::FROZEN_WEEKS_FILE);
+            let mut frozen = freeze::FrozenWeeks::load_from_json(&path)
+                .map_err(|e| CliError::io("Failed to load frozen weeks", e))?;
+            if frozen.freeze(week, Utc::now()) {
+                frozen.save_to_json(&path)
+                    .map_err(|e| CliError::io("Failed to save frozen weeks", e))?;
+                println!("Froze the week of {}. Changing its meals now needs --force.", week.format("%Y-%m-%d"));
+            } else {
+                println!("The week of {} is already frozen.", week.format("%Y-%m-%d"));
+            }
+        }
+        Some(Commands::Unfreeze { week }) => {
+            let week = resolve_week(&week, meal_plan.week_start_date)?;
+            let path = storage_path.join(freeze::FROZEN_WEEKS_FILE);
+            let mut frozen = freeze::FrozenWeeks::load_from_json(&path)
+                .map_err(|e| CliError::io("Failed to load frozen weeks", e))?;
+            if !frozen.unfreeze(week) {
+                return Err(CliError::not_found(format!("The week of {} isn't frozen.", week.format("%Y-%m-%d"))));
+            }
+            frozen.save_to_json(&path)
+                .map_err(|e| CliError::io("Failed to save frozen weeks", e))?;
+            println!("The week of {} can be changed again.", week.format("%Y-%m-%d"));
+        }
+        Some(Commands::Snapshot { action }) => {
+            let path = storage_path.join(snapshots::SNAPSHOTS_FILE);
+            let mut snapshots = snapshots::Snapshots::load_from_json(&path)
+                .map_err(|e| CliError::io("Failed to load snapshots", e))?;
+            match action {
+                SnapshotAction::Create { name, replace } => {
+                    let replaced = snapshots.take(&name, &meal_plan, Utc::now(), replace).map_err(CliError::conflict)?;
+                    snapshots.save_to_json(&path)
+                        .map_err(|e| CliError::io("Failed to save snapshots", e))?;
+                    println!(
+                        "{} snapshot \"{}\" of the week of {} ({} meals).",
+                        if replaced { "Retook" } else { "Took" },
+                        name.trim(),
+                        meal_plan.week_start_date.format("%Y-%m-%d"),
+                        meal_plan.meals().len()
+                    );
+                }
+                SnapshotAction::Restore { name } => {
+                    let snapshot = snapshots.find(&name)
+                        .ok_or_else(|| CliError::not_found(format!("No snapshot called \"{}\".", name.trim())))?;
+                    let summary = snapshots::restore(&mut meal_plan, snapshot).map_err(CliError::conflict)?;
+                    save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+                    println!(
+                        "Restored \"{}\": {} added, {} changed, {} moved to the trash.",
+                        snapshot.name, summary.added, summary.changed, summary.removed
+                    );
+                }
+                SnapshotAction::List => print!("{}", format_snapshots(&snapshots, meal_plan.week_start_date)),
+                SnapshotAction::Delete { name } => {
+                    if !snapshots.remove(&name) {
+                        return Err(CliError::not_found(format!("No snapshot called \"{}\".", name.trim())));
+                    }
+                    snapshots.save_to_json(&path)
+                        .map_err(|e| CliError::io("Failed to save snapshots", e))?;
+                    println!("Deleted snapshot \"{}\".", name.trim());
+                }
+            }
+        }
+        Some(Commands::SwapRequest { meal_type, day, to }) => {
+            validate_cook(&config, &to)?;
+            let slot = (parse_meal_type(&meal_type)?, parse_day(&day, &meal_plan)?);
+            let today = Local::now().date_naive();
+            if meal_plan.date_of(&slot.1) < today {
+                return Err(CliError::usage(format!("{} on {} is already past.", slot.0, slot.1)));
+            }
+            let path = storage_path.join(swaps::SWAPS_FILE);
+            let mut swaps = load_swaps(&path, today)?;
+            let request = swaps.request(&meal_plan, &slot.0, &slot.1, &to, Utc::now()).map_err(CliError::usage)?;
+            confirm_cook_available(&config, &meal_plan, &request.to, &day, prompter)?;
+            swaps.save_to_json(&path)
+                .map_err(|e| CliError::io("Failed to save swap requests", e))?;
+            println!("Asked {} to cook {} instead of {}.", request.to, request.meal(), request.from);
+            let message = format!(
+                "{} asked {} to cook {}. Run 'mealplan swap-accept' to take it over.",
+                request.from, request.to, request.meal()
+            );
+            notify_swap(&config, &request.to, &message);
+        }
+        Some(Commands::SwapAccept { meal_type, day, cook }) => {
+            let cook = cook.unwrap_or_else(|| current_user(&config));
+            let path = storage_path.join(swaps::SWAPS_FILE);
+            let mut swaps = load_swaps(&path, Local::now().date_naive())?;
+            let request = pick_swap(&swaps, &meal_plan, &cook, meal_type, day)?;
+            let applied = swaps::apply(&mut meal_plan, &request, &current_user(&config));
+            if applied.is_ok() {
+                save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+            }
+            // A request that no longer fits the plan can't be accepted later either
+            swaps.remove(&request);
+            swaps.save_to_json(&path)
+                .map_err(|e| CliError::io("Failed to save swap requests", e))?;
+            applied.map_err(CliError::conflict)?;
+            println!("{} is now cooking {}.", request.to, request.meal());
+            notify_swap(&config, &request.from, &format!("{} took over {} from {}.", request.to, request.meal(), request.from));
+        }
+        Some(Commands::SwapDecline { meal_type, day, cook }) => {
+            let cook = cook.unwrap_or_else(|| current_user(&config));
+            let path = storage_path.join(swaps::SWAPS_FILE);
+            let mut swaps = load_swaps(&path, Local::now().date_naive())?;
+            let request = pick_swap(&swaps, &meal_plan, &cook, meal_type, day)?;
+            swaps.remove(&request);
+            swaps.save_to_json(&path)
+                .map_err(|e| CliError::io("Failed to save swap requests", e))?;
+            println!("Declined {}; {} still cooks it.", request.meal(), request.from);
+            notify_swap(&config, &request.from, &format!("{} can't take over {}.", request.to, request.meal()));
+        }
+        Some(Commands::Swaps) => {
+            let swaps = load_swaps(&storage_path.join(swaps::SWAPS_FILE), Local::now().date_naive())?;
+            print!("{}", format_swaps(&swaps));
+        }
+        Some(Commands::History { limit }) => {
+            let events = EventLog::new(&storage_path).read()
+                .map_err(|e| CliError::io("Failed to read event log", e))?;
+            print!("{}", format_history(&events, limit));
+        }
+        Some(Commands::Trash { action: TrashAction::List }) => {
+            print!("{}", format_trash(&meal_plan, config.trash_retention_days));
+        }
+        Some(Commands::Trash { action: TrashAction::Restore { id } }) => {
+            if !meal_plan.trash.iter().any(|t| t.id == id) {
+                return Err(CliError::not_found(format!("No meal with id {} in the trash.", id)));
+            }
+            let meal = meal_plan.restore_from_trash(id).map_err(CliError::conflict)?;
+            println!("Restored {} on {}: {}", meal.meal_type, meal.day, meal.description);
+            save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+        }
+        Some(Commands::Archive { action: ArchiveAction::Compact { older_than } }) => {
+            let before = date_before(Local::now().date_naive(), parse_period(&older_than)?)?;
+            let summary = Archive::new(&storage_path).compact(before)
+                .map_err(|e| CliError::io("Failed to compact archive", e))?;
+            if summary.weeks == 0 {
+                println!("No archived weeks to compress.");
+            } else {
+                println!(
+                    "Compressed {} week{} from {} to {} bytes.",
+                    summary.weeks,
+                    if summary.weeks == 1 { "" } else { "s" },
+                    summary.bytes_before,
+                    summary.bytes_after
+                );
+            }
+        }
+        Some(Commands::Push { target: PushTarget::Sheets { spreadsheet_id, sheet, credentials } }) => {
+            let access_token = google_access_token(credentials)?;
+            sheets::push_week(&meal_plan, &spreadsheet_id, &sheet, &access_token)
+                .map_err(|e| CliError::io("Failed to update the Google Sheet", e))?;
+            println!("Pushed the week of {} to {}.", meal_plan.week_start_date.format("%Y-%m-%d"), sheets::sheet_range(&sheet));
+        }
+        Some(Commands::Report { month, year, format, output, filter }) => {
+            let period = match (month, year) {
+                (Some(month), _) => ReportPeriod::parse_month(&month)
+                    .ok_or_else(|| CliError::usage(format!("Invalid month: {}. Use YYYY-MM, e.g. 2025-02.", month)))?,
+                (None, Some(year)) => ReportPeriod::year(year)
+                    .ok_or_else(|| CliError::usage(format!("Invalid year: {}.", year)))?,
+                (None, None) => return Err(CliError::usage("Specify --month or --year.")),
+            };
+            let archive = Archive::new(&storage_path);
+            let history = filtered_history(&archive, &meal_plan, period.first_week().unwrap_or(NaiveDate::MIN), filter.as_deref())?;
+            let grocery_lists = report_grocery_lists(&archive, &meal_plan, &storage_path, period)?;
+            let report = PeriodReport::new(period, &history, &grocery_lists);
+            let contents = match format.to_lowercase().as_str() {
+                "markdown" | "md" => report.to_markdown(),
+                "html" => report.to_html(),
+                _ => return Err(CliError::usage("Invalid format. Must be markdown or html.")),
+            };
+
+            match output {
+                Some(output) => {
+                    std::fs::write(&output, contents)
+                        .map_err(|e| CliError::io("Failed to write report", e))?;
+                    println!("Report written to {:?}", output);
+                }
+                None => print!("{}", contents),
+            }
+        }
+        Some(Commands::Rollover { auto }) => {
+            if auto && args.force {
+                return Err(CliError::usage("--auto only rolls over once the week has ended, so it can't be used with --force."));
+            }
+            let today = Local::now().date_naive();
+            if auto && !week_has_ended(&meal_plan, today) {
+                println!(
+                    "The week of {} has not ended yet. Nothing to roll over.",
+                    meal_plan.week_start_date.format("%Y-%m-%d")
+                );
+            } else {
+                let archive = Archive::new(&storage_path);
+                let mut next_week = rollover(&meal_plan, &archive, today, args.force)?;
+                let week: Vec<NaiveDate> = (0..7).map(|n| next_week.week_start_date + Duration::days(n)).collect();
+                apply_day_templates(&config, &mut next_week, &week, None);
+                match GroceryList::load_from_json(storage_path.join("grocery_list.json")) {
+                    Ok(list) if list.week_start_date == meal_plan.week_start_date && !list.items.is_empty() => {
+                        archive.save_groceries(&list)
+                            .map_err(|e| CliError::io("Failed to archive grocery list", e))?;
+                    }
+                    _ => {}
+                }
+                save_meal_plan(&next_week, &storage_path, config.backup_count)?;
+
+                if config_path.exists() {
+                    let updated_config = Config {
+                        current_week_start_date: next_week.week_start_date,
+                        ..file_config.clone()
+                    };
+                    save_config(&updated_config, &config_path, config_error.as_deref())?;
+                }
+                println!("Started the week of {}.", next_week.week_start_date.format("%Y-%m-%d"));
+            }
+        }
+        Some(Commands::Daemon { interval, debounce, remotes, action: None }) => {
+            let config_with_storage = Config {
+                meal_plan_storage_path: storage_path.clone(),
+                ..config.clone()
+            };
+            let shared: reload::SharedConfig = Arc::new(RwLock::new(config_with_storage));
+            let storage_override = args.path.clone();
+            reload::watch_config(config_path.clone(), Arc::clone(&shared), move || reload_config(&config_path, storage_override.as_deref()));
+            run_daemon(&shared, interval, debounce, &remotes)?;
+        }
+        Some(Commands::Daemon { interval, debounce, remotes, action: Some(DaemonAction::Install) }) => {
+            daemon_install(&config_path, &storage_path, interval, debounce, &remotes, prompter)?;
+        }
+        Some(Commands::Daemon { action: Some(DaemonAction::Status), .. }) => {
+            daemon_status(&storage_path)?;
+        }
+        Some(Commands::Apply { file, dry_run }) => {
+            let input = if file == Path::new("-") {
+                let mut input = String::new();
+                io::stdin().read_to_string(&mut input)
+                    .map_err(|e| CliError::io("Failed to read operations from stdin", e))?;
+                input
+            } else {
+                std::fs::read_to_string(&file)
+                    .map_err(|e| CliError::io(&format!("Failed to read {:?}", file), e))?
+            };
+            let ops = bulk::parse_ops(&input).map_err(CliError::usage)?;
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            let (updated, summary) = apply_ops(&config, &meal_plan, &book, ops, &current_user(&config))?;
+            if !dry_run {
+                meal_plan = updated;
+                save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+            }
+            print!("{}", format_bulk_summary(&summary, dry_run));
+        }
+        Some(Commands::Import { file, map, from, no_header, dry_run, on_duplicate }) => {
+            let rows = match (from, map) {
+                (Some(app), _) => {
+                    let export = apps::read_export(app, &file)
+                        .map_err(|e| CliError::io(&format!("Failed to read {:?}", file), e))?;
+                    let book_path = storage_path.join("recipes.json");
+                    let mut book = RecipeBook::load_from_json(&book_path)
+                        .map_err(|e| CliError::io("Failed to load recipes", e))?;
+                    let (added, kept) = import_recipes(&mut book, export.recipes);
+                    if added > 0 && !dry_run {
+                        book.save_to_json(&book_path)
+                            .map_err(|e| CliError::io("Failed to save recipes", e))?;
+                    }
+                    if added + kept > 0 {
+                        println!(
+                            "{} {} recipe(s) from {}, leaving {} already in the recipe book.",
+                            if dry_run { "Would add" } else { "Added" },
+                            added,
+                            app,
+                            kept
+                        );
+                    }
+                    if export.meals.is_empty() {
+                        return Ok(());
+                    }
+                    export.meals
+                }
+                (None, Some(map)) => {
+                    let map = import::ColumnMap::parse(&map).map_err(CliError::usage)?;
+                    let rows = import::read_rows(&file)
+                        .map_err(|e| CliError::io(&format!("Failed to read {:?}", file), e))?;
+                    let (rows, first_row) = if no_header { (rows, 1) } else { (rows.into_iter().skip(1).collect(), 2) };
+                    import::map_rows(rows, &map, first_row)
+                }
+                (None, None) => return Err(CliError::usage("Pass --map, or --from with the app the file was exported from.")),
+            };
+
+            let archive = Archive::new(&storage_path);
+            let mut imported_plan = meal_plan.clone();
+            let interactive = prompter.interactive();
+            let summary = import_meals(&mut imported_plan, &archive, rows, &current_user(&config), |existing, incoming, collision| {
+                match on_duplicate {
+                    Some(strategy) => strategy,
+                    None if interactive => ask_duplicate(existing, incoming, collision, "The import", prompter),
+                    None => duplicates::Strategy::Skip,
+                }
+            })?;
+            for (row, problem) in &summary.errors {
+                println!("Row {}: {}", row, problem);
+            }
+            for (row, duplicate) in &summary.duplicates {
+                println!("Row {}: {}", row, duplicate);
+            }
+
+            if !dry_run {
+                for week in summary.archived_weeks.values() {
+                    archive.save_week(week)
+                        .map_err(|e| CliError::io("Failed to archive imported week", e))?;
+                }
+                if summary.into_current > 0 || summary.replaced + summary.combined > 0 {
+                    save_meal_plan(&imported_plan, &storage_path, config.backup_count)?;
+                }
+            }
+            println!(
+                "{} {} meal{} ({} into this week, {} into {} archived week{}). Skipped {} row{}.",
+                if dry_run { "Would import" } else { "Imported" },
+                summary.into_current + summary.into_archive,
+                if summary.into_current + summary.into_archive == 1 { "" } else { "s" },
+                summary.into_current,
+                summary.into_archive,
+                summary.archived_weeks.len(),
+                if summary.archived_weeks.len() == 1 { "" } else { "s" },
+                summary.errors.len(),
+                if summary.errors.len() == 1 { "" } else { "s" },
+            );
+            if !summary.duplicates.is_empty() || summary.replaced + summary.combined > 0 {
+                println!(
+                    "{} already planned: {} left as they were, {} replaced, {} kept alongside.",
+                    summary.duplicates.len() + summary.replaced + summary.combined,
+                    summary.duplicates.len(),
+                    summary.replaced,
+                    summary.combined,
+                );
+            }
+        }
+        Some(Commands::Bundle { action: BundleAction::Export { file } }) => {
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            bundle_export(&meal_plan, &book, &file, prompter)?;
+            println!("Meal plan bundle exported successfully: {:?}", file);
+        }
+        Some(Commands::Bundle { action: BundleAction::Import { file, on_duplicate } }) => {
+            let recipes_path = storage_path.join("recipes.json");
+            let mut book = RecipeBook::load_from_json(&recipes_path)
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            bundle_import(&mut meal_plan, &mut book, &file, on_duplicate, prompter)?;
+            book.save_to_json(&recipes_path)
+                .map_err(|e| CliError::io("Failed to save recipes", e))?;
+            save_meal_plan(&meal_plan, &storage_path, config.backup_count)?;
+        }
+        None => {
+            println!("Welcome to the Meal Plan CLI Tool!");
+            println!("This tool helps you organize and manage your weekly meal plans.");
+            println!("Use --help to see available commands.");
+            
+            // Show a summary of the current meal plan if it exists
+            if !meal_plan.meals().is_empty() {
+                println!("\nCurrent Meal Plan Summary:");
+                println!("Week starting: {}", meal_plan.week_start_date.format("%Y-%m-%d"));
+                println!("Total meals: {}", meal_plan.meals().len());
+                println!("Last modified: {}", meal_plan.last_modified.format("%Y-%m-%d %H:%M:%S"));
+                
+                // Group meals by day for a cleaner display
+                let mut meals_by_day: HashMap<String, Vec<&Meal>> = HashMap::new();
+                for meal in meal_plan.meals() {
+                    let day_str = format!("{}", meal.day);
+                    meals_by_day.entry(day_str).or_default().push(meal);
+                }
+                
+                for (day, meals) in meals_by_day {
+                    println!("\n{}:", day);
+                    for meal in meals {
+                        println!("  {}: {} (Cook: {})", meal.meal_type, meal_plan.render_description(meal), meal.cook);
+                    }
+                }
+            }
+        }
+    }
+
+    println!("Storage path: {:?}", storage_path);
+    Ok(())
+}
+
+/// The days `add` plans a meal on: the `--day` list as given, or every date from `--from`
+/// to `--to`, which must fall in the plan's week
+fn add_days(meal_plan: &MealPlan, day: Option<String>, from: Option<String>, to: Option<String>) -> Result<String, CliError> {
+    let (Some(from), Some(to)) = (from, to) else {
+        return Ok(day.unwrap_or_default());
+    };
+    let parse = |text: &str| {
+        NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d").map_err(|_| CliError::usage(format!("Invalid date {:?}. Use YYYY-MM-DD.", text)))
+    };
+    let (from, to) = (parse(&from)?, parse(&to)?);
+    if to < from {
+        return Err(CliError::usage(format!("The range ends on {} before it starts on {}.", to, from)));
+    }
+    let week_end = meal_plan.week_start_date + Duration::days(6);
+    if from < meal_plan.week_start_date || to > week_end {
+        return Err(CliError::usage(format!(
+            "The range must fall in the plan's week, {} to {}.",
+            meal_plan.week_start_date.format("%Y-%m-%d"),
+            week_end.format("%Y-%m-%d")
+        )));
+    }
+    let dates: Vec<String> = from.iter_days().take_while(|date| *date <= to).map(|date| date.format("%Y-%m-%d").to_string()).collect();
+    Ok(dates.join(","))
+}
+
+/// Details `add` sets on the meals it plans besides the description and cook
+#[derive(Debug, Default)]
+struct MealExtras {
+    packed: bool,
+    place: Option<String>,
+    photo: Option<String>,
+}
+
+/// Works out the description and extras for `add`. A meal eaten out must name a place in
+/// the places list, and is described by the place's name unless a description is given.
+/// A photo file is copied into the assets directory.
+fn meal_extras(storage_path: &Path, description: Option<String>, packed: bool, eat_out: Option<String>, photo: Option<String>) -> Result<(String, MealExtras), CliError> {
+    let photo = photo.map(|photo| import_photo(storage_path, &photo)).transpose()?;
+    let Some(eat_out) = eat_out else {
+        return Ok((description.unwrap_or_default(), MealExtras { packed, place: None, photo }));
+    };
+    let places = Places::load_from_json(storage_path.join("places.json"))
+        .map_err(|e| CliError::io("Failed to load places", e))?;
+    let place = places.find(&eat_out).ok_or_else(|| {
+        CliError::not_found(format!("No place named {}. Add it with 'mealplan places add \"{}\"'.", eat_out.trim(), eat_out.trim()))
+    })?;
+    let description = description.unwrap_or_else(|| place.name.clone());
+    Ok((description, MealExtras { packed, place: Some(place.name.clone()), photo }))
+}
+
+/// Copies a photo into the assets directory, or keeps a web address as it is
+fn import_photo(storage_path: &Path, photo: &str) -> Result<String, CliError> {
+    photos::import_photo(storage_path, photo).map_err(|e| match e.kind() {
+        std::io::ErrorKind::InvalidInput => CliError::usage(e.to_string()),
+        _ => CliError::io(&format!("Failed to copy the photo {}", photo.trim()), e),
+    })
+}
+
+/// Plans a meal on each of the days given. The cook may be left empty only for a meal
+/// eaten out.
+#[allow(clippy::too_many_arguments)]
+fn add_command(config: &Config, meal_plan: &mut MealPlan, book: &RecipeBook, description: String, meal_type: String, day: String, cook: String, extras: MealExtras, prompter: &mut dyn Prompter) -> Result<(), CliError> {
+    let days = days::split_days(&day);
+    if days.is_empty() {
+        return Err(CliError::usage("Give a day to plan the meal on."));
+    }
+    // Check every day before planning any of them
+    let parsed = days.iter().map(|day| parse_day(day, meal_plan)).collect::<Result<Vec<_>, _>>()?;
+
+    let alias = config.find_alias(&description);
+    let dish = match alias {
+        Some(alias) => dish(&alias.description, &alias.tags, book),
+        None => dish(&description, &[], book),
+    };
+    let cook = match cook.trim() {
+        "" if extras.place.is_some() => String::new(),
+        _ => cook_or_default(config, &cook, &parse_meal_type(&meal_type)?)?,
+    };
+    for day in &days {
+        let cook = match cook.trim() {
+            "" if extras.place.is_some() => String::new(),
+            _ => {
+                let cook = resolve_cook(config, meal_plan, &cook, &meal_type, day, &dish)?;
+                validate_cook(config, &cook)?;
+                confirm_cook_available(config, meal_plan, &cook, day, prompter)?;
+                cook
+            }
+        };
+        add_meal_as(meal_plan, meal_type.clone(), day.clone(), cook, description.clone(), alias, &current_user(config), prompter)?;
+    }
+    if extras.packed || extras.place.is_some() || extras.photo.is_some() {
+        let meal_type = parse_meal_type(&meal_type)?;
+        for day in &parsed {
+            meal_plan.change_meal(&meal_type, day, |meal| {
+                meal.packed = extras.packed;
+                meal.place = extras.place.clone();
+                meal.photo = extras.photo.clone();
+            });
+        }
+    }
+
+    let added_to = match parsed.len() {
+        1 => String::new(),
+        _ => format!(" on {}", parsed.iter().map(|day| day.to_string()).collect::<Vec<_>>().join(", ")),
+    };
+    match alias {
+        Some(alias) => println!("Meal added successfully{}: {}", added_to, alias.description),
+        None => println!("Meal added successfully{}.", added_to),
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn edit_command(config: &Config, meal_plan: &mut MealPlan, storage_path: &Path, description: Option<String>, meal_type: String, day: String, cook: Option<String>, packed: Option<bool>, photo: Option<String>, prompter: &mut dyn Prompter) -> Result<(), CliError> {
+    let photo = match photo {
+        Some(photo) if photo.trim().is_empty() => Some(None),
+        Some(photo) => Some(Some(import_photo(storage_path, &photo)?)),
+        None => None,
+    };
+    if let Some(cook) = &cook {
+        validate_cook(config, cook)?;
+        confirm_cook_available(config, meal_plan, cook, &day, prompter)?;
+    }
+    let completions = if cook.is_none() || description.is_none() {
+        edit_completions(config, meal_plan, storage_path)?
+    } else {
+        Completions::default()
+    };
+    let slot = (parse_meal_type(&meal_type)?, parse_day(&day, meal_plan)?);
+    edit_meal(meal_plan, meal_type, day, cook, description, &completions, &current_user(config), prompter)?;
+    if let Some(packed) = packed {
+        meal_plan.change_meal(&slot.0, &slot.1, |meal| meal.packed = packed);
+    }
+    if let Some(photo) = photo {
+        meal_plan.change_meal(&slot.0, &slot.1, |meal| meal.photo = photo);
+    }
+    println!("Meal updated successfully.");
+    Ok(())
+}
+
+/// Lists the example workflows, or prints one's commands, each under what `--help` says
+/// the command does
+fn format_examples(topic: Option<&str>) -> Result<String, CliError> {
+    let Some(name) = topic else {
+        let mut output = String::from("Examples:\n");
+        for topic in examples::TOPICS {
+            output.push_str(&format!("  {:<10} {}\n", topic.name, topic.title));
+        }
+        output.push_str("Run 'mealplan examples <topic>' for its commands.\n");
+        return Ok(output);
+    };
+    let topic = examples::find(name).ok_or_else(|| {
+        let names: Vec<&str> = examples::TOPICS.iter().map(|topic| topic.name).collect();
+        CliError::not_found(format!("No examples for '{}'. Try one of: {}.", name.trim(), names.join(", ")))
+    })?;
+
+    let root = Args::command();
+    let mut output = format!("# {}\n", topic.title);
+    for words in topic.commands {
+        let mut command = &root;
+        for word in words.iter() {
+            match command.find_subcommand(word) {
+                Some(subcommand) => command = subcommand,
+                None => break,
+            }
+        }
+        if let Some(about) = command.get_about() {
+            output.push_str(&format!("\n# {}\n", about));
+        }
+        let line: Vec<String> = words.iter().map(|word| examples::shell_word(word)).collect();
+        output.push_str(&format!("mealplan {}\n", line.join(" ")));
+    }
+    Ok(output)
+}
+
+const SHELL_HELP: &str = "\
+Type mealplan commands without 'mealplan', e.g. add \"Tacos\" -t dinner -d monday -c Alice
+add, edit, remove, clone and cooked change the plan in the shell; other commands need it saved first.
+  show   list the plan as it is in the shell
+  :w     save the plan
+  :q     save any changes and leave (also :wq, exit or Ctrl-D)
+  :q!    leave without saving
+Press Tab to complete commands, meal types, days and cooks, and Up for earlier lines.
+";
+
+/// Runs the interactive shell. The plan stays in memory between commands and is written
+/// to disk only on :w or when leaving.
+fn run_shell(config: &Config, meal_plan: &mut MealPlan, storage_path: &Path, prompter: &mut dyn Prompter) -> Result<(), CliError> {
+    let commands = Args::command()
+        .get_subcommands()
+        .map(|command| command.get_name().to_string())
+        .filter(|name| name != "shell")
+        .collect();
+    let mut arguments: Vec<String> = ["breakfast", "lunch", "dinner", "snack"].iter().map(|t| t.to_string()).collect();
+    arguments.extend(["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"].iter().map(|d| d.to_string()));
+    arguments.extend(config.cooks.iter().map(|c| c.name.clone()));
+    let mut editor = ShellEditor::new(commands, arguments)
+        .map_err(|e| CliError::io("Failed to start the shell", e))?;
+
+    println!(
+        "Planning the week of {}. Type help for commands, :w to save and :q to leave.",
+        meal_plan.week_start_date.format("%Y-%m-%d")
+    );
+    let mut unsaved = false;
+    loop {
+        let line = editor.read_line("mealplan> ")
+            .map_err(|e| CliError::io("Failed to read input", e))?;
+        let input = match line.as_deref().map(ShellInput::parse) {
+            None => ShellInput::Quit,
+            Some(Ok(input)) => input,
+            Some(Err(e)) => {
+                eprintln!("Error: {}", e);
+                continue;
+            }
+        };
+
+        match input {
+            ShellInput::Empty => {}
+            ShellInput::Help => print!("{}", SHELL_HELP),
+            ShellInput::Show => print!("{}", format_plan_listing(meal_plan)),
+            ShellInput::Write => {
+                save_meal_plan(meal_plan, storage_path, config.backup_count)?;
+                unsaved = false;
+                println!("Saved.");
+            }
+            ShellInput::Quit | ShellInput::WriteQuit => {
+                if unsaved {
+                    save_meal_plan(meal_plan, storage_path, config.backup_count)?;
+                    println!("Saved.");
+                }
+                return Ok(());
+            }
+            ShellInput::ForceQuit => {
+                if unsaved {
+                    println!("Unsaved changes were discarded.");
+                }
+                return Ok(());
+            }
+            ShellInput::Command(words) => {
+                let args = match Args::try_parse_from(std::iter::once("mealplan".to_string()).chain(words)) {
+                    Ok(args) => args,
+                    Err(e) => {
+                        let _ = e.print();
+                        continue;
+                    }
+                };
+                match shell_command(config, meal_plan, storage_path, args, unsaved, prompter) {
+                    Ok(changed) => unsaved |= changed,
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+            }
+        }
+    }
+}
+
+/// Runs one command typed at the shell, returning whether it changed the plan in memory.
+/// Commands that don't just change meals run as they would outside the shell, on the saved
+/// plan, which is then reloaded.
+fn shell_command(config: &Config, meal_plan: &mut MealPlan, storage_path: &Path, args: Args, unsaved: bool, prompter: &mut dyn Prompter) -> Result<bool, CliError> {
+    // Forcing one command lets the rest of the session's changes to a frozen week be saved
+    meal_plan.allow_frozen |= args.force;
+    match args.command {
+        Some(Commands::Add { description, meal_type, day, from, to, cook, packed, eat_out, photo }) => {
+            let day = add_days(meal_plan, day, from, to)?;
+            let (description, extras) = meal_extras(storage_path, description, packed, eat_out, photo)?;
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            add_command(config, meal_plan, &book, description, meal_type, day, cook.unwrap_or_default(), extras, prompter)?;
+            Ok(true)
+        }
+        Some(Commands::Edit { description, meal_type, day, cook, packed, photo }) => {
+            edit_command(config, meal_plan, storage_path, description, meal_type, day, cook, packed, photo, prompter)?;
+            Ok(true)
+        }
+        Some(Commands::Remove { meal_type, day, all: false, .. }) => {
+            remove_meal(meal_plan, meal_type.unwrap_or_default(), day.unwrap_or_default(), prompter)?;
+            println!("Meal moved to the trash.");
+            Ok(true)
+        }
+        Some(Commands::Remove { meal_type, day, all: true, cook, filter, week, yes }) => {
+            let matching = MealSelection { meal_type, day, cook, filter, week };
+            Ok(remove_matching(meal_plan, &matching, yes, prompter)? > 0)
+        }
+        Some(Commands::Clone { meal_type, day, to }) => {
+            clone_meal(meal_plan, &meal_type, &day, &to, &current_user(config), prompter)?;
+            Ok(true)
+        }
+        Some(Commands::Cooked { meal_type, day, rating }) => {
+            mark_cooked(meal_plan, &meal_type, &day, rating)?;
+            println!("Marked the meal as cooked.");
+            Ok(true)
+        }
+        Some(Commands::Shell) => Err(CliError::usage("You are already in the shell.")),
+        None => Ok(false),
+        Some(_) if unsaved => Err(CliError::usage("Save your changes with :w before running this command.")),
+        Some(command) => {
+            run(Args { command: Some(command), path: Some(storage_path.to_path_buf()), ..args }, prompter)?;
+            // A plan that has never been saved stays as it is
+            match load_meal_plan(storage_path) {
+                Ok(plan) => {
+                    *meal_plan = plan;
+                    meal_plan.replica_id = current_replica(config);
+                    meal_plan.user = Some(current_user(config));
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(CliError::io("Failed to reload the meal plan", e)),
+            }
+            Ok(false)
+        }
+    }
+}
+
+/// Lists the plan's meals in date order, one per line
+fn format_plan_listing(meal_plan: &MealPlan) -> String {
+    if meal_plan.meals().is_empty() {
+        return format!("No meals planned for the week of {}.\n", meal_plan.week_start_date.format("%Y-%m-%d"));
+    }
+    let mut meals: Vec<&Meal> = meal_plan.meals().iter().collect();
+    meals.sort_by_key(|meal| (meal_plan.date_of(&meal.day), spreadsheet::GRID_MEAL_TYPES.iter().position(|t| *t == meal.meal_type)));
+    let mut output = String::new();
+    for meal in meals {
+        output.push_str(&format!(
+            "{} {}: {}",
+            meal_plan.date_of(&meal.day).format("%a %Y-%m-%d"),
+            meal.meal_type,
+            meal_plan.render_description(meal)
+        ));
+        if !meal.cook.trim().is_empty() {
+            output.push_str(&format!(" ({})", meal.cook));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// Loads the current plan. The JSON snapshot is used when it matches its checksum and
+/// includes every event in the log; otherwise the plan is rebuilt by replaying the log,
+/// which then holds changes the snapshot is missing. With no log yet, the snapshot is used.
+fn load_meal_plan(storage_path: &Path) -> std::io::Result<MealPlan> {
+    let snapshot_path = storage_path.join("meal_plan.json");
+    let log = EventLog::new(storage_path);
+
+    let events = log.read()?;
+    let Some(last_seq) = events.last().map(|logged| logged.seq) else {
+        return load_snapshot(&snapshot_path, &log);
+    };
+    match storage::verify_checksum(&snapshot_path).ok().flatten() {
+        Some(true) => {
+            if let Some(plan) = MealPlan::load_from_json(&snapshot_path).ok().filter(|plan| plan.event_seq >= last_seq) {
+                return Ok(plan);
+            }
+        }
+        // Damaged, so recovered with a copy kept and a warning
+        Some(false) => return load_snapshot(&snapshot_path, &log),
+        None => {}
+    }
+    match events::project(&events) {
+        Some(plan) => Ok(plan),
+        None => load_snapshot(&snapshot_path, &log),
+    }
+}
+
+/// Loads the JSON snapshot. A damaged snapshot, one that doesn't load or doesn't match its
+/// checksum, is rebuilt from the event log, which holds every saved change, or else taken
+/// from the most recent backup that is intact. A snapshot that loads but doesn't match is
+/// copied to meal_plan.json.damaged first, and used after all if nothing else can be.
+fn load_snapshot(snapshot_path: &Path, log: &EventLog) -> std::io::Result<MealPlan> {
+    let (error, mismatched) = match MealPlan::load_from_json(snapshot_path) {
+        Ok(plan) if storage::verify_checksum(snapshot_path).ok().flatten() != Some(false) => return Ok(plan),
+        Ok(plan) => (
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "it doesn't match its checksum"),
+            Some(plan),
+        ),
+        Err(e) => (e, None),
+    };
+    if !snapshot_path.exists() {
+        return Err(error);
+    }
+
+    let recovered = log
+        .project()
+        .ok()
+        .flatten()
+        .map(|plan| (plan, format!("Rebuilt the meal plan from the change log {:?}.", log.path())))
+        .or_else(|| {
+            storage::backups(snapshot_path)
+                .into_iter()
+                .filter(|backup| storage::verify_checksum(backup).ok().flatten() != Some(false))
+                .find_map(|backup| {
+                    let plan = MealPlan::load_from_json(&backup).ok()?;
+                    Some((plan, format!("Recovered the meal plan from backup {:?}.", backup)))
+                })
+        });
+    match (recovered, mismatched) {
+        (Some((plan, recovered_from)), mismatched) => {
+            eprintln!("Warning: Failed to load meal plan: {}", error);
+            if mismatched.is_some() {
+                match keep_damaged_plan(snapshot_path) {
+                    Ok(damaged) => eprintln!("Kept a copy of it in {:?}.", damaged),
+                    Err(e) => eprintln!("Warning: Failed to keep a copy of it: {}", e),
+                }
+            }
+            eprintln!("{}", recovered_from);
+            Ok(plan)
+        }
+        (None, Some(plan)) => {
+            eprintln!("Warning: {:?} doesn't match its checksum and there is no backup to recover; using it as it is.", snapshot_path);
+            Ok(plan)
+        }
+        (None, None) => Err(error),
+    }
+}
+
+/// Opens a file in $VISUAL or $EDITOR, waiting for the editor to exit, or else hands it to
+/// the desktop's default app for its type
+fn open_file(path: &Path) -> Result<(), CliError> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.trim().is_empty());
+
+    let mut command = match editor {
+        Some(editor) => {
+            // Editors are often given with arguments, such as "code --wait"
+            let words = shell::split_words(&editor).map_err(|e| CliError::usage(format!("Invalid $EDITOR: {}", e)))?;
+            let mut command = std::process::Command::new(&words[0]);
+            command.args(&words[1..]);
+            command
+        }
+        None if cfg!(target_os = "macos") => std::process::Command::new("open"),
+        None if cfg!(target_os = "windows") => {
+            let mut command = std::process::Command::new("cmd");
+            command.args(["/C", "start", ""]);
+            command
+        }
+        None => std::process::Command::new("xdg-open"),
+    };
+
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .arg(path)
+        .status()
+        .map_err(|e| CliError::io(&format!("Failed to run {}", program), e))?;
+    if !status.success() {
+        return Err(CliError::new(error::ErrorKind::General, format!("{} exited with {}", program, status)));
+    }
+    Ok(())
+}
+
+/// Upgrades the current plan and the uncompressed archived weeks from older layouts. Each
+/// file that changes is copied to `backups/pre-migrate` first.
+fn migrate_storage(storage_path: &Path, fallback_week: NaiveDate, dry_run: bool, backup_count: usize) -> Result<(), CliError> {
+    let archive = Archive::new(storage_path);
+    let mut files = vec![(storage_path.join("meal_plan.json"), fallback_week)];
+    let weeks = archive.weeks().map_err(|e| CliError::io("Failed to read the archive", e))?;
+    files.extend(weeks.into_iter().map(|week| (archive.week_path(week), week)).filter(|(path, _)| path.exists()));
+
+    let mut upgraded = Vec::new();
+    for (path, fallback_week) in files {
+        if !path.exists() {
+            continue;
+        }
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| CliError::io(&format!("Failed to read {:?}", path), e))?;
+        let mut value: serde_json::Value = serde_json::from_str(&contents)
+            .map_err(|e| CliError::new(error::ErrorKind::Data, format!("{:?} isn't valid JSON: {}", path, e)))?;
+        let changes = migrate::migrate_plan(&mut value, fallback_week, Utc::now());
+        if changes.is_empty() {
+            continue;
+        }
+        let plan: MealPlan = serde_json::from_value(value).map_err(|e| {
+            CliError::new(error::ErrorKind::Data, format!("{:?} couldn't be upgraded: {}", path, e))
+        })?;
+
+        println!("{}{:?}:", if dry_run { "Would upgrade " } else { "Upgrading " }, path);
+        for change in &changes {
+            println!("  {}", change);
+        }
+        upgraded.push((path, plan));
+    }
+
+    if upgraded.is_empty() {
+        println!("All plan files are up to date.");
+        return Ok(());
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    let backup_dir = storage_path.join(storage::BACKUP_DIR).join("pre-migrate");
+    std::fs::create_dir_all(&backup_dir)
+        .map_err(|e| CliError::io("Failed to create the backup directory", e))?;
+    for (path, _) in &upgraded {
+        let relative = path.strip_prefix(storage_path).unwrap_or(path);
+        let backup = backup_dir.join(relative.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "-"));
+        std::fs::copy(path, &backup)
+            .map_err(|e| CliError::io(&format!("Failed to back up {:?}", path), e))?;
+    }
+
+    // Archived weeks are saved through the archive so its indexes pick up the upgrade, which
+    // can only happen once every week reads
+    let current = storage_path.join("meal_plan.json");
+    for (path, plan) in &upgraded {
+        if *path == current {
+            save_meal_plan(plan, storage_path, backup_count)?;
+        } else {
+            plan.save_to_json(path)
+                .map_err(|e| CliError::io(&format!("Failed to save {:?}", path), e))?;
+        }
+    }
+    for (path, plan) in upgraded.iter().filter(|(path, _)| *path != current) {
+        archive.save_week(plan)
+            .map_err(|e| CliError::io(&format!("Failed to index {:?}", path), e))?;
+    }
+
+    println!("Upgraded {} file(s). The originals were copied to {:?}.", upgraded.len(), backup_dir);
+    Ok(())
+}
+
+/// Copies a meal plan that couldn't be loaded out of the way so starting over doesn't lose it
+fn keep_damaged_plan(meal_plan_path: &Path) -> std::io::Result<PathBuf> {
+    let damaged = meal_plan_path.with_extension("json.damaged");
+    std::fs::copy(meal_plan_path, &damaged)?;
+    Ok(damaged)
+}
+
+/// Saves the meal plan to JSON, records what changed in the event log, and keeps
+/// the markdown copy in step with it
+fn save_meal_plan(meal_plan: &MealPlan, storage_path: &Path, backup_count: usize) -> Result<(), CliError> {
+    let log = EventLog::new(storage_path);
+    let previous = log.project()
+        .map_err(|e| CliError::io("Failed to read event log", e))?;
+    if let Some(previous) = previous.as_ref().filter(|previous| previous.week_start_date == meal_plan.week_start_date) {
+        check_frozen(previous, meal_plan, storage_path)?;
+    }
+
+    // The log is written first, and the snapshot records how far into it it goes, so a crash
+    // between the two leaves the log as the newer one
+    let event_seq = log.append(&events::diff(previous.as_ref(), meal_plan))
+        .map_err(|e| CliError::io("Failed to update event log", e))?;
+    let mut snapshot = meal_plan.clone();
+    snapshot.event_seq = event_seq;
+    snapshot.save_to_json_with_backups(storage_path.join("meal_plan.json"), backup_count)
+        .map_err(|e| CliError::io("Failed to save meal plan", e))?;
+    if let Err(e) = log.compact(&snapshot) {
+        eprintln!("Warning: Failed to compact the change log: {}", e);
+    }
+
+    // Also update markdown for consistency
+    match write_markdown(meal_plan, storage_path) {
+        Ok(Some(kept)) => eprintln!(
+            "Warning: meal_plan.md had been edited by hand and not synced yet; the edits were kept in {:?}.",
+            kept
+        ),
+        Ok(None) => {}
+        Err(e) => eprintln!("Warning: Failed to update markdown file: {}", e),
+    }
+
+    Ok(())
+}
+
+/// Rewrites the Markdown copy of the plan and records the files as in sync. Markdown edited
+/// since the last sync that hasn't been read back in with `sync` yet would be lost, so rather
+/// than being overwritten it is moved to meal_plan.md.edited first. Returns where it was moved.
+fn write_markdown(meal_plan: &MealPlan, storage_path: &Path) -> std::io::Result<Option<PathBuf>> {
+    let json_path = storage_path.join("meal_plan.json");
+    let markdown_path = storage_path.join("meal_plan.md");
+    let state = SyncState::load(storage_path);
+    let edited = state.markdown_hash.is_some() && state.changes(&json_path, &markdown_path)?.markdown;
+    let kept = if edited && markdown_path.exists() {
+        let kept = markdown_path.with_extension("md.edited");
+        std::fs::rename(&markdown_path, &kept)?;
+        Some(kept)
+    } else {
+        None
+    };
+    meal_plan.save_to_markdown(&markdown_path)?;
+    SyncState::record(storage_path, &json_path, &markdown_path)?;
+    Ok(kept)
+}
+
+/// Stops a change to a frozen week unless it was forced, listing what would have changed
+fn check_frozen(previous: &MealPlan, meal_plan: &MealPlan, storage_path: &Path) -> Result<(), CliError> {
+    let frozen = freeze::FrozenWeeks::load_from_json(storage_path.join(freeze::FROZEN_WEEKS_FILE))
+        .map_err(|e| CliError::io("Failed to load frozen weeks", e))?;
+    let Some(frozen_at) = frozen.frozen_at(meal_plan.week_start_date) else {
+        return Ok(());
+    };
+    let changes = freeze::frozen_changes(previous, meal_plan);
+    if changes.is_empty() {
+        return Ok(());
+    }
+    let week = meal_plan.week_start_date.format("%Y-%m-%d");
+    let changes: String = changes.iter().map(|change| format!("\n  {}", change)).collect();
+    if meal_plan.allow_frozen {
+        eprintln!("Warning: changing the frozen week of {}:{}", week, changes);
+        return Ok(());
+    }
+    Err(CliError::conflict(format!(
+        "The week of {} has been frozen since {}, so nothing was saved. The change would have been:{}\n\
+         Add --force to change it anyway, or run 'mealplan unfreeze' first.",
+        week,
+        frozen_at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+        changes
+    )))
+}
+
+/// Marks a meal as cooked, with an optional 1-5 rating
+fn mark_cooked(meal_plan: &mut MealPlan, meal_type_str: &str, day_str: &str, rating: Option<u8>) -> Result<(), CliError> {
+    let meal_type = parse_meal_type(meal_type_str)?;
+    let day = parse_day(day_str, meal_plan)?;
+
+    meal_plan.mark_cooked(&meal_type, &day, rating)
+        .map(|_| ())
+        .ok_or_else(|| CliError::not_found(format!("No {} meal found for {}.", meal_type, day)))
+}
+
+/// Copies a meal to each of the days in `to`, asking before replacing a meal already there.
+/// Whether it was cooked and how it was rated stay with the original.
+fn clone_meal(meal_plan: &mut MealPlan, meal_type_str: &str, day_str: &str, to: &str, updated_by: &str, prompter: &mut dyn Prompter) -> Result<(), CliError> {
+    let meal_type = parse_meal_type(meal_type_str)?;
+    let day = parse_day(day_str, meal_plan)?;
+    let original = meal_plan.find_meal(&meal_type, &day)
+        .cloned()
+        .ok_or_else(|| CliError::not_found(format!("No {} meal found for {}.", meal_type, day)))?;
+
+    let targets = days::split_days(to).iter().map(|d| parse_day(d, meal_plan)).collect::<Result<Vec<_>, _>>()?;
+    if targets.is_empty() {
+        return Err(CliError::usage("Give a day to copy the meal to."));
+    }
+    if targets.iter().any(|target| meal_plan.date_of(target) == meal_plan.date_of(&day)) {
+        return Err(CliError::usage(format!("The meal is already on {}.", day)));
+    }
+
+    for target in &targets {
+        if meal_plan.find_meal(&meal_type, target).is_some() {
+            println!("A {} meal already exists for {}. Do you want to replace it? (y/n)", meal_type, target);
+            if !prompter.confirm() {
+                return Err(CliError::cancelled("Meal not copied due to user cancellation."));
+            }
+        }
+        let mut copy = Meal::new(meal_type.clone(), target.clone(), original.cook.clone(), original.description.clone());
+        copy.tags = original.tags.clone();
+        copy.recipe = original.recipe.clone();
+        copy.updated_by = Some(updated_by.to_string());
+        meal_plan.add_meal(copy);
+    }
+
+    let targets: Vec<String> = targets.iter().map(|target| target.to_string()).collect();
+    println!("Copied {} on {} to {}.", original.description, day, targets.join(", "));
+    Ok(())
+}
+
+/// Moves a meal to the trash, returning its trash id
+fn remove_meal(meal_plan: &mut MealPlan, meal_type_str: String, day_str: String, prompter: &mut dyn Prompter) -> Result<u32, CliError> {
+    // Validate meal type
+    let meal_type = parse_meal_type(&meal_type_str)?;
+
+    // Validate day
+    let day = parse_day(&day_str, meal_plan)?;
+
+    // Check if the meal exists
+    if meal_plan.find_meal(&meal_type, &day).is_none() {
+        return Err(CliError::not_found(format!("No {} meal found for {}.", meal_type, day)));
+    }
+
+    // Check if this is the last meal in the plan
+    if meal_plan.meals().len() == 1 {
+        println!("This is the last meal in your plan. Are you sure you want to remove it? (y/n)");
+        if !prompter.confirm() {
+            return Err(CliError::cancelled("Meal removal cancelled by user."));
+        }
+    }
+
+    // Remove the meal, keeping it in the trash
+    meal_plan.trash_meal(&meal_type, &day)
+        .ok_or_else(|| CliError::not_found(format!("No {} meal found for {}.", meal_type, day)))
+}
+
+/// Which meals `remove --all` removes; options left out match every meal
+struct MealSelection {
+    meal_type: Option<String>,
+    day: Option<String>,
+    cook: Option<String>,
+    filter: Option<String>,
+    week: Option<String>,
+}
+
+/// Moves every meal matching the selection to the trash after one confirmation, unless
+/// `yes` is set. Returns how many meals were removed.
+fn remove_matching(meal_plan: &mut MealPlan, selection: &MealSelection, yes: bool, prompter: &mut dyn Prompter) -> Result<usize, CliError> {
+    let meal_type = selection.meal_type.as_deref().map(parse_meal_type).transpose()?;
+    // Weeks ahead are planned in the stored plan, with meals stored by date, while past
+    // weeks have been archived and aren't changed
+    let week = match &selection.week {
+        Some(spec) => Some(resolve_week(spec, meal_plan.week_start_date)?),
+        None => None,
+    };
+    if let Some(week) = week.filter(|week| *week < meal_plan.week_start_date) {
+        return Err(CliError::conflict(format!(
+            "The week of {} has been archived, and archived weeks aren't changed.",
+            week.format("%Y-%m-%d")
+        )));
+    }
+    // A weekday given with a week is that day of the week
+    let date = match &selection.day {
+        Some(day) => {
+            let day = parse_day(day, meal_plan)?;
+            let date = meal_plan.date_of(&day);
+            Some(match (day, week) {
+                (Day::Weekday(_), Some(week)) => date + (week - meal_plan.week_start_date),
+                _ => date,
+            })
+        }
+        None => None,
+    };
+    let in_week = |date: NaiveDate| week.is_none_or(|week| date >= week && date < week + Duration::days(7));
+    let filter = parse_filter(selection.filter.as_deref())?;
+
+    let slots: Vec<(MealType, Day)> = meal_plan
+        .meals()
+        .iter()
+        .filter(|meal| meal_type.as_ref().is_none_or(|t| &meal.meal_type == t))
+        .filter(|meal| date.is_none_or(|d| meal_plan.date_of(&meal.day) == d))
+        .filter(|meal| in_week(meal_plan.date_of(&meal.day)))
+        .filter(|meal| selection.cook.as_ref().is_none_or(|c| meal.cook.eq_ignore_ascii_case(c.trim())))
+        .filter(|meal| filter.as_ref().is_none_or(|f| f.matches(meal, meal_plan.date_of(&meal.day))))
+        .map(|meal| (meal.meal_type.clone(), meal.day.clone()))
+        .collect();
+    if slots.is_empty() {
+        println!("No meals match.");
+        return Ok(0);
+    }
+
+    if !yes {
+        println!("Remove these {} meal(s)?", slots.len());
+        for (meal_type, day) in &slots {
+            if let Some(meal) = meal_plan.find_meal(meal_type, day) {
+                println!("  {} {}: {}", day, meal_type, meal.description);
+            }
+        }
+        print!("(y/n) ");
+        if !prompter.confirm() {
+            return Err(CliError::cancelled("Meal removal cancelled by user."));
+        }
+    }
+
+    for (meal_type, day) in &slots {
+        meal_plan.trash_meal(meal_type, day);
+    }
+    println!("Moved {} meal(s) to the trash. See them with `mealplan trash list`.", slots.len());
+    Ok(slots.len())
+}
+
+/// Lists the week's trash, oldest removal first
+/// Lists the snapshots, newest first, marking those of other weeks, which can't be restored
+fn format_snapshots(snapshots: &snapshots::Snapshots, week_start_date: NaiveDate) -> String {
+    if snapshots.snapshots.is_empty() {
+        return "No snapshots. Take one with 'mealplan snapshot create <name>'.\n".to_string();
+    }
+    let mut listed: Vec<&snapshots::Snapshot> = snapshots.snapshots.iter().collect();
+    listed.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.taken_at));
+    let mut output = String::new();
+    for snapshot in listed {
+        output.push_str(&format!(
+            "{}  {} ({} meal{})",
+            snapshot.taken_at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+            snapshot.name,
+            snapshot.meals.len(),
+            if snapshot.meals.len() == 1 { "" } else { "s" }
+        ));
+        if snapshot.week_start_date != week_start_date {
+            output.push_str(&format!(", week of {}", snapshot.week_start_date.format("%Y-%m-%d")));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn format_trash(meal_plan: &MealPlan, retention_days: u32) -> String {
+    if meal_plan.trash.is_empty() {
+        return "The trash is empty.\n".to_string();
+    }
+
+    let mut output = format!("Trash for the week of {}:\n", meal_plan.week_start_date.format("%Y-%m-%d"));
+    for entry in &meal_plan.trash {
+        output.push_str(&format!(
+            "  {}. {} on {}: {} (cook: {}), removed {}\n",
+            entry.id,
+            entry.meal.meal_type,
+            entry.meal.day,
+            entry.meal.description,
+            entry.meal.cook,
+            entry.removed_at.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+        ));
+    }
+    output.push_str(&format!("Removed meals are deleted for good after {} days.\n", retention_days));
+    output
+}
+
+#[allow(clippy::too_many_arguments)]
+fn edit_meal(meal_plan: &mut MealPlan, meal_type_str: String, day_str: String, new_cook: Option<String>, new_description: Option<String>, completions: &Completions, updated_by: &str, prompter: &mut dyn Prompter) -> Result<(), CliError> {
+    // Validate meal type
+    let meal_type = parse_meal_type(&meal_type_str)?;
+
+    // Validate day
+    let day = parse_day(&day_str, meal_plan)?;
+
+    // Find the meal to edit
+    let meal = meal_plan.find_meal(&meal_type, &day)
+        .ok_or_else(|| CliError::not_found(format!("No {} meal found for {}.", meal_type, day)))?;
+
+    // Display current meal details
+    println!("Current meal details:");
+    println!("  Type: {}", meal.meal_type);
+    println!("  Day: {}", meal.day);
+    println!("  Cook: {}", meal.cook);
+    println!("  Description: {}", meal.description);
+    if let Some(updated_by) = &meal.updated_by {
+        println!("  Last updated by: {}", updated_by);
+    }
+    println!();
+
+    // Get updated values from user
+    let new_cook = if let Some(cook) = new_cook {
+        cook
+    } else {
+        println!("Enter new cook (leave empty to keep current value, Tab to complete):");
+        let input = prompter.answer_from(&completions.cooks).map_err(prompt_error)?;
+        if input.is_empty() {
+            meal.cook.clone()
+        } else {
+            input
+        }
+    };
+
+    let new_description = if let Some(desc) = new_description {
+        desc
+    } else {
+        println!("Enter new description (leave empty to keep current value, Tab to complete):");
+        let input = prompter.answer_from(&completions.descriptions).map_err(prompt_error)?;
+        if input.is_empty() {
+            meal.description.clone()
+        } else {
+            input
+        }
+    };
+
+    update_meal(meal_plan, meal_type, day, new_cook, new_description, updated_by);
+    Ok(())
+}
+
+/// Changes a planned meal's cook and description as one edit. Everything else is kept,
+/// except that a different dish drops the old one's tags, recipe, photo and rating.
+fn update_meal(meal_plan: &mut MealPlan, meal_type: MealType, day: Day, new_cook: String, new_description: String, updated_by: &str) {
+    meal_plan.change_meal(&meal_type, &day, |meal| {
+        if meal.description != new_description {
+            meal.tags.clear();
+            meal.recipe = None;
+            meal.photo = None;
+            meal.rating = None;
+            meal.cooked = false;
+        }
+        meal.cook = new_cook;
+        meal.description = new_description;
+        meal.updated_by = Some(updated_by.to_string());
+    });
+}
+
+/// Applies bulk operations to a copy of the plan, all or nothing. The first operation that
+/// fails stops the batch, and its error says which line it came from.
+fn apply_ops(config: &Config, meal_plan: &MealPlan, book: &RecipeBook, ops: Vec<(usize, BulkOp)>, updated_by: &str) -> Result<(MealPlan, BulkSummary), CliError> {
+    let mut plan = meal_plan.clone();
+    plan.user = Some(updated_by.to_string());
+    let mut summary = BulkSummary::default();
+    for (line, op) in ops {
+        let in_line = |e: CliError| CliError::new(e.kind, format!("Line {}: {}", line, e.message));
+        apply_op(config, &mut plan, book, op, updated_by, line, &mut summary).map_err(in_line)?;
+    }
+    plan.user = meal_plan.user.clone();
+    Ok((plan, summary))
+}
+
+fn apply_op(config: &Config, meal_plan: &mut MealPlan, book: &RecipeBook, op: BulkOp, updated_by: &str, line: usize, summary: &mut BulkSummary) -> Result<(), CliError> {
+    let mut check_cook = |meal_plan: &MealPlan, cook: &str, day: &Day| -> Result<(), CliError> {
+        validate_cook(config, cook)?;
+        if let Some(conflict) = availability_conflict(config, meal_plan, cook, day) {
+            summary.warnings.push((line, conflict));
+        }
+        Ok(())
+    };
+
+    match op {
+        BulkOp::Add { meal_type, day, cook, description, replace } => {
+            let meal_type = parse_meal_type(&meal_type)?;
+            let day = parse_day(&day, meal_plan)?;
+            if let Some(existing) = meal_plan.find_meal(&meal_type, &day) {
+                if !replace {
+                    return Err(CliError::conflict(format!(
+                        "{} on {} already has a meal: {}. Set \"replace\": true to replace it.",
+                        meal_type, day, existing.description
+                    )));
+                }
+            }
+            let cook = cook_or_default(config, &cook, &meal_type)?;
+            let cook = if cook.eq_ignore_ascii_case("auto") {
+                next_cook(config, meal_plan, &meal_type, &day, &dish(&description, &[], book))?
+            } else {
+                cook
+            };
+            check_cook(meal_plan, &cook, &day)?;
+
+            meal_plan.remove_meal(&meal_type, &day);
+            let mut meal = Meal::new(meal_type, day, cook, description.clone());
+            if let Some(alias) = config.find_alias(&description) {
+                alias.apply_to(&mut meal);
+            }
+            meal.updated_by = Some(updated_by.to_string());
+            meal_plan.add_meal(meal);
+            summary.added += 1;
+        }
+        BulkOp::Edit { meal_type, day, cook, description } => {
+            let meal_type = parse_meal_type(&meal_type)?;
+            let day = parse_day(&day, meal_plan)?;
+            let meal = meal_plan.find_meal(&meal_type, &day)
+                .ok_or_else(|| CliError::not_found(format!("No {} meal found for {}.", meal_type, day)))?;
+            if cook.is_none() && description.is_none() {
+                return Err(CliError::usage("An edit needs a cook, a description or both."));
+            }
+            let description = description.unwrap_or_else(|| meal.description.clone());
+            let cook = match cook {
+                Some(cook) if cook.eq_ignore_ascii_case("auto") => {
+                    next_cook(config, meal_plan, &meal_type, &day, &dish(&description, &[], book))?
+                }
+                Some(cook) => cook,
+                None => meal.cook.clone(),
+            };
+            check_cook(meal_plan, &cook, &day)?;
+            update_meal(meal_plan, meal_type, day, cook, description, updated_by);
+            summary.edited += 1;
+        }
+        BulkOp::Remove { meal_type, day } => {
+            let meal_type = parse_meal_type(&meal_type)?;
+            let day = parse_day(&day, meal_plan)?;
+            meal_plan.trash_meal(&meal_type, &day)
+                .ok_or_else(|| CliError::not_found(format!("No {} meal found for {}.", meal_type, day)))?;
+            summary.removed += 1;
+        }
+    }
+    Ok(())
+}
+
+fn format_bulk_summary(summary: &BulkSummary, dry_run: bool) -> String {
+    let mut output = String::new();
+    for (line, warning) in &summary.warnings {
+        output.push_str(&format!("Warning: line {}: {}\n", line, warning));
+    }
+    output.push_str(&format!(
+        "{} {} operation{}: {} added, {} edited, {} removed.\n",
+        if dry_run { "Would apply" } else { "Applied" },
+        summary.total(),
+        if summary.total() == 1 { "" } else { "s" },
+        summary.added,
+        summary.edited,
+        summary.removed
+    ));
+    output
+}
+
+/// Builds the completions offered by the edit prompts from the dishes and cooks planned before
+fn edit_completions(config: &Config, meal_plan: &MealPlan, storage_path: &Path) -> Result<Completions, CliError> {
+    let index = Archive::new(storage_path).index()
+        .map_err(|e| CliError::io("Failed to load archived weeks", e))?;
+    let descriptions = history::known_dishes(&index, meal_plan).into_iter().map(|dish| dish.description).collect();
+
+    let mut cooks: Vec<String> = Vec::new();
+    let planned = meal_plan.meals().iter().map(|meal| meal.cook.trim().to_string());
+    for cook in config.cooks.iter().map(|c| c.name.clone()).chain(planned) {
+        if !cook.is_empty() && !cooks.iter().any(|c| c.eq_ignore_ascii_case(&cook)) {
+            cooks.push(cook);
+        }
+    }
+    Ok(Completions { descriptions, cooks })
+}
+
+fn prompt_error(error: io::Error) -> CliError {
+    if error.kind() == io::ErrorKind::Interrupted {
+        CliError::cancelled("Edit cancelled by user.")
+    } else {
+        CliError::io("Failed to read input", error)
+    }
+}
+
+/// Adds a meal, taking its description, tags and recipe from the alias when one is given
+#[allow(clippy::too_many_arguments)]
+fn add_meal_as(meal_plan: &mut MealPlan, meal_type: String, day: String, cook: String, description: String, alias: Option<&MealAlias>, updated_by: &str, prompter: &mut dyn Prompter) -> Result<(), CliError> {
+    // Validate meal type
+    let meal_type = parse_meal_type(&meal_type)?;
+
+    // Validate day
+    let day = parse_day(&day, meal_plan)?;
+
+    // Check for duplicate meals
+    if meal_plan.find_meal(&meal_type, &day).is_some() {
+        println!("A meal of this type already exists for this day. Do you want to replace it? (y/n)");
+        if !prompter.confirm() {
+            return Err(CliError::cancelled("Meal not added due to user cancellation."));
+        }
+        meal_plan.remove_meal(&meal_type, &day);
+    }
+
+    // Add the new meal
+    let mut new_meal = Meal::new(meal_type, day, cook, description);
+    if let Some(alias) = alias {
+        alias.apply_to(&mut new_meal);
+    }
+    new_meal.updated_by = Some(updated_by.to_string());
+    meal_plan.add_meal(new_meal);
+
+    Ok(())
+}
+
+/// The date to pack for: today, tomorrow, or a day of the plan's week
+fn packing_date(meal_plan: &MealPlan, day: &str, today: NaiveDate) -> Result<NaiveDate, CliError> {
+    match day.trim().to_lowercase().as_str() {
+        "today" => Ok(today),
+        "tomorrow" => Ok(today + Duration::days(1)),
+        _ => Ok(meal_plan.date_of(&parse_day(day, meal_plan)?)),
+    }
+}
+
+/// Finds the recipe of the meal planned for a slot, which needs steps to be cooked along with
+fn recipe_to_cook<'a>(meal_plan: &MealPlan, book: &'a RecipeBook, meal_type: &MealType, date: NaiveDate) -> Result<&'a Recipe, CliError> {
+    let meal = meal_plan
+        .meals()
+        .iter()
+        .find(|meal| meal.meal_type == *meal_type && meal_plan.date_of(&meal.day) == date)
+        .ok_or_else(|| {
+            CliError::not_found(format!("No {} is planned for {}.", meal_type.to_string().to_lowercase(), date.format("%A, %b %-d")))
+        })?;
+    let description = meal_plan.render_description(meal);
+    let recipe = book.for_meal(meal).ok_or_else(|| {
+        CliError::not_found(format!("{} isn't in the recipe book. Add it with 'mealplan recipe add \"{}\" --step ...'.", description, description))
+    })?;
+    if recipe.steps.is_empty() {
+        return Err(CliError::not_found(format!(
+            "The recipe {} has no steps. Add them with 'mealplan recipe add \"{}\" --step ...'.",
+            recipe.name, recipe.name
+        )));
+    }
+    Ok(recipe)
+}
+
+/// What importing spreadsheet rows did
+#[derive(Debug, Default)]
+struct ImportSummary {
+    into_current: usize,
+    into_archive: usize,
+    /// Archived weeks with imported meals, ready to be saved
+    archived_weeks: BTreeMap<NaiveDate, MealPlan>,
+    /// Rows that were skipped, with why
+    errors: Vec<(usize, String)>,
+    /// Rows for meals already planned, which were left out
+    duplicates: Vec<(usize, String)>,
+    /// Planned meals the imported ones replaced or were added to
+    replaced: usize,
+    combined: usize,
+}
+
+/// Adds spreadsheet rows to the current plan or, for earlier dates, to the archived week
+/// they fall in. Rows that don't validate are reported and skipped, and `decide` says what
+/// to do with a row for a slot that already has a different meal.
+fn import_meals<F>(meal_plan: &mut MealPlan, archive: &Archive, rows: Vec<import::MappedRow>, updated_by: &str, mut decide: F) -> Result<ImportSummary, CliError>
+where
+    F: FnMut(&Meal, &Meal, duplicates::Collision) -> duplicates::Strategy,
+{
+    let archived = archive.weeks().map_err(|e| CliError::io("Failed to load archived weeks", e))?;
+    let mut summary = ImportSummary::default();
+    let mut imported_weeks = BTreeSet::new();
+
+    for row in rows {
+        let (week, meal) = match import_row(meal_plan, &row) {
+            Ok(parsed) => parsed,
+            Err(problem) => {
+                summary.errors.push((row.row, problem));
+                continue;
+            }
+        };
+
+        let plan = if week == meal_plan.week_start_date {
+            &mut *meal_plan
+        } else {
+            match summary.archived_weeks.entry(week) {
+                std::collections::btree_map::Entry::Occupied(entry) => entry.into_mut(),
+                std::collections::btree_map::Entry::Vacant(entry) => {
+                    let plan = if archived.contains(&week) {
+                        archive.load_week(week).map_err(|e| CliError::io("Failed to load archived week", e))?
+                    } else {
+                        let mut plan = MealPlan::new(week);
+                        plan.replica_id = meal_plan.replica_id.clone();
+                        plan
+                    };
+                    entry.insert(plan)
+                }
+            }
+        };
+
+        let date = plan.date_of(&meal.day);
+        let existing = duplicates::find_at(plan, &meal.meal_type, date).map(|existing| existing.description.clone()).unwrap_or_default();
+        let meal_type = meal.meal_type.to_string();
+        let mut meal = meal;
+        meal.updated_by = Some(updated_by.to_string());
+        match duplicates::resolve(plan, meal, &mut decide) {
+            duplicates::Outcome::Added => {}
+            duplicates::Outcome::Replaced => summary.replaced += 1,
+            duplicates::Outcome::Combined => summary.combined += 1,
+            duplicates::Outcome::Unchanged | duplicates::Outcome::Skipped(duplicates::Collision::Same | duplicates::Collision::Duplicate) => {
+                summary.duplicates.push((row.row, format!("already planned on {} as {}", date, existing)));
+                continue;
+            }
+            duplicates::Outcome::Skipped(duplicates::Collision::Conflict) => {
+                summary.errors.push((row.row, format!("{} on {} already has a meal: {}", meal_type, date, existing)));
+                continue;
+            }
+        }
+        if week == meal_plan.week_start_date {
+            summary.into_current += 1;
+        } else {
+            summary.into_archive += 1;
+            imported_weeks.insert(week);
+        }
+    }
+
+    // A week whose every row was skipped has nothing to save
+    summary.archived_weeks.retain(|week, _| imported_weeks.contains(week));
+    Ok(summary)
+}
+
+/// Adds recipes from another app to the recipe book, leaving any it already has alone.
+/// Returns how many were added and how many were already there.
+fn import_recipes(book: &mut RecipeBook, recipes: Vec<Recipe>) -> (usize, usize) {
+    let (mut added, mut kept) = (0, 0);
+    for recipe in recipes {
+        if book.find(&recipe.name).is_some() {
+            kept += 1;
+        } else {
+            book.add(recipe);
+            added += 1;
+        }
+    }
+    (added, kept)
+}
+
+/// Validates one row, returning the week it belongs to and the meal
+fn import_row(meal_plan: &MealPlan, row: &import::MappedRow) -> Result<(NaiveDate, Meal), String> {
+    let meal_type = parse_meal_type(&row.meal_type)
+        .map_err(|_| format!("invalid meal type '{}'", row.meal_type))?;
+    if row.description.is_empty() {
+        return Err("missing description".to_string());
+    }
+
+    let day = match &row.day {
+        import::Cell::Date(date) => Day::Date(*date),
+        import::Cell::Empty => return Err("missing day".to_string()),
+        day => parse_day(&day.text(), meal_plan).map_err(|_| format!("invalid day '{}'", day.text()))?,
+    };
+    let date = meal_plan.date_of(&day);
+    let weeks_back = (date - meal_plan.week_start_date).num_days().div_euclid(7);
+    if weeks_back > 0 {
+        return Err(format!("{} is after the current week", date));
+    }
+    let week = meal_plan.week_start_date + Duration::weeks(weeks_back);
+
+    // Stored by weekday, like meals added by name, so the week has one slot per day
+    let day = Day::Weekday(date.weekday());
+    Ok((week, Meal::new(meal_type, day, row.cook.clone(), row.description.clone())))
+}
+
+/// Returns the name recorded as the author of changes: the configured `user_name`,
+/// falling back to `$USER`
+fn current_user(config: &Config) -> String {
+    config.user_name.clone()
+        .or_else(|| std::env::var("USER").ok())
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Returns the name identifying this device in merges: the configured `replica_id`,
+/// falling back to the host name
+fn current_replica(config: &Config) -> String {
+    config.replica_id.clone()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| models::DEFAULT_REPLICA_ID.to_string())
+}
+
+/// Reads a pack from a web address, a file, or else the packs that come with mealplan
+fn load_pack(source: &str) -> Result<packs::Pack, CliError> {
+    let source = source.trim();
+    let json = if source.starts_with("http://") || source.starts_with("https://") {
+        packs::download(source).map_err(|e| CliError::io("Failed to download the pack", e))?
+    } else if Path::new(source).is_file() {
+        std::fs::read_to_string(source).map_err(|e| CliError::io(&format!("Failed to read {}", source), e))?
+    } else {
+        return packs::Pack::built_in(source).ok_or_else(|| {
+            let names: Vec<&str> = packs::BUILT_IN.iter().map(|(name, _)| *name).collect();
+            CliError::not_found(format!("No pack file or built-in pack named {}. The built-in packs are {}.", source, names.join(", ")))
+        });
+    };
+    packs::Pack::parse(&json).map_err(CliError::usage)
+}
+
+/// The built-in packs and the installed ones, as `mealplan pack list` shows them
+fn format_packs(installed: &packs::InstalledPacks) -> String {
+    let mut out = String::from("Built-in packs:\n");
+    for (name, json) in packs::BUILT_IN {
+        let pack = packs::Pack::parse(json).expect("built-in packs are valid");
+        let mark = if installed.find(&pack.name).is_some() { " (installed)" } else { "" };
+        out.push_str(&format!("  {}{}: {}\n", name, mark, pack.description));
+    }
+    if installed.packs.is_empty() {
+        return out;
+    }
+    out.push_str("Installed:\n");
+    for pack in &installed.packs {
+        out.push_str(&format!(
+            "  {}: {} recipes, {} aliases, {} template meals\n",
+            pack.name,
+            pack.recipes.len(),
+            pack.aliases.len(),
+            pack.week.len()
+        ));
+    }
+    out
+}
+
+/// The lines saying what a pack install or removal did
+fn format_pack_changes(changes: &packs::Changes) -> String {
+    let mut out = String::new();
+    for (heading, items) in [
+        ("Added", &changes.added),
+        ("Replaced", &changes.replaced),
+        ("Removed", &changes.removed),
+        ("Kept your own", &changes.kept),
+    ] {
+        if !items.is_empty() {
+            out.push_str(&format!("{}: {}\n", heading, items.join(", ")));
+        }
+    }
+    out
+}
+
+fn parse_meal_type(meal_type_str: &str) -> Result<MealType, CliError> {
+    MealType::parse(meal_type_str)
+        .ok_or_else(|| CliError::usage("Invalid meal type. Must be breakfast, lunch, dinner, or snack."))
+}
+
+/// Parses a day of the plan's week; numbered days count from the day the week starts
+fn parse_day(day_str: &str, meal_plan: &MealPlan) -> Result<Day, CliError> {
+    days::parse_day(day_str, meal_plan.week_start_date.weekday()).map_err(CliError::usage)
+}
+
+/// Parses a weekday name for availability, accepting plurals ("fridays"),
+/// abbreviations ("fri") and the other languages day names are understood in
+fn parse_weekday(day_str: &str) -> Result<Weekday, CliError> {
+    days::parse_weekday(day_str)
+        .ok_or_else(|| CliError::usage(format!("Invalid weekday '{}'. Use a day name such as friday or fridays.", day_str)))
+}
+
+fn set_cook_availability(config: &mut Config, name: &str, days: &[String], available: bool) -> Result<(), CliError> {
+    let weekdays = days.iter().map(|d| parse_weekday(d)).collect::<Result<Vec<_>, _>>()?;
+    let error = unknown_cook_error(config, name);
+    let cook = config.find_cook_mut(name).ok_or(error)?;
+
+    for weekday in weekdays {
+        if available {
+            cook.unavailable_days.retain(|d| *d != weekday);
+        } else if !cook.unavailable_days.contains(&weekday) {
+            cook.unavailable_days.push(weekday);
+        }
+    }
+    cook.unavailable_days.sort_by_key(|d| d.num_days_from_monday());
+    Ok(())
+}
+
+/// Adds a cook to the roster, returning false if an existing cook was updated instead.
+/// Only the details given are changed on an existing cook.
+fn alias_add(config: &mut Config, name: &str, description: String, tags: Vec<String>, recipe: Option<String>) -> Result<(), CliError> {
+    let name = name.trim().to_lowercase();
+    if name.is_empty() || description.trim().is_empty() {
+        return Err(CliError::usage("An alias needs a name and a description."));
+    }
+    config.aliases.insert(name, MealAlias { description, tags: clean_list(tags), recipe });
+    Ok(())
+}
+
+/// Trims the values of a list option and drops empty ones
+fn clean_list(values: Vec<String>) -> Vec<String> {
+    values.into_iter().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect()
+}
+
+fn format_recipe(recipe: &Recipe) -> String {
+    let mut output = recipe.name.clone();
+    if !recipe.tags.is_empty() {
+        output.push_str(&format!(" [{}]", recipe.tags.join(", ")));
+    }
+    if let Some(servings) = recipe.servings {
+        output.push_str(&format!(" (serves {})", servings));
+    }
+    output.push('\n');
+    if !recipe.ingredients.is_empty() {
+        output.push_str(&format!("  Ingredients: {}\n", recipe.ingredients.join(", ")));
+    }
+    if let Some(link) = &recipe.link {
+        output.push_str(&format!("  Link: {}\n", link));
+    }
+    if let Some(photo) = &recipe.photo {
+        output.push_str(&format!("  Photo: {}\n", photo));
+    }
+    if !recipe.equipment.is_empty() {
+        let equipment: Vec<String> = recipe.equipment.iter().map(|need| need.to_string()).collect();
+        output.push_str(&format!("  Equipment: {}\n", equipment.join(", ")));
+    }
+    for (n, step) in recipe.steps.iter().enumerate() {
+        output.push_str(&format!("  {}. {}\n", n + 1, step));
+    }
+    output
+}
+
+fn format_scaled_recipe(recipe: &Recipe, factor: f64) -> String {
+    let mut output = format!("{} x{}\n", recipe.name, units::format_amount(factor));
+    if recipe.ingredients.is_empty() {
+        output.push_str("  No ingredients listed.\n");
+    }
+    for ingredient in recipe.scaled_ingredients(factor) {
+        output.push_str(&format!("  {}\n", ingredient));
+    }
+    output
+}
+
+/// Builds a rotation, checking its recipes are in the recipe book and using their names
+/// as the book spells them
+fn create_rotation(config: &Config, book: &RecipeBook, name: String, recipes: &[String], meal_type: &str, day: &str, cook: Option<String>) -> Result<Rotation, CliError> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err(CliError::usage("The rotation name can't be empty."));
+    }
+    let recipes = recipes
+        .iter()
+        .map(|recipe| {
+            book.recipes
+                .iter()
+                .find(|r| r.name.eq_ignore_ascii_case(recipe.trim()))
+                .map(|r| r.name.clone())
+                .ok_or_else(|| CliError::not_found(format!("No recipe named {}. Add it with 'mealplan recipe add \"{}\"'.", recipe.trim(), recipe.trim())))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let cook = cook.map(|c| c.trim().to_string());
+    if let Some(cook) = cook.as_deref().filter(|c| !c.eq_ignore_ascii_case("auto")) {
+        validate_cook(config, cook)?;
+    }
+    Ok(Rotation::new(name, recipes, parse_meal_type(meal_type)?, parse_weekday(day)?, cook))
+}
+
+fn format_lunchbox(lunchbox: &Lunchbox) -> String {
+    if lunchbox.components.is_empty() && lunchbox.children.is_empty() {
+        return "No lunchbox items yet. Add one with 'mealplan lunchbox add <name> --part main'.\n".to_string();
+    }
+    let mut output = String::new();
+    for part in lunchbox::Part::ALL {
+        let items: Vec<String> = lunchbox
+            .components
+            .iter()
+            .filter(|c| c.part == part)
+            .map(|c| match c.tags.is_empty() {
+                true => c.name.clone(),
+                false => format!("{} [{}]", c.name, c.tags.join(", ")),
+            })
+            .collect();
+        let items = if items.is_empty() { "none yet".to_string() } else { items.join(", ") };
+        output.push_str(&format!("{}: {}\n", part, items));
+    }
+    for child in &lunchbox.children {
+        output.push_str(&format!("Child {}", child.name));
+        if !child.exclusions.is_empty() {
+            output.push_str(&format!(", no {}", child.exclusions.join(", ")));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn format_rotations(rotations: &Rotations) -> String {
+    if rotations.rotations.is_empty() {
+        return "No rotations yet. Create one with 'mealplan rotation create <name> <recipes>... --day <day>'.\n".to_string();
+    }
+    let mut output = String::new();
+    for rotation in &rotations.rotations {
+        output.push_str(&format!("{}: {} {}", rotation.name, weekday_name(rotation.day), rotation.meal_type));
+        if let Some(cook) = &rotation.cook {
+            output.push_str(&format!(", cooked by {}", cook));
+        }
+        output.push('\n');
+        output.push_str(&format!("  {}\n", rotation.recipes.join(" -> ")));
+        if let Some(next) = rotation.upcoming() {
+            output.push_str(&format!("  Next: {}\n", next));
+        }
+    }
+    output
+}
+
+/// Fills the empty slots of a meal type with recipes and cooks chosen to keep the
+/// configured plan rules, reporting any soft rule that had to be broken. Returns how many
+/// meals were added.
+fn generate_meals(config: &Config, meal_plan: &mut MealPlan, book: &RecipeBook, prices: &PriceHistory, meal_type: &str, days: Option<&str>) -> Result<usize, CliError> {
+    let meal_type = parse_meal_type(meal_type)?;
+    let dates = match days {
+        Some(days) => days::split_days(days)
+            .iter()
+            .map(|day| parse_day(day, meal_plan).map(|day| meal_plan.date_of(&day)))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => (0..7).map(|n| meal_plan.week_start_date + Duration::days(n)).collect(),
+    };
+    let templated = apply_day_templates(config, meal_plan, &dates, Some(&meal_type));
+    let planned: Vec<Placed> = meal_plan
+        .meals()
+        .iter()
+        .filter(|meal| meal.meal_type == meal_type)
+        .map(|meal| Placed { date: meal_plan.date_of(&meal.day), description: meal.description.clone(), cook: meal.cook.clone(), tags: meal.tags.clone() })
+        .collect();
+    let mut slots: Vec<NaiveDate> = dates.into_iter().filter(|date| !planned.iter().any(|meal| meal.date == *date)).collect();
+    slots.sort();
+    slots.dedup();
+    if slots.is_empty() {
+        if templated == 0 {
+            println!("Every day already has a {}.", meal_type);
+        }
+        return Ok(templated);
+    }
+
+    let candidates: Vec<Candidate> = book
+        .recipes
+        .iter()
+        .filter(|recipe| !meal_plan.meals().iter().any(|meal| meal.description.eq_ignore_ascii_case(&recipe.name)))
+        .map(|recipe| Candidate { description: recipe.name.clone(), tags: recipe.tags.clone(), minutes: recipe.minutes() })
+        .collect();
+    if candidates.len() < slots.len() {
+        return Err(CliError::conflict(format!(
+            "{} recipe(s) not yet planned this week for {} day(s). Add recipes with 'mealplan recipe add'.",
+            candidates.len(),
+            slots.len()
+        )));
+    }
+    let cooks: Vec<CookOption> = config
+        .rotation()
+        .into_iter()
+        .map(|name| {
+            let cook = config.find_cook(&name);
+            let unavailable_days = cook.map(|c| c.unavailable_days.clone()).unwrap_or_default();
+            let prefs = cook.map(|c| c.prefs.clone()).unwrap_or_default();
+            CookOption { name, unavailable_days, prefs }
+        })
+        .collect();
+    if cooks.is_empty() {
+        return Err(CliError::not_found("No cooks to assign. Add cooks with 'mealplan cooks add <name>'."));
+    }
+    let mut cook_counts: HashMap<String, usize> = HashMap::new();
+    for meal in meal_plan.meals() {
+        *cook_counts.entry(meal.cook.to_lowercase()).or_default() += 1;
+    }
+
+    let problem = Problem { slots, planned, cook_counts, candidates, cooks, rules: config.plan_rules.clone() };
+    let solution = solver::solve(&problem).map_err(|unsolvable| {
+        let mut message = if unsolvable.exhausted {
+            "No plan keeps every hard rule.".to_string()
+        } else {
+            "No plan keeping every hard rule was found in time.".to_string()
+        };
+        if unsolvable.blocking.is_empty() {
+            message.push_str(" Add recipes or cooks, or make some plan_rules soft.");
+        } else {
+            let rules: Vec<String> = unsolvable.blocking.iter().map(|rule| rule.to_string()).collect();
+            message.push_str(&format!(" Making one of these rules soft would allow a plan: {}.", rules.join("; ")));
+        }
+        CliError::conflict(message)
+    })?;
+
+    for planned in &solution.meals {
+        println!("{} {}: {} ({})", planned.date.format("%a %Y-%m-%d"), meal_type, planned.description, planned.cook);
+        let recipe = book.recipes.iter().find(|r| r.name == planned.description);
+        let mut meal = Meal::new(meal_type.clone(), Day::Weekday(planned.date.weekday()), planned.cook.clone(), planned.description.clone());
+        meal.tags = planned.tags.clone();
+        meal.recipe = Some(recipe.and_then(|r| r.link.clone()).unwrap_or_else(|| planned.description.clone()));
+        meal.photo = recipe.and_then(|r| r.photo.clone());
+        meal.updated_by = Some(current_user(config));
+        meal_plan.add_meal(meal);
+    }
+    let leftovers = slot_leftovers(config, meal_plan, book, &solution.meals);
+    for rule in &solution.broken {
+        println!("Broke the rule: {}", rule);
+    }
+    if !prices.items.is_empty() {
+        let ingredients: Vec<String> = solution
+            .meals
+            .iter()
+            .filter_map(|meal| book.find(&meal.description))
+            .flat_map(|recipe| recipe.ingredients.iter().cloned())
+            .collect();
+        let (estimate, unpriced) = prices.estimate(&ingredients);
+        print!("Estimated grocery cost {:.2} at the latest prices paid", estimate);
+        if unpriced > 0 {
+            print!(", leaving out {} ingredient(s) without a price", unpriced);
+        }
+        println!(".");
+    }
+    Ok(templated + solution.meals.len() + leftovers)
+}
+
+/// Plans the leftovers of newly planned meals for lunch on the next day, or the one after,
+/// that has no lunch yet, when the recipe makes more than the household eats. Returns how
+/// many lunches were planned.
+fn slot_leftovers(config: &Config, meal_plan: &mut MealPlan, book: &RecipeBook, cooked: &[Placed]) -> usize {
+    let Some(household_size) = config.household_size else {
+        return 0;
+    };
+    let week_end = meal_plan.week_start_date + Duration::days(6);
+    let mut planned = 0;
+    for meal in cooked {
+        let leftovers = book.find(&meal.description).map_or(0, |recipe| recipe.leftover_servings(household_size));
+        if leftovers == 0 {
+            continue;
+        }
+        let lunch_free = |date: &NaiveDate| {
+            !meal_plan.meals().iter().any(|m| m.meal_type == MealType::Lunch && meal_plan.date_of(&m.day) == *date)
+        };
+        let Some(date) = (1..=2).map(|n| meal.date + Duration::days(n)).filter(|date| *date <= week_end).find(lunch_free) else {
+            continue;
+        };
+        let description = format!("Leftovers: {}", meal.description);
+        println!("{} {}: {} ({} serving(s) left over)", date.format("%a %Y-%m-%d"), MealType::Lunch, description, leftovers);
+        let mut lunch = Meal::new(MealType::Lunch, Day::Weekday(date.weekday()), meal.cook.clone(), description);
+        lunch.updated_by = Some(current_user(config));
+        meal_plan.add_meal(lunch);
+        planned += 1;
+    }
+    planned
+}
+
+/// Plans the meals in `day_templates` on the given dates, of one meal type when given,
+/// leaving any already planned alone. Templates `mealplan config validate` reports as
+/// broken are skipped. Returns how many meals were planned.
+fn apply_day_templates(config: &Config, meal_plan: &mut MealPlan, dates: &[NaiveDate], only: Option<&MealType>) -> usize {
+    let mut planned = 0;
+    for date in dates {
+        for (meal_type, description) in config.day_template(date.weekday()) {
+            let Ok(meal_type) = parse_meal_type(meal_type.trim()) else {
+                continue;
+            };
+            let taken = meal_plan.meals().iter().any(|m| m.meal_type == meal_type && meal_plan.date_of(&m.day) == *date);
+            if taken || only.is_some_and(|only| *only != meal_type) || description.trim().is_empty() {
+                continue;
+            }
+            let day = Day::Weekday(date.weekday());
+            let alias = config.find_alias(description);
+            let description = alias.map_or(description.trim(), |alias| alias.description.as_str()).to_string();
+            // A template meal is planned even when nobody can take a turn; its cook can be set later
+            let cook = match config.default_cook(&meal_type).as_deref() {
+                Some("auto") => {
+                    let dish = Candidate { description: description.clone(), tags: alias.map(|a| a.tags.clone()).unwrap_or_default(), minutes: None };
+                    next_cook(config, meal_plan, &meal_type, &day, &dish).unwrap_or_default()
+                }
+                Some(cook) => cook.to_string(),
+                None => String::new(),
+            };
+            println!("{} {}: {} (from the {} template)", date.format("%a %Y-%m-%d"), meal_type, description, weekday_name(date.weekday()));
+            let mut meal = Meal::new(meal_type, day, cook, description);
+            if let Some(alias) = alias {
+                alias.apply_to(&mut meal);
+            }
+            meal.updated_by = Some(current_user(config));
+            meal_plan.add_meal(meal);
+            planned += 1;
+        }
+    }
+    planned
+}
+
+/// Plans the week's recipe from the named rotation, or from every rotation, and moves each
+/// one on to its next recipe. A rotation already applied to this week plans the same recipe.
+fn apply_rotations(config: &Config, meal_plan: &mut MealPlan, rotations: &mut Rotations, book: &RecipeBook, name: Option<&str>, cook: Option<&str>, prompter: &mut dyn Prompter) -> Result<(), CliError> {
+    let selected: Vec<&mut Rotation> = match name {
+        Some(name) => vec![rotations.find_mut(name).ok_or_else(|| CliError::not_found(format!("No rotation named {}.", name.trim())))?],
+        None => rotations.rotations.iter_mut().collect(),
+    };
+    if selected.is_empty() {
+        return Err(CliError::not_found("No rotations yet. Create one with 'mealplan rotation create'."));
+    }
+
+    for rotation in selected {
+        let Some(recipe_name) = rotation.recipe_for_week(meal_plan.week_start_date) else {
+            continue;
+        };
+        let day = weekday_name(rotation.day);
+        let meal_type = rotation.meal_type.to_string();
+        if meal_plan.find_meal(&rotation.meal_type, &Day::Weekday(rotation.day)).is_some_and(|m| m.description == recipe_name) {
+            println!("{} is already planned for {} {}.", recipe_name, day, meal_type);
+            continue;
+        }
+
+        let recipe = book.recipes.iter().find(|r| r.name.eq_ignore_ascii_case(&recipe_name));
+        let from_recipe = MealAlias {
+            description: recipe_name.clone(),
+            tags: recipe.map(|r| r.tags.clone()).unwrap_or_default(),
+            recipe: Some(recipe.and_then(|r| r.link.clone()).unwrap_or_else(|| recipe_name.clone())),
+        };
+        let cook = cook.or(rotation.cook.as_deref()).unwrap_or("auto");
+        let cook = resolve_cook(config, meal_plan, cook, &meal_type, &day, &dish(&recipe_name, &[], book))?;
+        validate_cook(config, &cook)?;
+        add_meal_as(meal_plan, meal_type.clone(), day.clone(), cook, recipe_name.clone(), Some(&from_recipe), &current_user(config), prompter)?;
+        println!("Planned {} for {} {} from the rotation {}.", recipe_name, day, meal_type, rotation.name);
+    }
+    Ok(())
+}
+
+fn format_places(places: &Places) -> String {
+    if places.places.is_empty() {
+        return "No places yet. Add one with 'mealplan places add <name> --cuisine <cuisine> --cost <cost>'.\n".to_string();
+    }
+    let mut sorted: Vec<&Place> = places.places.iter().collect();
+    sorted.sort_by_key(|p| p.name.to_lowercase());
+    let mut output = String::new();
+    for place in sorted {
+        output.push_str(&place.name);
+        if let Some(cuisine) = &place.cuisine {
+            output.push_str(&format!(" [{}]", cuisine));
+        }
+        if let Some(cost) = place.typical_cost {
+            output.push_str(&format!(", about {:.2}", cost));
+        }
+        if let Some(contact) = &place.contact {
+            output.push_str(&format!(" ({})", contact));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn format_place_suggestions(suggestions: &[(&Place, Option<NaiveDate>)], weeks: usize) -> String {
+    if suggestions.is_empty() {
+        return format!("Every place has been ordered from in the last {} week(s).\n", weeks);
+    }
+    let mut output = format!("Not ordered from in the last {} week(s):\n", weeks);
+    for (place, last) in suggestions {
+        let last = match last {
+            Some(date) => format!("last {}", date.format("%Y-%m-%d")),
+            None => "never".to_string(),
+        };
+        output.push_str(&format!("  {} ({})\n", place.name, last));
+    }
+    output
+}
+
+/// Builds a waste entry, linking it to the planned week its date falls in and to a recipe
+/// in the recipe book, spelled as the book has it
+fn waste_entry(meal_plan: &MealPlan, book: &RecipeBook, item: &str, recipe: Option<&str>, day: Option<&str>, today: NaiveDate) -> Result<WasteEntry, CliError> {
+    let item = item.trim();
+    if item.is_empty() {
+        return Err(CliError::usage("Say what was thrown away."));
+    }
+    let recipe = match recipe {
+        Some(name) => Some(
+            book.find(name)
+                .map(|r| r.name.clone())
+                .ok_or_else(|| CliError::not_found(format!("No recipe named {}.", name.trim())))?,
+        ),
+        None => None,
+    };
+    let date = match day {
+        Some(day) => meal_plan.date_of(&parse_day(day, meal_plan)?),
+        None => today,
+    };
+    let week_end = meal_plan.week_start_date + Duration::days(6);
+    let week = (meal_plan.week_start_date..=week_end).contains(&date).then_some(meal_plan.week_start_date);
+    Ok(WasteEntry { date, item: item.to_string(), recipe, week })
+}
+
+fn format_waste_log(log: &WasteLog) -> String {
+    if log.entries.is_empty() {
+        return "Nothing logged yet. Log waste with 'mealplan waste log <item>'.\n".to_string();
+    }
+    let mut output = String::new();
+    for entry in &log.entries {
+        output.push_str(&format!("{}  {}", entry.date.format("%Y-%m-%d"), entry.item));
+        if let Some(recipe) = &entry.recipe {
+            output.push_str(&format!(" (from {})", recipe));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// A day's meals, or one of them, as `attendance away` and `back` name them
+fn attendance_slot(date: NaiveDate, meal_type: Option<&MealType>) -> String {
+    match meal_type {
+        Some(meal_type) => format!("{} on {}", meal_type.to_string().to_lowercase(), date.format("%a %b %-d")),
+        None => format!("every meal on {}", date.format("%a %b %-d")),
+    }
+}
+
+/// Lists the household, with allergies, and the week's meals not everyone is eating
+fn format_attendance(attendance: &Attendance, meal_plan: &MealPlan) -> String {
+    if attendance.members.is_empty() {
+        return "No household members. Add them with 'mealplan attendance member <name>'.\n".to_string();
+    }
+    let mut output = String::from("Household:\n");
+    for member in &attendance.members {
+        output.push_str(&format!("  {}", member.name));
+        if !member.allergies.is_empty() {
+            output.push_str(&format!(" (allergic to {})", member.allergies.join(", ")));
+        }
+        output.push('\n');
+    }
+
+    let week = meal_plan.week_start_date..meal_plan.week_start_date + Duration::days(7);
+    let away: Vec<&attendance::Absence> = attendance.away.iter().filter(|absence| week.contains(&absence.date)).collect();
+    if away.is_empty() {
+        output.push_str("Everyone is eating every meal this week.\n");
+        return output;
+    }
+    output.push_str("Away this week:\n");
+    for absence in away {
+        output.push_str(&format!("  {}: {}\n", absence.member, attendance_slot(absence.date, absence.meal_type.as_ref())));
+    }
+
+    let mut meals: Vec<&Meal> = meal_plan.meals().iter().collect();
+    meals.sort_by_key(|meal| (meal_plan.date_of(&meal.day), spreadsheet::GRID_MEAL_TYPES.iter().position(|t| *t == meal.meal_type)));
+    let mut short: Vec<String> = Vec::new();
+    for meal in meals {
+        let eating = attendance.eating(meal_plan.date_of(&meal.day), &meal.meal_type).len();
+        if eating < attendance.members.len() {
+            short.push(format!(
+                "  {} {}: {}, {} of {} eating\n",
+                meal_plan.date_of(&meal.day).format("%a"),
+                meal.meal_type.to_string().to_lowercase(),
+                meal.description,
+                eating,
+                attendance.members.len()
+            ));
+        }
+    }
+    if !short.is_empty() {
+        output.push_str("Planned meals not everyone is eating:\n");
+        output.extend(short);
+    }
+    output
+}
+
+fn format_pantry(pantry: &Pantry) -> String {
+    if pantry.items.is_empty() {
+        return "The pantry is empty. Add what's in the kitchen with 'mealplan pantry add <item>...'.\n".to_string();
+    }
+    pantry.items.iter().map(|item| format!("  - {}\n", item)).collect()
+}
+
+/// Lists recipes with the share of their ingredients on hand, how long they take when the
+/// steps say, and what's missing
+fn format_pantry_matches(matches: &[pantry::PantryMatch]) -> String {
+    if matches.is_empty() {
+        return "No recipe uses anything in the pantry.\n".to_string();
+    }
+    let mut output = String::new();
+    for found in matches {
+        output.push_str(&format!("{:>4.0}%  {}", found.fraction() * 100.0, found.recipe.name));
+        if let Some(minutes) = found.recipe.minutes() {
+            output.push_str(&format!(" ({} min)", minutes));
+        }
+        output.push('\n');
+        if !found.missing.is_empty() {
+            output.push_str(&format!("       missing: {}\n", found.missing.join(", ")));
+        }
+    }
+    output
+}
+
+/// Writes the month's waste, pointing out recipes that made more than the household eats
+fn format_waste_report(report: &WasteReport, book: &RecipeBook, household_size: Option<u32>) -> String {
+    let mut output = format!("Food waste for {}\n", report.period.label());
+    if report.entries.is_empty() {
+        output.push_str("Nothing was logged as wasted.\n");
+        return output;
+    }
+    output.push_str(&format!("{} item(s) thrown away.\n", report.entries.len()));
+    if !report.recipes.is_empty() {
+        output.push_str("\nBy recipe:\n");
+        for (recipe, count) in &report.recipes {
+            output.push_str(&format!("  {}: {}", recipe, count));
+            let leftovers = book.find(recipe).zip(household_size).map_or(0, |(r, size)| r.leftover_servings(size));
+            if leftovers > 0 {
+                output.push_str(&format!(" (makes {} more serving(s) than the household eats; try a smaller batch)", leftovers));
+            }
+            output.push('\n');
+        }
+    }
+    if !report.repeated.is_empty() {
+        output.push_str("\nThrown away more than once:\n");
+        for (item, count) in &report.repeated {
+            output.push_str(&format!("  {}: {} times\n", item, count));
+        }
+    }
+    output
+}
+
+fn format_aliases(config: &Config) -> String {
+    if config.aliases.is_empty() {
+        return "No aliases yet. Add one with 'mealplan alias add <name> <description>'.\n".to_string();
+    }
+    let mut output = String::new();
+    for (name, alias) in &config.aliases {
+        output.push_str(&format!("{}: {}", name, alias.description));
+        if !alias.tags.is_empty() {
+            output.push_str(&format!(" [{}]", alias.tags.join(", ")));
+        }
+        if let Some(recipe) = &alias.recipe {
+            output.push_str(&format!(" (recipe: {})", recipe));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn cooks_add(config: &mut Config, name: &str, email: Option<String>, preferences: Vec<String>) -> bool {
+    let added = config.find_cook(name).is_none();
+    if added {
+        config.cooks.push(CookProfile::new(name.to_string()));
+    }
+
+    if let Some(cook) = config.find_cook_mut(name) {
+        if email.is_some() {
+            cook.email = email;
+        }
+        if !preferences.is_empty() {
+            cook.preferences = preferences;
+        }
+    }
+    added
+}
+
+fn cooks_remove(config: &mut Config, name: &str) -> Result<(), CliError> {
+    let index = config.cooks.iter()
+        .position(|c| c.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| CliError::not_found(format!("{} is not one of the cooks.", name)))?;
+    config.cooks.remove(index);
+    config.cook_rotation.retain(|n| !n.eq_ignore_ascii_case(name));
+    Ok(())
+}
+
+fn unknown_cook_error(config: &Config, name: &str) -> CliError {
+    let names: Vec<&str> = config.cooks.iter().map(|c| c.name.as_str()).collect();
+    CliError::not_found(if names.is_empty() {
+        format!("{} is not one of the cooks. Add them with 'mealplan cooks add \"{}\"'.", name, name)
+    } else {
+        format!(
+            "{} is not one of the cooks ({}). Add them with 'mealplan cooks add \"{}\"'.",
+            name, names.join(", "), name
+        )
+    })
+}
+
+/// Rejects cooks missing from the roster, once the roster has anyone on it
+/// The cook given for a meal, or else the configured default for its meal type. Without
+/// either, the meal has no cook, which is only allowed while there is no roster.
+fn cook_or_default(config: &Config, cook: &str, meal_type: &MealType) -> Result<String, CliError> {
+    if !cook.trim().is_empty() {
+        return Ok(cook.to_string());
+    }
+    match config.default_cook(meal_type) {
+        Some(cook) => Ok(cook),
+        None if config.cooks.is_empty() => Ok(String::new()),
+        None => Err(CliError::usage(format!(
+            "No cook given, and there's no default cook for {}. Pass --cook, or set one in default_cooks in the configuration.",
+            meal_type.to_string().to_lowercase()
+        ))),
+    }
+}
+
+fn validate_cook(config: &Config, name: &str) -> Result<(), CliError> {
+    if config.is_known_cook(name) {
+        Ok(())
+    } else {
+        Err(unknown_cook_error(config, name))
+    }
+}
+
+/// Lists the roster with each cook's details and how many meals they cook this week
+fn format_cooks(config: &Config, meal_plan: &MealPlan) -> String {
+    if config.cooks.is_empty() {
+        return "No cooks added yet. Add one with 'mealplan cooks add <name>'.\n".to_string();
+    }
+
+    let mut output = String::new();
+    for cook in &config.cooks {
+        let meals = meal_plan.meals().iter()
+            .filter(|m| m.cook.eq_ignore_ascii_case(&cook.name))
+            .count();
+
+        output.push_str(&cook.name);
+        if let Some(email) = &cook.email {
+            output.push_str(&format!(" <{}>", email));
+        }
+        output.push_str(&format!(": {} meal{} this week", meals, if meals == 1 { "" } else { "s" }));
+        if !cook.unavailable_days.is_empty() {
+            let days: Vec<String> = cook.unavailable_days.iter().map(|d| d.to_string()).collect();
+            output.push_str(&format!(", unavailable {}", days.join(", ")));
+        }
+        output.push('\n');
+        if !cook.preferences.is_empty() {
+            output.push_str(&format!("  Preferences: {}\n", cook.preferences.join(", ")));
+        }
+        for line in cook_prefs_lines(&cook.prefs) {
+            output.push_str(&format!("  {}\n", line));
+        }
+    }
+    output
+}
+
+/// Describes a cook's preferences, one line each
+fn cook_prefs_lines(prefs: &models::CookPrefs) -> Vec<String> {
+    let mut lines = Vec::new();
+    if !prefs.avoid.is_empty() {
+        lines.push(format!("Won't cook: {}", prefs.avoid.join(", ")));
+    }
+    if let Some(minutes) = prefs.max_minutes {
+        lines.push(format!("Recipes up to {} minutes", minutes));
+    }
+    if !prefs.days.is_empty() {
+        let days: Vec<String> = prefs.days.iter().map(|d| d.to_string()).collect();
+        lines.push(format!("Only cooks on {}", days.join(", ")));
+    }
+    lines
+}
+
+fn format_cook_prefs(cook: &CookProfile) -> String {
+    let lines = cook_prefs_lines(&cook.prefs);
+    if lines.is_empty() {
+        return format!("{} has no preferences.\n", cook.name);
+    }
+    let mut output = format!("{}:\n", cook.name);
+    for line in lines {
+        output.push_str(&format!("  {}\n", line));
+    }
+    output
+}
+
+/// Keys the preferences file may have
+const COOK_PREFS_KEYS: [&str; 3] = ["avoid", "max_minutes", "days"];
+
+/// Writes a cook's preferences as YAML to edit, every key present and explained
+fn cook_prefs_yaml(cook: &CookProfile) -> String {
+    let list = |values: Vec<String>| serde_json::to_string(&values).unwrap_or_else(|_| "[]".to_string());
+    format!(
+        "# Preferences for {}, respected by --cook auto and mealplan generate\n\
+         # avoid: tags or words of dishes they won't cook, e.g. [fish, pork]\n\
+         # max_minutes: longest recipe they take on, going by the times in its steps\n\
+         # days: the only weekdays they cook on, e.g. [Sat, Sun]; every day when empty\n\
+         avoid: {}\n\
+         max_minutes: {}\n\
+         days: {}\n",
+        cook.name,
+        list(cook.prefs.avoid.clone()),
+        cook.prefs.max_minutes.map_or("null".to_string(), |minutes| minutes.to_string()),
+        list(cook.prefs.days.iter().map(|d| d.to_string()).collect()),
+    )
+}
+
+fn parse_cook_prefs(yaml: &str) -> Result<models::CookPrefs, String> {
+    let value: serde_yaml::Value = serde_yaml::from_str(yaml).map_err(|e| e.to_string())?;
+    let value = match value {
+        serde_yaml::Value::Null => return Ok(models::CookPrefs::default()),
+        serde_yaml::Value::Mapping(mapping) => mapping,
+        _ => return Err("expected keys such as avoid, max_minutes and days".to_string()),
+    };
+    if let Some(key) = value.keys().find(|key| !key.as_str().is_some_and(|key| COOK_PREFS_KEYS.contains(&key))) {
+        return Err(format!("unknown key {}; use {}", serde_yaml::to_string(key).unwrap_or_default().trim(), COOK_PREFS_KEYS.join(", ")));
+    }
+    let mut prefs: models::CookPrefs = serde_yaml::from_value(serde_yaml::Value::Mapping(value)).map_err(|e| e.to_string())?;
+    prefs.avoid.retain(|avoid| !avoid.trim().is_empty());
+    prefs.days.sort_by_key(|d| d.num_days_from_monday());
+    prefs.days.dedup();
+    Ok(prefs)
+}
+
+/// Describes why a cook can't make a meal on the given day, if they can't
+fn availability_conflict(config: &Config, meal_plan: &MealPlan, cook: &str, day: &Day) -> Option<String> {
+    let date = meal_plan.date_of(day);
+    if config.is_cook_available(cook, date.weekday()) {
+        None
+    } else {
+        Some(format!("{} is unavailable on {}s ({}).", cook, date.format("%A"), date.format("%Y-%m-%d")))
+    }
+}
+
+/// Warns when the cook is unavailable on the day and asks whether to assign them anyway
+fn confirm_cook_available(config: &Config, meal_plan: &MealPlan, cook: &str, day_str: &str, prompter: &mut dyn Prompter) -> Result<(), CliError> {
+    let day = parse_day(day_str, meal_plan)?;
+    if let Some(conflict) = availability_conflict(config, meal_plan, cook, &day) {
+        println!("Warning: {} Assign anyway? (y/n)", conflict);
+        if !prompter.confirm() {
+            return Err(CliError::cancelled(format!("Meal not assigned because {} is unavailable.", cook)));
+        }
+    }
+    Ok(())
+}
+
+/// Everything `mealplan check` finds: cooks planned on days they're unavailable and meals
+/// with something someone eating them is allergic to, as errors, and whatever the rules find
+fn check_findings(config: &Config, meal_plan: &MealPlan, book: &RecipeBook, attendance: &Attendance, rules: &[lint::LintRule]) -> Vec<lint::Finding> {
+    let mut findings: Vec<lint::Finding> = meal_plan.meals().iter()
+        .filter_map(|meal| {
+            let conflict = availability_conflict(config, meal_plan, &meal.cook, &meal.day)?;
+            Some(lint::Finding {
+                rule: "cook-availability".to_string(),
+                severity: lint::Severity::Error,
+                date: Some(meal_plan.date_of(&meal.day)),
+                message: format!("{}: {}", meal.meal_type, conflict),
+            })
+        })
+        .collect();
+    findings.extend(attendance::allergy_findings(meal_plan, book, attendance));
+    findings.extend(equipment::equipment_findings(meal_plan, book, &config.appliances));
+    findings.extend(lint::check_plan(meal_plan, book, rules));
+    findings.sort_by_key(|finding| (finding.severity, finding.date));
+    findings
+}
+
+fn format_findings(findings: &[lint::Finding], format: &str) -> Result<String, CliError> {
+    let count = |severity| findings.iter().filter(|f| f.severity == severity).count();
+    match format.to_lowercase().as_str() {
+        "text" if findings.is_empty() => Ok("No problems found.\n".to_string()),
+        "text" => {
+            let mut output: String = findings.iter().map(|finding| format!("{}\n", finding)).collect();
+            output.push_str(&format!(
+                "{} error(s), {} warning(s), {} note(s).\n",
+                count(lint::Severity::Error), count(lint::Severity::Warning), count(lint::Severity::Info)
+            ));
+            Ok(output)
+        }
+        "json" => {
+            let report = serde_json::json!({
+                "findings": findings,
+                "errors": count(lint::Severity::Error),
+                "warnings": count(lint::Severity::Warning),
+                "notes": count(lint::Severity::Info),
+            });
+            serde_json::to_string_pretty(&report)
+                .map(|json| json + "\n")
+                .map_err(|e| CliError::new(error::ErrorKind::General, format!("Failed to write the findings: {}", e)))
+        }
+        _ => Err(CliError::usage("Invalid format. Must be text or json.")),
+    }
+}
+
+/// Works out which budgets the plan's week goes over: its grocery list, as estimated from
+/// earlier prices and as paid, against `weekly_budget`, and eating out in the months the
+/// week falls in, archived weeks included, against `monthly_takeout_budget`
+fn budget_alerts(config: &Config, storage_path: &Path, meal_plan: &MealPlan) -> Result<Vec<budget::BudgetAlert>, CliError> {
+    if config.weekly_budget.is_none() && config.monthly_takeout_budget.is_none() {
+        return Ok(Vec::new());
+    }
+    let places = Places::load_from_json(storage_path.join("places.json"))
+        .map_err(|e| CliError::io("Failed to load places", e))?;
+    let mut alerts = Vec::new();
+    if config.weekly_budget.is_some() {
+        let grocery_list = load_grocery_list(&storage_path.join("grocery_list.json"), meal_plan.week_start_date);
+        let prices = PriceHistory::load_from_json(storage_path.join("prices.json"))
+            .map_err(|e| CliError::io("Failed to load price history", e))?;
+        let review = WeeklyReview::new(meal_plan, &grocery_list, &places, &prices, config.weekly_budget);
+        alerts.extend(budget::grocery_alerts(meal_plan.week_start_date, review.estimated, review.spent, config.weekly_budget));
+    }
+    if config.monthly_takeout_budget.is_some() {
+        let first = budget::month_start(meal_plan.week_start_date);
+        let last = budget::month_start(meal_plan.week_start_date + Duration::days(6));
+        for month in if first == last { vec![first] } else { vec![first, last] } {
+            let month_end = month.checked_add_months(chrono::Months::new(1)).unwrap_or(month);
+            let archived = Archive::new(storage_path).load_weeks(month - Duration::days(6)..month_end)
+                .map_err(|e| CliError::io("Failed to load archived weeks", e))?;
+            let mut plans = vec![meal_plan];
+            plans.extend(archived.iter());
+            let spend = budget::takeout_spending(&plans, &places, month);
+            alerts.extend(budget::takeout_alert(month, spend, config.monthly_takeout_budget));
+        }
+    }
+    Ok(alerts)
+}
+
+fn budget_finding(alert: &budget::BudgetAlert) -> lint::Finding {
+    lint::Finding {
+        rule: "budget".to_string(),
+        severity: lint::Severity::Warning,
+        date: None,
+        message: alert.to_string(),
+    }
+}
+
+/// Sends the budget alerts not sent before, returning how many were sent. Alerts that
+/// couldn't be sent are tried again next time, and none are sent without a target.
+fn send_budget_alerts(targets: &[notify::NotifyTarget], storage_path: &Path, alerts: &[budget::BudgetAlert]) -> Result<usize, CliError> {
+    if targets.is_empty() {
+        return Ok(0);
+    }
+    let path = storage_path.join(budget::SENT_ALERTS_FILE);
+    let mut sent_alerts = budget::SentAlerts::load_from_json(&path)
+        .map_err(|e| CliError::io("Failed to load sent budget alerts", e))?;
+    let new = sent_alerts.take_new(alerts);
+    if new.is_empty() {
+        return Ok(0);
+    }
+
+    let notifiers = notify::notifiers(targets);
+    let mut sent = 0;
+    let mut failures = Vec::new();
+    for alert in new {
+        match notify::send_all(&notifiers, BUDGET_ALERT_TITLE, &alert.to_string()) {
+            Ok(()) => sent += 1,
+            Err(e) => {
+                failures.push(e.to_string());
+                sent_alerts.forget(alert);
+            }
+        }
+    }
+    sent_alerts.save_to_json(&path)
+        .map_err(|e| CliError::io("Failed to save sent budget alerts", e))?;
+    if failures.is_empty() {
+        Ok(sent)
+    } else {
+        Err(CliError::new(error::ErrorKind::General, format!("Failed to send {} budget alert(s): {}", failures.len(), failures.join("; "))))
+    }
+}
+
+/// Describes a dish for picking its cook: its tags, with those of its recipe, and how long
+/// the recipe takes
+fn dish(description: &str, tags: &[String], book: &RecipeBook) -> Candidate {
+    let recipe = book.find(description);
+    let mut tags = tags.to_vec();
+    tags.extend(recipe.iter().flat_map(|recipe| recipe.tags.iter().cloned()));
+    Candidate { description: description.to_string(), tags, minutes: recipe.and_then(Recipe::minutes) }
+}
+
+/// Returns the cook to assign, picking one from the rotation when `cook` is "auto"
+fn resolve_cook(config: &Config, meal_plan: &MealPlan, cook: &str, meal_type: &str, day: &str, dish: &Candidate) -> Result<String, CliError> {
+    if !cook.eq_ignore_ascii_case("auto") {
+        return Ok(cook.to_string());
+    }
+
+    let cook = next_cook(config, meal_plan, &parse_meal_type(meal_type)?, &parse_day(day, meal_plan)?, dish)?;
+    println!("Assigned {} as the cook.", cook);
+    Ok(cook)
+}
+
+/// Picks the available cook with the fewest meals this week, among those whose preferences
+/// allow the dish. Ties go to whoever comes next in the rotation after the cook of the most
+/// recently added meal.
+fn next_cook(config: &Config, meal_plan: &MealPlan, meal_type: &MealType, day: &Day, dish: &Candidate) -> Result<String, CliError> {
+    let rotation = config.rotation();
+    if rotation.is_empty() {
+        return Err(CliError::not_found("No cook rotation configured. Add cooks with 'mealplan cooks add <name>' or set one with 'mealplan cooks rotation <names>...'."));
+    }
+
+    // The meal being replaced, if any, doesn't count towards its cook's share
+    let meals: Vec<&Meal> = meal_plan.meals().iter()
+        .filter(|m| !(&m.meal_type == meal_type && &m.day == day))
+        .collect();
+    let count = |name: &str| meals.iter().filter(|m| m.cook.eq_ignore_ascii_case(name)).count();
+
+    let start = meals.iter().rev()
+        .find_map(|m| rotation.iter().position(|name| name.eq_ignore_ascii_case(&m.cook)))
+        .map_or(0, |last| last + 1);
+
+    let weekday = meal_plan.date_of(day).weekday();
+    let available: Vec<&String> = (0..rotation.len())
+        .map(|offset| &rotation[(start + offset) % rotation.len()])
+        .filter(|name| config.is_cook_available(name, weekday))
+        .collect();
+    if available.is_empty() {
+        return Err(CliError::conflict(format!("No cook in the rotation is available on {}s.", weekday_name(weekday))));
+    }
+    available
+        .into_iter()
+        .filter(|name| {
+            config.find_cook(name).is_none_or(|cook| {
+                cook.prefs.avoided_in(&dish.description, &dish.tags).is_none() && !cook.prefs.too_long(dish.minutes)
+            })
+        })
+        .min_by_key(|name| count(name))
+        .cloned()
+        .ok_or_else(|| CliError::conflict(format!(
+            "No cook available on {}s takes on {}; see 'mealplan cooks prefs show <name>'.",
+            weekday_name(weekday),
+            dish.description.trim()
+        )))
+}
+
+/// Reads a token typed at a prompt, or everything piped in, such as a key file
+fn read_secret(integration: secrets::Integration) -> Result<String, CliError> {
+    let mut secret = String::new();
+    if io::stdin().is_terminal() {
+        eprint!("{} token: ", integration);
+        io::stdin().read_line(&mut secret)
+    } else {
+        io::stdin().read_to_string(&mut secret)
+    }
+    .map_err(|e| CliError::io("Failed to read the token", e))?;
+    let secret = secret.trim().to_string();
+    if secret.is_empty() {
+        return Err(CliError::usage("The token is empty."));
+    }
+    if integration == secrets::Integration::Google {
+        serde_json::from_str::<sheets::ServiceAccountKey>(&secret)
+            .map_err(|e| CliError::usage(format!("That isn't a service account key file: {}", e)))?;
+    }
+    Ok(secret)
+}
+
+/// Takes the integration's token out of the configuration where it is the one just stored,
+/// returning whether anything changed. Other tokens are left, as they still need to be there.
+fn remove_plaintext_token(config: &mut Config, integration: secrets::Integration, secret: &str) -> bool {
+    let mut changed = false;
+    match integration {
+        secrets::Integration::Matrix => {
+            if let Some(matrix) = config.matrix.as_mut().filter(|matrix| matrix.access_token == secret) {
+                matrix.access_token.clear();
+                changed = true;
+            }
+        }
+        secrets::Integration::Ntfy | secrets::Integration::Gotify => {
+            for target in &mut config.notify {
+                match target {
+                    notify::NotifyTarget::Ntfy { token, .. } if integration == secrets::Integration::Ntfy && token.as_deref() == Some(secret) => {
+                        *token = None;
+                        changed = true;
+                    }
+                    notify::NotifyTarget::Gotify { token, .. } if integration == secrets::Integration::Gotify && token == secret => {
+                        token.clear();
+                        changed = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        secrets::Integration::Email => {
+            if let Some(email) = config.email.as_mut().filter(|email| email.password == secret) {
+                email.password.clear();
+                changed = true;
+            }
+        }
+        secrets::Integration::Google | secrets::Integration::Peer => {}
+    }
+    changed
+}
+
+/// Saves the configuration, unless the file it would replace couldn't be read (`load_error`):
+/// the configuration in hand is then only the defaults, and saving would wipe out the user's settings
+fn save_config(config: &Config, config_path: &Path, load_error: Option<&str>) -> Result<(), CliError> {
+    if let Some(e) = load_error {
+        return Err(CliError::new(
+            error::ErrorKind::Data,
+            format!(
+                "Not saving the configuration: {:?} couldn't be read ({}). Fix the file or run 'mealplan config init', then try again.",
+                config_path, e
+            ),
+        ));
+    }
+    if let Some(parent) = config_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| CliError::io("Failed to create config directory", e))?;
+    }
+    config.save(config_path)
+        .map_err(|e| CliError::io("Failed to save configuration", e))
+}
+
+/// Identifies a meal slot's calendar event, so re-exporting a slot updates its event
+fn meal_uid(meal_type: &MealType, date: NaiveDate) -> String {
+    format!("meal-{}-{}@mealplan", meal_type.to_string().to_lowercase(), date.format("%Y%m%d"))
+}
+
+/// Identifies the calendar event for a shopping trip
+fn shopping_uid(date: NaiveDate) -> String {
+    format!("shop-{}@mealplan", date.format("%Y%m%d"))
+}
+
+/// Identifies the to-do for prepping a meal slot
+fn prep_uid(meal_type: &MealType, date: NaiveDate) -> String {
+    format!("prep-{}-{}@mealplan", meal_type.to_string().to_lowercase(), date.format("%Y%m%d"))
+}
+
+/// What's needed to put the rest of the food workflow, shopping and prep, in a calendar
+struct CalendarWorkflow<'a> {
+    book: &'a RecipeBook,
+    grocery_list: &'a GroceryList,
+    attendance: &'a Attendance,
+    shopping_days: &'a [Weekday],
+}
+
+/// Loads the recipes, grocery list and attendance a workflow calendar is built from, when
+/// one was asked for
+fn workflow_sources(storage_path: &Path, meal_plan: &MealPlan, workflow: bool) -> Result<(Option<RecipeBook>, GroceryList, Attendance), CliError> {
+    if !workflow {
+        return Ok((None, GroceryList::new(meal_plan.week_start_date), Attendance::default()));
+    }
+    let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+        .map_err(|e| CliError::io("Failed to load recipes", e))?;
+    let grocery_list = load_grocery_list(&storage_path.join("grocery_list.json"), meal_plan.week_start_date);
+    let attendance = Attendance::load_from_json(storage_path.join(attendance::ATTENDANCE_FILE))
+        .map_err(|e| CliError::io("Failed to load attendance", e))?;
+    Ok((Some(book), grocery_list, attendance))
+}
+
+/// Writes the plan's meals as calendar events. With `include_removals`, removed meals are
+/// written as cancelled events so calendars that imported them drop them.
+fn export_ical(meal_plan: &MealPlan, cooks: &[CookProfile], workflow: Option<&CalendarWorkflow>, output_path: &PathBuf, include_removals: bool) -> Result<(), CliError> {
+    let mut calendar = meal_calendar(meal_plan, cooks, include_removals);
+    if let Some(workflow) = workflow {
+        add_workflow(&mut calendar, meal_plan, workflow);
+    }
+    std::fs::write(output_path, calendar.to_string())
+        .map_err(|e| CliError::io("Failed to write iCal file", e))
+}
+
+/// The plans' meals as a calendar per week or per month, keyed by the file name to write
+/// each to: the week's first day or the month, e.g. 2024-03-04 or 2024-03. A week that runs
+/// into the next month has its meals in both months' calendars. Weeks and months without
+/// meals get no calendar.
+fn split_calendars(plans: &[MealPlan], cooks: &[CookProfile], split: CalendarSplit) -> BTreeMap<String, Calendar> {
+    let mut calendars: BTreeMap<String, Calendar> = BTreeMap::new();
+    for plan in plans {
+        let name_of = |meal: &Meal| match split {
+            CalendarSplit::Weekly => plan.week_start_date.format("%Y-%m-%d").to_string(),
+            CalendarSplit::Monthly => plan.date_of(&meal.day).format("%Y-%m").to_string(),
+        };
+        let names: BTreeSet<String> = plan.meals().iter().map(name_of).collect();
+        for name in names {
+            let mut part = plan.clone();
+            part.retain_meals(|meal| name_of(meal) == name);
+            calendars.entry(name).or_default().append(&mut meal_calendar(&part, cooks, false));
+        }
+    }
+    calendars
+}
+
+/// What merging the plan into an existing calendar changed
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CalendarMergeSummary {
+    updated: usize,
+    added: usize,
+    removed: usize,
+    /// Meals edited only in the calendar, to take into the plan
+    pulled: Vec<PulledMeal>,
+    /// Meals edited in both, left as the calendar has them
+    conflicts: Vec<CalendarConflict>,
+}
+
+/// A meal as edited in the calendar
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PulledMeal {
+    meal_type: MealType,
+    date: NaiveDate,
+    description: String,
+    cook: Option<String>,
+}
+
+/// A meal changed both in the plan and in the calendar since the last merge
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CalendarConflict {
+    meal_type: MealType,
+    date: NaiveDate,
+    /// The event's summary as the plan has it and as the calendar has it
+    plan: String,
+    calendar: String,
+}
+
+/// Properties the merge records on each meal event, so the next merge can tell which side
+/// changed since: the SEQUENCE and LAST-MODIFIED it wrote
+const SYNCED_SEQUENCE: &str = "X-MEALPLAN-SEQUENCE";
+const SYNCED_MODIFIED: &str = "X-MEALPLAN-MODIFIED";
+
+/// Merges the plan's events into an existing calendar file and writes the result
+fn export_ical_merged(meal_plan: &MealPlan, cooks: &[CookProfile], workflow: Option<&CalendarWorkflow>, existing_path: &Path, output_path: &Path, prefer: Option<CalendarPreference>) -> Result<CalendarMergeSummary, CliError> {
+    let contents = match std::fs::read_to_string(existing_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Calendar::new().to_string(),
+        Err(e) => return Err(CliError::io("Failed to read calendar", e)),
+    };
+    let existing = CalendarFile::parse(&contents)
+        .map_err(|e| CliError::new(error::ErrorKind::Data, format!("Failed to parse {:?}: {}", existing_path, e)))?;
+
+    let mut plan_events = meal_calendar(meal_plan, cooks, false);
+    if let Some(workflow) = workflow {
+        add_workflow(&mut plan_events, meal_plan, workflow);
+    }
+    let (calendar, summary) = merge_calendar(existing, plan_events, meal_plan, workflow.is_some(), prefer);
+    std::fs::write(output_path, calendar.to_text())
+        .map_err(|e| CliError::io("Failed to write iCal file", e))?;
+    Ok(summary)
+}
+
+/// Returns the date in a mealplan event UID, or None for events from elsewhere. Also
+/// recognizes older UIDs that had the export time appended.
+fn meal_uid_date(uid: &str) -> Option<NaiveDate> {
+    let rest = uid.strip_prefix("meal-")?.strip_suffix("@mealplan")?;
+    let date = rest.split('-').nth(1)?;
+    NaiveDate::parse_from_str(date, "%Y%m%d").ok()
+}
+
+/// Returns the date in the UID of a shopping trip or prep to-do, or None for anything else
+fn workflow_uid_date(uid: &str) -> Option<NaiveDate> {
+    let rest = uid.strip_suffix("@mealplan")?;
+    let date = match rest.strip_prefix("shop-") {
+        Some(date) => date,
+        None => rest.strip_prefix("prep-")?.split('-').nth(1)?,
+    };
+    NaiveDate::parse_from_str(date, "%Y%m%d").ok()
+}
+
+/// Returns the meal type and date of a current mealplan meal event UID
+fn meal_uid_parts(uid: &str) -> Option<(MealType, NaiveDate)> {
+    let rest = uid.strip_prefix("meal-")?.strip_suffix("@mealplan")?;
+    let (meal_type, date) = rest.split_once('-')?;
+    Some((parse_meal_type(meal_type).ok()?, NaiveDate::parse_from_str(date, "%Y%m%d").ok()?))
+}
+
+fn event_uid(component: &icalendar::CalendarComponent) -> Option<&str> {
+    component.as_event().and_then(|event| event.get_uid())
+        .or_else(|| component.as_todo().and_then(|todo| todo.get_uid()))
+}
+
+/// Which side changed a meal event since the last merge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EventChange {
+    Plan,
+    Calendar,
+    Both,
+}
+
+fn ical_time(value: &str) -> Option<DateTime<Utc>> {
+    chrono::NaiveDateTime::parse_from_str(value.trim(), "%Y%m%dT%H%M%SZ").ok().map(|time| time.and_utc())
+}
+
+/// Compares a meal event in the calendar with the plan's. The calendar changed it when its
+/// SEQUENCE or LAST-MODIFIED went past what the last merge wrote; the plan changed it when
+/// the meal was edited after that and reads differently. Events never merged before, or
+/// that read the same on both sides, go by the plan.
+fn event_change(theirs: &Event, ours: &Event) -> EventChange {
+    let synced_modified = theirs.property_value(SYNCED_MODIFIED).and_then(ical_time);
+    let synced_sequence = theirs.property_value(SYNCED_SEQUENCE).and_then(|s| s.trim().parse::<u32>().ok());
+    let (Some(synced_modified), Some(synced_sequence)) = (synced_modified, synced_sequence) else {
+        return EventChange::Plan;
+    };
+    let same = theirs.get_summary() == ours.get_summary() && theirs.get_description() == ours.get_description();
+    if same {
+        return EventChange::Plan;
+    }
+
+    let calendar = theirs.get_sequence().unwrap_or(0) > synced_sequence
+        || theirs.property_value("LAST-MODIFIED").and_then(ical_time).is_some_and(|time| time > synced_modified);
+    let plan = ours.property_value("LAST-MODIFIED").and_then(ical_time).is_some_and(|time| time > synced_modified);
+    match (plan, calendar) {
+        (true, true) => EventChange::Both,
+        (false, true) => EventChange::Calendar,
+        _ => EventChange::Plan,
+    }
+}
+
+/// Reads a meal back from an event edited in the calendar: the summary after the meal
+/// type, and the cook from the description
+fn pulled_meal(event: &Event, meal_type: MealType, date: NaiveDate) -> PulledMeal {
+    let summary = event.get_summary().unwrap_or_default().trim();
+    let prefix = format!("{}:", meal_type);
+    let description = match summary.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(&prefix) => summary[prefix.len()..].trim(),
+        _ => summary,
+    };
+    let cook = event.get_description().unwrap_or_default().lines()
+        .find_map(|line| line.trim().strip_prefix("Cook:"))
+        .map(|cook| cook.trim().to_string())
+        .filter(|cook| !cook.is_empty());
+    PulledMeal { meal_type, date, description: description.to_string(), cook }
+}
+
+/// Records on an event the SEQUENCE and LAST-MODIFIED it's written with
+fn mark_synced(event: &mut Event, sequence: u32) {
+    event.sequence(sequence);
+    event.add_property(SYNCED_SEQUENCE, &sequence.to_string());
+    if let Some(modified) = event.property_value("LAST-MODIFIED").map(str::to_string) {
+        event.add_property(SYNCED_MODIFIED, &modified);
+    }
+}
+
+/// Puts the plan's events into an existing calendar. Events with the same UID as one of
+/// the plan's are replaced in place, mealplan events for this week's meals that are no
+/// longer planned are dropped, and all other events are kept as they were. Shopping trips
+/// and prep to-dos are only dropped when `workflow` says the plan's events include them.
+///
+/// A meal event edited in the calendar since the last merge is kept and listed in
+/// `pulled`. One edited on both sides is settled by `prefer`, or kept as the calendar has
+/// it and listed in `conflicts`.
+fn merge_calendar(existing: CalendarFile, plan_events: Calendar, meal_plan: &MealPlan, workflow: bool, prefer: Option<CalendarPreference>) -> (CalendarFile, CalendarMergeSummary) {
+    let week_end = meal_plan.week_start_date + Duration::days(7);
+    let in_week = |date: NaiveDate| date >= meal_plan.week_start_date && date < week_end;
+
+    let mut new_events = plan_events.components;
+    let mut summary = CalendarMergeSummary::default();
+    let mut merged = CalendarFile {
+        head: existing.head,
+        components: Vec::new(),
+        tail: existing.tail,
+    };
+
+    for text in existing.components {
+        // Anything mealplan didn't write is kept exactly as it was
+        let Some(uid) = component_uid(&text).filter(|uid| uid.ends_with(calendar_file::MEALPLAN_UID_SUFFIX)) else {
+            merged.components.push(text);
+            continue;
+        };
+        let replacement = new_events.iter().position(|c| event_uid(c) == Some(uid.as_str()));
+        let planned_date = meal_uid_date(&uid).or_else(|| workflow_uid_date(&uid).filter(|_| workflow));
+        match (replacement, planned_date) {
+            (Some(index), _) => {
+                let mut ours = new_events.remove(index);
+                let component = calendar_file::parse_component(&text);
+                let theirs = component.as_ref().and_then(|component| component.as_event());
+                let (Some((meal_type, date)), Some(theirs), icalendar::CalendarComponent::Event(event)) = (meal_uid_parts(&uid), theirs, &mut ours) else {
+                    merged.components.push(component_text(ours));
+                    summary.updated += 1;
+                    continue;
+                };
+                let sequence = theirs.get_sequence().unwrap_or(0);
+                let change = match (event_change(theirs, event), prefer) {
+                    (EventChange::Both, Some(CalendarPreference::Plan)) => EventChange::Plan,
+                    (EventChange::Both, Some(CalendarPreference::Calendar)) => EventChange::Calendar,
+                    (change, _) => change,
+                };
+                match change {
+                    EventChange::Plan => {
+                        let changed = theirs.get_summary() != event.get_summary() || theirs.get_description() != event.get_description();
+                        mark_synced(event, if changed { sequence + 1 } else { sequence });
+                        merged.components.push(component_text(ours));
+                        summary.updated += 1;
+                    }
+                    EventChange::Calendar => {
+                        summary.pulled.push(pulled_meal(theirs, meal_type, date));
+                        let mut theirs = theirs.clone();
+                        if let Some(modified) = theirs.property_value("LAST-MODIFIED").map(str::to_string) {
+                            theirs.add_property(SYNCED_MODIFIED, &modified);
+                        }
+                        theirs.add_property(SYNCED_SEQUENCE, &sequence.to_string());
+                        merged.components.push(component_text(theirs.into()));
+                    }
+                    EventChange::Both => {
+                        summary.conflicts.push(CalendarConflict {
+                            meal_type,
+                            date,
+                            plan: event.get_summary().unwrap_or_default().to_string(),
+                            calendar: theirs.get_summary().unwrap_or_default().to_string(),
+                        });
+                        merged.components.push(text);
+                    }
+                }
+            }
+            (None, Some(date)) if in_week(date) => summary.removed += 1,
+            _ => merged.components.push(text),
+        }
+    }
+
+    summary.added = new_events.len();
+    for mut component in new_events {
+        let is_meal = event_uid(&component).and_then(meal_uid_parts).is_some();
+        if let (true, icalendar::CalendarComponent::Event(event)) = (is_meal, &mut component) {
+            mark_synced(event, 0);
+        }
+        merged.components.push(component_text(component));
+    }
+    (merged, summary)
+}
+
+/// When a meal starts, approximately, going by its type
+fn meal_start(meal_type: &MealType, date: NaiveDate) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&date.and_time(meal_type.usual_time()))
+}
+
+/// Adds the shopping and prep around the plan's meals: an all-day event on each shopping
+/// day listing what to buy until the next one, and a to-do due when each meal with a
+/// recipe starts, listing its ingredients
+fn add_workflow(calendar: &mut Calendar, meal_plan: &MealPlan, workflow: &CalendarWorkflow) {
+    let shopping_dates = (0..7)
+        .map(|n| meal_plan.week_start_date + Duration::days(n))
+        .filter(|date| workflow.shopping_days.contains(&date.weekday()));
+    for (trip, date) in shopping_dates.enumerate() {
+        // What's left on the list is bought on the week's first trip
+        let mut items: Vec<String> = if trip == 0 {
+            workflow.grocery_list.remaining().iter().map(|item| item.name.clone()).collect()
+        } else {
+            Vec::new()
+        };
+        if let Some((from, to)) = grocery::shopping_window(date, workflow.shopping_days) {
+            items.extend(grocery::ingredients_for_meals(workflow.grocery_list, meal_plan, workflow.book, workflow.attendance, from, to));
+        }
+        let description = if items.is_empty() {
+            "Nothing to buy yet.".to_string()
+        } else {
+            format!("Buy:\n{}", items.iter().map(|item| format!("- {}", item)).collect::<Vec<_>>().join("\n"))
+        };
+
+        let mut event = Event::new();
+        event
+            .summary("Grocery shopping")
+            .description(&description)
+            .all_day(date);
+        event.append_property(Property::new("UID", &shopping_uid(date)));
+        calendar.push(event);
+    }
+
+    for meal in meal_plan.meals().iter().filter(|meal| meal.place.is_none()) {
+        let Some(recipe) = workflow.book.for_meal(meal) else { continue };
+        let date = meal_plan.date_of(&meal.day);
+        let mut description = format!("For {} on {}", meal.meal_type, date.format("%A"));
+        if !recipe.ingredients.is_empty() {
+            description.push_str("\nIngredients:");
+            for ingredient in &recipe.ingredients {
+                description.push_str(&format!("\n- {}", ingredient));
+            }
+        }
+
+        let mut todo = icalendar::Todo::new();
+        todo
+            .summary(&format!("Prep: {}", recipe.name))
+            .description(&description)
+            .due(meal_start(&meal.meal_type, date));
+        todo.append_property(Property::new("UID", &prep_uid(&meal.meal_type, date)));
+        calendar.push(todo);
+    }
+}
+
+/// Builds a calendar with an event for each of the plan's meals
+fn meal_calendar(meal_plan: &MealPlan, cooks: &[CookProfile], include_removals: bool) -> Calendar {
+    // Create a new calendar
+    let mut calendar = Calendar::new();
+    
+    // Add events for each meal
+    for meal in meal_plan.meals() {
+        // Create a new event
+        let summary = format!("{}: {}", meal.meal_type, meal_plan.render_description(meal));
+        let mut description = format!("{}: {}", "Cook", meal.cook);
+        if !meal.tags.is_empty() {
+            description.push_str(&format!("\nTags: {}", meal.tags.join(", ")));
+        }
+        if let Some(recipe) = &meal.recipe {
+            description.push_str(&format!("\nRecipe: {}", recipe));
+        }
+        
+        // Set date/time
+        let date = meal_plan.date_of(&meal.day);
+        
+        // Create start and end times (1 hour duration)
+        let start_time = meal_start(&meal.meal_type, date);
+        
+        let end_time = start_time + Duration::hours(1);
+
+        let mut event = Event::new();
+
+        event
+            .description(&description)
+            .ends(end_time)
+            .starts(start_time)
+            .summary(&summary);
+
+        
+        // Add a unique identifier
+        event.append_property(Property::new("UID", &meal_uid(&meal.meal_type, date)));
+        if let Some(updated_at) = meal.updated_at {
+            event.append_property(Property::new("LAST-MODIFIED", &updated_at.format("%Y%m%dT%H%M%SZ").to_string()));
+        }
+
+        // Invite the cook when we know their email address
+        if let Some(email) = cooks.iter()
+            .find(|c| c.name.eq_ignore_ascii_case(&meal.cook))
+            .and_then(|c| c.email.as_ref())
+        {
+            let mut attendee = Property::new("ATTENDEE", &format!("mailto:{}", email));
+            attendee.add_parameter("CN", &meal.cook);
+            event.append_multi_property(attendee);
+        }
+        
+        // Add the event to the calendar
+        calendar.push(event);
+    }
+
+    if include_removals {
+        for tombstone in &meal_plan.tombstones {
+            let date = meal_plan.date_of(&tombstone.day);
+            let mut event = Event::new();
+            event
+                .summary(&format!("{}: removed", tombstone.meal_type))
+                .status(icalendar::EventStatus::Cancelled)
+                .all_day(date);
+            event.append_property(Property::new("UID", &meal_uid(&tombstone.meal_type, date)));
+            calendar.push(event);
+        }
+    }
+
+    calendar
+}
+
+/// Renders the plan as short text lines, small enough to fit in a QR code
+fn compact_plan_text(meal_plan: &MealPlan) -> String {
+    let mut meals: Vec<&Meal> = meal_plan.meals().iter().collect();
+    meals.sort_by_key(|m| {
+        let day_key = match &m.day {
+            Day::Weekday(w) => format!("1{}", w.num_days_from_monday()),
+            Day::Date(date) => format!("0{}", date),
+        };
+        let type_key = match m.meal_type {
+            MealType::Breakfast => 0,
+            MealType::Lunch => 1,
+            MealType::Snack => 2,
+            MealType::Dinner => 3,
+        };
+        (day_key, type_key)
+    });
+
+    let mut text = format!("Meal plan week of {}", meal_plan.week_start_date.format("%Y-%m-%d"));
+    for meal in meals {
+        text.push_str(&format!("\n{} {}: {} ({})", meal.day, meal.meal_type, meal_plan.render_description(meal), meal.cook));
+    }
+    text
+}
+
+fn export_qr(contents: &str, output_path: Option<&PathBuf>) -> Result<(), CliError> {
+    let code = QrCode::new(contents.as_bytes())
+        .map_err(|e| format!("Failed to encode QR code: {}. Try --url for large plans.", e))?;
+
+    match output_path {
+        Some(path) => {
+            let image = code.render::<Luma<u8>>().min_dimensions(300, 300).build();
+            image.save(path)
+                .map_err(|e| format!("Failed to write QR code image: {}", e))?;
+        }
+        None => {
+            let rendered = code
+                .render::<unicode::Dense1x2>()
+                .dark_color(unicode::Dense1x2::Light)
+                .light_color(unicode::Dense1x2::Dark)
+                .build();
+            println!("{}", rendered);
+        }
+    }
+
+    Ok(())
+}
+
+fn config_validate(config_path: &Path) -> Result<(), CliError> {
+    let contents = std::fs::read_to_string(config_path)
+        .map_err(|e| CliError::io(&format!("Failed to read the configuration {:?}", config_path), e))?;
+    let issues = settings::validate_config(&contents, &settings::ValidationContext::current());
+
+    println!("Checked {:?}", config_path);
+    if issues.is_empty() {
+        println!("No problems found.");
+        return Ok(());
+    }
+    for issue in &issues {
+        println!("  {}", issue);
+    }
+    let errors = issues.iter().filter(|i| i.severity == settings::Severity::Error).count();
+    let warnings = issues.len() - errors;
+    println!("{} error(s), {} warning(s).", errors, warnings);
+    if errors > 0 {
+        return Err(CliError::new(error::ErrorKind::Data, "The configuration has errors."));
+    }
+    Ok(())
+}
+
+fn config_init(config_path: &Path, prompter: &mut dyn Prompter) -> Result<(), CliError> {
+    let config_dir = settings::storage_dir_for(config_path);
+    
+    // Create the directory if it doesn't exist
+    if !config_dir.exists() {
+        std::fs::create_dir_all(&config_dir)
+            .map_err(|e| CliError::io("Failed to create config directory", e))?;
+    }
+    
+    // Check if the config file already exists
+    if config_path.exists() {
+        println!("Configuration file already exists at {:?}. Overwrite? (y/n)", config_path);
+        if !prompter.confirm() {
+            return Err(CliError::cancelled("Configuration initialization cancelled by user."));
+        }
+    }
+    
+    // Create a new config with default values
+    let new_config = Config {
+        meal_plan_storage_path: config_dir.clone(),
+        current_week_start_date: Local::now().date_naive(),
+        ..Config::new()
+    };
+    
+    // Save the config
+    new_config.save(config_path)
+        .map_err(|e| CliError::io("Failed to save configuration", e))?;
+    
+    println!("Configuration saved to {:?}", config_path);
+    println!("Meal plan storage path: {:?}", new_config.meal_plan_storage_path);
+    println!("Current week start date: {}", new_config.current_week_start_date);
+    
+    Ok(())
+}
+
+/// Which way a sync would copy the plan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyncDirection {
+    UpToDate,
+    JsonToMarkdown,
+    MarkdownToJson,
+}
+
+/// Works out which way to sync from what changed since the files were last in sync,
+/// falling back to modification times when both changed and nothing was recorded
+fn sync_direction(storage_path: &Path, source_type: &str) -> Result<SyncDirection, CliError> {
+    let json_path = storage_path.join("meal_plan.json");
+    let markdown_path = storage_path.join("meal_plan.md");
+
+    // Check if both files exist
+    let json_exists = json_path.exists();
+    let markdown_exists = markdown_path.exists();
+
+    if !json_exists && !markdown_exists {
+        return Err(CliError::not_found("No meal plan files found to sync."));
+    }
+
+    let direction = match source_type.to_lowercase().as_str() {
+        "json" => SyncDirection::JsonToMarkdown,
+        "markdown" | "md" => SyncDirection::MarkdownToJson,
+        _ if !json_exists => SyncDirection::MarkdownToJson,
+        _ if !markdown_exists => SyncDirection::JsonToMarkdown,
+        _ => {
+            let state = SyncState::load(storage_path);
+            let state_recorded = state.markdown_hash.is_some();
+            let changes = state
+                .changes(&json_path, &markdown_path)
+                .map_err(|e| CliError::io("Failed to read meal plan files", e))?;
+            match (changes.json, changes.markdown) {
+                (false, false) => SyncDirection::UpToDate,
+                (true, false) => SyncDirection::JsonToMarkdown,
+                (false, true) => SyncDirection::MarkdownToJson,
+                // Markdown edits are applied on top of the JSON file, keeping its edits too
+                (true, true) if state_recorded => SyncDirection::MarkdownToJson,
+                (true, true) => {
+                    let modified = |path: &Path| {
+                        std::fs::metadata(path)
+                            .and_then(|m| m.modified())
+                            .map_err(|e| format!("Failed to get modification time of {:?}: {}", path, e))
+                    };
+                    if modified(&json_path)? >= modified(&markdown_path)? {
+                        SyncDirection::JsonToMarkdown
+                    } else {
+                        SyncDirection::MarkdownToJson
+                    }
+                }
+            }
+        }
+    };
+    Ok(direction)
+}
+
+fn sync_meal_plan(config: &Config, source_type: &str) -> Result<(), CliError> {
+    let json_path = config.meal_plan_storage_path.join("meal_plan.json");
+    let markdown_path = config.meal_plan_storage_path.join("meal_plan.md");
+
+    match sync_direction(&config.meal_plan_storage_path, source_type)? {
+        SyncDirection::UpToDate => {
+            println!("Meal plan files are already in sync.");
+        }
+        SyncDirection::JsonToMarkdown => {
+            println!("Syncing from JSON to Markdown...");
+            let meal_plan = MealPlan::load_from_json(&json_path)
+                .map_err(|e| CliError::io("Failed to load meal plan from JSON", e))?;
+
+            let kept = write_markdown(&meal_plan, &config.meal_plan_storage_path)
+                .map_err(|e| CliError::io("Failed to save meal plan to Markdown", e))?;
+            if let Some(kept) = kept {
+                println!("{:?} had been edited by hand; the edits were kept in {:?}.", markdown_path, kept);
+            }
+        }
+        SyncDirection::MarkdownToJson => {
+            println!("Syncing from Markdown to JSON...");
+            let changed = apply_markdown(config)?;
+            println!("Applied {} change(s) from the Markdown file.", changed);
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the edits made to meal_plan.md back into the plan. They are applied on top of the
+/// JSON file as it is now, so meals changed there since the Markdown was written are kept
+/// unless the Markdown changed the same slot. Returns how many slots changed.
+fn apply_markdown(config: &Config) -> Result<usize, CliError> {
+    let storage_path = &config.meal_plan_storage_path;
+    let json_path = storage_path.join("meal_plan.json");
+    let markdown_path = storage_path.join("meal_plan.md");
+    let markdown = MealPlan::load_from_markdown(&markdown_path)
+        .map_err(|e| CliError::io("Failed to load meal plan from Markdown", e))?;
+
+    let mut meal_plan = if json_path.exists() {
+        MealPlan::load_from_json(&json_path)
+            .map_err(|e| CliError::io("Failed to load meal plan from JSON", e))?
+    } else {
+        MealPlan::new(markdown.week_start_date)
+    };
+    if markdown.week_start_date != meal_plan.week_start_date {
+        return Err(CliError::conflict(format!(
+            "{:?} is for the week of {}, but the current plan is for the week of {}.",
+            markdown_path,
+            markdown.week_start_date.format("%Y-%m-%d"),
+            meal_plan.week_start_date.format("%Y-%m-%d")
+        )));
+    }
+    meal_plan.replica_id = current_replica(config);
+    meal_plan.user = Some(current_user(config));
+
+    // The Markdown was written from the JSON file as it was at the last sync, which is the
+    // file as it is now unless it changed since, and otherwise the plan mealplan last saved
+    let json_changed = SyncState::load(storage_path)
+        .changes(&json_path, &markdown_path)
+        .map_err(|e| CliError::io("Failed to read meal plan files", e))?
+        .json;
+    let recorded = if json_changed {
+        EventLog::new(storage_path).project()
+            .map_err(|e| CliError::io("Failed to read event log", e))?
+            .filter(|recorded| recorded.week_start_date == meal_plan.week_start_date)
+    } else {
+        None
+    };
+    let base = recorded.unwrap_or_else(|| if json_path.exists() { meal_plan.clone() } else { MealPlan::new(meal_plan.week_start_date) });
+
+    let changed = meal_plan.apply_markdown_edits(&base, &markdown);
+    // The Markdown has been read in, so rewriting it mustn't move it aside as unsynced edits
+    SyncState::record(storage_path, &json_path, &markdown_path)
+        .map_err(|e| CliError::io("Failed to record sync state", e))?;
+    save_meal_plan(&meal_plan, storage_path, config.backup_count)?;
+    Ok(changed)
+}
+
+/// Merges copies of the plan kept elsewhere, such as in a shared folder or on a mounted
+/// drive, into the local plan and writes the merged plan back to each, so every copy ends
+/// up the same. A copy that doesn't exist yet is created. Copies of another week are left
+/// alone and reported.
+fn sync_remotes(config: &Config, remotes: &[PathBuf]) -> Result<(), CliError> {
+    let storage_path = &config.meal_plan_storage_path;
+    if remotes.is_empty() || !storage_path.join("meal_plan.json").exists() {
+        return Ok(());
+    }
+    let local = load_meal_plan(storage_path)
+        .map_err(|e| CliError::io("Failed to load meal plan", e))?;
+
+    let mut merged = local.clone();
+    let mut skipped = Vec::new();
+    for remote in remotes.iter().filter(|remote| remote.exists()) {
+        let other = MealPlan::load_from_json(remote)
+            .map_err(|e| CliError::io(&format!("Failed to load meal plan from {:?}", remote), e))?;
+        if other.week_start_date == merged.week_start_date {
+            // With nobody to ask, the merge's choice stands
+            merge_copy(&mut merged, &other, |_, _, _| None);
+        } else {
+            skipped.push((remote, other.week_start_date));
+        }
+    }
+
+    let as_json = |plan: &MealPlan| serde_json::to_value(plan).ok();
+    if as_json(&merged) != as_json(&local) {
+        save_meal_plan(&merged, storage_path, config.backup_count)?;
+    }
+    for remote in remotes.iter().filter(|remote| !skipped.iter().any(|(skipped, _)| skipped == remote)) {
+        merged.save_to_json(remote)
+            .map_err(|e| CliError::io(&format!("Failed to write meal plan to {:?}", remote), e))?;
+    }
+
+    if !skipped.is_empty() {
+        return Err(CliError::conflict(format!(
+            "Not merged, as the current plan is for the week of {}: {}.",
+            merged.week_start_date.format("%Y-%m-%d"),
+            skipped.iter()
+                .map(|(remote, week)| format!("{:?} is for the week of {}", remote, week.format("%Y-%m-%d")))
+                .collect::<Vec<_>>()
+                .join("; ")
+        )));
+    }
+    Ok(())
+}
+
+/// Describes the state of the plan files and what `sync` would do, without syncing
+fn sync_status(storage_path: &Path, source_type: &str) -> Result<String, CliError> {
+    let json_path = storage_path.join("meal_plan.json");
+    let markdown_path = storage_path.join("meal_plan.md");
+    let changes = SyncState::load(storage_path)
+        .changes(&json_path, &markdown_path)
+        .map_err(|e| CliError::io("Failed to read meal plan files", e))?;
+
+    let mut output = String::new();
+    for (label, path, changed) in [("JSON", &json_path, changes.json), ("Markdown", &markdown_path, changes.markdown)] {
+        let state = match std::fs::metadata(path).and_then(|m| m.modified()) {
+            Err(_) => "missing".to_string(),
+            Ok(modified) => format!(
+                "{} since last sync (modified {})",
+                if changed { "changed" } else { "unchanged" },
+                DateTime::<Local>::from(modified).format("%Y-%m-%d %H:%M")
+            ),
+        };
+        output.push_str(&format!("{:<10}{}\n", format!("{}:", label), state));
+    }
+
+    // Meals edited in the JSON file by hand, compared with what mealplan last wrote
+    let recorded = EventLog::new(storage_path).project()
+        .map_err(|e| CliError::io("Failed to read event log", e))?;
+    if let (Some(recorded), Ok(current)) = (&recorded, MealPlan::load_from_json(&json_path)) {
+        let edits = format_meal_changes(&events::diff(Some(recorded), &current));
+        if !edits.is_empty() {
+            output.push_str("Meals changed in the JSON file outside mealplan:\n");
+            output.push_str(&edits);
+        }
+    }
+
+    // Copies a file sync service such as Dropbox or Syncthing left beside the plan
+    let copies = conflicted_copies(storage_path);
+    if copies.is_empty() {
+        output.push_str("Copies:   none left by a file sync service\n");
+    }
+    // Compared with the plan as mealplan last saved it, which loading it would use
+    let local = recorded.or_else(|| MealPlan::load_from_json(&json_path).ok());
+    for copy in &copies {
+        let name = copy.file_name().unwrap_or_default().to_string_lossy();
+        let other = match MealPlan::load_from_json(copy) {
+            Ok(other) => other,
+            Err(e) => {
+                output.push_str(&format!("Copy {:?} can't be read: {}\n", name, e));
+                continue;
+            }
+        };
+        match &local {
+            Some(local) if local.week_start_date != other.week_start_date => output.push_str(&format!(
+                "Copy {:?} is of the week of {}, so it can't be merged\n",
+                name,
+                other.week_start_date.format("%Y-%m-%d")
+            )),
+            Some(local) => {
+                let differences = local.differences(&other);
+                output.push_str(&format!("Copy {:?}: {} meal(s) differ from the plan\n", name, differences.len()));
+                output.push_str(&format_differences(&differences));
+                if !differences.is_empty() {
+                    output.push_str(&format!("  Merge it with 'mealplan sync merge {:?}'.\n", copy));
+                }
+            }
+            None => output.push_str(&format!("Copy {:?} waits to be merged\n", name)),
+        }
+    }
+
+    let next = match sync_direction(storage_path, source_type) {
+        Ok(SyncDirection::UpToDate) => "Files are in sync; a sync would do nothing.".to_string(),
+        Ok(SyncDirection::JsonToMarkdown) => "A sync would copy the JSON file to Markdown.".to_string(),
+        Ok(SyncDirection::MarkdownToJson) => "A sync would apply the Markdown file's edits to the JSON file.".to_string(),
+        Err(e) => e.message,
+    };
+    output.push_str(&next);
+    output.push('\n');
+    Ok(output)
+}
+
+/// Finds the copies of the plan that file sync services leave when two devices changed it at
+/// once, such as "meal_plan (conflicted copy).json" from Dropbox or
+/// "meal_plan.sync-conflict-20230102-180411-ABCDEFG.json" from Syncthing
+fn conflicted_copies(storage_path: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(storage_path) else {
+        return Vec::new();
+    };
+    let mut copies: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+            name.starts_with("meal_plan") && name.ends_with(".json") && name.contains("conflict")
+        })
+        .collect();
+    copies.sort();
+    copies
+}
+
+/// Lists how the meal slots differ between the plan and another copy, and which is newer
+fn format_differences(differences: &[models::SlotDifference]) -> String {
+    let describe = |meal: &Option<Meal>| match meal {
+        Some(meal) => format!("{:?}", meal.description),
+        None => "nothing".to_string(),
+    };
+    differences
+        .iter()
+        .map(|difference| {
+            let newer = match difference.newer {
+                models::NewerSide::Ours => "the plan is newer",
+                models::NewerSide::Theirs => "the copy is newer",
+                models::NewerSide::Both => "changed in both",
+            };
+            format!(
+                "  {} on {}: {} in the plan, {} in the copy ({})\n",
+                difference.meal_type,
+                difference.day,
+                describe(&difference.ours),
+                describe(&difference.theirs),
+                newer
+            )
+        })
+        .collect()
+}
+
+/// Lists the meal slots touched by a set of plan changes
+fn format_meal_changes(changes: &[events::Event]) -> String {
+    changes.iter().filter_map(describe_change).map(|change| format!("  {}\n", change)).collect()
+}
+
+/// Describes a change to the plan's meals in a line, with who made it when known. Changes
+/// to the trash are left out, as they go with a removal or restore described already.
+fn describe_change(change: &events::Event) -> Option<String> {
+    let by = |name: &Option<String>| name.as_ref().map(|name| format!(", by {}", name)).unwrap_or_default();
+    match change {
+        events::Event::MealAdded { meal } => Some(format!(
+            "{} on {}: {} (cook: {}){}",
+            meal.meal_type, meal.day, meal.description, meal.cook, by(&meal.updated_by)
+        )),
+        events::Event::MealRemoved { tombstone } => {
+            Some(format!("{} on {}: removed{}", tombstone.meal_type, tombstone.day, by(&tombstone.removed_by)))
+        }
+        events::Event::WeekStarted { week_start_date } => {
+            Some(format!("Week changed to {}", week_start_date.format("%Y-%m-%d")))
+        }
+        events::Event::MealTrashed { .. }
+        | events::Event::MealTakenFromTrash { .. }
+        | events::Event::TrashTombstoneAdded { .. } => None,
+    }
+}
+
+/// Lists the latest `limit` changes in the log, oldest first, with when they were made
+fn format_history(events: &[events::LoggedEvent], limit: usize) -> String {
+    let changes: Vec<(&events::LoggedEvent, String)> = events
+        .iter()
+        .filter_map(|logged| describe_change(&logged.event).map(|change| (logged, change)))
+        .collect();
+    if changes.is_empty() {
+        return "No changes recorded yet.\n".to_string();
+    }
+    changes[changes.len().saturating_sub(limit)..]
+        .iter()
+        .map(|(logged, change)| format!("{}  {}\n", logged.recorded_at.with_timezone(&Local).format("%Y-%m-%d %H:%M"), change))
+        .collect()
+}
+
+fn sync_peer<F>(meal_plan: &mut MealPlan, listen: Option<String>, connect: Option<String>, decide: F) -> Result<(), CliError>
+where
+    F: FnMut(&Meal, &Meal, duplicates::Collision) -> Option<duplicates::Strategy>,
+{
+    let secret = secrets::lookup(secrets::Integration::Peer).ok_or_else(|| {
+        CliError::usage("Set the same secret on both machines first with 'mealplan auth set peer'.")
+    })?;
+    let remote = match (listen, connect) {
+        (Some(addr), _) => {
+            println!("Waiting for a peer to connect on {}...", addr);
+            peer::listen(&addr, meal_plan, &secret)
+                .map_err(|e| CliError::io("Failed to sync with peer", e))?
+        }
+        (None, Some(addr)) => {
+            println!("Connecting to peer at {}...", addr);
+            peer::connect(&addr, meal_plan, &secret)
+                .map_err(|e| CliError::io("Failed to sync with peer", e))?
+        }
+        (None, None) => return Err(CliError::usage("Specify either --listen or --connect.")),
+    };
+
+    merge_peer_plan(meal_plan, &remote, decide)
+}
+
+/// Merges a plan received from a peer, refusing plans for a different week
+fn merge_peer_plan<F>(meal_plan: &mut MealPlan, remote: &MealPlan, decide: F) -> Result<(), CliError>
+where
+    F: FnMut(&Meal, &Meal, duplicates::Collision) -> Option<duplicates::Strategy>,
+{
+    if remote.week_start_date != meal_plan.week_start_date {
+        return Err(CliError::conflict(format!(
+            "Peer is planning the week of {}, but this machine is planning the week of {}.",
+            remote.week_start_date.format("%Y-%m-%d"),
+            meal_plan.week_start_date.format("%Y-%m-%d")
+        )));
+    }
+
+    let settled = merge_copy(meal_plan, remote, decide);
+    println!(
+        "Received {} meals from peer; plan now has {} meals.",
+        remote.meals().len(),
+        meal_plan.meals().len()
+    );
+    if settled > 0 {
+        println!("Settled {} meal(s) planned on both machines.", settled);
+    }
+
+    Ok(())
+}
+
+/// Merges another copy of the plan file, such as a conflicted copy left by a file sync service
+fn sync_merge_file<F>(meal_plan: &mut MealPlan, other_path: &PathBuf, decide: F) -> Result<(), CliError>
+where
+    F: FnMut(&Meal, &Meal, duplicates::Collision) -> Option<duplicates::Strategy>,
+{
+    let other = MealPlan::load_from_json(other_path)
+        .map_err(|e| format!("Failed to load meal plan from {:?}: {}", other_path, e))?;
+
+    if other.week_start_date != meal_plan.week_start_date {
+        return Err(CliError::conflict(format!(
+            "{:?} is for the week of {}, but the current plan is for the week of {}.",
+            other_path,
+            other.week_start_date.format("%Y-%m-%d"),
+            meal_plan.week_start_date.format("%Y-%m-%d")
+        )));
+    }
+
+    let settled = merge_copy(meal_plan, &other, decide);
+    println!("Merged {:?}; plan now has {} meals.", other_path, meal_plan.meals().len());
+    if settled > 0 {
+        println!("Settled {} meal(s) planned in both copies.", settled);
+    }
+
+    Ok(())
+}
+
+/// Merges another copy of the plan. Meals both copies planned for a slot without seeing
+/// each other's edit, or for the same date written once as a weekday and once as a date, go
+/// through the same duplicate handling as imports instead of the merge quietly keeping one.
+/// `decide` returns None to leave the merge's choice. Returns how many meals were settled.
+fn merge_copy<F>(meal_plan: &mut MealPlan, other: &MealPlan, mut decide: F) -> usize
+where
+    F: FnMut(&Meal, &Meal, duplicates::Collision) -> Option<duplicates::Strategy>,
+{
+    let mut pairs = meal_plan.concurrent_meals(other);
+    for theirs in other.meals() {
+        if meal_plan.find_meal(&theirs.meal_type, &theirs.day).is_some() {
+            continue;
+        }
+        if let Some(ours) = duplicates::find_at(meal_plan, &theirs.meal_type, meal_plan.date_of(&theirs.day)) {
+            pairs.push((ours.clone(), theirs.clone()));
+        }
+    }
+
+    let mut merged = meal_plan.merged_with(other);
+    let mut settled = 0;
+    for (ours, theirs) in pairs {
+        // Either copy may have removed the slot since, which the merge has already settled
+        let in_merged = |plan: &MealPlan, meal: &Meal| plan.find_meal(&meal.meal_type, &meal.day).is_some();
+        if !in_merged(&merged, &ours) || !in_merged(&merged, &theirs) {
+            continue;
+        }
+        let collision = duplicates::classify(&ours, &theirs);
+        let strategy = if collision == duplicates::Collision::Same {
+            duplicates::Strategy::Skip
+        } else {
+            match decide(&ours, &theirs, collision) {
+                Some(strategy) => strategy,
+                None if ours.day == theirs.day => continue,
+                // Both are in the merged plan, so one of them has to go
+                None => duplicates::Strategy::Skip,
+            }
+        };
+        let kept = match strategy {
+            duplicates::Strategy::Skip => ours.clone(),
+            duplicates::Strategy::Replace => theirs.clone(),
+            duplicates::Strategy::KeepBoth => duplicates::combine(&ours, &theirs),
+        };
+        if ours.day != theirs.day {
+            merged.remove_meal(&theirs.meal_type, &theirs.day);
+        } else if collision == duplicates::Collision::Same {
+            continue;
+        }
+        merged.change_meal(&ours.meal_type, &ours.day, |meal| *meal = Meal { day: ours.day.clone(), ..kept });
+        settled += 1;
+    }
+    *meal_plan = merged;
+    settled
+}
+
+/// What to do with a meal both copies planned: as `on_duplicate` says, as answered when
+/// there's someone to ask, and otherwise whatever the merge chose
+fn merge_decision<'a>(
+    on_duplicate: Option<duplicates::Strategy>,
+    prompter: &'a mut dyn Prompter,
+) -> impl FnMut(&Meal, &Meal, duplicates::Collision) -> Option<duplicates::Strategy> + 'a {
+    let interactive = prompter.interactive();
+    move |ours, theirs, collision| match on_duplicate {
+        Some(strategy) => Some(strategy),
+        None if interactive => Some(ask_duplicate(ours, theirs, collision, "The other copy", prompter)),
+        None => None,
+    }
+}
+
+/// Plans a meal and, on a later day, its leftovers, both assigned to the same cook
+#[allow(clippy::too_many_arguments)]
+fn batch_meal(
+    meal_plan: &mut MealPlan,
+    meal_type: &str,
+    day: &str,
+    leftovers_meal_type: &str,
+    leftovers_day: &str,
+    cook: &str,
+    description: &str,
+    updated_by: &str,
+    prompter: &mut dyn Prompter,
+) -> Result<(), CliError> {
+    let cook_date = meal_plan.date_of(&parse_day(day, meal_plan)?);
+    let leftovers_date = meal_plan.date_of(&parse_day(leftovers_day, meal_plan)?);
+    if leftovers_date <= cook_date {
+        return Err(CliError::usage(format!(
+            "Leftovers must be eaten after the meal is cooked ({} is not after {}).",
+            leftovers_date.format("%A %Y-%m-%d"),
+            cook_date.format("%A %Y-%m-%d")
+        )));
+    }
+    parse_meal_type(leftovers_meal_type)?;
+
+    add_meal_as(meal_plan, meal_type.to_string(), day.to_string(), cook.to_string(), description.to_string(), None, updated_by, prompter)?;
+    add_meal_as(
+        meal_plan,
+        leftovers_meal_type.to_string(),
+        leftovers_day.to_string(),
+        cook.to_string(),
+        format!("Leftovers: {}", description),
+        None,
+        updated_by,
+        prompter,
+    )
+}
+
+/// How many meals a batch makes: the meal itself and its leftovers
+const BATCH_FACTOR: f64 = 2.0;
+
+/// What a batch of `recipe` puts on the grocery list: its ingredients scaled for the meal
+/// and its leftovers, then any `extra` items given by hand
+fn batch_groceries(recipe: Option<&Recipe>, extra: &[String]) -> Vec<String> {
+    let mut items = recipe.map(|recipe| recipe.scaled_ingredients(BATCH_FACTOR)).unwrap_or_default();
+    items.extend(extra.iter().cloned());
+    items
+}
+
+/// Lists the recipes worth cooking in a double batch
+fn format_batch_suggestions(book: &RecipeBook) -> String {
+    let recipes = book.batch_friendly();
+    if recipes.is_empty() {
+        return format!(
+            "No batch-friendly recipes yet. Tag one with 'mealplan recipe add <name> --tags {}'.\n",
+            recipes::BATCH_FRIENDLY_TAGS[0]
+        );
+    }
+    let mut output = String::from("Batch-friendly recipes:\n");
+    for recipe in recipes {
+        output.push_str(&format!("  {}", recipe.name));
+        if let Some(servings) = recipe.servings {
+            output.push_str(&format!(" (serves {})", servings));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+/// Parses a length of time such as "60d" or "8w". A bare number counts days.
+fn parse_period(period: &str) -> Result<Duration, CliError> {
+    let period = period.trim().to_lowercase();
+    let (number, unit) = match period.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => period.split_at(index),
+        None => (period.as_str(), "d"),
+    };
+    let number: i64 = number.parse()
+        .map_err(|_| CliError::usage(format!("Invalid period '{}'. Use a number of days or weeks, e.g. 60d or 8w.", period)))?;
+
+    let duration = match unit {
+        "d" => Duration::try_days(number),
+        "w" => Duration::try_weeks(number),
+        _ => return Err(CliError::usage(format!("Invalid period '{}'. Use a number of days or weeks, e.g. 60d or 8w.", period))),
+    };
+    duration.ok_or_else(|| CliError::usage(format!("The period '{}' is too long.", period)))
+}
+
+/// The date a period before `today`, for options such as `--since 60d`
+fn date_before(today: NaiveDate, period: Duration) -> Result<NaiveDate, CliError> {
+    today.checked_sub_signed(period)
+        .ok_or_else(|| CliError::usage("The period reaches back further than dates go."))
+}
+
+/// Parses a `--modified-since` value: an RFC 3339 timestamp, a date, taken from the
+/// start of that day, or a period counted back from now
+fn parse_since(since: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, CliError> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(since.trim()) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(since.trim(), "%Y-%m-%d") {
+        let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+        return Local.from_local_datetime(&midnight)
+            .earliest()
+            .map(|start| start.with_timezone(&Utc))
+            .ok_or_else(|| CliError::usage(format!("{} has no midnight in the local time zone.", since)));
+    }
+    let period = parse_period(since)
+        .map_err(|_| CliError::usage(format!("Invalid --modified-since '{}'. Use a timestamp (2024-03-01T18:00:00Z), a date (YYYY-MM-DD) or a period such as 7d or 2w.", since)))?;
+    now.checked_sub_signed(period)
+        .ok_or_else(|| CliError::usage(format!("--modified-since {} reaches back further than dates go.", since.trim())))
+}
+
+/// Narrows the plan to what changed since `--modified-since`, if given. Also returns the
+/// time the export was taken, to pass as `--modified-since` on the next run.
+fn filter_modified_since(meal_plan: &MealPlan, since: Option<&str>) -> Result<(MealPlan, Option<DateTime<Utc>>), CliError> {
+    let now = Utc::now().trunc_subsecs(0);
+    match since {
+        Some(since) => Ok((meal_plan.modified_since(parse_since(since, now)?), Some(now))),
+        None => Ok((meal_plan.clone(), None)),
+    }
+}
+
+fn print_incremental_summary(changes: &MealPlan, next_since: Option<DateTime<Utc>>) {
+    if let Some(next_since) = next_since {
+        println!(
+            "Exported {} changed meal(s) and {} removal(s). Next time, use --modified-since {}",
+            changes.meals().len(),
+            changes.tombstones.len(),
+            next_since.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        );
+    }
+}
+
+fn format_search_results(meals: &[&IndexedMeal]) -> String {
+    if meals.is_empty() {
+        return "No meals match.\n".to_string();
+    }
+    let mut output = String::new();
+    for meal in meals {
+        output.push_str(&format!("{} {}: {}", meal.date.format("%a %Y-%m-%d"), meal.meal_type, meal.description));
+        if !meal.cook.trim().is_empty() {
+            output.push_str(&format!(" ({})", meal.cook));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn format_stale(stale: &[DishSummary], today: NaiveDate) -> String {
+    if stale.is_empty() {
+        return "Everything in your history has been planned recently.\n".to_string();
+    }
+
+    let mut output = String::from("Haven't had in a while:\n");
+    for dish in stale {
+        output.push_str(&format!(
+            "  {} (last planned {}, {} days ago)\n",
+            dish.description,
+            dish.last_planned.format("%Y-%m-%d"),
+            (today - dish.last_planned).num_days()
+        ));
+    }
+    output
+}
+
+fn format_variety(report: &VarietyReport) -> String {
+    let mut output = format!(
+        "Dinners over {} week{} starting {}\n",
+        report.weeks,
+        if report.weeks == 1 { "" } else { "s" },
+        report.first_week.format("%Y-%m-%d")
+    );
+    if report.total_dinners == 0 {
+        output.push_str("No dinners planned.\n");
+        return output;
+    }
+
+    output.push_str(&format!(
+        "Variety score: {:.0}% ({} unique dishes in {} dinners)\n",
+        report.variety_score * 100.0,
+        report.unique_dishes,
+        report.total_dinners
+    ));
+
+    let repeated: Vec<_> = report.dishes.iter().filter(|d| d.times > 1).collect();
+    if !repeated.is_empty() {
+        output.push_str("\nMost common:\n");
+        for dish in repeated.iter().take(5) {
+            output.push_str(&format!("  {} x{}\n", dish.description, dish.times));
+        }
+    }
+
+    let mut streaks: Vec<_> = report.dishes.iter().filter(|d| d.longest_streak_weeks > 1).collect();
+    streaks.sort_by_key(|d| std::cmp::Reverse(d.longest_streak_weeks));
+    if !streaks.is_empty() {
+        output.push_str("\nLongest repeat streaks:\n");
+        for dish in streaks.iter().take(5) {
+            output.push_str(&format!("  {}: {} weeks in a row\n", dish.description, dish.longest_streak_weeks));
+        }
+    }
+    output
+}
+
+/// Writes one row per dish, for people tracking variety in a spreadsheet
+fn variety_csv(report: &VarietyReport) -> String {
+    let mut csv = String::from("dish,times,longest_streak_weeks,last_planned\n");
+    for dish in &report.dishes {
+        csv.push_str(&format!(
+            "{},{},{},{}\n",
+            csv_field(&dish.description),
+            dish.times,
+            dish.longest_streak_weeks,
+            dish.last_planned.format("%Y-%m-%d")
+        ));
+    }
+    csv
+}
+
+/// Matches a receipt's lines to the grocery list, marking the items matched as purchased at
+/// the price on the receipt, summed when several lines match one item, and records the
+/// receipt's total as spent. Returns a summary of the matches.
+fn import_receipt(grocery_list: &mut GroceryList, prices: &mut PriceHistory, lines: &[ReceiptLine], source: &str, today: NaiveDate) -> String {
+    let names: Vec<String> = grocery_list.items.iter().map(|item| item.name.clone()).collect();
+    let name_refs: Vec<&str> = names.iter().map(String::as_str).collect();
+    let mut paid: BTreeMap<usize, f64> = BTreeMap::new();
+    let mut matched = String::new();
+    let mut unmatched = String::new();
+    for line in lines {
+        match receipt::match_item(&line.description, &name_refs) {
+            Some(index) => {
+                *paid.entry(index).or_default() += line.amount;
+                matched.push_str(&format!("  {} -> {} ({:.2})\n", line.description, names[index], line.amount));
+            }
+            None => unmatched.push_str(&format!("  {} ({:.2})\n", line.description, line.amount)),
+        }
+    }
+    for (index, price) in paid {
+        let item = &mut grocery_list.items[index];
+        item.purchased = true;
+        item.price = Some(price);
+        // Receipt amounts are always finite, so they're always recorded
+        prices.record(&item.name, price, today).ok();
+    }
+    let total: f64 = lines.iter().map(|line| line.amount).sum();
+    grocery_list.receipts.push(ImportedReceipt { source: source.to_string(), total });
+
+    let mut output = String::new();
+    if !matched.is_empty() {
+        output.push_str(&format!("Matched to the grocery list:\n{}", matched));
+    }
+    if !unmatched.is_empty() {
+        output.push_str(&format!("Not on the grocery list:\n{}", unmatched));
+    }
+    output.push_str(&format!("Recorded {:.2} from {} line(s) as spent this week.\n", total, lines.len()));
+    output
+}
+
+/// Parses a price given on the command line, which must be a finite amount of zero or more
+fn parse_price(s: &str) -> Result<f64, String> {
+    let price: f64 = s.trim().parse().map_err(|_| format!("{} isn't a price", s))?;
+    if price.is_nan() {
+        return Err(format!("{} isn't a number", s));
+    }
+    if price.is_infinite() {
+        return Err(format!("{} is too large for a price", s));
+    }
+    if price < 0.0 {
+        return Err("the price can't be negative".to_string());
+    }
+    Ok(price)
+}
+
+/// Adds a price paid today to the price history
+fn record_price(storage_path: &Path, item: &str, price: f64) -> Result<(), CliError> {
+    let prices_path = storage_path.join("prices.json");
+    let mut prices = PriceHistory::load_from_json(&prices_path)
+        .map_err(|e| CliError::io("Failed to load price history", e))?;
+    prices.record(item, price, Local::now().date_naive()).map_err(CliError::usage)?;
+    prices.save_to_json(&prices_path)
+        .map_err(|e| CliError::io("Failed to save price history", e))
+}
+
+/// Lists an item's recorded prices with the change from each to the next, or the latest
+/// price of every item
+fn format_price_history(prices: &PriceHistory, item: Option<&str>) -> Result<String, CliError> {
+    let Some(item) = item else {
+        if prices.items.is_empty() {
+            return Ok("No prices recorded yet. Record one with 'mealplan grocery cost set <item> <price>'.\n".to_string());
+        }
+        let mut output = String::new();
+        for item in prices.items.values() {
+            if let Some(last) = item.prices.last() {
+                output.push_str(&format!("{}: {:.2} on {}\n", item.name, last.price, last.date.format("%Y-%m-%d")));
+            }
+        }
+        return Ok(output);
+    };
+    let history = prices.history(item);
+    if history.is_empty() {
+        return Err(CliError::not_found(format!("No prices recorded for {}.", item.trim())));
+    }
+    let mut output = format!("Prices paid for {}:\n", item.trim());
+    for (n, point) in history.iter().enumerate() {
+        output.push_str(&format!("  {}  {:.2}", point.date.format("%Y-%m-%d"), point.price));
+        if let Some(previous) = n.checked_sub(1).map(|n| history[n].price).filter(|previous| *previous != 0.0) {
+            output.push_str(&format!(" ({:+.0}%)", (point.price - previous) / previous * 100.0));
+        }
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Writes the cart for an online grocery import: CSV or JSON with one entry per item, or
+/// plain lines for pasting
+fn format_cart(items: &[CartItem], format: &str) -> Result<String, CliError> {
+    match format.to_lowercase().as_str() {
+        "csv" => {
+            let mut csv = String::from("name,quantity,unit,note\n");
+            for item in items {
+                csv.push_str(&format!(
+                    "{},{},{},{}\n",
+                    csv_field(&item.name),
+                    units::format_amount(item.quantity),
+                    item.unit.as_deref().unwrap_or_default(),
+                    csv_field(item.note.as_deref().unwrap_or_default())
+                ));
+            }
+            Ok(csv)
+        }
+        "json" => serde_json::to_string_pretty(items)
+            .map(|json| json + "\n")
+            .map_err(|e| CliError::new(error::ErrorKind::Data, format!("Failed to serialize the grocery list: {}", e))),
+        "lines" | "text" => Ok(items
+            .iter()
+            .map(|item| match &item.unit {
+                Some(unit) => format!("{} {} {}\n", units::format_amount(item.quantity), unit, item.name),
+                None if item.quantity != 1.0 => format!("{} {}\n", units::format_amount(item.quantity), item.name),
+                None => format!("{}\n", item.name),
+            })
+            .collect()),
+        _ => Err(CliError::usage("Invalid format. Must be csv, json, or lines.")),
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Gets a Google access token from a service account key, or else from the environment
+fn google_access_token(credentials: Option<PathBuf>) -> Result<String, CliError> {
+    let credentials = credentials.or_else(|| std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS").map(PathBuf::from));
+    if let Some(path) = credentials {
+        let key = sheets::ServiceAccountKey::load(&path)
+            .map_err(|e| CliError::io(&format!("Failed to read service account key {:?}", path), e))?;
+        return key.access_token().map_err(|e| CliError::io("Failed to sign in to Google", e));
+    }
+    if let Some(stored) = secrets::lookup(secrets::Integration::Google) {
+        let key: sheets::ServiceAccountKey = serde_json::from_str(&stored)
+            .map_err(|e| CliError::io("Failed to read the stored service account key", e.into()))?;
+        return key.access_token().map_err(|e| CliError::io("Failed to sign in to Google", e));
+    }
+
+    match std::env::var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+        Ok(token) if !token.trim().is_empty() => Ok(token.trim().to_string()),
+        _ => Err(CliError::usage(
+            "No Google credentials. Pass --credentials with a service account key, store one with 'mealplan auth set google', or set GOOGLE_OAUTH_ACCESS_TOKEN.",
+        )),
+    }
+}
+
+/// Loads the current plan and the archived weeks before it, `weeks` weeks in all, oldest first
+fn parse_filter(filter: Option<&str>) -> Result<Option<Filter>, CliError> {
+    filter
+        .map(|filter| Filter::parse(filter).map_err(|e| CliError::usage(format!("Invalid filter: {}", e))))
+        .transpose()
+}
+
+/// Returns the plan with only the meals matching the filter, or the whole plan without one
+/// The plan an export works on: the one in the file given with `--input`, read from stdin
+/// when it is `-`, or else the stored plan
+fn input_plan(meal_plan: &MealPlan, input: Option<&Path>) -> Result<MealPlan, CliError> {
+    let Some(input) = input else {
+        return Ok(meal_plan.clone());
+    };
+    let (contents, source) = if input == Path::new("-") {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)
+            .map_err(|e| CliError::io("Failed to read the plan from stdin", e))?;
+        (contents, "stdin".to_string())
+    } else {
+        let contents = std::fs::read_to_string(input)
+            .map_err(|e| CliError::io(&format!("Failed to read {:?}", input), e))?;
+        (contents, format!("{:?}", input))
+    };
+    serde_json::from_str(&contents)
+        .map_err(|e| CliError::new(error::ErrorKind::Data, format!("The plan from {} isn't a valid meal plan: {}", source, e)))
+}
+
+fn filtered_plan(meal_plan: &MealPlan, filter: Option<&str>) -> Result<MealPlan, CliError> {
+    let mut meal_plan = meal_plan.clone();
+    if let Some(filter) = parse_filter(filter)? {
+        filter.retain(&mut meal_plan);
+    }
+    Ok(meal_plan)
+}
+
+/// Loads the history since `first_week`, keeping only the meals matching the filter
+fn filtered_history(archive: &Archive, meal_plan: &MealPlan, first_week: NaiveDate, filter: Option<&str>) -> Result<MealHistory, CliError> {
+    let filter = parse_filter(filter)?;
+    let mut plans = history::load_plans_since(archive, meal_plan, first_week)
+        .map_err(|e| CliError::io("Failed to load archived weeks", e))?;
+    if let Some(filter) = &filter {
+        plans.iter_mut().for_each(|plan| filter.retain(plan));
+    }
+    Ok(MealHistory::from_plans(&plans))
+}
+
+/// The start of the first of `weeks` weeks ending with the plan's week, for `--weeks`
+fn first_week(meal_plan: &MealPlan, weeks: usize) -> Result<NaiveDate, CliError> {
+    history::first_week_of(meal_plan.week_start_date, weeks)
+        .ok_or_else(|| CliError::usage(format!("{} weeks reaches back further than dates go.", weeks)))
+}
+
+fn recent_weeks(archive: &Archive, meal_plan: &MealPlan, weeks: usize) -> Result<Vec<MealPlan>, CliError> {
+    let first_week = first_week(meal_plan, weeks)?;
+    let mut plans: Vec<MealPlan> = archive
+        .load_weeks(first_week..meal_plan.week_start_date)
+        .map_err(|e| CliError::io("Failed to load archived weeks", e))?;
+    plans.push(meal_plan.clone());
+    Ok(plans)
+}
+
+/// Resolves a week for `compare` and `freeze`: "this", "next", "last", a number of weeks
+/// ago, or a date in the week. Returns the week's start date.
+fn resolve_week(spec: &str, current_week: NaiveDate) -> Result<NaiveDate, CliError> {
+    let spec = spec.trim().to_lowercase();
+    let weeks_ago = match spec.as_str() {
+        "this" | "current" => 0,
+        "next" => -1,
+        "last" | "previous" => 1,
+        _ => match (spec.parse::<u32>(), NaiveDate::parse_from_str(&spec, "%Y-%m-%d")) {
+            (Ok(weeks), _) => weeks as i64,
+            (_, Ok(date)) => {
+                return current_week.checked_add_signed(Duration::weeks((date - current_week).num_days().div_euclid(7)))
+                    .ok_or_else(|| CliError::usage(format!("The week of {} is further off than dates go.", spec)))
+            }
+            _ => {
+                return Err(CliError::usage(format!(
+                    "Invalid week '{}'. Use this, next, last, a number of weeks ago, or a date (YYYY-MM-DD).",
+                    spec
+                )))
+            }
+        },
+    };
+    current_week.checked_sub_signed(Duration::weeks(weeks_ago))
+        .ok_or_else(|| CliError::usage(format!("{} weeks ago is further back than dates go.", weeks_ago)))
+}
+
+/// Loads a week's plan and grocery list for `compare`: the current ones for this week,
+/// otherwise the archived ones
+fn load_compared_week(archive: &Archive, meal_plan: &MealPlan, storage_path: &Path, spec: &str) -> Result<(MealPlan, Option<GroceryList>), CliError> {
+    let week = resolve_week(spec, meal_plan.week_start_date)?;
+    if week == meal_plan.week_start_date {
+        let groceries = GroceryList::load_from_json(storage_path.join("grocery_list.json"))
+            .ok()
+            .filter(|list| list.week_start_date == week);
+        return Ok((meal_plan.clone(), groceries));
+    }
+
+    let plan = archive.load_week(week).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            CliError::not_found(format!("No archived plan for the week of {}.", week.format("%Y-%m-%d")))
+        } else {
+            CliError::io("Failed to load archived week", e)
+        }
+    })?;
+    let groceries = archive.load_groceries(week).map_err(|e| CliError::io("Failed to load archived grocery list", e))?;
+    Ok((plan, groceries))
+}
+
+/// Collects the grocery lists of the weeks starting in a report's period: archived ones,
+/// plus the current week's list
+fn report_grocery_lists(archive: &Archive, meal_plan: &MealPlan, storage_path: &Path, period: ReportPeriod) -> Result<Vec<GroceryList>, CliError> {
+    let weeks = archive.weeks().map_err(|e| CliError::io("Failed to load archived weeks", e))?;
+    let mut lists = Vec::new();
+    for week in weeks.into_iter().filter(|week| period.contains(*week) && *week != meal_plan.week_start_date) {
+        if let Some(list) = archive.load_groceries(week).map_err(|e| CliError::io("Failed to load archived grocery list", e))? {
+            lists.push(list);
+        }
+    }
+
+    if period.contains(meal_plan.week_start_date) {
+        if let Ok(list) = GroceryList::load_from_json(storage_path.join("grocery_list.json")) {
+            if list.week_start_date == meal_plan.week_start_date {
+                lists.push(list);
+            }
+        }
+    }
+    Ok(lists)
+}
+
+fn week_has_ended(meal_plan: &MealPlan, today: NaiveDate) -> bool {
+    today >= meal_plan.week_start_date + Duration::days(7)
+}
+
+/// Archives the current week and returns an empty plan for the week after it. When the
+/// week ended more than a week ago, the new plan starts at the week containing `today`.
+fn rollover(meal_plan: &MealPlan, archive: &Archive, today: NaiveDate, force: bool) -> Result<MealPlan, CliError> {
+    if !force && !week_has_ended(meal_plan, today) {
+        return Err(CliError::conflict(format!(
+            "The week of {} has not ended yet. Use --force to roll over anyway.",
+            meal_plan.week_start_date.format("%Y-%m-%d")
+        )));
+    }
+
+    if !meal_plan.meals().is_empty() {
+        let path = archive.save_week(meal_plan)
+            .map_err(|e| CliError::io("Failed to archive meal plan", e))?;
+        println!("Archived the week of {} to {:?}", meal_plan.week_start_date.format("%Y-%m-%d"), path);
+    }
+
+    let weeks_passed = ((today - meal_plan.week_start_date).num_days() / 7).max(1);
+    let mut next_week = MealPlan::new(meal_plan.week_start_date + Duration::days(7 * weeks_passed));
+    next_week.replica_id = meal_plan.replica_id.clone();
+
+    Ok(next_week)
+}
+
+/// The ingredients merged in a storage path. A file that can't be read is warned about, and
+/// only the built-in names are used.
+fn load_ingredients(storage_path: &Path) -> ingredients::Registry {
+    ingredients::Registry::load_from_json(storage_path.join(ingredients::INGREDIENTS_FILE)).unwrap_or_else(|e| {
+        eprintln!("Warning: Failed to load merged ingredients: {}", e);
+        ingredients::Registry::default()
+    })
+}
+
+/// Loads the configuration file again for a long-running command, with the environment
+/// overrides and the --path given when it started
+fn reload_config(config_path: &Path, storage_override: Option<&Path>) -> Result<Config, String> {
+    let config = Config::load(config_path).map_err(|e| e.to_string())?;
+    let mut config = settings::apply_env_overrides(&config, std::env::vars())?;
+    if let Some(path) = storage_override {
+        config.meal_plan_storage_path = path.to_path_buf();
+    }
+    http::configure(config.http.clone(), Some(config.meal_plan_storage_path.join(http::OUTBOX_FILE)));
+    ingredients::configure(load_ingredients(&config.meal_plan_storage_path));
+    Ok(config)
+}
+
+fn run_daemon(config: &reload::SharedConfig, interval: u64, debounce: u64, remotes: &[PathBuf]) -> Result<(), CliError> {
+    let storage_path = reload::current(config).meal_plan_storage_path;
+    let status = Arc::new(Mutex::new(daemon::DaemonStatus::new()));
+
+    // The socket stays where the daemon started, so `daemon status` finds it until a restart
+    #[cfg(unix)]
+    daemon::serve_status(&storage_path.join(daemon::STATUS_SOCKET), Arc::clone(&status))
+        .map_err(|e| CliError::io("Failed to open daemon status socket", e))?;
+
+    // Timed reminders are sent from their own thread, as syncing only happens on changes
+    // Budget alerts are checked there too, so spending recorded by hand is noticed
+    let shared = Arc::clone(config);
+    std::thread::spawn(move || loop {
+        let reminder_config = reload::current(&shared);
+        let reminder_path = reminder_config.meal_plan_storage_path.clone();
+        let now = Utc::now().format("%Y-%m-%d %H:%M:%S");
+        // Pushes that waited for the network go out before anything new
+        match http::flush_outbox(&reminder_path.join(http::OUTBOX_FILE)) {
+            Ok(summary) => {
+                if summary.sent > 0 {
+                    println!("[{}] Sent {} push(es) from the outbox.", now, summary.sent);
+                }
+                for rejected in summary.rejected {
+                    eprintln!("[{}] Dropped {}", now, rejected);
+                }
+            }
+            Err(e) => eprintln!("[{}] Failed to update the outbox: {}", now, e),
+        }
+        match send_due_reminders(&reminder_config.notify, &reminder_path, Local::now().naive_local()) {
+            Ok(0) => {}
+            Ok(sent) => println!("[{}] Sent {} reminder(s).", now, sent),
+            Err(e) => eprintln!("[{}] {}", now, e),
+        }
+        let alerts = load_meal_plan(&reminder_path)
+            .map_err(|e| CliError::io("Failed to load meal plan", e))
+            .and_then(|meal_plan| budget_alerts(&reminder_config, &reminder_path, &meal_plan))
+            .and_then(|alerts| send_budget_alerts(&reminder_config.notify, &reminder_path, &alerts));
+        match alerts {
+            Ok(0) => {}
+            Ok(sent) => println!("[{}] Sent {} budget alert(s).", now, sent),
+            Err(e) => eprintln!("[{}] {}", now, e),
+        }
+        std::thread::sleep(std::time::Duration::from_secs(REMINDER_CHECK_SECS));
+    });
+
+    println!("Watching {:?} for changes. Press Ctrl+C to stop.", storage_path);
+    // Edits to either file are synced into the other, then the plan is merged with the
+    // remote copies, which are watched too so their changes come in as they are made
+    daemon::watch(
+        || {
+            let storage_path = reload::current(config).meal_plan_storage_path;
+            let mut paths = vec![storage_path.join("meal_plan.json"), storage_path.join("meal_plan.md")];
+            paths.extend(remotes.iter().cloned());
+            paths
+        },
+        std::time::Duration::from_secs(interval.max(1)),
+        std::time::Duration::from_secs(debounce),
+        status,
+        || {
+            let config = reload::current(config);
+            sync_meal_plan(&config, "auto")
+                .and_then(|()| sync_remotes(&config, remotes))
+                .map_err(|e| e.to_string())
+        },
+    );
+
+    Ok(())
+}
+
+fn matrix_config(config: &Config) -> Result<&matrix::MatrixConfig, CliError> {
+    config.matrix.as_ref().ok_or_else(|| {
+        CliError::not_found("No Matrix room configured. Add a \"matrix\" section with homeserver, room_id and access_token to the configuration.")
+    })
+}
+
+/// Lists the meals planned on a date, in meal order, for posting to a chat
+fn format_daily_menu(meal_plan: &MealPlan, date: NaiveDate) -> String {
+    let mut meals: Vec<&Meal> = meal_plan.meals().iter().filter(|meal| meal_plan.date_of(&meal.day) == date).collect();
+    if meals.is_empty() {
+        return format!("Nothing planned for {}.", date.format("%A, %b %-d"));
+    }
+    meals.sort_by_key(|meal| spreadsheet::GRID_MEAL_TYPES.iter().position(|t| *t == meal.meal_type));
+    let mut text = format!("Menu for {}:", date.format("%A, %b %-d"));
+    for meal in meals {
+        text.push_str(&format!("\n- {}: {}", meal.meal_type, meal_plan.render_description(meal)));
+        if !meal.cook.trim().is_empty() {
+            text.push_str(&format!(" ({})", meal.cook));
+        }
+    }
+    text
+}
+
+/// Says what's planned for a day in a sentence for a voice assistant to read out, e.g.
+/// "Tonight Alice is cooking chili; lunch is leftover soup."
+fn format_speech(meal_plan: &MealPlan, date: NaiveDate, today: NaiveDate) -> String {
+    let day = if date == today {
+        "today".to_string()
+    } else if date == today + Duration::days(1) {
+        "tomorrow".to_string()
+    } else {
+        format!("on {}", date.format("%A"))
+    };
+    let mut meals: Vec<&Meal> = meal_plan.meals().iter().filter(|meal| meal_plan.date_of(&meal.day) == date).collect();
+    if meals.is_empty() {
+        return format!("Nothing is planned {}.", day);
+    }
+    // Dinner first, as it's what's most often asked about
+    meals.sort_by_key(|meal| (meal.meal_type != MealType::Dinner, spreadsheet::GRID_MEAL_TYPES.iter().position(|t| *t == meal.meal_type)));
+
+    let dish = |meal: &Meal| {
+        let description = meal_plan.render_description(meal);
+        match description.trim().strip_prefix(history::LEFTOVERS_PREFIX) {
+            Some(rest) if !rest.trim().is_empty() => format!("leftover {}", rest.trim()),
+            Some(_) => "leftovers".to_string(),
+            None => description.trim().to_string(),
+        }
+    };
+    let clauses: Vec<String> = meals
+        .iter()
+        .enumerate()
+        .map(|(n, meal)| {
+            let meal_type = meal.meal_type.to_string().to_lowercase();
+            let cook = meal.cook.trim();
+            match (n, &meal.meal_type) {
+                (0, MealType::Dinner) => {
+                    let when = match day.as_str() {
+                        "today" => "tonight".to_string(),
+                        "tomorrow" => "tomorrow night".to_string(),
+                        _ => format!("{} night", day),
+                    };
+                    match (cook, &meal.place) {
+                        (_, Some(place)) if !place.eq_ignore_ascii_case(&dish(meal)) => format!("{} it's {} from {}", when, dish(meal), place),
+                        (_, Some(place)) => format!("{} it's {}", when, place),
+                        ("", None) => format!("{} dinner is {}", when, dish(meal)),
+                        (cook, None) => format!("{} {} is cooking {}", when, cook, dish(meal)),
+                    }
+                }
+                (0, _) => format!("{}, {} is {}", day, meal_type, dish(meal)),
+                _ => format!("{} is {}", meal_type, dish(meal)),
+            }
+        })
+        .collect();
+    let sentence = clauses.join("; ");
+    let mut chars = sentence.chars();
+    match chars.next() {
+        Some(first) => format!("{}{}.", first.to_uppercase(), chars.as_str()),
+        None => sentence,
+    }
+}
+
+/// Answers a request to `mealplan serve`, loading the plan afresh so edits show up
+/// straight away
+fn serve_request(config: &Config, storage_path: &Path, request: &server::Request, today: NaiveDate, read_only: bool) -> server::Response {
+    if let Some((content_type, body)) = webapp::asset(&request.path) {
+        return server::Response { status: 200, content_type, body };
+    }
+    match request.path.as_str() {
+        "/api/plan" => match load_meal_plan(storage_path) {
+            Ok(meal_plan) => server::Response::json(200, &webapp::plan_json(&meal_plan)),
+            Err(e) => server::Response::text(500, format!("Failed to load meal plan: {}\n", e)),
+        },
+        "/api/ops" if request.method != "POST" => server::Response::text(405, "Post edits as newline-delimited JSON\n"),
+        "/api/ops" if read_only => server::Response::text(403, "The server is read-only\n"),
+        "/api/ops" if request.content_type() != webapp::OPS_CONTENT_TYPE => {
+            server::Response::text(415, format!("Send edits as {}\n", webapp::OPS_CONTENT_TYPE))
+        }
+        "/api/ops" => match serve_ops(config, storage_path, &request.body) {
+            Ok(summary) => server::Response::json(200, &serde_json::json!({
+                "added": summary.added,
+                "edited": summary.edited,
+                "removed": summary.removed,
+                "warnings": summary.warnings.iter().map(|(_, warning)| warning).collect::<Vec<_>>(),
+            })),
+            Err(e) => {
+                let status = match e.kind {
+                    error::ErrorKind::Usage => 400,
+                    error::ErrorKind::NotFound => 404,
+                    error::ErrorKind::Conflict => 409,
+                    _ => 500,
+                };
+                server::Response::text(status, format!("{}\n", e.message))
+            }
+        },
+        "/today/speech" => {
+            let meal_plan = match load_meal_plan(storage_path) {
+                Ok(meal_plan) => meal_plan,
+                Err(e) => return server::Response::text(500, format!("Failed to load meal plan: {}\n", e)),
+            };
+            let day = request.query.get("day").map_or("today", String::as_str);
+            match packing_date(&meal_plan, day, today) {
+                Ok(date) => server::Response::text(200, format_speech(&meal_plan, date, today)),
+                Err(e) => server::Response::text(400, format!("{}\n", e)),
+            }
+        }
+        _ => server::Response::not_found(),
+    }
+}
+
+/// Applies edits posted by the web app, all or nothing, and saves the plan
+fn serve_ops(config: &Config, storage_path: &Path, body: &str) -> Result<BulkSummary, CliError> {
+    let ops = bulk::parse_ops(body).map_err(CliError::usage)?;
+    let meal_plan = load_meal_plan(storage_path).map_err(|e| CliError::io("Failed to load meal plan", e))?;
+    let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+        .map_err(|e| CliError::io("Failed to load recipes", e))?;
+    let (updated, summary) = apply_ops(config, &meal_plan, &book, ops, WEB_APP_USER)?;
+    save_meal_plan(&updated, storage_path, config.backup_count)?;
+    Ok(summary)
+}
+
+/// Carries out a command posted in the Matrix room and returns the reply. Meals added
+/// there are saved straight away and recorded as added by the sender.
+fn matrix_reply(config: &Config, storage_path: &Path, command: matrix::BotCommand, sender: &str, today: NaiveDate) -> Result<String, CliError> {
+    let mut meal_plan = load_meal_plan(storage_path).map_err(|e| CliError::io("Failed to load meal plan", e))?;
+    match command {
+        matrix::BotCommand::Help => Ok(matrix::BOT_HELP.to_string()),
+        matrix::BotCommand::Today => Ok(format_daily_menu(&meal_plan, today)),
+        matrix::BotCommand::Tonight => Ok(meal_plan.meals().iter()
+            .find(|meal| meal.meal_type == MealType::Dinner && meal_plan.date_of(&meal.day) == today)
+            .map_or_else(
+                || "Nothing planned for dinner tonight.".to_string(),
+                |meal| match meal.cook.trim() {
+                    "" => format!("Tonight: {}", meal_plan.render_description(meal)),
+                    cook => format!("Tonight: {}, cooked by {}", meal_plan.render_description(meal), cook),
+                },
+            )),
+        matrix::BotCommand::Add { meal_type, day, description, cook } => {
+            let cook = match cook {
+                Some(cook) => cook,
+                None if !config.rotation().is_empty() => "auto".to_string(),
+                None => matrix::sender_name(sender),
+            };
+            let book = RecipeBook::load_from_json(storage_path.join("recipes.json"))
+                .map_err(|e| CliError::io("Failed to load recipes", e))?;
+            let cook = resolve_cook(config, &meal_plan, &cook, &meal_type, &day, &dish(&description, &[], &book))?;
+            validate_cook(config, &cook)?;
+            let parsed = parse_day(&day, &meal_plan)?;
+            // There's no one to confirm replacing a meal, so planned meals are left alone
+            if let Some(meal) = meal_plan.find_meal(&parse_meal_type(&meal_type)?, &parsed) {
+                return Err(CliError::conflict(format!(
+                    "{} on {} is already planned: {}.",
+                    meal.meal_type,
+                    meal_plan.date_of(&parsed).format("%A"),
+                    meal_plan.render_description(meal)
+                )));
+            }
+            add_meal_as(&mut meal_plan, meal_type.clone(), day, cook.clone(), description.clone(), config.find_alias(&description), sender, &mut Scripted::default())?;
+            save_meal_plan(&meal_plan, storage_path, config.backup_count)?;
+
+            let mut reply = format!(
+                "Added {} on {}: {} ({}).",
+                parse_meal_type(&meal_type)?.to_string().to_lowercase(),
+                meal_plan.date_of(&parsed).format("%A"),
+                description,
+                cook
+            );
+            if let Some(conflict) = availability_conflict(config, &meal_plan, &cook, &parsed) {
+                reply.push_str(&format!(" Note: {}", conflict));
+            }
+            Ok(reply)
+        }
+    }
+}
+
+/// Long-polls the Matrix room for commands, posting the day's menu once its time comes
+fn run_matrix_bot(config: &Config, storage_path: &Path) -> Result<(), CliError> {
+    let matrix = matrix_config(config)?;
+    let post_at = matrix.post_time().map_err(CliError::usage)?;
+    let client = matrix::MatrixClient::new(matrix);
+    let own_id = client.whoami().map_err(|e| CliError::io("Failed to sign in to Matrix", e))?;
+
+    let state_path = storage_path.join("matrix.json");
+    let mut state = matrix::BotState::load_from_json(&state_path)
+        .map_err(|e| CliError::io("Failed to load the Matrix bot's state", e))?;
+    let save_state = |state: &matrix::BotState| state.save_to_json(&state_path)
+        .map_err(|e| CliError::io("Failed to save the Matrix bot's state", e));
+    if state.next_batch.is_none() {
+        // Start from now rather than answering the room's history
+        state.next_batch = Some(client.sync(None, 0).map_err(|e| CliError::io("Failed to sync with Matrix", e))?.next_batch);
+        save_state(&state)?;
+    }
+
+    println!("Posting to {} as {}. Press Ctrl+C to stop.", matrix.room_id, own_id);
+    let log_time = || Utc::now().format("%Y-%m-%d %H:%M:%S");
+    loop {
+        let now = Local::now().naive_local();
+        if state.last_posted != Some(now.date()) && now.time() >= post_at {
+            let menu = load_meal_plan(storage_path).map(|plan| format_daily_menu(&plan, now.date()));
+            match menu.and_then(|menu| client.send_text(&menu)) {
+                Ok(()) => {
+                    state.last_posted = Some(now.date());
+                    save_state(&state)?;
+                    println!("[{}] Posted the menu.", log_time());
+                }
+                Err(e) => eprintln!("[{}] Failed to post the menu: {}", log_time(), e),
+            }
+        }
+
+        let batch = match client.sync(state.next_batch.as_deref(), MATRIX_SYNC_TIMEOUT_MS) {
+            Ok(batch) => batch,
+            Err(e) => {
+                eprintln!("[{}] Failed to sync with Matrix: {}", log_time(), e);
+                std::thread::sleep(std::time::Duration::from_secs(MATRIX_RETRY_SECS));
+                continue;
+            }
+        };
+        for message in batch.messages.iter().filter(|message| message.sender != own_id) {
+            let Some(command) = matrix::parse_command(&message.body) else { continue };
+            let reply = command
+                .map_err(CliError::usage)
+                .and_then(|command| matrix_reply(config, storage_path, command, &message.sender, Local::now().date_naive()))
+                .unwrap_or_else(|e| e.to_string());
+            if let Err(e) = client.send_text(&reply) {
+                eprintln!("[{}] Failed to reply to {}: {}", log_time(), message.sender, e);
+            }
+        }
+        state.next_batch = Some(batch.next_batch);
+        save_state(&state)?;
+    }
+}
+
+/// How long each Matrix sync waits for new messages
+const MATRIX_SYNC_TIMEOUT_MS: u64 = 30_000;
+
+/// Seconds to wait before syncing again after the homeserver couldn't be reached
+const MATRIX_RETRY_SECS: u64 = 10;
+
+/// Title of reminder notifications
+const REMINDER_TITLE: &str = "Meal plan reminder";
+const SWAP_TITLE: &str = "Meal swap";
+/// Who edits from the web app are recorded as made by
+const WEB_APP_USER: &str = "web";
+const BUDGET_ALERT_TITLE: &str = "Meal plan budget alert";
+
+/// Seconds between the daemon's checks for reminders that have come due
+const REMINDER_CHECK_SECS: u64 = 30;
+
+/// Sends the reminders that are due by `now`, returning how many were sent. Reminders that
+/// couldn't be sent stay queued to be tried again.
+fn send_due_reminders(targets: &[notify::NotifyTarget], storage_path: &Path, now: chrono::NaiveDateTime) -> Result<usize, CliError> {
+    let path = storage_path.join("reminders.json");
+    let mut reminders = notify::Reminders::load_from_json(&path)
+        .map_err(|e| CliError::io("Failed to load reminders", e))?;
+    let due = reminders.take_due(now);
+    if due.is_empty() {
+        return Ok(0);
+    }
+
+    let notifiers = notify::notifiers(targets);
+    let mut sent = 0;
+    let mut failures = Vec::new();
+    for reminder in due {
+        match notify::send_all(&notifiers, REMINDER_TITLE, &reminder.message) {
+            Ok(()) => sent += 1,
+            Err(e) => {
+                failures.push(format!("{:?}: {}", reminder.message, e));
+                reminders.add(reminder);
+            }
+        }
+    }
+    reminders.save_to_json(&path)
+        .map_err(|e| CliError::io("Failed to save reminders", e))?;
+    if failures.is_empty() {
+        Ok(sent)
+    } else {
+        Err(CliError::new(error::ErrorKind::General, format!("Failed to send {} reminder(s): {}", failures.len(), failures.join("; "))))
+    }
+}
+
+/// Loads the swap requests, leaving out those for meals already past
+fn load_swaps(path: &Path, today: NaiveDate) -> Result<swaps::Swaps, CliError> {
+    let mut swaps = swaps::Swaps::load_from_json(path)
+        .map_err(|e| CliError::io("Failed to load swap requests", e))?;
+    swaps.expire(today);
+    Ok(swaps)
+}
+
+/// Picks the request waiting for a cook, which must be the only one left once narrowed
+/// down by meal type and day
+fn pick_swap(swaps: &swaps::Swaps, meal_plan: &MealPlan, cook: &str, meal_type: Option<String>, day: Option<String>) -> Result<swaps::SwapRequest, CliError> {
+    let meal_type = meal_type.as_deref().map(parse_meal_type).transpose()?;
+    let date = match day {
+        Some(day) => Some(meal_plan.date_of(&parse_day(&day, meal_plan)?)),
+        None => None,
+    };
+    let waiting = swaps.waiting_for(cook, meal_type.as_ref(), date);
+    match waiting.as_slice() {
+        [request] => Ok((*request).clone()),
+        [] if swaps.pending.is_empty() => Err(CliError::not_found("No swap requests are waiting.")),
+        [] => Err(CliError::not_found(format!(
+            "No matching swap request is waiting for {}. Pass --as to answer as another cook.\n{}",
+            cook.trim(),
+            format_swaps(swaps).trim_end()
+        ))),
+        _ => Err(CliError::usage(format!(
+            "{} swap requests are waiting for {}; pick one with --day and --meal-type:\n{}",
+            waiting.len(),
+            cook.trim(),
+            waiting.iter().map(|request| format!("  {}", request.meal())).collect::<Vec<_>>().join("\n")
+        ))),
+    }
+}
+
+/// Lists the swap requests waiting to be accepted
+fn format_swaps(swaps: &swaps::Swaps) -> String {
+    if swaps.pending.is_empty() {
+        return "No swap requests waiting.\n".to_string();
+    }
+    let mut output = String::from("Swap requests waiting:\n");
+    for request in &swaps.pending {
+        output.push_str(&format!("  {}: {} asked {}\n", request.meal(), request.from, request.to));
+    }
+    output
+}
+
+/// Tells a cook about a swap through the configured notifications. The swap stands whether
+/// or not the notification gets through.
+fn notify_swap(config: &Config, cook: &str, message: &str) {
+    match notify::send_all(&notify::notifiers(&config.notify), SWAP_TITLE, message) {
+        Ok(()) => println!("Notified {}.", cook),
+        Err(e) => eprintln!("Warning: Couldn't notify {}: {}", cook, e),
+    }
+}
+
+/// Lists the reminders waiting to be sent
+fn format_reminders(reminders: &notify::Reminders, today: NaiveDate) -> String {
+    if reminders.pending.is_empty() {
+        return "No reminders waiting.\n".to_string();
+    }
+    reminders.pending.iter()
+        .map(|reminder| format!("{}: {}\n", notify::describe_time(reminder.at, today), reminder.message))
+        .collect()
+}
+
+/// Lists the pushes waiting in the outbox, oldest first
+fn format_outbox(outbox: &http::Outbox) -> String {
+    if outbox.pending.is_empty() {
+        return "Nothing waiting to be sent.\n".to_string();
+    }
+    outbox.pending.iter()
+        .map(|push| {
+            let queued = push.queued_at.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+            match push.attempts {
+                0 => format!("{}: {}\n", queued, push.description),
+                attempts => format!("{}: {} (tried {} more time(s))\n", queued, push.description, attempts),
+            }
+        })
+        .collect()
+}
+
+fn daemon_install(
+    config_path: &Path,
+    storage_path: &Path,
+    interval: u64,
+    debounce: u64,
+    remotes: &[PathBuf],
+    prompter: &mut dyn Prompter,
+) -> Result<(), CliError> {
+    let executable = std::env::current_exe()
+        .map_err(|e| CliError::io("Could not determine the mealplan executable", e))?;
+    let config_path = std::path::absolute(config_path)
+        .map_err(|e| CliError::io("Could not determine the configuration file's path", e))?;
+    let mut args = vec![
+        "--config".to_string(),
+        config_path.display().to_string(),
+        "--path".to_string(),
+        storage_path.display().to_string(),
+        "daemon".to_string(),
+        "--interval".to_string(),
+        interval.to_string(),
+        "--debounce".to_string(),
+        debounce.to_string(),
+    ];
+    for remote in remotes {
+        let remote = std::path::absolute(remote)
+            .map_err(|e| CliError::io("Could not determine a remote plan's path", e))?;
+        args.extend(["--remote".to_string(), remote.display().to_string()]);
+    }
+
+    let unit_dir = dirs::config_dir()
+        .ok_or_else(|| "Could not determine config directory".to_string())?
+        .join("systemd")
+        .join("user");
+    std::fs::create_dir_all(&unit_dir)
+        .map_err(|e| CliError::io("Failed to create systemd unit directory", e))?;
+
+    let unit_path = unit_dir.join("mealplan.service");
+    if unit_path.exists() {
+        println!("Unit file {:?} already exists. Overwrite? (y/n)", unit_path);
+        if !prompter.confirm() {
+            return Err(CliError::cancelled("Daemon installation cancelled by user."));
+        }
+    }
+
+    std::fs::write(&unit_path, daemon::systemd_unit(&executable, &args))
+        .map_err(|e| CliError::io("Failed to write systemd unit", e))?;
+
+    println!("Systemd unit written to {:?}", unit_path);
+    println!("Enable it with: systemctl --user enable --now mealplan.service");
+    Ok(())
+}
+
+#[cfg(unix)]
+fn daemon_status(storage_path: &Path) -> Result<(), CliError> {
+    let status = daemon::query_status(&storage_path.join(daemon::STATUS_SOCKET))
+        .map_err(|e| CliError::io("Daemon is not running or not reachable", e))?;
+
+    println!("Daemon running (pid {})", status.pid);
+    println!("Started: {}", status.started_at.format("%Y-%m-%d %H:%M:%S"));
+    println!("Syncs performed: {}", status.sync_count);
+    match (status.last_sync, status.last_result) {
+        (Some(at), Some(result)) => println!("Last sync: {} ({})", at.format("%Y-%m-%d %H:%M:%S"), result),
+        _ => println!("Last sync: never"),
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn daemon_status(_storage_path: &Path) -> Result<(), CliError> {
+    Err("Daemon status is only available on Unix systems.".to_string())
+}
+
+fn export_json(meal_plan: &MealPlan, output_path: &PathBuf) -> Result<(), CliError> {
+    // Simply use the existing save_to_json method
+    meal_plan.save_to_json(output_path)
+        .map_err(|e| CliError::io("Failed to export meal plan to JSON", e))
+}
+
+fn bundle_export(meal_plan: &MealPlan, book: &RecipeBook, output_path: &PathBuf, prompter: &mut dyn Prompter) -> Result<(), CliError> {
+    if output_path.exists() {
+        println!("File {:?} already exists. Overwrite? (y/n)", output_path);
+        if !prompter.confirm() {
+            return Err(CliError::cancelled("Bundle export cancelled by user."));
+        }
+    }
+
+    PlanBundle::new(meal_plan.clone(), book)
+        .save(output_path)
+        .map_err(|e| CliError::io("Failed to export bundle", e))
+}
+
+fn bundle_import(meal_plan: &mut MealPlan, book: &mut RecipeBook, input_path: &PathBuf, on_duplicate: Option<duplicates::Strategy>, prompter: &mut dyn Prompter) -> Result<(), CliError> {
+    let bundle = PlanBundle::load(input_path)
+        .map_err(|e| CliError::io("Failed to load bundle", e))?;
+
+    println!(
+        "Importing {} meals from the week of {}...",
+        bundle.meal_plan.meals().len(),
+        bundle.meal_plan.week_start_date.format("%Y-%m-%d")
+    );
+
+    let summary = bundle.merge_into(meal_plan, |existing, incoming, collision| {
+        on_duplicate.unwrap_or_else(|| ask_duplicate(existing, incoming, collision, "The bundle", prompter))
+    });
+
+    println!(
+        "Bundle imported: {} added, {} replaced, {} kept alongside, {} unchanged, {} skipped.",
+        summary.added, summary.replaced, summary.combined, summary.unchanged, summary.skipped
+    );
+
+    let recipes_added = bundle.merge_recipes_into(book);
+    if !bundle.recipes.is_empty() {
+        println!(
+            "Recipes: {} added to the recipe book, {} already there.",
+            recipes_added,
+            bundle.recipes.len() - recipes_added
+        );
+    }
+
+    Ok(())
+}
+
+/// Loads the grocery list for the given week, starting a new one if none exists
+/// or the stored list belongs to a different week
+fn load_grocery_list(path: &PathBuf, week_start_date: NaiveDate) -> GroceryList {
+    match GroceryList::load_from_json(path) {
+        Ok(list) if list.week_start_date == week_start_date => list,
+        Ok(_) => {
+            println!("Grocery list is from a previous week. Starting a new one.");
+            GroceryList::new(week_start_date)
+        }
+        Err(e) => {
+            if path.exists() {
+                eprintln!("Warning: Failed to load grocery list: {}", e);
+                eprintln!("Creating a new grocery list instead.");
+            }
+            GroceryList::new(week_start_date)
+        }
+    }
+}
+
+/// Adds an item to the grocery list, returning the list line it ended up on, which differs
+/// from the item when its amount was combined with one already listed
+fn grocery_add(grocery_list: &mut GroceryList, item: &str, category: Option<String>) -> Result<String, CliError> {
+    let item = item.trim();
+    if item.is_empty() {
+        return Err(CliError::usage("Grocery item cannot be empty."));
+    }
+
+    match grocery_list.add(item, category) {
+        Some(line) => Ok(line.name.clone()),
+        None => Err(CliError::conflict(format!("{} is already on the grocery list.", item))),
+    }
+}
+
+fn grocery_added_message(item: &str, line: &str) -> String {
+    if line.eq_ignore_ascii_case(item.trim()) {
+        format!("Added {} to the grocery list.", item.trim())
+    } else {
+        format!("Added {} to the grocery list, which now has {}.", item.trim(), line)
+    }
+}
+
+fn grocery_set_purchased(grocery_list: &mut GroceryList, item: &str, purchased: bool) -> Result<(), CliError> {
+    if !grocery_list.set_purchased(item.trim(), purchased) {
+        return Err(CliError::not_found(format!("{} is not on the grocery list.", item)));
+    }
+
+    Ok(())
+}
+
+fn grocery_status(grocery_list: &GroceryList) {
+    let remaining = grocery_list.remaining();
+    let purchased = grocery_list.items.len() - remaining.len();
+
+    println!("Grocery list for week of {}", grocery_list.week_start_date.format("%Y-%m-%d"));
+    println!("Purchased: {} of {}", purchased, grocery_list.items.len());
+
+    if remaining.is_empty() {
+        println!("Nothing left to buy.");
+    } else {
+        println!("\nRemaining items:");
+        for item in remaining {
+            println!("  [ ] {}", item.name);
+        }
+    }
+}
+
+/// Renders the grocery list, optionally split into one section per preferred store
+fn format_grocery_list(grocery_list: &GroceryList, store_preferences: Option<&HashMap<String, String>>) -> String {
+    let mut output = format!("Grocery list for week of {}\n", grocery_list.week_start_date.format("%Y-%m-%d"));
+
+    if grocery_list.items.is_empty() {
+        output.push_str("The grocery list is empty.\n");
+        return output;
+    }
+
+    let format_item = |item: &GroceryItem| {
+        let mark = if item.purchased { "x" } else { " " };
+        format!("  [{}] {}\n", mark, item.name)
+    };
+
+    match store_preferences {
+        Some(store_preferences) => {
+            for (store, items) in grocery_list.group_by_store(store_preferences) {
+                output.push_str(&format!("\n{}:\n", store));
+                for item in items {
+                    output.push_str(&format_item(item));
+                }
+            }
+        }
+        None => {
+            for item in &grocery_list.items {
+                output.push_str(&format_item(item));
+            }
+        }
+    }
+
+    output
+}
+
+/// Lists what the meals until the next shopping day need from their recipes that isn't on
+/// the grocery list, or the whole week's meals when there are no shopping days
+fn format_meal_ingredients(grocery_list: &GroceryList, meal_plan: &MealPlan, book: &RecipeBook, attendance: &Attendance, shopping_days: &[Weekday], today: NaiveDate) -> String {
+    let week_end = meal_plan.week_start_date + Duration::days(6);
+    let (from, to) = match grocery::shopping_window(today, shopping_days) {
+        Some((from, to)) => (from.max(meal_plan.week_start_date), to.min(week_end)),
+        None => (meal_plan.week_start_date, week_end),
+    };
+    if from > to {
+        return String::new();
+    }
+    let needed = grocery::ingredients_for_meals(grocery_list, meal_plan, book, attendance, from, to);
+    if needed.is_empty() {
+        return String::new();
+    }
+    let mut output = format!("\nAlso needed for meals {} to {}:\n", from.format("%a %b %-d"), to.format("%a %b %-d"));
+    for ingredient in needed {
+        output.push_str(&format!("  - {}\n", ingredient));
+    }
+    output
+}
+
+/// Asks what to do with an incoming meal for a slot that's already planned; `source` names
+/// where the meal came from, e.g. "The import"
+fn ask_duplicate(existing: &Meal, incoming: &Meal, collision: duplicates::Collision, source: &str, prompter: &mut dyn Prompter) -> duplicates::Strategy {
+    println!(
+        "{} on {} is already planned as \"{}\" (Cook: {}).",
+        existing.meal_type, existing.day, existing.description, existing.cook
+    );
+    if collision == duplicates::Collision::Duplicate {
+        println!("\"{}\" (Cook: {}) looks like the same meal.", incoming.description, incoming.cook);
+    } else {
+        println!("{} has \"{}\" (Cook: {}).", source, incoming.description, incoming.cook);
+    }
+    loop {
+        print!("[s]kip, [r]eplace or [k]eep both? ");
+        // With nothing left to read, leave the planned meal alone
+        let Ok(Some(input)) = prompter.answer() else {
+            return duplicates::Strategy::Skip;
+        };
+        match input.trim().to_lowercase().as_str() {
+            "s" | "skip" => return duplicates::Strategy::Skip,
+            "r" | "replace" => return duplicates::Strategy::Replace,
+            "k" | "keep" | "keep both" => return duplicates::Strategy::KeepBoth,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    /// Adds a meal with nobody to answer, so planning over a meal is cancelled
+    fn add_meal(meal_plan: &mut MealPlan, meal_type: String, day: String, cook: String, description: String, updated_by: &str) -> Result<(), CliError> {
+        add_meal_as(meal_plan, meal_type, day, cook, description, None, updated_by, &mut Scripted::default())
+    }
+
+    #[test]
+    fn verify_cli() {
+        Args::command().debug_assert()
+    }
+
+    #[test]
+    fn test_add_command() {
+        let args = Args::parse_from([
+            "mealplan",
+            "add",
+            "Spaghetti Bolognese",
+            "--meal-type", "Dinner",
+            "--day", "Monday",
+            "--cook", "John",
+        ]);
+        match args.command {
+            Some(Commands::Add { description, meal_type, day, cook, .. }) => {
+                assert_eq!(description, Some("Spaghetti Bolognese".to_string()));
+                assert_eq!(meal_type, "Dinner");
+                assert_eq!(day, Some("Monday".to_string()));
+                assert_eq!(cook, Some("John".to_string()));
+            }
+            _ => panic!("Expected Add command"),
+        }
+    }
+
+    #[test]
+    fn test_edit_command() {
+        let args = Args::parse_from([
+            "mealplan",
+            "edit",
+            "Updated meal description",
+            "--meal-type", "Lunch",
+            "--day", "Tuesday",
+        ]);
+        match args.command {
+            Some(Commands::Edit { description, meal_type, day, cook, .. }) => {
+                assert_eq!(description, Some("Updated meal description".to_string()));
+                assert_eq!(meal_type, "Lunch");
+                assert_eq!(day, "Tuesday");
+                assert_eq!(cook, None);
+            }
+            _ => panic!("Expected Edit command"),
+        }
+    }
+
+    #[test]
+    fn test_remove_command() {
+        let args = Args::parse_from([
+            "mealplan",
+            "remove",
+            "--meal-type", "Breakfast",
+            "--day", "Wednesday"
+        ]);
+        match args.command {
+            Some(Commands::Remove { meal_type, day, all, .. }) => {
+                assert_eq!(meal_type, Some("Breakfast".to_string()));
+                assert_eq!(day, Some("Wednesday".to_string()));
+                assert!(!all);
+            }
+            _ => panic!("Expected Remove command"),
+        }
+    }
+
+    #[test]
+    fn test_recipe_search_command() {
+        let args = Args::parse_from([
+            "mealplan",
+            "recipe", "search", "chick parm",
+            "--ingredient", "chicken",
+            "-i", "cheese"
+        ]);
+        match args.command {
+            Some(Commands::Recipe { action: RecipeAction::Search { query, ingredient, tag } }) => {
+                assert_eq!(query.as_deref(), Some("chick parm"));
+                assert_eq!(ingredient, vec!["chicken", "cheese"]);
+                assert!(tag.is_empty());
+            }
+            _ => panic!("Expected Recipe Search command"),
+        }
+    }
+
+    #[test]
+    fn test_pack_command() {
+        let args = Args::parse_from(["mealplan", "pack", "install", "summer grilling", "--replace"]);
+        match args.command {
+            Some(Commands::Pack { action: PackAction::Install { source, replace } }) => {
+                assert_eq!(source, "summer grilling");
+                assert!(replace);
+            }
+            _ => panic!("Expected Pack Install command"),
+        }
+
+        assert_eq!(load_pack("Holiday-Week").unwrap().name, "Holiday week");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("camping.json");
+        std::fs::write(&path, r#"{"name": "Camping", "week": {"sat": {"dinner": "Foil packets"}}}"#).unwrap();
+        assert_eq!(load_pack(path.to_str().unwrap()).unwrap().week.len(), 1);
+        std::fs::write(&path, r#"{"name": "Camping", "week": {"sat": {"supper": "Foil packets"}}}"#).unwrap();
+        assert_eq!(load_pack(path.to_str().unwrap()).unwrap_err().kind, error::ErrorKind::Usage);
+        let missing = load_pack("winter").unwrap_err();
+        assert_eq!(missing.kind, error::ErrorKind::NotFound);
+        assert!(missing.message.contains("back-to-school, summer-grilling, holiday-week"));
+    }
+
+    #[test]
+    fn test_export_ical_command() {
+        let args = Args::parse_from([
+            "mealplan",
+            "export-ical",
+            "--output", "/tmp/mealplan.ics"
+        ]);
+        match args.command {
+            Some(Commands::ExportIcal { output, .. }) => {
+                assert_eq!(output, Some(PathBuf::from("/tmp/mealplan.ics")));
+            }
+            _ => panic!("Expected ExportIcal command"),
+        }
+
+        // One of --output and --merge is required
+        assert!(Args::try_parse_from(["mealplan", "export-ical"]).is_err());
+        assert!(Args::try_parse_from(["mealplan", "export-ical", "--merge", "family.ics"]).is_ok());
+
+        // Splitting writes into a directory, so it can't be merged
+        let args = Args::parse_from(["mealplan", "export-ical", "--output", "calendars", "--split", "weekly", "--weeks", "4"]);
+        match args.command {
+            Some(Commands::ExportIcal { split, weeks, .. }) => {
+                assert_eq!(split, Some(CalendarSplit::Weekly));
+                assert_eq!(weeks, Some(4));
+            }
+            _ => panic!("Expected ExportIcal command"),
+        }
+        assert!(Args::try_parse_from(["mealplan", "export-ical", "--merge", "family.ics", "--split", "monthly"]).is_err());
+        assert!(Args::try_parse_from(["mealplan", "export-ical", "--output", "calendars", "--weeks", "4"]).is_err());
+    }
+
+    #[test]
+    fn test_import_on_duplicate() {
+        let args = Args::parse_from([
+            "mealplan",
+            "import",
+            "--file", "meals.csv",
+            "--map", "A=day,B=meal_type,C=description",
+            "--on-duplicate", "keep-both"
+        ]);
+        match args.command {
+            Some(Commands::Import { on_duplicate, .. }) => {
+                assert_eq!(on_duplicate, Some(duplicates::Strategy::KeepBoth));
+            }
+            _ => panic!("Expected Import command"),
+        }
+
+        assert!(Args::try_parse_from(["mealplan", "bundle", "import", "week.json", "--on-duplicate", "merge"]).is_err());
+    }
+
+    #[test]
+    fn test_ingredient_merge_command() {
+        let args = Args::parse_from(["mealplan", "ingredient", "merge", "scallion", "spring onion", "--into", "green onion"]);
+        match args.command {
+            Some(Commands::Ingredient { action: IngredientAction::Merge { names, into } }) => {
+                assert_eq!(names, vec!["scallion", "spring onion"]);
+                assert_eq!(into, "green onion");
+            }
+            _ => panic!("Expected Ingredient Merge command"),
+        }
+
+        assert!(Args::try_parse_from(["mealplan", "ingredient", "merge", "--into", "green onion"]).is_err());
+    }
+
+    #[test]
+    fn test_whatcanimake_command() {
+        let args = Args::parse_from(["mealplan", "whatcanimake", "--max-minutes", "30", "-n", "3"]);
+        match args.command {
+            Some(Commands::WhatCanIMake { max_minutes, limit }) => {
+                assert_eq!(max_minutes, Some(30));
+                assert_eq!(limit, 3);
+            }
+            _ => panic!("Expected WhatCanIMake command"),
+        }
+
+        let mut pantry = Pantry::default();
+        pantry.add("eggs");
+        let mut book = RecipeBook::default();
+        book.add(Recipe {
+            name: "Omelette".to_string(),
+            tags: Vec::new(),
+            ingredients: vec!["3 eggs".to_string(), "1/2 cup cheese".to_string()],
+            link: None,
+            servings: None,
+            photo: None,
+            steps: vec!["Cook for 5 minutes".to_string()],
+            equipment: Vec::new(),
+        });
+        let matches = pantry::what_can_i_make(&pantry, &book, None);
+        assert_eq!(format_pantry_matches(&matches), "  50%  Omelette (5 min)\n       missing: 1/2 cup cheese\n");
+    }
+
+    #[test]
+    fn test_examples() {
+        for topic in examples::TOPICS {
+            for words in topic.commands {
+                let args = std::iter::once("mealplan").chain(words.iter().copied());
+                if let Err(e) = Args::try_parse_from(args) {
+                    panic!("The {} example `mealplan {}` doesn't parse: {}", topic.name, words.join(" "), e);
+                }
+            }
+        }
+
+        assert!(format_examples(None).unwrap().contains("  week       Plan a week\n"));
+        let groceries = format_examples(Some("groceries")).unwrap();
+        assert!(groceries.starts_with("# Generate the grocery list\n\n# Add a recipe, or replace it"));
+        assert!(groceries.contains("mealplan recipe add Chili --ingredient \"1 lb ground beef\""));
+        assert!(groceries.contains("# Add an item to the grocery list\nmealplan grocery add milk\n"));
+        assert!(format_examples(Some("taxes")).is_err());
+    }
+
+    #[test]
+    fn test_attendance_command() {
+        let args = Args::parse_from(["mealplan", "attendance", "away", "Sam", "--day", "tue", "-t", "dinner"]);
+        match args.command {
+            Some(Commands::Attendance { action: AttendanceAction::Away { name, day, meal_type } }) => {
+                assert_eq!((name.as_str(), day.as_str(), meal_type.as_deref()), ("Sam", "tue", Some("dinner")));
+            }
+            _ => panic!("Expected Attendance Away command"),
+        }
+        let args = Args::parse_from(["mealplan", "attendance", "member", "Sam", "--allergy", "peanut", "--allergy", "sesame"]);
+        match args.command {
+            Some(Commands::Attendance { action: AttendanceAction::Member { allergies, .. } }) => assert_eq!(allergies, vec!["peanut", "sesame"]),
+            _ => panic!("Expected Attendance Member command"),
+        }
+
+        let mut attendance = Attendance::default();
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        assert!(format_attendance(&attendance, &meal_plan).starts_with("No household members."));
+        attendance.set_member("Alex", &[]).unwrap();
+        attendance.set_member("Sam", &["peanut".to_string()]).unwrap();
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Tuesday".to_string(), "Alex".to_string(), "Chili".to_string(), "tester").unwrap();
+        attendance.set_away("Sam", NaiveDate::from_ymd_opt(2023, 1, 3).unwrap(), None).unwrap();
+        assert_eq!(
+            format_attendance(&attendance, &meal_plan),
+            "Household:\n  Alex\n  Sam (allergic to peanut)\n\
+             Away this week:\n  Sam: every meal on Tue Jan 3\n\
+             Planned meals not everyone is eating:\n  Tue dinner: Chili, 1 of 2 eating\n"
+        );
+    }
+
+    #[test]
+    fn test_snapshot_command() {
+        let args = Args::parse_from(["mealplan", "snapshot", "create", "before guests", "--replace"]);
+        match args.command {
+            Some(Commands::Snapshot { action: SnapshotAction::Create { name, replace } }) => {
+                assert_eq!(name, "before guests");
+                assert!(replace);
+            }
+            _ => panic!("Expected Snapshot Create command"),
+        }
+        assert!(Args::try_parse_from(["mealplan", "snapshot", "restore"]).is_err());
+    }
+
+    #[test]
+    fn test_swap_commands() {
+        let args = Args::parse_from(["mealplan", "swap-request", "--day", "friday", "--meal-type", "dinner", "--to", "Bob"]);
+        match args.command {
+            Some(Commands::SwapRequest { meal_type, day, to }) => assert_eq!((meal_type.as_str(), day.as_str(), to.as_str()), ("dinner", "friday", "Bob")),
+            _ => panic!("Expected SwapRequest command"),
+        }
+        match Args::parse_from(["mealplan", "swap-accept", "--as", "Bob"]).command {
+            Some(Commands::SwapAccept { cook, day: None, meal_type: None }) => assert_eq!(cook.as_deref(), Some("Bob")),
+            _ => panic!("Expected SwapAccept command"),
+        }
+        assert!(Args::try_parse_from(["mealplan", "swap-request", "--day", "friday", "--meal-type", "dinner"]).is_err());
+
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        let mut swaps = swaps::Swaps::default();
+        assert_eq!(pick_swap(&swaps, &meal_plan, "Bob", None, None).unwrap_err().kind, error::ErrorKind::NotFound);
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Friday".to_string(), "Alice".to_string(), "Tacos".to_string(), "tester").unwrap();
+        add_meal(&mut meal_plan, "Lunch".to_string(), "Friday".to_string(), "Alice".to_string(), "Soup".to_string(), "tester").unwrap();
+        for meal_type in [MealType::Dinner, MealType::Lunch] {
+            swaps.request(&meal_plan, &meal_type, &Day::Weekday(Weekday::Fri), "Bob", Utc::now()).unwrap();
+        }
+        assert_eq!(
+            format_swaps(&swaps),
+            "Swap requests waiting:\n  Dinner on Fri 2024-01-05 (Tacos): Alice asked Bob\n  Lunch on Fri 2024-01-05 (Soup): Alice asked Bob\n"
+        );
+
+        // Two are waiting, so the meal type has to be given
+        assert_eq!(pick_swap(&swaps, &meal_plan, "bob", None, Some("friday".to_string())).unwrap_err().kind, error::ErrorKind::Usage);
+        let request = pick_swap(&swaps, &meal_plan, "bob", Some("lunch".to_string()), None).unwrap();
+        assert_eq!(request.description, "Soup");
+        let error = pick_swap(&swaps, &meal_plan, "Carol", None, None).unwrap_err();
+        assert!(error.message.contains("Pass --as"), "{}", error.message);
+    }
+
+    #[test]
+    fn test_sync_peer_command() {
+        let args = Args::parse_from([
+            "mealplan",
+            "sync",
+            "peer",
+            "--connect", "kitchen-pi.local:7878"
+        ]);
+        match args.command {
+            Some(Commands::Sync { action: Some(SyncAction::Peer { listen, connect, .. }), .. }) => {
+                assert_eq!(listen, None);
+                assert_eq!(connect, Some("kitchen-pi.local:7878".to_string()));
+            }
+            _ => panic!("Expected Sync Peer command"),
+        }
+
+        // Exactly one of --listen and --connect is required
+        assert!(Args::try_parse_from(["mealplan", "sync", "peer"]).is_err());
+        assert!(Args::try_parse_from([
+            "mealplan", "sync", "peer", "--listen", "0.0.0.0:7878", "--connect", "pi:7878"
+        ]).is_err());
+    }
+
+    #[test]
+    fn test_daemon_command() {
+        let args = Args::parse_from([
+            "mealplan",
+            "daemon",
+            "--debounce", "5"
+        ]);
+        match args.command {
+            Some(Commands::Daemon { interval, debounce, remotes, action: None }) => {
+                assert_eq!(interval, 1);
+                assert_eq!(debounce, 5);
+                assert!(remotes.is_empty());
+            }
+            _ => panic!("Expected Daemon command"),
+        }
+
+        let args = Args::parse_from(["mealplan", "daemon", "install"]);
+        assert!(matches!(args.command, Some(Commands::Daemon { action: Some(DaemonAction::Install), .. })));
+    }
+
+    #[test]
+    fn test_remove_plaintext_token() {
+        let mut config = Config::new();
+        config.notify = vec![
+            notify::NotifyTarget::Gotify { server: "https://push.example.com".to_string(), token: "abc".to_string(), priority: None },
+            notify::NotifyTarget::Gotify { server: "https://other.example.com".to_string(), token: "xyz".to_string(), priority: None },
+            notify::NotifyTarget::Ntfy { server: notify::DEFAULT_NTFY_SERVER.to_string(), topic: "dinner".to_string(), token: Some("abc".to_string()) },
+        ];
+        assert!(remove_plaintext_token(&mut config, secrets::Integration::Gotify, "abc"));
+        assert!(!remove_plaintext_token(&mut config, secrets::Integration::Gotify, "abc"));
+        assert!(matches!(&config.notify[0], notify::NotifyTarget::Gotify { token, .. } if token.is_empty()));
+        assert!(matches!(&config.notify[1], notify::NotifyTarget::Gotify { token, .. } if token == "xyz"));
+        assert!(matches!(&config.notify[2], notify::NotifyTarget::Ntfy { token: Some(_), .. }));
+        assert!(!remove_plaintext_token(&mut config, secrets::Integration::Matrix, "abc"));
+        assert!(!remove_plaintext_token(&mut config, secrets::Integration::Email, "abc"));
+
+        let args = Args::parse_from(["mealplan", "auth", "set", "matrix"]);
+        assert!(matches!(args.command, Some(Commands::Auth { action: AuthAction::Set { integration: secrets::Integration::Matrix } })));
+    }
+
+    #[test]
+    fn test_config_init_command() {
+        let args = Args::parse_from([
+            "mealplan",
+            "config",
+            "init"
+        ]);
+        match args.command {
+            Some(Commands::Config { action: ConfigAction::Init }) => {},
+            _ => panic!("Expected Config Init command"),
+        }
+    }
+
+    #[test]
+    fn test_grocery_check_command() {
+        let args = Args::parse_from([
+            "mealplan",
+            "grocery",
+            "check",
+            "milk"
+        ]);
+        match args.command {
+            Some(Commands::Grocery { action: Some(GroceryAction::Check { item, .. }), .. }) => {
+                assert_eq!(item, "milk");
+            }
+            _ => panic!("Expected Grocery Check command"),
+        }
+
+        let args = Args::parse_from([
+            "mealplan",
+            "grocery",
+            "--by-store"
+        ]);
+        match args.command {
+            Some(Commands::Grocery { by_store, action: None, .. }) => assert!(by_store),
+            _ => panic!("Expected Grocery command"),
+        }
+    }
+
+    #[test]
+    fn test_add_meal() {
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        
+        // Test adding a valid meal
+        assert!(add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").is_ok());
+        
+        // Test adding an invalid meal type
+        assert!(add_meal(&mut meal_plan, "Brunch".to_string(), "Tuesday".to_string(), "Alice".to_string(), "Eggs".to_string(), "tester").is_err());
+        
+        // Test adding a meal with an invalid day
+        assert!(add_meal(&mut meal_plan, "Lunch".to_string(), "Someday".to_string(), "Bob".to_string(), "Sandwich".to_string(), "tester").is_err());
+        
+        // Test adding a duplicate meal, which asks before replacing: with no answer it fails
+        assert!(add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "Jane".to_string(), "Pizza".to_string(), "tester").is_err());
+
+        // Answering yes replaces it
+        let mut answers = Scripted::new(["y"]);
+        assert!(add_meal_as(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "Jane".to_string(), "Pizza".to_string(), None, "tester", &mut answers).is_ok());
+        assert_eq!(answers.remaining(), 0);
+        assert_eq!(meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap().description, "Pizza");
+    }
+
+    #[test]
+    fn test_edit_meal() {
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        
+        // Add a meal first
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        
+        // Test editing a non-existent meal
+        assert!(edit_meal(&mut meal_plan, "Breakfast".to_string(), "Monday".to_string(), Some("Alice".to_string()), None, &Completions::default(), "tester", &mut Scripted::default()).is_err());
+        
+        // Test editing with invalid meal type
+        assert!(edit_meal(&mut meal_plan, "Brunch".to_string(), "Monday".to_string(), Some("Alice".to_string()), None, &Completions::default(), "tester", &mut Scripted::default()).is_err());
+        
+        // Test editing with invalid day
+        assert!(edit_meal(&mut meal_plan, "Dinner".to_string(), "Someday".to_string(), Some("Alice".to_string()), None, &Completions::default(), "tester", &mut Scripted::default()).is_err());
+        
+        // Test successful edit with provided values (no interactive prompts)
+        assert!(edit_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), 
+                         Some("Alice".to_string()), Some("Updated pasta dish".to_string()),
+                         &Completions::default(), "tester", &mut Scripted::default()).is_ok());
+        
+        // Verify the meal was updated
+        let updated_meal = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap();
+        assert_eq!(updated_meal.cook, "Alice");
+        assert_eq!(updated_meal.description, "Updated pasta dish");
+        assert_eq!(updated_meal.updated_by.as_deref(), Some("tester"));
+
+        // Values left out are asked for; an empty answer keeps the current one
+        let mut answers = Scripted::new(["", "Soup"]);
+        assert!(edit_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), None, None, &Completions::default(), "tester", &mut answers).is_ok());
+        let updated_meal = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap();
+        assert_eq!(updated_meal.cook, "Alice");
+        assert_eq!(updated_meal.description, "Soup");
+    }
+
+    #[test]
+    fn test_remove_meal() {
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        
+        // Test removing a non-existent meal
+        assert!(remove_meal(&mut meal_plan, "Breakfast".to_string(), "Monday".to_string(), &mut Scripted::default()).is_err());
+        
+        // Test removing with invalid meal type
+        assert!(remove_meal(&mut meal_plan, "Brunch".to_string(), "Monday".to_string(), &mut Scripted::default()).is_err());
+        
+        // Test removing with invalid day
+        assert!(remove_meal(&mut meal_plan, "Dinner".to_string(), "Someday".to_string(), &mut Scripted::default()).is_err());
+        
+        // Add a meal first
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        
+        // Removing the last meal asks first; answering no keeps it
+        assert!(remove_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), &mut Scripted::new(["n"])).is_err());
+        assert_eq!(meal_plan.meals().len(), 1);
+
+        // Test successful removal
+        assert!(remove_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), &mut Scripted::new(["y"])).is_ok());
+        
+        // Verify the meal was removed
+        assert!(meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).is_none());
+        
+        // Add multiple meals to test the last meal confirmation
+        add_meal(&mut meal_plan, "Breakfast".to_string(), "Monday".to_string(), "Alice".to_string(), "Cereal".to_string(), "tester").unwrap();
+        add_meal(&mut meal_plan, "Lunch".to_string(), "Monday".to_string(), "Bob".to_string(), "Sandwich".to_string(), "tester").unwrap();
+        
+        // Remove one meal, should succeed without confirmation (not the last meal)
+        assert!(remove_meal(&mut meal_plan, "Breakfast".to_string(), "Monday".to_string(), &mut Scripted::default()).is_ok());
+        
+        // Verify only one meal remains
+        assert_eq!(meal_plan.meals().len(), 1);
+        
+        // Test removing the last meal with confirmation
+        assert!(remove_meal(&mut meal_plan, "Lunch".to_string(), "Monday".to_string(), &mut Scripted::new(["y"])).is_ok());
+        
+        // Verify all meals are removed
+        assert_eq!(meal_plan.meals().len(), 0);
+
+        let args = Args::try_parse_from(["mealplan", "remove", "-m", "lunch", "-d", "monday", "--answers-file", "answers.txt"]).unwrap();
+        assert_eq!(args.answers_file, Some(PathBuf::from("answers.txt")));
+    }
+
+    #[test]
+    fn test_errors_are_categorized() {
+        use error::ErrorKind;
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+
+        let kind = |result: Result<u32, CliError>| result.unwrap_err().kind;
+        assert_eq!(kind(remove_meal(&mut meal_plan, "Brunch".to_string(), "Monday".to_string(), &mut Scripted::default())), ErrorKind::Usage);
+        assert_eq!(kind(remove_meal(&mut meal_plan, "Dinner".to_string(), "Someday".to_string(), &mut Scripted::default())), ErrorKind::Usage);
+        assert_eq!(kind(remove_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), &mut Scripted::default())), ErrorKind::NotFound);
+        assert_eq!(parse_period("soon").unwrap_err().kind, ErrorKind::Usage);
+        assert_eq!(
+            rollover(&meal_plan, &Archive::new(Path::new("unused")), meal_plan.week_start_date, false).unwrap_err().kind,
+            ErrorKind::Conflict
+        );
+    }
+
+    #[test]
+    fn test_import_meals() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let archive = Archive::new(temp_dir.path());
+        let mut archived = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        archived.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Al".to_string(), "Soup".to_string()));
+        archive.save_week(&archived).unwrap();
+
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 16).unwrap());
+        let row = |row: usize, day: import::Cell, meal_type: &str, description: &str| import::MappedRow {
+            row,
+            day,
+            meal_type: meal_type.to_string(),
+            description: description.to_string(),
+            cook: "Al".to_string(),
+        };
+        let date = |d: u32| import::Cell::Date(NaiveDate::from_ymd_opt(2023, 1, d).unwrap());
+        let rows = vec![
+            row(2, import::Cell::Text("tuesday".to_string()), "Dinner", "Tacos"),
+            row(3, date(3), "lunch", "Salad"),
+            row(4, date(2), "Dinner", "Stew"),
+            row(5, import::Cell::Text("2023-01-10".to_string()), "Dinner", "Chili"),
+            row(6, date(11), "brunch", "Waffles"),
+            row(7, date(30), "Dinner", "Pizza"),
+            row(8, import::Cell::Empty, "Dinner", "Pasta"),
+            row(9, date(11), "Dinner", ""),
+            row(10, date(2), "dinner", "Homemade soup"),
+        ];
+
+        let summary = import_meals(&mut meal_plan, &archive, rows, "tester", |_, _, _| duplicates::Strategy::Skip).unwrap();
+        assert_eq!(summary.into_current, 1);
+        assert_eq!(summary.into_archive, 2);
+        let weeks: Vec<NaiveDate> = summary.archived_weeks.keys().copied().collect();
+        assert_eq!(weeks, vec![NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(), NaiveDate::from_ymd_opt(2023, 1, 9).unwrap()]);
+        assert_eq!(summary.archived_weeks[&weeks[0]].meals().len(), 2);
+        assert_eq!(meal_plan.meals()[0].updated_by.as_deref(), Some("tester"));
+
+        let errors: Vec<usize> = summary.errors.iter().map(|(row, _)| *row).collect();
+        assert_eq!(errors, vec![4, 6, 7, 8, 9]);
+        assert!(summary.errors[0].1.contains("already has a meal: Soup"));
+        assert!(summary.errors[1].1.contains("invalid meal type 'brunch'"));
+        assert!(summary.errors[2].1.contains("after the current week"));
+        // A similar description is reported as a duplicate rather than a conflict
+        assert_eq!(summary.duplicates.len(), 1);
+        assert_eq!(summary.duplicates[0], (10, "already planned on 2023-01-02 as Soup".to_string()));
+    }
+
+    #[test]
+    fn test_import_from_app() {
+        assert!(Args::try_parse_from(["mealplan", "import", "-f", "plan.csv", "--from", "plan-to-eat"]).is_ok());
+        assert!(Args::try_parse_from(["mealplan", "import", "-f", "plan.csv", "--from", "anylist", "--map", "A=day"]).is_err());
+        assert!(Args::try_parse_from(["mealplan", "import", "-f", "plan.csv"]).is_err());
+
+        let recipe = |name: &str| Recipe { name: name.to_string(), tags: Vec::new(), ingredients: vec!["rice".to_string()], link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() };
+        let mut book = RecipeBook::default();
+        book.add(Recipe { ingredients: vec!["beans".to_string()], ..recipe("Chili") });
+        assert_eq!(import_recipes(&mut book, vec![recipe("chili"), recipe("Risotto")]), (1, 1));
+        assert_eq!(book.find("Chili").unwrap().ingredients, vec!["beans".to_string()]);
+        assert!(book.find("Risotto").is_some());
+    }
+
+    #[test]
+    fn test_recent_weeks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let archive = Archive::new(temp_dir.path());
+        let current = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 16).unwrap());
+        for week in [2, 9] {
+            let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, week).unwrap());
+            plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Al".to_string(), "Soup".to_string()));
+            archive.save_week(&plan).unwrap();
+        }
+
+        let plans = recent_weeks(&archive, &current, 2).unwrap();
+        let weeks: Vec<NaiveDate> = plans.iter().map(|p| p.week_start_date).collect();
+        assert_eq!(weeks, vec![NaiveDate::from_ymd_opt(2023, 1, 9).unwrap(), current.week_start_date]);
+        assert_eq!(recent_weeks(&archive, &current, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_apply_ops() {
+        let mut config = Config::new();
+        cooks_add(&mut config, "Alice", None, Vec::new());
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Soup".to_string()));
+
+        let ops = bulk::parse_ops(r#"{"op": "add", "meal_type": "dinner", "day": "monday", "cook": "Alice", "description": "Tacos"}
+{"op": "edit", "meal_type": "lunch", "day": "monday", "description": "Salad"}
+{"op": "add", "meal_type": "dinner", "day": "tuesday", "cook": "auto", "description": "Chili"}
+{"op": "remove", "meal_type": "dinner", "day": "monday"}"#).unwrap();
+        let (updated, summary) = apply_ops(&config, &meal_plan, &RecipeBook::default(), ops, "script").unwrap();
+        assert_eq!((summary.added, summary.edited, summary.removed), (2, 1, 1));
+        assert_eq!(updated.meals().len(), 2);
+        assert_eq!(updated.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Mon)).unwrap().description, "Salad");
+        assert_eq!(updated.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Tue)).unwrap().updated_by.as_deref(), Some("script"));
+        assert_eq!(updated.trash.len(), 1);
+        assert_eq!(format_bulk_summary(&summary, false), "Applied 4 operations: 2 added, 1 edited, 1 removed.\n");
+
+        // One failing operation stops the batch and leaves the plan alone
+        let ops = bulk::parse_ops(r#"{"op": "add", "meal_type": "dinner", "day": "monday", "cook": "Alice", "description": "Tacos"}
+{"op": "add", "meal_type": "lunch", "day": "monday", "cook": "Alice", "description": "Stew"}"#).unwrap();
+        let error = apply_ops(&config, &meal_plan, &RecipeBook::default(), ops, "script").unwrap_err();
+        assert_eq!(error.kind, error::ErrorKind::Conflict);
+        assert!(error.message.starts_with("Line 2: Lunch on Mon already has a meal: Soup."));
+        assert_eq!(meal_plan.meals().len(), 1);
+
+        let ops = bulk::parse_ops(r#"{"op": "add", "meal_type": "lunch", "day": "monday", "cook": "Bob", "description": "Stew", "replace": true}"#).unwrap();
+        assert!(apply_ops(&config, &meal_plan, &RecipeBook::default(), ops, "script").unwrap_err().message.starts_with("Line 1: "));
+    }
+
+    #[test]
+    fn test_add_command_several_days() {
+        let mut config = Config::new();
+        cooks_add(&mut config, "Alice", None, Vec::new());
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+
+        add_command(&config, &mut meal_plan, &RecipeBook::default(), "Oatmeal".to_string(), "breakfast".to_string(), "mon, wed,weekend".to_string(), "Alice".to_string(), MealExtras::default(), &mut Scripted::default()).unwrap();
+        let days: Vec<&Day> = meal_plan.meals().iter().map(|m| &m.day).collect();
+        assert_eq!(days, vec![
+            &Day::Weekday(Weekday::Mon),
+            &Day::Weekday(Weekday::Wed),
+            &Day::Weekday(Weekday::Sat),
+            &Day::Weekday(Weekday::Sun),
+        ]);
+
+        // Nothing is added when one of the days is invalid
+        let error = add_command(&config, &mut meal_plan, &RecipeBook::default(), "Soup".to_string(), "lunch".to_string(), "tue,someday".to_string(), "Alice".to_string(), MealExtras::default(), &mut Scripted::default()).unwrap_err();
+        assert_eq!(error.kind, error::ErrorKind::Usage);
+        assert_eq!(meal_plan.meals().len(), 4);
+    }
+
+    #[test]
+    fn test_add_days_from_range() {
+        let meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2025, 7, 28).unwrap());
+        let range = |from: &str, to: &str| add_days(&meal_plan, None, Some(from.to_string()), Some(to.to_string()));
+        assert_eq!(range("2025-08-01", "2025-08-03").unwrap(), "2025-08-01,2025-08-02,2025-08-03");
+        assert_eq!(range("2025-07-28", "2025-07-28").unwrap(), "2025-07-28");
+        // The range has to run forwards and stay in the plan's week
+        assert!(range("2025-08-02", "2025-08-01").is_err());
+        assert!(range("2025-08-01", "2025-08-04").is_err());
+        assert!(range("2025-08-01", "August 3").is_err());
+        assert_eq!(add_days(&meal_plan, Some("mon,tue".to_string()), None, None).unwrap(), "mon,tue");
+
+        let args = Args::parse_from(["mealplan", "add", "Camping food", "-t", "dinner", "--from", "2025-08-01", "--to", "2025-08-03", "-c", "Group"]);
+        assert!(matches!(args.command, Some(Commands::Add { day: None, from: Some(_), to: Some(_), .. })));
+        assert!(Args::try_parse_from(["mealplan", "add", "Soup", "-t", "dinner", "--from", "2025-08-01", "-c", "Group"]).is_err());
+        assert!(Args::try_parse_from(["mealplan", "add", "Soup", "-t", "dinner", "-d", "mon", "--from", "2025-08-01", "--to", "2025-08-02", "-c", "Group"]).is_err());
+    }
+
+    #[test]
+    fn test_remove_matching() {
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Tacos".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Snack, Day::Weekday(Weekday::Mon), "Bob".to_string(), "Fruit".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Snack, Day::Date(NaiveDate::from_ymd_opt(2023, 1, 4).unwrap()), "alice".to_string(), "Nuts".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Wed), "Bob".to_string(), "Soup".to_string()));
+        let selection = |meal_type: Option<&str>, day: Option<&str>, cook: Option<&str>| MealSelection {
+            meal_type: meal_type.map(str::to_string),
+            day: day.map(str::to_string),
+            cook: cook.map(str::to_string),
+            filter: None,
+            week: None,
+        };
+
+        assert_eq!(remove_matching(&mut meal_plan, &selection(None, None, Some("Carol")), true, &mut Scripted::default()).unwrap(), 0);
+        // The cook matches regardless of case, and a day matches meals stored by date
+        assert_eq!(remove_matching(&mut meal_plan, &selection(Some("snack"), Some("wed"), Some("Alice")), true, &mut Scripted::default()).unwrap(), 1);
+        assert_eq!(remove_matching(&mut meal_plan, &selection(None, None, Some("Bob")), true, &mut Scripted::default()).unwrap(), 2);
+        let left: Vec<&str> = meal_plan.meals().iter().map(|m| m.description.as_str()).collect();
+        assert_eq!(left, vec!["Tacos"]);
+        assert_eq!(meal_plan.trash.len(), 3);
+
+        let by_filter = MealSelection { filter: Some("cook == Alice".to_string()), ..selection(None, None, None) };
+        assert_eq!(remove_matching(&mut meal_plan, &by_filter, true, &mut Scripted::default()).unwrap(), 1);
+        assert!(meal_plan.meals().is_empty());
+
+        // Next week's meals are those planned ahead by date
+        meal_plan.add_meal(Meal::new(MealType::Snack, Day::Weekday(Weekday::Tue), "Bob".to_string(), "Fruit".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Snack, Day::Date(NaiveDate::from_ymd_opt(2023, 1, 10).unwrap()), "Bob".to_string(), "Nuts".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Date(NaiveDate::from_ymd_opt(2023, 1, 11).unwrap()), "Bob".to_string(), "Soup".to_string()));
+        let next_week = |day: Option<&str>| MealSelection { week: Some("next".to_string()), ..selection(Some("snack"), day, None) };
+        assert_eq!(remove_matching(&mut meal_plan, &next_week(Some("wed")), true, &mut Scripted::default()).unwrap(), 0);
+        assert_eq!(remove_matching(&mut meal_plan, &next_week(Some("tue")), true, &mut Scripted::default()).unwrap(), 1);
+        let left: Vec<&str> = meal_plan.meals().iter().map(|m| m.description.as_str()).collect();
+        assert_eq!(left, vec!["Fruit", "Soup"]);
+        let last_week = MealSelection { week: Some("last".to_string()), ..selection(None, None, None) };
+        let err = remove_matching(&mut meal_plan, &last_week, true, &mut Scripted::default()).unwrap_err();
+        assert_eq!(err.kind, error::ErrorKind::Conflict);
+
+        assert!(Args::try_parse_from(["mealplan", "remove", "--cook", "Alice"]).is_err());
+        assert!(Args::try_parse_from(["mealplan", "remove", "--week", "next"]).is_err());
+        assert!(Args::try_parse_from(["mealplan", "remove", "--all", "--cook", "Alice", "--yes"]).is_ok());
+    }
+
+    #[test]
+    fn test_clone_meal() {
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let mut tacos = Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Tacos".to_string());
+        tacos.tags = vec!["mexican".to_string()];
+        tacos.recipe = Some("https://example.com/tacos".to_string());
+        tacos.cooked = true;
+        meal_plan.add_meal(tacos);
+
+        clone_meal(&mut meal_plan, "dinner", "monday", "wed, 2023-01-06", "Bob", &mut Scripted::default()).unwrap();
+        assert_eq!(meal_plan.meals().len(), 3);
+        let copy = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Wed)).unwrap();
+        assert_eq!((copy.description.as_str(), copy.cook.as_str()), ("Tacos", "Alice"));
+        assert_eq!(copy.tags, vec!["mexican"]);
+        assert_eq!(copy.recipe.as_deref(), Some("https://example.com/tacos"));
+        assert!(!copy.cooked);
+        assert_eq!(copy.updated_by.as_deref(), Some("Bob"));
+        assert!(meal_plan.find_meal(&MealType::Dinner, &Day::Date(NaiveDate::from_ymd_opt(2023, 1, 6).unwrap())).is_some());
+
+        assert_eq!(clone_meal(&mut meal_plan, "lunch", "monday", "tue", "Bob", &mut Scripted::default()).unwrap_err().kind, error::ErrorKind::NotFound);
+        assert_eq!(clone_meal(&mut meal_plan, "dinner", "monday", "2023-01-02", "Bob", &mut Scripted::default()).unwrap_err().kind, error::ErrorKind::Usage);
+        assert_eq!(clone_meal(&mut meal_plan, "dinner", "monday", "someday", "Bob", &mut Scripted::default()).unwrap_err().kind, error::ErrorKind::Usage);
+    }
+
+    #[test]
+    fn test_rotations() {
+        let mut config = Config::new();
+        cooks_add(&mut config, "Alice", None, Vec::new());
+        let mut book = RecipeBook::default();
+        for name in ["Lasagna", "Chili"] {
+            book.add(Recipe { name: name.to_string(), tags: vec!["staple".to_string()], ingredients: Vec::new(), link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() });
+        }
+        let recipes = ["lasagna".to_string(), "CHILI".to_string()];
+        let rotation = create_rotation(&config, &book, " Staples ".to_string(), &recipes, "dinner", "sundays", Some("Alice".to_string())).unwrap();
+        assert_eq!(rotation.name, "Staples");
+        assert_eq!(rotation.recipes, vec!["Lasagna", "Chili"]);
+        assert_eq!(rotation.day, Weekday::Sun);
+        let missing = ["Lasagna".to_string(), "Sushi".to_string()];
+        assert_eq!(create_rotation(&config, &book, "X".to_string(), &missing, "dinner", "sun", None).unwrap_err().kind, error::ErrorKind::NotFound);
+        assert!(create_rotation(&config, &book, "X".to_string(), &recipes, "dinner", "sun", Some("Zed".to_string())).is_err());
+
+        let mut rotations = Rotations::default();
+        rotations.add(rotation);
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        apply_rotations(&config, &mut meal_plan, &mut rotations, &book, None, None, &mut Scripted::default()).unwrap();
+        // Applying again in the same week changes nothing
+        apply_rotations(&config, &mut meal_plan, &mut rotations, &book, Some("staples"), None, &mut Scripted::default()).unwrap();
+        assert_eq!(meal_plan.meals().len(), 1);
+        let meal = &meal_plan.meals()[0];
+        assert_eq!((meal.description.as_str(), meal.cook.as_str()), ("Lasagna", "Alice"));
+        assert_eq!(meal.day, Day::Weekday(Weekday::Sun));
+        assert_eq!(meal.tags, vec!["staple"]);
+
+        // The next week gets the next recipe
+        let mut next_week = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 9).unwrap());
+        apply_rotations(&config, &mut next_week, &mut rotations, &book, Some("Staples"), None, &mut Scripted::default()).unwrap();
+        assert_eq!(next_week.meals()[0].description, "Chili");
+        assert_eq!(rotations.rotations[0].upcoming(), Some("Lasagna"));
+        assert!(format_rotations(&rotations).contains("Staples: Sunday Dinner, cooked by Alice\n  Lasagna -> Chili\n"));
+        assert!(apply_rotations(&config, &mut next_week, &mut rotations, &book, Some("Pizza"), None, &mut Scripted::default()).is_err());
+    }
+
+    #[test]
+    fn test_generate_meals() {
+        let mut config = Config::new();
+        cooks_add(&mut config, "Alice", None, Vec::new());
+        cooks_add(&mut config, "Bob", None, Vec::new());
+        config.plan_rules = serde_json::from_str(r#"[{"rule": "on_day", "tag": "fish", "day": "Wed"}, {"rule": "max_meals", "cook": "Alice", "count": 1}]"#).unwrap();
+        let mut book = RecipeBook::default();
+        for (name, tag) in [("Salmon", "fish"), ("Chili", "beans"), ("Tacos", "mexican"), ("Soup", "quick")] {
+            book.add(Recipe { name: name.to_string(), tags: vec![tag.to_string()], ingredients: Vec::new(), link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() });
+        }
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Bob".to_string(), "Tacos".to_string()));
+
+        assert_eq!(generate_meals(&config, &mut meal_plan, &book, &PriceHistory::default(), "dinner", Some("mon,tue,wed")).unwrap(), 2);
+        let wednesday = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Wed)).unwrap();
+        assert_eq!(wednesday.description, "Salmon");
+        assert_eq!(wednesday.recipe.as_deref(), Some("Salmon"));
+        assert_eq!(meal_plan.meals().iter().filter(|m| m.cook == "Alice").count(), 1);
+
+        // Only Soup is left for the rest of the week
+        assert_eq!(generate_meals(&config, &mut meal_plan, &book, &PriceHistory::default(), "dinner", None).unwrap_err().kind, error::ErrorKind::Conflict);
+        config.find_cook_mut("Bob").unwrap().unavailable_days = vec![Weekday::Fri];
+        let error = generate_meals(&config, &mut meal_plan, &book, &PriceHistory::default(), "dinner", Some("fri")).unwrap_err();
+        assert_eq!(error.kind, error::ErrorKind::Conflict);
+        assert!(error.message.contains("Alice cooks at most 1 meal(s)"), "{}", error.message);
+    }
+
+    #[test]
+    fn test_generate_slots_leftovers() {
+        let mut config = Config::new();
+        cooks_add(&mut config, "Alice", None, Vec::new());
+        config.household_size = Some(3);
+        let mut book = RecipeBook::default();
+        for (name, servings) in [("Chili", Some(6)), ("Lasagna", Some(8))] {
+            book.add(Recipe { name: name.to_string(), tags: Vec::new(), ingredients: Vec::new(), link: None, servings, photo: None, steps: Vec::new(), equipment: Vec::new() });
+        }
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Alice".to_string(), "Salad".to_string()));
+
+        // Monday's leftovers skip Tuesday, which has a lunch, and Sunday's would fall in the
+        // next week
+        assert_eq!(generate_meals(&config, &mut meal_plan, &book, &PriceHistory::default(), "dinner", Some("mon,sun")).unwrap(), 3);
+        let monday = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap().description.clone();
+        let wednesday = meal_plan.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Wed)).unwrap();
+        assert_eq!(wednesday.description, format!("Leftovers: {}", monday));
+        assert_eq!(wednesday.cook, "Alice");
+        assert_eq!(meal_plan.meals().len(), 4);
+
+        // Without a household size nothing is left over
+        config.household_size = None;
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        assert_eq!(generate_meals(&config, &mut meal_plan, &book, &PriceHistory::default(), "dinner", Some("mon,tue")).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_day_templates() {
+        let mut config = Config::new();
+        cooks_add(&mut config, "Alice", None, Vec::new());
+        config.default_cooks.insert("breakfast".to_string(), "Alice".to_string());
+        config.aliases.insert("pizza".to_string(), MealAlias { description: "Homemade Pizza".to_string(), tags: vec!["treat".to_string()], recipe: None });
+        config.day_templates = serde_json::from_str(r#"{"Saturday": {"breakfast": "Pancakes"}, "fri": {"dinner": "pizza", "brunch": "Waffles"}}"#).unwrap();
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let week: Vec<NaiveDate> = (0..7).map(|n| meal_plan.week_start_date + Duration::days(n)).collect();
+
+        assert_eq!(apply_day_templates(&config, &mut meal_plan, &week, None), 2);
+        let saturday = meal_plan.find_meal(&MealType::Breakfast, &Day::Weekday(Weekday::Sat)).unwrap();
+        assert_eq!((saturday.description.as_str(), saturday.cook.as_str()), ("Pancakes", "Alice"));
+        let friday = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Fri)).unwrap();
+        assert_eq!((friday.description.as_str(), friday.cook.as_str()), ("Homemade Pizza", ""));
+        assert_eq!(friday.tags, vec!["treat".to_string()]);
+
+        // Meals already planned, such as templated ones that were edited, are kept
+        meal_plan.change_meal(&MealType::Breakfast, &Day::Weekday(Weekday::Sat), |meal| meal.description = "Waffles".to_string());
+        assert_eq!(apply_day_templates(&config, &mut meal_plan, &week, None), 0);
+        assert_eq!(meal_plan.find_meal(&MealType::Breakfast, &Day::Weekday(Weekday::Sat)).unwrap().description, "Waffles");
+
+        // Generating a meal type plans its templates before the rest of the days
+        let mut book = RecipeBook::default();
+        book.add(Recipe { name: "Chili".to_string(), tags: Vec::new(), ingredients: Vec::new(), link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() });
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        assert_eq!(generate_meals(&config, &mut meal_plan, &book, &PriceHistory::default(), "dinner", Some("thu,fri")).unwrap(), 2);
+        assert_eq!(meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Fri)).unwrap().description, "Homemade Pizza");
+        assert_eq!(meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Thu)).unwrap().description, "Chili");
+        assert!(meal_plan.find_meal(&MealType::Breakfast, &Day::Weekday(Weekday::Sat)).is_none());
+    }
+
+    #[test]
+    fn test_packed_meals() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::new();
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        add_command(&config, &mut meal_plan, &RecipeBook::default(), "Sandwich".to_string(), "lunch".to_string(), "mon,tue".to_string(), "Sam".to_string(), MealExtras { packed: true, place: None, photo: None }, &mut Scripted::default()).unwrap();
+        assert!(meal_plan.meals().iter().all(|m| m.packed));
+
+        // Editing keeps the flag unless it's given
+        let monday = (MealType::Lunch, Day::Weekday(Weekday::Mon));
+        edit_command(&config, &mut meal_plan, dir.path(), Some("Wrap".to_string()), "lunch".to_string(), "mon".to_string(), Some("Sam".to_string()), None, None, &mut Scripted::default()).unwrap();
+        assert!(meal_plan.find_meal(&monday.0, &monday.1).unwrap().packed);
+        edit_command(&config, &mut meal_plan, dir.path(), Some("Wrap".to_string()), "lunch".to_string(), "mon".to_string(), Some("Sam".to_string()), Some(false), None, &mut Scripted::default()).unwrap();
+        assert!(!meal_plan.find_meal(&monday.0, &monday.1).unwrap().packed);
+        assert!(Args::try_parse_from(["mealplan", "edit", "-t", "lunch", "-d", "mon", "--packed"]).is_ok());
+
+        let today = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        assert_eq!(packing_date(&meal_plan, "tomorrow", today).unwrap(), NaiveDate::from_ymd_opt(2023, 1, 3).unwrap());
+        assert_eq!(packing_date(&meal_plan, "Today", today).unwrap(), today);
+        assert_eq!(packing_date(&meal_plan, "fri", today).unwrap(), NaiveDate::from_ymd_opt(2023, 1, 6).unwrap());
+        assert!(packing_date(&meal_plan, "someday", today).is_err());
+    }
+
+    #[test]
+    fn test_recipe_to_cook() {
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Sam".to_string(), "Chili".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), "Sam".to_string(), "Tacos".to_string()));
+        let mut book = RecipeBook::default();
+        let steps = vec!["Brown the beef".to_string(), "Simmer 20 minutes".to_string()];
+        book.add(Recipe { name: "chili".to_string(), tags: Vec::new(), ingredients: Vec::new(), link: None, servings: None, photo: None, steps, equipment: Vec::new() });
+        book.add(Recipe { name: "Tacos".to_string(), tags: Vec::new(), ingredients: Vec::new(), link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() });
+
+        let monday = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        assert_eq!(recipe_to_cook(&meal_plan, &book, &MealType::Dinner, monday).unwrap().name, "chili");
+        let error = recipe_to_cook(&meal_plan, &book, &MealType::Dinner, monday + Duration::days(1)).unwrap_err();
+        assert!(error.message.contains("has no steps"));
+        let error = recipe_to_cook(&meal_plan, &book, &MealType::Lunch, monday).unwrap_err();
+        assert_eq!(error.message, "No lunch is planned for Monday, Jan 2.");
+        assert!(Args::try_parse_from(["mealplan", "cook", "--day", "today", "--meal-type", "dinner"]).is_ok());
+    }
+
+    #[test]
+    fn test_today_speech() {
+        let monday = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut meal_plan = MealPlan::new(monday);
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Mon), String::new(), "Leftovers: Soup".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "chili".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Breakfast, Day::Weekday(Weekday::Mon), "Bob".to_string(), "oatmeal".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Wed), "Bob".to_string(), "sandwiches".to_string()));
+        let mut takeout = Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), String::new(), "pad thai".to_string());
+        takeout.place = Some("Thai Palace".to_string());
+        meal_plan.add_meal(takeout);
+
+        assert_eq!(format_speech(&meal_plan, monday, monday), "Tonight Alice is cooking chili; breakfast is oatmeal; lunch is leftover Soup.");
+        assert_eq!(format_speech(&meal_plan, monday + Duration::days(1), monday), "Tomorrow night it's pad thai from Thai Palace.");
+        assert_eq!(format_speech(&meal_plan, monday + Duration::days(2), monday), "On Wednesday, lunch is sandwiches.");
+        assert_eq!(format_speech(&meal_plan, monday + Duration::days(3), monday), "Nothing is planned on Thursday.");
+
+        let dir = tempfile::tempdir().unwrap();
+        save_meal_plan(&meal_plan, dir.path(), 0).unwrap();
+        let config = Config::new();
+        let request = |line: &str| serve_request(&config, dir.path(), &server::Request::parse(line).unwrap(), monday, false);
+        assert_eq!(request("GET /today/speech HTTP/1.1").body, "Tonight Alice is cooking chili; breakfast is oatmeal; lunch is leftover Soup.");
+        assert_eq!(request("GET /today/speech?day=tomorrow HTTP/1.1").body, "Tomorrow night it's pad thai from Thai Palace.");
+        assert_eq!(request("GET /today/speech?day=someday HTTP/1.1").status, 400);
+        assert_eq!(request("GET /tomorrow HTTP/1.1").status, 404);
+    }
+
+    #[test]
+    fn test_serve_web_app() {
+        let monday = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        save_meal_plan(&MealPlan::new(monday), dir.path(), 0).unwrap();
+        let config = Config::new();
+        let post = |body: &str, content_type: &str, read_only: bool| {
+            let mut request = server::Request::parse("POST /api/ops HTTP/1.1").unwrap();
+            request.headers.insert("content-type".to_string(), content_type.to_string());
+            request.body = body.to_string();
+            serve_request(&config, dir.path(), &request, monday, read_only)
+        };
+
+        let page = serve_request(&config, dir.path(), &server::Request::parse("GET / HTTP/1.1").unwrap(), monday, false);
+        assert_eq!((page.status, page.content_type), (200, "text/html; charset=utf-8"));
+
+        let add = r#"{"op": "add", "meal_type": "Dinner", "day": "2023-01-03", "cook": "Sam", "description": "Tacos", "replace": true}"#;
+        assert_eq!(post(add, "text/plain", false).status, 415);
+        assert_eq!(post(add, webapp::OPS_CONTENT_TYPE, true).status, 403);
+        let response = post(add, "application/x-ndjson; charset=utf-8", false);
+        assert_eq!(response.status, 200, "{}", response.body);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&response.body).unwrap()["added"], 1);
+
+        let meal_plan = load_meal_plan(dir.path()).unwrap();
+        let meal = meal_plan.meals().iter().find(|meal| meal_plan.date_of(&meal.day) == monday + Duration::days(1)).unwrap();
+        assert_eq!((meal.description.as_str(), meal.updated_by.as_deref()), ("Tacos", Some(WEB_APP_USER)));
+        let plan = serve_request(&config, dir.path(), &server::Request::parse("GET /api/plan HTTP/1.1").unwrap(), monday, false);
+        assert!(plan.body.contains("\"description\":\"Tacos\""));
+
+        // Edits the plan can't take are turned down, so the app drops them
+        let remove = r#"{"op": "remove", "meal_type": "Lunch", "day": "2023-01-03"}"#;
+        assert_eq!(post(remove, webapp::OPS_CONTENT_TYPE, false).status, 404);
+        assert_eq!(post("not json", webapp::OPS_CONTENT_TYPE, false).status, 400);
+    }
+
+    #[test]
+    fn test_serve_ops_edit_keeps_meal() {
+        let monday = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let mut meal_plan = MealPlan::new(monday);
+        let mut meal = Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), "Alice".to_string(), "Tacos for {{cook}}".to_string());
+        meal.tags = vec!["mexican".to_string()];
+        meal.recipe = Some("https://example.com/tacos".to_string());
+        meal.photo = Some("assets/tacos.png".to_string());
+        meal.packed = true;
+        meal.place = Some("Taqueria".to_string());
+        meal.rating = Some(5);
+        meal.cooked = true;
+        meal_plan.add_meal(meal);
+        save_meal_plan(&meal_plan, dir.path(), 0).unwrap();
+        let before = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Tue)).unwrap().clone();
+
+        // What the web app sends when the form is saved with only the cook changed
+        let edit = r#"{"op": "edit", "meal_type": "Dinner", "day": "Tue", "cook": "Sam", "description": "Tacos for {{cook}}"}"#;
+        let summary = serve_ops(&Config::new(), dir.path(), edit).unwrap();
+        assert_eq!((summary.added, summary.edited), (0, 1));
+
+        let meal_plan = load_meal_plan(dir.path()).unwrap();
+        let after = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Tue)).unwrap();
+        assert_eq!((after.cook.as_str(), after.description.as_str()), ("Sam", "Tacos for {{cook}}"));
+        assert_eq!(after.tags, before.tags);
+        assert_eq!(after.recipe, before.recipe);
+        assert_eq!(after.photo, before.photo);
+        assert_eq!((after.packed, after.cooked, after.rating), (true, true, Some(5)));
+        assert_eq!(after.place, before.place);
+        assert_eq!(after.created_at, before.created_at);
+        assert_eq!(after.updated_by.as_deref(), Some(WEB_APP_USER));
+        assert!(after.clock.dominates(&before.clock) && after.clock != before.clock);
+        assert!(meal_plan.trash.is_empty());
+
+        // The form gets the description as stored, and the page the rendered text
+        let json = webapp::plan_json(&meal_plan);
+        assert_eq!((json["meals"][0]["description"].as_str(), json["meals"][0]["text"].as_str()), (Some("Tacos for {{cook}}"), Some("Tacos for Sam")));
+    }
+
+    #[test]
+    fn test_meal_photos() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = dir.path().join("storage");
+        std::fs::create_dir(&storage).unwrap();
+        let photo = dir.path().join("tacos.png");
+        std::fs::write(&photo, b"tacos").unwrap();
+        let config = Config::new();
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+
+        let (description, extras) = meal_extras(&storage, Some("Tacos".to_string()), false, None, Some(photo.display().to_string())).unwrap();
+        add_command(&config, &mut meal_plan, &RecipeBook::default(), description, "dinner".to_string(), "mon".to_string(), "Sam".to_string(), extras, &mut Scripted::default()).unwrap();
+        let monday = (MealType::Dinner, Day::Weekday(Weekday::Mon));
+        assert_eq!(meal_plan.find_meal(&monday.0, &monday.1).unwrap().photo.as_deref(), Some("assets/tacos.png"));
+        assert!(storage.join("assets/tacos.png").exists());
+
+        edit_command(&config, &mut meal_plan, &storage, Some("Tacos".to_string()), "dinner".to_string(), "mon".to_string(), Some("Sam".to_string()), None, Some(String::new()), &mut Scripted::default()).unwrap();
+        assert!(meal_plan.find_meal(&monday.0, &monday.1).unwrap().photo.is_none());
+        let error = meal_extras(&storage, Some("Tacos".to_string()), false, None, Some("notes.txt".to_string())).unwrap_err();
+        assert_eq!(error.kind, error::ErrorKind::Usage);
+    }
+
+    #[test]
+    fn test_add_eat_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut places = Places::default();
+        places.add(Place { name: "Thai Palace".to_string(), cuisine: Some("thai".to_string()), contact: None, typical_cost: Some(35.0) });
+        places.save_to_json(dir.path().join("places.json")).unwrap();
+        let mut config = Config::new();
+        cooks_add(&mut config, "Alice", None, Vec::new());
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+
+        let (description, extras) = meal_extras(dir.path(), None, false, Some("thai palace".to_string()), None).unwrap();
+        assert_eq!(description, "Thai Palace");
+        add_command(&config, &mut meal_plan, &RecipeBook::default(), description, "dinner".to_string(), "fri".to_string(), String::new(), extras, &mut Scripted::default()).unwrap();
+        let meal = &meal_plan.meals()[0];
+        assert_eq!((meal.cook.as_str(), meal.place.as_deref()), ("", Some("Thai Palace")));
+
+        assert_eq!(meal_extras(dir.path(), None, false, Some("Burger Hut".to_string()), None).unwrap_err().kind, error::ErrorKind::NotFound);
+        // Without a place the cook is still checked against the roster
+        assert!(add_command(&config, &mut meal_plan, &RecipeBook::default(), "Soup".to_string(), "lunch".to_string(), "fri".to_string(), String::new(), MealExtras::default(), &mut Scripted::default()).is_err());
+        assert!(Args::try_parse_from(["mealplan", "add", "-t", "dinner", "-d", "fri", "--eat-out", "Thai Palace"]).is_ok());
+        assert!(Args::try_parse_from(["mealplan", "add", "-t", "dinner", "-d", "fri"]).is_err());
+
+        assert_eq!(format_places(&places), "Thai Palace [thai], about 35.00\n");
+        let suggestions = places.not_ordered_since(std::slice::from_ref(&meal_plan), NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        assert_eq!(format_place_suggestions(&suggestions, 4), "Every place has been ordered from in the last 4 week(s).\n");
+    }
+
+    #[test]
+    fn test_shell_command_keeps_changes_in_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::new();
+        cooks_add(&mut config, "Alice", None, Vec::new());
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let shell_args = |line: &str| Args::try_parse_from(std::iter::once("mealplan".to_string()).chain(shell::split_words(line).unwrap())).unwrap();
+
+        let changed = shell_command(&config, &mut meal_plan, dir.path(), shell_args("add \"Chicken curry\" -t dinner -d monday -c Alice"), false, &mut Scripted::default()).unwrap();
+        assert!(changed);
+        assert_eq!(meal_plan.meals()[0].description, "Chicken curry");
+        assert!(!dir.path().join("meal_plan.json").exists());
+        assert_eq!(format_plan_listing(&meal_plan), "Mon 2023-01-02 Dinner: Chicken curry (Alice)\n");
+
+        // Other commands need the plan saved first
+        let error = shell_command(&config, &mut meal_plan, dir.path(), shell_args("stats variety"), true, &mut Scripted::default()).unwrap_err();
+        assert_eq!(error.kind, error::ErrorKind::Usage);
+        assert!(shell_command(&config, &mut meal_plan, dir.path(), shell_args("shell"), false, &mut Scripted::default()).is_err());
+    }
+
+    #[test]
+    fn test_filtered_history() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let archive = Archive::new(temp_dir.path());
+        let mut archived = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 9).unwrap());
+        archived.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Soup".to_string()));
+        archived.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Mon), "Bob".to_string(), "Salad".to_string()));
+        archive.save_week(&archived).unwrap();
+        let mut current = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 16).unwrap());
+        current.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), "alice".to_string(), "Chili".to_string()));
+
+        let history = filtered_history(&archive, &current, archived.week_start_date, Some("cook == Alice")).unwrap();
+        let dishes: Vec<&str> = history.entries.iter().map(|e| e.description.as_str()).collect();
+        assert_eq!(dishes, vec!["Soup", "Chili"]);
+        assert_eq!(filtered_history(&archive, &current, archived.week_start_date, None).unwrap().entries.len(), 3);
+
+        let error = filtered_plan(&current, Some("cook = Alice")).unwrap_err();
+        assert_eq!(error.kind, error::ErrorKind::Usage);
+        assert!(error.message.starts_with("Invalid filter:"));
+    }
+
+    #[test]
+    fn test_report_grocery_lists() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let archive = Archive::new(temp_dir.path());
+        let january = NaiveDate::from_ymd_opt(2025, 1, 27).unwrap();
+        let february = NaiveDate::from_ymd_opt(2025, 2, 3).unwrap();
+
+        let mut archived = MealPlan::new(january);
+        archived.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Al".to_string(), "Soup".to_string()));
+        archive.save_week(&archived).unwrap();
+        let mut groceries = GroceryList::new(january);
+        groceries.add_item("Broth", None);
+        archive.save_groceries(&groceries).unwrap();
+
+        let current = MealPlan::new(february);
+        GroceryList::new(february).save_to_json(temp_dir.path().join("grocery_list.json")).unwrap();
+
+        let lists = report_grocery_lists(&archive, &current, temp_dir.path(), ReportPeriod::year(2025).unwrap()).unwrap();
+        assert_eq!(lists.iter().map(|l| l.week_start_date).collect::<Vec<_>>(), vec![january, february]);
+        let lists = report_grocery_lists(&archive, &current, temp_dir.path(), ReportPeriod::parse_month("2025-02").unwrap()).unwrap();
+        assert_eq!(lists.len(), 1);
+
+        assert!(Args::try_parse_from(["mealplan", "report"]).is_err());
+        assert!(Args::try_parse_from(["mealplan", "report", "--month", "2025-02", "--year", "2025"]).is_err());
+    }
+
+    #[test]
+    fn test_meal_aliases() {
+        let mut config = Config::new();
+        alias_add(&mut config, "Tacos", "Beef tacos with salsa verde".to_string(), vec!["mexican".to_string(), " ".to_string()], Some("tacos".to_string())).unwrap();
+        assert!(alias_add(&mut config, "empty", " ".to_string(), Vec::new(), None).is_err());
+        assert_eq!(format_aliases(&config), "tacos: Beef tacos with salsa verde [mexican] (recipe: tacos)\n");
+
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        let alias = config.find_alias("TACOS ");
+        add_meal_as(&mut meal_plan, "dinner".to_string(), "tuesday".to_string(), "Sam".to_string(), "TACOS ".to_string(), alias, "tester", &mut Scripted::default()).unwrap();
+        let meal = &meal_plan.meals()[0];
+        assert_eq!(meal.description, "Beef tacos with salsa verde");
+        assert_eq!(meal.tags, vec!["mexican"]);
+        assert_eq!(meal.recipe.as_deref(), Some("tacos"));
+
+        // Editing the cook keeps the dish's details; changing the dish drops them
+        edit_meal(&mut meal_plan, "dinner".to_string(), "tuesday".to_string(), Some("Al".to_string()), Some("Beef tacos with salsa verde".to_string()), &Completions::default(), "tester", &mut Scripted::default()).unwrap();
+        assert_eq!(meal_plan.meals()[0].recipe.as_deref(), Some("tacos"));
+        edit_meal(&mut meal_plan, "dinner".to_string(), "tuesday".to_string(), Some("Al".to_string()), Some("Soup".to_string()), &Completions::default(), "tester", &mut Scripted::default()).unwrap();
+        assert!(meal_plan.meals()[0].tags.is_empty());
+    }
+
+    #[test]
+    fn test_mark_cooked() {
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+
+        mark_cooked(&mut meal_plan, "dinner", "monday", Some(4)).unwrap();
+        let meal = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap();
+        assert!(meal.cooked);
+        assert_eq!(meal.rating, Some(4));
+
+        // Marking it again without a rating keeps the rating
+        mark_cooked(&mut meal_plan, "dinner", "monday", None).unwrap();
+        assert_eq!(meal_plan.meals()[0].rating, Some(4));
+
+        let error = mark_cooked(&mut meal_plan, "lunch", "monday", None).unwrap_err();
+        assert_eq!(error.kind, error::ErrorKind::NotFound);
+        assert!(Args::try_parse_from(["mealplan", "cooked", "-m", "dinner", "-d", "monday", "-r", "6"]).is_err());
+
+        let args = Args::parse_from(["mealplan", "review", "--journal"]);
+        assert!(matches!(args.command, Some(Commands::Review { journal: Some(None) })));
+    }
+
+    #[test]
+    fn test_removed_meals_go_to_trash() {
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        assert_eq!(format_trash(&meal_plan, 30), "The trash is empty.\n");
+
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "John".to_string(), "Pasta".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Jane".to_string(), "Soup".to_string()));
+        let id = remove_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), &mut Scripted::default()).unwrap();
+        assert!(meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).is_none());
+
+        let listing = format_trash(&meal_plan, 30);
+        assert!(listing.starts_with("Trash for the week of 2023-01-02:\n  1. Dinner on Mon: Pasta (cook: John), removed "));
+        assert!(listing.ends_with("deleted for good after 30 days.\n"));
+
+        // Restoring refuses to overwrite a meal planned since
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Jane".to_string(), "Tacos".to_string()));
+        assert!(meal_plan.restore_from_trash(id).is_err());
+        meal_plan.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon));
+        assert_eq!(meal_plan.restore_from_trash(id).unwrap().description, "Pasta");
+        assert!(meal_plan.trash.is_empty());
+        assert!(meal_plan.restore_from_trash(id).is_err());
+
+        // Old removals are purged
+        remove_meal(&mut meal_plan, "Lunch".to_string(), "Tuesday".to_string(), &mut Scripted::default()).unwrap();
+        assert_eq!(meal_plan.purge_trash(Utc::now() - Duration::days(30)), 0);
+        assert_eq!(meal_plan.purge_trash(Utc::now() + Duration::days(1)), 1);
+    }
+
+    #[test]
+    fn test_updated_by_attribution() {
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Thursday".to_string(), "John".to_string(), "Lasagna".to_string(), "alice").unwrap();
+        let meal = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Thu)).unwrap();
+        assert_eq!(meal.updated_by.as_deref(), Some("alice"));
+
+        edit_meal(&mut meal_plan, "Dinner".to_string(), "Thursday".to_string(), Some("John".to_string()), Some("Leftovers".to_string()), &Completions::default(), "bob", &mut Scripted::default()).unwrap();
+        let meal = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Thu)).unwrap();
+        assert_eq!(meal.description, "Leftovers");
+        assert_eq!(meal.updated_by.as_deref(), Some("bob"));
+
+        // The configured user name takes precedence over the environment
+        let config = Config {
+            user_name: Some("Carol".to_string()),
+            ..Config::new()
+        };
+        assert_eq!(current_user(&config), "Carol");
+    }
+
+    #[test]
+    fn test_history_shows_who_changed_meals() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = temp_dir.path();
+        let log = EventLog::new(storage_path);
+        assert_eq!(format_history(&log.read().unwrap(), 20), "No changes recorded yet.\n");
+
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Thursday".to_string(), "John".to_string(), "Lasagna".to_string(), "alice").unwrap();
+        save_meal_plan(&meal_plan, storage_path, 1).unwrap();
+        meal_plan.user = Some("bob".to_string());
+        remove_meal(&mut meal_plan, "dinner".to_string(), "thu".to_string(), &mut Scripted::new(["y"])).unwrap();
+        save_meal_plan(&meal_plan, storage_path, 1).unwrap();
+
+        let removed = &load_meal_plan(storage_path).unwrap().tombstones[0];
+        assert_eq!(removed.removed_by.as_deref(), Some("bob"));
+        let history = format_history(&log.read().unwrap(), 20);
+        let lines: Vec<&str> = history.lines().map(|line| &line[18..]).collect();
+        assert_eq!(lines, ["Week changed to 2023-01-02", "Dinner on Thu: Lasagna (cook: John), by alice", "Dinner on Thu: removed, by bob"]);
+        assert_eq!(format_history(&log.read().unwrap(), 1).lines().count(), 1);
+    }
+
+    #[test]
+    fn test_parse_day() {
+        // The week starts on a Saturday
+        let meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 7).unwrap());
+        assert!(matches!(parse_day("2023-05-01", &meal_plan), Ok(Day::Date(_))));
+        assert!(matches!(parse_day("Monday", &meal_plan), Ok(Day::Weekday(Weekday::Mon))));
+        assert!(matches!(parse_day("lundi", &meal_plan), Ok(Day::Weekday(Weekday::Mon))));
+        assert!(matches!(parse_day("2", &meal_plan), Ok(Day::Weekday(Weekday::Sun))));
+        assert!(parse_day("Invalid", &meal_plan).is_err());
+    }
+    
+    #[test]
+    fn test_export_json() {
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        
+        // Add a meal
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        
+        // Create a temporary file for testing
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("test_export.json");
+        
+        // Export to JSON
+        assert!(export_json(&meal_plan, &output_path).is_ok());
+        
+        // Verify the file exists
+        assert!(output_path.exists());
+        
+        // Load the exported file and verify contents
+        let loaded_plan = MealPlan::load_from_json(&output_path).unwrap();
+        assert_eq!(loaded_plan.meals().len(), 1);
+        assert_eq!(loaded_plan.meals()[0].description, "Pasta");
+    }
+    
+    #[test]
+    fn test_export_ical() {
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        
+        // Add a meal
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        
+        // Create a temporary file for testing
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("test_export.ics");
+        
+        // Export to iCal
+        assert!(export_ical(&meal_plan, &[], None, &output_path, false).is_ok());
+        
+        // Verify the file exists
+        assert!(output_path.exists());
+        
+        // Read the file and check for expected iCal format elements
+        let content = std::fs::read_to_string(&output_path).unwrap();
+        assert!(content.contains("BEGIN:VCALENDAR"));
+        assert!(content.contains("VERSION:2.0"));
+        assert!(content.contains("BEGIN:VEVENT"));
+        assert!(content.contains("SUMMARY:Dinner: Pasta"));
+        assert!(content.contains("DESCRIPTION:Cook: John"));
+        assert!(content.contains("END:VEVENT"));
+        assert!(content.contains("END:VCALENDAR"));
+    }
+
+    #[test]
+    fn test_split_calendars() {
+        // The week of Monday 2024-01-29 runs into February
+        let mut first = MealPlan::new(NaiveDate::from_ymd_opt(2024, 1, 29).unwrap());
+        add_meal(&mut first, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        add_meal(&mut first, "Dinner".to_string(), "Friday".to_string(), "John".to_string(), "Pizza".to_string(), "tester").unwrap();
+        let mut second = MealPlan::new(NaiveDate::from_ymd_opt(2024, 2, 5).unwrap());
+        add_meal(&mut second, "Lunch".to_string(), "Tuesday".to_string(), "Jane".to_string(), "Soup".to_string(), "tester").unwrap();
+        let empty = MealPlan::new(NaiveDate::from_ymd_opt(2024, 2, 12).unwrap());
+        let plans = vec![first, second, empty];
+
+        let weekly = split_calendars(&plans, &[], CalendarSplit::Weekly);
+        assert_eq!(weekly.keys().collect::<Vec<_>>(), vec!["2024-01-29", "2024-02-05"]);
+        assert_eq!(weekly["2024-01-29"].components.len(), 2);
+
+        let monthly = split_calendars(&plans, &[], CalendarSplit::Monthly);
+        assert_eq!(monthly.keys().collect::<Vec<_>>(), vec!["2024-01", "2024-02"]);
+        let february = monthly["2024-02"].to_string();
+        assert!(february.contains("SUMMARY:Dinner: Pizza"));
+        assert!(february.contains("SUMMARY:Lunch: Soup"));
+        assert!(!february.contains("Pasta"));
+    }
+
+    #[test]
+    fn test_export_ical_incremental() {
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "John".to_string(), "Pasta".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Jane".to_string(), "Soup".to_string()));
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("changes.ics");
+
+        // Only the removal happened after the cutoff
+        let cutoff = meal_plan.meals()[0].updated_at.unwrap() + Duration::seconds(1);
+        meal_plan.remove_meal(&MealType::Lunch, &Day::Weekday(Weekday::Tue));
+        meal_plan.tombstones[0].removed_at = Some(cutoff);
+
+        let changes = meal_plan.modified_since(cutoff);
+        export_ical(&changes, &[], None, &output_path, true).unwrap();
+        let content = std::fs::read_to_string(&output_path).unwrap();
+        assert!(!content.contains("SUMMARY:Dinner: Pasta"));
+        assert!(content.contains("UID:meal-lunch-20230103@mealplan"));
+        assert!(content.contains("STATUS:CANCELLED"));
+
+        // The full export uses the same stable identifiers and skips removals
+        export_ical(&meal_plan, &[], None, &output_path, false).unwrap();
+        let content = std::fs::read_to_string(&output_path).unwrap();
+        assert!(content.contains("UID:meal-dinner-20230102@mealplan"));
+        assert!(content.contains("LAST-MODIFIED:"));
+        assert!(!content.contains("CANCELLED"));
+    }
+
+    #[test]
+    fn test_export_ical_merge() {
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "John".to_string(), "Pasta".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Jane".to_string(), "Soup".to_string()));
+        let temp_dir = tempfile::tempdir().unwrap();
+        let calendar_path = temp_dir.path().join("family.ics");
+
+        // A calendar with an event of its own, last week's dinner and this week's meals
+        let mut existing = meal_calendar(&meal_plan, &[], false);
+        existing.push(Event::new().uid("dentist@example.com").summary("Dentist").done());
+        existing.push(Event::new().uid("meal-dinner-20221226@mealplan").summary("Dinner: Stew").done());
+        std::fs::write(&calendar_path, existing.to_string()).unwrap();
+
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "John".to_string(), "Risotto".to_string()));
+        meal_plan.remove_meal(&MealType::Lunch, &Day::Weekday(Weekday::Tue));
+        meal_plan.add_meal(Meal::new(MealType::Breakfast, Day::Weekday(Weekday::Wed), "Jane".to_string(), "Eggs".to_string()));
+
+        let summary = export_ical_merged(&meal_plan, &[], None, &calendar_path, &calendar_path, None).unwrap();
+        assert_eq!(summary, CalendarMergeSummary { updated: 1, added: 1, removed: 1, ..Default::default() });
+
+        let content = std::fs::read_to_string(&calendar_path).unwrap();
+        assert!(content.contains("SUMMARY:Dinner: Risotto"));
+        assert!(!content.contains("Pasta"));
+        assert!(!content.contains("Soup"));
+        assert!(content.contains("SUMMARY:Breakfast: Eggs"));
+        assert!(content.contains("SUMMARY:Dentist"));
+        assert!(content.contains("SUMMARY:Dinner: Stew"));
+        assert_eq!(content.matches("BEGIN:VEVENT").count(), 4);
+
+        // Merging into a calendar that doesn't exist yet just writes the plan's events
+        let new_path = temp_dir.path().join("new.ics");
+        let summary = export_ical_merged(&meal_plan, &[], None, &new_path, &new_path, None).unwrap();
+        assert_eq!(summary.added, 2);
+
+        // Time zones, alarms and other people's to-dos come back exactly as they were
+        let foreign = "BEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\nBEGIN:STANDARD\r\nDTSTART:19701025T030000\r\n\
+            TZOFFSETFROM:+0200\r\nTZOFFSETTO:+0100\r\nEND:STANDARD\r\nEND:VTIMEZONE\r\n\
+            BEGIN:VTODO\r\nUID:taxes@example.com\r\nDTSTAMP:20200101T000000Z\r\nSUMMARY:Taxes\r\n\
+            BEGIN:VALARM\r\nACTION:DISPLAY\r\nTRIGGER:-PT15M\r\nEND:VALARM\r\nEND:VTODO\r\n";
+        let content = std::fs::read_to_string(&calendar_path).unwrap().replacen("BEGIN:VEVENT", &format!("{}BEGIN:VEVENT", foreign), 1);
+        std::fs::write(&calendar_path, content).unwrap();
+        export_ical_merged(&meal_plan, &[], None, &calendar_path, &calendar_path, None).unwrap();
+        let content = std::fs::read_to_string(&calendar_path).unwrap();
+        assert!(content.contains(foreign));
+        assert_eq!(content.matches("DTSTAMP").count(), 5);
+
+        assert_eq!(meal_uid_date("meal-dinner-20230102@mealplan"), NaiveDate::from_ymd_opt(2023, 1, 2));
+        assert_eq!(meal_uid_date("meal-dinner-20230102-1672700000@mealplan"), NaiveDate::from_ymd_opt(2023, 1, 2));
+        assert_eq!(meal_uid_date("dentist@example.com"), None);
+    }
+
+    #[test]
+    fn test_export_ical_merge_conflicts() {
+        let date = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let edited_at = |hour| Some(Utc.with_ymd_and_hms(2023, 1, 1, hour, 0, 0).unwrap());
+        let mut meal_plan = MealPlan::new(date);
+        let mut meal = Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "John".to_string(), "Pasta".to_string());
+        meal.updated_at = edited_at(10);
+        meal_plan.restore_meal(meal.clone());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let calendar_path = temp_dir.path().join("family.ics");
+        export_ical_merged(&meal_plan, &[], None, &calendar_path, &calendar_path, None).unwrap();
+        let edit_calendar = |from: &str, to: &str, sequence: u32| {
+            let content = std::fs::read_to_string(&calendar_path).unwrap()
+                .replace(from, to)
+                .replace(&format!("\nSEQUENCE:{}", sequence - 1), &format!("\nSEQUENCE:{}", sequence));
+            std::fs::write(&calendar_path, content).unwrap();
+        };
+
+        // Edited only in the calendar: kept there and handed back to the plan
+        edit_calendar("SUMMARY:Dinner: Pasta", "SUMMARY:Dinner: Pasta Bake", 1);
+        let summary = export_ical_merged(&meal_plan, &[], None, &calendar_path, &calendar_path, None).unwrap();
+        assert_eq!(summary.updated, 0);
+        assert_eq!(summary.pulled, vec![PulledMeal { meal_type: MealType::Dinner, date, description: "Pasta Bake".to_string(), cook: Some("John".to_string()) }]);
+        assert!(std::fs::read_to_string(&calendar_path).unwrap().contains("SUMMARY:Dinner: Pasta Bake"));
+
+        // Once the plan has it, merging again changes nothing
+        meal.description = "Pasta Bake".to_string();
+        meal.updated_at = edited_at(11);
+        meal_plan.restore_meal(meal.clone());
+        let summary = export_ical_merged(&meal_plan, &[], None, &calendar_path, &calendar_path, None).unwrap();
+        assert_eq!(summary, CalendarMergeSummary { updated: 1, ..Default::default() });
+
+        // Edited on both sides: the calendar's version stays until a side is preferred
+        edit_calendar("SUMMARY:Dinner: Pasta Bake", "SUMMARY:Dinner: Lasagne", 2);
+        meal.description = "Risotto".to_string();
+        meal.updated_at = edited_at(12);
+        meal_plan.restore_meal(meal);
+        let summary = export_ical_merged(&meal_plan, &[], None, &calendar_path, &calendar_path, None).unwrap();
+        assert_eq!(summary.conflicts, vec![CalendarConflict {
+            meal_type: MealType::Dinner,
+            date,
+            plan: "Dinner: Risotto".to_string(),
+            calendar: "Dinner: Lasagne".to_string(),
+        }]);
+        assert!(summary.pulled.is_empty());
+        assert!(std::fs::read_to_string(&calendar_path).unwrap().contains("SUMMARY:Dinner: Lasagne"));
+
+        let summary = export_ical_merged(&meal_plan, &[], None, &calendar_path, &calendar_path, Some(CalendarPreference::Plan)).unwrap();
+        assert_eq!(summary, CalendarMergeSummary { updated: 1, ..Default::default() });
+        let content = std::fs::read_to_string(&calendar_path).unwrap();
+        assert!(content.contains("SUMMARY:Dinner: Risotto"));
+        assert!(content.contains("SEQUENCE:3"));
+    }
+
+    #[test]
+    fn test_export_ical_invites_cook() {
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        add_meal(&mut meal_plan, "Lunch".to_string(), "Monday".to_string(), "Jane".to_string(), "Soup".to_string(), "tester").unwrap();
+
+        let mut john = CookProfile::new("John".to_string());
+        john.email = Some("john@example.com".to_string());
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("test_export.ics");
+        export_ical(&meal_plan, &[john], None, &output_path, false).unwrap();
+
+        let content = std::fs::read_to_string(&output_path).unwrap();
+        assert!(content.contains("ATTENDEE;CN=John:mailto:john@example.com"));
+        assert_eq!(content.matches("ATTENDEE").count(), 1);
+    }
+
+    #[test]
+    fn test_export_from_input_file() {
+        let stored = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let mut other = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 9).unwrap());
+        other.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "John".to_string(), "Pasta".to_string()));
+        let temp_dir = tempfile::tempdir().unwrap();
+        let input_path = temp_dir.path().join("other.json");
+        export_json(&other, &input_path).unwrap();
+
+        assert_eq!(input_plan(&stored, None).unwrap().week_start_date, stored.week_start_date);
+        let loaded = input_plan(&stored, Some(&input_path)).unwrap();
+        assert_eq!(loaded.week_start_date, other.week_start_date);
+        assert_eq!(loaded.meals().len(), 1);
+
+        let output_path = temp_dir.path().join("other.ics");
+        export_ical(&loaded, &[], None, &output_path, false).unwrap();
+        assert!(std::fs::read_to_string(&output_path).unwrap().contains("UID:meal-dinner-20230109@mealplan"));
+
+        std::fs::write(&input_path, "not a plan").unwrap();
+        assert_eq!(input_plan(&stored, Some(&input_path)).unwrap_err().kind, error::ErrorKind::Data);
+        assert_eq!(input_plan(&stored, Some(&temp_dir.path().join("missing.json"))).unwrap_err().kind, error::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_export_ical_workflow() {
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "John".to_string(), "Chili".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Fri), "Jane".to_string(), "Tacos".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Jane".to_string(), "Soup".to_string()));
+        let mut book = RecipeBook::default();
+        book.add(Recipe { name: "Chili".to_string(), tags: Vec::new(), ingredients: vec!["1 lb beef".to_string()], link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() });
+        book.add(Recipe { name: "Tacos".to_string(), tags: Vec::new(), ingredients: vec!["8 tortillas".to_string()], link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() });
+        let mut grocery_list = GroceryList::new(meal_plan.week_start_date);
+        grocery_list.add_item("Milk", None);
+        let shopping_days = [Weekday::Mon, Weekday::Thu];
+        let workflow = CalendarWorkflow { book: &book, grocery_list: &grocery_list, attendance: &Attendance::default(), shopping_days: &shopping_days };
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("workflow.ics");
+        export_ical(&meal_plan, &[], Some(&workflow), &output_path, false).unwrap();
+        let content = std::fs::read_to_string(&output_path).unwrap();
+        assert_eq!(content.matches("SUMMARY:Grocery shopping").count(), 2);
+        assert!(content.contains("UID:shop-20230102@mealplan"));
+        assert!(content.contains("UID:shop-20230105@mealplan"));
+        assert!(content.contains("- Milk\\n- 1 lb beef"));
+        assert!(content.contains("- 8 tortillas"));
+        assert_eq!(content.matches("BEGIN:VTODO").count(), 2);
+        assert!(content.contains("SUMMARY:Prep: Chili"));
+        assert!(content.contains("UID:prep-dinner-20230106@mealplan"));
+        assert!(content.contains("DUE:20230106T180000Z"));
+
+        // Merging drops trips and prep for meals no longer planned, but only with --workflow
+        let mut merged_plan = meal_plan.clone();
+        merged_plan.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Fri));
+        let summary = export_ical_merged(&merged_plan, &[], None, &output_path, &output_path, None).unwrap();
+        assert_eq!(summary, CalendarMergeSummary { updated: 2, added: 0, removed: 1, ..Default::default() });
+        let summary = export_ical_merged(&merged_plan, &[], Some(&workflow), &output_path, &output_path, None).unwrap();
+        assert_eq!(summary, CalendarMergeSummary { updated: 5, added: 0, removed: 1, ..Default::default() });
+        assert_eq!(workflow_uid_date("prep-dinner-20230106@mealplan"), NaiveDate::from_ymd_opt(2023, 1, 6));
+        assert_eq!(workflow_uid_date("meal-dinner-20230106@mealplan"), None);
+    }
+    
+    #[test]
+    fn test_export_qr() {
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        add_meal(&mut meal_plan, "Breakfast".to_string(), "Monday".to_string(), "Alice".to_string(), "Eggs".to_string(), "tester").unwrap();
+
+        let text = compact_plan_text(&meal_plan);
+        assert_eq!(text, "Meal plan week of 2023-01-02\nMon Breakfast: Eggs (Alice)\nMon Dinner: Pasta (John)");
+
+        // Write a PNG and check the file signature
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output_path = temp_dir.path().join("plan.png");
+        assert!(export_qr(&text, Some(&output_path)).is_ok());
+        let bytes = std::fs::read(&output_path).unwrap();
+        assert_eq!(&bytes[1..4], b"PNG");
+
+        // Content beyond QR capacity is rejected
+        assert!(export_qr(&"x".repeat(8000), Some(&output_path)).is_err());
+    }
+
+    #[test]
+    fn test_sync_meal_plan() {
+        // Create a temporary directory for testing
+        let temp_dir = tempfile::tempdir().unwrap();
+        let json_path = temp_dir.path().join("meal_plan.json");
+        let markdown_path = temp_dir.path().join("meal_plan.md");
+        
+        // Create a test config with the temp directory
+        let mut config = Config::new();
+        config.meal_plan_storage_path = temp_dir.path().to_path_buf();
+        
+        // Create a meal plan
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        
+        // Save to JSON
+        meal_plan.save_to_json(&json_path).unwrap();
+        
+        // Test sync from JSON to Markdown
+        assert!(sync_meal_plan(&config, "json").is_ok());
+        
+        // Verify the markdown file was created
+        assert!(markdown_path.exists());
+        
+        // Read the markdown file and check for expected content
+        let content = std::fs::read_to_string(&markdown_path).unwrap();
+        assert!(content.contains("# Meal Plan"));
+        assert!(content.contains("## Mon"));
+        assert!(content.contains("### Dinner"));
+        assert!(content.contains("- Cook: John"));
+        assert!(content.contains("- Description: Pasta"));
+        
+        // Test sync with non-existent files
+        let empty_dir = tempfile::tempdir().unwrap();
+        let empty_config = Config {
+            meal_plan_storage_path: empty_dir.path().to_path_buf(),
+            current_week_start_date: Local::now().date_naive(),
+            ..Config::new()
+        };
+        
+        assert!(sync_meal_plan(&empty_config, "auto").is_err());
+    }
+
+    #[test]
+    fn test_sync_direction_uses_content() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = temp_dir.path();
+        let config = Config {
+            meal_plan_storage_path: storage_path.to_path_buf(),
+            ..Config::new()
+        };
+
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        save_meal_plan(&meal_plan, storage_path, 1).unwrap();
+
+        // The markdown is written last, but saving leaves the files in sync
+        assert!(sync_meal_plan(&config, "auto").is_ok());
+
+        // Only the JSON changed, even though the markdown was touched afterwards
+        let mut edited = meal_plan.clone();
+        add_meal(&mut edited, "Lunch".to_string(), "Monday".to_string(), "John".to_string(), "Soup".to_string(), "tester").unwrap();
+        edited.save_to_json(storage_path.join("meal_plan.json")).unwrap();
+        let markdown = std::fs::read(storage_path.join("meal_plan.md")).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(storage_path.join("meal_plan.md"), markdown).unwrap();
+
+        assert!(sync_meal_plan(&config, "auto").is_ok());
+        let content = std::fs::read_to_string(storage_path.join("meal_plan.md")).unwrap();
+        assert!(content.contains("- Description: Soup"));
+
+        // Markdown that can't be read back isn't synced, nor lost when the plan is saved
+        std::fs::write(storage_path.join("meal_plan.md"), "# My notes\n").unwrap();
+        assert!(sync_meal_plan(&config, "auto").is_err());
+        save_meal_plan(&meal_plan, storage_path, 1).unwrap();
+        assert_eq!(std::fs::read_to_string(storage_path.join("meal_plan.md.edited")).unwrap(), "# My notes\n");
+        assert!(std::fs::read_to_string(storage_path.join("meal_plan.md")).unwrap().contains("- Description: Pasta"));
+        assert!(sync_meal_plan(&config, "auto").is_ok());
+    }
+
+    #[test]
+    fn test_sync_applies_markdown_edits() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = temp_dir.path();
+        let config = Config {
+            meal_plan_storage_path: storage_path.to_path_buf(),
+            ..Config::new()
+        };
+
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        add_meal(&mut meal_plan, "Lunch".to_string(), "Tuesday".to_string(), "John".to_string(), "Soup".to_string(), "tester").unwrap();
+        save_meal_plan(&meal_plan, storage_path, 1).unwrap();
+
+        // Both files are edited: the JSON gets a breakfast, the Markdown a new dinner and
+        // one meal fewer
+        let mut edited = meal_plan.clone();
+        add_meal(&mut edited, "Breakfast".to_string(), "Wednesday".to_string(), "Jane".to_string(), "Eggs".to_string(), "tester").unwrap();
+        edited.save_to_json(storage_path.join("meal_plan.json")).unwrap();
+        let markdown_path = storage_path.join("meal_plan.md");
+        let markdown = std::fs::read_to_string(&markdown_path).unwrap()
+            .replace("- Description: Pasta", "- Description: Risotto")
+            .replace("## Tue\n\n### Lunch\n- Cook: John\n- Description: Soup\n", "");
+        std::fs::write(&markdown_path, markdown).unwrap();
+        assert_eq!(sync_direction(storage_path, "auto").unwrap(), SyncDirection::MarkdownToJson);
+
+        sync_meal_plan(&config, "auto").unwrap();
+        let synced = MealPlan::load_from_json(storage_path.join("meal_plan.json")).unwrap();
+        assert_eq!(synced.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap().description, "Risotto");
+        assert!(synced.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Tue)).is_none());
+        assert_eq!(synced.find_meal(&MealType::Breakfast, &Day::Weekday(Weekday::Wed)).unwrap().description, "Eggs");
+        assert!(std::fs::read_to_string(&markdown_path).unwrap().contains("- Description: Eggs"));
+        assert!(!storage_path.join("meal_plan.md.edited").exists());
+        assert_eq!(sync_direction(storage_path, "auto").unwrap(), SyncDirection::UpToDate);
+    }
+
+    #[test]
+    fn test_sync_remotes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = temp_dir.path();
+        let config = Config {
+            meal_plan_storage_path: storage_path.to_path_buf(),
+            ..Config::new()
+        };
+        let shared = tempfile::tempdir().unwrap();
+        let remote = shared.path().join("meal_plan.json");
+        let missing = shared.path().join("new.json");
+
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        save_meal_plan(&meal_plan, storage_path, 1).unwrap();
+        let mut other = MealPlan::new(meal_plan.week_start_date);
+        other.replica_id = "laptop".to_string();
+        add_meal(&mut other, "Lunch".to_string(), "Tuesday".to_string(), "Jane".to_string(), "Soup".to_string(), "tester").unwrap();
+        other.save_to_json(&remote).unwrap();
+
+        sync_remotes(&config, &[remote.clone(), missing.clone()]).unwrap();
+        let local = load_meal_plan(storage_path).unwrap();
+        assert_eq!(local.meals().len(), 2);
+        for copy in [&remote, &missing] {
+            assert_eq!(MealPlan::load_from_json(copy).unwrap().meals().len(), 2);
+        }
+
+        // A copy of another week is reported and left as it is
+        let old = MealPlan::new(meal_plan.week_start_date - chrono::Duration::days(7));
+        old.save_to_json(&remote).unwrap();
+        let err = sync_remotes(&config, &[remote.clone(), missing]).unwrap_err();
+        assert_eq!(err.kind, error::ErrorKind::Conflict);
+        assert!(MealPlan::load_from_json(&remote).unwrap().meals().is_empty());
+    }
+
+    #[test]
+    fn test_sync_status() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = temp_dir.path();
+
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        save_meal_plan(&meal_plan, storage_path, 1).unwrap();
+
+        let status = sync_status(storage_path, "auto").unwrap();
+        assert!(status.contains("JSON:     unchanged since last sync"));
+        assert!(status.ends_with("Files are in sync; a sync would do nothing.\n"));
+
+        // A hand edit to the JSON is listed meal by meal, and nothing is synced
+        let mut edited = meal_plan.clone();
+        edited.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon));
+        edited.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Jane".to_string(), "Soup".to_string()));
+        edited.save_to_json(storage_path.join("meal_plan.json")).unwrap();
+        let markdown = std::fs::read_to_string(storage_path.join("meal_plan.md")).unwrap();
+
+        let status = sync_status(storage_path, "auto").unwrap();
+        assert!(status.contains("JSON:     changed since last sync"));
+        assert!(status.contains("Markdown: unchanged since last sync"));
+        assert!(status.contains("  Dinner on Mon: removed\n  Lunch on Tue: Soup (cook: Jane)\n"));
+        assert!(status.ends_with("A sync would copy the JSON file to Markdown.\n"));
+        assert_eq!(std::fs::read_to_string(storage_path.join("meal_plan.md")).unwrap(), markdown);
+        assert!(status.contains("Copies:   none left by a file sync service\n"));
+    }
+
+    #[test]
+    fn test_sync_status_compares_conflicted_copies() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = temp_dir.path();
+
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        meal_plan.replica_id = "laptop".to_string();
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "John".to_string(), "Pasta".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Jane".to_string(), "Soup".to_string()));
+        let mut copy = meal_plan.clone();
+        copy.replica_id = "phone".to_string();
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Jane".to_string(), "Salad".to_string()));
+        save_meal_plan(&meal_plan, storage_path, 1).unwrap();
+
+        copy.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "John".to_string(), "Pizza".to_string()));
+        copy.remove_meal(&MealType::Lunch, &Day::Weekday(Weekday::Tue));
+        copy.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Jane".to_string(), "Stew".to_string()));
+        copy.add_meal(Meal::new(MealType::Snack, Day::Weekday(Weekday::Wed), "Jane".to_string(), "Fruit".to_string()));
+        copy.save_to_json(storage_path.join("meal_plan (conflicted copy).json")).unwrap();
+
+        let status = sync_status(storage_path, "auto").unwrap();
+        assert!(status.contains("Copy \"meal_plan (conflicted copy).json\": 3 meal(s) differ from the plan\n"));
+        assert!(status.contains("  Dinner on Mon: \"Pasta\" in the plan, \"Pizza\" in the copy (the copy is newer)\n"));
+        assert!(status.contains("  Lunch on Tue: \"Salad\" in the plan, \"Stew\" in the copy (changed in both)\n"));
+        assert!(status.contains("  Snack on Wed: nothing in the plan, \"Fruit\" in the copy (the copy is newer)\n"));
+        assert!(status.contains("sync merge"));
+        assert!(!storage_path.join("meal_plan.json.damaged").exists());
     }
 
-    println!("Storage path: {:?}", storage_path);
-    Ok(())
-}
+    #[test]
+    fn test_merge_peer_plan() {
+        let week_start = Local::now().date_naive();
+        let mut meal_plan = MealPlan::new(week_start);
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
 
-fn remove_meal(meal_plan: &mut MealPlan, meal_type_str: String, day_str: String) -> Result<(), String> {
-    // Validate meal type
-    let meal_type = match meal_type_str.to_lowercase().as_str() {
-        "breakfast" => MealType::Breakfast,
-        "lunch" => MealType::Lunch,
-        "dinner" => MealType::Dinner,
-        "snack" => MealType::Snack,
-        _ => return Err("Invalid meal type. Must be breakfast, lunch, dinner, or snack.".to_string()),
-    };
+        let mut remote = MealPlan::new(week_start);
+        add_meal(&mut remote, "Lunch".to_string(), "Monday".to_string(), "Alice".to_string(), "Soup".to_string(), "tester").unwrap();
 
-    // Validate day
-    let day = parse_day(&day_str)?;
+        assert!(merge_peer_plan(&mut meal_plan, &remote, |_, _, _| None).is_ok());
+        assert_eq!(meal_plan.meals().len(), 2);
 
-    // Check if the meal exists
-    if meal_plan.find_meal(&meal_type, &day).is_none() {
-        return Err(format!("No {} meal found for {}.", meal_type, day));
+        // Plans for another week are rejected
+        let other_week = MealPlan::new(week_start + Duration::days(7));
+        let result = merge_peer_plan(&mut meal_plan, &other_week, |_, _, _| None);
+        assert!(result.unwrap_err().message.contains("Peer is planning the week of"));
     }
 
-    // Check if this is the last meal in the plan
-    if meal_plan.meals.len() == 1 {
-        println!("This is the last meal in your plan. Are you sure you want to remove it? (y/n)");
-        if !confirm() {
-            return Err("Meal removal cancelled by user.".to_string());
-        }
-    }
+    #[test]
+    fn test_merge_copy_settles_duplicates() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut base = MealPlan::new(week_start);
+        base.replica_id = "laptop".to_string();
+        base.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Al".to_string(), "Pasta".to_string()));
 
-    // Remove the meal
-    meal_plan.remove_meal(&meal_type, &day);
-    Ok(())
-}
+        // Both copies changed Monday's dinner, and both planned Tuesday's lunch, one by date
+        let mut ours = base.clone();
+        ours.change_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon), |meal| meal.description = "Chili".to_string());
+        ours.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Al".to_string(), "Tomato soup".to_string()));
+        let mut theirs = base.clone();
+        theirs.replica_id = "pi".to_string();
+        theirs.change_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon), |meal| meal.description = "Pasta bake".to_string());
+        let tuesday = Day::Date(NaiveDate::from_ymd_opt(2023, 1, 3).unwrap());
+        theirs.add_meal(Meal::new(MealType::Lunch, tuesday.clone(), "Bo".to_string(), "Soup".to_string()));
 
-fn edit_meal(meal_plan: &mut MealPlan, meal_type_str: String, day_str: String, new_cook: Option<String>, new_description: Option<String>) -> Result<(), String> {
-    // Validate meal type
-    let meal_type = match meal_type_str.to_lowercase().as_str() {
-        "breakfast" => MealType::Breakfast,
-        "lunch" => MealType::Lunch,
-        "dinner" => MealType::Dinner,
-        "snack" => MealType::Snack,
-        _ => return Err("Invalid meal type. Must be breakfast, lunch, dinner, or snack.".to_string()),
-    };
+        // Left to the merge, the lunch written both ways is still only planned once
+        let mut merged = ours.clone();
+        assert_eq!(merge_copy(&mut merged, &theirs, |_, _, _| None), 1);
+        assert_eq!(merged.meals().len(), 2);
+        assert!(merged.find_meal(&MealType::Lunch, &tuesday).is_none());
 
-    // Validate day
-    let day = parse_day(&day_str)?;
+        let mut asked = Vec::new();
+        let mut merged = ours.clone();
+        let settled = merge_copy(&mut merged, &theirs, |_, _, collision| {
+            asked.push(collision);
+            Some(duplicates::Strategy::KeepBoth)
+        });
+        assert_eq!(settled, 2);
+        assert_eq!(asked, vec![duplicates::Collision::Conflict, duplicates::Collision::Duplicate]);
+        assert_eq!(merged.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap().description, "Chili + Pasta bake");
+        assert_eq!(merged.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Tue)).unwrap().description, "Tomato soup");
+        assert_eq!(merged.meals().len(), 2);
 
-    // Find the meal to edit
-    let meal = meal_plan.find_meal(&meal_type, &day)
-        .ok_or_else(|| format!("No {} meal found for {}.", meal_type, day))?;
+        // The other copy takes the settled meals without being asked again
+        let mut back = theirs.clone();
+        assert_eq!(merge_copy(&mut back, &merged, |_, _, _| panic!("asked again")), 0);
+        assert_eq!(back.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap().description, "Chili + Pasta bake");
+        assert!(back.find_meal(&MealType::Lunch, &tuesday).is_none());
 
-    // Display current meal details
-    println!("Current meal details:");
-    println!("  Type: {}", meal.meal_type);
-    println!("  Day: {}", meal.day);
-    println!("  Cook: {}", meal.cook);
-    println!("  Description: {}", meal.description);
-    println!();
+        let args = Args::parse_from(["mealplan", "sync", "merge", "copy.json", "--on-duplicate", "replace"]);
+        assert!(matches!(
+            args.command,
+            Some(Commands::Sync { action: Some(SyncAction::Merge { on_duplicate: Some(duplicates::Strategy::Replace), .. }), .. })
+        ));
+    }
 
-    // Get updated values from user
-    let new_cook = if let Some(cook) = new_cook {
-        cook
-    } else {
-        println!("Enter new cook (leave empty to keep current value):");
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).map_err(|e| e.to_string())?;
-        let input = input.trim();
-        if input.is_empty() {
-            meal.cook.clone()
-        } else {
-            input.to_string()
-        }
-    };
+    #[test]
+    fn test_sync_merge_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let conflicted_path = temp_dir.path().join("meal_plan (conflicted copy).json");
+        let week_start = Local::now().date_naive();
 
-    let new_description = if let Some(desc) = new_description {
-        desc
-    } else {
-        println!("Enter new description (leave empty to keep current value):");
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input).map_err(|e| e.to_string())?;
-        let input = input.trim();
-        if input.is_empty() {
-            meal.description.clone()
-        } else {
-            input.to_string()
-        }
-    };
+        let mut meal_plan = MealPlan::new(week_start);
+        meal_plan.replica_id = "laptop".to_string();
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
 
-    // Remove the old meal and add the updated one
-    meal_plan.remove_meal(&meal_type, &day);
-    let updated_meal = Meal::new(meal_type, day, new_cook, new_description);
-    meal_plan.add_meal(updated_meal);
+        // The other device removed Monday's dinner and planned Tuesday's lunch
+        let mut other = meal_plan.clone();
+        other.replica_id = "pi".to_string();
+        other.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon));
+        add_meal(&mut other, "Lunch".to_string(), "Tuesday".to_string(), "Alice".to_string(), "Soup".to_string(), "tester").unwrap();
+        other.save_to_json(&conflicted_path).unwrap();
 
-    Ok(())
-}
+        assert!(sync_merge_file(&mut meal_plan, &conflicted_path, |_, _, _| None).is_ok());
+        assert_eq!(meal_plan.meals().len(), 1);
+        assert!(meal_plan.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Tue)).is_some());
 
-fn add_meal(meal_plan: &mut MealPlan, meal_type: String, day: String, cook: String, description: String) -> Result<(), String> {
-    // Validate meal type
-    let meal_type = match meal_type.to_lowercase().as_str() {
-        "breakfast" => MealType::Breakfast,
-        "lunch" => MealType::Lunch,
-        "dinner" => MealType::Dinner,
-        "snack" => MealType::Snack,
-        _ => return Err("Invalid meal type. Must be breakfast, lunch, dinner, or snack.".to_string()),
-    };
+        let config = Config {
+            replica_id: Some("kitchen".to_string()),
+            ..Config::new()
+        };
+        assert_eq!(current_replica(&config), "kitchen");
+    }
 
-    // Validate day
-    let day = parse_day(&day)?;
+    #[test]
+    fn test_rollover() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let archive = Archive::new(temp_dir.path());
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
 
-    // Check for duplicate meals
-    if meal_plan.find_meal(&meal_type, &day).is_some() {
-        println!("A meal of this type already exists for this day. Do you want to replace it? (y/n)");
-        if !confirm() {
-            return Err("Meal not added due to user cancellation.".to_string());
-        }
-        meal_plan.remove_meal(&meal_type, &day);
-    }
+        let mut meal_plan = MealPlan::new(week_start);
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
 
-    // Add the new meal
-    let new_meal = Meal::new(meal_type, day, cook, description);
-    meal_plan.add_meal(new_meal);
+        // The week isn't over until seven days after it started
+        let mid_week = NaiveDate::from_ymd_opt(2023, 1, 4).unwrap();
+        assert!(rollover(&meal_plan, &archive, mid_week, false).is_err());
+        assert!(archive.weeks().unwrap().is_empty());
 
-    Ok(())
-}
+        let next_week = rollover(&meal_plan, &archive, NaiveDate::from_ymd_opt(2023, 1, 8).unwrap(), false).unwrap();
+        assert_eq!(next_week.week_start_date, NaiveDate::from_ymd_opt(2023, 1, 8).unwrap());
+        assert!(next_week.meals().is_empty());
+        assert_eq!(archive.weeks().unwrap(), vec![week_start]);
+        assert_eq!(archive.load_week(week_start).unwrap().meals().len(), 1);
 
-fn parse_day(day_str: &str) -> Result<Day, String> {
-    // Try parsing as a date first
-    if let Ok(date) = NaiveDate::parse_from_str(day_str, "%Y-%m-%d") {
-        return Ok(Day::Date(date));
-    }
+        // Forcing a rollover mid-week starts the following week
+        let forced = rollover(&meal_plan, &archive, mid_week, true).unwrap();
+        assert_eq!(forced.week_start_date, NaiveDate::from_ymd_opt(2023, 1, 8).unwrap());
 
-    // If not a date, try parsing as a weekday
-    match day_str.to_lowercase().as_str() {
-        "monday" => Ok(Day::Weekday(Weekday::Mon)),
-        "tuesday" => Ok(Day::Weekday(Weekday::Tue)),
-        "wednesday" => Ok(Day::Weekday(Weekday::Wed)),
-        "thursday" => Ok(Day::Weekday(Weekday::Thu)),
-        "friday" => Ok(Day::Weekday(Weekday::Fri)),
-        "saturday" => Ok(Day::Weekday(Weekday::Sat)),
-        "sunday" => Ok(Day::Weekday(Weekday::Sun)),
-        _ => Err("Invalid day format. Use YYYY-MM-DD or day name.".to_string()),
+        // Missed weeks are skipped so the new plan covers today
+        let late = rollover(&meal_plan, &archive, NaiveDate::from_ymd_opt(2023, 1, 25).unwrap(), false).unwrap();
+        assert_eq!(late.week_start_date, NaiveDate::from_ymd_opt(2023, 1, 22).unwrap());
     }
-}
 
-fn export_ical(meal_plan: &MealPlan, output_path: &PathBuf) -> Result<(), String> {
-    // Create a new calendar
-    let mut calendar = Calendar::new();
-    
-    // Add events for each meal
-    for meal in &meal_plan.meals {
-        // Create a new event
-        let summary = format!("{}: {}", meal.meal_type, meal.description);
-        let description = format!("{}: {}", "Cook", meal.cook);
-        
-        // Set date/time
-        let date = match &meal.day {
-            Day::Weekday(weekday) => {
-                // Find the next occurrence of this weekday from the week start date
-                let days_to_add = (*weekday as i64 - meal_plan.week_start_date.weekday().num_days_from_monday() as i64)
-                    .rem_euclid(7);
-                meal_plan.week_start_date + Duration::days(days_to_add)
-            },
-            Day::Date(date) => *date,
-        };
-        
-        // Set meal time based on meal type (approximate times)
-        let (hour, minute) = match meal.meal_type {
-            MealType::Breakfast => (8, 0),
-            MealType::Lunch => (12, 0),
-            MealType::Dinner => (18, 0),
-            MealType::Snack => (15, 0),
-        };
-        
-        // Create start and end times (1 hour duration)
-        let start_time = Utc.with_ymd_and_hms(
-            date.year(), date.month(), date.day(), 
-            hour, minute, 0
-        ).unwrap();
-        
-        let end_time = start_time + Duration::hours(1);
+    #[test]
+    fn test_frozen_week() {
+        let dir = tempfile::tempdir().unwrap();
+        let week = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut meal_plan = MealPlan::new(week);
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Chili".to_string()));
+        save_meal_plan(&meal_plan, dir.path(), 0).unwrap();
 
-        let mut event = Event::new();
+        let mut frozen = freeze::FrozenWeeks::default();
+        frozen.freeze(resolve_week("this", week).unwrap(), Utc::now());
+        frozen.freeze(resolve_week("next", week).unwrap(), Utc::now());
+        assert_eq!(resolve_week("2", week).unwrap(), NaiveDate::from_ymd_opt(2022, 12, 19).unwrap());
+        assert_eq!(resolve_week("2023-01-11", week).unwrap(), NaiveDate::from_ymd_opt(2023, 1, 9).unwrap());
+        assert_eq!(resolve_week("4000000000", week).unwrap_err().kind, error::ErrorKind::Usage);
+        frozen.save_to_json(dir.path().join(freeze::FROZEN_WEEKS_FILE)).unwrap();
 
-        event
-            .description(&description)
-            .ends(end_time)
-            .starts(start_time)
-            .summary(&summary);
+        // Cooking a meal is fine, changing one isn't
+        mark_cooked(&mut meal_plan, "dinner", "monday", Some(5)).unwrap();
+        save_meal_plan(&meal_plan, dir.path(), 0).unwrap();
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Tuesday".to_string(), "Bob".to_string(), "Tacos".to_string(), "tester").unwrap();
+        let error = save_meal_plan(&meal_plan, dir.path(), 0).unwrap_err();
+        assert_eq!(error.kind, error::ErrorKind::Conflict);
+        assert!(error.message.contains("The change would have been:\n  Dinner on Tue: added Tacos\nAdd --force"), "{}", error.message);
+        assert_eq!(load_meal_plan(dir.path()).unwrap().meals().len(), 1);
 
-        
-        // Add a unique identifier
-        let uid = format!("meal-{}-{}-{:?}@mealplan", 
-            meal.meal_type.to_string().to_lowercase(),
-            date.format("%Y%m%d"),
-            std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
-        );
-        event.append_property(Property::new("UID", &uid));
-        
-        // Add the event to the calendar
-        calendar.push(event);
+        meal_plan.allow_frozen = true;
+        save_meal_plan(&meal_plan, dir.path(), 0).unwrap();
+        assert_eq!(load_meal_plan(dir.path()).unwrap().meals().len(), 2);
+
+        // A week that isn't frozen can be changed as usual
+        let mut other_week = MealPlan::new(week - Duration::days(7));
+        save_meal_plan(&other_week, dir.path(), 0).unwrap();
+        add_meal(&mut other_week, "Dinner".to_string(), "Monday".to_string(), "Bob".to_string(), "Soup".to_string(), "tester").unwrap();
+        save_meal_plan(&other_week, dir.path(), 0).unwrap();
+        assert!(Args::try_parse_from(["mealplan", "rollover", "--force"]).unwrap().force);
     }
-    
-    // Write the calendar to file
-    let ical_string = calendar.to_string();
-    std::fs::write(output_path, ical_string)
-        .map_err(|e| format!("Failed to write iCal file: {}", e))?;
-    
-    Ok(())
-}
 
-fn config_init(_config: &Config) -> Result<(), String> {
-    // Define the config file path
-    let config_dir = dirs::home_dir()
-        .ok_or_else(|| "Could not determine home directory".to_string())?
-        .join(".config")
-        .join("mealplan");
-    
-    // Create the directory if it doesn't exist
-    if !config_dir.exists() {
-        std::fs::create_dir_all(&config_dir)
-            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    #[test]
+    fn test_parse_weekday() {
+        assert_eq!(parse_weekday("fridays").unwrap(), Weekday::Fri);
+        assert_eq!(parse_weekday("Friday").unwrap(), Weekday::Fri);
+        assert_eq!(parse_weekday("tue").unwrap(), Weekday::Tue);
+        assert_eq!(parse_weekday("Thursdays").unwrap(), Weekday::Thu);
+        assert!(parse_weekday("someday").is_err());
+        assert!(parse_weekday("fris").is_err());
     }
-    
-    let config_path = config_dir.join("config.json");
-    
-    // Check if the config file already exists
-    if config_path.exists() {
-        println!("Configuration file already exists at {:?}. Overwrite? (y/n)", config_path);
-        if !confirm() {
-            return Err("Configuration initialization cancelled by user.".to_string());
-        }
+
+    #[test]
+    fn test_cook_availability() {
+        let mut config = Config::new();
+        assert!(set_cook_availability(&mut config, "Alice", &["fridays".to_string()], false).is_err());
+        cooks_add(&mut config, "Alice", None, Vec::new());
+        set_cook_availability(&mut config, "Alice", &["fridays".to_string(), "mon".to_string()], false).unwrap();
+        set_cook_availability(&mut config, "alice", &["friday".to_string()], false).unwrap();
+        assert_eq!(config.unavailable_days("Alice"), &[Weekday::Mon, Weekday::Fri]);
+        assert!(set_cook_availability(&mut config, "Alice", &["someday".to_string()], false).is_err());
+
+        // 2023-01-02 is a Monday
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Fri), "Alice".to_string(), "Pizza".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), "Alice".to_string(), "Soup".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Fri), "Bob".to_string(), "Salad".to_string()));
+
+        let conflicts = check_findings(&config, &meal_plan, &RecipeBook::default(), &Attendance::default(), &[]);
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].message.contains("Alice is unavailable on Fridays"));
+
+        // Becoming available again clears the conflict
+        set_cook_availability(&mut config, "Alice", &["friday".to_string()], true).unwrap();
+        assert!(check_findings(&config, &meal_plan, &RecipeBook::default(), &Attendance::default(), &[]).is_empty());
+        assert_eq!(config.unavailable_days("Alice"), &[Weekday::Mon]);
+
+        // Planning her on Monday asks first, and only goes ahead on a yes
+        let add = |meal_plan: &mut MealPlan, prompter: &mut Scripted| {
+            add_command(&config, meal_plan, &RecipeBook::default(), "Stew".to_string(), "dinner".to_string(), "mon".to_string(), "Alice".to_string(), MealExtras::default(), prompter)
+        };
+        let error = add(&mut meal_plan, &mut Scripted::new(["n"])).unwrap_err();
+        assert_eq!(error.kind, error::ErrorKind::Cancelled);
+        assert!(meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).is_none());
+        let mut answers = Scripted::new(["y"]);
+        add(&mut meal_plan, &mut answers).unwrap();
+        assert_eq!(answers.remaining(), 0);
+        assert_eq!(meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap().description, "Stew");
     }
-    
-    // Create a new config with default values
-    let new_config = Config {
-        meal_plan_storage_path: config_dir.clone(),
-        current_week_start_date: Local::now().date_naive(),
-    };
-    
-    // Save the config
-    new_config.save(&config_path)
-        .map_err(|e| format!("Failed to save configuration: {}", e))?;
-    
-    println!("Configuration saved to {:?}", config_path);
-    println!("Meal plan storage path: {:?}", new_config.meal_plan_storage_path);
-    println!("Current week start date: {}", new_config.current_week_start_date);
-    
-    Ok(())
-}
 
-fn sync_meal_plan(config: &Config, source_type: &str) -> Result<(), String> {
-    let json_path = config.meal_plan_storage_path.join("meal_plan.json");
-    let markdown_path = config.meal_plan_storage_path.join("meal_plan.md");
-    
-    // Check if both files exist
-    let json_exists = json_path.exists();
-    let markdown_exists = markdown_path.exists();
-    
-    if !json_exists && !markdown_exists {
-        return Err("No meal plan files found to sync.".to_string());
+    #[test]
+    fn test_check_with_rules() {
+        let mut config = Config::new();
+        cooks_add(&mut config, "Alice", None, Vec::new());
+        set_cook_availability(&mut config, "Alice", &["friday".to_string()], false).unwrap();
+        // 2023-01-02 is a Monday
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Thu), "Alice".to_string(), "Pizza".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Fri), "Alice".to_string(), "Pizza".to_string()));
+        let rules = lint::parse_rules("rules:\n  - name: same-dish\n    check: in_a_row\n").unwrap();
+
+        let findings = check_findings(&config, &meal_plan, &RecipeBook::default(), &Attendance::default(), &rules);
+        assert_eq!(format_findings(&findings, "text").unwrap(), "\
+error [cook-availability] Fri 2023-01-06: Dinner: Alice is unavailable on Fridays (2023-01-06).
+warning [same-dish] Fri 2023-01-06: Pizza for dinner two days in a row (Thursday and Friday)
+1 error(s), 1 warning(s), 0 note(s).
+");
+        let json: serde_json::Value = serde_json::from_str(&format_findings(&findings, "json").unwrap()).unwrap();
+        assert_eq!(json["errors"], 1);
+        assert_eq!(json["findings"][1]["rule"], "same-dish");
+        assert_eq!(json["findings"][1]["date"], "2023-01-06");
+        assert_eq!(format_findings(&[], "text").unwrap(), "No problems found.\n");
+        assert!(format_findings(&[], "yaml").is_err());
     }
-    
-    // Get file modification times
-    let json_modified = if json_exists {
-        std::fs::metadata(&json_path)
-            .map_err(|e| format!("Failed to read JSON file metadata: {}", e))?
-            .modified()
-            .map_err(|e| format!("Failed to get JSON file modification time: {}", e))?
-    } else {
-        // If JSON doesn't exist, use a very old time
-        std::time::SystemTime::UNIX_EPOCH
-    };
-    
-    let markdown_modified = if markdown_exists {
-        std::fs::metadata(&markdown_path)
-            .map_err(|e| format!("Failed to read Markdown file metadata: {}", e))?
-            .modified()
-            .map_err(|e| format!("Failed to get Markdown file modification time: {}", e))?
-    } else {
-        // If Markdown doesn't exist, use a very old time
-        std::time::SystemTime::UNIX_EPOCH
-    };
-    
-    // Determine which file is more recent or use the specified source
-    let (from_json, from_markdown) = match source_type.to_lowercase().as_str() {
-        "json" => (true, false),
-        "markdown" | "md" => (false, true),
-        "auto" | _ => {
-            if !json_exists {
-                (false, true)
-            } else if !markdown_exists {
-                (true, false)
-            } else {
-                // Compare modification times
-                match json_modified.duration_since(markdown_modified) {
-                    Ok(_) => (true, false),  // JSON is newer
-                    Err(_) => (false, true), // Markdown is newer
-                }
-            }
+
+    #[test]
+    fn test_budget_alerts() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = dir.path();
+        let mut config = Config::new();
+        // 2023-01-30 is a Monday, in a week running into February
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 30).unwrap());
+        assert!(budget_alerts(&config, storage, &meal_plan).unwrap().is_empty());
+
+        let mut places = Places::default();
+        places.add(Place { name: "Pizza Place".to_string(), cuisine: None, contact: None, typical_cost: Some(30.0) });
+        places.save_to_json(storage.join("places.json")).unwrap();
+        let mut archived = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 23).unwrap());
+        for (plan, weekday) in [(&mut archived, Weekday::Fri), (&mut meal_plan, Weekday::Mon)] {
+            let mut meal = Meal::new(MealType::Dinner, Day::Weekday(weekday), "John".to_string(), "Pizza".to_string());
+            meal.place = Some("Pizza Place".to_string());
+            plan.add_meal(meal);
         }
-    };
-    
-    if from_json {
-        println!("Syncing from JSON to Markdown...");
-        let meal_plan = MealPlan::load_from_json(&json_path)
-            .map_err(|e| format!("Failed to load meal plan from JSON: {}", e))?;
-        
-        meal_plan.save_to_markdown(&markdown_path)
-            .map_err(|e| format!("Failed to save meal plan to Markdown: {}", e))?;
-    } else if from_markdown {
-        println!("Syncing from Markdown to JSON...");
-        // Since loading from Markdown is not fully implemented, we'll provide a helpful error
-        return Err("Syncing from Markdown to JSON is not fully implemented yet. Please use JSON as the source.".to_string());
+        Archive::new(storage).save_week(&archived).unwrap();
+
+        let mut grocery_list = GroceryList::new(meal_plan.week_start_date);
+        grocery_list.add_item("Cheese", None);
+        grocery_list.set_purchased("Cheese", true);
+        grocery_list.set_price("Cheese", 45.0);
+        grocery_list.save_to_json(storage.join("grocery_list.json")).unwrap();
+
+        config.weekly_budget = Some(40.0);
+        config.monthly_takeout_budget = Some(50.0);
+        let alerts = budget_alerts(&config, storage, &meal_plan).unwrap();
+        let messages: Vec<String> = alerts.iter().map(|alert| alert.to_string()).collect();
+        assert_eq!(messages, vec![
+            "Spent 45.00 on groceries for the week of 2023-01-30, 5.00 over the 40.00 weekly budget.".to_string(),
+            "Eating out in January 2023 comes to about 60.00, 10.00 over the 50.00 monthly takeout budget.".to_string(),
+        ]);
+        assert_eq!(budget_finding(&alerts[1]).to_string(), format!("warning [budget]: {}", messages[1]));
+
+        // Nothing is sent, or marked as sent, without a notification target
+        assert_eq!(send_budget_alerts(&[], storage, &alerts).unwrap(), 0);
+        assert!(!storage.join(budget::SENT_ALERTS_FILE).exists());
     }
-    
-    Ok(())
-}
 
-fn export_json(meal_plan: &MealPlan, output_path: &PathBuf) -> Result<(), String> {
-    // Simply use the existing save_to_json method
-    meal_plan.save_to_json(output_path)
-        .map_err(|e| format!("Failed to export meal plan to JSON: {}", e))
-}
+    #[test]
+    fn test_cooks_roster() {
+        let mut config = Config::new();
+        // Anyone can cook until the roster has someone on it
+        assert!(validate_cook(&config, "Dave").is_ok());
 
-fn confirm() -> bool {
-    io::stdout().flush().unwrap();
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
-    input.trim().to_lowercase() == "y"
-}
+        assert!(cooks_add(&mut config, "Alice", Some("alice@example.com".to_string()), vec!["vegetarian".to_string()]));
+        assert!(cooks_add(&mut config, "Bob", None, Vec::new()));
+        // Adding again only updates the details that were given
+        assert!(!cooks_add(&mut config, "alice", None, vec!["spicy".to_string()]));
+        let alice = config.find_cook("Alice").unwrap();
+        assert_eq!(alice.email.as_deref(), Some("alice@example.com"));
+        assert_eq!(alice.preferences, vec!["spicy".to_string()]);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use clap::CommandFactory;
-    use std::io::Read;
+        assert!(validate_cook(&config, "bob").is_ok());
+        let err = validate_cook(&config, "Dave").unwrap_err();
+        assert!(err.message.contains("Dave is not one of the cooks (Alice, Bob)"));
+        assert_eq!(err.kind, error::ErrorKind::NotFound);
+
+        // 2023-01-02 is a Monday
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Soup".to_string()));
+        set_cook_availability(&mut config, "Bob", &["sunday".to_string()], false).unwrap();
+        assert_eq!(
+            format_cooks(&config, &meal_plan),
+            "Alice <alice@example.com>: 1 meal this week\n  Preferences: spicy\nBob: 0 meals this week, unavailable Sun\n"
+        );
+
+        config.cook_rotation = vec!["Bob".to_string(), "Alice".to_string()];
+        cooks_remove(&mut config, "bob").unwrap();
+        assert_eq!(config.cook_rotation, vec!["Alice".to_string()]);
+        assert!(cooks_remove(&mut config, "Bob").is_err());
+    }
 
     #[test]
-    fn verify_cli() {
-        Args::command().debug_assert()
+    fn test_default_cooks() {
+        let mut config = Config::new();
+        let book = RecipeBook::default();
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        // Without a roster a meal doesn't need a cook
+        add_command(&config, &mut meal_plan, &book, "Toast".to_string(), "breakfast".to_string(), "mon".to_string(), String::new(), MealExtras::default(), &mut Scripted::default()).unwrap();
+        assert_eq!(meal_plan.find_meal(&MealType::Breakfast, &Day::Weekday(Weekday::Mon)).unwrap().cook, "");
+
+        cooks_add(&mut config, "Alex", None, Vec::new());
+        cooks_add(&mut config, "Bob", None, Vec::new());
+        config.default_cooks.insert("breakfast".to_string(), "Alex".to_string());
+        config.default_cooks.insert("Dinner".to_string(), "rotate".to_string());
+
+        add_command(&config, &mut meal_plan, &book, "Pancakes".to_string(), "breakfast".to_string(), "tue".to_string(), String::new(), MealExtras::default(), &mut Scripted::default()).unwrap();
+        assert_eq!(meal_plan.find_meal(&MealType::Breakfast, &Day::Weekday(Weekday::Tue)).unwrap().cook, "Alex");
+        add_command(&config, &mut meal_plan, &book, "Eggs".to_string(), "breakfast".to_string(), "wed".to_string(), "Bob".to_string(), MealExtras::default(), &mut Scripted::default()).unwrap();
+        assert_eq!(meal_plan.find_meal(&MealType::Breakfast, &Day::Weekday(Weekday::Wed)).unwrap().cook, "Bob");
+
+        // Dinners take turns, and Bob cooked last
+        let ops = bulk::parse_ops(r#"{"op": "add", "meal_type": "dinner", "day": "mon", "description": "Chili"}"#).unwrap();
+        let (updated, _) = apply_ops(&config, &meal_plan, &book, ops, "tester").unwrap();
+        assert_eq!(updated.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap().cook, "Alex");
+
+        let error = add_command(&config, &mut meal_plan, &book, "Soup".to_string(), "lunch".to_string(), "mon".to_string(), String::new(), MealExtras::default(), &mut Scripted::default()).unwrap_err();
+        assert_eq!(error.message, "No cook given, and there's no default cook for lunch. Pass --cook, or set one in default_cooks in the configuration.");
     }
 
     #[test]
-    fn test_add_command() {
-        let args = Args::parse_from(&[
-            "mealplan",
-            "add",
-            "Spaghetti Bolognese",
-            "--meal-type", "Dinner",
-            "--day", "Monday",
-            "--cook", "John",
-        ]);
-        match args.command {
-            Some(Commands::Add { description, meal_type, day, cook }) => {
-                assert_eq!(description, "Spaghetti Bolognese");
-                assert_eq!(meal_type, "Dinner");
-                assert_eq!(day, "Monday");
-                assert_eq!(cook, "John");
-            }
-            _ => panic!("Expected Add command"),
+    fn test_next_cook() {
+        let mut config = Config::new();
+        // 2023-01-02 is a Monday
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let dinner = MealType::Dinner;
+        let chili = dish("Chili", &[], &RecipeBook::default());
+        let monday = Day::Weekday(Weekday::Mon);
+        assert!(next_cook(&config, &meal_plan, &dinner, &monday, &chili).unwrap_err().message.contains("No cook rotation"));
+
+        // The roster order is the rotation unless one is configured
+        for name in ["Alice", "Bob", "Carol"] {
+            cooks_add(&mut config, name, None, Vec::new());
         }
+        assert_eq!(next_cook(&config, &meal_plan, &dinner, &monday, &chili).unwrap(), "Alice");
+
+        // Turns follow the rotation
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Soup".to_string()));
+        assert_eq!(next_cook(&config, &meal_plan, &dinner, &Day::Weekday(Weekday::Tue), &chili).unwrap(), "Bob");
+
+        // Counts are balanced: Carol cooked manually, so Bob is still behind
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Carol".to_string(), "Salad".to_string()));
+        assert_eq!(next_cook(&config, &meal_plan, &dinner, &Day::Weekday(Weekday::Tue), &chili).unwrap(), "Bob");
+
+        // Unavailable cooks are skipped
+        set_cook_availability(&mut config, "Bob", &["wednesday".to_string()], false).unwrap();
+        let wednesday = Day::Weekday(Weekday::Wed);
+        assert_eq!(next_cook(&config, &meal_plan, &dinner, &wednesday, &chili).unwrap(), "Alice");
+
+        // Replacing a meal doesn't count the cook being replaced
+        assert_eq!(next_cook(&config, &meal_plan, &MealType::Lunch, &Day::Weekday(Weekday::Tue), &chili).unwrap(), "Bob");
+
+        set_cook_availability(&mut config, "Alice", &["wednesday".to_string()], false).unwrap();
+        set_cook_availability(&mut config, "Carol", &["wednesday".to_string()], false).unwrap();
+        assert!(next_cook(&config, &meal_plan, &dinner, &wednesday, &chili).unwrap_err().message.contains("Wednesdays"));
+
+        // Preferences rule out cooks for some dishes and days
+        let mut book = RecipeBook::default();
+        book.add(Recipe { name: "Paella".to_string(), tags: vec!["seafood".to_string()], ingredients: Vec::new(), link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() });
+        book.add(Recipe { name: "Stew".to_string(), tags: Vec::new(), ingredients: Vec::new(), link: None, servings: None, photo: None, steps: vec!["Simmer 2 hours".to_string()], equipment: Vec::new() });
+        config.find_cook_mut("Bob").unwrap().prefs = models::CookPrefs { avoid: vec!["fish".to_string(), "seafood".to_string()], max_minutes: Some(30), days: Vec::new() };
+        let thursday = Day::Weekday(Weekday::Thu);
+        config.find_cook_mut("Alice").unwrap().prefs.days = vec![Weekday::Sat, Weekday::Sun];
+        assert!(!config.is_cook_available("Alice", Weekday::Thu));
+        assert_eq!(next_cook(&config, &meal_plan, &dinner, &thursday, &chili).unwrap(), "Bob");
+        assert_eq!(next_cook(&config, &meal_plan, &dinner, &thursday, &dish("Grilled fish", &[], &book)).unwrap(), "Carol");
+        assert_eq!(next_cook(&config, &meal_plan, &dinner, &thursday, &dish("paella", &[], &book)).unwrap(), "Carol");
+        assert_eq!(next_cook(&config, &meal_plan, &dinner, &thursday, &dish("Stew", &[], &book)).unwrap(), "Carol");
+        config.find_cook_mut("Carol").unwrap().prefs.max_minutes = Some(60);
+        assert!(next_cook(&config, &meal_plan, &dinner, &thursday, &dish("Stew", &[], &book)).unwrap_err().message.contains("takes on Stew"));
     }
 
     #[test]
-    fn test_edit_command() {
-        let args = Args::parse_from(&[
-            "mealplan",
-            "edit",
-            "Updated meal description",
-            "--meal-type", "Lunch",
-            "--day", "Tuesday",
-        ]);
-        match args.command {
-            Some(Commands::Edit { description, meal_type, day, cook }) => {
-                assert_eq!(description, Some("Updated meal description".to_string()));
-                assert_eq!(meal_type, "Lunch");
-                assert_eq!(day, "Tuesday");
-                assert_eq!(cook, None);
-            }
-            _ => panic!("Expected Edit command"),
-        }
+    fn test_cook_prefs_yaml() {
+        let mut cook = CookProfile::new("Alice".to_string());
+        assert_eq!(parse_cook_prefs(&cook_prefs_yaml(&cook)).unwrap(), models::CookPrefs::default());
+        cook.prefs = models::CookPrefs { avoid: vec!["fish".to_string()], max_minutes: Some(30), days: vec![Weekday::Sat, Weekday::Sun] };
+        let yaml = cook_prefs_yaml(&cook);
+        assert!(yaml.contains("avoid: [\"fish\"]\nmax_minutes: 30\ndays: [\"Sat\",\"Sun\"]\n"));
+        assert_eq!(parse_cook_prefs(&yaml).unwrap(), cook.prefs);
+
+        let edited = parse_cook_prefs("avoid: [pork, '']\ndays: [sunday, saturday, Sun]\n").unwrap();
+        assert_eq!(edited, models::CookPrefs { avoid: vec!["pork".to_string()], max_minutes: None, days: vec![Weekday::Sat, Weekday::Sun] });
+        assert!(parse_cook_prefs("max_minute: 30").unwrap_err().contains("unknown key max_minute"));
+        assert!(parse_cook_prefs("max_minutes: soon").is_err());
+        assert!(format_cook_prefs(&cook).contains("  Won't cook: fish\n  Recipes up to 30 minutes\n  Only cooks on Sat, Sun\n"));
     }
 
     #[test]
-    fn test_remove_command() {
-        let args = Args::parse_from(&[
-            "mealplan",
-            "remove",
-            "--meal-type", "Breakfast",
-            "--day", "Wednesday"
-        ]);
-        match args.command {
-            Some(Commands::Remove { meal_type, day }) => {
-                assert_eq!(meal_type, "Breakfast");
-                assert_eq!(day, "Wednesday");
-            }
-            _ => panic!("Expected Remove command"),
-        }
+    fn test_matrix_commands() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = temp_dir.path();
+        let mut config = Config::new();
+        // 2023-01-02 is a Monday
+        let monday = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut meal_plan = MealPlan::new(monday);
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "Jane".to_string(), "Chili".to_string(), "tester").unwrap();
+        add_meal(&mut meal_plan, "Breakfast".to_string(), "Monday".to_string(), "John".to_string(), "Eggs".to_string(), "tester").unwrap();
+        save_meal_plan(&meal_plan, storage_path, 1).unwrap();
+
+        assert_eq!(format_daily_menu(&meal_plan, monday), "Menu for Monday, Jan 2:\n- Breakfast: Eggs (John)\n- Dinner: Chili (Jane)");
+        assert_eq!(format_daily_menu(&meal_plan, monday + Duration::days(1)), "Nothing planned for Tuesday, Jan 3.");
+
+        let reply = |config: &Config, command: matrix::BotCommand, today: NaiveDate| {
+            matrix_reply(config, storage_path, command, "@sam:example.org", today)
+        };
+        assert_eq!(reply(&config, matrix::BotCommand::Tonight, monday).unwrap(), "Tonight: Chili, cooked by Jane");
+        assert_eq!(reply(&config, matrix::BotCommand::Tonight, monday + Duration::days(1)).unwrap(), "Nothing planned for dinner tonight.");
+
+        let add = |day: &str, cook: Option<&str>| matrix::BotCommand::Add {
+            meal_type: "dinner".to_string(),
+            day: day.to_string(),
+            description: "Tacos".to_string(),
+            cook: cook.map(str::to_string),
+        };
+        assert_eq!(reply(&config, add("fri", None), monday).unwrap(), "Added dinner on Friday: Tacos (Sam).");
+        assert_eq!(reply(&config, add("mon", None), monday).unwrap_err().message, "Dinner on Monday is already planned: Chili.");
+        let saved = load_meal_plan(storage_path).unwrap();
+        let tacos = saved.meals().iter().find(|meal| meal.description == "Tacos").unwrap();
+        assert_eq!(tacos.updated_by.as_deref(), Some("@sam:example.org"));
+
+        // With a roster, the cook has to be on it and the rotation picks one when none is given
+        cooks_add(&mut config, "Jane", None, Vec::new());
+        assert!(reply(&config, add("sat", Some("Sam")), monday).is_err());
+        set_cook_availability(&mut config, "Jane", &["saturday".to_string()], false).unwrap();
+        assert!(reply(&config, add("sat", None), monday).is_err());
+        assert_eq!(
+            reply(&config, add("sat", Some("jane")), monday).unwrap(),
+            "Added dinner on Saturday: Tacos (jane). Note: jane is unavailable on Saturdays (2023-01-07)."
+        );
+    }
+
+    #[test]
+    fn test_meal_plan_is_loaded_from_event_log() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = temp_dir.path();
+
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        save_meal_plan(&meal_plan, storage_path, 1).unwrap();
+        meal_plan.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon));
+        add_meal(&mut meal_plan, "Lunch".to_string(), "Tuesday".to_string(), "Jane".to_string(), "Soup".to_string(), "tester").unwrap();
+        save_meal_plan(&meal_plan, storage_path, 1).unwrap();
+
+        let log = EventLog::new(storage_path);
+        assert_eq!(log.read().unwrap().len(), 4);
+
+        // The snapshot records how far into the log it goes
+        let snapshot = MealPlan::load_from_json(storage_path.join("meal_plan.json")).unwrap();
+        assert_eq!(snapshot.event_seq, 4);
+        assert_eq!(load_meal_plan(storage_path).unwrap().event_seq, 4);
+
+        // The log wins over a snapshot that is missing some of its events, however new the file
+        let mut older = snapshot.clone();
+        older.event_seq = 3;
+        older.save_to_json_with_backups(storage_path.join("meal_plan.json"), 0).unwrap();
+        older.add_meal(Meal::new(MealType::Snack, Day::Weekday(Weekday::Wed), "Jane".to_string(), "Fruit".to_string()));
+        older.save_to_json_with_backups(storage_path.join("meal_plan.json"), 0).unwrap();
+        assert_eq!(load_meal_plan(storage_path).unwrap().meals().len(), 1);
+        std::fs::remove_file(storage_path.join("meal_plan.json")).unwrap();
+        let loaded = load_meal_plan(storage_path).unwrap();
+        assert_eq!(loaded.meals().len(), 1);
+        assert_eq!(loaded.meals()[0].description, "Soup");
+
+        // A snapshot changed behind mealplan's back doesn't match its checksum, so the log is
+        // used and the changed file is kept aside
+        save_meal_plan(&loaded, storage_path, 1).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let mut edited = loaded.clone();
+        edited.add_meal(Meal::new(MealType::Snack, Day::Weekday(Weekday::Wed), "Jane".to_string(), "Fruit".to_string()));
+        edited.save_to_json(storage_path.join("meal_plan.json")).unwrap();
+        let loaded = load_meal_plan(storage_path).unwrap();
+        assert_eq!(loaded.meals().len(), 1);
+        assert!(storage_path.join("meal_plan.json.damaged").exists());
+
+        // An edit saved properly is recorded in the log
+        save_meal_plan(&edited, storage_path, 1).unwrap();
+        assert_eq!(load_meal_plan(storage_path).unwrap().meals().len(), 2);
+        assert_eq!(log.read().unwrap().len(), 5);
+        assert_eq!(log.project().unwrap().unwrap().meals().len(), 2);
     }
 
     #[test]
-    fn test_export_ical_command() {
-        let args = Args::parse_from(&[
-            "mealplan",
-            "export-ical",
-            "--output", "/tmp/mealplan.ics"
-        ]);
-        match args.command {
-            Some(Commands::ExportIcal { output }) => {
-                assert_eq!(output, PathBuf::from("/tmp/mealplan.ics"));
-            }
-            _ => panic!("Expected ExportIcal command"),
-        }
+    fn test_damaged_snapshot_is_rebuilt_from_event_log() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage_path = temp_dir.path();
+        let snapshot_path = storage_path.join("meal_plan.json");
+
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        save_meal_plan(&meal_plan, storage_path, 3).unwrap();
+        add_meal(&mut meal_plan, "Lunch".to_string(), "Tuesday".to_string(), "Jane".to_string(), "Soup".to_string(), "tester").unwrap();
+        save_meal_plan(&meal_plan, storage_path, 3).unwrap();
+
+        // Cut short after the log was written, so the damaged snapshot is the newer file
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let contents = std::fs::read_to_string(&snapshot_path).unwrap();
+        std::fs::write(&snapshot_path, &contents[..contents.len() / 2]).unwrap();
+
+        let loaded = load_meal_plan(storage_path).unwrap();
+        let mut descriptions: Vec<&str> = loaded.meals().iter().map(|meal| meal.description.as_str()).collect();
+        descriptions.sort();
+        assert_eq!(descriptions, vec!["Pasta", "Soup"]);
+
+        // Saving the recovered plan records nothing as removed
+        let events = EventLog::new(storage_path).read().unwrap().len();
+        save_meal_plan(&loaded, storage_path, 3).unwrap();
+        assert_eq!(EventLog::new(storage_path).read().unwrap().len(), events);
+        assert_eq!(load_meal_plan(storage_path).unwrap().meals().len(), 2);
     }
 
     #[test]
-    fn test_config_init_command() {
-        let args = Args::parse_from(&[
-            "mealplan",
-            "config",
-            "init"
-        ]);
-        match args.command {
-            Some(Commands::Config { action: ConfigAction::Init }) => {},
-            _ => panic!("Expected Config Init command"),
-        }
+    fn test_damaged_snapshot_is_recovered_from_backup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let snapshot_path = temp_dir.path().join("meal_plan.json");
+
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "John".to_string(), "Pasta".to_string()));
+        meal_plan.save_to_json_with_backups(&snapshot_path, 3).unwrap();
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Jane".to_string(), "Soup".to_string()));
+        meal_plan.save_to_json_with_backups(&snapshot_path, 3).unwrap();
+
+        // Cut short mid-write
+        let contents = std::fs::read_to_string(&snapshot_path).unwrap();
+        std::fs::write(&snapshot_path, &contents[..contents.len() / 2]).unwrap();
+
+        // Without an event log to rebuild from, the newest backup is used
+        let log = EventLog::new(temp_dir.path());
+        let loaded = load_snapshot(&snapshot_path, &log).unwrap();
+        assert_eq!(loaded.meals().len(), 1);
+        assert_eq!(loaded.meals()[0].description, "Pasta");
+
+        // With no usable backup the error comes through, and the damaged file can be kept
+        std::fs::remove_dir_all(temp_dir.path().join(storage::BACKUP_DIR)).unwrap();
+        assert!(load_snapshot(&snapshot_path, &log).is_err());
+        let damaged = keep_damaged_plan(&snapshot_path).unwrap();
+        assert_eq!(damaged, temp_dir.path().join("meal_plan.json.damaged"));
+        assert_eq!(std::fs::read_to_string(damaged).unwrap(), &contents[..contents.len() / 2]);
     }
 
     #[test]
-    #[ignore]
-    fn test_add_meal() {
-        let mut meal_plan = MealPlan::new(Local::now().date_naive());
-        
-        // Test adding a valid meal
-        assert!(add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string()).is_ok());
-        
-        // Test adding an invalid meal type
-        assert!(add_meal(&mut meal_plan, "Brunch".to_string(), "Tuesday".to_string(), "Alice".to_string(), "Eggs".to_string()).is_err());
-        
-        // Test adding a meal with an invalid day
-        assert!(add_meal(&mut meal_plan, "Lunch".to_string(), "Someday".to_string(), "Bob".to_string(), "Sandwich".to_string()).is_err());
-        
-        // Test adding a duplicate meal (this would normally prompt the user, but in tests it will just fail)
-        assert!(add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "Jane".to_string(), "Pizza".to_string()).is_err());
+    fn test_mismatched_snapshot_falls_back_to_backup() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let snapshot_path = temp_dir.path().join("meal_plan.json");
+        let log = EventLog::new(temp_dir.path());
+
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "John".to_string(), "Pasta".to_string()));
+        meal_plan.save_to_json_with_backups(&snapshot_path, 3).unwrap();
+
+        // With nothing to recover from, a snapshot that still loads is used
+        let contents = std::fs::read_to_string(&snapshot_path).unwrap();
+        let changed = contents.replace("Pasta", "Pizza");
+        std::fs::write(&snapshot_path, &changed).unwrap();
+        assert_eq!(load_snapshot(&snapshot_path, &log).unwrap().meals()[0].description, "Pizza");
+
+        // Otherwise the backup is, and the mismatched file is kept aside
+        std::fs::write(&snapshot_path, &contents).unwrap();
+        meal_plan.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Jane".to_string(), "Soup".to_string()));
+        meal_plan.save_to_json_with_backups(&snapshot_path, 3).unwrap();
+        let saved = std::fs::read_to_string(&snapshot_path).unwrap();
+        std::fs::write(&snapshot_path, saved.replace("Soup", "Stew")).unwrap();
+        let loaded = load_snapshot(&snapshot_path, &log).unwrap();
+        assert_eq!(loaded.meals().len(), 1);
+        assert_eq!(loaded.meals()[0].description, "Pasta");
+        let damaged = std::fs::read_to_string(temp_dir.path().join("meal_plan.json.damaged")).unwrap();
+        assert!(damaged.contains("Stew"));
     }
 
     #[test]
-    fn test_edit_meal() {
-        let mut meal_plan = MealPlan::new(Local::now().date_naive());
-        
-        // Add a meal first
-        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string()).unwrap();
-        
-        // Test editing a non-existent meal
-        assert!(edit_meal(&mut meal_plan, "Breakfast".to_string(), "Monday".to_string(), Some("Alice".to_string()), None).is_err());
-        
-        // Test editing with invalid meal type
-        assert!(edit_meal(&mut meal_plan, "Brunch".to_string(), "Monday".to_string(), Some("Alice".to_string()), None).is_err());
-        
-        // Test editing with invalid day
-        assert!(edit_meal(&mut meal_plan, "Dinner".to_string(), "Someday".to_string(), Some("Alice".to_string()), None).is_err());
-        
-        // Test successful edit with provided values (no interactive prompts)
-        assert!(edit_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), 
-                         Some("Alice".to_string()), Some("Updated pasta dish".to_string())).is_ok());
-        
-        // Verify the meal was updated
-        let updated_meal = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap();
-        assert_eq!(updated_meal.cook, "Alice");
-        assert_eq!(updated_meal.description, "Updated pasta dish");
+    fn test_parse_since() {
+        let now = Utc.with_ymd_and_hms(2023, 1, 10, 12, 0, 0).unwrap();
+        assert_eq!(parse_since("7d", now).unwrap(), now - Duration::days(7));
+        assert_eq!(parse_since("1w", now).unwrap(), now - Duration::weeks(1));
+
+        let midnight = NaiveDate::from_ymd_opt(2023, 1, 5).unwrap().and_hms_opt(0, 0, 0).unwrap();
+        let expected = Local.from_local_datetime(&midnight).unwrap().with_timezone(&Utc);
+        assert_eq!(parse_since("2023-01-05", now).unwrap(), expected);
+        assert_eq!(
+            parse_since("2023-01-09T08:30:00+01:00", now).unwrap(),
+            Utc.with_ymd_and_hms(2023, 1, 9, 7, 30, 0).unwrap()
+        );
+
+        assert_eq!(parse_since("last week", now).unwrap_err().kind, error::ErrorKind::Usage);
+        assert_eq!(parse_since("100000000w", now).unwrap_err().kind, error::ErrorKind::Usage);
     }
 
     #[test]
-    #[ignore]
-    fn test_remove_meal() {
-        let mut meal_plan = MealPlan::new(Local::now().date_naive());
-        
-        // Test removing a non-existent meal
-        assert!(remove_meal(&mut meal_plan, "Breakfast".to_string(), "Monday".to_string()).is_err());
-        
-        // Test removing with invalid meal type
-        assert!(remove_meal(&mut meal_plan, "Brunch".to_string(), "Monday".to_string()).is_err());
-        
-        // Test removing with invalid day
-        assert!(remove_meal(&mut meal_plan, "Dinner".to_string(), "Someday".to_string()).is_err());
-        
-        // Add a meal first
-        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string()).unwrap();
-        
-        // Test successful removal
-        assert!(remove_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string()).is_ok());
-        
-        // Verify the meal was removed
-        assert!(meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).is_none());
-        
-        // Add multiple meals to test the last meal confirmation
-        add_meal(&mut meal_plan, "Breakfast".to_string(), "Monday".to_string(), "Alice".to_string(), "Cereal".to_string()).unwrap();
-        add_meal(&mut meal_plan, "Lunch".to_string(), "Monday".to_string(), "Bob".to_string(), "Sandwich".to_string()).unwrap();
-        
-        // Remove one meal, should succeed without confirmation (not the last meal)
-        assert!(remove_meal(&mut meal_plan, "Breakfast".to_string(), "Monday".to_string()).is_ok());
-        
-        // Verify only one meal remains
-        assert_eq!(meal_plan.meals.len(), 1);
-        
-        // Test removing the last meal with confirmation
-        // Simulate user input of "y" for confirmation
-        let input = b"y\n";
-        std::io::stdin().read_exact(&mut input.to_vec()).unwrap();
-        assert!(remove_meal(&mut meal_plan, "Lunch".to_string(), "Monday".to_string()).is_ok());
-        
-        // Verify all meals are removed
-        assert_eq!(meal_plan.meals.len(), 0);
+    fn test_parse_period() {
+        assert_eq!(parse_period("60d").unwrap(), Duration::days(60));
+        assert_eq!(parse_period("8W").unwrap(), Duration::weeks(8));
+        assert_eq!(parse_period("30").unwrap(), Duration::days(30));
+        assert!(parse_period("d").is_err());
+        assert!(parse_period("2m").is_err());
+        assert_eq!(parse_period("999999999999d").unwrap_err().kind, error::ErrorKind::Usage);
+
+        let today = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        assert_eq!(date_before(today, Duration::days(28)).unwrap(), NaiveDate::from_ymd_opt(2023, 2, 1).unwrap());
+        assert_eq!(date_before(today, parse_period("100000000d").unwrap()).unwrap_err().kind, error::ErrorKind::Usage);
     }
 
     #[test]
-    fn test_parse_day() {
-        assert!(matches!(parse_day("2023-05-01"), Ok(Day::Date(_))));
-        assert!(matches!(parse_day("Monday"), Ok(Day::Weekday(Weekday::Mon))));
-        assert!(parse_day("Invalid").is_err());
+    fn test_format_stale() {
+        let today = NaiveDate::from_ymd_opt(2023, 3, 1).unwrap();
+        assert_eq!(format_stale(&[], today), "Everything in your history has been planned recently.\n");
+
+        let stale = vec![DishSummary {
+            description: "Lasagna".to_string(),
+            last_planned: NaiveDate::from_ymd_opt(2023, 1, 3).unwrap(),
+            times: 2,
+        }];
+        assert_eq!(
+            format_stale(&stale, today),
+            "Haven't had in a while:\n  Lasagna (last planned 2023-01-03, 57 days ago)\n"
+        );
     }
-    
+
     #[test]
-    fn test_export_json() {
-        let mut meal_plan = MealPlan::new(Local::now().date_naive());
-        
-        // Add a meal
-        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string()).unwrap();
-        
-        // Create a temporary file for testing
-        let temp_dir = tempfile::tempdir().unwrap();
-        let output_path = temp_dir.path().join("test_export.json");
-        
-        // Export to JSON
-        assert!(export_json(&meal_plan, &output_path).is_ok());
-        
-        // Verify the file exists
-        assert!(output_path.exists());
-        
-        // Load the exported file and verify contents
-        let loaded_plan = MealPlan::load_from_json(&output_path).unwrap();
-        assert_eq!(loaded_plan.meals.len(), 1);
-        assert_eq!(loaded_plan.meals[0].description, "Pasta");
+    fn test_variety_output() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut meal_plan = MealPlan::new(week_start);
+        for (day, description) in [(Weekday::Mon, "Mac, cheese"), (Weekday::Tue, "Soup"), (Weekday::Wed, "mac, cheese")] {
+            meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(day), "Alice".to_string(), description.to_string()));
+        }
+        let report = MealHistory::from_plans([&meal_plan]).variety(week_start, 1);
+
+        let text = format_variety(&report);
+        assert!(text.contains("Variety score: 67% (2 unique dishes in 3 dinners)"));
+        assert!(text.contains("mac, cheese x2"));
+        assert!(!text.contains("Longest repeat streaks"));
+
+        assert_eq!(
+            variety_csv(&report),
+            "dish,times,longest_streak_weeks,last_planned\n\"mac, cheese\",2,1,2023-01-04\nSoup,1,1,2023-01-03\n"
+        );
     }
-    
+
     #[test]
-    fn test_export_ical() {
-        let mut meal_plan = MealPlan::new(Local::now().date_naive());
-        
-        // Add a meal
-        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string()).unwrap();
-        
-        // Create a temporary file for testing
-        let temp_dir = tempfile::tempdir().unwrap();
-        let output_path = temp_dir.path().join("test_export.ics");
-        
-        // Export to iCal
-        assert!(export_ical(&meal_plan, &output_path).is_ok());
-        
-        // Verify the file exists
-        assert!(output_path.exists());
-        
-        // Read the file and check for expected iCal format elements
-        let content = std::fs::read_to_string(&output_path).unwrap();
-        assert!(content.contains("BEGIN:VCALENDAR"));
-        assert!(content.contains("VERSION:2.0"));
-        assert!(content.contains("BEGIN:VEVENT"));
-        assert!(content.contains("SUMMARY:Dinner: Pasta"));
-        assert!(content.contains("DESCRIPTION:Cook: John"));
-        assert!(content.contains("END:VEVENT"));
-        assert!(content.contains("END:VCALENDAR"));
+    fn test_batch_meal() {
+        // 2023-01-01 is a Sunday
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap());
+
+        assert!(batch_meal(&mut meal_plan, "Dinner", "Sunday", "Dinner", "Wednesday", "Alice", "Chili", "tester", &mut Scripted::default()).is_ok());
+        let cooked = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Sun)).unwrap();
+        assert_eq!(cooked.description, "Chili");
+        let leftovers = meal_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Wed)).unwrap();
+        assert_eq!(leftovers.description, "Leftovers: Chili");
+        assert_eq!(leftovers.cook, "Alice");
+
+        // Leftovers can be a different meal type
+        assert!(batch_meal(&mut meal_plan, "Dinner", "Monday", "Lunch", "Tuesday", "Bob", "Stew", "tester", &mut Scripted::default()).is_ok());
+        assert!(meal_plan.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Tue)).is_some());
+
+        // Leftovers can't come before the meal is cooked
+        let result = batch_meal(&mut meal_plan, "Dinner", "Friday", "Dinner", "Thursday", "Bob", "Curry", "tester", &mut Scripted::default());
+        assert!(result.unwrap_err().message.contains("Leftovers must be eaten after"));
+        assert!(batch_meal(&mut meal_plan, "Dinner", "Friday", "Brunch", "Saturday", "Bob", "Curry", "tester", &mut Scripted::default()).is_err());
+        assert_eq!(meal_plan.meals().len(), 4);
     }
-    
+
     #[test]
-    fn test_sync_meal_plan() {
-        // Create a temporary directory for testing
-        let temp_dir = tempfile::tempdir().unwrap();
-        let json_path = temp_dir.path().join("meal_plan.json");
-        let markdown_path = temp_dir.path().join("meal_plan.md");
-        
-        // Create a test config with the temp directory
-        let mut config = Config::new();
-        config.meal_plan_storage_path = temp_dir.path().to_path_buf();
-        
-        // Create a meal plan
-        let mut meal_plan = MealPlan::new(Local::now().date_naive());
-        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string()).unwrap();
-        
-        // Save to JSON
-        meal_plan.save_to_json(&json_path).unwrap();
-        
-        // Test sync from JSON to Markdown
-        assert!(sync_meal_plan(&config, "json").is_ok());
-        
-        // Verify the markdown file was created
-        assert!(markdown_path.exists());
-        
-        // Read the markdown file and check for expected content
-        let content = std::fs::read_to_string(&markdown_path).unwrap();
-        assert!(content.contains("# Meal Plan"));
-        assert!(content.contains("## Mon"));
-        assert!(content.contains("### Dinner"));
-        assert!(content.contains("- Cook: John"));
-        assert!(content.contains("- Description: Pasta"));
-        
-        // Test sync with non-existent files
-        let empty_dir = tempfile::tempdir().unwrap();
-        let empty_config = Config {
-            meal_plan_storage_path: empty_dir.path().to_path_buf(),
-            current_week_start_date: Local::now().date_naive(),
-        };
-        
-        assert!(sync_meal_plan(&empty_config, "auto").is_err());
+    fn test_batch_groceries() {
+        let mut book = RecipeBook::default();
+        assert!(format_batch_suggestions(&book).starts_with("No batch-friendly recipes yet."));
+        book.add(Recipe { name: "Chili".to_string(), tags: vec!["freezes-well".to_string()], ingredients: vec!["1 lb beef".to_string(), "salt to taste".to_string()], link: None, servings: Some(6), photo: None, steps: Vec::new(), equipment: Vec::new() });
+        book.add(Recipe { name: "Salad".to_string(), tags: Vec::new(), ingredients: Vec::new(), link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() });
+        assert_eq!(format_batch_suggestions(&book), "Batch-friendly recipes:\n  Chili (serves 6)\n");
+
+        // The recipe is doubled for the meal and its leftovers, and extra items follow
+        let items = batch_groceries(book.find("chili"), &["Tortilla chips".to_string()]);
+        assert_eq!(items, vec!["2 lb beef", "salt to taste", "Tortilla chips"]);
+        assert_eq!(batch_groceries(None, &["Rice".to_string()]), vec!["Rice"]);
+
+        assert!(Args::try_parse_from(["mealplan", "batch", "--suggest"]).is_ok());
+        assert!(Args::try_parse_from(["mealplan", "batch", "Chili"]).is_err());
+        assert!(Args::try_parse_from(["mealplan", "batch", "Chili", "--suggest"]).is_err());
     }
-    
+
     #[test]
     fn test_config_init() {
         // Create a temporary directory for testing
         let temp_dir = tempfile::tempdir().unwrap();
         
-        // Create a test config with the temp directory
-        let mut config = Config::new();
-        config.meal_plan_storage_path = temp_dir.path().to_path_buf();
-        
         // Mock the home directory for testing
         let original_home = std::env::var("HOME").ok();
         std::env::set_var("HOME", temp_dir.path().to_str().unwrap());
         
         // Test config initialization
-        assert!(config_init(&config).is_ok());
+        let config_dir = settings::config_dir(None).unwrap();
+        assert!(config_init(&config_dir.join("config.json"), &mut Scripted::default()).is_ok());
         
         // Verify the config file was created
         let config_path = temp_dir.path().join(".config").join("mealplan").join("config.json");
@@ -900,7 +10162,22 @@ mod tests {
             std::env::remove_var("HOME");
         }
     }
-    
+
+    #[test]
+    fn test_save_config_keeps_unreadable_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.json");
+        std::fs::write(&config_path, "{ not json").unwrap();
+
+        // The defaults in hand mustn't replace a file that couldn't be read
+        let err = save_config(&Config::new(), &config_path, Some("expected value")).unwrap_err();
+        assert!(err.message.contains("couldn't be read"));
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), "{ not json");
+
+        save_config(&Config::new(), &config_path, None).unwrap();
+        assert!(Config::load(&config_path).is_ok());
+    }
+
     #[test]
     fn test_end_to_end_workflow() {
         // Create a temporary directory for testing
@@ -914,6 +10191,7 @@ mod tests {
         let config = Config {
             meal_plan_storage_path: storage_path.clone(),
             current_week_start_date: Local::now().date_naive(),
+            ..Config::new()
         };
         
         // Create a new meal plan
@@ -925,7 +10203,8 @@ mod tests {
             "Dinner".to_string(), 
             "Monday".to_string(), 
             "John".to_string(), 
-            "Pasta".to_string()
+            "Pasta".to_string(),
+            "tester"
         ).is_ok());
         
         // Save the meal plan
@@ -937,14 +10216,17 @@ mod tests {
             "Dinner".to_string(),
             "Monday".to_string(),
             Some("Alice".to_string()),
-            Some("Spaghetti Bolognese".to_string())
+            Some("Spaghetti Bolognese".to_string()),
+            &Completions::default(),
+            "tester",
+            &mut Scripted::default()
         ).is_ok());
         
         // Save the updated meal plan
         assert!(meal_plan.save_to_json(&json_path).is_ok());
         
         // Step 3: Export to iCal
-        assert!(export_ical(&meal_plan, &ical_path).is_ok());
+        assert!(export_ical(&meal_plan, &[], None, &ical_path, false).is_ok());
         assert!(ical_path.exists());
         
         // Step 4: Export to Markdown
@@ -956,7 +10238,7 @@ mod tests {
         
         // Verify final state
         let loaded_plan = MealPlan::load_from_json(&json_path).unwrap();
-        assert_eq!(loaded_plan.meals.len(), 1);
+        assert_eq!(loaded_plan.meals().len(), 1);
         
         let meal = loaded_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap();
         assert_eq!(meal.cook, "Alice");
@@ -972,6 +10254,205 @@ mod tests {
         assert!(ical_content.contains("SUMMARY:Dinner: Spaghetti Bolognese"));
     }
     
+    #[test]
+    fn test_grocery_tracking() {
+        let mut grocery_list = GroceryList::new(Local::now().date_naive());
+
+        assert!(grocery_add(&mut grocery_list, "Milk", None).is_ok());
+        assert!(grocery_add(&mut grocery_list, "Bread", None).is_ok());
+        assert!(grocery_add(&mut grocery_list, "milk", None).is_err());
+        assert!(grocery_add(&mut grocery_list, "  ", None).is_err());
+
+        assert!(grocery_set_purchased(&mut grocery_list, "milk", true).is_ok());
+        assert!(grocery_set_purchased(&mut grocery_list, "Cheese", true).is_err());
+        assert_eq!(grocery_list.remaining().len(), 1);
+
+        assert!(grocery_set_purchased(&mut grocery_list, "Milk", false).is_ok());
+        assert_eq!(grocery_list.remaining().len(), 2);
+
+        // The list persists purchased state between runs
+        let temp_dir = tempfile::tempdir().unwrap();
+        let grocery_path = temp_dir.path().join("grocery_list.json");
+        grocery_set_purchased(&mut grocery_list, "Bread", true).unwrap();
+        grocery_list.save_to_json(&grocery_path).unwrap();
+
+        let loaded = load_grocery_list(&grocery_path, grocery_list.week_start_date);
+        assert_eq!(loaded.remaining().len(), 1);
+        assert_eq!(loaded.remaining()[0].name, "Milk");
+
+        // A list from another week is not carried over
+        let next_week = grocery_list.week_start_date + Duration::days(7);
+        assert!(load_grocery_list(&grocery_path, next_week).items.is_empty());
+    }
+
+    #[test]
+    fn test_grocery_list_by_store() {
+        let mut grocery_list = GroceryList::new(Local::now().date_naive());
+        grocery_add(&mut grocery_list, "Paper towels", None).unwrap();
+        grocery_add(&mut grocery_list, "Basil", Some("produce".to_string())).unwrap();
+        grocery_set_purchased(&mut grocery_list, "Basil", true).unwrap();
+
+        let mut store_preferences = HashMap::new();
+        store_preferences.insert("Paper towels".to_string(), "Costco".to_string());
+        store_preferences.insert("produce".to_string(), "Corner Shop".to_string());
+
+        let output = format_grocery_list(&grocery_list, Some(&store_preferences));
+        let costco = output.find("Costco:").unwrap();
+        let corner_shop = output.find("Corner Shop:").unwrap();
+        assert!(corner_shop < output.find("[x] Basil").unwrap());
+        assert!(costco < output.find("[ ] Paper towels").unwrap());
+
+        // Without store preferences the items are listed together
+        let output = format_grocery_list(&grocery_list, None);
+        assert!(!output.contains("Costco"));
+        assert!(output.contains("[ ] Paper towels"));
+    }
+
+    #[test]
+    fn test_waste_entry_and_report() {
+        let meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let mut book = RecipeBook::default();
+        book.add(Recipe { name: "Chili".to_string(), tags: Vec::new(), ingredients: Vec::new(), link: None, servings: Some(8), photo: None, steps: Vec::new(), equipment: Vec::new() });
+        let today = NaiveDate::from_ymd_opt(2023, 1, 4).unwrap();
+
+        let entry = waste_entry(&meal_plan, &book, " rice ", Some("chili"), Some("tue"), today).unwrap();
+        assert_eq!(entry, WasteEntry { date: NaiveDate::from_ymd_opt(2023, 1, 3).unwrap(), item: "rice".to_string(), recipe: Some("Chili".to_string()), week: Some(meal_plan.week_start_date) });
+        let later = waste_entry(&meal_plan, &book, "rice", None, None, NaiveDate::from_ymd_opt(2023, 1, 20).unwrap()).unwrap();
+        assert_eq!(later.week, None);
+        assert_eq!(waste_entry(&meal_plan, &book, "rice", Some("Soup"), None, today).unwrap_err().kind, error::ErrorKind::NotFound);
+        assert_eq!(waste_entry(&meal_plan, &book, " ", None, None, today).unwrap_err().kind, error::ErrorKind::Usage);
+
+        let mut log = WasteLog::default();
+        log.log(entry);
+        log.log(later);
+        let report = WasteReport::new(&log, ReportPeriod::parse_month("2023-01").unwrap());
+        assert_eq!(
+            format_waste_report(&report, &book, Some(4)),
+            "Food waste for January 2023\n2 item(s) thrown away.\n\nBy recipe:\n  \
+             Chili: 1 (makes 4 more serving(s) than the household eats; try a smaller batch)\n\n\
+             Thrown away more than once:\n  rice: 2 times\n"
+        );
+        assert!(format_waste_log(&log).starts_with("2023-01-03  rice (from Chili)\n"));
+    }
+
+    #[test]
+    fn test_import_receipt() {
+        let mut grocery_list = GroceryList::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        grocery_add(&mut grocery_list, "Milk", None).unwrap();
+        grocery_add(&mut grocery_list, "8 tortillas", None).unwrap();
+        grocery_add(&mut grocery_list, "Basil", None).unwrap();
+        let mut prices = PriceHistory::default();
+        let line = |description: &str, amount: f64| ReceiptLine { description: description.to_string(), amount };
+        let lines = [line("ORG WHOLE MILK", 4.99), line("TORTILLAS", 3.5), line("TORTILLAS", 3.5), line("PAPER TOWELS", 6.0)];
+        let today = NaiveDate::from_ymd_opt(2023, 1, 4).unwrap();
+
+        let output = import_receipt(&mut grocery_list, &mut prices, &lines, "receipt.csv", today);
+        assert!(output.contains("  TORTILLAS -> 8 tortillas (3.50)\n"));
+        assert!(output.ends_with("Not on the grocery list:\n  PAPER TOWELS (6.00)\nRecorded 17.99 from 4 line(s) as spent this week.\n"));
+        assert_eq!(grocery_list.items[1].price, Some(7.0));
+        assert!(grocery_list.items[0].purchased && !grocery_list.items[2].purchased);
+        assert!((grocery_list.receipt_total().unwrap() - 17.99).abs() < 1e-9);
+        assert_eq!(prices.latest("milk"), Some(4.99));
+    }
+
+    #[test]
+    fn test_format_price_history() {
+        let mut prices = PriceHistory::default();
+        assert!(format_price_history(&prices, None).unwrap().starts_with("No prices recorded yet."));
+        prices.record("Milk", 2.0, NaiveDate::from_ymd_opt(2023, 1, 3).unwrap()).unwrap();
+        prices.record("milk", 2.5, NaiveDate::from_ymd_opt(2023, 1, 10).unwrap()).unwrap();
+        prices.record("Eggs", 3.0, NaiveDate::from_ymd_opt(2023, 1, 10).unwrap()).unwrap();
+
+        assert_eq!(
+            format_price_history(&prices, Some("Milk")).unwrap(),
+            "Prices paid for Milk:\n  2023-01-03  2.00\n  2023-01-10  2.50 (+25%)\n"
+        );
+        assert_eq!(format_price_history(&prices, None).unwrap(), "Eggs: 3.00 on 2023-01-10\nMilk: 2.50 on 2023-01-10\n");
+        assert_eq!(format_price_history(&prices, Some("bread")).unwrap_err().kind, error::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_parse_price() {
+        assert_eq!(parse_price("3.50"), Ok(3.5));
+        assert_eq!(parse_price("0"), Ok(0.0));
+        for price in ["-1", "NaN", "inf", "lots"] {
+            assert!(parse_price(price).is_err(), "{} was accepted", price);
+        }
+        assert_eq!(parse_price("NaN").unwrap_err(), "NaN isn't a number");
+        assert!(Args::try_parse_from(["mealplan", "grocery", "check", "milk", "--price", "NaN"]).is_err());
+        assert!(Args::try_parse_from(["mealplan", "grocery", "cost", "set", "milk", "inf"]).is_err());
+        assert!(Args::try_parse_from(["mealplan", "grocery", "cost", "set", "milk", "2.25"]).is_ok());
+    }
+
+    #[test]
+    fn test_format_cart() {
+        let mut grocery_list = GroceryList::new(Local::now().date_naive());
+        grocery_add(&mut grocery_list, "2 cups flour", Some("baking, dry".to_string())).unwrap();
+        grocery_add(&mut grocery_list, "6 eggs", None).unwrap();
+        grocery_add(&mut grocery_list, "Paper towels", None).unwrap();
+        let items = grocery_list.cart_items();
+
+        assert_eq!(
+            format_cart(&items, "csv").unwrap(),
+            "name,quantity,unit,note\nflour,2,cup,\"baking, dry\"\neggs,6,,\nPaper towels,1,,\n"
+        );
+        assert_eq!(format_cart(&items, "lines").unwrap(), "2 cup flour\n6 eggs\nPaper towels\n");
+        let json: serde_json::Value = serde_json::from_str(&format_cart(&items, "JSON").unwrap()).unwrap();
+        assert_eq!(json[0]["unit"], "cup");
+        assert_eq!(json[2]["note"], serde_json::Value::Null);
+        assert!(format_cart(&items, "xml").is_err());
+    }
+
+    #[test]
+    fn test_meal_ingredients_until_shopping_day() {
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        for (day, description) in [(Weekday::Tue, "Tacos"), (Weekday::Thu, "Chili"), (Weekday::Sat, "Tacos")] {
+            meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(day), "Al".to_string(), description.to_string()));
+        }
+        let mut book = RecipeBook::default();
+        for (name, ingredients) in [("Tacos", ["1 lb beef", "8 tortillas"]), ("Chili", ["1 lb beef", "2 cans beans"])] {
+            book.add(Recipe { name: name.to_string(), tags: Vec::new(), ingredients: ingredients.iter().map(|i| i.to_string()).collect(), link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() });
+        }
+        let grocery_list = GroceryList::new(meal_plan.week_start_date);
+        let tuesday = NaiveDate::from_ymd_opt(2023, 1, 3).unwrap();
+
+        // Shopping again on Friday covers Tuesday to Thursday
+        let output = format_meal_ingredients(&grocery_list, &meal_plan, &book, &Attendance::default(), &[Weekday::Fri], tuesday);
+        assert_eq!(output, "\nAlso needed for meals Tue Jan 3 to Thu Jan 5:\n  - 2 lb beef\n  - 8 tortillas\n  - 2 cans beans\n");
+        // Without shopping days the rest of the week is covered from its start
+        let output = format_meal_ingredients(&grocery_list, &meal_plan, &book, &Attendance::default(), &[], tuesday);
+        assert!(output.starts_with("\nAlso needed for meals Mon Jan 2 to Sun Jan 8:\n  - 3 lb beef\n  - 16 tortillas\n"));
+        // Nothing is listed once the week is over
+        assert_eq!(format_meal_ingredients(&grocery_list, &meal_plan, &book, &Attendance::default(), &[Weekday::Fri], NaiveDate::from_ymd_opt(2023, 1, 9).unwrap()), "");
+    }
+
+    #[test]
+    fn test_bundle_export_import() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let bundle_path = temp_dir.path().join("week.mpb");
+
+        let mut meal_plan = MealPlan::new(Local::now().date_naive());
+        add_meal(&mut meal_plan, "Dinner".to_string(), "Monday".to_string(), "John".to_string(), "Pasta".to_string(), "tester").unwrap();
+        let mut book = RecipeBook::default();
+        book.add(Recipe { name: "Pasta".to_string(), tags: Vec::new(), ingredients: vec!["spaghetti".to_string()], link: None, servings: None, photo: None, steps: Vec::new(), equipment: Vec::new() });
+        assert!(bundle_export(&meal_plan, &book, &bundle_path, &mut Scripted::default()).is_ok());
+        assert!(bundle_path.exists());
+
+        // Importing into a plan without collisions adds the meals without prompting
+        let mut other_plan = MealPlan::new(Local::now().date_naive());
+        add_meal(&mut other_plan, "Lunch".to_string(), "Monday".to_string(), "Alice".to_string(), "Soup".to_string(), "tester").unwrap();
+        let mut other_book = RecipeBook::default();
+        assert!(bundle_import(&mut other_plan, &mut other_book, &bundle_path, Some(duplicates::Strategy::Skip), &mut Scripted::default()).is_ok());
+        assert_eq!(other_plan.meals().len(), 2);
+        assert!(other_plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).is_some());
+        // The recipe for the bundled meal comes along
+        assert_eq!(other_book.find("Pasta").unwrap().ingredients, vec!["spaghetti".to_string()]);
+
+        // Missing bundles are reported as errors
+        let missing = temp_dir.path().join("missing.mpb");
+        assert!(bundle_import(&mut other_plan, &mut other_book, &missing, None, &mut Scripted::default()).is_err());
+    }
+
     #[test]
     fn test_error_handling() {
         // Test handling of invalid inputs
@@ -983,10 +10464,11 @@ mod tests {
             "InvalidMealType".to_string(),
             "Monday".to_string(),
             "John".to_string(),
-            "Test Meal".to_string()
+            "Test Meal".to_string(),
+            "tester"
         );
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Invalid meal type"));
+        assert!(result.unwrap_err().message.contains("Invalid meal type"));
         
         // Invalid day
         let result = add_meal(
@@ -994,10 +10476,11 @@ mod tests {
             "Dinner".to_string(),
             "InvalidDay".to_string(),
             "John".to_string(),
-            "Test Meal".to_string()
+            "Test Meal".to_string(),
+            "tester"
         );
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Invalid day format"));
+        assert!(result.unwrap_err().message.contains("Invalid day format"));
         
         // Non-existent meal for edit
         let result = edit_meal(
@@ -1005,18 +10488,22 @@ mod tests {
             "Breakfast".to_string(),
             "Monday".to_string(),
             Some("Alice".to_string()),
-            None
+            None,
+            &Completions::default(),
+            "tester",
+            &mut Scripted::default()
         );
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("No Breakfast meal found"));
+        assert!(result.unwrap_err().message.contains("No Breakfast meal found"));
         
         // Non-existent meal for remove
         let result = remove_meal(
             &mut meal_plan,
             "Lunch".to_string(),
-            "Tuesday".to_string()
+            "Tuesday".to_string(),
+            &mut Scripted::default()
         );
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("No Lunch meal found"));
+        assert!(result.unwrap_err().message.contains("No Lunch meal found"));
     }
 }
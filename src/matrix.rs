@@ -0,0 +1,313 @@
+use crate::http::{self, Body, Delivery, Request};
+use crate::secrets::{self, Integration};
+use crate::sheets::percent_encode;
+use chrono::{NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// What messages to the bot start with
+pub const COMMAND_PREFIX: &str = "!meal";
+
+/// Messages sent by this process, so each gets its own transaction ID
+static SENT: AtomicU64 = AtomicU64::new(0);
+
+fn default_post_at() -> String {
+    "07:00".to_string()
+}
+
+/// The Matrix room the daily menu is posted to, kept in the configuration's `matrix`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatrixConfig {
+    /// Address of the homeserver, e.g. https://matrix.example.org
+    pub homeserver: String,
+    /// ID of the room, e.g. !abcdef:example.org
+    pub room_id: String,
+    /// Access token of the account the bot posts as. Leave it out to use the one stored with
+    /// `mealplan auth set matrix`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub access_token: String,
+    /// Local time to post the day's menu at, e.g. 07:00
+    #[serde(default = "default_post_at")]
+    pub post_at: String,
+}
+
+impl MatrixConfig {
+    pub fn post_time(&self) -> Result<NaiveTime, String> {
+        NaiveTime::parse_from_str(&self.post_at, "%H:%M")
+            .map_err(|_| format!("The time {:?} should be written as HH:MM, e.g. 07:00.", self.post_at))
+    }
+}
+
+/// A text message posted in the room
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoomMessage {
+    pub sender: String,
+    pub body: String,
+}
+
+/// What a sync returned: where the next one picks up, and the room's new messages
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncBatch {
+    pub next_batch: String,
+    pub messages: Vec<RoomMessage>,
+}
+
+/// Where the bot left off, kept in matrix.json in the storage path so a restart neither
+/// answers old commands again nor posts the menu twice
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BotState {
+    pub next_batch: Option<String>,
+    pub last_posted: Option<NaiveDate>,
+}
+
+impl BotState {
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the state from a JSON file, which doesn't exist before the bot first runs
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+/// Talks to the homeserver through the client-server API
+pub struct MatrixClient<'a> {
+    config: &'a MatrixConfig,
+}
+
+impl<'a> MatrixClient<'a> {
+    pub fn new(config: &'a MatrixConfig) -> Self {
+        Self { config }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/_matrix/client/v3/{}", self.config.homeserver.trim_end_matches('/'), path)
+    }
+
+    fn authorization(&self) -> String {
+        match self.config.access_token.as_str() {
+            "" => format!("Bearer {}", secrets::lookup(Integration::Matrix).unwrap_or_default()),
+            token => format!("Bearer {}", token),
+        }
+    }
+
+    /// The ID of the account the access token belongs to
+    pub fn whoami(&self) -> io::Result<String> {
+        let request = Request::new("GET", self.url("account/whoami")).header("Authorization", &self.authorization());
+        let response: Value = http::send(&request).map_err(|e| api_error(*e))?.into_json()?;
+        response["user_id"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "The homeserver didn't say who the access token belongs to"))
+    }
+
+    /// Posts a text message to the room. One the homeserver can't be reached for waits in
+    /// the outbox; its transaction ID keeps it from being posted twice.
+    pub fn send_text(&self, body: &str) -> io::Result<()> {
+        let txn_id = format!("mealplan-{}-{}", chrono::Utc::now().timestamp_millis(), SENT.fetch_add(1, Ordering::Relaxed));
+        let path = format!("rooms/{}/send/m.room.message/{}", percent_encode(&self.config.room_id), txn_id);
+        let request = Request::new("PUT", self.url(&path))
+            .header("Authorization", &self.authorization())
+            .body(Body::Json(serde_json::json!({ "msgtype": "m.text", "body": body })));
+        if http::push(request, &format!("Matrix message to {}", self.config.room_id)).map_err(|e| api_error(*e))? == Delivery::Queued {
+            eprintln!("Couldn't reach {}; the message waits in the outbox until it can be sent.", self.config.homeserver);
+        }
+        Ok(())
+    }
+
+    /// Waits up to `timeout_ms` for new messages in the room since the last sync. Without
+    /// `since`, returns no messages, only where to start from.
+    pub fn sync(&self, since: Option<&str>, timeout_ms: u64) -> io::Result<SyncBatch> {
+        let timeline_limit = if since.is_some() { 50 } else { 0 };
+        let filter = serde_json::json!({
+            "presence": { "types": [] },
+            "account_data": { "types": [] },
+            "room": {
+                "rooms": [self.config.room_id],
+                "state": { "types": [] },
+                "ephemeral": { "types": [] },
+                "timeline": { "limit": timeline_limit, "types": ["m.room.message"] },
+            },
+        });
+        let mut request = Request::new("GET", self.url("sync"))
+            .extra_wait(std::time::Duration::from_millis(timeout_ms))
+            .header("Authorization", &self.authorization())
+            .query("timeout", &timeout_ms.to_string())
+            .query("filter", &filter.to_string());
+        if let Some(since) = since {
+            request = request.query("since", since);
+        }
+        let response: Value = http::send(&request).map_err(|e| api_error(*e))?.into_json()?;
+        let next_batch = response["next_batch"]
+            .as_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "The sync response has no next_batch"))?
+            .to_string();
+        let messages = if since.is_some() { room_messages(&response, &self.config.room_id) } else { Vec::new() };
+        Ok(SyncBatch { next_batch, messages })
+    }
+}
+
+/// Picks the text messages posted in the room out of a sync response
+pub fn room_messages(response: &Value, room_id: &str) -> Vec<RoomMessage> {
+    let events = response["rooms"]["join"][room_id]["timeline"]["events"].as_array();
+    events
+        .into_iter()
+        .flatten()
+        .filter(|event| event["type"] == "m.room.message" && event["content"]["msgtype"] == "m.text")
+        .filter_map(|event| {
+            Some(RoomMessage {
+                sender: event["sender"].as_str()?.to_string(),
+                body: event["content"]["body"].as_str()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Turns a failed request into an error that includes the homeserver's explanation, if any
+fn api_error(error: ureq::Error) -> io::Error {
+    match error {
+        ureq::Error::Status(code, response) => {
+            let body = response.into_string().unwrap_or_default();
+            let message = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|json| json["error"].as_str().map(str::to_string))
+                .unwrap_or(body);
+            let kind = match code {
+                401 | 403 => io::ErrorKind::PermissionDenied,
+                404 => io::ErrorKind::NotFound,
+                _ => io::ErrorKind::Other,
+            };
+            io::Error::new(kind, format!("The homeserver returned {}: {}", code, message.trim()))
+        }
+        ureq::Error::Transport(transport) => io::Error::other(transport.to_string()),
+    }
+}
+
+/// A command posted to the bot
+#[derive(Debug, Clone, PartialEq)]
+pub enum BotCommand {
+    /// What's for dinner today
+    Tonight,
+    /// The whole day's menu
+    Today,
+    /// Plan a meal; without a cook, the rotation picks one or the sender cooks
+    Add { meal_type: String, day: String, description: String, cook: Option<String> },
+    Help,
+}
+
+pub const BOT_HELP: &str = "!meal tonight: what's for dinner today
+!meal today: the day's menu
+!meal add <meal type> <day> <description> [by <cook>]: plan a meal, e.g. !meal add dinner fri Tacos by Sam
+!meal help: this list";
+
+/// Reads a command from a message, or None when the message isn't for the bot
+pub fn parse_command(body: &str) -> Option<Result<BotCommand, String>> {
+    let rest = body.trim().strip_prefix(COMMAND_PREFIX)?;
+    if !(rest.is_empty() || rest.starts_with(char::is_whitespace)) {
+        return None;
+    }
+    let mut words = rest.split_whitespace();
+    let command = match words.next().map(str::to_lowercase).as_deref() {
+        None | Some("help") => Ok(BotCommand::Help),
+        Some("tonight") => Ok(BotCommand::Tonight),
+        Some("today") => Ok(BotCommand::Today),
+        Some("add") => {
+            let words: Vec<&str> = words.collect();
+            if words.len() < 3 {
+                return Some(Err("Say what to add, e.g. !meal add dinner fri Tacos".to_string()));
+            }
+            let rest = &words[2..];
+            let (description, cook) = match rest.iter().rposition(|word| word.eq_ignore_ascii_case("by")) {
+                Some(by) if by > 0 && by + 1 < rest.len() => (rest[..by].join(" "), Some(rest[by + 1..].join(" "))),
+                _ => (rest.join(" "), None),
+            };
+            Ok(BotCommand::Add { meal_type: words[0].to_string(), day: words[1].to_string(), description, cook })
+        }
+        Some(other) => Err(format!("Unknown command {:?}. Try !meal help.", other)),
+    };
+    Some(command)
+}
+
+/// The name to record as the cook for a Matrix user, from the local part of their ID
+pub fn sender_name(sender: &str) -> String {
+    let local = sender.trim_start_matches('@').split(':').next().unwrap_or_default();
+    let mut chars = local.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => sender.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("hello"), None);
+        assert_eq!(parse_command("!meals"), None);
+        assert_eq!(parse_command("!meal"), Some(Ok(BotCommand::Help)));
+        assert_eq!(parse_command(" !meal Tonight "), Some(Ok(BotCommand::Tonight)));
+        assert_eq!(parse_command("!meal add dinner fri Tacos by Sam Lee"), Some(Ok(BotCommand::Add {
+            meal_type: "dinner".to_string(),
+            day: "fri".to_string(),
+            description: "Tacos".to_string(),
+            cook: Some("Sam Lee".to_string()),
+        })));
+        assert_eq!(parse_command("!meal add lunch mon Pick up by"), Some(Ok(BotCommand::Add {
+            meal_type: "lunch".to_string(),
+            day: "mon".to_string(),
+            description: "Pick up by".to_string(),
+            cook: None,
+        })));
+        assert!(matches!(parse_command("!meal add dinner"), Some(Err(_))));
+        assert!(matches!(parse_command("!meal dance"), Some(Err(_))));
+        assert_eq!(sender_name("@sam:example.org"), "Sam");
+    }
+
+    #[test]
+    fn test_room_messages() {
+        let response = serde_json::json!({
+            "next_batch": "s2",
+            "rooms": { "join": { "!room:example.org": { "timeline": { "events": [
+                { "type": "m.room.message", "sender": "@sam:example.org", "content": { "msgtype": "m.text", "body": "!meal tonight" } },
+                { "type": "m.room.message", "sender": "@sam:example.org", "content": { "msgtype": "m.image", "body": "photo.jpg" } },
+                { "type": "m.room.member", "sender": "@alex:example.org", "content": { "membership": "join" } },
+            ] } } } },
+        });
+        assert_eq!(room_messages(&response, "!room:example.org"), vec![RoomMessage {
+            sender: "@sam:example.org".to_string(),
+            body: "!meal tonight".to_string(),
+        }]);
+        assert!(room_messages(&response, "!other:example.org").is_empty());
+    }
+
+    #[test]
+    fn test_bot_state_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("matrix.json");
+        assert_eq!(BotState::load_from_json(&path).unwrap().next_batch, None);
+
+        let state = BotState { next_batch: Some("s2".to_string()), last_posted: NaiveDate::from_ymd_opt(2023, 1, 2) };
+        state.save_to_json(&path).unwrap();
+        let loaded = BotState::load_from_json(&path).unwrap();
+        assert_eq!(loaded.next_batch, state.next_batch);
+        assert_eq!(loaded.last_posted, state.last_posted);
+    }
+}
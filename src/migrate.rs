@@ -0,0 +1,209 @@
+use crate::days;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::{Map, Value};
+
+/// Names older versions and other tools used for a meal's description
+const DESCRIPTION_KEYS: [&str; 4] = ["name", "meal", "title", "dish"];
+
+/// Names older versions used for a meal's cook
+const COOK_KEYS: [&str; 2] = ["chef", "cooked_by"];
+
+/// Names older versions used for the plan's week start date
+const WEEK_KEYS: [&str; 2] = ["week_start", "week_of"];
+
+/// Upgrades a plan file's JSON from older layouts to the current one, returning what was
+/// changed. A plan already in the current layout is left alone and nothing is returned.
+/// `fallback_week` is the week start used when the file doesn't record one.
+pub fn migrate_plan(value: &mut Value, fallback_week: NaiveDate, now: DateTime<Utc>) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    // The earliest files were just a list of meals
+    if let Value::Array(meals) = value {
+        *value = Value::Object(Map::from_iter([("meals".to_string(), Value::Array(std::mem::take(meals)))]));
+        changes.push("Wrapped the list of meals in a plan".to_string());
+    }
+    let Value::Object(plan) = value else {
+        return changes;
+    };
+
+    if !plan.contains_key("week_start_date") {
+        match rename_first(plan, &WEEK_KEYS, "week_start_date") {
+            Some(old) => changes.push(format!("Renamed {} to week_start_date", old)),
+            None => {
+                plan.insert("week_start_date".to_string(), Value::String(fallback_week.format("%Y-%m-%d").to_string()));
+                changes.push(format!("Set the missing week start date to {}", fallback_week));
+            }
+        }
+    }
+
+    match plan.get("last_modified") {
+        None | Some(Value::Null) => {
+            plan.insert("last_modified".to_string(), now.timestamp().into());
+            changes.push("Set the missing last modified time".to_string());
+        }
+        Some(Value::String(text)) => {
+            let timestamp = DateTime::parse_from_rfc3339(text).map(|t| t.timestamp()).unwrap_or(now.timestamp());
+            plan.insert("last_modified".to_string(), timestamp.into());
+            changes.push("Converted the last modified time to a timestamp".to_string());
+        }
+        Some(_) => {}
+    }
+
+    if !plan.contains_key("meals") {
+        plan.insert("meals".to_string(), Value::Array(Vec::new()));
+        changes.push("Added the missing list of meals".to_string());
+    }
+    if let Some(Value::Array(meals)) = plan.get_mut("meals") {
+        for (n, meal) in meals.iter_mut().enumerate() {
+            if let Value::Object(meal) = meal {
+                changes.extend(migrate_meal(meal).into_iter().map(|change| format!("Meal {}: {}", n + 1, change)));
+            }
+        }
+    }
+    changes
+}
+
+fn migrate_meal(meal: &mut Map<String, Value>) -> Vec<String> {
+    let mut changes = Vec::new();
+
+    if !meal.contains_key("description") {
+        match rename_first(meal, &DESCRIPTION_KEYS, "description") {
+            Some(old) => changes.push(format!("renamed {} to description", old)),
+            None => {
+                meal.insert("description".to_string(), Value::String(String::new()));
+                changes.push("added an empty description".to_string());
+            }
+        }
+    }
+    if !meal.contains_key("cook") {
+        match rename_first(meal, &COOK_KEYS, "cook") {
+            Some(old) => changes.push(format!("renamed {} to cook", old)),
+            None => {
+                meal.insert("cook".to_string(), Value::String(String::new()));
+                changes.push("added an empty cook".to_string());
+            }
+        }
+    }
+    if !meal.contains_key("meal_type") && rename_first(meal, &["type"], "meal_type").is_some() {
+        changes.push("renamed type to meal_type".to_string());
+    }
+    if let Some(Value::String(text)) = meal.get("meal_type") {
+        let canonical = capitalize(text);
+        if &canonical != text {
+            changes.push(format!("changed the meal type {:?} to {:?}", text, canonical));
+            meal.insert("meal_type".to_string(), Value::String(canonical));
+        }
+    }
+
+    if !meal.contains_key("day") && rename_first(meal, &["date"], "day").is_some() {
+        changes.push("renamed date to day".to_string());
+    }
+    if let Some(Value::String(text)) = meal.get("day") {
+        if let Some(day) = parse_day(text) {
+            changes.push(format!("changed the day {:?} to the current format", text));
+            meal.insert("day".to_string(), day);
+        }
+    }
+
+    if let Some(Value::String(text)) = meal.get("tags") {
+        let tags: Vec<Value> = text
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(|tag| Value::String(tag.to_string()))
+            .collect();
+        meal.insert("tags".to_string(), Value::Array(tags));
+        changes.push("split the tags into a list".to_string());
+    }
+
+    for key in ["created_at", "updated_at"] {
+        if let Some(Value::String(text)) = meal.get(key) {
+            let timestamp = DateTime::parse_from_rfc3339(text).map(|t| Value::from(t.timestamp())).unwrap_or(Value::Null);
+            meal.insert(key.to_string(), timestamp);
+            changes.push(format!("converted {} to a timestamp", key));
+        }
+    }
+    changes
+}
+
+/// Moves the first of `old_keys` present to `new_key`, returning the key that was moved
+fn rename_first(map: &mut Map<String, Value>, old_keys: &[&str], new_key: &str) -> Option<String> {
+    let old = old_keys.iter().find(|key| map.contains_key(**key))?;
+    let value = map.remove(*old)?;
+    map.insert(new_key.to_string(), value);
+    Some(old.to_string())
+}
+
+fn capitalize(text: &str) -> String {
+    let lower = text.trim().to_lowercase();
+    let mut chars = lower.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => lower,
+    }
+}
+
+/// Converts a day written as a weekday name or a date into the current layout
+fn parse_day(text: &str) -> Option<Value> {
+    if let Ok(date) = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d") {
+        return Some(serde_json::json!({ "Date": date }));
+    }
+    let weekday = days::parse_weekday(text)?;
+    Some(serde_json::json!({ "Weekday": weekday }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, MealPlan, MealType};
+    use chrono::{TimeZone, Weekday};
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2023, 1, 5, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_migrate_old_layout() {
+        let mut value = serde_json::json!({
+            "week_start": "2023-01-02",
+            "last_modified": "2023-01-03T08:00:00Z",
+            "meals": [
+                {"type": "dinner", "day": "monday", "name": "Tacos", "chef": "Alice"},
+                {"meal_type": "Lunch", "date": "2023-01-04", "description": "Soup", "cook": "Bob", "tags": "quick, soup"},
+            ]
+        });
+        let changes = migrate_plan(&mut value, NaiveDate::from_ymd_opt(2020, 1, 6).unwrap(), now());
+        assert_eq!(changes[0], "Renamed week_start to week_start_date");
+        assert!(changes.contains(&"Meal 1: renamed name to description".to_string()));
+        assert!(changes.contains(&"Meal 2: split the tags into a list".to_string()));
+
+        let plan: MealPlan = serde_json::from_value(value).unwrap();
+        assert_eq!(plan.week_start_date, NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        assert_eq!(plan.last_modified, Utc.with_ymd_and_hms(2023, 1, 3, 8, 0, 0).unwrap());
+        assert_eq!(plan.meals()[0].meal_type, MealType::Dinner);
+        assert_eq!(plan.meals()[0].day, Day::Weekday(Weekday::Mon));
+        assert_eq!((plan.meals()[0].description.as_str(), plan.meals()[0].cook.as_str()), ("Tacos", "Alice"));
+        assert_eq!(plan.meals()[1].day, Day::Date(NaiveDate::from_ymd_opt(2023, 1, 4).unwrap()));
+        assert_eq!(plan.meals()[1].tags, vec!["quick", "soup"]);
+    }
+
+    #[test]
+    fn test_migrate_list_of_meals() {
+        let mut value = serde_json::json!([{"meal_type": "Breakfast", "day": {"Weekday": "Tue"}, "meal": "Oatmeal"}]);
+        let changes = migrate_plan(&mut value, NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(), now());
+        assert_eq!(changes.len(), 5);
+        let plan: MealPlan = serde_json::from_value(value).unwrap();
+        assert_eq!(plan.week_start_date, NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        assert_eq!(plan.meals()[0].cook, "");
+    }
+
+    #[test]
+    fn test_current_layout_is_unchanged() {
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(crate::models::Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Al".to_string(), "Tacos".to_string()));
+        let mut value = serde_json::to_value(&plan).unwrap();
+        let before = value.clone();
+        assert!(migrate_plan(&mut value, NaiveDate::from_ymd_opt(2020, 1, 6).unwrap(), now()).is_empty());
+        assert_eq!(value, before);
+    }
+}
@@ -1,190 +1,178 @@
 #![allow(dead_code)]
-use chrono::{DateTime, Utc, NaiveDate, Weekday};
+use crate::days;
+use crate::email::EmailSettings;
+use crate::http::HttpSettings;
+use crate::storage;
+use crate::matrix::MatrixConfig;
+use crate::notify::NotifyTarget;
+use crate::solver::PlanRule;
+use chrono::{NaiveDate, Utc, Weekday};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+pub use mealplan::plan::*;
 
-/// Represents the type of meal
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-pub enum MealType {
-    Breakfast,
-    Lunch,
-    Dinner,
-    Snack,
+/// Configuration settings for the meal plan application
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub meal_plan_storage_path: PathBuf,
+    pub current_week_start_date: NaiveDate,
+    /// Maps grocery items or categories to the store they should be bought at
+    #[serde(default)]
+    pub store_preferences: HashMap<String, String>,
+    /// Name recorded on meals you add or edit; defaults to `$USER` when unset
+    #[serde(default)]
+    pub user_name: Option<String>,
+    /// Identifies this device when merging plans; defaults to the host name when unset
+    #[serde(default)]
+    pub replica_id: Option<String>,
+    /// The household's cooks. When empty, any name is accepted as a cook.
+    #[serde(default)]
+    pub cooks: Vec<CookProfile>,
+    /// Cooks taking turns when a meal is added with `--cook auto`, in rotation order.
+    /// Falls back to the order of `cooks` when empty.
+    #[serde(default)]
+    pub cook_rotation: Vec<String>,
+    /// How many previous versions of the meal plan to keep for recovery
+    #[serde(default = "default_backup_count")]
+    pub backup_count: usize,
+    /// Days a removed meal stays in the trash before it is deleted for good
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u32,
+    /// Weekly grocery budget that `mealplan review` compares spending against
+    #[serde(default)]
+    pub weekly_budget: Option<f64>,
+    /// Monthly budget for meals from restaurants and takeout places, alerted on by
+    /// `mealplan check` and `mealplan review`
+    #[serde(default)]
+    pub monthly_takeout_budget: Option<f64>,
+    /// How many people eat each meal, for working out what a recipe leaves over
+    #[serde(default)]
+    pub household_size: Option<u32>,
+    /// Days the household goes shopping. `mealplan grocery` covers the meals from today
+    /// until the next one, or the whole week when empty.
+    #[serde(default)]
+    pub shopping_days: Vec<Weekday>,
+    /// Meal shortcuts usable as the description in `mealplan add`, keyed by lowercase name
+    #[serde(default)]
+    pub aliases: BTreeMap<String, MealAlias>,
+    /// Cook used when a meal is added without one, by meal type, e.g. breakfast = Alex.
+    /// "rotate" takes turns as `--cook auto` does.
+    #[serde(default)]
+    pub default_cooks: BTreeMap<String, String>,
+    /// Meals every new week starts with, by day and then meal type, e.g. saturday =
+    /// {breakfast = Pancakes}. The description may be an alias.
+    #[serde(default)]
+    pub day_templates: BTreeMap<String, BTreeMap<String, String>>,
+    /// Household rules `mealplan generate` plans the week by
+    #[serde(default)]
+    pub plan_rules: Vec<PlanRule>,
+    /// How many dishes each appliance takes at once, e.g. stove = 4, for `mealplan check`.
+    /// Appliances not listed take one, and an oven only takes dishes at one temperature.
+    #[serde(default)]
+    pub appliances: BTreeMap<String, u32>,
+    /// Push services that reminders and other notifications go to. Notifications are
+    /// shown on the desktop when empty.
+    #[serde(default)]
+    pub notify: Vec<NotifyTarget>,
+    /// Matrix room to post the daily menu to and take commands from with `mealplan matrix run`
+    #[serde(default)]
+    pub matrix: Option<MatrixConfig>,
+    /// Timeouts, retries and queueing for requests to push services, Matrix and Google
+    #[serde(default)]
+    pub http: HttpSettings,
+    /// Mail server the weekly digest is sent through with `mealplan digest`
+    #[serde(default)]
+    pub email: Option<EmailSettings>,
 }
 
-impl std::fmt::Display for MealType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            MealType::Breakfast => write!(f, "Breakfast"),
-            MealType::Lunch => write!(f, "Lunch"),
-            MealType::Dinner => write!(f, "Dinner"),
-            MealType::Snack => write!(f, "Snack"),
-        }
-    }
+fn default_backup_count() -> usize {
+    storage::DEFAULT_BACKUP_COUNT
 }
 
-/// Represents a day, which can be a weekday or a specific date
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-pub enum Day {
-    Weekday(Weekday),
-    Date(NaiveDate),
-}
+/// Days removed meals are kept when the configuration doesn't say
+pub const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
 
-impl std::fmt::Display for Day {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Day::Weekday(weekday) => write!(f, "{:?}", weekday),
-            Day::Date(date) => write!(f, "{}", date.format("%Y-%m-%d")),
-        }
-    }
+fn default_trash_retention_days() -> u32 {
+    DEFAULT_TRASH_RETENTION_DAYS
 }
 
-/// Represents a single meal entry
+/// A member of the household who cooks
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Meal {
-    pub meal_type: MealType,
-    pub day: Day,
-    pub cook: String,
-    pub description: String,
+pub struct CookProfile {
+    pub name: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    /// Free-form notes such as dietary needs or favourite dishes
+    #[serde(default)]
+    pub preferences: Vec<String>,
+    #[serde(default)]
+    pub unavailable_days: Vec<Weekday>,
+    /// What the cook takes on when cooks are picked automatically
+    #[serde(default, skip_serializing_if = "CookPrefs::is_empty")]
+    pub prefs: CookPrefs,
 }
 
-impl Meal {
-    /// Creates a new meal
-    pub fn new(meal_type: MealType, day: Day, cook: String, description: String) -> Self {
+impl CookProfile {
+    /// Creates a cook who is available every day
+    pub fn new(name: String) -> Self {
         Self {
-            meal_type,
-            day,
-            cook,
-            description,
+            name,
+            email: None,
+            preferences: Vec::new(),
+            unavailable_days: Vec::new(),
+            prefs: CookPrefs::default(),
         }
     }
 }
 
-/// Represents a week's meal plan
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MealPlan {
-    pub meals: Vec<Meal>,
-    pub week_start_date: NaiveDate,
-    #[serde(with = "chrono::serde::ts_seconds")]
-    pub last_modified: DateTime<Utc>,
+/// A cook's preferences, respected by `--cook auto` and `mealplan generate`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CookPrefs {
+    /// Tags or words of dishes the cook won't make, e.g. fish
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub avoid: Vec<String>,
+    /// Longest recipe the cook takes on, in minutes, going by its timed steps
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_minutes: Option<u32>,
+    /// The only weekdays the cook cooks on; every day when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
 }
 
-impl MealPlan {
-    /// Creates a new empty meal plan
-    pub fn new(week_start_date: NaiveDate) -> Self {
-        Self {
-            meals: Vec::new(),
-            week_start_date,
-            last_modified: Utc::now(),
-        }
-    }
-
-    /// Adds a meal to the plan
-    pub fn add_meal(&mut self, meal: Meal) {
-        self.meals.push(meal);
-        self.last_modified = Utc::now();
+impl CookPrefs {
+    pub fn is_empty(&self) -> bool {
+        self.avoid.is_empty() && self.max_minutes.is_none() && self.days.is_empty()
     }
 
-    /// Removes a meal from the plan
-    pub fn remove_meal(&mut self, meal_type: &MealType, day: &Day) -> Option<Meal> {
-        if let Some(index) = self.meals.iter().position(|m| &m.meal_type == meal_type && &m.day == day) {
-            let meal = self.meals.remove(index);
-            self.last_modified = Utc::now();
-            Some(meal)
-        } else {
-            None
-        }
-    }
-
-    /// Finds a meal in the plan
-    pub fn find_meal(&self, meal_type: &MealType, day: &Day) -> Option<&Meal> {
-        self.meals.iter().find(|m| &m.meal_type == meal_type && &m.day == day)
-    }
-
-    /// Saves the meal plan to a JSON file
-    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
-        let json = serde_json::to_string_pretty(self)?;
-        let mut file = File::create(path)?;
-        file.write_all(json.as_bytes())?;
-        Ok(())
-    }
-
-    /// Loads a meal plan from a JSON file
-    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
-        let mut file = File::open(path)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        let meal_plan: MealPlan = serde_json::from_str(&contents)?;
-        Ok(meal_plan)
+    pub fn cooks_on(&self, weekday: Weekday) -> bool {
+        self.days.is_empty() || self.days.contains(&weekday)
     }
 
-    /// Saves the meal plan to a Markdown file
-    pub fn save_to_markdown<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
-        let mut markdown = format!("# Meal Plan for Week of {}\n\n", self.week_start_date.format("%Y-%m-%d"));
-        
-        // Group meals by day
-        let mut meals_by_day: HashMap<&Day, Vec<&Meal>> = HashMap::new();
-        for meal in &self.meals {
-            meals_by_day.entry(&meal.day).or_default().push(meal);
-        }
-        
-        // Sort days
-        let mut days: Vec<&Day> = meals_by_day.keys().cloned().collect();
-        days.sort_by_key(|d| match d {
-            Day::Weekday(w) => format!("1{:?}", w),
-            Day::Date(date) => format!("0{}", date),
-        });
-        
-        for day in days {
-            markdown.push_str(&format!("## {}\n\n", day));
-            
-            if let Some(meals) = meals_by_day.get(day) {
-                for meal in meals {
-                    markdown.push_str(&format!("### {}\n", meal.meal_type));
-                    markdown.push_str(&format!("- Cook: {}\n", meal.cook));
-                    markdown.push_str(&format!("- Description: {}\n\n", meal.description));
-                }
-            }
-        }
-        
-        markdown.push_str(&format!("\n*Last modified: {}*", self.last_modified.format("%Y-%m-%d %H:%M:%S")));
-        
-        let mut file = File::create(path)?;
-        file.write_all(markdown.as_bytes())?;
-        Ok(())
+    /// Returns what the cook avoids about a dish: one of its tags, or a word of its
+    /// description
+    pub fn avoided_in(&self, description: &str, tags: &[String]) -> Option<&str> {
+        let words: Vec<String> = description
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        self.avoid.iter().map(|avoid| avoid.trim()).filter(|avoid| !avoid.is_empty()).find(|avoid| {
+            let avoid = avoid.to_lowercase();
+            tags.iter().any(|tag| tag.trim().eq_ignore_ascii_case(&avoid))
+                || words.iter().any(|word| *word == avoid || word.strip_suffix('s') == Some(avoid.as_str()))
+        })
     }
 
-    /// Loads a meal plan from a Markdown file (basic implementation)
-    /// Note: This is a simplified implementation and might not handle all edge cases
-    pub fn load_from_markdown<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
-        // For simplicity, we'll just check if the file exists and then suggest using JSON
-        // A full implementation would parse the Markdown structure
-        if !path.as_ref().exists() {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "Markdown file not found",
-            ));
-        }
-        
-        // This is a placeholder. In a real implementation, you would parse the Markdown
-        // and extract the meal plan data.
-        Err(std::io::Error::new(
-            std::io::ErrorKind::Unsupported,
-            "Loading from Markdown is not fully implemented. Please use JSON format.",
-        ))
+    /// Whether a recipe takes longer than the cook wants to spend. Dishes of unknown
+    /// length are never too long.
+    pub fn too_long(&self, minutes: Option<u32>) -> bool {
+        matches!((self.max_minutes, minutes), (Some(max), Some(minutes)) if minutes > max)
     }
 }
 
-/// Configuration settings for the meal plan application
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
-    pub meal_plan_storage_path: PathBuf,
-    pub current_week_start_date: NaiveDate,
-}
-
 impl Config {
     /// Creates a new configuration with default values
     pub fn new() -> Self {
@@ -201,9 +189,92 @@ impl Config {
         Self {
             meal_plan_storage_path: storage_path,
             current_week_start_date: Utc::now().date_naive(),
+            store_preferences: HashMap::new(),
+            user_name: None,
+            replica_id: None,
+            cooks: Vec::new(),
+            cook_rotation: Vec::new(),
+            backup_count: default_backup_count(),
+            trash_retention_days: default_trash_retention_days(),
+            weekly_budget: None,
+            monthly_takeout_budget: None,
+            household_size: None,
+            shopping_days: Vec::new(),
+            aliases: BTreeMap::new(),
+            default_cooks: BTreeMap::new(),
+            day_templates: BTreeMap::new(),
+            plan_rules: Vec::new(),
+            appliances: BTreeMap::new(),
+            notify: Vec::new(),
+            matrix: None,
+            http: HttpSettings::default(),
+            email: None,
+        }
+    }
+
+    /// Looks up a meal alias, ignoring case and surrounding whitespace
+    pub fn find_alias(&self, name: &str) -> Option<&MealAlias> {
+        self.aliases.get(&name.trim().to_lowercase())
+    }
+
+    /// The default cook for a meal type, if one is set; "auto" when cooks take turns
+    pub fn default_cook(&self, meal_type: &MealType) -> Option<String> {
+        let name = meal_type.to_string();
+        let cook = self.default_cooks.iter().find(|(key, _)| key.trim().eq_ignore_ascii_case(&name))?.1.trim();
+        match cook.to_lowercase().as_str() {
+            "" => None,
+            "rotate" | "rotation" | "auto" => Some("auto".to_string()),
+            _ => Some(cook.to_string()),
+        }
+    }
+
+    /// The meals templated for a weekday, as meal type and description pairs
+    pub fn day_template(&self, weekday: Weekday) -> Vec<(&str, &str)> {
+        self.day_templates
+            .iter()
+            .filter(|(day, _)| days::parse_weekday(day) == Some(weekday))
+            .flat_map(|(_, meals)| meals.iter().map(|(meal_type, description)| (meal_type.as_str(), description.as_str())))
+            .collect()
+    }
+
+    /// Finds a cook in the roster, matching the name case-insensitively
+    pub fn find_cook(&self, name: &str) -> Option<&CookProfile> {
+        self.cooks.iter().find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Finds a cook in the roster for updating
+    pub fn find_cook_mut(&mut self, name: &str) -> Option<&mut CookProfile> {
+        self.cooks.iter_mut().find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns true if the name may be assigned as a cook: anyone goes while the roster is empty
+    pub fn is_known_cook(&self, name: &str) -> bool {
+        self.cooks.is_empty() || self.find_cook(name).is_some()
+    }
+
+    /// Returns the order cooks take turns in, defaulting to the roster order
+    pub fn rotation(&self) -> Vec<String> {
+        if self.cook_rotation.is_empty() {
+            self.cooks.iter().map(|c| c.name.clone()).collect()
+        } else {
+            self.cook_rotation.clone()
         }
     }
 
+    /// Returns the weekdays a cook is unavailable
+    pub fn unavailable_days(&self, cook: &str) -> &[Weekday] {
+        self.find_cook(cook)
+            .map(|c| c.unavailable_days.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Returns true unless the cook has declared the weekday unavailable, or only cooks
+    /// on other days
+    pub fn is_cook_available(&self, cook: &str, weekday: Weekday) -> bool {
+        !self.unavailable_days(cook).contains(&weekday)
+            && self.find_cook(cook).is_none_or(|c| c.prefs.cooks_on(weekday))
+    }
+
     /// Saves the configuration to a JSON file
     pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
         let json = serde_json::to_string_pretty(self)?;
@@ -225,140 +296,8 @@ impl Config {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::ErrorKind;
     use tempfile::tempdir;
 
-    #[test]
-    fn test_meal_creation() {
-        let meal = Meal::new(
-            MealType::Dinner,
-            Day::Weekday(Weekday::Mon),
-            "John".to_string(),
-            "Steak with potatoes".to_string(),
-        );
-        
-        assert_eq!(meal.meal_type, MealType::Dinner);
-        assert_eq!(meal.cook, "John");
-        assert_eq!(meal.description, "Steak with potatoes");
-        
-        match meal.day {
-            Day::Weekday(day) => assert_eq!(day, Weekday::Mon),
-            _ => panic!("Expected Weekday"),
-        }
-    }
-
-    #[test]
-    fn test_meal_plan_operations() {
-        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
-        let mut plan = MealPlan::new(week_start);
-        
-        // Add a meal
-        let meal = Meal::new(
-            MealType::Lunch,
-            Day::Weekday(Weekday::Wed),
-            "Alice".to_string(),
-            "Caesar Salad".to_string(),
-        );
-        plan.add_meal(meal);
-        
-        // Find the meal
-        let found = plan.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Wed));
-        assert!(found.is_some());
-        assert_eq!(found.unwrap().cook, "Alice");
-        
-        // Remove the meal
-        let removed = plan.remove_meal(&MealType::Lunch, &Day::Weekday(Weekday::Wed));
-        assert!(removed.is_some());
-        
-        // Verify it's gone
-        let not_found = plan.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Wed));
-        assert!(not_found.is_none());
-    }
-
-    #[test]
-    fn test_json_serialization() {
-        let temp_dir = tempdir().unwrap();
-        let file_path = temp_dir.path().join("test_meal_plan.json");
-        
-        // Create a meal plan
-        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
-        let mut plan = MealPlan::new(week_start);
-        
-        let meal = Meal::new(
-            MealType::Breakfast,
-            Day::Date(NaiveDate::from_ymd_opt(2023, 1, 3).unwrap()),
-            "Bob".to_string(),
-            "Pancakes with syrup".to_string(),
-        );
-        plan.add_meal(meal);
-        
-        // Save to JSON
-        plan.save_to_json(&file_path).unwrap();
-        
-        // Load from JSON
-        let loaded_plan = MealPlan::load_from_json(&file_path).unwrap();
-        
-        // Verify data
-        assert_eq!(loaded_plan.week_start_date, week_start);
-        assert_eq!(loaded_plan.meals.len(), 1);
-        assert_eq!(loaded_plan.meals[0].cook, "Bob");
-    }
-
-    #[test]
-    fn test_markdown_export() {
-        let temp_dir = tempdir().unwrap();
-        let file_path = temp_dir.path().join("test_meal_plan.md");
-        
-        // Create a meal plan
-        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
-        let mut plan = MealPlan::new(week_start);
-        
-        let meal1 = Meal::new(
-            MealType::Breakfast,
-            Day::Weekday(Weekday::Mon),
-            "Charlie".to_string(),
-            "Oatmeal with berries".to_string(),
-        );
-        plan.add_meal(meal1);
-        
-        let meal2 = Meal::new(
-            MealType::Dinner,
-            Day::Weekday(Weekday::Mon),
-            "Diana".to_string(),
-            "Grilled chicken with vegetables".to_string(),
-        );
-        plan.add_meal(meal2);
-        
-        // Save to Markdown
-        plan.save_to_markdown(&file_path).unwrap();
-        
-        // Verify file exists
-        assert!(file_path.exists());
-        
-        // Read the file content to verify it contains expected text
-        let content = fs::read_to_string(&file_path).unwrap();
-        assert!(content.contains("# Meal Plan for Week of 2023-01-02"));
-        assert!(content.contains("## Mon"));
-        assert!(content.contains("### Breakfast"));
-        assert!(content.contains("- Cook: Charlie"));
-        assert!(content.contains("- Description: Oatmeal with berries"));
-        assert!(content.contains("### Dinner"));
-        assert!(content.contains("- Cook: Diana"));
-        assert!(content.contains("- Description: Grilled chicken with vegetables"));
-    }
-
-    #[test]
-    fn test_markdown_import_not_found() {
-        let temp_dir = tempdir().unwrap();
-        let file_path = temp_dir.path().join("nonexistent.md");
-        
-        let result = MealPlan::load_from_markdown(&file_path);
-        assert!(result.is_err());
-        
-        let err = result.unwrap_err();
-        assert_eq!(err.kind(), ErrorKind::NotFound);
-    }
-
     #[test]
     fn test_config() {
         let temp_dir = tempdir().unwrap();
@@ -376,4 +315,25 @@ mod tests {
         // Verify paths match
         assert_eq!(loaded_config.meal_plan_storage_path, config.meal_plan_storage_path);
     }
+
+    #[test]
+    fn test_cook_availability() {
+        let mut config = Config::new();
+        assert!(config.is_known_cook("Anyone"));
+
+        let mut alice = CookProfile::new("Alice".to_string());
+        alice.unavailable_days.push(Weekday::Fri);
+        config.cooks.push(alice);
+
+        assert!(!config.is_cook_available("alice", Weekday::Fri));
+        assert!(config.is_cook_available("Alice", Weekday::Mon));
+        assert!(config.is_cook_available("Bob", Weekday::Fri));
+        assert!(!config.is_known_cook("Bob"));
+        assert_eq!(config.rotation(), vec!["Alice".to_string()]);
+
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("config.json");
+        config.save(&file_path).unwrap();
+        assert_eq!(Config::load(&file_path).unwrap().unavailable_days("Alice"), &[Weekday::Fri]);
+    }
 }
@@ -0,0 +1,128 @@
+use crate::models::{Meal, MealPlan};
+use crate::spreadsheet::GRID_MEAL_TYPES;
+use chrono::{Duration, NaiveDate};
+
+/// Narrowest snippet that still fits a heading and a short dish
+pub const MIN_WIDTH: usize = 16;
+
+/// A piece of a meal that the snippet can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Type,
+    Dish,
+    Cook,
+}
+
+/// Parses the comma-separated fields to show, such as "type,dish,cook"
+pub fn parse_fields(text: &str) -> Result<Vec<Field>, String> {
+    let mut fields = Vec::new();
+    for name in text.split(',').map(|name| name.trim().to_lowercase()).filter(|name| !name.is_empty()) {
+        let field = match name.as_str() {
+            "type" | "meal_type" => Field::Type,
+            "dish" | "description" | "meal" => Field::Dish,
+            "cook" => Field::Cook,
+            other => return Err(format!("Unknown field '{}'. Use type, dish or cook.", other)),
+        };
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+    if fields.is_empty() {
+        return Err("Give at least one of type, dish or cook.".to_string());
+    }
+    Ok(fields)
+}
+
+/// Shortens text to a number of characters, marking the cut with an ellipsis
+fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+fn day_meals(meal_plan: &MealPlan, date: NaiveDate) -> Vec<&Meal> {
+    let mut meals: Vec<&Meal> = meal_plan.meals().iter().filter(|meal| meal_plan.date_of(&meal.day) == date).collect();
+    meals.sort_by_key(|meal| GRID_MEAL_TYPES.iter().position(|t| *t == meal.meal_type));
+    meals
+}
+
+/// One meal in the order of the fields, with the type as its initial and the cook in
+/// parentheses, e.g. "D Chili (Bob)"
+fn meal_text(meal_plan: &MealPlan, meal: &Meal, fields: &[Field]) -> String {
+    fields
+        .iter()
+        .filter_map(|field| match field {
+            Field::Type => meal.meal_type.to_string().chars().next().map(String::from),
+            Field::Dish => Some(meal_plan.render_description(meal).trim().to_string()),
+            Field::Cook if meal.cook.trim().is_empty() => None,
+            Field::Cook => Some(format!("({})", meal.cook.trim())),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Today's and tomorrow's meals as lines no wider than `width` characters, for /etc/motd
+/// or conky
+pub fn motd(meal_plan: &MealPlan, today: NaiveDate, width: usize, fields: &[Field]) -> String {
+    let mut lines = Vec::new();
+    for (label, date) in [("Today", today), ("Tomorrow", today + Duration::days(1))] {
+        lines.push(fit(&format!("{} ({})", label, date.format("%a %b %-d")), width));
+        let meals = day_meals(meal_plan, date);
+        if meals.is_empty() {
+            lines.push(fit("  Nothing planned", width));
+        }
+        for meal in meals {
+            lines.push(fit(&format!("  {}", meal_text(meal_plan, meal, fields)), width));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Today's and tomorrow's meals on one line no wider than `width` characters, for a tmux
+/// status line, e.g. "Today: D Chili | Tomorrow: D Tacos"
+pub fn motd_line(meal_plan: &MealPlan, today: NaiveDate, width: usize, fields: &[Field]) -> String {
+    let day = |label: &str, date: NaiveDate| {
+        let meals: Vec<String> = day_meals(meal_plan, date).iter().map(|meal| meal_text(meal_plan, meal, fields)).collect();
+        let meals = if meals.is_empty() { "-".to_string() } else { meals.join(", ") };
+        format!("{}: {}", label, meals)
+    };
+    fit(&format!("{} | {}", day("Today", today), day("Tomorrow", today + Duration::days(1))), width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, MealType};
+    use chrono::Weekday;
+
+    fn plan() -> MealPlan {
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Bob".to_string(), "Chili con carne with cornbread".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Breakfast, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Oatmeal".to_string()));
+        meal_plan
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        assert_eq!(parse_fields("dish, cook,dish").unwrap(), vec![Field::Dish, Field::Cook]);
+        assert!(parse_fields("dish,price").unwrap_err().contains("price"));
+        assert!(parse_fields(" , ").is_err());
+    }
+
+    #[test]
+    fn test_motd() {
+        let monday = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let fields = [Field::Type, Field::Dish, Field::Cook];
+        assert_eq!(
+            motd(&plan(), monday, 30, &fields),
+            "Today (Mon Jan 2)\n  B Oatmeal (Alice)\n  D Chili con carne with corn…\nTomorrow (Tue Jan 3)\n  Nothing planned"
+        );
+        assert!(motd(&plan(), monday, 30, &fields).lines().all(|line| line.chars().count() <= 30));
+
+        assert_eq!(motd_line(&plan(), monday, 80, &[Field::Dish]), "Today: Oatmeal, Chili con carne with cornbread | Tomorrow: -");
+        assert_eq!(motd_line(&plan(), monday, 20, &[Field::Dish]), "Today: Oatmeal, Chi…");
+    }
+}
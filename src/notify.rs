@@ -0,0 +1,361 @@
+use crate::http::{self, Body, Delivery, Request};
+use crate::secrets::{self, Integration};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Server used for ntfy targets that don't name one
+pub const DEFAULT_NTFY_SERVER: &str = "https://ntfy.sh";
+
+fn default_ntfy_server() -> String {
+    DEFAULT_NTFY_SERVER.to_string()
+}
+
+/// A push service to send notifications to, kept in the configuration's `notify`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "service", rename_all = "snake_case")]
+pub enum NotifyTarget {
+    /// A topic on an ntfy server; phones subscribed to it in the ntfy app get the notification
+    Ntfy {
+        #[serde(default = "default_ntfy_server")]
+        server: String,
+        topic: String,
+        /// Access token for a protected topic. Leave it out to use the one stored with
+        /// `mealplan auth set ntfy`.
+        #[serde(default)]
+        token: Option<String>,
+    },
+    /// An application on a Gotify server, identified by its app token
+    Gotify {
+        server: String,
+        /// Leave it out to use the one stored with `mealplan auth set gotify`
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        token: String,
+        #[serde(default)]
+        priority: Option<u8>,
+    },
+}
+
+/// Something that can deliver a notification
+pub trait Notifier {
+    /// Where the notification goes, for messages
+    fn describe(&self) -> String;
+    fn send(&self, title: &str, message: &str) -> Result<(), String>;
+}
+
+/// The desktop's own notifier
+pub struct Desktop;
+
+impl Notifier for Desktop {
+    fn describe(&self) -> String {
+        "the desktop".to_string()
+    }
+
+    fn send(&self, title: &str, message: &str) -> Result<(), String> {
+        let mut command = if cfg!(target_os = "macos") {
+            let script = format!("display notification {:?} with title {:?}", message, title);
+            let mut command = std::process::Command::new("osascript");
+            command.args(["-e", &script]);
+            command
+        } else if cfg!(windows) {
+            return Err("Desktop notifications aren't supported on Windows yet.".to_string());
+        } else {
+            let mut command = std::process::Command::new("notify-send");
+            command.args([title, message]);
+            command
+        };
+        let status = command.status().map_err(|e| format!("Failed to run the notifier: {}", e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("The notifier exited with {}", status))
+        }
+    }
+}
+
+/// What a push service is sent for one notification
+#[derive(Debug, Clone, PartialEq)]
+pub struct PushRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: PushBody,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PushBody {
+    Text(String),
+    Json(serde_json::Value),
+}
+
+impl NotifyTarget {
+    /// Builds the request that posts a notification to the service
+    pub fn request(&self, title: &str, message: &str) -> PushRequest {
+        match self {
+            NotifyTarget::Ntfy { server, topic, token } => {
+                let mut headers = vec![("Title".to_string(), title.to_string())];
+                if let Some(token) = token.clone().or_else(|| secrets::lookup(Integration::Ntfy)) {
+                    headers.push(("Authorization".to_string(), format!("Bearer {}", token)));
+                }
+                PushRequest {
+                    url: format!("{}/{}", server.trim_end_matches('/'), topic),
+                    headers,
+                    body: PushBody::Text(message.to_string()),
+                }
+            }
+            NotifyTarget::Gotify { server, token, priority } => PushRequest {
+                url: format!("{}/message", server.trim_end_matches('/')),
+                headers: vec![(
+                    "X-Gotify-Key".to_string(),
+                    if token.is_empty() { secrets::lookup(Integration::Gotify).unwrap_or_default() } else { token.clone() },
+                )],
+                body: PushBody::Json(serde_json::json!({
+                    "title": title,
+                    "message": message,
+                    "priority": priority.unwrap_or(5),
+                })),
+            },
+        }
+    }
+
+    /// The server's address, which must be a web address
+    pub fn server(&self) -> &str {
+        match self {
+            NotifyTarget::Ntfy { server, .. } | NotifyTarget::Gotify { server, .. } => server,
+        }
+    }
+}
+
+impl Notifier for NotifyTarget {
+    fn describe(&self) -> String {
+        match self {
+            NotifyTarget::Ntfy { server, topic, .. } => format!("ntfy topic {} on {}", topic, server),
+            NotifyTarget::Gotify { server, .. } => format!("Gotify on {}", server),
+        }
+    }
+
+    fn send(&self, title: &str, message: &str) -> Result<(), String> {
+        let push = self.request(title, message);
+        let mut request = Request::new("POST", push.url).body(match push.body {
+            PushBody::Text(text) => Body::Text(text),
+            PushBody::Json(json) => Body::Json(json),
+        });
+        for (name, value) in &push.headers {
+            request = request.header(name, value);
+        }
+        match http::push(request, &self.describe()).map_err(|e| *e) {
+            Ok(Delivery::Sent) => Ok(()),
+            Ok(Delivery::Queued) => {
+                eprintln!("Couldn't reach {}; the notification waits in the outbox until it can be sent.", self.describe());
+                Ok(())
+            }
+            Err(ureq::Error::Status(code, response)) => {
+                let body = response.into_string().unwrap_or_default();
+                Err(format!("{} returned {}: {}", self.describe(), code, body.trim()))
+            }
+            Err(ureq::Error::Transport(transport)) => Err(format!("Couldn't reach {}: {}", self.describe(), transport)),
+        }
+    }
+}
+
+/// Where notifications go: the configured push targets, or the desktop when there are none
+pub fn notifiers(targets: &[NotifyTarget]) -> Vec<Box<dyn Notifier>> {
+    if targets.is_empty() {
+        return vec![Box::new(Desktop)];
+    }
+    targets.iter().map(|target| Box::new(target.clone()) as Box<dyn Notifier>).collect()
+}
+
+/// Sends a notification to every notifier, failing with what went wrong if any of them failed
+pub fn send_all(notifiers: &[Box<dyn Notifier>], title: &str, message: &str) -> Result<(), String> {
+    let failures: Vec<String> = notifiers.iter().filter_map(|notifier| notifier.send(title, message).err()).collect();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("; "))
+    }
+}
+
+/// A reminder waiting to be sent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reminder {
+    /// Local time to send it at
+    pub at: NaiveDateTime,
+    pub message: String,
+}
+
+/// Reminders waiting to be sent, kept in reminders.json in the storage path
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Reminders {
+    pub pending: Vec<Reminder>,
+}
+
+impl Reminders {
+    /// Adds a reminder, keeping them in the order they are due
+    pub fn add(&mut self, reminder: Reminder) {
+        let position = self.pending.partition_point(|r| r.at <= reminder.at);
+        self.pending.insert(position, reminder);
+    }
+
+    /// Takes out the reminders due by `now`
+    pub fn take_due(&mut self, now: NaiveDateTime) -> Vec<Reminder> {
+        let due = self.pending.partition_point(|r| r.at <= now);
+        self.pending.drain(..due).collect()
+    }
+
+    /// Saves the reminders to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the reminders from a JSON file, which is empty until the first one is set
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+/// Parses when to send a reminder: a date and time (YYYY-MM-DD HH:MM), or a time of day
+/// such as 20:00 or 8pm, which means the next time the clock shows it
+pub fn parse_reminder_time(text: &str, now: NaiveDateTime) -> Result<NaiveDateTime, String> {
+    let text = text.trim();
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"] {
+        if let Ok(at) = NaiveDateTime::parse_from_str(text, format) {
+            return Ok(at);
+        }
+    }
+    let time = parse_time_of_day(text)
+        .ok_or_else(|| format!("Couldn't read the time {:?}. Use a time such as 20:00 or 8pm, or a date and time such as 2024-03-01 20:00.", text))?;
+    let today = now.date().and_time(time);
+    Ok(if today > now { today } else { today + Duration::days(1) })
+}
+
+fn parse_time_of_day(text: &str) -> Option<NaiveTime> {
+    let lower = text.to_lowercase().replace(' ', "");
+    let (clock, offset) = match (lower.strip_suffix("am"), lower.strip_suffix("pm")) {
+        (Some(clock), _) => (clock.to_string(), Some(0)),
+        (_, Some(clock)) => (clock.to_string(), Some(12)),
+        _ => (lower, None),
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        None => (clock.parse::<u32>().ok()?, 0),
+    };
+    let hour = match offset {
+        Some(offset) if (1..=12).contains(&hour) => hour % 12 + offset,
+        Some(_) => return None,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// Describes when a reminder is sent relative to today, e.g. "today at 20:00"
+pub fn describe_time(at: NaiveDateTime, today: NaiveDate) -> String {
+    let day = match (at.date() - today).num_days() {
+        0 => "today".to_string(),
+        1 => "tomorrow".to_string(),
+        _ => at.format("%a %Y-%m-%d").to_string(),
+    };
+    format!("{} at {}", day, at.format("%H:%M"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2023, 1, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_push_requests() {
+        let ntfy: NotifyTarget = serde_json::from_str(r#"{"service": "ntfy", "topic": "dinner"}"#).unwrap();
+        assert_eq!(ntfy.request("Reminder", "Defrost the chicken"), PushRequest {
+            url: "https://ntfy.sh/dinner".to_string(),
+            headers: vec![("Title".to_string(), "Reminder".to_string())],
+            body: PushBody::Text("Defrost the chicken".to_string()),
+        });
+
+        let gotify = NotifyTarget::Gotify { server: "https://push.example.com/".to_string(), token: "abc".to_string(), priority: None };
+        let request = gotify.request("Reminder", "Defrost the chicken");
+        assert_eq!(request.url, "https://push.example.com/message");
+        assert_eq!(request.headers, vec![("X-Gotify-Key".to_string(), "abc".to_string())]);
+        assert_eq!(request.body, PushBody::Json(serde_json::json!({"title": "Reminder", "message": "Defrost the chicken", "priority": 5})));
+        assert_eq!(notifiers(&[]).len(), 1);
+    }
+
+    #[test]
+    fn test_send_to_ntfy() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                head.push(line.trim().to_string());
+            }
+            let mut body = vec![0; 19];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+
+        let target = NotifyTarget::Ntfy { server, topic: "dinner".to_string(), token: Some("tk".to_string()) };
+        target.send("Reminder", "Defrost the chicken").unwrap();
+        let (head, body) = handle.join().unwrap();
+        assert_eq!(head[0], "POST /dinner HTTP/1.1");
+        assert!(head.iter().any(|line| line.eq_ignore_ascii_case("title: Reminder")));
+        assert!(head.iter().any(|line| line.eq_ignore_ascii_case("authorization: Bearer tk")));
+        assert_eq!(body, "Defrost the chicken");
+    }
+
+    #[test]
+    fn test_parse_reminder_time() {
+        let now = at(2, 19, 30);
+        assert_eq!(parse_reminder_time("20:00", now), Ok(at(2, 20, 0)));
+        assert_eq!(parse_reminder_time("8pm", now), Ok(at(2, 20, 0)));
+        assert_eq!(parse_reminder_time("8:15 am", now), Ok(at(3, 8, 15)));
+        assert_eq!(parse_reminder_time("12am", now), Ok(at(3, 0, 0)));
+        assert_eq!(parse_reminder_time("2023-01-05 07:00", now), Ok(at(5, 7, 0)));
+        assert!(parse_reminder_time("13pm", now).is_err());
+        assert!(parse_reminder_time("soon", now).is_err());
+        assert_eq!(describe_time(at(3, 8, 15), now.date()), "tomorrow at 08:15");
+    }
+
+    #[test]
+    fn test_reminders() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("reminders.json");
+        let mut reminders = Reminders::load_from_json(&path).unwrap();
+        reminders.add(Reminder { at: at(3, 8, 0), message: "Start the slow cooker".to_string() });
+        reminders.add(Reminder { at: at(2, 20, 0), message: "Defrost the chicken".to_string() });
+        reminders.save_to_json(&path).unwrap();
+
+        let mut reminders = Reminders::load_from_json(&path).unwrap();
+        assert!(reminders.take_due(at(2, 19, 59)).is_empty());
+        let due = reminders.take_due(at(2, 20, 0));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].message, "Defrost the chicken");
+        assert_eq!(reminders.pending.len(), 1);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::models::{MealPlan, MealType};
+use crate::recipes::RecipeBook;
+use crate::spreadsheet::GRID_MEAL_TYPES;
+use chrono::NaiveDate;
+
+/// A packed meal to assemble, with the ingredients its recipe lists
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackItem {
+    pub meal_type: MealType,
+    pub description: String,
+    pub cook: String,
+    pub ingredients: Vec<String>,
+}
+
+/// The packed meals planned on a date, in meal order. Ingredients come from the recipe
+/// book entry named by the meal's recipe or description.
+pub fn packing_list(meal_plan: &MealPlan, book: &RecipeBook, date: NaiveDate) -> Vec<PackItem> {
+    let mut meals: Vec<_> = meal_plan.meals().iter().filter(|meal| meal.packed && meal_plan.date_of(&meal.day) == date).collect();
+    meals.sort_by_key(|meal| GRID_MEAL_TYPES.iter().position(|t| *t == meal.meal_type));
+    meals
+        .into_iter()
+        .map(|meal| PackItem {
+            meal_type: meal.meal_type.clone(),
+            description: meal_plan.render_description(meal),
+            cook: meal.cook.clone(),
+            ingredients: book.for_meal(meal).map(|r| r.ingredients.clone()).unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Writes the list as plain text, or as a Markdown checklist to tick off while packing
+pub fn render(items: &[PackItem], date: NaiveDate, checklist: bool) -> String {
+    let title = format!("Packing list for {}", date.format("%A, %b %-d"));
+    if items.is_empty() {
+        return format!("Nothing to pack for {}.\n", date.format("%A, %b %-d"));
+    }
+    let (mut text, bullet) = match checklist {
+        true => (format!("## {}\n\n", title), "- [ ]"),
+        false => (format!("{}:\n", title), "-"),
+    };
+    for item in items {
+        text.push_str(&format!("{} {}: {}", bullet, item.meal_type, item.description));
+        if !item.cook.trim().is_empty() {
+            text.push_str(&format!(" ({})", item.cook));
+        }
+        text.push('\n');
+        for ingredient in &item.ingredients {
+            text.push_str(&format!("  {} {}\n", bullet, ingredient));
+        }
+    }
+    text
+}
+
+/// One line summing the list up, for a reminder notification
+pub fn summary(items: &[PackItem]) -> String {
+    let meals: Vec<String> = items.iter().map(|item| format!("{}: {}", item.meal_type, item.description)).collect();
+    meals.join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, Meal};
+    use crate::recipes::Recipe;
+    use chrono::Weekday;
+
+    #[test]
+    fn test_packing_list() {
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let mut lunch = Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Sam".to_string(), "Turkey sandwich".to_string());
+        lunch.packed = true;
+        plan.add_meal(lunch);
+        let mut snack = Meal::new(MealType::Snack, Day::Date(NaiveDate::from_ymd_opt(2023, 1, 3).unwrap()), String::new(), "Apple".to_string());
+        snack.packed = true;
+        plan.add_meal(snack);
+        plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), "Sam".to_string(), "Chili".to_string()));
+        let mut book = RecipeBook::default();
+        book.add(Recipe {
+            name: "turkey sandwich".to_string(),
+            tags: Vec::new(),
+            ingredients: vec!["bread".to_string(), "turkey".to_string()],
+            link: None,
+            servings: None,
+            photo: None,
+            steps: Vec::new(),
+            equipment: Vec::new(),
+        });
+
+        let tuesday = NaiveDate::from_ymd_opt(2023, 1, 3).unwrap();
+        let items = packing_list(&plan, &book, tuesday);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].ingredients, vec!["bread", "turkey"]);
+        assert_eq!(
+            render(&items, tuesday, true),
+            "## Packing list for Tuesday, Jan 3\n\n\
+             - [ ] Lunch: Turkey sandwich (Sam)\n  - [ ] bread\n  - [ ] turkey\n\
+             - [ ] Snack: Apple\n"
+        );
+        assert!(render(&items, tuesday, false).starts_with("Packing list for Tuesday, Jan 3:\n- Lunch"));
+        assert_eq!(summary(&items), "Lunch: Turkey sandwich; Snack: Apple");
+
+        let wednesday = NaiveDate::from_ymd_opt(2023, 1, 4).unwrap();
+        assert!(packing_list(&plan, &book, wednesday).is_empty());
+        assert_eq!(render(&[], wednesday, true), "Nothing to pack for Wednesday, Jan 4.\n");
+    }
+}
@@ -0,0 +1,402 @@
+use crate::days;
+use crate::http::{self, Request};
+use crate::models::{Config, MealAlias, MealType};
+use crate::recipes::{Recipe, RecipeBook};
+use crate::spreadsheet::GRID_MEAL_TYPES;
+use chrono::Weekday;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// File in the storage path recording what each installed pack added
+pub const INSTALLED_PACKS_FILE: &str = "packs.json";
+
+/// Largest pack that's downloaded, so a wrong address can't fill the disk
+pub const MAX_PACK_BYTES: u64 = 1024 * 1024;
+
+/// Packs that come with mealplan, installable by name
+pub const BUILT_IN: &[(&str, &str)] = &[
+    ("back-to-school", include_str!("../packs/back-to-school.json")),
+    ("summer-grilling", include_str!("../packs/summer-grilling.json")),
+    ("holiday-week", include_str!("../packs/holiday-week.json")),
+];
+
+/// A set of recipes, meal aliases and week templates installed together, such as "Summer
+/// grilling"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pack {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub recipes: Vec<Recipe>,
+    /// Meal shortcuts, with the tags meals planned from them get
+    #[serde(default)]
+    pub aliases: BTreeMap<String, MealAlias>,
+    /// Meals for the week templates, by day and then meal type, as in `day_templates`
+    #[serde(default)]
+    pub week: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Pack {
+    /// Reads a pack from its JSON, checking that its days and meal types are ones mealplan knows
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let pack: Pack = serde_json::from_str(json).map_err(|e| format!("Not a template pack: {}", e))?;
+        if pack.name.trim().is_empty() {
+            return Err("The pack has no name.".to_string());
+        }
+        if pack.recipes.iter().any(|recipe| recipe.name.trim().is_empty()) {
+            return Err(format!("A recipe in {} has no name.", pack.name));
+        }
+        if pack.aliases.keys().any(|name| name.trim().is_empty()) {
+            return Err(format!("An alias in {} has no name.", pack.name));
+        }
+        pack.slots().map(|_| pack)
+    }
+
+    /// The built-in pack with this name, ignoring case and allowing spaces for dashes
+    pub fn built_in(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase().replace(' ', "-");
+        BUILT_IN.iter()
+            .find(|(built_in, _)| *built_in == name)
+            .map(|(_, json)| Self::parse(json).expect("built-in packs are valid"))
+    }
+
+    /// The pack's template meals in day order
+    pub fn slots(&self) -> Result<Vec<Slot>, String> {
+        let mut slots = Vec::new();
+        for (day, meals) in &self.week {
+            let weekday = days::parse_weekday(day).ok_or_else(|| format!("{} in {} isn't a day of the week.", day, self.name))?;
+            for (meal_type, description) in meals {
+                let meal_type = GRID_MEAL_TYPES.iter()
+                    .find(|t| t.to_string().eq_ignore_ascii_case(meal_type.trim()))
+                    .ok_or_else(|| format!("{} in {} isn't a meal type; use breakfast, lunch, dinner or snack.", meal_type, self.name))?;
+                if description.trim().is_empty() {
+                    return Err(format!("The {} {} meal in {} has no description.", day, meal_type, self.name));
+                }
+                slots.push(Slot { day: weekday, meal_type: meal_type.clone(), description: description.trim().to_string() });
+            }
+        }
+        slots.sort_by_key(|slot| slot.day.num_days_from_monday());
+        Ok(slots)
+    }
+}
+
+/// A meal of the week templates
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Slot {
+    pub day: Weekday,
+    pub meal_type: MealType,
+    pub description: String,
+}
+
+impl std::fmt::Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", days::weekday_name(self.day), self.meal_type)
+    }
+}
+
+/// What installing a pack wrote, so removing it takes out only those
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Installed {
+    pub name: String,
+    #[serde(default)]
+    pub recipes: Vec<Recipe>,
+    #[serde(default)]
+    pub aliases: BTreeMap<String, MealAlias>,
+    #[serde(default)]
+    pub week: Vec<Slot>,
+}
+
+/// What an install or removal did, as descriptions such as "recipe Pasta Bake" or
+/// "Friday Dinner template"
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+    pub added: Vec<String>,
+    pub replaced: Vec<String>,
+    pub removed: Vec<String>,
+    /// Items the household already had, or has changed since the pack wrote them, which
+    /// were left alone
+    pub kept: Vec<String>,
+}
+
+/// The packs installed, kept in packs.json in the storage path
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InstalledPacks {
+    pub packs: Vec<Installed>,
+}
+
+impl InstalledPacks {
+    /// Finds an installed pack by name, ignoring case
+    pub fn find(&self, name: &str) -> Option<&Installed> {
+        self.packs.iter().find(|pack| pack.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Adds a pack's recipes to the book and its aliases and week templates to the config.
+    /// Items the household already has with other contents are kept unless `replace`.
+    pub fn install(&mut self, pack: &Pack, book: &mut RecipeBook, config: &mut Config, replace: bool) -> Result<Changes, String> {
+        let slots = pack.slots()?;
+        let mut changes = Changes::default();
+        let mut installed = Installed { name: pack.name.trim().to_string(), recipes: Vec::new(), aliases: BTreeMap::new(), week: Vec::new() };
+
+        for recipe in &pack.recipes {
+            let label = format!("recipe {}", recipe.name);
+            match book.find(&recipe.name) {
+                // The household's own, so removing the pack leaves it
+                Some(existing) if existing == recipe => continue,
+                Some(_) if !replace => {
+                    changes.kept.push(label);
+                    continue;
+                }
+                Some(_) => changes.replaced.push(label),
+                None => changes.added.push(label),
+            }
+            book.add(recipe.clone());
+            installed.recipes.push(recipe.clone());
+        }
+
+        for (name, alias) in &pack.aliases {
+            let key = name.trim().to_lowercase();
+            let label = format!("alias {}", key);
+            match config.aliases.get(&key) {
+                Some(existing) if existing == alias => continue,
+                Some(_) if !replace => {
+                    changes.kept.push(label);
+                    continue;
+                }
+                Some(_) => changes.replaced.push(label),
+                None => changes.added.push(label),
+            }
+            config.aliases.insert(key.clone(), alias.clone());
+            installed.aliases.insert(key, alias.clone());
+        }
+
+        for slot in slots {
+            let label = format!("{} template", slot);
+            let meals = template_day(config, slot.day);
+            let key = meals.keys().find(|t| t.trim().eq_ignore_ascii_case(&slot.meal_type.to_string())).cloned();
+            match key.as_ref().and_then(|key| meals.get(key)) {
+                Some(existing) if existing.trim() == slot.description => continue,
+                Some(existing) if !existing.trim().is_empty() && !replace => {
+                    changes.kept.push(label);
+                    continue;
+                }
+                Some(existing) if !existing.trim().is_empty() => changes.replaced.push(label),
+                _ => changes.added.push(label),
+            }
+            let key = key.unwrap_or_else(|| slot.meal_type.to_string().to_lowercase());
+            meals.insert(key, slot.description.clone());
+            installed.week.push(slot);
+        }
+        config.day_templates.retain(|_, meals| !meals.is_empty());
+
+        // Installing again keeps track of what the earlier install wrote too
+        if let Some(earlier) = self.packs.iter().position(|p| p.name.eq_ignore_ascii_case(&installed.name)) {
+            let earlier = self.packs.remove(earlier);
+            for recipe in earlier.recipes {
+                if !installed.recipes.iter().any(|r| r.name.eq_ignore_ascii_case(&recipe.name)) {
+                    installed.recipes.push(recipe);
+                }
+            }
+            for (name, alias) in earlier.aliases {
+                installed.aliases.entry(name).or_insert(alias);
+            }
+            for slot in earlier.week {
+                if !installed.week.iter().any(|s| s.day == slot.day && s.meal_type == slot.meal_type) {
+                    installed.week.push(slot);
+                }
+            }
+        }
+        self.packs.push(installed);
+        self.packs.sort_by_key(|pack| pack.name.to_lowercase());
+        Ok(changes)
+    }
+
+    /// Takes out what an installed pack added, leaving anything changed since. Returns None
+    /// if no pack by that name is installed.
+    pub fn uninstall(&mut self, name: &str, book: &mut RecipeBook, config: &mut Config) -> Option<Changes> {
+        let index = self.packs.iter().position(|pack| pack.name.eq_ignore_ascii_case(name.trim()))?;
+        let installed = self.packs.remove(index);
+        let mut changes = Changes::default();
+        for recipe in &installed.recipes {
+            let label = format!("recipe {}", recipe.name);
+            if book.find(&recipe.name) == Some(recipe) {
+                book.remove(&recipe.name);
+                changes.removed.push(label);
+            } else if book.find(&recipe.name).is_some() {
+                changes.kept.push(label);
+            }
+        }
+        for (name, alias) in &installed.aliases {
+            let label = format!("alias {}", name);
+            if config.aliases.get(name) == Some(alias) {
+                config.aliases.remove(name);
+                changes.removed.push(label);
+            } else if config.aliases.contains_key(name) {
+                changes.kept.push(label);
+            }
+        }
+        for slot in &installed.week {
+            let label = format!("{} template", slot);
+            let meals = template_day(config, slot.day);
+            let key = meals.iter()
+                .find(|(t, _)| t.trim().eq_ignore_ascii_case(&slot.meal_type.to_string()))
+                .map(|(t, description)| (t.clone(), description.trim() == slot.description));
+            match key {
+                Some((key, true)) => {
+                    meals.remove(&key);
+                    changes.removed.push(label);
+                }
+                Some((_, false)) => changes.kept.push(label),
+                None => {}
+            }
+        }
+        config.day_templates.retain(|_, meals| !meals.is_empty());
+        Some(changes)
+    }
+
+    /// Saves the installed packs to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the installed packs from a JSON file, which is empty until the first install
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+/// The config's template meals for a weekday, under the key already used for that day or
+/// else its lowercase name
+fn template_day(config: &mut Config, weekday: Weekday) -> &mut BTreeMap<String, String> {
+    let key = config.day_templates.keys()
+        .find(|day| days::parse_weekday(day) == Some(weekday))
+        .cloned()
+        .unwrap_or_else(|| days::weekday_name(weekday).to_lowercase());
+    config.day_templates.entry(key).or_default()
+}
+
+/// Downloads a pack's JSON from a web address
+pub fn download(url: &str) -> io::Result<String> {
+    let request = Request::new("GET", url).header("Accept", "application/json");
+    let response = http::send(&request).map_err(|error| match *error {
+        ureq::Error::Status(code, _) => io::Error::other(format!("{} answered {}.", url, code)),
+        ureq::Error::Transport(transport) => io::Error::other(format!("Couldn't reach {}: {}", url, transport)),
+    })?;
+    let mut body = Vec::new();
+    response.into_reader().take(MAX_PACK_BYTES + 1).read_to_end(&mut body)?;
+    if body.len() as u64 > MAX_PACK_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} is over 1 MB, too big for a template pack.", url)));
+    }
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_built_in_packs() {
+        for (name, json) in BUILT_IN {
+            let pack = Pack::parse(json).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert!(!pack.recipes.is_empty() && !pack.week.is_empty(), "{}", name);
+            // The pack's aliases and template meals name recipes in the pack
+            for recipe in pack.aliases.values().filter_map(|alias| alias.recipe.as_deref()) {
+                assert!(pack.recipes.iter().any(|r| r.name == recipe), "{}: {}", name, recipe);
+            }
+        }
+        assert_eq!(Pack::built_in("Summer Grilling").unwrap().name, "Summer grilling");
+        assert!(Pack::built_in("winter").is_none());
+        assert!(Pack::parse(r#"{"name": "Odd", "week": {"payday": {"dinner": "Steak"}}}"#).unwrap_err().contains("payday"));
+        assert!(Pack::parse(r#"{"name": "Odd", "week": {"fri": {"tea": "Scones"}}}"#).unwrap_err().contains("tea"));
+        assert!(Pack::parse(r#"{"name": " "}"#).is_err());
+        assert!(Pack::parse("[]").unwrap_err().starts_with("Not a template pack"));
+    }
+
+    #[test]
+    fn test_install_pack() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(INSTALLED_PACKS_FILE);
+        let pack = Pack::built_in("back-to-school").unwrap();
+        let mut book = RecipeBook::default();
+        let mut config = Config::new();
+        // The household's own Pasta Bake and Friday dinner are kept
+        let mut own = pack.recipes.iter().find(|r| r.name == "Pasta Bake").unwrap().clone();
+        own.servings = Some(8);
+        book.add(own.clone());
+        config.day_templates.insert("Fri".to_string(), BTreeMap::from([("Dinner".to_string(), "Pizza".to_string())]));
+
+        let mut installed = InstalledPacks::load_from_json(&path).unwrap();
+        let changes = installed.install(&pack, &mut book, &mut config, false).unwrap();
+        assert_eq!(changes.kept, vec!["recipe Pasta Bake", "Friday Dinner template"]);
+        assert!(changes.added.contains(&"alias oats".to_string()));
+        assert!(changes.added.contains(&"Monday Breakfast template".to_string()));
+        assert_eq!(book.find("pasta bake"), Some(&own));
+        assert_eq!(config.day_template(Weekday::Fri), vec![("Dinner", "Pizza")]);
+        assert_eq!(config.day_template(Weekday::Mon), vec![("breakfast", "oats"), ("lunch", "wraps")]);
+        assert_eq!(config.find_alias("Oats").unwrap().recipe.as_deref(), Some("Overnight Oats"));
+
+        // Installing again with --replace overwrites them, and changes nothing else
+        let changes = installed.install(&pack, &mut book, &mut config, true).unwrap();
+        assert_eq!(changes.replaced, vec!["recipe Pasta Bake", "Friday Dinner template"]);
+        assert!(changes.added.is_empty() && changes.kept.is_empty());
+        assert_eq!(config.day_template(Weekday::Fri), vec![("Dinner", "Pasta Bake")]);
+        assert_eq!(installed.packs.len(), 1);
+
+        installed.save_to_json(&path).unwrap();
+        let mut installed = InstalledPacks::load_from_json(&path).unwrap();
+        assert!(installed.find("BACK TO SCHOOL").is_some());
+        // Changed since, so removing the pack leaves it
+        config.aliases.get_mut("wraps").unwrap().description = "Ham Wraps".to_string();
+        let changes = installed.uninstall("back to school", &mut book, &mut config).unwrap();
+        assert_eq!(changes.kept, vec!["alias wraps"]);
+        assert_eq!(changes.removed.len(), 4 + 1 + 5);
+        assert!(book.recipes.is_empty());
+        assert_eq!(config.aliases.keys().collect::<Vec<_>>(), vec!["wraps"]);
+        assert!(config.day_templates.is_empty());
+        assert!(installed.uninstall("back to school", &mut book, &mut config).is_none());
+    }
+
+    #[test]
+    fn test_uninstall_leaves_what_was_there_before() {
+        let pack = Pack::built_in("summer-grilling").unwrap();
+        let mut book = RecipeBook::default();
+        let mut config = Config::new();
+        // The household already had some of the pack, just as the pack has it
+        let burgers = pack.recipes.iter().find(|r| r.name == "Grilled Burgers").unwrap().clone();
+        book.add(burgers.clone());
+        config.aliases.insert("burgers".to_string(), pack.aliases["burgers"].clone());
+        config.day_templates.insert("saturday".to_string(), BTreeMap::from([("dinner".to_string(), "burgers".to_string())]));
+
+        let mut installed = InstalledPacks::default();
+        let changes = installed.install(&pack, &mut book, &mut config, false).unwrap();
+        assert!(changes.kept.is_empty() && changes.replaced.is_empty());
+        assert_eq!(changes.added, vec![
+            "recipe Chicken Skewers",
+            "recipe Grilled Corn",
+            "recipe Watermelon Salad",
+            "alias skewers",
+            "Wednesday Dinner template",
+        ]);
+
+        let changes = installed.uninstall("summer grilling", &mut book, &mut config).unwrap();
+        assert_eq!(changes.removed.len(), 5);
+        assert_eq!(book.recipes, vec![burgers]);
+        assert_eq!(config.aliases.keys().collect::<Vec<_>>(), vec!["burgers"]);
+        assert_eq!(config.day_template(Weekday::Sat), vec![("dinner", "burgers")]);
+        assert_eq!(config.day_template(Weekday::Wed), vec![]);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::recipes::{Recipe, RecipeBook};
+use crate::units::{self, Quantity};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Ingredients taken to be in every kitchen, so recipes aren't marked down for them
+const ALWAYS_ON_HAND: &[&str] = &["water", "salt", "pepper", "black pepper", "salt and pepper", "ice"];
+
+/// What's in the kitchen, kept in pantry.json in the storage path. Items are matched to
+/// recipe ingredients by ingredient, so "onions" covers "2 cups diced onions".
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Pantry {
+    pub items: Vec<String>,
+}
+
+/// The ingredient a recipe line names, without its amount or notes such as ", chopped"
+fn line_ingredient(line: &str) -> String {
+    let name = Quantity::parse(line).map_or_else(|| line.to_string(), |quantity| quantity.ingredient);
+    let name = name.split([',', '(']).next().unwrap_or_default().trim();
+    name.strip_suffix(" to taste").unwrap_or(name).to_string()
+}
+
+impl Pantry {
+    /// Adds an item, returning false when the pantry already has that ingredient
+    pub fn add(&mut self, item: &str) -> bool {
+        let item = item.trim();
+        if item.is_empty() || self.find(item).is_some() {
+            return false;
+        }
+        self.items.push(item.to_string());
+        self.items.sort_by_key(|item| item.to_lowercase());
+        true
+    }
+
+    /// Takes an item out, returning false when the pantry doesn't have that ingredient
+    pub fn remove(&mut self, item: &str) -> bool {
+        match self.find(item) {
+            Some(index) => {
+                self.items.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn find(&self, item: &str) -> Option<usize> {
+        let key = units::ingredient_name(item);
+        self.items.iter().position(|listed| units::ingredient_name(listed) == key)
+    }
+
+    /// Whether a recipe line's ingredient is on hand. The line's last words are tried on
+    /// their own too, so "diced green onions" is covered by scallions.
+    pub fn has(&self, line: &str) -> bool {
+        let ingredient = line_ingredient(line);
+        let words: Vec<&str> = ingredient.split_whitespace().collect();
+        let keys: Vec<String> = self.items.iter().map(|item| units::ingredient_name(item)).collect();
+        (0..words.len()).any(|start| {
+            let key = units::ingredient_name(&words[start..].join(" "));
+            keys.contains(&key) || ALWAYS_ON_HAND.iter().any(|staple| units::ingredient_name(staple) == key)
+        })
+    }
+
+    /// Saves the pantry to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the pantry from a JSON file, which is empty until the first item is added
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+/// How much of a recipe can be made from the pantry
+#[derive(Debug, Clone, PartialEq)]
+pub struct PantryMatch<'a> {
+    pub recipe: &'a Recipe,
+    /// How many of its ingredients are on hand
+    pub have: usize,
+    /// The ingredient lines that aren't
+    pub missing: Vec<String>,
+}
+
+impl PantryMatch<'_> {
+    /// The share of the recipe's ingredients on hand, from 0 to 1
+    pub fn fraction(&self) -> f64 {
+        self.have as f64 / (self.have + self.missing.len()) as f64
+    }
+}
+
+/// Recipes with at least one ingredient on hand, those with the most of their ingredients
+/// first and then those missing the fewest. Recipes known to take longer than `max_minutes`
+/// are left out; those whose steps don't say how long they take are kept.
+pub fn what_can_i_make<'a>(pantry: &Pantry, book: &'a RecipeBook, max_minutes: Option<u32>) -> Vec<PantryMatch<'a>> {
+    let mut matches: Vec<PantryMatch> = book
+        .recipes
+        .iter()
+        .filter(|recipe| !recipe.ingredients.is_empty())
+        .filter(|recipe| !matches!((max_minutes, recipe.minutes()), (Some(max), Some(minutes)) if minutes > max))
+        .map(|recipe| {
+            let (have, missing): (Vec<&String>, Vec<&String>) = recipe.ingredients.iter().partition(|line| pantry.has(line));
+            PantryMatch { recipe, have: have.len(), missing: missing.into_iter().cloned().collect() }
+        })
+        .filter(|found| found.have > 0)
+        .collect();
+    matches.sort_by(|a, b| {
+        b.fraction()
+            .partial_cmp(&a.fraction())
+            .unwrap_or(Ordering::Equal)
+            .then(a.missing.len().cmp(&b.missing.len()))
+            .then_with(|| a.recipe.name.to_lowercase().cmp(&b.recipe.name.to_lowercase()))
+    });
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn recipe(name: &str, ingredients: &[&str], steps: &[&str]) -> Recipe {
+        Recipe {
+            name: name.to_string(),
+            tags: Vec::new(),
+            ingredients: ingredients.iter().map(|i| i.to_string()).collect(),
+            link: None,
+            servings: None,
+            photo: None,
+            steps: steps.iter().map(|s| s.to_string()).collect(),
+            equipment: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_pantry_items() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("pantry.json");
+        let mut pantry = Pantry::load_from_json(&path).unwrap();
+        assert!(pantry.add("Onions"));
+        assert!(pantry.add("scallions"));
+        assert!(!pantry.add("onion"));
+        assert!(pantry.has("2 cups diced onions"));
+        assert!(pantry.has("3 green onions, sliced"));
+        assert!(pantry.has("Salt to taste"));
+        assert!(!pantry.has("1 lb ground beef"));
+
+        pantry.save_to_json(&path).unwrap();
+        let mut pantry = Pantry::load_from_json(&path).unwrap();
+        assert_eq!(pantry.items, vec!["Onions", "scallions"]);
+        assert!(pantry.remove("ONION"));
+        assert!(!pantry.remove("onion"));
+    }
+
+    #[test]
+    fn test_what_can_i_make() {
+        let mut pantry = Pantry::default();
+        for item in ["rice", "eggs", "soy sauce", "tortillas"] {
+            pantry.add(item);
+        }
+        let mut book = RecipeBook::default();
+        book.add(recipe("Fried Rice", &["2 cups cooked rice", "2 eggs", "1 tbsp soy sauce", "1 cup peas"], &["Fry for 10 minutes"]));
+        book.add(recipe("Egg Fried Rice", &["2 cups rice", "3 eggs", "salt"], &["Simmer for 40 minutes", "Fry for 5 minutes"]));
+        book.add(recipe("Tacos", &["8 tortillas", "1 lb ground beef"], &[]));
+        book.add(recipe("Stew", &["beef", "carrots"], &[]));
+        book.add(recipe("Toast", &[], &[]));
+
+        let matches = what_can_i_make(&pantry, &book, None);
+        let names: Vec<&str> = matches.iter().map(|m| m.recipe.name.as_str()).collect();
+        assert_eq!(names, vec!["Egg Fried Rice", "Fried Rice", "Tacos"]);
+        assert_eq!(matches[1].missing, vec!["1 cup peas"]);
+        assert_eq!(matches[1].fraction(), 0.75);
+
+        // Egg fried rice takes 45 minutes; tacos don't say, so they stay
+        let names: Vec<String> = what_can_i_make(&pantry, &book, Some(30)).iter().map(|m| m.recipe.name.clone()).collect();
+        assert_eq!(names, vec!["Fried Rice", "Tacos"]);
+    }
+}
@@ -0,0 +1,292 @@
+use crate::models::MealPlan;
+use ring::hmac;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// How long to wait for a peer to accept the connection or to send or take its plan
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The longest line a peer may send, so one that never ends its line can't use up the memory
+const MAX_MESSAGE: u64 = 8 * 1024 * 1024;
+
+/// Bytes of the random challenge each side sends before the plans
+const CHALLENGE_LEN: usize = 16;
+
+/// Waits for a single peer to connect and exchanges plans with it, returning the remote plan.
+/// Waiting for the connection has no time limit; the exchange itself does.
+pub fn listen<A: ToSocketAddrs>(addr: A, local: &MealPlan, secret: &str) -> std::io::Result<MealPlan> {
+    let listener = TcpListener::bind(addr)?;
+    let (stream, _) = listener.accept()?;
+    exchange(stream, local, secret, false)
+}
+
+/// Connects to a listening peer and exchanges plans with it, returning the remote plan
+pub fn connect<A: ToSocketAddrs>(addr: A, local: &MealPlan, secret: &str) -> std::io::Result<MealPlan> {
+    let mut last_error = None;
+    for addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, PEER_TIMEOUT) {
+            Ok(stream) => return exchange(stream, local, secret, true),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "The peer address didn't resolve to any address")
+    }))
+}
+
+/// Sends the local plan and receives the remote one as a single line of JSON each.
+/// The connecting side speaks first so the two peers never block writing at once.
+///
+/// Both sides first send a random challenge, and the listening side answers with an HMAC of
+/// the two, keyed with the shared secret. The connecting side checks it before sending
+/// anything, so a listener that doesn't know the secret never gets a plan. Each plan is then
+/// sent after an HMAC of the two challenges and the plan, so a plan is only taken from a
+/// peer that knows the secret and can't be replayed from an earlier exchange.
+pub fn exchange(stream: TcpStream, local: &MealPlan, secret: &str, initiator: bool) -> std::io::Result<MealPlan> {
+    exchange_within(stream, local, secret, initiator, PEER_TIMEOUT)
+}
+
+/// Exchanges plans, giving up when the peer stalls for longer than `timeout`
+fn exchange_within(
+    stream: TcpStream,
+    local: &MealPlan,
+    secret: &str,
+    initiator: bool,
+    timeout: Duration,
+) -> std::io::Result<MealPlan> {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+
+    let mut challenge = [0u8; CHALLENGE_LEN];
+    SystemRandom::new()
+        .fill(&mut challenge)
+        .map_err(|_| std::io::Error::other("Couldn't generate a random challenge"))?;
+    let ours = hex(&challenge);
+    write_line(&mut writer, &ours)?;
+    let theirs = read_line(&mut reader)?;
+    if theirs.len() != CHALLENGE_LEN * 2 || !theirs.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid("The peer didn't start the exchange with a challenge"));
+    }
+
+    // The sender's role goes into what is signed, so a plan can't be reflected back to its sender
+    let signed = |role: &str, first: &str, second: &str, json: &str| format!("{}\n{}\n{}\n{}", role, first, second, json);
+
+    let refused = || invalid("The peer's plan isn't signed with the same secret; check `mealplan auth set peer` on both machines");
+
+    // The listener proves it knows the secret before the connecting side gives up its plan
+    if initiator {
+        let proof = unhex(&read_line(&mut reader)?).ok_or_else(refused)?;
+        hmac::verify(&key, signed("proof", &ours, &theirs, "").as_bytes(), &proof).map_err(|_| refused())?;
+    } else {
+        write_line(&mut writer, &hex(hmac::sign(&key, signed("proof", &theirs, &ours, "").as_bytes()).as_ref()))?;
+    }
+
+    let send = |writer: &mut TcpStream| -> std::io::Result<()> {
+        let json = serde_json::to_string(local)?;
+        let role = if initiator { "connect" } else { "listen" };
+        let tag = hmac::sign(&key, signed(role, &theirs, &ours, &json).as_bytes());
+        write_line(writer, &format!("{} {}", hex(tag.as_ref()), json))
+    };
+
+    let receive = |reader: &mut BufReader<TcpStream>| -> std::io::Result<MealPlan> {
+        let line = read_line(reader)?;
+        let (tag, json) = line.split_once(' ').ok_or_else(|| invalid("The peer's plan isn't signed"))?;
+        let role = if initiator { "listen" } else { "connect" };
+        let tag = unhex(tag).ok_or_else(|| invalid("The peer's plan isn't signed"))?;
+        hmac::verify(&key, signed(role, &ours, &theirs, json).as_bytes(), &tag).map_err(|_| refused())?;
+        Ok(serde_json::from_str(json)?)
+    };
+
+    if initiator {
+        send(&mut writer)?;
+        receive(&mut reader)
+    } else {
+        let remote = receive(&mut reader)?;
+        send(&mut writer)?;
+        Ok(remote)
+    }
+}
+
+fn write_line(writer: &mut TcpStream, line: &str) -> std::io::Result<()> {
+    writer.write_all(line.as_bytes())?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+/// Reads a line of at most `MAX_MESSAGE` bytes, without its line ending
+fn read_line(reader: &mut BufReader<TcpStream>) -> std::io::Result<String> {
+    let mut line = String::new();
+    if reader.by_ref().take(MAX_MESSAGE).read_line(&mut line)? == 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "Peer closed the connection before sending its plan",
+        ));
+    }
+    if !line.ends_with('\n') {
+        return Err(if line.len() as u64 >= MAX_MESSAGE {
+            invalid("The peer sent more than the largest plan allowed")
+        } else {
+            std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Peer closed the connection partway through its plan")
+        });
+    }
+    line.truncate(line.trim_end_matches(['\r', '\n']).len());
+    Ok(line)
+}
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| text.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, Meal, MealType};
+    use chrono::{NaiveDate, Weekday};
+    use std::thread;
+
+    #[test]
+    fn test_exchange_over_tcp() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut laptop = MealPlan::new(week_start);
+        laptop.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Mon),
+            "Alice".to_string(),
+            "Chili".to_string(),
+        ));
+        let kitchen = MealPlan::new(week_start);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            exchange(stream, &kitchen, "s3cret", false).unwrap()
+        });
+
+        let received_by_laptop = connect(addr, &laptop, "s3cret").unwrap();
+        let received_by_kitchen = server.join().unwrap();
+
+        assert!(received_by_laptop.meals().is_empty());
+        assert_eq!(received_by_kitchen.meals().len(), 1);
+        assert_eq!(received_by_kitchen.meals()[0].description, "Chili");
+    }
+
+    #[test]
+    fn test_unresponsive_peer_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // The peer accepts the connection but never sends its plan
+        let peer = thread::spawn(move || listener.accept().unwrap());
+        let stream = TcpStream::connect(addr).unwrap();
+        let local = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let err = exchange_within(stream, &local, "s3cret", true, Duration::from_millis(100)).unwrap_err();
+        assert!(matches!(err.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut));
+        drop(peer.join().unwrap());
+    }
+
+    #[test]
+    fn test_peer_without_the_secret_is_refused() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            exchange(stream, &MealPlan::new(week_start), "s3cret", false)
+        });
+
+        // The listener's proof doesn't check out with another secret, so nothing is sent
+        let err = connect(addr, &MealPlan::new(week_start), "guess").unwrap_err();
+        assert!(err.to_string().contains("same secret"));
+        assert_eq!(server.join().unwrap().unwrap_err().kind(), std::io::ErrorKind::UnexpectedEof);
+
+        // A peer that sends a plan anyway has it turned down
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            exchange(stream, &MealPlan::new(week_start), "s3cret", false)
+        });
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let plan = serde_json::to_string(&MealPlan::new(week_start)).unwrap();
+        stream.write_all(format!("{}\n{} {}\n", "ab".repeat(CHALLENGE_LEN), "cd".repeat(32), plan).as_bytes()).unwrap();
+        let err = server.join().unwrap().unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("same secret"));
+    }
+
+    #[test]
+    fn test_listener_without_the_secret_gets_no_plan() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // The listener sends its challenge and a made-up proof, then takes whatever comes
+        let peer = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(format!("{}\n{}\n", "ab".repeat(CHALLENGE_LEN), "cd".repeat(32)).as_bytes()).unwrap();
+            let mut received = String::new();
+            let _ = stream.read_to_string(&mut received);
+            received
+        });
+        let mut laptop = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        laptop.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Chili".to_string()));
+        let err = connect(addr, &laptop, "s3cret").unwrap_err();
+        assert!(err.to_string().contains("same secret"));
+
+        // Only the challenge was sent
+        let received = peer.join().unwrap();
+        assert_eq!(received.lines().count(), 1);
+        assert!(!received.contains("Chili"));
+    }
+
+    #[test]
+    fn test_oversized_message_is_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // The peer sends its challenge, then a line longer than any plan
+        let peer = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(format!("{}\n", "ab".repeat(CHALLENGE_LEN)).as_bytes()).unwrap();
+            let chunk = vec![b'x'; 1024 * 1024];
+            for _ in 0..=MAX_MESSAGE / chunk.len() as u64 {
+                if stream.write_all(&chunk).is_err() {
+                    break;
+                }
+            }
+            // Takes what was sent until the other side hangs up, so closing doesn't reset it
+            let _ = std::io::copy(&mut stream, &mut std::io::sink());
+        });
+        let stream = TcpStream::connect(addr).unwrap();
+        let local = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let err = exchange(stream, &local, "s3cret", true).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("largest plan"));
+        peer.join().unwrap();
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        assert_eq!(unhex(&hex(&[0, 15, 255])), Some(vec![0, 15, 255]));
+        assert_eq!(unhex("abc"), None);
+        assert_eq!(unhex("zz"), None);
+    }
+}
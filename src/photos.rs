@@ -0,0 +1,211 @@
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::path::{Component, Path};
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    crate::plan::MealPlan,
+    std::io::{Error, ErrorKind},
+    std::path::PathBuf,
+};
+
+/// Directory in the storage path that photos of meals and recipes are copied into
+pub const ASSETS_DIR: &str = "assets";
+
+#[cfg(not(target_arch = "wasm32"))]
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp", "svg", "avif"];
+
+/// Whether a photo is a web address rather than a file in the assets directory
+pub fn is_url(photo: &str) -> bool {
+    let photo = photo.trim().to_lowercase();
+    photo.starts_with("http://") || photo.starts_with("https://")
+}
+
+/// Whether a photo is one this app could have stored: a web address, or a file directly in
+/// the assets directory. Plans come from other people too, so anything else, such as an
+/// absolute path or one climbing out with `..`, is never read or written.
+pub fn is_stored_photo(photo: &str) -> bool {
+    if is_url(photo) {
+        return true;
+    }
+    let mut components = Path::new(photo).components();
+    match (components.next(), components.next(), components.next()) {
+        (Some(Component::Normal(dir)), Some(Component::Normal(_)), None) => dir == ASSETS_DIR,
+        _ => false,
+    }
+}
+
+/// Deserializes a meal's or recipe's photo, refusing any that [`is_stored_photo`] doesn't accept
+pub fn deserialize_photo<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(photo) if !is_stored_photo(&photo) => Err(D::Error::custom(format!(
+            "photo {:?} is neither a web address nor a file in {}/",
+            photo, ASSETS_DIR
+        ))),
+        photo => Ok(photo),
+    }
+}
+
+/// Stores a photo for a meal or recipe and returns how to refer to it. Web addresses are
+/// kept as they are; image files are copied into the assets directory and referred to by
+/// their path relative to the storage path, so exports written there can link to them.
+/// A file already copied with the same contents is reused.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn import_photo(storage_path: &Path, source: &str) -> std::io::Result<String> {
+    let source = source.trim();
+    if is_url(source) {
+        return Ok(source.to_string());
+    }
+
+    let path = Path::new(source);
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default().to_lowercase();
+    if !IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} is not an image; use a {} file", source, IMAGE_EXTENSIONS.join(", ")),
+        ));
+    }
+    let contents = std::fs::read(path)?;
+
+    let stem = asset_stem(path.file_stem().and_then(|s| s.to_str()).unwrap_or_default());
+    let dir = storage_path.join(ASSETS_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let mut n = 1;
+    loop {
+        let name = match n {
+            1 => format!("{}.{}", stem, extension),
+            _ => format!("{}-{}.{}", stem, n, extension),
+        };
+        let target = dir.join(&name);
+        match std::fs::read(&target) {
+            Ok(existing) if existing == contents => return Ok(format!("{}/{}", ASSETS_DIR, name)),
+            Ok(_) => n += 1,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                std::fs::write(&target, &contents)?;
+                return Ok(format!("{}/{}", ASSETS_DIR, name));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Copies the photos of a plan's meals from the storage path into `dir`, so an export
+/// written there can show them. Returns the photos that were missing.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn copy_photos(storage_path: &Path, meal_plan: &MealPlan, dir: &Path) -> std::io::Result<Vec<String>> {
+    let mut missing = Vec::new();
+    for photo in meal_plan.meals().iter().filter_map(|meal| meal.photo.as_deref()) {
+        if is_url(photo) || missing.iter().any(|m| m == photo) {
+            continue;
+        }
+        let source = storage_path.join(photo);
+        if !is_stored_photo(photo) || !source.exists() {
+            missing.push(photo.to_string());
+            continue;
+        }
+        let target: PathBuf = dir.join(photo);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Copying a file onto itself would empty it
+        if target.exists() && source.canonicalize()? == target.canonicalize()? {
+            continue;
+        }
+        std::fs::copy(&source, &target)?;
+    }
+    Ok(missing)
+}
+
+/// Lowercases a file name and replaces anything but letters and digits with dashes
+#[cfg(not(target_arch = "wasm32"))]
+fn asset_stem(stem: &str) -> String {
+    let mut name = String::new();
+    for c in stem.trim().to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            name.push(c);
+        } else if !name.is_empty() && !name.ends_with('-') {
+            name.push('-');
+        }
+    }
+    let name = name.trim_end_matches('-');
+    if name.is_empty() { "photo".to_string() } else { name.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::{Day, Meal, MealType};
+    use chrono::{NaiveDate, Weekday};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_import_photo() {
+        let dir = tempdir().unwrap();
+        let storage = dir.path().join("storage");
+        std::fs::create_dir(&storage).unwrap();
+        let source = dir.path().join("My Chili!.JPG");
+        std::fs::write(&source, b"chili").unwrap();
+
+        assert_eq!(import_photo(&storage, source.to_str().unwrap()).unwrap(), "assets/my-chili.jpg");
+        assert_eq!(std::fs::read(storage.join("assets/my-chili.jpg")).unwrap(), b"chili");
+        // The same photo again is reused, a different one with the same name is not
+        assert_eq!(import_photo(&storage, source.to_str().unwrap()).unwrap(), "assets/my-chili.jpg");
+        std::fs::write(&source, b"other chili").unwrap();
+        assert_eq!(import_photo(&storage, source.to_str().unwrap()).unwrap(), "assets/my-chili-2.jpg");
+
+        assert_eq!(import_photo(&storage, " https://example.com/tacos.png ").unwrap(), "https://example.com/tacos.png");
+        let notes = dir.path().join("notes.txt");
+        std::fs::write(&notes, b"text").unwrap();
+        assert_eq!(import_photo(&storage, notes.to_str().unwrap()).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert_eq!(import_photo(&storage, "missing.png").unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_copy_photos() {
+        let dir = tempdir().unwrap();
+        let storage = dir.path().join("storage");
+        std::fs::create_dir_all(storage.join(ASSETS_DIR)).unwrap();
+        std::fs::write(storage.join("assets/chili.jpg"), b"chili").unwrap();
+
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let photos = [Some("assets/chili.jpg"), Some("https://example.com/tacos.png"), Some("assets/gone.jpg"), None];
+        for (weekday, photo) in [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu].into_iter().zip(photos) {
+            let mut meal = Meal::new(MealType::Dinner, Day::Weekday(weekday), "John".to_string(), "Dinner".to_string());
+            meal.photo = photo.map(str::to_string);
+            meal_plan.add_meal(meal);
+        }
+
+        let site = dir.path().join("site");
+        assert_eq!(copy_photos(&storage, &meal_plan, &site).unwrap(), vec!["assets/gone.jpg".to_string()]);
+        assert_eq!(std::fs::read(site.join("assets/chili.jpg")).unwrap(), b"chili");
+        assert!(copy_photos(&storage, &meal_plan, &storage).is_ok());
+        assert_eq!(std::fs::read(storage.join("assets/chili.jpg")).unwrap(), b"chili");
+
+        // Photos outside the assets directory are neither read nor written
+        let victim = dir.path().join("victim.txt");
+        std::fs::write(&victim, b"keep me").unwrap();
+        let mut meal = Meal::new(MealType::Lunch, Day::Weekday(Weekday::Fri), "John".to_string(), "Lunch".to_string());
+        meal.photo = Some(victim.to_str().unwrap().to_string());
+        meal_plan.add_meal(meal);
+        let mut meal = Meal::new(MealType::Dinner, Day::Weekday(Weekday::Fri), "John".to_string(), "Dinner".to_string());
+        meal.photo = Some("../victim.txt".to_string());
+        meal_plan.add_meal(meal);
+        let missing = copy_photos(&storage, &meal_plan, &site).unwrap();
+        assert_eq!(missing.len(), 3);
+        assert_eq!(std::fs::read(&victim).unwrap(), b"keep me");
+    }
+
+    #[test]
+    fn test_is_stored_photo() {
+        assert!(is_stored_photo("assets/chili.jpg"));
+        assert!(is_stored_photo("https://example.com/tacos.png"));
+        assert!(!is_stored_photo("/tmp/victim.txt"));
+        assert!(!is_stored_photo("../victim.txt"));
+        assert!(!is_stored_photo("assets/../../victim.txt"));
+        assert!(!is_stored_photo("assets/nested/chili.jpg"));
+        assert!(!is_stored_photo("chili.jpg"));
+
+        let meal: Result<Meal, _> = serde_json::from_str(
+            r#"{"meal_type":"Dinner","day":{"Weekday":"Mon"},"cook":"John","description":"Chili","photo":"/etc/passwd"}"#,
+        );
+        assert!(meal.unwrap_err().to_string().contains("neither a web address"));
+    }
+}
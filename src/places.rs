@@ -0,0 +1,175 @@
+use crate::models::MealPlan;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// A restaurant or takeout place the household orders from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Place {
+    pub name: String,
+    #[serde(default)]
+    pub cuisine: Option<String>,
+    /// Phone number or website for ordering
+    #[serde(default)]
+    pub contact: Option<String>,
+    /// What a meal from the place usually costs the household
+    #[serde(default)]
+    pub typical_cost: Option<f64>,
+}
+
+/// The household's places, kept in places.json in the storage path
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Places {
+    pub places: Vec<Place>,
+}
+
+impl Places {
+    /// Adds a place, replacing one with the same name. Returns true if it is new.
+    pub fn add(&mut self, place: Place) -> bool {
+        match self.places.iter_mut().find(|p| p.name.eq_ignore_ascii_case(&place.name)) {
+            Some(existing) => {
+                *existing = place;
+                false
+            }
+            None => {
+                self.places.push(place);
+                true
+            }
+        }
+    }
+
+    /// Removes a place by name, ignoring case, returning false if there was none
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.places.len();
+        self.places.retain(|p| !p.name.eq_ignore_ascii_case(name.trim()));
+        self.places.len() != before
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Place> {
+        self.places.iter().find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// What the week's meals from places cost, going by their typical cost, with how many
+    /// meals came from a place and how many of those have no known cost
+    pub fn eating_out(&self, meal_plan: &MealPlan) -> (f64, usize, usize) {
+        let costs: Vec<Option<f64>> = meal_plan
+            .meals()
+            .iter()
+            .filter_map(|meal| meal.place.as_deref())
+            .map(|name| self.find(name).and_then(|p| p.typical_cost))
+            .collect();
+        let total = costs.iter().flatten().sum();
+        let unpriced = costs.iter().filter(|c| c.is_none()).count();
+        (total, costs.len(), unpriced)
+    }
+
+    /// Places not ordered from since `cutoff`, those never ordered from first and then the
+    /// longest ago, with the date of the last order
+    pub fn not_ordered_since<'a>(&'a self, plans: &[MealPlan], cutoff: NaiveDate) -> Vec<(&'a Place, Option<NaiveDate>)> {
+        let mut last_ordered: HashMap<String, NaiveDate> = HashMap::new();
+        for plan in plans {
+            for meal in plan.meals() {
+                if let Some(name) = &meal.place {
+                    let date = plan.date_of(&meal.day);
+                    let last = last_ordered.entry(name.trim().to_lowercase()).or_insert(date);
+                    *last = (*last).max(date);
+                }
+            }
+        }
+        let mut places: Vec<(&Place, Option<NaiveDate>)> = self
+            .places
+            .iter()
+            .map(|place| (place, last_ordered.get(&place.name.trim().to_lowercase()).copied()))
+            .filter(|(_, last)| last.is_none_or(|date| date < cutoff))
+            .collect();
+        places.sort_by_key(|(place, last)| (*last, place.name.to_lowercase()));
+        places
+    }
+
+    /// Saves the places to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the places from a JSON file, which is empty until the first place is added
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, Meal, MealType};
+    use chrono::Weekday;
+    use tempfile::tempdir;
+
+    fn place(name: &str, typical_cost: Option<f64>) -> Place {
+        Place { name: name.to_string(), cuisine: None, contact: None, typical_cost }
+    }
+
+    fn places() -> Places {
+        let mut places = Places::default();
+        places.add(place("Thai Palace", Some(35.0)));
+        places.add(place("Pizza Barn", Some(24.5)));
+        places.add(place("Taco Truck", None));
+        places
+    }
+
+    fn takeout(plan: &mut MealPlan, day: Weekday, name: &str) {
+        let mut meal = Meal::new(MealType::Dinner, Day::Weekday(day), String::new(), name.to_string());
+        meal.place = Some(name.to_string());
+        plan.add_meal(meal);
+    }
+
+    #[test]
+    fn test_eating_out() {
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        takeout(&mut plan, Weekday::Mon, "thai palace");
+        takeout(&mut plan, Weekday::Fri, "Taco Truck");
+        plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), "Al".to_string(), "Soup".to_string()));
+        assert_eq!(places().eating_out(&plan), (35.0, 2, 1));
+    }
+
+    #[test]
+    fn test_not_ordered_since() {
+        let mut old = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        takeout(&mut old, Weekday::Mon, "Pizza Barn");
+        takeout(&mut old, Weekday::Fri, "Thai Palace");
+        let mut recent = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 30).unwrap());
+        takeout(&mut recent, Weekday::Tue, "Thai Palace");
+
+        let places = places();
+        let stale = places.not_ordered_since(&[old, recent], NaiveDate::from_ymd_opt(2023, 1, 16).unwrap());
+        let names: Vec<(&str, Option<NaiveDate>)> = stale.iter().map(|(p, last)| (p.name.as_str(), *last)).collect();
+        assert_eq!(names, vec![("Taco Truck", None), ("Pizza Barn", NaiveDate::from_ymd_opt(2023, 1, 2))]);
+    }
+
+    #[test]
+    fn test_places_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("places.json");
+        assert!(Places::load_from_json(&path).unwrap().places.is_empty());
+
+        let mut places = places();
+        assert!(!places.add(place("PIZZA BARN", Some(30.0))));
+        assert!(places.remove("taco truck"));
+        places.save_to_json(&path).unwrap();
+        let loaded = Places::load_from_json(&path).unwrap();
+        assert_eq!(loaded.places, places.places);
+        assert_eq!(loaded.find("pizza barn").unwrap().typical_cost, Some(30.0));
+    }
+}
@@ -0,0 +1,1574 @@
+use crate::store::Storage;
+use crate::{days, photos};
+use chrono::{DateTime, Datelike, SubsecRound, Utc, NaiveDate, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    crate::storage,
+    std::fs::File,
+    std::io::Read,
+    std::path::Path,
+};
+
+/// Represents the type of meal
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum MealType {
+    Breakfast,
+    Lunch,
+    Dinner,
+    Snack,
+}
+
+impl MealType {
+    /// Parses a meal type's name, ignoring case
+    pub fn parse(text: &str) -> Option<MealType> {
+        match text.trim().to_lowercase().as_str() {
+            "breakfast" => Some(MealType::Breakfast),
+            "lunch" => Some(MealType::Lunch),
+            "dinner" => Some(MealType::Dinner),
+            "snack" => Some(MealType::Snack),
+            _ => None,
+        }
+    }
+
+    /// When the meal is usually eaten, which calendar events start at and dishes are
+    /// planned to be ready by
+    pub fn usual_time(&self) -> NaiveTime {
+        let (hour, minute) = match self {
+            MealType::Breakfast => (8, 0),
+            MealType::Lunch => (12, 0),
+            MealType::Dinner => (18, 0),
+            MealType::Snack => (15, 0),
+        };
+        NaiveTime::from_hms_opt(hour, minute, 0).expect("meal times are valid")
+    }
+}
+
+impl std::fmt::Display for MealType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MealType::Breakfast => write!(f, "Breakfast"),
+            MealType::Lunch => write!(f, "Lunch"),
+            MealType::Dinner => write!(f, "Dinner"),
+            MealType::Snack => write!(f, "Snack"),
+        }
+    }
+}
+
+/// Represents a day, which can be a weekday or a specific date
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Day {
+    Weekday(Weekday),
+    Date(NaiveDate),
+}
+
+impl std::fmt::Display for Day {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Day::Weekday(weekday) => write!(f, "{:?}", weekday),
+            Day::Date(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+        }
+    }
+}
+
+/// Represents a single meal entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Meal {
+    pub meal_type: MealType,
+    pub day: Day,
+    pub cook: String,
+    pub description: String,
+    /// Who last added or edited this meal
+    #[serde(default)]
+    pub updated_by: Option<String>,
+    /// Edit history of this meal's slot, used to merge copies edited on different devices
+    #[serde(default)]
+    pub clock: VectorClock,
+    /// When the meal was first planned. Unknown for meals saved before this was recorded.
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the meal was last added or edited
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Whether the meal was actually cooked
+    #[serde(default)]
+    pub cooked: bool,
+    /// How the meal was rated once cooked, from 1 to 5
+    #[serde(default)]
+    pub rating: Option<u8>,
+    /// Labels such as a cuisine, set from a meal alias
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Link or name of the recipe
+    #[serde(default)]
+    pub recipe: Option<String>,
+    /// Packed to take along, such as a school lunch, so it's assembled the night before
+    #[serde(default)]
+    pub packed: bool,
+    /// Restaurant or takeout place the meal comes from, from the places list
+    #[serde(default)]
+    pub place: Option<String>,
+    /// Photo of the dish: a web address, or a path in the storage path's assets directory
+    #[serde(default, deserialize_with = "crate::photos::deserialize_photo")]
+    pub photo: Option<String>,
+}
+
+impl Meal {
+    /// Creates a new meal
+    pub fn new(meal_type: MealType, day: Day, cook: String, description: String) -> Self {
+        Self {
+            meal_type,
+            day,
+            cook,
+            description,
+            updated_by: None,
+            clock: VectorClock::default(),
+            created_at: None,
+            updated_at: None,
+            cooked: false,
+            rating: None,
+            tags: Vec::new(),
+            recipe: None,
+            packed: false,
+            place: None,
+            photo: None,
+        }
+    }
+}
+
+/// A short name that stands for a full meal when adding one, e.g. `tacos`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MealAlias {
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub recipe: Option<String>,
+}
+
+impl MealAlias {
+    /// Gives the meal this alias's description, tags and recipe
+    pub fn apply_to(&self, meal: &mut Meal) {
+        meal.description = self.description.clone();
+        meal.tags = self.tags.clone();
+        meal.recipe = self.recipe.clone();
+    }
+}
+
+/// Counts the edits each replica (device) has made to a meal slot
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VectorClock(BTreeMap<String, u64>);
+
+impl VectorClock {
+    /// Records one more edit by the given replica
+    pub fn tick(&mut self, replica: &str) {
+        *self.0.entry(replica.to_string()).or_insert(0) += 1;
+    }
+
+    /// Takes the highest counter of each replica from both clocks
+    pub fn merge(&mut self, other: &VectorClock) {
+        for (replica, count) in &other.0 {
+            let entry = self.0.entry(replica.clone()).or_insert(0);
+            *entry = (*entry).max(*count);
+        }
+    }
+
+    /// Returns true if this clock has seen every edit the other clock has
+    pub fn dominates(&self, other: &VectorClock) -> bool {
+        other.0.iter().all(|(replica, count)| self.0.get(replica).unwrap_or(&0) >= count)
+    }
+}
+
+/// Records that a meal slot was emptied, so merging with an older copy
+/// does not bring the removed meal back
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub meal_type: MealType,
+    pub day: Day,
+    pub clock: VectorClock,
+    /// When the meal was removed. Unknown for removals saved before this was recorded.
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    pub removed_at: Option<DateTime<Utc>>,
+    /// Who removed the meal, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed_by: Option<String>,
+}
+
+/// A removed meal kept in the week's trash so it can be restored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashedMeal {
+    /// Number used to restore the meal, unique within the week
+    pub id: u32,
+    pub meal: Meal,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub removed_at: DateTime<Utc>,
+}
+
+/// Records that a meal left the week's trash, restored or deleted for good, so merging with
+/// a copy that still has it doesn't put it back
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashTombstone {
+    pub meal_type: MealType,
+    pub day: Day,
+    /// When the meal that left the trash had been removed, which tells it from others
+    /// trashed from the same slot
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub removed_at: DateTime<Utc>,
+}
+
+impl TrashTombstone {
+    fn of(entry: &TrashedMeal) -> Self {
+        Self { meal_type: entry.meal.meal_type.clone(), day: entry.meal.day.clone(), removed_at: entry.removed_at }
+    }
+
+    /// Whether this records the given trash entry leaving the trash
+    pub fn matches(&self, entry: &TrashedMeal) -> bool {
+        self.meal_type == entry.meal.meal_type && self.day == entry.meal.day && self.removed_at == entry.removed_at
+    }
+}
+
+/// Replica name used until the plan is told which device is editing it
+pub const DEFAULT_REPLICA_ID: &str = "local";
+
+fn default_replica_id() -> String {
+    DEFAULT_REPLICA_ID.to_string()
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+/// The state of one meal slot, as seen by a merge
+#[derive(Clone, Copy)]
+enum SlotState<'a> {
+    Planned(&'a Meal),
+    Removed(&'a Tombstone),
+}
+
+impl SlotState<'_> {
+    fn clock(&self) -> &VectorClock {
+        match self {
+            SlotState::Planned(meal) => &meal.clock,
+            SlotState::Removed(tombstone) => &tombstone.clock,
+        }
+    }
+
+    fn updated_at(&self) -> Option<DateTime<Utc>> {
+        match self {
+            SlotState::Planned(meal) => meal.updated_at,
+            SlotState::Removed(_) => None,
+        }
+    }
+
+    /// Returns true if the other side was edited more recently, when both edit times are known
+    fn older_than(&self, other: &SlotState) -> Option<bool> {
+        match (self.updated_at(), other.updated_at()) {
+            (Some(ours), Some(theirs)) if ours != theirs => Some(theirs > ours),
+            _ => None,
+        }
+    }
+
+    /// Deterministic ordering key used when neither side's clock dominates. It covers every
+    /// field of the meal, so versions differing only in tags or a photo still order the same way.
+    fn tie_break_key(&self) -> (bool, String) {
+        match self {
+            SlotState::Planned(meal) => (true, serde_json::to_string(meal).unwrap_or_default()),
+            SlotState::Removed(_) => (false, String::new()),
+        }
+    }
+}
+
+/// Identifies a meal's place in the plan
+type Slot = (MealType, Day);
+
+/// Which of two copies of a plan has the newer version of a meal slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewerSide {
+    Ours,
+    Theirs,
+    /// Each copy changed the slot without seeing the other's change
+    Both,
+}
+
+/// A meal slot that differs between two copies of a plan. A side without a meal had it
+/// removed, or never planned it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotDifference {
+    pub meal_type: MealType,
+    pub day: Day,
+    pub ours: Option<Meal>,
+    pub theirs: Option<Meal>,
+    pub newer: NewerSide,
+}
+
+/// Represents a week's meal plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredMealPlan")]
+pub struct MealPlan {
+    /// Meals in the order they were added, changed only through the plan's methods so the
+    /// slot index stays in step
+    meals: Vec<Meal>,
+    pub week_start_date: NaiveDate,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub last_modified: DateTime<Utc>,
+    /// Slots whose meals were removed, kept so merges can tell removals from additions
+    #[serde(default)]
+    pub tombstones: Vec<Tombstone>,
+    /// Meals removed this week, oldest first
+    #[serde(default)]
+    pub trash: Vec<TrashedMeal>,
+    /// Trash entries taken out this week, kept so merges can tell them from new ones
+    #[serde(default)]
+    pub trash_tombstones: Vec<TrashTombstone>,
+    /// Sequence number of the last change-log event this copy includes, 0 if unknown. Tells
+    /// whether the saved snapshot or the log is the newer of the two.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub event_seq: u64,
+    /// The device making changes to this copy of the plan
+    #[serde(skip, default = "default_replica_id")]
+    pub replica_id: String,
+    /// The person making changes to this copy of the plan, recorded on the meals they remove
+    #[serde(skip)]
+    pub user: Option<String>,
+    /// Whether saving this copy may change a frozen week, as with `--force`
+    #[serde(skip)]
+    pub allow_frozen: bool,
+    /// Position of each slot's meal in `meals`, for constant-time lookups
+    #[serde(skip)]
+    meal_index: HashMap<Slot, usize>,
+}
+
+/// The serialized form of a meal plan, converted so the slot index is built on load
+#[derive(Deserialize)]
+struct StoredMealPlan {
+    meals: Vec<Meal>,
+    week_start_date: NaiveDate,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    last_modified: DateTime<Utc>,
+    #[serde(default)]
+    tombstones: Vec<Tombstone>,
+    #[serde(default)]
+    trash: Vec<TrashedMeal>,
+    #[serde(default)]
+    trash_tombstones: Vec<TrashTombstone>,
+    #[serde(default)]
+    event_seq: u64,
+}
+
+impl From<StoredMealPlan> for MealPlan {
+    fn from(stored: StoredMealPlan) -> Self {
+        let mut plan = MealPlan {
+            meals: stored.meals,
+            week_start_date: stored.week_start_date,
+            last_modified: stored.last_modified,
+            tombstones: stored.tombstones,
+            trash: stored.trash,
+            trash_tombstones: stored.trash_tombstones,
+            event_seq: stored.event_seq,
+            replica_id: default_replica_id(),
+            user: None,
+            allow_frozen: false,
+            meal_index: HashMap::new(),
+        };
+        plan.rebuild_index();
+        plan
+    }
+}
+
+impl MealPlan {
+    /// Creates a new empty meal plan
+    pub fn new(week_start_date: NaiveDate) -> Self {
+        Self {
+            meals: Vec::new(),
+            week_start_date,
+            last_modified: Utc::now(),
+            tombstones: Vec::new(),
+            trash: Vec::new(),
+            trash_tombstones: Vec::new(),
+            event_seq: 0,
+            replica_id: default_replica_id(),
+            user: None,
+            allow_frozen: false,
+            meal_index: HashMap::new(),
+        }
+    }
+
+    /// Adds a meal to the plan, recording the edit on the slot's clock and the meal's
+    /// timestamps. A meal that already has a creation time keeps it.
+    pub fn add_meal(&mut self, mut meal: Meal) {
+        let mut clock = self.slot_clock(&meal.meal_type, &meal.day);
+        clock.tick(&self.replica_id);
+        meal.clock = clock;
+
+        // Whole seconds, as stored, so a reloaded meal compares equal
+        let now = Utc::now().trunc_subsecs(0);
+        meal.created_at.get_or_insert(now);
+        meal.updated_at = Some(now);
+
+        self.restore_meal(meal);
+        self.last_modified = Utc::now();
+    }
+
+    /// Puts a meal in its slot exactly as given, clock included, replacing whatever was there.
+    /// Used when replaying or merging edits that were already recorded elsewhere.
+    pub fn restore_meal(&mut self, meal: Meal) {
+        self.tombstones.retain(|t| !(t.meal_type == meal.meal_type && t.day == meal.day));
+        match self.meal_position(&meal.meal_type, &meal.day) {
+            Some(index) => self.meals[index] = meal,
+            None => {
+                self.meal_index.insert((meal.meal_type.clone(), meal.day.clone()), self.meals.len());
+                self.meals.push(meal);
+            }
+        }
+    }
+
+    /// Empties a slot with the given tombstone, keeping its clock as is
+    pub fn restore_tombstone(&mut self, tombstone: Tombstone) {
+        if let Some(index) = self.meal_position(&tombstone.meal_type, &tombstone.day) {
+            self.meals.remove(index);
+            self.rebuild_index();
+        }
+        self.tombstones.retain(|t| !(t.meal_type == tombstone.meal_type && t.day == tombstone.day));
+        self.tombstones.push(tombstone);
+    }
+
+    /// Removes a meal from the plan, leaving a tombstone in its slot
+    pub fn remove_meal(&mut self, meal_type: &MealType, day: &Day) -> Option<Meal> {
+        if let Some(index) = self.meal_position(meal_type, day) {
+            let mut clock = self.slot_clock(meal_type, day);
+            clock.tick(&self.replica_id);
+
+            let meal = self.meals.remove(index);
+            self.rebuild_index();
+            self.tombstones.retain(|t| !(&t.meal_type == meal_type && &t.day == day));
+            self.last_modified = Utc::now();
+            self.tombstones.push(Tombstone {
+                meal_type: meal_type.clone(),
+                day: day.clone(),
+                clock,
+                removed_at: Some(self.last_modified.trunc_subsecs(0)),
+                removed_by: self.user.clone(),
+            });
+            Some(meal)
+        } else {
+            None
+        }
+    }
+
+    /// Records that a meal was cooked, with an optional rating. Returns None if the slot is empty.
+    pub fn mark_cooked(&mut self, meal_type: &MealType, day: &Day, rating: Option<u8>) -> Option<&Meal> {
+        let mut meal = self.find_meal(meal_type, day)?.clone();
+        meal.cooked = true;
+        if rating.is_some() {
+            meal.rating = rating;
+        }
+        self.add_meal(meal);
+        self.find_meal(meal_type, day)
+    }
+
+    /// Changes details of a planned meal, such as whether it's packed, as one edit
+    pub fn change_meal(&mut self, meal_type: &MealType, day: &Day, change: impl FnOnce(&mut Meal)) -> Option<&Meal> {
+        let mut meal = self.find_meal(meal_type, day)?.clone();
+        change(&mut meal);
+        self.add_meal(meal);
+        self.find_meal(meal_type, day)
+    }
+
+    /// Removes a meal from the plan and keeps it in the trash. Returns the trash entry's id.
+    pub fn trash_meal(&mut self, meal_type: &MealType, day: &Day) -> Option<u32> {
+        let meal = self.remove_meal(meal_type, day)?;
+        // Whole seconds, as stored, so a reloaded entry compares equal
+        let removed_at = self.last_modified.trunc_subsecs(0);
+        Some(self.put_in_trash(meal, removed_at))
+    }
+
+    fn put_in_trash(&mut self, meal: Meal, removed_at: DateTime<Utc>) -> u32 {
+        let id = self.trash.iter().map(|t| t.id).max().unwrap_or(0) + 1;
+        self.trash.push(TrashedMeal { id, meal, removed_at });
+        id
+    }
+
+    /// Takes a meal out of the trash without putting it back in the plan
+    pub fn take_from_trash(&mut self, id: u32) -> Option<TrashedMeal> {
+        let index = self.trash.iter().position(|t| t.id == id)?;
+        let entry = self.trash.remove(index);
+        self.add_trash_tombstone(TrashTombstone::of(&entry));
+        Some(entry)
+    }
+
+    /// Records that a trash entry left the trash, dropping it if it's still there
+    pub fn add_trash_tombstone(&mut self, tombstone: TrashTombstone) {
+        self.trash.retain(|entry| !tombstone.matches(entry));
+        if !self.trash_tombstones.contains(&tombstone) {
+            self.trash_tombstones.push(tombstone);
+        }
+    }
+
+    /// Puts a trashed meal back in its slot. Fails if the slot has been filled since,
+    /// leaving the meal in the trash.
+    pub fn restore_from_trash(&mut self, id: u32) -> Result<&Meal, String> {
+        let entry = self.trash.iter().find(|t| t.id == id)
+            .ok_or_else(|| format!("No meal with id {} in the trash.", id))?;
+        let (meal_type, day) = (entry.meal.meal_type.clone(), entry.meal.day.clone());
+        if self.find_meal(&meal_type, &day).is_some() {
+            return Err(format!("There is already a {} meal for {}. Remove it first.", meal_type, day));
+        }
+
+        let entry = self.take_from_trash(id).expect("trash entry was just found");
+        self.add_meal(entry.meal);
+        Ok(self.find_meal(&meal_type, &day).expect("meal was just restored"))
+    }
+
+    /// Permanently deletes trashed meals removed before the cutoff. Returns how many were deleted.
+    pub fn purge_trash(&mut self, cutoff: DateTime<Utc>) -> usize {
+        let expired: Vec<u32> = self.trash.iter().filter(|t| t.removed_at < cutoff).map(|t| t.id).collect();
+        for id in &expired {
+            self.take_from_trash(*id);
+        }
+        expired.len()
+    }
+
+    /// Returns a copy of the plan holding only the meals added, edited or removed at or
+    /// after `since`, with removals kept as tombstones. Changes saved before their times
+    /// were recorded count as made when the plan was last modified.
+    pub fn modified_since(&self, since: DateTime<Utc>) -> MealPlan {
+        let mut plan = self.clone();
+        let last_modified = self.last_modified;
+        plan.meals.retain(|meal| meal.updated_at.unwrap_or(last_modified) >= since);
+        plan.tombstones.retain(|t| t.removed_at.unwrap_or(last_modified) >= since);
+        plan.trash.retain(|t| t.removed_at >= since);
+        plan.rebuild_index();
+        plan
+    }
+
+    /// Returns a meal's description with its placeholders filled in for display.
+    /// Supported: `{{cook}}`, `{{meal_type}}`, `{{day}}`, `{{date}}`, `{{week_start}}`.
+    pub fn render_description(&self, meal: &Meal) -> String {
+        let date = self.date_of(&meal.day);
+        expand_placeholders(&meal.description, |name| match name {
+            "cook" => Some(meal.cook.clone()),
+            "meal_type" => Some(meal.meal_type.to_string()),
+            "day" => Some(date.format("%A").to_string()),
+            "date" => Some(date.format("%Y-%m-%d").to_string()),
+            "week_start" => Some(self.week_start_date.format("%Y-%m-%d").to_string()),
+            _ => None,
+        })
+    }
+
+    /// Returns the calendar date of a day in this plan's week. Weekdays resolve to their
+    /// next occurrence on or after the week start date.
+    pub fn date_of(&self, day: &Day) -> NaiveDate {
+        match day {
+            Day::Weekday(weekday) => {
+                let days_to_add = (weekday.num_days_from_monday() as i64
+                    - self.week_start_date.weekday().num_days_from_monday() as i64)
+                    .rem_euclid(7);
+                self.week_start_date + chrono::Duration::days(days_to_add)
+            }
+            Day::Date(date) => *date,
+        }
+    }
+
+    /// The planned meals, in the order they were added
+    pub fn meals(&self) -> &[Meal] {
+        &self.meals
+    }
+
+    /// Keeps only the meals `keep` returns true for, without leaving tombstones, as when
+    /// narrowing a copy of the plan down for display or export
+    pub fn retain_meals(&mut self, keep: impl FnMut(&Meal) -> bool) {
+        self.meals.retain(keep);
+        self.rebuild_index();
+    }
+
+    /// Finds a meal in the plan
+    pub fn find_meal(&self, meal_type: &MealType, day: &Day) -> Option<&Meal> {
+        self.meal_position(meal_type, day).map(|index| &self.meals[index])
+    }
+
+    /// Looks a slot up in the index, which every change to `meals` keeps in step
+    fn meal_position(&self, meal_type: &MealType, day: &Day) -> Option<usize> {
+        self.meal_index.get(&(meal_type.clone(), day.clone())).copied()
+    }
+
+    fn rebuild_index(&mut self) {
+        self.meal_index = self.meals
+            .iter()
+            .enumerate()
+            .map(|(index, meal)| ((meal.meal_type.clone(), meal.day.clone()), index))
+            .collect();
+    }
+
+    /// Returns the combined clock of everything that has happened to a slot
+    fn slot_clock(&self, meal_type: &MealType, day: &Day) -> VectorClock {
+        let mut clock = VectorClock::default();
+        if let Some(state) = self.slot_state(meal_type, day) {
+            clock.merge(state.clock());
+        }
+        clock
+    }
+
+    fn slot_state(&self, meal_type: &MealType, day: &Day) -> Option<SlotState<'_>> {
+        self.find_meal(meal_type, day)
+            .map(SlotState::Planned)
+            .or_else(|| {
+                self.tombstones
+                    .iter()
+                    .find(|t| &t.meal_type == meal_type && &t.day == day)
+                    .map(SlotState::Removed)
+            })
+    }
+
+    /// Lists the meal slots that differ from another copy of the same week, with which copy
+    /// changed each one last, judged as a merge would: by the slots' clocks, then edit times
+    pub fn differences(&self, other: &MealPlan) -> Vec<SlotDifference> {
+        let mut slots: Vec<(&MealType, &Day)> = Vec::new();
+        let all_slots = self.meals.iter().map(|m| (&m.meal_type, &m.day))
+            .chain(other.meals.iter().map(|m| (&m.meal_type, &m.day)))
+            .chain(self.tombstones.iter().map(|t| (&t.meal_type, &t.day)))
+            .chain(other.tombstones.iter().map(|t| (&t.meal_type, &t.day)));
+        for slot in all_slots {
+            if !slots.contains(&slot) {
+                slots.push(slot);
+            }
+        }
+
+        let mut differences = Vec::new();
+        for (meal_type, day) in slots {
+            let ours = self.find_meal(meal_type, day);
+            let theirs = other.find_meal(meal_type, day);
+            if ours == theirs {
+                continue;
+            }
+            let newer = match (self.slot_state(meal_type, day), other.slot_state(meal_type, day)) {
+                (Some(_), None) => NewerSide::Ours,
+                (None, Some(_)) => NewerSide::Theirs,
+                (Some(a), Some(b)) => match (a.clock().dominates(b.clock()), b.clock().dominates(a.clock())) {
+                    (true, false) => NewerSide::Ours,
+                    (false, true) => NewerSide::Theirs,
+                    _ => match a.older_than(&b) {
+                        Some(true) => NewerSide::Theirs,
+                        Some(false) => NewerSide::Ours,
+                        None => NewerSide::Both,
+                    },
+                },
+                (None, None) => continue,
+            };
+            differences.push(SlotDifference {
+                meal_type: meal_type.clone(),
+                day: day.clone(),
+                ours: ours.cloned(),
+                theirs: theirs.cloned(),
+                newer,
+            });
+        }
+        differences
+    }
+
+    /// The slots both copies planned with a different meal, each without having seen the
+    /// other's edit, as `(ours, theirs)`. A merge keeps the more recently edited of the two.
+    pub fn concurrent_meals(&self, other: &MealPlan) -> Vec<(Meal, Meal)> {
+        self.meals
+            .iter()
+            .filter_map(|ours| {
+                let theirs = other.find_meal(&ours.meal_type, &ours.day)?;
+                let concurrent = ours != theirs && !ours.clock.dominates(&theirs.clock) && !theirs.clock.dominates(&ours.clock);
+                concurrent.then(|| (ours.clone(), theirs.clone()))
+            })
+            .collect()
+    }
+
+    /// Combines this plan with another copy of the same week, slot by slot. A slot takes
+    /// whichever side has seen the other's edits; when both sides edited it independently,
+    /// a planned meal beats a removal, then the more recently edited meal wins, and
+    /// otherwise the meal content decides. Copies without clock history fall back to the
+    /// meals' edit times and then the most recently modified plan. The result is the same
+    /// whichever side performs the merge.
+    pub fn merged_with(&self, other: &MealPlan) -> MealPlan {
+        let other_is_newer = other.last_modified > self.last_modified;
+
+        let mut slots: Vec<(&MealType, &Day)> = Vec::new();
+        let all_slots = self.meals.iter().map(|m| (&m.meal_type, &m.day))
+            .chain(other.meals.iter().map(|m| (&m.meal_type, &m.day)))
+            .chain(self.tombstones.iter().map(|t| (&t.meal_type, &t.day)))
+            .chain(other.tombstones.iter().map(|t| (&t.meal_type, &t.day)));
+        for slot in all_slots {
+            if !slots.contains(&slot) {
+                slots.push(slot);
+            }
+        }
+
+        let mut merged = MealPlan {
+            meals: Vec::new(),
+            week_start_date: self.week_start_date,
+            last_modified: self.last_modified.max(other.last_modified),
+            tombstones: Vec::new(),
+            trash: self.trash.clone(),
+            trash_tombstones: self.trash_tombstones.clone(),
+            event_seq: self.event_seq,
+            replica_id: self.replica_id.clone(),
+            user: self.user.clone(),
+            allow_frozen: self.allow_frozen,
+            meal_index: HashMap::new(),
+        };
+        for entry in &other.trash {
+            let known = merged.trash.iter().any(|t| t.meal == entry.meal && t.removed_at == entry.removed_at);
+            if !known {
+                merged.put_in_trash(entry.meal.clone(), entry.removed_at);
+            }
+        }
+        // Entries taken out of the trash on either side stay out
+        for tombstone in &other.trash_tombstones {
+            merged.add_trash_tombstone(tombstone.clone());
+        }
+        let tombstones = merged.trash_tombstones.clone();
+        merged.trash.retain(|entry| !tombstones.iter().any(|t| t.matches(entry)));
+        merged.trash.sort_by_key(|t| (t.removed_at, t.id));
+
+        for (meal_type, day) in slots {
+            let ours = self.slot_state(meal_type, day);
+            let theirs = other.slot_state(meal_type, day);
+
+            let winner = match (ours, theirs) {
+                (Some(ours), Some(theirs)) => {
+                    let (ours_clock, theirs_clock) = (ours.clock(), theirs.clock());
+                    let theirs_wins = if ours_clock == theirs_clock {
+                        // Same history (or none at all): prefer the newer plan
+                        if ours.tie_break_key() == theirs.tie_break_key() {
+                            false
+                        } else if let Some(theirs_is_newer) = ours.older_than(&theirs) {
+                            theirs_is_newer
+                        } else if self.last_modified != other.last_modified {
+                            other_is_newer
+                        } else {
+                            theirs.tie_break_key() > ours.tie_break_key()
+                        }
+                    } else if ours_clock.dominates(theirs_clock) {
+                        false
+                    } else if theirs_clock.dominates(ours_clock) {
+                        true
+                    } else {
+                        ours.older_than(&theirs)
+                            .unwrap_or_else(|| theirs.tie_break_key() > ours.tie_break_key())
+                    };
+
+                    let mut clock = ours_clock.clone();
+                    clock.merge(theirs_clock);
+                    (if theirs_wins { theirs } else { ours }, clock)
+                }
+                (Some(state), None) | (None, Some(state)) => (state, state.clock().clone()),
+                (None, None) => continue,
+            };
+
+            match winner {
+                (SlotState::Planned(meal), clock) => {
+                    let mut meal = meal.clone();
+                    meal.clock = clock;
+                    merged.restore_meal(meal);
+                }
+                (SlotState::Removed(tombstone), clock) => {
+                    let mut tombstone = tombstone.clone();
+                    tombstone.clock = clock;
+                    merged.restore_tombstone(tombstone);
+                }
+            }
+        }
+
+        merged
+    }
+
+    /// Loads the plan stored under `name`, or returns None if nothing is stored there
+    pub fn load_from(storage: &dyn Storage, name: &str) -> std::io::Result<Option<Self>> {
+        match storage.read(name)? {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores the plan as JSON under `name`
+    pub fn save_to(&self, storage: &mut dyn Storage, name: &str) -> std::io::Result<()> {
+        storage.write(name, &serde_json::to_string_pretty(self)?)
+    }
+
+    /// Saves the meal plan to a JSON file, leaving the file untouched if nothing changed
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        storage::write_if_changed(path, json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Saves the meal plan to a JSON file with a checksum, keeping up to `keep` previous versions
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_to_json_with_backups<P: AsRef<Path>>(&self, path: P, keep: usize) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        storage::write_with_backups(path.as_ref(), json.as_bytes(), keep)?;
+        Ok(())
+    }
+
+    /// Loads a meal plan from a JSON file
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let meal_plan: MealPlan = serde_json::from_str(&contents)?;
+        Ok(meal_plan)
+    }
+
+    /// Saves the meal plan to a Markdown file, leaving the file untouched if nothing changed
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_to_markdown<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        storage::write_if_changed(path, self.to_markdown().as_bytes())?;
+        Ok(())
+    }
+
+    /// Renders the meal plan as Markdown, grouped by day
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# Meal Plan for Week of {}\n\n", self.week_start_date.format("%Y-%m-%d"));
+        
+        // Group meals by day
+        let mut meals_by_day: HashMap<&Day, Vec<&Meal>> = HashMap::new();
+        for meal in &self.meals {
+            meals_by_day.entry(&meal.day).or_default().push(meal);
+        }
+        
+        // Sort days
+        let mut days: Vec<&Day> = meals_by_day.keys().cloned().collect();
+        days.sort_by_key(|d| match d {
+            Day::Weekday(w) => format!("1{:?}", w),
+            Day::Date(date) => format!("0{}", date),
+        });
+        
+        for day in days {
+            markdown.push_str(&format!("## {}\n\n", day));
+            
+            if let Some(meals) = meals_by_day.get(day) {
+                for meal in meals {
+                    markdown.push_str(&format!("### {}\n", meal.meal_type));
+                    markdown.push_str(&format!("- Cook: {}\n", meal.cook));
+                    markdown.push_str(&format!("- Description: {}\n", self.render_description(meal)));
+                    if !meal.tags.is_empty() {
+                        markdown.push_str(&format!("- Tags: {}\n", meal.tags.join(", ")));
+                    }
+                    if let Some(recipe) = &meal.recipe {
+                        markdown.push_str(&format!("- Recipe: {}\n", recipe));
+                    }
+                    if let Some(photo) = &meal.photo {
+                        markdown.push_str(&format!("- Photo: ![{}]({})\n", self.render_description(meal), photo));
+                    }
+                    if let Some(updated_by) = &meal.updated_by {
+                        markdown.push_str(&format!("- Updated by: {}\n", updated_by));
+                    }
+                    markdown.push('\n');
+                }
+            }
+        }
+        
+        if !self.trash.is_empty() {
+            markdown.push_str("## Trash\n\n");
+            for entry in &self.trash {
+                markdown.push_str(&format!(
+                    "- {}: {} on {}, {} (cook: {})\n",
+                    entry.id, entry.meal.meal_type, entry.meal.day, entry.meal.description, entry.meal.cook
+                ));
+            }
+            markdown.push('\n');
+        }
+
+        markdown.push_str(&format!("\n*Last modified: {}*", self.last_modified.format("%Y-%m-%d %H:%M:%S")));
+        markdown
+    }
+
+    /// Loads a meal plan from a Markdown file, as [`MealPlan::from_markdown`] reads it
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_from_markdown<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let markdown = std::fs::read_to_string(path)?;
+        Self::from_markdown(&markdown).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Reads a plan back from the Markdown [`MealPlan::to_markdown`] writes: each meal's cook,
+    /// description, tags, recipe and photo. Day headings may be anything
+    /// [`days::parse_day`] accepts, such as "Mon", "Thursday", "Montag" or a date. The
+    /// trash and timestamps are left out, as they aren't meant to be edited there.
+    pub fn from_markdown(markdown: &str) -> Result<Self, String> {
+        let mut plan: Option<MealPlan> = None;
+        let mut day: Option<Day> = None;
+        let mut meal: Option<Meal> = None;
+        let mut in_trash = false;
+
+        for (n, line) in markdown.lines().enumerate() {
+            let at_line = |e: String| format!("Line {}: {}", n + 1, e);
+            let line = line.trim();
+            if let Some(title) = line.strip_prefix("# ") {
+                let date = title.trim().strip_prefix("Meal Plan for Week of ")
+                    .and_then(|date| NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok())
+                    .ok_or_else(|| at_line("Expected \"# Meal Plan for Week of YYYY-MM-DD\".".to_string()))?;
+                plan = Some(MealPlan::new(date));
+                continue;
+            }
+            let Some(plan) = plan.as_mut() else {
+                if line.is_empty() {
+                    continue;
+                }
+                return Err(at_line("The plan has to start with its \"# Meal Plan for Week of\" title.".to_string()));
+            };
+
+            if let Some(heading) = line.strip_prefix("## ") {
+                plan.finish_markdown_meal(meal.take())?;
+                in_trash = heading.trim().eq_ignore_ascii_case("trash");
+                if !in_trash {
+                    day = Some(days::parse_day(heading, plan.week_start_date.weekday()).map_err(at_line)?);
+                }
+            } else if in_trash {
+                continue;
+            } else if let Some(heading) = line.strip_prefix("### ") {
+                plan.finish_markdown_meal(meal.take())?;
+                let meal_type = MealType::parse(heading)
+                    .ok_or_else(|| at_line(format!("Unknown meal type \"{}\".", heading.trim())))?;
+                let day = day.clone().ok_or_else(|| at_line("A meal has to come under a day's heading.".to_string()))?;
+                meal = Some(Meal::new(meal_type, day, String::new(), String::new()));
+            } else if let Some((key, value)) = line.strip_prefix("- ").and_then(|item| item.split_once(':')) {
+                let Some(meal) = meal.as_mut() else {
+                    continue;
+                };
+                let value = value.trim();
+                match key.trim().to_lowercase().as_str() {
+                    "cook" => meal.cook = value.to_string(),
+                    "description" => meal.description = value.to_string(),
+                    "tags" => meal.tags = value.split(',').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect(),
+                    "recipe" => meal.recipe = Some(value.to_string()).filter(|r| !r.is_empty()),
+                    "photo" => {
+                        let photo = value.rsplit_once("](").and_then(|(_, rest)| rest.strip_suffix(')')).unwrap_or(value).trim();
+                        if !photos::is_stored_photo(photo) {
+                            return Err(at_line(format!("Photo {:?} is neither a web address nor a file in {}/.", photo, photos::ASSETS_DIR)));
+                        }
+                        meal.photo = Some(photo.to_string());
+                    }
+                    "updated by" => meal.updated_by = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut plan = plan.ok_or("The file has no \"# Meal Plan for Week of\" title.")?;
+        plan.finish_markdown_meal(meal)?;
+        Ok(plan)
+    }
+
+    fn finish_markdown_meal(&mut self, meal: Option<Meal>) -> Result<(), String> {
+        if let Some(meal) = meal {
+            if meal.description.is_empty() {
+                return Err(format!("The {} on {} has no description.", meal.meal_type, meal.day));
+            }
+            self.add_meal(meal);
+        }
+        Ok(())
+    }
+
+    /// Applies the edits made to a Markdown copy of the plan. `base` is the plan the
+    /// Markdown was written from, and `markdown` the plan read back from it. Each slot the
+    /// Markdown changed is changed the same way here, as a new edit: meals are edited in
+    /// place, added, or moved to the trash. Slots it left alone keep what this plan has, so
+    /// edits made elsewhere since aren't undone. Returns how many slots changed.
+    pub fn apply_markdown_edits(&mut self, base: &MealPlan, markdown: &MealPlan) -> usize {
+        let as_written = |plan: &MealPlan, meal: &Meal| {
+            (meal.cook.clone(), plan.render_description(meal), meal.tags.clone(), meal.recipe.clone(), meal.photo.clone())
+        };
+        let mut changed = 0;
+
+        for edited in &markdown.meals {
+            let (meal_type, day) = (&edited.meal_type, &edited.day);
+            let unchanged = base.find_meal(meal_type, day)
+                .is_some_and(|meal| as_written(base, meal) == as_written(markdown, edited));
+            if unchanged {
+                continue;
+            }
+            let updated_by = self.user.clone().or_else(|| edited.updated_by.clone());
+            let stored = self.find_meal(meal_type, day).map(|meal| (meal.description.clone(), self.render_description(meal)));
+            match stored {
+                Some((description, rendered)) => {
+                    self.change_meal(meal_type, day, |meal| {
+                        meal.cook = edited.cook.clone();
+                        // A description shown with its placeholders filled in is kept as stored
+                        meal.description = if rendered == edited.description { description } else { edited.description.clone() };
+                        meal.tags = edited.tags.clone();
+                        meal.recipe = edited.recipe.clone();
+                        meal.photo = edited.photo.clone();
+                        meal.updated_by = updated_by;
+                    });
+                }
+                None => {
+                    let mut meal = Meal::new(meal_type.clone(), day.clone(), edited.cook.clone(), edited.description.clone());
+                    meal.tags = edited.tags.clone();
+                    meal.recipe = edited.recipe.clone();
+                    meal.photo = edited.photo.clone();
+                    meal.updated_by = updated_by;
+                    self.add_meal(meal);
+                }
+            }
+            changed += 1;
+        }
+
+        for meal in &base.meals {
+            if markdown.find_meal(&meal.meal_type, &meal.day).is_none() && self.trash_meal(&meal.meal_type, &meal.day).is_some() {
+                changed += 1;
+            }
+        }
+        changed
+    }
+}
+
+
+/// Replaces each `{{name}}` in the text with its value. Names without a value, and
+/// unclosed braces, are left as written.
+pub fn expand_placeholders<F: Fn(&str) -> Option<String>>(text: &str, value: F) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let placeholder = &rest[start..start + len + 4];
+        expanded.push_str(&rest[..start]);
+        match value(placeholder[2..placeholder.len() - 2].trim()) {
+            Some(value) => expanded.push_str(&value),
+            None => expanded.push_str(placeholder),
+        }
+        rest = &rest[start + len + 4..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_meal_creation() {
+        let meal = Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Mon),
+            "John".to_string(),
+            "Steak with potatoes".to_string(),
+        );
+        
+        assert_eq!(meal.meal_type, MealType::Dinner);
+        assert_eq!(meal.cook, "John");
+        assert_eq!(meal.description, "Steak with potatoes");
+        
+        match meal.day {
+            Day::Weekday(day) => assert_eq!(day, Weekday::Mon),
+            _ => panic!("Expected Weekday"),
+        }
+    }
+
+    #[test]
+    fn test_meal_plan_operations() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut plan = MealPlan::new(week_start);
+        
+        // Add a meal
+        let meal = Meal::new(
+            MealType::Lunch,
+            Day::Weekday(Weekday::Wed),
+            "Alice".to_string(),
+            "Caesar Salad".to_string(),
+        );
+        plan.add_meal(meal);
+        
+        // Find the meal
+        let found = plan.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Wed));
+        assert!(found.is_some());
+        assert_eq!(found.unwrap().cook, "Alice");
+        
+        // Remove the meal
+        let removed = plan.remove_meal(&MealType::Lunch, &Day::Weekday(Weekday::Wed));
+        assert!(removed.is_some());
+        
+        // Verify it's gone
+        let not_found = plan.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Wed));
+        assert!(not_found.is_none());
+    }
+
+    #[test]
+    fn test_merged_with() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut laptop = MealPlan::new(week_start);
+        let mut kitchen = MealPlan::new(week_start);
+
+        laptop.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Mon),
+            "Alice".to_string(),
+            "Lasagna".to_string(),
+        ));
+        kitchen.add_meal(Meal::new(
+            MealType::Lunch,
+            Day::Weekday(Weekday::Mon),
+            "Bob".to_string(),
+            "Soup".to_string(),
+        ));
+        kitchen.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Mon),
+            "Bob".to_string(),
+            "Leftovers".to_string(),
+        ));
+        kitchen.last_modified = laptop.last_modified + chrono::Duration::seconds(5);
+
+        let merged = laptop.merged_with(&kitchen);
+        assert_eq!(merged.meals.len(), 2);
+        assert_eq!(merged.last_modified, kitchen.last_modified);
+        let dinner = merged.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap();
+        assert_eq!(dinner.description, "Leftovers");
+
+        // Both sides converge on the same result
+        let reverse = kitchen.merged_with(&laptop);
+        let dinner = reverse.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap();
+        assert_eq!(dinner.description, "Leftovers");
+        assert_eq!(reverse.meals.len(), 2);
+    }
+
+    #[test]
+    fn test_merge_keeps_restored_meals_out_of_trash() {
+        let mut laptop = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        laptop.replica_id = "laptop".to_string();
+        laptop.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Lasagna".to_string()));
+        laptop.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), "Bob".to_string(), "Tacos".to_string()));
+        laptop.trash_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon));
+        laptop.trash_meal(&MealType::Dinner, &Day::Weekday(Weekday::Tue));
+        let mut kitchen = laptop.clone();
+        kitchen.replica_id = "kitchen".to_string();
+
+        // Restored on the laptop while the kitchen still has it in the trash
+        laptop.restore_from_trash(1).unwrap();
+        for (a, b) in [(&laptop, &kitchen), (&kitchen, &laptop)] {
+            let merged = a.merged_with(b);
+            assert!(merged.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).is_some());
+            assert_eq!(merged.trash.len(), 1);
+            assert_eq!(merged.trash[0].meal.description, "Tacos");
+        }
+
+        // Merged again with the kitchen's stale copy, it stays out of the trash
+        let merged = laptop.merged_with(&kitchen).merged_with(&kitchen);
+        assert_eq!(merged.trash.len(), 1);
+        assert_eq!(merged.trash_tombstones.len(), 1);
+    }
+
+    #[test]
+    fn test_vector_clock() {
+        let mut laptop = VectorClock::default();
+        laptop.tick("laptop");
+        let mut pi = laptop.clone();
+        pi.tick("pi");
+
+        assert!(pi.dominates(&laptop));
+        assert!(!laptop.dominates(&pi));
+
+        // Independent edits on both devices are concurrent
+        laptop.tick("laptop");
+        assert!(!laptop.dominates(&pi));
+        assert!(!pi.dominates(&laptop));
+
+        laptop.merge(&pi);
+        assert!(laptop.dominates(&pi));
+    }
+
+    #[test]
+    fn test_merge_offline_edits() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut base = MealPlan::new(week_start);
+        base.replica_id = "laptop".to_string();
+        base.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Mon),
+            "Alice".to_string(),
+            "Lasagna".to_string(),
+        ));
+        base.add_meal(Meal::new(
+            MealType::Lunch,
+            Day::Weekday(Weekday::Tue),
+            "Bob".to_string(),
+            "Soup".to_string(),
+        ));
+
+        // The pi removes Tuesday's lunch and edits Monday's dinner while offline
+        let mut pi = base.clone();
+        pi.replica_id = "pi".to_string();
+        pi.remove_meal(&MealType::Lunch, &Day::Weekday(Weekday::Tue));
+        pi.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon));
+        pi.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Mon),
+            "Bob".to_string(),
+            "Tacos".to_string(),
+        ));
+
+        // Meanwhile the laptop adds a breakfast
+        let mut laptop = base.clone();
+        laptop.add_meal(Meal::new(
+            MealType::Breakfast,
+            Day::Weekday(Weekday::Wed),
+            "Alice".to_string(),
+            "Oatmeal".to_string(),
+        ));
+        // Make the laptop's copy look newer so plan-level timestamps can't decide
+        laptop.last_modified = pi.last_modified + chrono::Duration::seconds(60);
+
+        for merged in [laptop.merged_with(&pi), pi.merged_with(&laptop)] {
+            assert_eq!(merged.meals.len(), 2);
+            let dinner = merged.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap();
+            assert_eq!(dinner.description, "Tacos");
+            assert!(merged.find_meal(&MealType::Breakfast, &Day::Weekday(Weekday::Wed)).is_some());
+            assert!(merged.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Tue)).is_none());
+            assert_eq!(merged.tombstones.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_merge_concurrent_edits_is_deterministic() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut laptop = MealPlan::new(week_start);
+        laptop.replica_id = "laptop".to_string();
+        let mut pi = MealPlan::new(week_start);
+        pi.replica_id = "pi".to_string();
+
+        laptop.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Fri),
+            "Alice".to_string(),
+            "Curry".to_string(),
+        ));
+        pi.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Fri),
+            "Bob".to_string(),
+            "Pizza".to_string(),
+        ));
+
+        let a = laptop.merged_with(&pi);
+        let b = pi.merged_with(&laptop);
+        let dinner_a = a.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Fri)).unwrap();
+        let dinner_b = b.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Fri)).unwrap();
+        assert_eq!(dinner_a.description, dinner_b.description);
+        assert_eq!(dinner_a.clock, dinner_b.clock);
+
+        // A removal loses to a concurrent edit so no meal is silently dropped
+        let mut removed = laptop.clone();
+        removed.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Fri));
+        let mut edited = laptop.clone();
+        edited.replica_id = "pi".to_string();
+        edited.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Fri));
+        edited.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Fri),
+            "Bob".to_string(),
+            "Pizza".to_string(),
+        ));
+        let merged = removed.merged_with(&edited);
+        assert!(merged.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Fri)).is_some());
+
+        // Versions differing only in fields other than the dish and cook still agree
+        let mut tagged = laptop.clone();
+        tagged.meals[0].tags = vec!["vegetarian".to_string()];
+        tagged.meals[0].photo = Some("curry.jpg".to_string());
+        tagged.meals[0].updated_at = laptop.meals[0].updated_at;
+        tagged.last_modified = laptop.last_modified;
+        let a = laptop.merged_with(&tagged);
+        let b = tagged.merged_with(&laptop);
+        let dinner_a = a.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Fri)).unwrap();
+        let dinner_b = b.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Fri)).unwrap();
+        assert_eq!(dinner_a.tags, dinner_b.tags);
+        assert_eq!(dinner_a.photo, dinner_b.photo);
+    }
+
+    #[test]
+    fn test_render_description() {
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Fri),
+            "Sam".to_string(),
+            "{{cook}}'s choice for {{ day }} {{meal_type}} ({{date}}, week of {{week_start}})".to_string(),
+        ));
+        assert_eq!(
+            plan.render_description(&plan.meals[0]),
+            "Sam's choice for Friday Dinner (2023-01-06, week of 2023-01-02)"
+        );
+        // The stored description keeps its placeholders
+        assert!(plan.meals[0].description.starts_with("{{cook}}"));
+
+        let lookup = |name: &str| (name == "cook").then(|| "Sam".to_string());
+        assert_eq!(expand_placeholders("{{unknown}} by {{cook}}", lookup), "{{unknown}} by Sam");
+        assert_eq!(expand_placeholders("{{cook} and {{cook", lookup), "{{cook} and {{cook");
+        assert_eq!(expand_placeholders("Plain", lookup), "Plain");
+    }
+
+    #[test]
+    fn test_meal_timestamps() {
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let dinner = |description: &str| {
+            Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), description.to_string())
+        };
+
+        plan.add_meal(dinner("Lasagna"));
+        let added = plan.meals[0].clone();
+        assert!(added.created_at.is_some());
+        assert_eq!(added.created_at, added.updated_at);
+
+        // An edit keeps the creation time it is given
+        let mut edited = dinner("Tacos");
+        edited.created_at = Some(added.created_at.unwrap() - chrono::Duration::days(1));
+        plan.add_meal(edited);
+        let meal = &plan.meals[0];
+        assert_eq!(meal.created_at, Some(added.created_at.unwrap() - chrono::Duration::days(1)));
+        assert!(meal.updated_at >= added.updated_at);
+
+        let since = meal.updated_at.unwrap();
+        assert_eq!(plan.modified_since(since).meals.len(), 1);
+        assert!(plan.modified_since(since + chrono::Duration::seconds(1)).meals.is_empty());
+    }
+
+    #[test]
+    fn test_merge_prefers_later_edit() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut laptop = MealPlan::new(week_start);
+        laptop.replica_id = "laptop".to_string();
+        let mut pi = MealPlan::new(week_start);
+        pi.replica_id = "pi".to_string();
+
+        // "Curry" would win on content alone, but the pizza was planned later
+        laptop.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Fri), "Alice".to_string(), "Curry".to_string()));
+        pi.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Fri), "Bob".to_string(), "Pizza".to_string()));
+        let earlier = laptop.meals[0].updated_at.unwrap() - chrono::Duration::minutes(5);
+        laptop.meals[0].updated_at = Some(earlier);
+
+        for merged in [laptop.merged_with(&pi), pi.merged_with(&laptop)] {
+            let dinner = merged.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Fri)).unwrap();
+            assert_eq!(dinner.description, "Pizza");
+        }
+    }
+
+    #[test]
+    fn test_meal_index() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("meal_plan.json");
+
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        for (day, description) in [(Weekday::Mon, "Chili"), (Weekday::Tue, "Soup"), (Weekday::Wed, "Tacos")] {
+            plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(day), "Alice".to_string(), description.to_string()));
+        }
+
+        // Removing a meal shifts the ones after it
+        plan.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon));
+        assert_eq!(plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Wed)).unwrap().description, "Tacos");
+        assert!(plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).is_none());
+
+        // Adding to a planned slot replaces the meal in place
+        plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), "Bob".to_string(), "Stew".to_string()));
+        assert_eq!(plan.meals.len(), 2);
+        assert_eq!(plan.meals[0].description, "Stew");
+
+        // The index is rebuilt on load, and the file format is unchanged
+        plan.save_to_json(&file_path).unwrap();
+        assert!(!std::fs::read_to_string(&file_path).unwrap().contains("meal_index"));
+        let loaded = MealPlan::load_from_json(&file_path).unwrap();
+        assert_eq!(loaded.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Wed)).unwrap().description, "Tacos");
+
+        // Narrowing the meals keeps the index in step
+        let mut narrowed = loaded.clone();
+        narrowed.retain_meals(|meal| meal.description == "Tacos");
+        assert_eq!(narrowed.meals().len(), 1);
+        assert!(narrowed.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Tue)).is_none());
+        assert_eq!(narrowed.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Wed)).unwrap().description, "Tacos");
+    }
+
+    #[test]
+    fn test_date_of() {
+        // 2023-01-04 is a Wednesday
+        let plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 4).unwrap());
+        assert_eq!(plan.date_of(&Day::Weekday(Weekday::Wed)), NaiveDate::from_ymd_opt(2023, 1, 4).unwrap());
+        assert_eq!(plan.date_of(&Day::Weekday(Weekday::Fri)), NaiveDate::from_ymd_opt(2023, 1, 6).unwrap());
+        assert_eq!(plan.date_of(&Day::Weekday(Weekday::Mon)), NaiveDate::from_ymd_opt(2023, 1, 9).unwrap());
+
+        let date = NaiveDate::from_ymd_opt(2023, 2, 1).unwrap();
+        assert_eq!(plan.date_of(&Day::Date(date)), date);
+    }
+
+    #[test]
+    fn test_json_serialization() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test_meal_plan.json");
+        
+        // Create a meal plan
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut plan = MealPlan::new(week_start);
+        
+        let meal = Meal::new(
+            MealType::Breakfast,
+            Day::Date(NaiveDate::from_ymd_opt(2023, 1, 3).unwrap()),
+            "Bob".to_string(),
+            "Pancakes with syrup".to_string(),
+        );
+        plan.add_meal(meal);
+        
+        // Save to JSON
+        plan.save_to_json(&file_path).unwrap();
+        
+        // Load from JSON
+        let loaded_plan = MealPlan::load_from_json(&file_path).unwrap();
+        
+        // Verify data
+        assert_eq!(loaded_plan.week_start_date, week_start);
+        assert_eq!(loaded_plan.meals.len(), 1);
+        assert_eq!(loaded_plan.meals[0].cook, "Bob");
+    }
+
+    #[test]
+    fn test_markdown_export() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("test_meal_plan.md");
+        
+        // Create a meal plan
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut plan = MealPlan::new(week_start);
+        
+        let meal1 = Meal::new(
+            MealType::Breakfast,
+            Day::Weekday(Weekday::Mon),
+            "Charlie".to_string(),
+            "Oatmeal with berries".to_string(),
+        );
+        plan.add_meal(meal1);
+        
+        let mut meal2 = Meal::new(
+            MealType::Dinner,
+            Day::Weekday(Weekday::Mon),
+            "Diana".to_string(),
+            "Grilled chicken with vegetables".to_string(),
+        );
+        meal2.photo = Some("assets/chicken.jpg".to_string());
+        plan.add_meal(meal2);
+        
+        // Save to Markdown
+        plan.save_to_markdown(&file_path).unwrap();
+        
+        // Verify file exists
+        assert!(file_path.exists());
+        
+        // Read the file content to verify it contains expected text
+        let content = std::fs::read_to_string(&file_path).unwrap();
+        assert!(content.contains("# Meal Plan for Week of 2023-01-02"));
+        assert!(content.contains("## Mon"));
+        assert!(content.contains("### Breakfast"));
+        assert!(content.contains("- Cook: Charlie"));
+        assert!(content.contains("- Description: Oatmeal with berries"));
+        assert!(content.contains("### Dinner"));
+        assert!(content.contains("- Cook: Diana"));
+        assert!(content.contains("- Description: Grilled chicken with vegetables"));
+        assert!(content.contains("- Photo: ![Grilled chicken with vegetables](assets/chicken.jpg)"));
+    }
+
+    #[test]
+    fn test_meal_without_updated_by_loads() {
+        let json = r#"{"meal_type":"Lunch","day":{"Weekday":"Tue"},"cook":"Eve","description":"Soup"}"#;
+        let meal: Meal = serde_json::from_str(json).unwrap();
+        assert_eq!(meal.description, "Soup");
+        assert!(meal.updated_by.is_none());
+    }
+
+    #[test]
+    fn test_markdown_import_not_found() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("nonexistent.md");
+        
+        let result = MealPlan::load_from_markdown(&file_path);
+        assert!(result.is_err());
+        
+        let err = result.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_markdown_roundtrip() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut plan = MealPlan::new(week_start);
+        let mut dinner = Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Diana".to_string(), "Chili".to_string());
+        dinner.tags = vec!["spicy".to_string(), "batch".to_string()];
+        dinner.recipe = Some("https://example.com/chili".to_string());
+        dinner.photo = Some("assets/chili.jpg".to_string());
+        plan.add_meal(dinner);
+        let date = Day::Date(NaiveDate::from_ymd_opt(2023, 1, 4).unwrap());
+        plan.add_meal(Meal::new(MealType::Lunch, date.clone(), "Eve".to_string(), "Soup".to_string()));
+        plan.trash_meal(&MealType::Lunch, &date);
+        plan.add_meal(Meal::new(MealType::Breakfast, date.clone(), "Eve".to_string(), "Toast".to_string()));
+
+        let loaded = MealPlan::from_markdown(&plan.to_markdown()).unwrap();
+        assert_eq!(loaded.week_start_date, week_start);
+        assert_eq!(loaded.meals().len(), 2);
+        assert!(loaded.trash.is_empty());
+        let dinner = loaded.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap();
+        assert_eq!(dinner.cook, "Diana");
+        assert_eq!(dinner.description, "Chili");
+        assert_eq!(dinner.tags, vec!["spicy", "batch"]);
+        assert_eq!(dinner.recipe.as_deref(), Some("https://example.com/chili"));
+        assert_eq!(dinner.photo.as_deref(), Some("assets/chili.jpg"));
+        assert_eq!(loaded.find_meal(&MealType::Breakfast, &date).unwrap().description, "Toast");
+
+        let err = MealPlan::from_markdown("# Meal Plan for Week of 2023-01-02\n\n## Someday\n").unwrap_err();
+        assert!(err.starts_with("Line 3:"), "{}", err);
+        let err = MealPlan::from_markdown("# Meal Plan for Week of 2023-01-02\n\n## Mon\n### Dinner\n- Cook: Al\n").unwrap_err();
+        assert!(err.contains("no description"), "{}", err);
+        assert!(MealPlan::from_markdown("# My notes\n").is_err());
+    }
+
+    #[test]
+    fn test_markdown_day_names() {
+        let markdown = "# Meal Plan for Week of 2023-01-02\n\n\
+            ## Montag\n\n### Dinner\n- Cook: Ann\n- Description: Spätzle\n\n\
+            ## thu\n\n### Lunch\n- Cook: Ben\n- Description: Soup\n\n\
+            ## Mercredi\n\n### Breakfast\n- Cook: Ann\n- Description: Crêpes\n";
+        let plan = MealPlan::from_markdown(markdown).unwrap();
+        assert_eq!(plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap().description, "Spätzle");
+        assert_eq!(plan.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Thu)).unwrap().cook, "Ben");
+        assert_eq!(plan.find_meal(&MealType::Breakfast, &Day::Weekday(Weekday::Wed)).unwrap().description, "Crêpes");
+    }
+
+    #[test]
+    fn test_apply_markdown_edits() {
+        let week_start = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let mut base = MealPlan::new(week_start);
+        base.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Al".to_string(), "Pasta".to_string()));
+        base.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Tue), "Al".to_string(), "Soup".to_string()));
+        base.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Wed), "Al".to_string(), "Salad".to_string()));
+
+        // Edited in the JSON since the Markdown was written
+        let mut plan = base.clone();
+        plan.user = Some("Bo".to_string());
+        plan.change_meal(&MealType::Lunch, &Day::Weekday(Weekday::Wed), |meal| meal.description = "Wraps".to_string());
+
+        // The Markdown changes Monday's dinner, drops Tuesday's lunch and adds a breakfast
+        let mut markdown = MealPlan::new(week_start);
+        markdown.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Al".to_string(), "Risotto".to_string()));
+        markdown.add_meal(Meal::new(MealType::Lunch, Day::Weekday(Weekday::Wed), "Al".to_string(), "Salad".to_string()));
+        markdown.add_meal(Meal::new(MealType::Breakfast, Day::Weekday(Weekday::Thu), "Cy".to_string(), "Eggs".to_string()));
+
+        assert_eq!(plan.apply_markdown_edits(&base, &markdown), 3);
+        let dinner = plan.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap();
+        assert_eq!(dinner.description, "Risotto");
+        assert_eq!(dinner.updated_by.as_deref(), Some("Bo"));
+        assert!(plan.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Tue)).is_none());
+        assert_eq!(plan.trash.len(), 1);
+        assert_eq!(plan.find_meal(&MealType::Lunch, &Day::Weekday(Weekday::Wed)).unwrap().description, "Wraps");
+        assert_eq!(plan.find_meal(&MealType::Breakfast, &Day::Weekday(Weekday::Thu)).unwrap().cook, "Cy");
+
+        // Applying it again changes nothing
+        let applied = plan.clone();
+        assert_eq!(plan.apply_markdown_edits(&applied, &MealPlan::from_markdown(&applied.to_markdown()).unwrap()), 0);
+    }
+}
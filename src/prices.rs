@@ -0,0 +1,225 @@
+use crate::units;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// What an item cost on a day
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PricePoint {
+    pub date: NaiveDate,
+    pub price: f64,
+}
+
+/// The prices recorded for one item, under the name it was first priced with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemPrices {
+    pub name: String,
+    pub prices: Vec<PricePoint>,
+}
+
+/// An item whose price moved, from its earlier price to its latest
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceChange {
+    pub item: String,
+    pub previous: f64,
+    pub current: f64,
+}
+
+impl PriceChange {
+    /// The change as a percentage of the earlier price
+    pub fn percent(&self) -> f64 {
+        if self.previous == 0.0 {
+            return 0.0;
+        }
+        (self.current - self.previous) / self.previous * 100.0
+    }
+}
+
+/// Prices paid for grocery items over time, kept in prices.json in the storage path. Items
+/// are keyed by ingredient, so "2 cups milk" and "Milk" share a history.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PriceHistory {
+    pub items: BTreeMap<String, ItemPrices>,
+}
+
+impl PriceHistory {
+    /// Records a price paid, replacing one recorded for the item on the same day. Prices that
+    /// aren't finite are refused, as JSON can't hold them and the history would no longer load.
+    pub fn record(&mut self, item: &str, price: f64, date: NaiveDate) -> Result<(), String> {
+        if !price.is_finite() {
+            return Err(format!("{} isn't a price", price));
+        }
+        let name = units::Quantity::parse(item).map_or_else(|| item.trim().to_string(), |quantity| quantity.ingredient);
+        let points = &mut self.items.entry(units::ingredient_name(item)).or_insert(ItemPrices { name, prices: Vec::new() }).prices;
+        points.retain(|point| point.date != date);
+        let position = points.partition_point(|point| point.date < date);
+        points.insert(position, PricePoint { date, price });
+        Ok(())
+    }
+
+    pub fn history(&self, item: &str) -> &[PricePoint] {
+        self.items.get(&units::ingredient_name(item)).map(|item| item.prices.as_slice()).unwrap_or_default()
+    }
+
+    pub fn latest(&self, item: &str) -> Option<f64> {
+        self.history(item).last().map(|point| point.price)
+    }
+
+    /// The last price recorded for an item before a date
+    pub fn price_before(&self, item: &str, date: NaiveDate) -> Option<f64> {
+        self.history(item).iter().rev().find(|point| point.date < date).map(|point| point.price)
+    }
+
+    /// Items priced between `from` and `to` whose latest price there differs from the one
+    /// before it
+    pub fn changes(&self, from: NaiveDate, to: NaiveDate) -> Vec<PriceChange> {
+        self.items
+            .values()
+            .filter_map(|ItemPrices { name, prices: points }| {
+                let last = points.iter().rposition(|point| point.date >= from && point.date <= to)?;
+                let current = points[last].price;
+                let previous = points[..last].last()?.price;
+                (previous != current).then(|| PriceChange { item: name.clone(), previous, current })
+            })
+            .collect()
+    }
+
+    /// What a list of ingredients would cost at their latest prices, with how many have no
+    /// price yet
+    pub fn estimate(&self, ingredients: &[String]) -> (f64, usize) {
+        ingredients.iter().fold((0.0, 0), |(total, unpriced), ingredient| match self.latest(ingredient) {
+            Some(price) => (total + price, unpriced),
+            None => (total, unpriced + 1),
+        })
+    }
+
+    /// Files items under the ingredient their name stands for now, joining the histories of
+    /// names that have since been merged. The earlier-priced name is kept, and where both
+    /// have a price on the same day, the one filed under the ingredient's own name wins.
+    pub fn regroup(&mut self) {
+        let mut items: BTreeMap<String, ItemPrices> = BTreeMap::new();
+        for (key, item) in std::mem::take(&mut self.items) {
+            let canonical = units::ingredient_name(&item.name);
+            let own = key == canonical;
+            match items.entry(canonical) {
+                std::collections::btree_map::Entry::Vacant(entry) => {
+                    entry.insert(item);
+                }
+                std::collections::btree_map::Entry::Occupied(mut entry) => {
+                    let joined = entry.get_mut();
+                    if item.prices.first().map(|point| point.date) < joined.prices.first().map(|point| point.date) {
+                        joined.name = item.name;
+                    }
+                    for point in item.prices {
+                        match joined.prices.iter_mut().find(|joined| joined.date == point.date) {
+                            Some(existing) if own => *existing = point,
+                            Some(_) => {}
+                            None => {
+                                let position = joined.prices.partition_point(|joined| joined.date < point.date);
+                                joined.prices.insert(position, point);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.items = items;
+    }
+
+    /// Saves the history to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the history from a JSON file, which is empty until the first price is recorded
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let mut history: Self = serde_json::from_str(&contents)?;
+        history.regroup();
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 1, day).unwrap()
+    }
+
+    #[test]
+    fn test_price_changes() {
+        let mut prices = PriceHistory::default();
+        prices.record("Milk", 1.79, date(3)).unwrap();
+        prices.record("1 l milk", 1.89, date(10)).unwrap();
+        prices.record("milk", 1.99, date(10)).unwrap();
+        prices.record("Eggs", 3.5, date(4)).unwrap();
+        prices.record("eggs", 3.5, date(11)).unwrap();
+        prices.record("Bread", 2.0, date(12)).unwrap();
+
+        assert_eq!(prices.history("MILK").len(), 2);
+        assert_eq!(prices.latest("milk"), Some(1.99));
+        assert_eq!(prices.price_before("milk", date(10)), Some(1.79));
+        assert_eq!(prices.price_before("milk", date(3)), None);
+        let changes = prices.changes(date(9), date(15));
+        assert_eq!(changes, vec![PriceChange { item: "Milk".to_string(), previous: 1.79, current: 1.99 }]);
+        assert!((changes[0].percent() - 11.17).abs() < 0.01);
+        assert!(prices.changes(date(1), date(8)).is_empty());
+
+        let ingredients = vec!["2 cups milk".to_string(), "bread".to_string(), "butter".to_string()];
+        assert_eq!(prices.estimate(&ingredients), (3.99, 1));
+    }
+
+    #[test]
+    fn test_non_finite_prices_refused() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("prices.json");
+        let mut prices = PriceHistory::default();
+        assert!(prices.record("Milk", f64::NAN, date(3)).is_err());
+        assert!(prices.record("Milk", f64::INFINITY, date(3)).is_err());
+        assert!(prices.items.is_empty());
+        prices.record("Milk", 1.79, date(3)).unwrap();
+        prices.save_to_json(&path).unwrap();
+        assert_eq!(PriceHistory::load_from_json(&path).unwrap().latest("milk"), Some(1.79));
+    }
+
+    #[test]
+    fn test_price_history_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("prices.json");
+        assert!(PriceHistory::load_from_json(&path).unwrap().items.is_empty());
+
+        let mut prices = PriceHistory::default();
+        prices.record("Milk", 1.79, date(3)).unwrap();
+        prices.save_to_json(&path).unwrap();
+        assert_eq!(PriceHistory::load_from_json(&path).unwrap().history("milk"), prices.history("milk"));
+    }
+
+    #[test]
+    fn test_regroup() {
+        // Histories recorded before "green onion" was known as scallion
+        let point = |day: u32, price: f64| PricePoint { date: date(day), price };
+        let mut prices = PriceHistory::default();
+        prices.items.insert("green onion".to_string(), ItemPrices { name: "Green onions".to_string(), prices: vec![point(3, 0.99), point(10, 1.05)] });
+        prices.items.insert("scallion".to_string(), ItemPrices { name: "scallions".to_string(), prices: vec![point(10, 1.09), point(17, 1.19)] });
+
+        prices.regroup();
+        assert_eq!(prices.items.len(), 1);
+        assert_eq!(prices.items["scallion"].name, "Green onions");
+        assert_eq!(prices.history("spring onion"), &[point(3, 0.99), point(10, 1.09), point(17, 1.19)]);
+    }
+}
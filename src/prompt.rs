@@ -0,0 +1,239 @@
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+
+/// Values previously used in meal plans, offered when a prompt is answered
+#[derive(Debug, Clone, Default)]
+pub struct Completions {
+    /// Dish descriptions, most often planned first
+    pub descriptions: Vec<String>,
+    pub cooks: Vec<String>,
+}
+
+/// Returns the candidates that fuzzily match `input`, ignoring case, best matches first.
+/// Each group of equally good matches keeps the candidates' order.
+pub fn fuzzy_matches<'a>(input: &str, candidates: &'a [String]) -> Vec<&'a str> {
+    let input = input.trim().to_lowercase();
+    let mut ranked: Vec<(usize, &str)> = candidates
+        .iter()
+        .filter_map(|candidate| Some((match_rank(&input, candidate)?, candidate.as_str())))
+        .collect();
+    ranked.sort_by_key(|(rank, _)| *rank);
+    ranked.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+/// How well lowercase `input` matches `candidate`, lower being better: 0 when the candidate
+/// starts with it, 1 when one of its words does, 2 when it contains it anywhere and 3 when
+/// it contains its letters in order. None when it doesn't match at all.
+pub fn match_rank(input: &str, candidate: &str) -> Option<usize> {
+    let lower = candidate.to_lowercase();
+    if lower.starts_with(input) {
+        Some(0)
+    } else if lower.split_whitespace().any(|word| word.starts_with(input)) {
+        Some(1)
+    } else if lower.contains(input) {
+        Some(2)
+    } else if is_subsequence(input, &lower) {
+        Some(3)
+    } else {
+        None
+    }
+}
+
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+/// Returns the known spelling of an answer that matches a candidate apart from case,
+/// so "chicken curry" is stored the same way as the "Chicken Curry" planned before
+pub fn canonicalize(input: &str, candidates: &[String]) -> String {
+    let input = input.trim();
+    candidates
+        .iter()
+        .find(|candidate| candidate.trim().to_lowercase() == input.to_lowercase())
+        .map(|candidate| candidate.trim().to_string())
+        .unwrap_or_else(|| input.to_string())
+}
+
+/// Completes the whole line from the candidates when Tab is pressed
+struct CandidateHelper {
+    candidates: Vec<String>,
+}
+
+impl Completer for CandidateHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let matches = fuzzy_matches(&line[..pos], &self.candidates);
+        Ok((0, matches.into_iter().map(str::to_string).collect()))
+    }
+}
+
+impl Hinter for CandidateHelper {
+    type Hint = String;
+}
+
+impl Highlighter for CandidateHelper {}
+
+impl Validator for CandidateHelper {}
+
+impl Helper for CandidateHelper {}
+
+/// Reads a line from the terminal with Tab completion over `candidates`, returning it
+/// trimmed and with a known spelling. When stdin isn't a terminal, the line is read as is.
+/// End of input gives an empty answer; Ctrl-C gives an `Interrupted` error.
+pub fn prompt_line(candidates: &[String]) -> io::Result<String> {
+    let config = Config::builder().completion_type(CompletionType::List).auto_add_history(false).build();
+    let mut editor: Editor<CandidateHelper, DefaultHistory> = Editor::with_config(config).map_err(readline_error)?;
+    editor.set_helper(Some(CandidateHelper { candidates: candidates.to_vec() }));
+
+    match editor.readline("> ") {
+        Ok(line) => Ok(canonicalize(&line, candidates)),
+        Err(ReadlineError::Eof) => Ok(String::new()),
+        Err(e) => Err(readline_error(e)),
+    }
+}
+
+/// Where the answers to a command's questions come from. Commands ask through a prompter,
+/// so tests and scripts can answer in place of someone at the terminal.
+pub trait Prompter {
+    /// Reads the answer to a question already printed, or None at the end of input
+    fn answer(&mut self) -> io::Result<Option<String>>;
+
+    /// Reads an answer with Tab completion over `candidates`, trimmed and with a known
+    /// spelling. The end of input gives an empty answer.
+    fn answer_from(&mut self, candidates: &[String]) -> io::Result<String>;
+
+    /// Reads a yes or no to a question already printed; anything but "y" is a no
+    fn confirm(&mut self) -> bool {
+        matches!(self.answer(), Ok(Some(answer)) if answer.trim().eq_ignore_ascii_case("y"))
+    }
+
+    /// Whether there's anyone, or any script, to answer. Commands that can go on without
+    /// asking, like imports, only ask when there is.
+    fn interactive(&self) -> bool {
+        true
+    }
+}
+
+/// Answers typed at the terminal
+pub struct Terminal;
+
+impl Prompter for Terminal {
+    fn answer(&mut self) -> io::Result<Option<String>> {
+        io::stdout().flush()?;
+        let mut line = String::new();
+        match io::stdin().lock().read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line.trim_end_matches(['\r', '\n']).to_string())),
+        }
+    }
+
+    fn answer_from(&mut self, candidates: &[String]) -> io::Result<String> {
+        prompt_line(candidates)
+    }
+
+    fn interactive(&self) -> bool {
+        io::stdin().is_terminal()
+    }
+}
+
+/// Answers given in advance, one per question in the order they're asked, from
+/// `--answers-file` or a test. Each is printed as if it had been typed. Once they run out,
+/// every question gets the end of input: no to a yes or no, and an empty answer otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct Scripted {
+    answers: VecDeque<String>,
+}
+
+impl Scripted {
+    pub fn new<I: IntoIterator<Item = S>, S: Into<String>>(answers: I) -> Self {
+        Self { answers: answers.into_iter().map(Into::into).collect() }
+    }
+
+    /// Reads the answers from a file, one per line. An empty line is an empty answer.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(std::fs::read_to_string(path)?.lines()))
+    }
+
+    /// How many answers haven't been asked for yet
+    pub fn remaining(&self) -> usize {
+        self.answers.len()
+    }
+}
+
+impl Prompter for Scripted {
+    fn answer(&mut self) -> io::Result<Option<String>> {
+        let answer = self.answers.pop_front();
+        if let Some(answer) = &answer {
+            println!("{}", answer);
+        }
+        Ok(answer)
+    }
+
+    fn answer_from(&mut self, candidates: &[String]) -> io::Result<String> {
+        Ok(canonicalize(&self.answer()?.unwrap_or_default(), candidates))
+    }
+}
+
+fn readline_error(error: ReadlineError) -> io::Error {
+    match error {
+        ReadlineError::Io(e) => e,
+        ReadlineError::Interrupted => io::Error::new(io::ErrorKind::Interrupted, "Interrupted"),
+        e => io::Error::other(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dishes() -> Vec<String> {
+        ["Chicken Curry", "Pasta Bake", "Thai Green Curry", "Chili", "Carrot Cake"]
+            .iter()
+            .map(|d| d.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_fuzzy_matches_rank() {
+        let dishes = dishes();
+        assert_eq!(fuzzy_matches("cur", &dishes), vec!["Chicken Curry", "Thai Green Curry"]);
+        assert_eq!(fuzzy_matches("C", &dishes), vec!["Chicken Curry", "Chili", "Carrot Cake", "Thai Green Curry"]);
+        assert_eq!(fuzzy_matches("ake", &dishes), vec!["Pasta Bake", "Carrot Cake"]);
+        assert_eq!(fuzzy_matches("pbk", &dishes), vec!["Pasta Bake"]);
+        assert!(fuzzy_matches("sushi", &dishes).is_empty());
+        assert_eq!(fuzzy_matches("", &dishes).len(), dishes.len());
+    }
+
+    #[test]
+    fn test_scripted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("answers.txt");
+        std::fs::write(&path, "y\n\nchicken curry\nn\n").unwrap();
+        let mut prompter = Scripted::from_file(&path).unwrap();
+        assert!(prompter.confirm());
+        assert_eq!(prompter.answer_from(&dishes()).unwrap(), "");
+        assert_eq!(prompter.answer_from(&dishes()).unwrap(), "Chicken Curry");
+        assert_eq!(prompter.remaining(), 1);
+        assert!(!prompter.confirm());
+        // Out of answers, as at the end of input
+        assert_eq!(prompter.answer().unwrap(), None);
+        assert!(!prompter.confirm());
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        let dishes = dishes();
+        assert_eq!(canonicalize(" chicken curry ", &dishes), "Chicken Curry");
+        assert_eq!(canonicalize("Chicken Korma", &dishes), "Chicken Korma");
+    }
+}
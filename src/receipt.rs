@@ -0,0 +1,122 @@
+use crate::import::Cell;
+use crate::units;
+
+/// Headings of the column that says what was bought, in the order they are preferred
+const DESCRIPTION_HEADINGS: [&str; 6] = ["description", "item", "name", "product", "memo", "payee"];
+
+/// Headings of the column that says what was paid, in the order they are preferred
+const AMOUNT_HEADINGS: [&str; 6] = ["amount", "price", "total", "cost", "debit", "paid"];
+
+/// How many rows from the top to look for the headings in, as bank exports often start
+/// with account details
+const HEADING_ROWS: usize = 10;
+
+/// A line of a receipt or bank statement
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReceiptLine {
+    pub description: String,
+    pub amount: f64,
+}
+
+/// Reads the lines of an exported receipt or statement, finding the description and amount
+/// columns by their headings. Rows without an amount, such as subtotals left blank, are
+/// skipped; rows whose description mentions a total are too.
+pub fn read_lines(rows: &[Vec<Cell>]) -> Result<Vec<ReceiptLine>, String> {
+    let find = |row: &[Cell], headings: &[&str]| {
+        headings
+            .iter()
+            .find_map(|heading| row.iter().position(|cell| cell.text().to_lowercase().contains(heading)))
+    };
+    let (heading_row, description, amount) = rows
+        .iter()
+        .take(HEADING_ROWS)
+        .enumerate()
+        .find_map(|(n, row)| Some((n, find(row, &DESCRIPTION_HEADINGS)?, find(row, &AMOUNT_HEADINGS)?)))
+        .ok_or_else(|| "No description and amount columns found. Name them in a heading row, e.g. Description,Amount.".to_string())?;
+
+    let text = |row: &[Cell], index: usize| row.get(index).map(Cell::text).unwrap_or_default();
+    Ok(rows[heading_row + 1..]
+        .iter()
+        .filter_map(|row| {
+            let description = text(row, description);
+            let amount = parse_amount(&text(row, amount))?;
+            (!description.is_empty() && !description.to_lowercase().contains("total")).then_some(ReceiptLine { description, amount })
+        })
+        .collect())
+}
+
+/// Parses an amount such as "$4.99", "-4.99", "(4.99)" or "1,204.50", ignoring its sign as
+/// banks write purchases either way
+pub fn parse_amount(text: &str) -> Option<f64> {
+    let cleaned: String = text.chars().filter(|c| c.is_ascii_digit() || *c == '.').collect();
+    cleaned.parse().ok().filter(|amount: &f64| amount.is_finite())
+}
+
+/// Finds the grocery item a receipt line is for: the one whose every word starts a word of
+/// the line, or is started by one, so "ORG WHOLE MILK 1GAL" matches "milk" and "TORTILLAS"
+/// matches "tortilla". The item with the most words wins.
+pub fn match_item(line: &str, items: &[&str]) -> Option<usize> {
+    let line_words = words(line);
+    items
+        .iter()
+        .enumerate()
+        .filter_map(|(index, item)| {
+            let item_words = words(&units::ingredient_name(item));
+            let matches = !item_words.is_empty()
+                && item_words.iter().all(|word| {
+                    line_words.iter().any(|line_word| {
+                        let (short, long) = if word.len() <= line_word.len() { (word, line_word) } else { (line_word, word) };
+                        short.len() >= 3 && long.starts_with(short.as_str())
+                    })
+                });
+            matches.then_some((index, item_words.len()))
+        })
+        .max_by_key(|(index, len)| (*len, std::cmp::Reverse(*index)))
+        .map(|(index, _)| index)
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(cells: &[&str]) -> Vec<Cell> {
+        cells.iter().map(|c| if c.is_empty() { Cell::Empty } else { Cell::Text(c.to_string()) }).collect()
+    }
+
+    #[test]
+    fn test_read_lines() {
+        let rows = vec![
+            row(&["Account", "1234"]),
+            row(&["Date", "Item Description", "Qty", "Amount"]),
+            row(&["2023-01-03", "ORG WHOLE MILK 1GAL", "1", "$4.99"]),
+            row(&["2023-01-03", "TORTILLAS", "2", "(3.50)"]),
+            row(&["2023-01-03", "Coupon", "", ""]),
+            row(&["2023-01-03", "SUBTOTAL", "", "8.49"]),
+        ];
+        let lines = read_lines(&rows).unwrap();
+        assert_eq!(lines, vec![
+            ReceiptLine { description: "ORG WHOLE MILK 1GAL".to_string(), amount: 4.99 },
+            ReceiptLine { description: "TORTILLAS".to_string(), amount: 3.5 },
+        ]);
+        assert!(read_lines(&[row(&["a", "b"])]).is_err());
+        assert_eq!(parse_amount("-1,204.50"), Some(1204.5));
+    }
+
+    #[test]
+    fn test_match_item() {
+        let items = ["Milk", "2 cups almond milk", "8 tortillas", "Ground beef", "ice"];
+        assert_eq!(match_item("ORG WHOLE MILK 1GAL", &items), Some(0));
+        assert_eq!(match_item("ALMOND MILK UNSWT", &items), Some(1));
+        assert_eq!(match_item("TORTILLAS FLOUR", &items), Some(2));
+        assert_eq!(match_item("BEEF GRND 80/20", &items), None);
+        // Words shorter than three letters don't match on their own
+        assert_eq!(match_item("RICE", &items), None);
+    }
+}
@@ -0,0 +1,314 @@
+use crate::cooking::step_duration;
+use crate::equipment::EquipmentUse;
+use crate::models::Meal;
+use crate::prompt::match_rank;
+use crate::units::Quantity;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// How much a query word matching each field counts towards a recipe's score
+const NAME_WEIGHT: usize = 3;
+const TAG_WEIGHT: usize = 2;
+const INGREDIENT_WEIGHT: usize = 1;
+
+/// A recipe in the household's recipe book
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recipe {
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub ingredients: Vec<String>,
+    /// Where the full recipe can be found
+    #[serde(default)]
+    pub link: Option<String>,
+    /// How many servings the recipe makes
+    #[serde(default)]
+    pub servings: Option<u32>,
+    /// Photo of the dish: a web address, or a path in the storage path's assets directory
+    #[serde(default, deserialize_with = "crate::photos::deserialize_photo")]
+    pub photo: Option<String>,
+    /// The method, one step at a time, for `mealplan cook`
+    #[serde(default)]
+    pub steps: Vec<String>,
+    /// Appliances the dish keeps busy, for `mealplan check` to spot dishes of one meal
+    /// that can't cook at the same time
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub equipment: Vec<EquipmentUse>,
+}
+
+impl Recipe {
+    /// Scores how well one lowercase query word matches the recipe, None if it matches nothing.
+    /// Only the name is matched letter by letter, as that's too loose for long ingredient lists.
+    fn term_score(&self, term: &str) -> Option<usize> {
+        let points = |rank: usize, weight: usize| (4 - rank) * weight;
+        let name = match_rank(term, &self.name).map(|rank| points(rank, NAME_WEIGHT));
+        let field = |values: &[String], weight: usize| {
+            values
+                .iter()
+                .filter_map(|value| match_rank(term, value).filter(|&rank| rank < 3))
+                .map(|rank| points(rank, weight))
+                .max()
+        };
+        [name, field(&self.tags, TAG_WEIGHT), field(&self.ingredients, INGREDIENT_WEIGHT)]
+            .into_iter()
+            .flatten()
+            .max()
+    }
+
+    /// How long the recipe takes, adding up the durations in its steps, or None when no
+    /// step says
+    pub fn minutes(&self) -> Option<u32> {
+        let seconds: u64 = self.steps.iter().filter_map(|step| step_duration(step)).map(|d| d.as_secs()).sum();
+        (seconds > 0).then(|| seconds.div_ceil(60) as u32)
+    }
+
+    /// The ingredients with their amounts multiplied by `factor`. Ingredients without an
+    /// amount, like "salt to taste", are left as they are.
+    pub fn scaled_ingredients(&self, factor: f64) -> Vec<String> {
+        self.ingredients
+            .iter()
+            .map(|ingredient| match Quantity::parse(ingredient) {
+                Some(quantity) => quantity.scaled(factor).to_string(),
+                None => ingredient.clone(),
+            })
+            .collect()
+    }
+
+    /// Servings left over once a household of `household_size` has eaten, or 0 when the
+    /// recipe's yield isn't known
+    pub fn leftover_servings(&self, household_size: u32) -> u32 {
+        self.servings.map_or(0, |servings| servings.saturating_sub(household_size))
+    }
+
+    /// Whether the recipe carries one of the [`BATCH_FRIENDLY_TAGS`]
+    pub fn is_batch_friendly(&self) -> bool {
+        BATCH_FRIENDLY_TAGS.iter().any(|tag| self.has_tag(tag))
+    }
+
+    fn has_ingredient(&self, ingredient: &str) -> bool {
+        let ingredient = ingredient.trim().to_lowercase();
+        self.ingredients.iter().any(|i| i.to_lowercase().contains(&ingredient))
+    }
+
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.trim().eq_ignore_ascii_case(tag.trim()))
+    }
+}
+
+/// Filters a search must satisfy besides matching the query
+#[derive(Debug, Clone, Default)]
+pub struct RecipeFilter {
+    /// Every one of these must be part of an ingredient
+    pub ingredients: Vec<String>,
+    /// Every one of these must be a tag
+    pub tags: Vec<String>,
+}
+
+/// A recipe found by a search, with how well it matched
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult<'a> {
+    pub recipe: &'a Recipe,
+    pub score: usize,
+}
+
+/// Tags marking a recipe that scales up or freezes well, for cooking once and eating twice
+pub const BATCH_FRIENDLY_TAGS: [&str; 2] = ["batch-friendly", "freezes-well"];
+
+/// The household's recipes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecipeBook {
+    pub recipes: Vec<Recipe>,
+}
+
+impl RecipeBook {
+    /// Adds a recipe, replacing one with the same name. Returns true if it is new.
+    pub fn add(&mut self, recipe: Recipe) -> bool {
+        match self.recipes.iter_mut().find(|r| r.name.eq_ignore_ascii_case(&recipe.name)) {
+            Some(existing) => {
+                *existing = recipe;
+                false
+            }
+            None => {
+                self.recipes.push(recipe);
+                true
+            }
+        }
+    }
+
+    /// Removes a recipe by name, ignoring case, returning false if there was none
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.recipes.len();
+        self.recipes.retain(|r| !r.name.eq_ignore_ascii_case(name.trim()));
+        self.recipes.len() != before
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Recipe> {
+        self.recipes.iter().find(|r| r.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// The batch-friendly recipes, by name
+    pub fn batch_friendly(&self) -> Vec<&Recipe> {
+        let mut recipes: Vec<&Recipe> = self.recipes.iter().filter(|r| r.is_batch_friendly()).collect();
+        recipes.sort_by_key(|r| r.name.to_lowercase());
+        recipes
+    }
+
+    /// The recipe a meal is made from, named by its recipe or else its description
+    pub fn for_meal(&self, meal: &Meal) -> Option<&Recipe> {
+        [meal.recipe.as_deref(), Some(meal.description.as_str())]
+            .into_iter()
+            .flatten()
+            .find_map(|name| self.find(name))
+    }
+
+    /// Finds the recipes matching every word of the query and the filters, best match first.
+    /// An empty query matches every recipe that passes the filters.
+    pub fn search(&self, query: &str, filter: &RecipeFilter) -> Vec<SearchResult<'_>> {
+        let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let mut results: Vec<SearchResult> = self
+            .recipes
+            .iter()
+            .filter(|recipe| filter.ingredients.iter().all(|i| recipe.has_ingredient(i)))
+            .filter(|recipe| filter.tags.iter().all(|t| recipe.has_tag(t)))
+            .filter_map(|recipe| {
+                let score = terms.iter().map(|term| recipe.term_score(term)).sum::<Option<usize>>()?;
+                Some(SearchResult { recipe, score })
+            })
+            .collect();
+        results.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.recipe.name.cmp(&b.recipe.name)));
+        results
+    }
+
+    /// Saves the recipe book to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the recipe book from a JSON file, which is empty until the first recipe is added
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn recipe(name: &str, tags: &[&str], ingredients: &[&str]) -> Recipe {
+        Recipe {
+            name: name.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ingredients: ingredients.iter().map(|i| i.to_string()).collect(),
+            link: None,
+            servings: None,
+            photo: None,
+            steps: Vec::new(),
+            equipment: Vec::new(),
+        }
+    }
+
+    fn book() -> RecipeBook {
+        let mut book = RecipeBook::default();
+        book.add(recipe("Chicken Parmesan", &["italian"], &["chicken breast", "parmesan", "tomato sauce"]));
+        book.add(recipe("Eggplant Parmigiana", &["italian", "vegetarian"], &["eggplant", "parmesan"]));
+        book.add(recipe("Chicken Curry", &["indian"], &["chicken thighs", "coconut milk"]));
+        book.add(recipe("Pesto Pasta", &["italian", "quick"], &["basil", "parmesan", "pasta"]));
+        book
+    }
+
+    fn names(results: &[SearchResult]) -> Vec<String> {
+        results.iter().map(|r| r.recipe.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_leftover_servings() {
+        let mut chili = recipe("Chili", &[], &[]);
+        assert_eq!(chili.leftover_servings(4), 0);
+        chili.servings = Some(6);
+        assert_eq!(chili.leftover_servings(4), 2);
+        assert_eq!(chili.leftover_servings(8), 0);
+    }
+
+    #[test]
+    fn test_minutes() {
+        let mut chili = recipe("Chili", &[], &[]);
+        assert_eq!(chili.minutes(), None);
+        chili.steps = vec!["Brown the beef for 5 minutes".to_string(), "Add the beans".to_string(), "Simmer 1 hr".to_string()];
+        assert_eq!(chili.minutes(), Some(65));
+    }
+
+    #[test]
+    fn test_scaled_ingredients() {
+        let pancakes = recipe("Pancakes", &[], &["1 1/2 cups flour", "2 tsp sugar", "2 eggs", "salt to taste"]);
+        assert_eq!(pancakes.scaled_ingredients(2.0), vec!["3 cups flour", "1.33 tbsp sugar", "4 eggs", "salt to taste"]);
+        assert_eq!(book().find("pesto PASTA").map(|r| r.name.as_str()), Some("Pesto Pasta"));
+    }
+
+    #[test]
+    fn test_batch_friendly() {
+        let mut book = book();
+        book.add(recipe("Chili", &["Freezes-Well"], &[]));
+        book.add(recipe("Bolognese", &["batch-friendly", "italian"], &[]));
+        assert!(!book.find("Pesto Pasta").unwrap().is_batch_friendly());
+        let names: Vec<&str> = book.batch_friendly().iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["Bolognese", "Chili"]);
+    }
+
+    #[test]
+    fn test_search_ranks_by_score() {
+        let book = book();
+        let results = book.search("chick parm", &RecipeFilter::default());
+        assert_eq!(names(&results), vec!["Chicken Parmesan"]);
+
+        // A name match beats a match on an ingredient
+        let results = book.search("parm", &RecipeFilter::default());
+        assert_eq!(names(&results), vec!["Chicken Parmesan", "Eggplant Parmigiana", "Pesto Pasta"]);
+        assert!(results[1].score > results[2].score);
+
+        let results = book.search("ITALIAN", &RecipeFilter::default());
+        assert_eq!(results.len(), 3);
+        assert!(book.search("sushi", &RecipeFilter::default()).is_empty());
+    }
+
+    #[test]
+    fn test_search_filters() {
+        let book = book();
+        let filter = RecipeFilter { ingredients: vec!["Chicken".to_string()], tags: Vec::new() };
+        assert_eq!(names(&book.search("", &filter)), vec!["Chicken Curry", "Chicken Parmesan"]);
+
+        let filter = RecipeFilter { ingredients: vec!["parmesan".to_string()], tags: vec!["vegetarian".to_string()] };
+        assert_eq!(names(&book.search("", &filter)), vec!["Eggplant Parmigiana"]);
+        assert!(book.search("pesto", &filter).is_empty());
+    }
+
+    #[test]
+    fn test_recipe_book_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("recipes.json");
+        assert!(RecipeBook::load_from_json(&path).unwrap().recipes.is_empty());
+
+        let mut book = book();
+        assert!(!book.add(recipe("chicken curry", &[], &["chickpeas"])));
+        assert!(book.remove("pesto pasta"));
+        assert!(!book.remove("Pesto Pasta"));
+        book.save_to_json(&path).unwrap();
+
+        let loaded = RecipeBook::load_from_json(&path).unwrap();
+        assert_eq!(loaded.recipes.len(), 3);
+        assert_eq!(loaded.recipes[2], recipe("chicken curry", &[], &["chickpeas"]));
+    }
+}
@@ -0,0 +1,115 @@
+use crate::daemon::FileWatcher;
+use crate::models::Config;
+use chrono::Utc;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Duration;
+
+/// How often long-running commands check the configuration file for changes
+pub const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The configuration of a long-running command, replaced whenever the file changes
+pub type SharedConfig = Arc<RwLock<Config>>;
+
+/// A copy of the configuration as it is now
+pub fn current(config: &SharedConfig) -> Config {
+    // A thread that panicked while holding the lock can't have left a half-written
+    // configuration, as it is only ever replaced whole
+    config.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+}
+
+/// The top-level settings that differ between two configurations, in alphabetical order
+pub fn changed_settings(old: &Config, new: &Config) -> Vec<String> {
+    let (Ok(Value::Object(old)), Ok(Value::Object(new))) = (serde_json::to_value(old), serde_json::to_value(new)) else {
+        return Vec::new();
+    };
+    let mut changed: Vec<String> = old
+        .keys()
+        .chain(new.keys().filter(|key| !old.contains_key(*key)))
+        .filter(|key| old.get(*key) != new.get(*key))
+        .cloned()
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// Loads the configuration again if its file changed, replacing the shared one and
+/// describing what changed. A file that doesn't load leaves the configuration as it was.
+pub fn reload<F>(watcher: &mut FileWatcher, config: &SharedConfig, load: F) -> Option<String>
+where
+    F: FnOnce() -> Result<Config, String>,
+{
+    if !watcher.poll() {
+        return None;
+    }
+    let new = match load() {
+        Ok(new) => new,
+        Err(e) => return Some(format!("Kept the previous configuration, as the new one doesn't load: {}", e)),
+    };
+    let mut shared = config.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let changed = changed_settings(&shared, &new);
+    *shared = new;
+    Some(if changed.is_empty() {
+        "Reloaded the configuration; no settings changed.".to_string()
+    } else {
+        format!("Reloaded the configuration, with changes to {}.", changed.join(", "))
+    })
+}
+
+/// Watches the configuration file from a thread of its own, reloading it on every change
+/// and logging what changed
+pub fn watch_config<F>(path: PathBuf, config: SharedConfig, load: F)
+where
+    F: Fn() -> Result<Config, String> + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut watcher = FileWatcher::new(&[path]);
+        loop {
+            thread::sleep(CONFIG_CHECK_INTERVAL);
+            if let Some(message) = reload(&mut watcher, &config, &load) {
+                println!("[{}] {}", Utc::now().format("%Y-%m-%d %H:%M:%S"), message);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_changed_settings() {
+        let old = Config::new();
+        let mut new = old.clone();
+        assert!(changed_settings(&old, &new).is_empty());
+        new.weekly_budget = Some(120.0);
+        new.backup_count += 1;
+        assert_eq!(changed_settings(&old, &new), vec!["backup_count".to_string(), "weekly_budget".to_string()]);
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        let shared: SharedConfig = Arc::new(RwLock::new(Config::new()));
+        let mut watcher = FileWatcher::new(std::slice::from_ref(&path));
+        let load = || Config::load(&path).map_err(|e| e.to_string());
+        assert_eq!(reload(&mut watcher, &shared, load), None);
+
+        let mut config = Config::new();
+        config.weekly_budget = Some(90.0);
+        config.save(&path).unwrap();
+        assert_eq!(reload(&mut watcher, &shared, load).unwrap(), "Reloaded the configuration, with changes to weekly_budget.");
+        assert_eq!(current(&shared).weekly_budget, Some(90.0));
+
+        // A broken file leaves the last good configuration in place
+        std::fs::write(&path, "{").unwrap();
+        let file = std::fs::File::options().append(true).open(&path).unwrap();
+        file.set_modified(std::time::SystemTime::now() + Duration::from_secs(5)).unwrap();
+        assert!(reload(&mut watcher, &shared, load).unwrap().starts_with("Kept the previous configuration"));
+        assert_eq!(current(&shared).weekly_budget, Some(90.0));
+    }
+}
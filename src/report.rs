@@ -0,0 +1,281 @@
+use crate::grocery::GroceryList;
+use crate::history::{HistoryEntry, MealHistory, LEFTOVERS_PREFIX};
+use chrono::{Datelike, Duration, NaiveDate};
+use std::collections::HashMap;
+
+/// How many dishes a report lists as the most common
+const TOP_DISHES: usize = 10;
+
+/// Calendar period a report covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportPeriod {
+    Month { year: i32, month: u32 },
+    Year(i32),
+}
+
+impl ReportPeriod {
+    /// Parses a month written as YYYY-MM
+    pub fn parse_month(s: &str) -> Option<Self> {
+        let (year, month) = s.trim().split_once('-')?;
+        let year = year.parse().ok()?;
+        let month = month.parse().ok()?;
+        ReportPeriod::Month { year, month }.checked()
+    }
+
+    /// A calendar year, if its dates can all be represented
+    pub fn year(year: i32) -> Option<Self> {
+        ReportPeriod::Year(year).checked()
+    }
+
+    /// The period itself, if it has a start, an end and a first week
+    fn checked(self) -> Option<Self> {
+        self.end()?;
+        self.first_week()?;
+        Some(self)
+    }
+
+    /// First day of the period
+    pub fn start(&self) -> Option<NaiveDate> {
+        match *self {
+            ReportPeriod::Month { year, month } => NaiveDate::from_ymd_opt(year, month, 1),
+            ReportPeriod::Year(year) => NaiveDate::from_ymd_opt(year, 1, 1),
+        }
+    }
+
+    /// First day after the period
+    pub fn end(&self) -> Option<NaiveDate> {
+        match *self {
+            ReportPeriod::Month { year: _, month: 12 } | ReportPeriod::Year(_) => {
+                NaiveDate::from_ymd_opt(self.start()?.year().checked_add(1)?, 1, 1)
+            }
+            ReportPeriod::Month { year, month } => NaiveDate::from_ymd_opt(year, month + 1, 1),
+        }
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        match (self.start(), self.end()) {
+            (Some(start), Some(end)) => date >= start && date < end,
+            _ => false,
+        }
+    }
+
+    /// The earliest week start whose week overlaps the period
+    pub fn first_week(&self) -> Option<NaiveDate> {
+        self.start()?.checked_sub_signed(Duration::days(6))
+    }
+
+    pub fn label(&self) -> String {
+        match *self {
+            ReportPeriod::Month { year, month } => match self.start() {
+                Some(start) => start.format("%B %Y").to_string(),
+                None => format!("{}-{:02}", year, month),
+            },
+            ReportPeriod::Year(year) => year.to_string(),
+        }
+    }
+}
+
+/// Totals for the meals planned over a month or year
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeriodReport {
+    pub period: ReportPeriod,
+    pub total_meals: usize,
+    /// Weeks with at least one meal in the period
+    pub weeks: usize,
+    /// Most common dishes with how often they were planned, leftovers skipped
+    pub top_dishes: Vec<(String, usize)>,
+    /// Meals per cook, most first
+    pub cooks: Vec<(String, usize)>,
+    /// Spent on groceries in weeks starting within the period
+    pub spent: f64,
+    /// How many of those weeks have a grocery list
+    pub grocery_weeks: usize,
+}
+
+impl PeriodReport {
+    /// Builds the report from the meal history and the grocery lists of the weeks involved
+    pub fn new(period: ReportPeriod, history: &MealHistory, grocery_lists: &[GroceryList]) -> Self {
+        let entries: Vec<&HistoryEntry> = history.entries.iter().filter(|e| period.contains(e.date)).collect();
+
+        let mut weeks: Vec<NaiveDate> = entries.iter().map(|e| e.week_start_date).collect();
+        weeks.dedup();
+
+        let dishes = entries
+            .iter()
+            .map(|e| e.description.trim())
+            .filter(|d| !d.is_empty() && !d.starts_with(LEFTOVERS_PREFIX));
+        let mut top_dishes = count_by(dishes.map(|d| (d.to_lowercase(), d)));
+        top_dishes.truncate(TOP_DISHES);
+
+        let cooks = entries.iter().map(|e| e.cook.trim()).filter(|c| !c.is_empty());
+        let cooks = count_by(cooks.map(|c| (c.to_lowercase(), c)));
+
+        let grocery_lists: Vec<&GroceryList> = grocery_lists
+            .iter()
+            .filter(|list| period.contains(list.week_start_date))
+            .collect();
+
+        Self {
+            period,
+            total_meals: entries.len(),
+            weeks: weeks.len(),
+            top_dishes,
+            cooks,
+            spent: grocery_lists.iter().fold(0.0, |total, list| total + list.spending().0),
+            grocery_weeks: grocery_lists.len(),
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# Meal Report: {}\n\n", self.period.label());
+        out.push_str(&format!("- Meals planned: {}\n", self.total_meals));
+        out.push_str(&format!("- Weeks planned: {}\n", self.weeks));
+        out.push_str(&format!("- Grocery spending: {:.2} over {} week{}\n", self.spent, self.grocery_weeks, plural(self.grocery_weeks)));
+
+        out.push_str("\n## Top Dishes\n\n");
+        if self.top_dishes.is_empty() {
+            out.push_str("No dishes planned.\n");
+        }
+        for (n, (dish, times)) in self.top_dishes.iter().enumerate() {
+            out.push_str(&format!("{}. {} ({})\n", n + 1, dish, times));
+        }
+
+        out.push_str("\n## Meals by Cook\n\n");
+        if self.cooks.is_empty() {
+            out.push_str("No cooks assigned.\n");
+        }
+        for (cook, meals) in &self.cooks {
+            out.push_str(&format!("- {}: {} ({:.0}%)\n", cook, meals, self.share(*meals)));
+        }
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let title = format!("Meal Report: {}", escape_html(&self.period.label()));
+        let mut out = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n"
+        );
+        out.push_str("<ul>\n");
+        out.push_str(&format!("<li>Meals planned: {}</li>\n", self.total_meals));
+        out.push_str(&format!("<li>Weeks planned: {}</li>\n", self.weeks));
+        out.push_str(&format!(
+            "<li>Grocery spending: {:.2} over {} week{}</li>\n",
+            self.spent,
+            self.grocery_weeks,
+            plural(self.grocery_weeks)
+        ));
+        out.push_str("</ul>\n");
+
+        out.push_str("<h2>Top Dishes</h2>\n<table>\n<tr><th>Dish</th><th>Times</th></tr>\n");
+        for (dish, times) in &self.top_dishes {
+            out.push_str(&format!("<tr><td>{}</td><td>{}</td></tr>\n", escape_html(dish), times));
+        }
+        out.push_str("</table>\n");
+
+        out.push_str("<h2>Meals by Cook</h2>\n<table>\n<tr><th>Cook</th><th>Meals</th><th>Share</th></tr>\n");
+        for (cook, meals) in &self.cooks {
+            out.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{:.0}%</td></tr>\n",
+                escape_html(cook),
+                meals,
+                self.share(*meals)
+            ));
+        }
+        out.push_str("</table>\n</body>\n</html>\n");
+        out
+    }
+
+    /// Percentage of the period's meals
+    fn share(&self, meals: usize) -> f64 {
+        if self.total_meals == 0 {
+            0.0
+        } else {
+            meals as f64 * 100.0 / self.total_meals as f64
+        }
+    }
+}
+
+/// Counts values grouped by key, keeping the first spelling seen, most common first
+fn count_by<'a, I: Iterator<Item = (String, &'a str)>>(values: I) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, (String, usize)> = HashMap::new();
+    for (key, value) in values {
+        counts.entry(key).or_insert_with(|| (value.to_string(), 0)).1 += 1;
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_values().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+fn plural(n: usize) -> &'static str {
+    if n == 1 { "" } else { "s" }
+}
+
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, Meal, MealPlan, MealType};
+    use chrono::Weekday;
+
+    #[test]
+    fn test_period_bounds() {
+        let december = ReportPeriod::parse_month("2024-12").unwrap();
+        assert_eq!(december.start(), NaiveDate::from_ymd_opt(2024, 12, 1));
+        assert_eq!(december.end(), NaiveDate::from_ymd_opt(2025, 1, 1));
+        assert_eq!(december.label(), "December 2024");
+        assert_eq!(ReportPeriod::year(2025).unwrap().end(), NaiveDate::from_ymd_opt(2026, 1, 1));
+        assert!(ReportPeriod::parse_month("2025-13").is_none());
+        assert!(ReportPeriod::parse_month("February").is_none());
+        // Years beyond what dates can hold are refused rather than panicking
+        assert!(ReportPeriod::year(999999).is_none());
+        assert!(ReportPeriod::year(i32::MAX).is_none());
+        assert!(ReportPeriod::parse_month("262143-12").is_none());
+    }
+
+    #[test]
+    fn test_month_report() {
+        // The week of Jan 27 runs into February
+        let mut straddling = MealPlan::new(NaiveDate::from_ymd_opt(2025, 1, 27).unwrap());
+        let mut february = MealPlan::new(NaiveDate::from_ymd_opt(2025, 2, 3).unwrap());
+        let meal = |day, cook: &str, description: &str| {
+            Meal::new(MealType::Dinner, Day::Weekday(day), cook.to_string(), description.to_string())
+        };
+        straddling.add_meal(meal(Weekday::Mon, "Alice", "Soup"));
+        straddling.add_meal(meal(Weekday::Sat, "Alice", "Tacos"));
+        february.add_meal(meal(Weekday::Mon, "Bob", "tacos"));
+        february.add_meal(meal(Weekday::Wed, "alice", "Leftovers: Tacos"));
+        let history = MealHistory::from_plans([&straddling, &february]);
+
+        let mut groceries = GroceryList::new(february.week_start_date);
+        groceries.add_item("Tortillas", None);
+        groceries.set_purchased("Tortillas", true);
+        groceries.set_price("Tortillas", 3.5);
+        let mut january_groceries = GroceryList::new(straddling.week_start_date);
+        january_groceries.add_item("Broth", None);
+        january_groceries.set_purchased("Broth", true);
+        january_groceries.set_price("Broth", 2.0);
+
+        let report = PeriodReport::new(
+            ReportPeriod::parse_month("2025-02").unwrap(),
+            &history,
+            &[january_groceries, groceries],
+        );
+        assert_eq!(report.total_meals, 3);
+        assert_eq!(report.weeks, 2);
+        assert_eq!(report.top_dishes, vec![("Tacos".to_string(), 2)]);
+        assert_eq!(report.cooks, vec![("Alice".to_string(), 2), ("Bob".to_string(), 1)]);
+        assert_eq!(report.spent, 3.5);
+
+        let markdown = report.to_markdown();
+        assert!(markdown.starts_with("# Meal Report: February 2025"));
+        assert!(markdown.contains("1. Tacos (2)"));
+        assert!(markdown.contains("- Alice: 2 (67%)"));
+        assert!(report.to_html().contains("<tr><td>Bob</td><td>1</td><td>33%</td></tr>"));
+
+        let empty = PeriodReport::new(ReportPeriod::year(2030).unwrap(), &history, &[]);
+        assert!(empty.to_markdown().contains("- Grocery spending: 0.00 over 0 weeks"));
+    }
+}
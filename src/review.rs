@@ -0,0 +1,314 @@
+use crate::grocery::GroceryList;
+use crate::history::LEFTOVERS_PREFIX;
+use crate::models::{Meal, MealPlan, MealType};
+use crate::places::Places;
+use crate::prices::{PriceChange, PriceHistory};
+use chrono::{Duration, NaiveDate};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// A meal as it appears in the review, with its calendar date
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReviewedMeal {
+    pub date: NaiveDate,
+    pub meal_type: MealType,
+    pub description: String,
+    pub cooked: bool,
+    pub rating: Option<u8>,
+}
+
+impl ReviewedMeal {
+    fn label(&self) -> String {
+        format!("{} on {}: {}", self.meal_type, self.date.format("%a %Y-%m-%d"), self.description)
+    }
+}
+
+/// End-of-week summary of what was planned and what actually happened
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeeklyReview {
+    pub week_start_date: NaiveDate,
+    /// Every planned meal in date order, leftovers included
+    pub meals: Vec<ReviewedMeal>,
+    /// Total paid for groceries: the imported receipts' total, or else what the purchased
+    /// items were checked off at
+    pub spent: f64,
+    /// What the imported receipts came to, when any were imported
+    pub receipts: Option<f64>,
+    /// What the list's items would have cost at the prices paid before the week
+    pub estimated: f64,
+    /// Items on the list with no earlier price to estimate from
+    pub unestimated: usize,
+    /// Purchased grocery items with no recorded price
+    pub unpriced_items: usize,
+    /// What the meals from restaurants and takeout places usually cost
+    pub eating_out: f64,
+    /// Meals from restaurants and takeout places
+    pub eaten_out: usize,
+    /// Meals eaten out from places with no typical cost
+    pub unpriced_eaten_out: usize,
+    pub budget: Option<f64>,
+    /// Items bought this week at a different price than last time
+    pub price_changes: Vec<PriceChange>,
+}
+
+impl WeeklyReview {
+    pub fn new(meal_plan: &MealPlan, grocery_list: &GroceryList, places: &Places, prices: &PriceHistory, budget: Option<f64>) -> Self {
+        let mut meals: Vec<ReviewedMeal> = meal_plan
+            .meals()
+            .iter()
+            .map(|meal: &Meal| ReviewedMeal {
+                date: meal_plan.date_of(&meal.day),
+                meal_type: meal.meal_type.clone(),
+                description: meal_plan.render_description(meal),
+                cooked: meal.cooked,
+                rating: meal.rating,
+            })
+            .collect();
+        meals.sort_by_key(|m| {
+            let type_key = match m.meal_type {
+                MealType::Breakfast => 0,
+                MealType::Lunch => 1,
+                MealType::Snack => 2,
+                MealType::Dinner => 3,
+            };
+            (m.date, type_key)
+        });
+
+        let (spent, unpriced_items) = grocery_list.spending();
+        let receipts = grocery_list.receipt_total();
+        let (estimated, unestimated) = grocery_list
+            .items
+            .iter()
+            .fold((0.0, 0), |(total, missing), item| match prices.price_before(&item.name, meal_plan.week_start_date) {
+                Some(price) => (total + price, missing),
+                None => (total, missing + 1),
+            });
+        let (eating_out, eaten_out, unpriced_eaten_out) = places.eating_out(meal_plan);
+        Self {
+            week_start_date: meal_plan.week_start_date,
+            meals,
+            spent: receipts.unwrap_or(spent),
+            receipts,
+            estimated,
+            unestimated,
+            unpriced_items,
+            eating_out,
+            eaten_out,
+            unpriced_eaten_out,
+            budget,
+            price_changes: prices.changes(meal_plan.week_start_date, meal_plan.week_start_date + Duration::days(6)),
+        }
+    }
+
+    /// Groceries and eating out together
+    pub fn total_spent(&self) -> f64 {
+        self.spent + self.eating_out
+    }
+
+    pub fn cooked(&self) -> usize {
+        self.meals.iter().filter(|m| m.cooked).count()
+    }
+
+    pub fn leftovers(&self) -> Vec<&ReviewedMeal> {
+        self.meals
+            .iter()
+            .filter(|m| m.description.trim().starts_with(LEFTOVERS_PREFIX))
+            .collect()
+    }
+
+    /// Average of the ratings collected, if any were
+    pub fn average_rating(&self) -> Option<f64> {
+        let ratings: Vec<u8> = self.meals.iter().filter_map(|m| m.rating).collect();
+        if ratings.is_empty() {
+            return None;
+        }
+        Some(ratings.iter().map(|&r| r as f64).sum::<f64>() / ratings.len() as f64)
+    }
+
+    /// Renders the review as a Markdown section, as shown and as written to the journal
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("## Week of {}\n\n", self.week_start_date.format("%Y-%m-%d"));
+
+        out.push_str("### Planned vs. cooked\n\n");
+        out.push_str(&format!("Cooked {} of {} planned meals.\n", self.cooked(), self.meals.len()));
+        let skipped: Vec<&ReviewedMeal> = self.meals.iter().filter(|m| !m.cooked).collect();
+        if !skipped.is_empty() {
+            out.push_str("\nNot cooked:\n");
+            for meal in skipped {
+                out.push_str(&format!("- {}\n", meal.label()));
+            }
+        }
+
+        out.push_str("\n### Cost\n\n");
+        let spent = self.total_spent();
+        out.push_str(&format!("Spent {:.2}", spent));
+        match self.budget {
+            Some(budget) if spent > budget => {
+                out.push_str(&format!(" of a {:.2} budget, {:.2} over.\n", budget, spent - budget))
+            }
+            Some(budget) => {
+                out.push_str(&format!(" of a {:.2} budget, {:.2} under.\n", budget, budget - spent))
+            }
+            None if self.eaten_out > 0 => out.push_str(" on groceries and eating out. No weekly budget is set.\n"),
+            None => out.push_str(" on groceries. No weekly budget is set.\n"),
+        }
+        if self.eaten_out > 0 {
+            out.push_str(&format!(
+                "Includes about {:.2} for {} meal{} eaten out.\n",
+                self.eating_out,
+                self.eaten_out,
+                if self.eaten_out == 1 { "" } else { "s" }
+            ));
+        }
+        if self.unpriced_eaten_out > 0 {
+            out.push_str(&format!(
+                "{} meal{} eaten out had no typical cost for the place.\n",
+                self.unpriced_eaten_out,
+                if self.unpriced_eaten_out == 1 { "" } else { "s" }
+            ));
+        }
+        if let Some(receipts) = self.receipts {
+            out.push_str(&format!("Receipts came to {:.2} against {:.2} estimated from earlier prices", receipts, self.estimated));
+            match self.unestimated {
+                0 => out.push_str(".\n"),
+                1 => out.push_str("; 1 item had no earlier price.\n"),
+                n => out.push_str(&format!("; {} items had no earlier price.\n", n)),
+            }
+        } else if self.unpriced_items > 0 {
+            out.push_str(&format!(
+                "{} purchased item{} had no price recorded.\n",
+                self.unpriced_items,
+                if self.unpriced_items == 1 { "" } else { "s" }
+            ));
+        }
+        if !self.price_changes.is_empty() {
+            out.push_str("\nPrice changes since last bought:\n");
+            for change in &self.price_changes {
+                out.push_str(&format!("- {}: {:.2} -> {:.2} ({:+.0}%)\n", change.item, change.previous, change.current, change.percent()));
+            }
+        }
+
+        out.push_str("\n### Ratings\n\n");
+        match self.average_rating() {
+            Some(average) => {
+                out.push_str(&format!("Average rating {:.1} out of 5.\n\n", average));
+                for meal in self.meals.iter().filter(|m| m.rating.is_some()) {
+                    out.push_str(&format!("- {}: {}/5\n", meal.label(), meal.rating.unwrap_or_default()));
+                }
+            }
+            None => out.push_str("No meals were rated.\n"),
+        }
+
+        out.push_str("\n### Leftovers\n\n");
+        let leftovers = self.leftovers();
+        if leftovers.is_empty() {
+            out.push_str("No leftovers were planned.\n");
+        }
+        for meal in leftovers {
+            let status = if meal.cooked { "eaten" } else { "not eaten" };
+            out.push_str(&format!("- {} ({})\n", meal.label(), status));
+        }
+
+        out
+    }
+
+    /// Appends the review to a journal file, creating it if needed
+    pub fn append_to_journal(&self, path: &Path) -> std::io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "# Meal Plan Review Journal")?;
+        }
+        write!(file, "\n{}", self.to_markdown())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Day;
+    use chrono::Weekday;
+    use tempfile::tempdir;
+
+    fn sample_plan() -> MealPlan {
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        let meal = |day, description: &str| {
+            Meal::new(MealType::Dinner, Day::Weekday(day), "Jane".to_string(), description.to_string())
+        };
+        plan.add_meal(meal(Weekday::Tue, "Chili"));
+        plan.add_meal(meal(Weekday::Mon, "Pasta"));
+        plan.add_meal(meal(Weekday::Thu, "Leftovers: Chili"));
+        plan.mark_cooked(&MealType::Dinner, &Day::Weekday(Weekday::Mon), Some(4));
+        plan.mark_cooked(&MealType::Dinner, &Day::Weekday(Weekday::Tue), Some(5));
+        plan
+    }
+
+    #[test]
+    fn test_review_summary() {
+        let plan = sample_plan();
+        let mut groceries = GroceryList::new(plan.week_start_date);
+        groceries.add_item("Beans", None);
+        groceries.set_purchased("Beans", true);
+        groceries.set_price("Beans", 62.5);
+        groceries.add_item("Cheese", None);
+        groceries.set_purchased("Cheese", true);
+
+        let review = WeeklyReview::new(&plan, &groceries, &Places::default(), &PriceHistory::default(), Some(50.0));
+        assert_eq!(review.meals[0].description, "Pasta");
+        assert_eq!(review.cooked(), 2);
+        assert_eq!(review.average_rating(), Some(4.5));
+
+        let text = review.to_markdown();
+        assert!(text.starts_with("## Week of 2023-01-02"));
+        assert!(text.contains("Cooked 2 of 3 planned meals."));
+        assert!(text.contains("Spent 62.50 of a 50.00 budget, 12.50 over."));
+        assert!(text.contains("1 purchased item had no price recorded."));
+        assert!(text.contains("- Dinner on Tue 2023-01-03: Chili: 5/5"));
+        assert!(text.contains("- Dinner on Thu 2023-01-05: Leftovers: Chili (not eaten)"));
+        assert!(!text.contains("Price changes"));
+
+        let mut prices = PriceHistory::default();
+        prices.record("Beans", 50.0, NaiveDate::from_ymd_opt(2022, 12, 20).unwrap()).unwrap();
+        prices.record("Beans", 62.5, NaiveDate::from_ymd_opt(2023, 1, 4).unwrap()).unwrap();
+        let text = WeeklyReview::new(&plan, &groceries, &Places::default(), &prices, Some(50.0)).to_markdown();
+        assert!(text.contains("Price changes since last bought:\n- Beans: 50.00 -> 62.50 (+25%)\n"));
+
+        // Receipts count as what was spent and are compared with the earlier prices
+        groceries.receipts.push(crate::grocery::ImportedReceipt { source: "receipt.csv".to_string(), total: 70.25 });
+        let text = WeeklyReview::new(&plan, &groceries, &Places::default(), &prices, Some(50.0)).to_markdown();
+        assert!(text.contains("Spent 70.25 of a 50.00 budget, 20.25 over.\nReceipts came to 70.25 against 50.00 estimated from earlier prices; 1 item had no earlier price.\n"));
+    }
+
+    #[test]
+    fn test_review_counts_eating_out() {
+        let mut plan = sample_plan();
+        let mut takeout = Meal::new(MealType::Dinner, Day::Weekday(Weekday::Fri), String::new(), "Pad thai".to_string());
+        takeout.place = Some("Thai Palace".to_string());
+        plan.add_meal(takeout);
+        let mut places = Places::default();
+        places.add(crate::places::Place { name: "Thai Palace".to_string(), cuisine: None, contact: None, typical_cost: Some(30.0) });
+        let mut groceries = GroceryList::new(plan.week_start_date);
+        groceries.add_item("Beans", None);
+        groceries.set_purchased("Beans", true);
+        groceries.set_price("Beans", 40.0);
+
+        let text = WeeklyReview::new(&plan, &groceries, &places, &PriceHistory::default(), Some(80.0)).to_markdown();
+        assert!(text.contains("Spent 70.00 of a 80.00 budget, 10.00 under.\nIncludes about 30.00 for 1 meal eaten out.\n"));
+    }
+
+    #[test]
+    fn test_append_to_journal() {
+        let plan = sample_plan();
+        let review = WeeklyReview::new(&plan, &GroceryList::new(plan.week_start_date), &Places::default(), &PriceHistory::default(), None);
+        let dir = tempdir().unwrap();
+        let journal = dir.path().join("journal.md");
+
+        review.append_to_journal(&journal).unwrap();
+        review.append_to_journal(&journal).unwrap();
+
+        let content = std::fs::read_to_string(&journal).unwrap();
+        assert_eq!(content.matches("# Meal Plan Review Journal").count(), 1);
+        assert_eq!(content.matches("## Week of 2023-01-02").count(), 2);
+        assert!(content.contains("No weekly budget is set."));
+    }
+}
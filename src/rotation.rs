@@ -0,0 +1,155 @@
+use crate::models::MealType;
+use chrono::{NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// An ordered list of recipes planned in turn, one per week, on the same day and meal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rotation {
+    pub name: String,
+    pub recipes: Vec<String>,
+    pub meal_type: MealType,
+    pub day: Weekday,
+    /// Cook for the planned meals; the cook rotation picks one when unset
+    #[serde(default)]
+    pub cook: Option<String>,
+    /// Position in `recipes` of the recipe the next week gets
+    #[serde(default)]
+    pub next: usize,
+    /// Start of the week the rotation was last applied to
+    #[serde(default)]
+    pub applied_week: Option<NaiveDate>,
+}
+
+impl Rotation {
+    pub fn new(name: String, recipes: Vec<String>, meal_type: MealType, day: Weekday, cook: Option<String>) -> Self {
+        Self { name, recipes, meal_type, day, cook, next: 0, applied_week: None }
+    }
+
+    /// The recipe the rotation would give a new week
+    pub fn upcoming(&self) -> Option<&str> {
+        self.recipes.get(self.next % self.recipes.len().max(1)).map(String::as_str)
+    }
+
+    /// Takes the recipe for a week, moving on through the list when the week hasn't had one
+    /// yet. Applying again in the same week gives the same recipe.
+    pub fn recipe_for_week(&mut self, week_start: NaiveDate) -> Option<String> {
+        if self.recipes.is_empty() {
+            return None;
+        }
+        let len = self.recipes.len();
+        if self.applied_week == Some(week_start) {
+            return Some(self.recipes[(self.next + len - 1) % len].clone());
+        }
+        let recipe = self.recipes[self.next % len].clone();
+        self.next = (self.next + 1) % len;
+        self.applied_week = Some(week_start);
+        Some(recipe)
+    }
+}
+
+/// The household's rotations, kept in rotations.json in the storage path
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Rotations {
+    pub rotations: Vec<Rotation>,
+}
+
+impl Rotations {
+    /// Adds a rotation, replacing one with the same name. Returns true if it is new.
+    pub fn add(&mut self, rotation: Rotation) -> bool {
+        match self.rotations.iter_mut().find(|r| r.name.eq_ignore_ascii_case(&rotation.name)) {
+            Some(existing) => {
+                *existing = rotation;
+                false
+            }
+            None => {
+                self.rotations.push(rotation);
+                true
+            }
+        }
+    }
+
+    /// Removes a rotation by name, ignoring case, returning false if there was none
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.rotations.len();
+        self.rotations.retain(|r| !r.name.eq_ignore_ascii_case(name.trim()));
+        self.rotations.len() != before
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Rotation> {
+        self.rotations.iter_mut().find(|r| r.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Saves the rotations to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the rotations from a JSON file, which is empty until the first one is created
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn week(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 1, day).unwrap()
+    }
+
+    fn pizza_fridays() -> Rotation {
+        let recipes = vec!["Margherita".to_string(), "Pepperoni".to_string(), "Veggie".to_string()];
+        Rotation::new("Pizza".to_string(), recipes, MealType::Dinner, Weekday::Fri, None)
+    }
+
+    #[test]
+    fn test_recipe_for_week() {
+        let mut rotation = pizza_fridays();
+        assert_eq!(rotation.upcoming(), Some("Margherita"));
+        assert_eq!(rotation.recipe_for_week(week(2)).as_deref(), Some("Margherita"));
+        // The same week keeps its recipe
+        assert_eq!(rotation.recipe_for_week(week(2)).as_deref(), Some("Margherita"));
+        assert_eq!(rotation.recipe_for_week(week(9)).as_deref(), Some("Pepperoni"));
+        assert_eq!(rotation.recipe_for_week(week(16)).as_deref(), Some("Veggie"));
+        assert_eq!(rotation.upcoming(), Some("Margherita"));
+        assert_eq!(rotation.recipe_for_week(week(23)).as_deref(), Some("Margherita"));
+
+        let mut empty = Rotation::new("Empty".to_string(), Vec::new(), MealType::Lunch, Weekday::Mon, None);
+        assert_eq!(empty.upcoming(), None);
+        assert_eq!(empty.recipe_for_week(week(2)), None);
+    }
+
+    #[test]
+    fn test_rotations_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("rotations.json");
+        assert!(Rotations::load_from_json(&path).unwrap().rotations.is_empty());
+
+        let mut rotations = Rotations::default();
+        assert!(rotations.add(pizza_fridays()));
+        rotations.find_mut("pizza").unwrap().recipe_for_week(week(2));
+        rotations.save_to_json(&path).unwrap();
+
+        let mut loaded = Rotations::load_from_json(&path).unwrap();
+        assert_eq!(loaded.rotations, rotations.rotations);
+        assert!(!loaded.add(pizza_fridays()));
+        assert_eq!(loaded.rotations[0].next, 0);
+        assert!(loaded.remove("PIZZA"));
+        assert!(loaded.rotations.is_empty());
+    }
+}
@@ -0,0 +1,637 @@
+use crate::http::{self, Request};
+use crate::recipes::Recipe;
+use scraper::{ElementRef, Html, Selector};
+use serde_json::Value;
+use std::fs;
+use std::io::{self, Read};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Directory in the storage path where fetched pages are kept
+pub const CACHE_DIR: &str = "recipe_cache";
+
+/// How long a fetched page is used before it is fetched again
+pub const CACHE_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Largest page read; recipe pages are well under this, and anything bigger isn't one
+pub const MAX_PAGE_BYTES: u64 = 5 * 1024 * 1024;
+
+/// How the scraper introduces itself to sites, so they can tell it apart and block it in robots.txt
+pub const USER_AGENT: &str = concat!("mealplan/", env!("CARGO_PKG_VERSION"), " (recipe import; +https://github.com/evcraddock/mealplan)");
+
+/// The name robots.txt rules for the scraper go under
+const ROBOTS_AGENT: &str = "mealplan";
+
+/// Most redirects followed to reach a page, as many as browsers commonly allow
+const MAX_REDIRECTS: usize = 5;
+
+/// Pulls a recipe out of the markup of particular sites or recipe plugins, for pages
+/// without recipe data in JSON-LD
+struct Adapter {
+    name: &'static str,
+    /// Sites the adapter is for; an empty list means any site with the markup
+    hosts: &'static [&'static str],
+    title: &'static str,
+    ingredients: &'static str,
+    steps: &'static str,
+    servings: &'static str,
+    image: &'static str,
+}
+
+const ADAPTERS: &[Adapter] = &[
+    Adapter {
+        name: "Smitten Kitchen",
+        hosts: &["smittenkitchen.com"],
+        title: ".jetpack-recipe-title, h1.entry-title",
+        ingredients: ".jetpack-recipe-ingredient",
+        steps: ".jetpack-recipe-directions p, .jetpack-recipe-directions li",
+        servings: ".jetpack-recipe-servings",
+        image: "meta[property='og:image']",
+    },
+    Adapter {
+        name: "Budget Bytes",
+        hosts: &["budgetbytes.com"],
+        title: ".wprm-recipe-name, h1",
+        ingredients: ".wprm-recipe-ingredient",
+        steps: ".wprm-recipe-instruction-text",
+        servings: ".wprm-recipe-servings",
+        image: "meta[property='og:image']",
+    },
+    Adapter {
+        name: "WP Recipe Maker",
+        hosts: &[],
+        title: ".wprm-recipe-name",
+        ingredients: ".wprm-recipe-ingredient",
+        steps: ".wprm-recipe-instruction-text",
+        servings: ".wprm-recipe-servings",
+        image: ".wprm-recipe-image img, meta[property='og:image']",
+    },
+    Adapter {
+        name: "Tasty Recipes",
+        hosts: &[],
+        title: ".tasty-recipes-title",
+        ingredients: ".tasty-recipes-ingredients li",
+        steps: ".tasty-recipes-instructions li, .tasty-recipes-instructions p",
+        servings: ".tasty-recipes-yield",
+        image: ".tasty-recipes-image img, meta[property='og:image']",
+    },
+    Adapter {
+        name: "microdata",
+        hosts: &[],
+        title: "[itemtype$='schema.org/Recipe'] [itemprop='name']",
+        ingredients: "[itemprop='recipeIngredient'], [itemprop='ingredients']",
+        steps: "[itemprop='recipeInstructions'] li, [itemprop='recipeInstructions']:not(:has(li))",
+        servings: "[itemprop='recipeYield']",
+        image: "[itemprop='image'], meta[property='og:image']",
+    },
+];
+
+/// A recipe read from a web page, with how it was found
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scraped {
+    pub recipe: Recipe,
+    /// "recipe data" for JSON-LD, or the name of the adapter that read it
+    pub source: String,
+    pub from_cache: bool,
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Splits a web address into its scheme, host (with any port) and path
+fn split_url(url: &str) -> io::Result<(&str, &str, &str)> {
+    let (scheme, rest) = url.split_once("://").ok_or_else(|| invalid_input(format!("{} isn't a web address.", url)))?;
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return Err(invalid_input(format!("Only http and https addresses can be imported, not {}.", scheme)));
+    }
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (host, path) = rest.split_at(end);
+    if host.is_empty() || host.contains('@') {
+        return Err(invalid_input(format!("{} isn't a web address.", url)));
+    }
+    Ok((scheme, host, if path.is_empty() { "/" } else { path }))
+}
+
+/// The address a redirect's Location points to, which may be relative to the page
+fn join_url(base: &str, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let (scheme, rest) = base.split_once("://").unwrap_or(("http", base));
+    if let Some(authority) = location.strip_prefix("//") {
+        return format!("{}://{}", scheme, authority);
+    }
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (host, path) = rest.split_at(end);
+    if location.starts_with('/') {
+        return format!("{}://{}{}", scheme, host, location);
+    }
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let directory = path.rfind('/').map_or("/", |slash| &path[..=slash]);
+    format!("{}://{}{}{}", scheme, host, directory, location)
+}
+
+/// Whether an address is on the internet rather than this machine or the local network
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified() || ip.is_broadcast()),
+        IpAddr::V6(ip) => {
+            let local = (ip.segments()[0] & 0xfe00) == 0xfc00 || (ip.segments()[0] & 0xffc0) == 0xfe80;
+            !(ip.is_loopback() || ip.is_unspecified() || local) && ip.to_ipv4_mapped().is_none_or(|ip| is_public_address(IpAddr::V4(ip)))
+        }
+    }
+}
+
+/// Turns down hosts on this machine or the local network, so a recipe link can't be used
+/// to reach a router's or another service's pages. Returns the addresses checked, which the
+/// request is then sent to, so a host can't point somewhere else once it has been checked.
+fn check_public_host(scheme: &str, host: &str) -> io::Result<Vec<SocketAddr>> {
+    let default_port = if scheme.eq_ignore_ascii_case("https") { 443 } else { 80 };
+    let (name, port) = match host.strip_prefix('[') {
+        Some(rest) => {
+            let (ip, after) = rest.split_once(']').unwrap_or((rest, ""));
+            (ip, after.strip_prefix(':'))
+        }
+        None => match host.rsplit_once(':') {
+            Some((name, port)) => (name, Some(port)),
+            None => (host, None),
+        },
+    };
+    let port = port.and_then(|port| port.parse().ok()).unwrap_or(default_port);
+    let addresses: Vec<SocketAddr> = (name, port).to_socket_addrs()
+        .map_err(|e| io::Error::new(e.kind(), format!("Couldn't find {}: {}", host, e)))?
+        .collect();
+    if addresses.iter().any(|address| !is_public_address(address.ip())) {
+        return Err(invalid_input(format!("{} is on this machine or the local network, so it isn't imported from.", host)));
+    }
+    Ok(addresses)
+}
+
+/// The robots a robots.txt group is for, and its rules: whether each allows, and the path
+type RobotsGroup = (Vec<String>, Vec<(bool, String)>);
+
+/// Whether robots.txt lets the scraper fetch a path: the most specific Allow or Disallow
+/// rule in the group for mealplan, or else the one for every robot, wins
+pub fn robots_allows(robots: &str, path: &str) -> bool {
+    let mut groups: Vec<RobotsGroup> = Vec::new();
+    let mut in_agents = false;
+    for line in robots.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let Some((field, value)) = line.split_once(':') else { continue };
+        let (field, value) = (field.trim().to_lowercase(), value.trim());
+        match field.as_str() {
+            "user-agent" => {
+                if !in_agents {
+                    groups.push((Vec::new(), Vec::new()));
+                }
+                if let Some(group) = groups.last_mut() {
+                    group.0.push(value.to_lowercase());
+                }
+                in_agents = true;
+            }
+            "allow" | "disallow" => {
+                in_agents = false;
+                if let Some(group) = groups.last_mut().filter(|_| !value.is_empty()) {
+                    group.1.push((field == "allow", value.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    let group = groups
+        .iter()
+        .find(|(agents, _)| agents.iter().any(|agent| agent == ROBOTS_AGENT))
+        .or_else(|| groups.iter().find(|(agents, _)| agents.iter().any(|agent| agent == "*")));
+    let Some((_, rules)) = group else { return true };
+    rules
+        .iter()
+        .filter(|(_, prefix)| path.starts_with(prefix.trim_end_matches('*').trim_end_matches('$')))
+        .max_by_key(|(allow, prefix)| (prefix.len(), *allow))
+        .is_none_or(|(allow, _)| *allow)
+}
+
+/// Fetches pages politely: as mealplan, within robots.txt, no more often than the
+/// configured interval per site, and from the cache while it is fresh
+pub struct Fetcher {
+    cache_dir: PathBuf,
+    /// Fetch again even when the cache holds the page
+    pub refresh: bool,
+    /// Hosts on this machine fetched from all the same, such as a test server
+    local_hosts: Vec<String>,
+}
+
+impl Fetcher {
+    pub fn new(storage_path: &Path) -> Self {
+        Self { cache_dir: storage_path.join(CACHE_DIR), refresh: false, local_hosts: Vec::new() }
+    }
+
+    /// The cache file for a web address, named by its hash
+    fn cache_path(&self, url: &str) -> PathBuf {
+        let digest = ring::digest::digest(&ring::digest::SHA256, url.as_bytes());
+        let name: String = digest.as_ref().iter().map(|byte| format!("{:02x}", byte)).collect();
+        self.cache_dir.join(format!("{}.html", name))
+    }
+
+    fn cached(&self, url: &str) -> Option<String> {
+        let path = self.cache_path(url);
+        let age = fs::metadata(&path).and_then(|meta| meta.modified()).ok()?.elapsed().unwrap_or_default();
+        (age <= CACHE_MAX_AGE).then(|| fs::read_to_string(&path).ok()).flatten()
+    }
+
+    /// Reads a page as text, up to the size limit. Redirects are followed one at a time so
+    /// each address is checked to be public, and for a recipe page allowed by robots.txt.
+    /// `html_only` marks a recipe page, and turns down pages that aren't HTML, such as PDFs
+    /// and images.
+    fn download(&self, url: &str, html_only: bool) -> io::Result<Option<String>> {
+        let mut url = url.to_string();
+        let mut redirects = 0;
+        let response = loop {
+            let (scheme, host, path) = split_url(&url)?;
+            let addresses = if self.local_hosts.iter().any(|local| local.eq_ignore_ascii_case(host)) {
+                Vec::new()
+            } else {
+                check_public_host(scheme, host)?
+            };
+            if html_only {
+                self.check_robots(scheme, host, path)?;
+            }
+            let request = Request::new("GET", url.as_str())
+                .header("User-Agent", USER_AGENT)
+                .header("Accept", "text/html,application/xhtml+xml")
+                .no_redirects()
+                .connect_to(addresses);
+            let response = match http::send(&request) {
+                Ok(response) => response,
+                Err(error) => match *error {
+                    ureq::Error::Status(404 | 410, _) if !html_only => return Ok(None),
+                    ureq::Error::Status(code, _) => return Err(io::Error::other(format!("{} answered {}.", url, code))),
+                    ureq::Error::Transport(transport) => return Err(io::Error::other(format!("Couldn't reach {}: {}", url, transport))),
+                },
+            };
+            if !(300..400).contains(&response.status()) {
+                break response;
+            }
+            let location = response.header("Location")
+                .ok_or_else(|| io::Error::other(format!("{} answered {} without saying where to go.", url, response.status())))?;
+            if redirects == MAX_REDIRECTS {
+                return Err(io::Error::other(format!("{} redirected more than {} times.", url, MAX_REDIRECTS)));
+            }
+            url = join_url(&url, location.trim());
+            redirects += 1;
+        };
+        if html_only && !response.content_type().contains("html") {
+            return Err(invalid_input(format!("{} is a {} file, not a web page.", url, response.content_type())));
+        }
+        let mut body = Vec::new();
+        response.into_reader().take(MAX_PAGE_BYTES + 1).read_to_end(&mut body)?;
+        if body.len() as u64 > MAX_PAGE_BYTES {
+            return Err(invalid_input(format!("{} is over {} MB, too big for a recipe page.", url, MAX_PAGE_BYTES / 1024 / 1024)));
+        }
+        Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+    }
+
+    /// The page at a web address, and whether it came from the cache
+    pub fn fetch(&self, url: &str) -> io::Result<(String, bool)> {
+        split_url(url)?;
+        if !self.refresh {
+            if let Some(html) = self.cached(url) {
+                return Ok((html, true));
+            }
+        }
+
+        let html = self.download(url, true)?.unwrap_or_default();
+        self.store(url, &html);
+        Ok((html, false))
+    }
+
+    /// Turns down a path the site's robots.txt asks robots not to fetch
+    fn check_robots(&self, scheme: &str, host: &str, path: &str) -> io::Result<()> {
+        let robots_url = format!("{}://{}/robots.txt", scheme, host);
+        let robots = match self.cached(&robots_url).filter(|_| !self.refresh) {
+            Some(robots) => robots,
+            None => {
+                let robots = self.download(&robots_url, false).unwrap_or_default().unwrap_or_default();
+                self.store(&robots_url, &robots);
+                robots
+            }
+        };
+        if !robots_allows(&robots, path) {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("{} asks robots not to fetch {}.", host, path)));
+        }
+        Ok(())
+    }
+
+    /// Keeps a page in the cache. A cache that can't be written only means fetching again.
+    fn store(&self, url: &str, contents: &str) {
+        let _ = fs::create_dir_all(&self.cache_dir).and_then(|_| fs::write(self.cache_path(url), contents));
+    }
+
+    /// Removes cached pages older than the maximum age, returning how many
+    pub fn prune(&self) -> io::Result<usize> {
+        let Ok(entries) = fs::read_dir(&self.cache_dir) else { return Ok(0) };
+        let now = SystemTime::now();
+        let mut removed = 0;
+        for entry in entries {
+            let entry = entry?;
+            let modified = entry.metadata()?.modified()?;
+            if now.duration_since(modified).unwrap_or_default() > CACHE_MAX_AGE {
+                fs::remove_file(entry.path())?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Fetches a recipe page and reads the recipe from it
+pub fn scrape(fetcher: &Fetcher, url: &str) -> io::Result<Scraped> {
+    let (html, from_cache) = fetcher.fetch(url)?;
+    let (_, host, _) = split_url(url)?;
+    let (recipe, source) = extract(&html, host, url).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("No recipe found at {}. Add it with 'mealplan recipe add' instead.", url))
+    })?;
+    Ok(Scraped { recipe, source, from_cache })
+}
+
+/// Reads a recipe from a page: with the adapter for the site when there is one, then from
+/// the page's JSON-LD recipe data, then with the adapters for recipe plugins and microdata
+pub fn extract(html: &str, host: &str, url: &str) -> Option<(Recipe, String)> {
+    let document = Html::parse_document(html);
+    let host = host.split(':').next().unwrap_or(host).trim_start_matches("www.").to_lowercase();
+    let site = ADAPTERS.iter().filter(|adapter| adapter.hosts.iter().any(|h| host == *h || host.ends_with(&format!(".{}", h))));
+    let from_site = site.clone().find_map(|adapter| adapt(&document, adapter).map(|recipe| (recipe, adapter.name.to_string())));
+    let (mut recipe, source) = from_site
+        .or_else(|| json_ld(&document).map(|recipe| (recipe, "recipe data".to_string())))
+        .or_else(|| {
+            ADAPTERS
+                .iter()
+                .filter(|adapter| adapter.hosts.is_empty())
+                .find_map(|adapter| adapt(&document, adapter).map(|recipe| (recipe, adapter.name.to_string())))
+        })?;
+    recipe.link = Some(url.to_string());
+    Some((recipe, source))
+}
+
+fn select<'a>(document: &'a Html, selector: &str) -> Vec<ElementRef<'a>> {
+    Selector::parse(selector).map(|selector| document.select(&selector).collect()).unwrap_or_default()
+}
+
+/// An element's text with its whitespace collapsed, or its content attribute for meta tags
+fn element_text(element: &ElementRef) -> String {
+    let text = match element.value().attr("content").or_else(|| element.value().attr("src")) {
+        Some(attribute) if element.text().all(|text| text.trim().is_empty()) => attribute.to_string(),
+        _ => element.text().collect::<Vec<_>>().join(" "),
+    };
+    clean_text(&text)
+}
+
+fn adapt(document: &Html, adapter: &Adapter) -> Option<Recipe> {
+    let texts = |selector: &str| -> Vec<String> { select(document, selector).iter().map(element_text).filter(|text| !text.is_empty()).collect() };
+    let ingredients = texts(adapter.ingredients);
+    if ingredients.is_empty() {
+        return None;
+    }
+    let name = texts(adapter.title).into_iter().next().or_else(|| texts("h1").into_iter().next())?;
+    Some(Recipe {
+        name,
+        tags: Vec::new(),
+        ingredients,
+        link: None,
+        servings: texts(adapter.servings).iter().find_map(|text| first_number(text)),
+        photo: texts(adapter.image).into_iter().find(|image| image.starts_with("http")),
+        steps: texts(adapter.steps),
+        equipment: Vec::new(),
+    })
+}
+
+/// Reads the first Recipe in the page's JSON-LD blocks, which may be nested in a @graph or a list
+fn json_ld(document: &Html) -> Option<Recipe> {
+    select(document, "script[type='application/ld+json']").iter().find_map(|script| {
+        let json: Value = serde_json::from_str(script.text().collect::<String>().trim()).ok()?;
+        find_recipe(&json).and_then(recipe_from_json)
+    })
+}
+
+fn find_recipe(json: &Value) -> Option<&Value> {
+    match json {
+        Value::Array(items) => items.iter().find_map(find_recipe),
+        Value::Object(object) => {
+            let is_recipe = match object.get("@type") {
+                Some(Value::String(kind)) => kind == "Recipe",
+                Some(Value::Array(kinds)) => kinds.iter().any(|kind| kind == "Recipe"),
+                _ => false,
+            };
+            if is_recipe {
+                return Some(json);
+            }
+            object.get("@graph").or_else(|| object.get("mainEntity")).and_then(find_recipe)
+        }
+        _ => None,
+    }
+}
+
+/// Every piece of text in a JSON-LD value: a string, a list, or objects with text, name or url
+fn json_texts(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(text) => vec![clean_text(text)],
+        Value::Number(number) => vec![number.to_string()],
+        Value::Array(items) => items.iter().flat_map(json_texts).collect(),
+        // Sections of steps hold their steps in itemListElement
+        Value::Object(object) => match object.get("itemListElement") {
+            Some(items) => json_texts(items),
+            None => ["text", "name", "url"].iter().find_map(|key| object.get(*key)).map(json_texts).unwrap_or_default(),
+        },
+        _ => Vec::new(),
+    }
+    .into_iter()
+    .filter(|text| !text.is_empty())
+    .collect()
+}
+
+fn recipe_from_json(json: &Value) -> Option<Recipe> {
+    let texts = |key: &str| json.get(key).map(json_texts).unwrap_or_default();
+    let name = texts("name").into_iter().next()?;
+    let mut tags: Vec<String> = Vec::new();
+    for tag in ["recipeCategory", "recipeCuisine", "keywords"].iter().flat_map(|key| texts(key)).flat_map(|text| split_list(&text)) {
+        if !tags.iter().any(|known| known.eq_ignore_ascii_case(&tag)) {
+            tags.push(tag);
+        }
+    }
+    // A single block of instructions is split into its lines
+    let mut steps = texts("recipeInstructions");
+    if steps.len() == 1 {
+        steps = json["recipeInstructions"].as_str().map(|text| text.lines().map(clean_text).filter(|line| !line.is_empty()).collect()).unwrap_or(steps);
+    }
+    Some(Recipe {
+        name,
+        tags,
+        ingredients: texts("recipeIngredient").into_iter().chain(texts("ingredients")).collect(),
+        link: None,
+        servings: texts("recipeYield").iter().find_map(|text| first_number(text)),
+        photo: texts("image").into_iter().find(|image| image.starts_with("http")),
+        steps,
+        equipment: Vec::new(),
+    })
+}
+
+/// Decodes the entities and drops the tags sites leave in recipe text, and collapses whitespace
+fn clean_text(text: &str) -> String {
+    let mut plain = String::new();
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                plain.push(' ');
+            }
+            c if !in_tag => plain.push(c),
+            _ => {}
+        }
+    }
+    let decoded = [("&nbsp;", " "), ("&quot;", "\""), ("&#39;", "'"), ("&#039;", "'"), ("&apos;", "'"), ("&lt;", "<"), ("&gt;", ">"), ("&frac12;", "½"), ("&amp;", "&")]
+        .iter()
+        .fold(plain, |text, (entity, replacement)| text.replace(entity, replacement));
+    decoded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn split_list(text: &str) -> Vec<String> {
+    text.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect()
+}
+
+/// The first whole number in text such as "4 servings" or "Serves 6-8"
+fn first_number(text: &str) -> Option<u32> {
+    let digits: String = text.chars().skip_while(|c| !c.is_ascii_digit()).take_while(char::is_ascii_digit).collect();
+    digits.parse().ok().filter(|servings| *servings > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{Ipv4Addr, Ipv6Addr, TcpListener};
+
+    #[test]
+    fn test_json_ld() {
+        let html = r#"<html><head><script type="application/ld+json">
+            {"@context": "https://schema.org", "@graph": [
+                {"@type": "WebPage", "name": "Ignored"},
+                {"@type": ["Recipe"], "name": "Chili &amp; Cornbread", "recipeYield": ["6", "6 servings"],
+                 "image": [{"@type": "ImageObject", "url": "https://example.com/chili.jpg"}],
+                 "keywords": "beans, slow cooker", "recipeCategory": "Dinner",
+                 "recipeIngredient": ["1 lb beef", "2 cans <b>beans</b>"],
+                 "recipeInstructions": [{"@type": "HowToSection", "name": "Cook", "itemListElement": [
+                     {"@type": "HowToStep", "text": "Brown the beef."}, {"@type": "HowToStep", "text": "Simmer 2 hours."}]}]}
+            ]}</script></head><body></body></html>"#;
+        let (recipe, source) = extract(html, "www.example.com", "https://www.example.com/chili").unwrap();
+        assert_eq!(source, "recipe data");
+        assert_eq!(recipe.name, "Chili & Cornbread");
+        assert_eq!(recipe.ingredients, vec!["1 lb beef".to_string(), "2 cans beans".to_string()]);
+        assert_eq!(recipe.steps, vec!["Brown the beef.".to_string(), "Simmer 2 hours.".to_string()]);
+        assert_eq!(recipe.tags, vec!["Dinner".to_string(), "beans".to_string(), "slow cooker".to_string()]);
+        assert_eq!((recipe.servings, recipe.photo.as_deref()), (Some(6), Some("https://example.com/chili.jpg")));
+        assert_eq!(recipe.link.as_deref(), Some("https://www.example.com/chili"));
+        assert!(extract("<html><h1>Not a recipe</h1></html>", "example.com", "https://example.com").is_none());
+    }
+
+    #[test]
+    fn test_adapters() {
+        let wprm = r#"<html><head><meta property="og:image" content="https://example.com/soup.jpg"></head><body>
+            <h2 class="wprm-recipe-name">Lentil Soup</h2><span class="wprm-recipe-servings">4</span>
+            <ul><li class="wprm-recipe-ingredient">1 cup  lentils</li><li class="wprm-recipe-ingredient">1 onion</li></ul>
+            <div class="wprm-recipe-instruction-text">Simmer.</div></body></html>"#;
+        let (recipe, source) = extract(wprm, "soup.example.com", "https://soup.example.com/lentil").unwrap();
+        assert_eq!(source, "WP Recipe Maker");
+        assert_eq!(recipe.ingredients, vec!["1 cup lentils".to_string(), "1 onion".to_string()]);
+        assert_eq!((recipe.servings, recipe.photo.as_deref(), recipe.steps.len()), (Some(4), Some("https://example.com/soup.jpg"), 1));
+
+        // The site's own adapter is tried before the page's recipe data
+        let jetpack = r#"<html><script type="application/ld+json">{"@type": "Recipe", "name": "Wrong"}</script>
+            <div class="jetpack-recipe-title">Tomato Tart</div><li class="jetpack-recipe-ingredient">3 tomatoes</li>
+            <div class="jetpack-recipe-directions"><p>Slice.</p><p>Bake.</p></div></html>"#;
+        let (recipe, source) = extract(jetpack, "smittenkitchen.com", "https://smittenkitchen.com/tart").unwrap();
+        assert_eq!((recipe.name.as_str(), source.as_str(), recipe.steps.len()), ("Tomato Tart", "Smitten Kitchen", 2));
+    }
+
+    #[test]
+    fn test_politeness() {
+        let robots = "User-agent: *\nDisallow: /private\n\nUser-agent: mealplan\nUser-agent: other\nDisallow: /recipes/\nAllow: /recipes/public\n";
+        assert!(robots_allows(robots, "/private/page"));
+        assert!(!robots_allows(robots, "/recipes/chili"));
+        assert!(robots_allows(robots, "/recipes/public/chili"));
+        assert!(!robots_allows("User-agent: *\nDisallow: /\n", "/chili"));
+        assert!(robots_allows("", "/chili"));
+
+        assert!(is_public_address(IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34))));
+        for ip in ["127.0.0.1", "10.1.2.3", "192.168.1.1", "169.254.1.1", "::1", "fd00::1", "::ffff:192.168.1.1"] {
+            assert!(!is_public_address(ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(is_public_address(IpAddr::V6(Ipv6Addr::new(0x2606, 0x4700, 0, 0, 0, 0, 0, 1))));
+        assert_eq!(check_public_host("https", "93.184.216.34").unwrap(), vec!["93.184.216.34:443".parse().unwrap()]);
+        assert_eq!(check_public_host("http", "[2606:4700::1]:8080").unwrap(), vec!["[2606:4700::1]:8080".parse().unwrap()]);
+        assert!(check_public_host("http", "10.1.2.3").is_err());
+        assert!(split_url("file:///etc/passwd").is_err());
+        assert_eq!(split_url("https://example.com").unwrap(), ("https", "example.com", "/"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let fetcher = Fetcher::new(dir.path());
+        assert!(fetcher.fetch("http://127.0.0.1:9/chili").unwrap_err().to_string().contains("local network"));
+        // A cached page is used without going to the network
+        fetcher.store("https://example.com/chili", "<html>Chili</html>");
+        assert_eq!(fetcher.fetch("https://example.com/chili").unwrap(), ("<html>Chili</html>".to_string(), true));
+    }
+
+    /// Serves each path's response from a thread, answering 404 for any other
+    fn serve(pages: Vec<(&'static str, String)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 0 && !header.trim().is_empty() {
+                    header.clear();
+                }
+                let path = line.split_whitespace().nth(1).unwrap_or_default();
+                let head = pages.iter()
+                    .find(|(page, _)| *page == path)
+                    .map_or("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n".to_string(), |(_, head)| head.clone());
+                reader.get_mut().write_all(format!("{}Connection: close\r\n\r\n", head).as_bytes()).unwrap();
+            }
+        });
+        host
+    }
+
+    #[test]
+    fn test_redirects() {
+        assert_eq!(join_url("https://a.example/x/y?q=1", "https://b.example/z"), "https://b.example/z");
+        assert_eq!(join_url("https://a.example/x/y?q=1", "//b.example/z"), "https://b.example/z");
+        assert_eq!(join_url("https://a.example/x/y?q=1", "/z"), "https://a.example/z");
+        assert_eq!(join_url("https://a.example/x/y?q=1", "z"), "https://a.example/x/z");
+
+        let redirect = |location: &str| format!("HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n", location);
+        let html = "<html>Chili</html>";
+        let host = serve(vec![
+            ("/robots.txt", "HTTP/1.1 200 OK\r\nContent-Length: 32\r\n\r\nUser-agent: *\nDisallow: /private".to_string()),
+            ("/chili", redirect("/recipes/chili")),
+            ("/recipes/chili", format!("HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n{}", html.len(), html)),
+            ("/router", redirect("http://127.0.0.1:1/admin")),
+            ("/hidden", redirect("private/chili")),
+            ("/loop", redirect("/loop")),
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+        let mut fetcher = Fetcher::new(dir.path());
+        fetcher.local_hosts.push(host.clone());
+        let url = |path: &str| format!("http://{}{}", host, path);
+
+        assert_eq!(fetcher.fetch(&url("/chili")).unwrap(), (html.to_string(), false));
+        // A public page can't send the scraper on to this machine or past robots.txt
+        assert!(fetcher.fetch(&url("/router")).unwrap_err().to_string().contains("local network"));
+        assert_eq!(fetcher.fetch(&url("/hidden")).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert!(fetcher.fetch(&url("/loop")).unwrap_err().to_string().contains("redirected more than 5 times"));
+    }
+}
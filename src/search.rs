@@ -0,0 +1,259 @@
+use crate::models::{MealPlan, MealType};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Gap left between the positions of a meal's fields, so a phrase can't run from one into the next
+const FIELD_GAP: usize = 1000;
+
+/// A meal as stored in the search index, with everything needed to show it in results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexedMeal {
+    pub date: NaiveDate,
+    pub meal_type: MealType,
+    pub cook: String,
+    pub description: String,
+}
+
+/// Where a term appears: the meal, and the positions of the term among the meal's words
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Posting {
+    week: NaiveDate,
+    meal: usize,
+    positions: Vec<usize>,
+}
+
+/// Identifies a meal by its week and its place in that week's list
+type MealRef = (NaiveDate, usize);
+
+/// One part of a query. Every part must match for a meal to be found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryTerm {
+    /// A whole word
+    Word(String),
+    /// The start of a word, written with a trailing `*`
+    Prefix(String),
+    /// Words that must appear next to each other in order, written in double quotes
+    Phrase(Vec<String>),
+}
+
+/// Parses a query such as `"chicken curry" alice` or `lasag*`
+pub fn parse_query(query: &str) -> Vec<QueryTerm> {
+    let mut terms = Vec::new();
+    for (n, part) in query.split('"').enumerate() {
+        if n % 2 == 1 {
+            match tokenize(part).as_slice() {
+                [] => {}
+                [word] => terms.push(QueryTerm::Word(word.clone())),
+                words => terms.push(QueryTerm::Phrase(words.to_vec())),
+            }
+            continue;
+        }
+        for word in part.split_whitespace() {
+            let prefix = word.ends_with('*');
+            for token in tokenize(word) {
+                terms.push(if prefix { QueryTerm::Prefix(token) } else { QueryTerm::Word(token) });
+            }
+        }
+    }
+    terms
+}
+
+/// Splits text into lowercase words of letters and digits
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// An inverted index from words to the archived meals they appear in, so searching years
+/// of plans doesn't mean reading every week
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    /// Indexed meals by week start date. Weeks with no meals are kept so they aren't re-read.
+    pub weeks: BTreeMap<NaiveDate, Vec<IndexedMeal>>,
+    terms: BTreeMap<String, Vec<Posting>>,
+}
+
+impl SearchIndex {
+    /// Indexes a week's meals, replacing what was indexed for that week before
+    pub fn add_week(&mut self, meal_plan: &MealPlan) {
+        self.remove_week(meal_plan.week_start_date);
+
+        let week = meal_plan.week_start_date;
+        let mut meals = Vec::new();
+        for (n, meal) in meal_plan.meals().iter().enumerate() {
+            let fields = [meal.description.as_str(), meal.cook.as_str(), &meal.tags.join(" ")].map(tokenize);
+            let mut positions: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+            for (field, words) in fields.into_iter().enumerate() {
+                for (offset, word) in words.into_iter().enumerate() {
+                    positions.entry(word).or_default().push(field * FIELD_GAP + offset);
+                }
+            }
+            for (word, positions) in positions {
+                self.terms.entry(word).or_default().push(Posting { week, meal: n, positions });
+            }
+
+            meals.push(IndexedMeal {
+                date: meal_plan.date_of(&meal.day),
+                meal_type: meal.meal_type.clone(),
+                cook: meal.cook.clone(),
+                description: meal.description.clone(),
+            });
+        }
+        self.weeks.insert(week, meals);
+    }
+
+    /// Drops a week from the index
+    pub fn remove_week(&mut self, week_start_date: NaiveDate) {
+        if self.weeks.remove(&week_start_date).is_none() {
+            return;
+        }
+        for postings in self.terms.values_mut() {
+            postings.retain(|p| p.week != week_start_date);
+        }
+        self.terms.retain(|_, postings| !postings.is_empty());
+    }
+
+    /// Finds the meals matching every term of the query, most recent first
+    pub fn search(&self, query: &[QueryTerm]) -> Vec<&IndexedMeal> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut found: Option<BTreeSet<MealRef>> = None;
+        for term in query {
+            let matches = self.matches(term);
+            found = Some(match found {
+                Some(found) => found.intersection(&matches).copied().collect(),
+                None => matches,
+            });
+        }
+
+        let mut meals: Vec<&IndexedMeal> = found
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(week, n)| self.weeks.get(&week)?.get(n))
+            .collect();
+        meals.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| meal_type_order(&b.meal_type).cmp(&meal_type_order(&a.meal_type))));
+        meals
+    }
+
+    fn matches(&self, term: &QueryTerm) -> BTreeSet<MealRef> {
+        let refs = |postings: &Vec<Posting>| postings.iter().map(|p| (p.week, p.meal)).collect::<Vec<_>>();
+        match term {
+            QueryTerm::Word(word) => self.terms.get(word).map(refs).unwrap_or_default().into_iter().collect(),
+            QueryTerm::Prefix(prefix) => self
+                .terms
+                .range(prefix.clone()..)
+                .take_while(|(word, _)| word.starts_with(prefix.as_str()))
+                .flat_map(|(_, postings)| refs(postings))
+                .collect(),
+            QueryTerm::Phrase(words) => self.phrase_matches(words),
+        }
+    }
+
+    /// Finds the meals where the words appear one after another
+    fn phrase_matches(&self, words: &[String]) -> BTreeSet<MealRef> {
+        let positions = |word: &String, meal: MealRef| -> Option<&Vec<usize>> {
+            self.terms.get(word)?.iter().find(|p| (p.week, p.meal) == meal).map(|p| &p.positions)
+        };
+        let Some(first) = self.terms.get(&words[0]) else {
+            return BTreeSet::new();
+        };
+
+        first
+            .iter()
+            .filter(|posting| {
+                let meal = (posting.week, posting.meal);
+                posting.positions.iter().any(|&start| {
+                    words[1..].iter().enumerate().all(|(n, word)| {
+                        positions(word, meal).is_some_and(|p| p.contains(&(start + n + 1)))
+                    })
+                })
+            })
+            .map(|posting| (posting.week, posting.meal))
+            .collect()
+    }
+}
+
+fn meal_type_order(meal_type: &MealType) -> usize {
+    match meal_type {
+        MealType::Breakfast => 0,
+        MealType::Lunch => 1,
+        MealType::Snack => 2,
+        MealType::Dinner => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, Meal};
+    use chrono::Weekday;
+
+    fn week(start: NaiveDate, meals: &[(Weekday, &str, &str)]) -> MealPlan {
+        let mut plan = MealPlan::new(start);
+        for (day, cook, description) in meals {
+            plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(*day), cook.to_string(), description.to_string()));
+        }
+        plan
+    }
+
+    fn index() -> SearchIndex {
+        let first = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        let second = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        let mut index = SearchIndex::default();
+        index.add_week(&week(first, &[(Weekday::Mon, "Alice", "Chicken curry"), (Weekday::Tue, "Bob", "Curry chicken pie")]));
+        index.add_week(&week(second, &[(Weekday::Wed, "Alice", "Green chicken curry"), (Weekday::Thu, "Alice", "Lasagna")]));
+        index
+    }
+
+    fn descriptions(meals: Vec<&IndexedMeal>) -> Vec<&str> {
+        meals.into_iter().map(|m| m.description.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(
+            parse_query("\"Chicken  Curry\" alice lasag* \"pie\""),
+            vec![
+                QueryTerm::Phrase(vec!["chicken".to_string(), "curry".to_string()]),
+                QueryTerm::Word("alice".to_string()),
+                QueryTerm::Prefix("lasag".to_string()),
+                QueryTerm::Word("pie".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_words_and_phrases() {
+        let index = index();
+        let search = |query: &str| descriptions(index.search(&parse_query(query)));
+
+        assert_eq!(search("curry chicken"), vec!["Green chicken curry", "Curry chicken pie", "Chicken curry"]);
+        assert_eq!(search("\"chicken curry\""), vec!["Green chicken curry", "Chicken curry"]);
+        assert_eq!(search("\"chicken curry\" bob"), Vec::<&str>::new());
+        assert_eq!(search("alice lasag*"), vec!["Lasagna"]);
+        assert_eq!(search("lasag"), Vec::<&str>::new());
+        // Phrases don't run from the description into the cook
+        assert_eq!(search("\"lasagna alice\""), Vec::<&str>::new());
+        assert!(search("").is_empty());
+    }
+
+    #[test]
+    fn test_reindexing_a_week_replaces_it() {
+        let mut index = index();
+        let first = NaiveDate::from_ymd_opt(2023, 1, 2).unwrap();
+        index.add_week(&week(first, &[(Weekday::Fri, "Carol", "Fish tacos")]));
+
+        assert_eq!(descriptions(index.search(&parse_query("chicken"))), vec!["Green chicken curry"]);
+        let tacos = index.search(&parse_query("tacos"));
+        assert_eq!(tacos[0].date, NaiveDate::from_ymd_opt(2023, 1, 6).unwrap());
+
+        index.remove_week(first);
+        assert!(index.search(&parse_query("tacos")).is_empty());
+        assert!(!index.terms.contains_key("tacos"));
+    }
+}
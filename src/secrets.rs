@@ -0,0 +1,271 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Service name the tokens are filed under in the system keyring
+pub const KEYRING_SERVICE: &str = "mealplan";
+
+/// Encrypted file next to the configuration holding tokens when there is no keyring
+pub const SECRETS_FILE: &str = "secrets.json";
+
+/// Key for `secrets.json`, generated the first time a token is stored there
+pub const KEY_FILE: &str = "secrets.key";
+
+/// Environment variable with a passphrase to encrypt `secrets.json` with instead of the key file
+pub const PASSPHRASE_VAR: &str = "MEALPLAN_SECRETS_PASSPHRASE";
+
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+/// Services whose tokens can be kept out of the configuration file
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Integration {
+    /// The access token for protected ntfy topics
+    Ntfy,
+    /// The app token of Gotify targets
+    Gotify,
+    /// The access token of the Matrix bot's account
+    Matrix,
+    /// A service account key for pushing to Google Sheets, as the key file's contents
+    Google,
+    /// The password of the mail server account the weekly digest is sent with
+    Email,
+    /// The secret shared by machines that sync with `sync peer`
+    Peer,
+}
+
+impl Integration {
+    /// The name the token is stored under
+    pub fn key(&self) -> &'static str {
+        match self {
+            Integration::Ntfy => "ntfy",
+            Integration::Gotify => "gotify",
+            Integration::Matrix => "matrix",
+            Integration::Google => "google",
+            Integration::Email => "email",
+            Integration::Peer => "peer",
+        }
+    }
+}
+
+impl fmt::Display for Integration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Integration::Ntfy => write!(f, "ntfy"),
+            Integration::Gotify => write!(f, "Gotify"),
+            Integration::Matrix => write!(f, "Matrix"),
+            Integration::Google => write!(f, "Google"),
+            Integration::Email => write!(f, "email"),
+            Integration::Peer => write!(f, "peer"),
+        }
+    }
+}
+
+/// Where a token was stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Keyring,
+    /// The encrypted file, as the keyring couldn't be used, with why
+    File { path: PathBuf, reason: String },
+}
+
+/// The encrypted file's contents: the tokens as a JSON object, sealed with ChaCha20-Poly1305
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedFile {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Tokens for one configuration, kept in the system keyring, or in an encrypted file beside
+/// the configuration when there is no keyring, such as on a headless server
+#[derive(Debug, Clone)]
+pub struct SecretStore {
+    /// The configuration's directory, which also tells profiles' tokens apart in the keyring
+    dir: PathBuf,
+    use_keyring: bool,
+}
+
+impl SecretStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir, use_keyring: true }
+    }
+
+    /// A store that only uses the encrypted file
+    pub fn file_only(dir: PathBuf) -> Self {
+        Self { dir, use_keyring: false }
+    }
+
+    pub fn file_path(&self) -> PathBuf {
+        self.dir.join(SECRETS_FILE)
+    }
+
+    fn entry(&self, integration: Integration) -> keyring::Result<keyring::Entry> {
+        keyring::Entry::new(KEYRING_SERVICE, &format!("{} ({})", integration.key(), self.dir.display()))
+    }
+
+    /// The stored token, looking in the keyring first
+    pub fn get(&self, integration: Integration) -> io::Result<Option<String>> {
+        if self.use_keyring {
+            if let Ok(secret) = self.entry(integration).and_then(|entry| entry.get_password()) {
+                return Ok(Some(secret));
+            }
+        }
+        if !self.file_path().exists() {
+            return Ok(None);
+        }
+        Ok(self.load_file()?.remove(integration.key()))
+    }
+
+    /// Stores a token in the keyring, or in the encrypted file when the keyring can't be used
+    pub fn set(&self, integration: Integration, secret: &str) -> io::Result<Location> {
+        let reason = if self.use_keyring {
+            match self.entry(integration).and_then(|entry| entry.set_password(secret)) {
+                Ok(()) => {
+                    // An older copy in the file would otherwise turn up if the keyring went away
+                    self.remove_from_file(integration)?;
+                    return Ok(Location::Keyring);
+                }
+                Err(e) => e.to_string(),
+            }
+        } else {
+            "the keyring is turned off".to_string()
+        };
+        let mut secrets = if self.file_path().exists() { self.load_file()? } else { BTreeMap::new() };
+        secrets.insert(integration.key().to_string(), secret.to_string());
+        self.save_file(&secrets)?;
+        Ok(Location::File { path: self.file_path(), reason })
+    }
+
+    /// Forgets a token wherever it is stored, returning whether there was one
+    pub fn remove(&self, integration: Integration) -> io::Result<bool> {
+        let in_keyring = self.use_keyring && self.entry(integration).and_then(|entry| entry.delete_credential()).is_ok();
+        let in_file = self.remove_from_file(integration)?;
+        Ok(in_keyring || in_file)
+    }
+
+    fn remove_from_file(&self, integration: Integration) -> io::Result<bool> {
+        if !self.file_path().exists() {
+            return Ok(false);
+        }
+        let mut secrets = self.load_file()?;
+        if secrets.remove(integration.key()).is_none() {
+            return Ok(false);
+        }
+        self.save_file(&secrets)?;
+        Ok(true)
+    }
+
+    /// The file's key, from the passphrase in the environment or else the key file
+    fn file_key(&self, salt: &[u8], create: bool) -> io::Result<LessSafeKey> {
+        let secret = match std::env::var(PASSPHRASE_VAR) {
+            Ok(passphrase) if !passphrase.is_empty() => passphrase.into_bytes(),
+            _ => {
+                let path = self.dir.join(KEY_FILE);
+                if !path.exists() && create {
+                    let mut key = [0; 32];
+                    SystemRandom::new().fill(&mut key).map_err(|_| io::Error::other("Couldn't generate a key"))?;
+                    write_private(&path, STANDARD.encode(key).as_bytes())?;
+                }
+                fs::read(&path).map_err(|e| io::Error::new(e.kind(), format!("Can't read the key for {}: {}", SECRETS_FILE, e)))?
+            }
+        };
+        let mut key = [0; 32];
+        let rounds = NonZeroU32::new(PBKDF2_ROUNDS).unwrap_or(NonZeroU32::MIN);
+        ring::pbkdf2::derive(ring::pbkdf2::PBKDF2_HMAC_SHA256, rounds, salt, &secret, &mut key);
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &key).map_err(|_| io::Error::other("Invalid key"))?;
+        Ok(LessSafeKey::new(key))
+    }
+
+    fn load_file(&self) -> io::Result<BTreeMap<String, String>> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{} {}", SECRETS_FILE, message));
+        let file: EncryptedFile = serde_json::from_str(&fs::read_to_string(self.file_path())?)?;
+        let decode = |text: &str| STANDARD.decode(text).map_err(|_| invalid("is damaged."));
+        let (salt, nonce, mut data) = (decode(&file.salt)?, decode(&file.nonce)?, decode(&file.ciphertext)?);
+        let nonce = Nonce::try_assume_unique_for_key(&nonce).map_err(|_| invalid("is damaged."))?;
+        let plain = self
+            .file_key(&salt, false)?
+            .open_in_place(nonce, Aad::empty(), &mut data)
+            .map_err(|_| invalid(&format!("can't be decrypted; check {} or the key file.", PASSPHRASE_VAR)))?;
+        Ok(serde_json::from_slice(plain)?)
+    }
+
+    fn save_file(&self, secrets: &BTreeMap<String, String>) -> io::Result<()> {
+        let rng = SystemRandom::new();
+        let (mut salt, mut nonce) = ([0; 16], [0; NONCE_LEN]);
+        rng.fill(&mut salt).and_then(|_| rng.fill(&mut nonce)).map_err(|_| io::Error::other("Couldn't generate a nonce"))?;
+        let mut data = serde_json::to_vec(secrets)?;
+        self.file_key(&salt, true)?
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+            .map_err(|_| io::Error::other("Couldn't encrypt the tokens"))?;
+        let file = EncryptedFile { salt: STANDARD.encode(salt), nonce: STANDARD.encode(nonce), ciphertext: STANDARD.encode(data) };
+        write_private(&self.file_path(), serde_json::to_string_pretty(&file)?.as_bytes())
+    }
+}
+
+/// Writes a file only its owner can read
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents)
+}
+
+// The store the integrations look tokens up in, set once the configuration is found
+static STORE: RwLock<Option<SecretStore>> = RwLock::new(None);
+
+/// Sets where tokens left out of the configuration are looked up from now on
+pub fn configure(store: SecretStore) {
+    *STORE.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(store);
+}
+
+/// The stored token for an integration, if any. A store that can't be read is warned about
+/// rather than failing, as the request then fails with the service's own explanation.
+pub fn lookup(integration: Integration) -> Option<String> {
+    let store = STORE.read().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()?;
+    store.get(integration).unwrap_or_else(|e| {
+        eprintln!("Warning: Couldn't read the stored {} token: {}", integration, e);
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_secrets_file() {
+        let dir = tempdir().unwrap();
+        let store = SecretStore::file_only(dir.path().to_path_buf());
+        assert_eq!(store.get(Integration::Matrix).unwrap(), None);
+
+        let location = store.set(Integration::Matrix, "syt_abc").unwrap();
+        assert!(matches!(location, Location::File { ref path, .. } if *path == dir.path().join(SECRETS_FILE)));
+        store.set(Integration::Gotify, "AbCdEf").unwrap();
+        assert_eq!(store.get(Integration::Matrix).unwrap().as_deref(), Some("syt_abc"));
+        assert!(!fs::read_to_string(store.file_path()).unwrap().contains("syt_abc"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(dir.path().join(KEY_FILE)).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        assert!(store.remove(Integration::Matrix).unwrap());
+        assert!(!store.remove(Integration::Matrix).unwrap());
+        assert_eq!(store.get(Integration::Gotify).unwrap().as_deref(), Some("AbCdEf"));
+
+        // Without the key the file can't be read
+        fs::write(dir.path().join(KEY_FILE), "another key").unwrap();
+        assert!(store.get(Integration::Gotify).unwrap_err().to_string().contains("can't be decrypted"));
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+/// How long a client has to send its request before the connection is dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest request body accepted, which is plenty for a batch of queued edits
+const MAX_BODY: usize = 1 << 20;
+
+/// Longest request line or header read, so a client can't have a line buffered without end
+const MAX_LINE: u64 = 8 * 1024;
+
+/// Most headers read from one request
+const MAX_HEADERS: usize = 100;
+
+/// The parts of an HTTP request the server looks at
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: BTreeMap<String, String>,
+    /// Header names are lowercased
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+impl Request {
+    /// Parses a request line such as `GET /today/speech?day=tomorrow HTTP/1.1`
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let method = parts.next()?.to_uppercase();
+        let target = parts.next()?;
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (percent_decode(key), percent_decode(value))
+            })
+            .collect();
+        let path = match path.trim_end_matches('/') {
+            "" => "/".to_string(),
+            path => path.to_string(),
+        };
+        Some(Self { method, path, query, headers: BTreeMap::new(), body: String::new() })
+    }
+
+    /// Reads a header line such as `Content-Type: text/plain` into the request
+    fn add_header(&mut self, line: &str) {
+        if let Some((name, value)) = line.split_once(':') {
+            self.headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    /// The media type the body was sent as, without parameters such as the charset
+    pub fn content_type(&self) -> &str {
+        self.headers.get("content-type").map_or("", |value| value.split(';').next().unwrap_or_default().trim())
+    }
+}
+
+/// A response to send back, always closing the connection after it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        Self { status, content_type: "text/plain; charset=utf-8", body: body.into() }
+    }
+
+    pub fn json(status: u16, value: &serde_json::Value) -> Self {
+        Self { status, content_type: "application/json", body: value.to_string() }
+    }
+
+    pub fn not_found() -> Self {
+        Self::text(404, "Not found\n")
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            415 => "Unsupported Media Type",
+            431 => "Request Header Fields Too Large",
+            _ => "Internal Server Error",
+        }
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        write!(
+            out,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            self.reason(),
+            self.content_type,
+            self.body.len(),
+            self.body
+        )?;
+        out.flush()
+    }
+}
+
+/// Answers requests one at a time until the process is stopped. GET, HEAD and POST are
+/// served; a connection that fails is logged and dropped.
+pub fn serve<F: Fn(&Request) -> Response>(listener: TcpListener, handler: F) -> std::io::Result<()> {
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| handle_connection(stream, &handler));
+        if let Err(e) = result {
+            eprintln!("Failed to answer a request: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn handle_connection<F: Fn(&Request) -> Response>(stream: TcpStream, handler: &F) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    let response = match read_head(&mut reader) {
+        Err(response) => response,
+        Ok(request) if request.method == "GET" => handler(&request),
+        Ok(request) if request.method == "HEAD" => Response { body: String::new(), ..handler(&request) },
+        Ok(mut request) if request.method == "POST" => match read_body(&mut reader, &request) {
+            Ok(body) => {
+                request.body = body;
+                handler(&request)
+            }
+            Err(response) => response,
+        },
+        Ok(_) => Response::text(405, "Only GET and POST are supported\n"),
+    };
+    response.write_to(&mut writer)
+}
+
+/// Reads the request line and headers, refusing lines longer than `MAX_LINE` and more
+/// than `MAX_HEADERS` headers
+fn read_head<R: BufRead>(reader: &mut R) -> Result<Request, Response> {
+    let mut request = Request::parse(&read_head_line(reader)?).ok_or_else(|| Response::text(400, "Bad request\n"))?;
+    let mut headers = 0;
+    loop {
+        let header = read_head_line(reader)?;
+        if header.trim().is_empty() {
+            return Ok(request);
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Err(Response::text(431, "The request has too many headers\n"));
+        }
+        request.add_header(&header);
+    }
+}
+
+/// Reads one line of the request head, which is empty once the client stops sending
+fn read_head_line<R: BufRead>(reader: &mut R) -> Result<String, Response> {
+    let mut line = String::new();
+    reader.by_ref().take(MAX_LINE).read_line(&mut line)
+        .map_err(|_| Response::text(400, "Bad request\n"))?;
+    if line.len() as u64 >= MAX_LINE && !line.ends_with('\n') {
+        return Err(Response::text(431, "A request line or header is too long\n"));
+    }
+    Ok(line)
+}
+
+/// Reads the body of a request, as long as its Content-Length says
+fn read_body<R: Read>(reader: &mut R, request: &Request) -> Result<String, Response> {
+    let length = match request.headers.get("content-length").map(|length| length.parse::<usize>()) {
+        None => 0,
+        Some(Ok(length)) if length <= MAX_BODY => length,
+        Some(Ok(_)) => return Err(Response::text(413, "The request is too large\n")),
+        Some(Err(_)) => return Err(Response::text(400, "Bad Content-Length\n")),
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|_| Response::text(400, "The request ended early\n"))?;
+    String::from_utf8(body).map_err(|_| Response::text(400, "The request body isn't UTF-8\n"))
+}
+
+/// Decodes %XX escapes and + as a space in a query string part
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match s.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
+                }
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        let request = Request::parse("GET /today/speech/?day=next%20friday&x HTTP/1.1\r\n").unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, "/today/speech");
+        assert_eq!(request.query.get("day").map(String::as_str), Some("next friday"));
+        assert_eq!(request.query.get("x").map(String::as_str), Some(""));
+        assert_eq!(Request::parse("GET / HTTP/1.1").unwrap().path, "/");
+        assert!(Request::parse("").is_none());
+    }
+
+    #[test]
+    fn test_read_head_limits() {
+        let read = |head: String| read_head(&mut std::io::Cursor::new(head.into_bytes()));
+        let request = read("GET / HTTP/1.1\r\nHost: localhost\r\n\r\nbody".to_string()).unwrap();
+        assert_eq!(request.headers.get("host").map(String::as_str), Some("localhost"));
+
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE as usize));
+        assert_eq!(read(long_line).unwrap_err().status, 431);
+        let long_header = format!("GET / HTTP/1.1\r\nX-Pad: {}\r\n\r\n", "a".repeat(MAX_LINE as usize));
+        assert_eq!(read(long_header).unwrap_err().status, 431);
+        let many_headers = format!("GET / HTTP/1.1\r\n{}\r\n", "X-Pad: a\r\n".repeat(MAX_HEADERS + 1));
+        assert_eq!(read(many_headers).unwrap_err().status, 431);
+        assert!(read(format!("GET / HTTP/1.1\r\n{}\r\n", "X-Pad: a\r\n".repeat(MAX_HEADERS))).is_ok());
+        assert_eq!(read(String::new()).unwrap_err().status, 400);
+    }
+
+    #[test]
+    fn test_serve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            serve(listener, |request| match request.path.as_str() {
+                "/hello" => Response::text(200, format!("Hello {}", request.query.get("name").cloned().unwrap_or_default())),
+                "/echo" => Response::text(200, format!("{} {}", request.content_type(), request.body)),
+                _ => Response::not_found(),
+            })
+        });
+
+        let get = |request: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let response = get("GET /hello?name=Sam HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Length: 9\r\n"));
+        assert!(response.ends_with("\r\n\r\nHello Sam"));
+        assert!(get("GET /nope HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(get("PUT /hello HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+
+        let response = get("POST /echo HTTP/1.1\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 5\r\n\r\nhello");
+        assert!(response.ends_with("\r\n\r\ntext/plain hello"), "{}", response);
+        assert!(get("POST /echo HTTP/1.1\r\nContent-Length: 99999999\r\n\r\n").starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+    }
+}
@@ -0,0 +1,532 @@
+use crate::days;
+use crate::models::{Config, MealType};
+use crate::solver::RuleKind;
+use crate::sheets::ServiceAccountKey;
+use chrono::NaiveDate;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Prefix of the environment variables that override configuration keys
+pub const ENV_PREFIX: &str = "MEALPLAN_";
+
+/// Shorter names accepted for configuration keys in environment variables
+const ENV_ALIASES: [(&str, &str); 2] = [
+    ("STORAGE_PATH", "meal_plan_storage_path"),
+    ("WEEK_START", "current_week_start_date"),
+];
+
+/// The directory holding the configuration: `~/.config/mealplan`, or a directory of its own
+/// under `profiles` for a named profile so households don't share files
+pub fn config_dir(profile: Option<&str>) -> Result<PathBuf, String> {
+    let base = dirs::home_dir()
+        .ok_or_else(|| "Could not determine home directory".to_string())?
+        .join(".config")
+        .join("mealplan");
+    match profile {
+        None => Ok(base),
+        Some(name) => {
+            validate_profile_name(name)?;
+            Ok(base.join("profiles").join(name))
+        }
+    }
+}
+
+/// The directory a configuration file's plan is stored in when it doesn't say: the one
+/// holding the file, made absolute so it still works when saved in the configuration
+pub fn storage_dir_for(config_path: &Path) -> PathBuf {
+    let dir = match config_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// Profile names become directory names, so only plain names are allowed
+pub fn validate_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!(
+            "Invalid profile name {:?}. Use letters, digits, '-' and '_'.",
+            name
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the configuration key an environment variable overrides, if any, such as
+/// `backup_count` for `MEALPLAN_BACKUP_COUNT`
+fn env_key(variable: &str, keys: &[String]) -> Option<String> {
+    let name = variable.strip_prefix(ENV_PREFIX)?;
+    if let Some((_, key)) = ENV_ALIASES.iter().find(|(alias, _)| *alias == name) {
+        return Some(key.to_string());
+    }
+    keys.iter().find(|key| key.to_uppercase() == name).cloned()
+}
+
+/// Applies `MEALPLAN_<KEY>` environment variables on top of a configuration. Text settings
+/// take the variable as written; others take JSON, such as `5` or `["Alice", "Bob"]`, with
+/// plain text also accepted for unset optional settings.
+pub fn apply_env_overrides<I>(config: &Config, variables: I) -> Result<Config, String>
+where
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut value = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    let Value::Object(fields) = &mut value else {
+        return Err("The configuration isn't a JSON object.".to_string());
+    };
+    let keys: Vec<String> = fields.keys().cloned().collect();
+
+    let mut overrides: Vec<(String, String, String)> = variables
+        .into_iter()
+        .filter_map(|(variable, text)| Some((env_key(&variable, &keys)?, variable, text)))
+        .collect();
+    // Applied in a fixed order so an alias and the full name don't race
+    overrides.sort();
+
+    for (key, variable, text) in overrides {
+        let current = &fields[&key];
+        let parsed = match current {
+            Value::String(_) => Value::String(text),
+            Value::Null => serde_json::from_str(&text).unwrap_or(Value::String(text)),
+            _ => serde_json::from_str(&text).map_err(|e| format!("{}: {}", variable, e))?,
+        };
+        fields.insert(key, parsed);
+        // Checked one variable at a time so an error names the one at fault
+        serde_json::from_value::<Config>(Value::Object(fields.clone()))
+            .map_err(|e| format!("{}: {}", variable, e))?;
+    }
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+/// Keys a cook in the roster may have
+const COOK_KEYS: [&str; 5] = ["name", "email", "preferences", "unavailable_days", "prefs"];
+
+/// How far the week start date may be from today before it looks like a mistake
+const WEEK_START_TOLERANCE_DAYS: i64 = 366;
+
+/// Longest removed meals may be kept in the trash, well short of dates running out
+const MAX_TRASH_RETENTION_DAYS: u32 = 36500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Something wrong with a configuration file, and the key it is about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        if self.key.is_empty() {
+            write!(f, "{}: {}", severity, self.message)
+        } else {
+            write!(f, "{}: {}: {}", severity, self.key, self.message)
+        }
+    }
+}
+
+/// What a configuration file is checked against besides its own contents
+pub struct ValidationContext {
+    pub today: NaiveDate,
+    /// Service account key used for pushing to Google Sheets, if one is set up
+    pub google_credentials: Option<PathBuf>,
+}
+
+impl ValidationContext {
+    /// Checks against today's date and the credentials set in the environment
+    pub fn current() -> Self {
+        Self {
+            today: chrono::Local::now().date_naive(),
+            google_credentials: std::env::var_os("GOOGLE_APPLICATION_CREDENTIALS").map(PathBuf::from),
+        }
+    }
+}
+
+/// Checks the text of a configuration file, returning its problems with the errors first
+pub fn validate_config(contents: &str, context: &ValidationContext) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut issue = |severity: Severity, key: &str, message: String| {
+        issues.push(Issue { severity, key: key.to_string(), message });
+    };
+
+    let value: Value = match serde_json::from_str(contents) {
+        Ok(value) => value,
+        Err(e) => {
+            issue(Severity::Error, "", format!("The file isn't valid JSON: {}.", e));
+            return issues;
+        }
+    };
+    let Some(fields) = value.as_object() else {
+        issue(Severity::Error, "", "The configuration must be a JSON object.".to_string());
+        return issues;
+    };
+
+    let known: Vec<String> = match serde_json::to_value(Config::new()) {
+        Ok(Value::Object(defaults)) => defaults.keys().cloned().collect(),
+        _ => Vec::new(),
+    };
+    for key in fields.keys() {
+        if known.contains(key) {
+            continue;
+        }
+        let hint = closest(key, &known).map(|k| format!(" Did you mean {}?", k)).unwrap_or_default();
+        issue(Severity::Warning, key, format!("Unknown key; it is ignored.{}", hint));
+    }
+    for (n, cook) in fields.get("cooks").and_then(Value::as_array).into_iter().flatten().enumerate() {
+        for key in cook.as_object().into_iter().flat_map(|c| c.keys()) {
+            if !COOK_KEYS.contains(&key.as_str()) {
+                issue(Severity::Warning, &format!("cooks[{}].{}", n, key), "Unknown key; it is ignored.".to_string());
+            }
+        }
+    }
+
+    let config: Config = match serde_json::from_value(value.clone()) {
+        Ok(config) => config,
+        Err(e) => {
+            // Name the key at fault by checking each one against the defaults
+            let mut blamed = false;
+            if let Ok(Value::Object(defaults)) = serde_json::to_value(Config::new()) {
+                for (key, field) in fields {
+                    let mut single = defaults.clone();
+                    single.insert(key.clone(), field.clone());
+                    if let Err(e) = serde_json::from_value::<Config>(Value::Object(single)) {
+                        issue(Severity::Error, key, format!("{}.", e));
+                        blamed = true;
+                    }
+                }
+            }
+            if !blamed {
+                issue(Severity::Error, "", format!("{}.", e));
+            }
+            issues.sort_by_key(|issue| issue.severity);
+            return issues;
+        }
+    };
+
+    let days_off = (config.current_week_start_date - context.today).num_days();
+    if days_off.abs() > WEEK_START_TOLERANCE_DAYS {
+        issue(
+            Severity::Warning,
+            "current_week_start_date",
+            format!("{} is more than a year from today; check the year.", config.current_week_start_date),
+        );
+    }
+
+    let storage = &config.meal_plan_storage_path;
+    if storage.is_relative() {
+        issue(Severity::Warning, "meal_plan_storage_path", format!("{:?} is relative, so it depends on the directory mealplan is run from.", storage));
+    }
+    if storage.is_file() {
+        issue(Severity::Error, "meal_plan_storage_path", format!("{:?} is a file, not a directory.", storage));
+    } else if storage.is_dir() {
+        if let Err(e) = check_writable(storage) {
+            issue(Severity::Error, "meal_plan_storage_path", format!("{:?} can't be written to: {}.", storage, e));
+        }
+    } else {
+        issue(Severity::Warning, "meal_plan_storage_path", format!("{:?} doesn't exist yet; it will be created.", storage));
+    }
+
+    let mut names = BTreeSet::new();
+    for cook in &config.cooks {
+        if cook.name.trim().is_empty() {
+            issue(Severity::Error, "cooks", "A cook has no name.".to_string());
+        } else if !names.insert(cook.name.to_lowercase()) {
+            issue(Severity::Error, "cooks", format!("{} is listed more than once.", cook.name));
+        }
+        if let Some(email) = &cook.email {
+            if !email.contains('@') {
+                issue(Severity::Warning, "cooks", format!("{}'s email {:?} doesn't look like an address.", cook.name, email));
+            }
+        }
+    }
+    for name in &config.cook_rotation {
+        if !config.is_known_cook(name) {
+            issue(
+                Severity::Error,
+                "cook_rotation",
+                format!("{} isn't one of the cooks. Add them with 'mealplan cooks add \"{}\"' or take them out of the rotation.", name, name),
+            );
+        }
+    }
+    for rule in &config.plan_rules {
+        if let RuleKind::MaxMeals { cook, .. } = &rule.kind {
+            if !config.is_known_cook(cook) {
+                issue(Severity::Warning, "plan_rules", format!("The rule \"{}\" names {}, who isn't one of the cooks.", rule, cook));
+            }
+        }
+    }
+    for (name, alias) in &config.aliases {
+        if alias.description.trim().is_empty() {
+            issue(Severity::Error, "aliases", format!("The alias {} has no description.", name));
+        }
+    }
+    let meal_types = [MealType::Breakfast, MealType::Lunch, MealType::Snack, MealType::Dinner];
+    for meal_type in config.default_cooks.keys() {
+        let key = format!("default_cooks.{}", meal_type);
+        let Some(parsed) = meal_types.iter().find(|t| t.to_string().eq_ignore_ascii_case(meal_type.trim())) else {
+            issue(Severity::Error, &key, "Not a meal type; use breakfast, lunch, dinner or snack.".to_string());
+            continue;
+        };
+        match config.default_cook(parsed).as_deref() {
+            None => issue(Severity::Warning, &key, "No cook is given; meals need a cook passed to --cook.".to_string()),
+            Some("auto") if config.rotation().is_empty() => {
+                issue(Severity::Error, &key, "Cooks can't take turns without any cooks. Add them with 'mealplan cooks add <name>'.".to_string())
+            }
+            Some("auto") => {}
+            Some(name) if !config.is_known_cook(name) => {
+                issue(Severity::Error, &key, format!("{} isn't one of the cooks. Add them with 'mealplan cooks add \"{}\"'.", name, name))
+            }
+            Some(_) => {}
+        }
+    }
+    for (day, meals) in &config.day_templates {
+        if days::parse_weekday(day).is_none() {
+            issue(Severity::Error, &format!("day_templates.{}", day), "Not a day of the week.".to_string());
+            continue;
+        }
+        for (meal_type, description) in meals {
+            let key = format!("day_templates.{}.{}", day, meal_type);
+            if !meal_types.iter().any(|t| t.to_string().eq_ignore_ascii_case(meal_type.trim())) {
+                issue(Severity::Error, &key, "Not a meal type; use breakfast, lunch, dinner or snack.".to_string());
+            } else if description.trim().is_empty() {
+                issue(Severity::Error, &key, "The meal has no description.".to_string());
+            }
+        }
+    }
+    if config.weekly_budget.is_some_and(|budget| budget < 0.0) {
+        issue(Severity::Error, "weekly_budget", "The budget can't be negative.".to_string());
+    }
+    if config.monthly_takeout_budget.is_some_and(|budget| budget < 0.0) {
+        issue(Severity::Error, "monthly_takeout_budget", "The budget can't be negative.".to_string());
+    }
+    if config.household_size == Some(0) {
+        issue(Severity::Error, "household_size", "The household needs at least one person.".to_string());
+    }
+    if config.trash_retention_days > MAX_TRASH_RETENTION_DAYS {
+        issue(
+            Severity::Error,
+            "trash_retention_days",
+            format!("Removed meals can be kept for at most {} days (100 years).", MAX_TRASH_RETENTION_DAYS),
+        );
+    }
+    for (appliance, capacity) in &config.appliances {
+        if *capacity == 0 {
+            issue(Severity::Error, &format!("appliances.{}", appliance), "An appliance takes at least one dish at a time.".to_string());
+        }
+    }
+    for target in &config.notify {
+        let server = target.server();
+        if !(server.starts_with("https://") || server.starts_with("http://")) {
+            issue(Severity::Error, "notify", format!("The server {:?} isn't a web address; it should start with https://.", server));
+        }
+    }
+    if let Some(matrix) = &config.matrix {
+        if !(matrix.homeserver.starts_with("https://") || matrix.homeserver.starts_with("http://")) {
+            issue(Severity::Error, "matrix", format!("The homeserver {:?} isn't a web address; it should start with https://.", matrix.homeserver));
+        }
+        if !matrix.room_id.starts_with('!') {
+            issue(Severity::Error, "matrix", format!("{:?} isn't a room ID; find it in the room's settings, e.g. !abcdef:example.org.", matrix.room_id));
+        }
+        if let Err(e) = matrix.post_time() {
+            issue(Severity::Error, "matrix", e);
+        }
+    }
+    if let Some(email) = &config.email {
+        if email.from.parse::<lettre::message::Mailbox>().is_err() {
+            issue(Severity::Error, "email.from", format!("{:?} isn't an email address; write it as e.g. Meal Plan <mealplan@example.com>.", email.from));
+        }
+    }
+    if config.http.timeout_secs == 0 {
+        issue(Severity::Error, "http.timeout_secs", "Requests need at least a second to get an answer.".to_string());
+    }
+    if config.backup_count == 0 {
+        issue(Severity::Warning, "backup_count", "No backups are kept, so a bad save can't be undone.".to_string());
+    }
+
+    if let Some(path) = &context.google_credentials {
+        match ServiceAccountKey::load(path) {
+            Ok(key) if !key.private_key.contains("PRIVATE KEY") => issue(
+                Severity::Error,
+                "GOOGLE_APPLICATION_CREDENTIALS",
+                format!("{:?} has no private key in PEM format.", path),
+            ),
+            Ok(_) => {}
+            Err(e) => issue(
+                Severity::Error,
+                "GOOGLE_APPLICATION_CREDENTIALS",
+                format!("{:?} isn't a readable service account key: {}. Download a new key from the Google Cloud console.", path, e),
+            ),
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.severity);
+    issues
+}
+
+/// Checks a directory can be written to by creating and removing a file in it
+fn check_writable(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(format!(".mealplan-write-check-{}", std::process::id()));
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
+}
+
+/// Finds the known key an unknown one is most likely a typo of
+fn closest<'a>(key: &str, known: &'a [String]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|k| (edit_distance(key, k), k))
+        .filter(|(distance, k)| *distance <= 3 && *distance < k.len() / 2)
+        .min()
+        .map(|(_, k)| k.as_str())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = (previous + usize::from(ca != *cb)).min(row[j] + 1).min(current + 1);
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_apply_env_overrides() {
+        let config = Config::new();
+        let updated = apply_env_overrides(&config, vars(&[
+            ("MEALPLAN_STORAGE_PATH", "/srv/meals"),
+            ("MEALPLAN_BACKUP_COUNT", "2"),
+            ("MEALPLAN_USER_NAME", "alice"),
+            ("MEALPLAN_COOK_ROTATION", r#"["Alice", "Bob"]"#),
+            ("MEALPLAN_CURRENT_WEEK_START_DATE", "2023-01-02"),
+            ("MEALPLAN_PROFILE", "cabin"),
+            ("HOME", "/root"),
+        ])).unwrap();
+
+        assert_eq!(updated.meal_plan_storage_path, PathBuf::from("/srv/meals"));
+        assert_eq!(updated.backup_count, 2);
+        assert_eq!(updated.user_name.as_deref(), Some("alice"));
+        assert_eq!(updated.cook_rotation, vec!["Alice", "Bob"]);
+        assert_eq!(updated.current_week_start_date, NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        // Settings without a variable are kept
+        assert_eq!(updated.trash_retention_days, config.trash_retention_days);
+    }
+
+    #[test]
+    fn test_apply_env_overrides_errors() {
+        let config = Config::new();
+        let error = apply_env_overrides(&config, vars(&[("MEALPLAN_BACKUP_COUNT", "lots")])).unwrap_err();
+        assert!(error.starts_with("MEALPLAN_BACKUP_COUNT: "), "{}", error);
+        let error = apply_env_overrides(&config, vars(&[("MEALPLAN_WEEK_START", "next monday")])).unwrap_err();
+        assert!(error.starts_with("MEALPLAN_WEEK_START: "), "{}", error);
+    }
+
+    #[test]
+    fn test_profile_names() {
+        assert!(validate_profile_name("cabin-2").is_ok());
+        assert!(validate_profile_name("../home").is_err());
+        assert!(validate_profile_name("").is_err());
+        assert!(config_dir(Some("cabin")).unwrap().ends_with("mealplan/profiles/cabin"));
+        assert_eq!(storage_dir_for(Path::new("/srv/meals/cabin.json")), PathBuf::from("/srv/meals"));
+        assert_eq!(storage_dir_for(Path::new("cabin.json")), std::path::absolute(".").unwrap());
+    }
+
+    fn context() -> ValidationContext {
+        ValidationContext { today: NaiveDate::from_ymd_opt(2023, 1, 2).unwrap(), google_credentials: None }
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let dir = tempdir().unwrap();
+        let contents = serde_json::json!({
+            "meal_plan_storage_path": dir.path(),
+            "current_week_start_date": "2023-01-02",
+            "backup_count": 3,
+            "cooks": [{"name": "Alice"}, {"name": "alice", "email": "alice"}, {"name": "Bob", "colour": "red"}],
+            "cook_rotation": ["Alice", "Zed"],
+            "plan_rules": [{"rule": "max_meals", "cook": "Zed", "count": 2}],
+            "default_cooks": {"breakfast": "alice", "brunch": "Bob", "dinner": "rotate", "lunch": "Zed"},
+            "day_templates": {"Saturday": {"breakfast": "Pancakes", "tea": "Scones"}, "fri": {"dinner": " "}, "payday": {"dinner": "Steak"}},
+            "notify": [{"service": "ntfy", "topic": "dinner"}, {"service": "gotify", "server": "push.example.com", "token": "abc"}],
+            "matrix": {"homeserver": "https://matrix.example.org", "room_id": "#kitchen:example.org", "access_token": "abc", "post_at": "7am"},
+            "email": {"smtp_host": "smtp.example.com", "from": "mealplan"},
+            "http": {"timeout_secs": 0, "retries": 3},
+            "trash_retention_days": 4000000000u64,
+            "appliances": {"oven": 1, "air fryer": 0},
+            "weekly_budgt": 80,
+        }).to_string();
+        let issues: Vec<String> = validate_config(&contents, &context()).iter().map(Issue::to_string).collect();
+        assert_eq!(issues, vec![
+            "error: cooks: alice is listed more than once.",
+            "error: cook_rotation: Zed isn't one of the cooks. Add them with 'mealplan cooks add \"Zed\"' or take them out of the rotation.",
+            "error: default_cooks.brunch: Not a meal type; use breakfast, lunch, dinner or snack.",
+            "error: default_cooks.lunch: Zed isn't one of the cooks. Add them with 'mealplan cooks add \"Zed\"'.",
+            "error: day_templates.Saturday.tea: Not a meal type; use breakfast, lunch, dinner or snack.",
+            "error: day_templates.fri.dinner: The meal has no description.",
+            "error: day_templates.payday: Not a day of the week.",
+            "error: trash_retention_days: Removed meals can be kept for at most 36500 days (100 years).",
+            "error: appliances.air fryer: An appliance takes at least one dish at a time.",
+            "error: notify: The server \"push.example.com\" isn't a web address; it should start with https://.",
+            "error: matrix: \"#kitchen:example.org\" isn't a room ID; find it in the room's settings, e.g. !abcdef:example.org.",
+            "error: matrix: The time \"7am\" should be written as HH:MM, e.g. 07:00.",
+            "error: email.from: \"mealplan\" isn't an email address; write it as e.g. Meal Plan <mealplan@example.com>.",
+            "error: http.timeout_secs: Requests need at least a second to get an answer.",
+            "warning: weekly_budgt: Unknown key; it is ignored. Did you mean weekly_budget?",
+            "warning: cooks[2].colour: Unknown key; it is ignored.",
+            "warning: cooks: alice's email \"alice\" doesn't look like an address.",
+            "warning: plan_rules: The rule \"Zed cooks at most 2 meal(s)\" names Zed, who isn't one of the cooks.",
+        ]);
+    }
+
+    #[test]
+    fn test_validate_config_bad_values() {
+        let issues = validate_config("{\"meal_plan_storage_path\": \"/tmp\", \"current_week_start_date\": \"2023-13-01\", \"backup_count\": -1}", &context());
+        let keys: Vec<&str> = issues.iter().map(|i| i.key.as_str()).collect();
+        assert_eq!(keys, vec!["backup_count", "current_week_start_date"]);
+
+        let issues = validate_config("{\"meal_plan_storage_path\": \"/tmp\", \"current_week_start_date\": \"2023-01-02\", \"household_size\": 0}", &context());
+        assert!(issues.iter().any(|i| i.severity == Severity::Error && i.key == "household_size"));
+        assert!(issues.iter().all(|i| i.severity == Severity::Error));
+
+        let issues = validate_config("{\"meal_plan_storage_path\": ", &context());
+        assert!(issues[0].message.starts_with("The file isn't valid JSON"));
+
+        let dir = tempdir().unwrap();
+        let key = dir.path().join("key.json");
+        std::fs::write(&key, "{\"client_email\": \"a@b.c\"}").unwrap();
+        let contents = serde_json::json!({"meal_plan_storage_path": dir.path().join("new"), "current_week_start_date": "2025-01-06"}).to_string();
+        let context = ValidationContext { google_credentials: Some(key), ..context() };
+        let issues = validate_config(&contents, &context);
+        let found: Vec<(Severity, &str)> = issues.iter().map(|i| (i.severity, i.key.as_str())).collect();
+        assert_eq!(found, vec![
+            (Severity::Error, "GOOGLE_APPLICATION_CREDENTIALS"),
+            (Severity::Warning, "current_week_start_date"),
+            (Severity::Warning, "meal_plan_storage_path"),
+        ]);
+    }
+}
@@ -0,0 +1,179 @@
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::{DefaultHistory, History};
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+use std::io;
+
+/// Shell commands that aren't mealplan subcommands
+pub const SHELL_COMMANDS: [&str; 6] = [":w", ":q", ":q!", ":wq", "show", "help"];
+
+/// What a line typed at the shell asks for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShellInput {
+    /// Save the plan
+    Write,
+    /// Leave, refusing if there are unsaved changes
+    Quit,
+    /// Leave without saving
+    ForceQuit,
+    /// Save the plan and leave
+    WriteQuit,
+    /// List the plan as it is in the shell
+    Show,
+    Help,
+    /// A mealplan subcommand and its arguments
+    Command(Vec<String>),
+    Empty,
+}
+
+impl ShellInput {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let words = split_words(line)?;
+        Ok(match words.first().map(String::as_str) {
+            None => ShellInput::Empty,
+            Some(":w") if words.len() == 1 => ShellInput::Write,
+            Some(":q") | Some("quit") | Some("exit") if words.len() == 1 => ShellInput::Quit,
+            Some(":q!") if words.len() == 1 => ShellInput::ForceQuit,
+            Some(":wq") | Some(":x") if words.len() == 1 => ShellInput::WriteQuit,
+            Some("show") if words.len() == 1 => ShellInput::Show,
+            Some("help") | Some("?") if words.len() == 1 => ShellInput::Help,
+            Some(_) => ShellInput::Command(words),
+        })
+    }
+}
+
+/// Splits a line into words like a POSIX shell would: quotes group words and a backslash
+/// escapes the next character
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                let escaped = chars.next().ok_or("The line ends with a backslash.")?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(q) = quote {
+        return Err(format!("Missing closing {} quote.", q));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Completes the first word from the commands and later words from `arguments`
+struct ShellHelper {
+    commands: Vec<String>,
+    arguments: Vec<String>,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let word = line[start..pos].to_lowercase();
+        let candidates = if line[..start].trim().is_empty() { &self.commands } else { &self.arguments };
+        let matches = candidates
+            .iter()
+            .filter(|candidate| candidate.to_lowercase().starts_with(&word))
+            .map(|candidate| if candidate.contains(' ') { format!("\"{}\"", candidate) } else { candidate.clone() })
+            .collect();
+        Ok((start, matches))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// The shell's line editor, which keeps the session's command history
+pub struct ShellEditor {
+    editor: Editor<ShellHelper, DefaultHistory>,
+}
+
+impl ShellEditor {
+    /// Creates the editor, completing `commands` as the first word and `arguments` after it
+    pub fn new(commands: Vec<String>, arguments: Vec<String>) -> io::Result<Self> {
+        let config = Config::builder().completion_type(CompletionType::List).auto_add_history(false).build();
+        let mut editor: Editor<ShellHelper, DefaultHistory> = Editor::with_config(config).map_err(readline_error)?;
+        let mut commands = commands;
+        commands.extend(SHELL_COMMANDS.iter().map(|c| c.to_string()));
+        editor.set_helper(Some(ShellHelper { commands, arguments }));
+        Ok(Self { editor })
+    }
+
+    /// Reads a line, or None at the end of input. Ctrl-C discards the line being typed.
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<Option<String>> {
+        loop {
+            match self.editor.readline(prompt) {
+                Ok(line) => {
+                    if !line.trim().is_empty() {
+                        self.editor.history_mut().add(&line).map_err(readline_error)?;
+                    }
+                    return Ok(Some(line));
+                }
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => return Ok(None),
+                Err(e) => return Err(readline_error(e)),
+            }
+        }
+    }
+}
+
+fn readline_error(error: ReadlineError) -> io::Error {
+    match error {
+        ReadlineError::Io(e) => e,
+        e => io::Error::other(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(
+            split_words(r#"add "Chicken curry" -t dinner -d 'mon' -c Al\ Bo """#).unwrap(),
+            vec!["add", "Chicken curry", "-t", "dinner", "-d", "mon", "-c", "Al Bo", ""]
+        );
+        assert_eq!(split_words(r#"add "say \"hi\"""#).unwrap(), vec!["add", "say \"hi\""]);
+        assert!(split_words("   ").unwrap().is_empty());
+        assert!(split_words("add \"Tacos").is_err());
+    }
+
+    #[test]
+    fn test_shell_input() {
+        assert_eq!(ShellInput::parse(" :wq ").unwrap(), ShellInput::WriteQuit);
+        assert_eq!(ShellInput::parse("exit").unwrap(), ShellInput::Quit);
+        assert_eq!(ShellInput::parse("").unwrap(), ShellInput::Empty);
+        assert_eq!(
+            ShellInput::parse("remove -m dinner -d mon").unwrap(),
+            ShellInput::Command(vec!["remove".to_string(), "-m".to_string(), "dinner".to_string(), "-d".to_string(), "mon".to_string()])
+        );
+        // A word that happens to be a shell command is left alone when it has arguments
+        assert!(matches!(ShellInput::parse("show extra").unwrap(), ShellInput::Command(_)));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::models::{Meal, MealPlan, VectorClock};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// File in the storage path holding the named snapshots
+pub const SNAPSHOTS_FILE: &str = "snapshots.json";
+
+/// The meals of a week as they were at one point, kept under a name to go back to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub taken_at: DateTime<Utc>,
+    pub week_start_date: NaiveDate,
+    pub meals: Vec<Meal>,
+}
+
+/// What restoring a snapshot changed in the plan
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RestoreSummary {
+    pub added: usize,
+    pub changed: usize,
+    pub removed: usize,
+}
+
+/// Named copies of the week to try out other plans with, kept apart from the change log
+/// and backups so going back to one is a single step
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshots {
+    pub snapshots: Vec<Snapshot>,
+}
+
+/// A meal without its edit history, for telling whether two copies plan the same thing
+fn content(meal: &Meal) -> Meal {
+    Meal { updated_by: None, clock: VectorClock::default(), created_at: None, updated_at: None, ..meal.clone() }
+}
+
+impl Snapshots {
+    /// Finds a snapshot by name, ignoring case
+    pub fn find(&self, name: &str) -> Option<&Snapshot> {
+        self.snapshots.iter().find(|snapshot| snapshot.name.eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Takes a snapshot of the plan's meals. A snapshot with the same name is only replaced
+    /// when `replace` is set; returns whether one was.
+    pub fn take(&mut self, name: &str, meal_plan: &MealPlan, at: DateTime<Utc>, replace: bool) -> Result<bool, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("The snapshot needs a name.".to_string());
+        }
+        let replaced = self.find(name).is_some();
+        if replaced && !replace {
+            return Err(format!("There is already a snapshot called \"{}\". Pass --replace to take it again.", name));
+        }
+        self.remove(name);
+        self.snapshots.push(Snapshot {
+            name: name.to_string(),
+            taken_at: at,
+            week_start_date: meal_plan.week_start_date,
+            meals: meal_plan.meals().to_vec(),
+        });
+        Ok(replaced)
+    }
+
+    /// Deletes a snapshot, returning false if there is none by that name
+    pub fn remove(&mut self, name: &str) -> bool {
+        let before = self.snapshots.len();
+        self.snapshots.retain(|snapshot| !snapshot.name.eq_ignore_ascii_case(name.trim()));
+        self.snapshots.len() < before
+    }
+
+    /// Saves the snapshots to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the snapshots from a JSON file, which is empty until the first one is taken
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+/// Puts the plan's meals back the way they were in a snapshot of the same week. Restoring is
+/// recorded as ordinary edits, so it syncs to other devices and shows in the change log, and
+/// meals the snapshot doesn't have go to the trash.
+pub fn restore(meal_plan: &mut MealPlan, snapshot: &Snapshot) -> Result<RestoreSummary, String> {
+    if snapshot.week_start_date != meal_plan.week_start_date {
+        return Err(format!(
+            "\"{}\" is a snapshot of the week of {}, not of this week ({}).",
+            snapshot.name, snapshot.week_start_date, meal_plan.week_start_date
+        ));
+    }
+    let mut summary = RestoreSummary::default();
+
+    let dropped: Vec<Meal> = meal_plan
+        .meals()
+        .iter()
+        .filter(|meal| !snapshot.meals.iter().any(|kept| kept.meal_type == meal.meal_type && kept.day == meal.day))
+        .cloned()
+        .collect();
+    for meal in dropped {
+        meal_plan.trash_meal(&meal.meal_type, &meal.day);
+        summary.removed += 1;
+    }
+
+    for meal in &snapshot.meals {
+        match meal_plan.find_meal(&meal.meal_type, &meal.day) {
+            Some(current) if content(current) == content(meal) => {}
+            Some(_) => {
+                meal_plan.add_meal(meal.clone());
+                summary.changed += 1;
+            }
+            None => {
+                meal_plan.add_meal(meal.clone());
+                summary.added += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, MealType};
+    use chrono::Weekday;
+    use tempfile::tempdir;
+
+    fn meal(meal_type: MealType, weekday: Weekday, description: &str) -> Meal {
+        Meal::new(meal_type, Day::Weekday(weekday), "Al".to_string(), description.to_string())
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(SNAPSHOTS_FILE);
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(meal(MealType::Dinner, Weekday::Mon, "Chili"));
+        plan.add_meal(meal(MealType::Dinner, Weekday::Tue, "Tacos"));
+
+        let mut snapshots = Snapshots::load_from_json(&path).unwrap();
+        assert_eq!(snapshots.take("Before guests", &plan, Utc::now(), false), Ok(false));
+        assert!(snapshots.take("before guests", &plan, Utc::now(), false).is_err());
+        assert_eq!(snapshots.take("before guests", &plan, Utc::now(), true), Ok(true));
+        snapshots.save_to_json(&path).unwrap();
+        let snapshots = Snapshots::load_from_json(&path).unwrap();
+        assert_eq!(snapshots.snapshots.len(), 1);
+
+        // Try another plan, then go back
+        plan.add_meal(meal(MealType::Dinner, Weekday::Mon, "Roast"));
+        plan.remove_meal(&MealType::Dinner, &Day::Weekday(Weekday::Tue));
+        plan.add_meal(meal(MealType::Lunch, Weekday::Wed, "Salad"));
+        let summary = restore(&mut plan, snapshots.find("BEFORE GUESTS").unwrap()).unwrap();
+        assert_eq!(summary, RestoreSummary { added: 1, changed: 1, removed: 1 });
+        let mut descriptions: Vec<&str> = plan.meals().iter().map(|meal| meal.description.as_str()).collect();
+        descriptions.sort();
+        assert_eq!(descriptions, vec!["Chili", "Tacos"]);
+        assert_eq!(plan.trash.last().unwrap().meal.description, "Salad");
+        assert_eq!(restore(&mut plan, snapshots.find("before guests").unwrap()).unwrap(), RestoreSummary::default());
+
+        // A snapshot of another week isn't restored over this one
+        let next_week = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 9).unwrap());
+        assert!(restore(&mut next_week.clone(), &snapshots.snapshots[0]).unwrap_err().contains("week of 2023-01-02"));
+    }
+}
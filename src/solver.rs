@@ -0,0 +1,372 @@
+use crate::days::weekday_name;
+use crate::models::CookPrefs;
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// How many partial plans the search tries before settling for the best one found
+const SEARCH_LIMIT: usize = 200_000;
+
+/// A household rule for `mealplan generate`, kept in the configuration's `plan_rules`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanRule {
+    #[serde(flatten)]
+    pub kind: RuleKind,
+    /// A soft rule is broken when the week can't be planned otherwise; a hard rule never is
+    #[serde(default)]
+    pub soft: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum RuleKind {
+    /// Meals with the tag are never planned on two days in a row
+    NotConsecutive { tag: String },
+    /// The cook cooks at most `count` meals in the week, counting every meal type
+    MaxMeals { cook: String, count: usize },
+    /// The meal planned on the day has the tag
+    OnDay { tag: String, day: Weekday },
+}
+
+impl fmt::Display for PlanRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            RuleKind::NotConsecutive { tag } => write!(f, "no {} meals on consecutive days", tag)?,
+            RuleKind::MaxMeals { cook, count } => write!(f, "{} cooks at most {} meal(s)", cook, count)?,
+            RuleKind::OnDay { tag, day } => write!(f, "a {} meal on {}s", tag, weekday_name(*day))?,
+        }
+        if self.soft {
+            write!(f, " (soft)")?;
+        }
+        Ok(())
+    }
+}
+
+/// A meal on a date, either already in the plan or chosen by the solver
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placed {
+    pub date: NaiveDate,
+    pub description: String,
+    pub cook: String,
+    pub tags: Vec<String>,
+}
+
+impl Placed {
+    fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t.trim().eq_ignore_ascii_case(tag.trim()))
+    }
+}
+
+/// A dish the solver may plan, used at most once in the week
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub description: String,
+    pub tags: Vec<String>,
+    /// How long the recipe takes, when its steps say
+    pub minutes: Option<u32>,
+}
+
+/// A cook the solver may assign, with the weekdays they can't cook and what they take on
+#[derive(Debug, Clone, PartialEq)]
+pub struct CookOption {
+    pub name: String,
+    pub unavailable_days: Vec<Weekday>,
+    pub prefs: CookPrefs,
+}
+
+/// The empty slots of one meal type to fill, and what they're filled from
+#[derive(Debug, Clone)]
+pub struct Problem {
+    /// Dates to plan a meal on
+    pub slots: Vec<NaiveDate>,
+    /// Meals of the same type already planned this week
+    pub planned: Vec<Placed>,
+    /// Meals of every type each cook already has this week, keyed by lowercase name
+    pub cook_counts: HashMap<String, usize>,
+    pub candidates: Vec<Candidate>,
+    /// Cooks in the order they take turns
+    pub cooks: Vec<CookOption>,
+    pub rules: Vec<PlanRule>,
+}
+
+/// A meal for every slot, with the soft rules that had to be broken
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    pub meals: Vec<Placed>,
+    pub broken: Vec<PlanRule>,
+}
+
+/// Why no plan was found
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsolvable {
+    /// False when the search gave up before trying every plan
+    pub exhausted: bool,
+    /// Hard rules that would allow a plan if any one of them were soft
+    pub blocking: Vec<PlanRule>,
+}
+
+/// Finds a meal and cook for every slot that keeps every hard rule and breaks as few soft
+/// rules as possible
+pub fn solve(problem: &Problem) -> Result<Solution, Unsolvable> {
+    let mut search = Search::new(problem);
+    search.run(0, 0);
+    if let Some((_, meals)) = search.best {
+        let broken = problem
+            .rules
+            .iter()
+            .filter(|rule| (0..meals.len()).any(|n| breaks(rule, &meals[n], problem, &meals[..n])))
+            .cloned()
+            .collect();
+        return Ok(Solution { meals, broken });
+    }
+
+    let blocking = (0..problem.rules.len())
+        .filter(|&n| !problem.rules[n].soft)
+        .filter(|&n| {
+            let mut relaxed = problem.clone();
+            relaxed.rules[n].soft = true;
+            let mut search = Search::new(&relaxed);
+            search.run(0, 0);
+            search.best.is_some()
+        })
+        .map(|n| problem.rules[n].clone())
+        .collect();
+    Err(Unsolvable { exhausted: search.exhausted, blocking })
+}
+
+/// Whether placing a meal breaks a rule, given the meals placed before it
+fn breaks(rule: &PlanRule, meal: &Placed, problem: &Problem, placed: &[Placed]) -> bool {
+    let others = || problem.planned.iter().chain(placed);
+    match &rule.kind {
+        RuleKind::NotConsecutive { tag } => {
+            meal.has_tag(tag) && others().any(|other| other.has_tag(tag) && (other.date - meal.date).num_days().abs() == 1)
+        }
+        RuleKind::MaxMeals { cook, count } => {
+            if !meal.cook.eq_ignore_ascii_case(cook.trim()) {
+                return false;
+            }
+            let before = problem.cook_counts.get(&cook.trim().to_lowercase()).copied().unwrap_or(0);
+            let assigned = placed.iter().filter(|other| other.cook.eq_ignore_ascii_case(cook.trim())).count();
+            before + assigned + 1 > *count
+        }
+        RuleKind::OnDay { tag, day } => meal.date.weekday() == *day && !meal.has_tag(tag),
+    }
+}
+
+struct Search<'a> {
+    problem: &'a Problem,
+    assigned: Vec<Placed>,
+    /// Fewest soft rules broken so far, with the meals that did it
+    best: Option<(usize, Vec<Placed>)>,
+    tried: usize,
+    exhausted: bool,
+}
+
+impl<'a> Search<'a> {
+    fn new(problem: &'a Problem) -> Self {
+        Self { problem, assigned: Vec::new(), best: None, tried: 0, exhausted: true }
+    }
+
+    fn done(&self) -> bool {
+        matches!(self.best, Some((0, _)))
+    }
+
+    fn run(&mut self, slot: usize, cost: usize) {
+        if self.best.as_ref().is_some_and(|(best, _)| cost >= *best) {
+            return;
+        }
+        if self.tried >= SEARCH_LIMIT {
+            self.exhausted = false;
+            return;
+        }
+        self.tried += 1;
+        let Some(&date) = self.problem.slots.get(slot) else {
+            self.best = Some((cost, self.assigned.clone()));
+            return;
+        };
+
+        for candidate in &self.problem.candidates {
+            let used = self.assigned.iter().any(|meal| meal.description.eq_ignore_ascii_case(&candidate.description));
+            if used {
+                continue;
+            }
+            for cook in self.cooks_for(date, candidate) {
+                let meal = Placed { date, description: candidate.description.clone(), cook, tags: candidate.tags.clone() };
+                let mut extra = 0;
+                let mut allowed = true;
+                for rule in &self.problem.rules {
+                    if breaks(rule, &meal, self.problem, &self.assigned) {
+                        if rule.soft {
+                            extra += 1;
+                        } else {
+                            allowed = false;
+                            break;
+                        }
+                    }
+                }
+                if !allowed {
+                    continue;
+                }
+                self.assigned.push(meal);
+                self.run(slot + 1, cost + extra);
+                self.assigned.pop();
+                if self.done() || !self.exhausted {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Cooks available on the date who take on the dish, those with the fewest meals so
+    /// far first
+    fn cooks_for(&self, date: NaiveDate, candidate: &Candidate) -> Vec<String> {
+        let load = |name: &str| {
+            let before = self.problem.cook_counts.get(&name.to_lowercase()).copied().unwrap_or(0);
+            before + self.assigned.iter().filter(|meal| meal.cook.eq_ignore_ascii_case(name)).count()
+        };
+        let mut cooks: Vec<&CookOption> = self
+            .problem
+            .cooks
+            .iter()
+            .filter(|cook| !cook.unavailable_days.contains(&date.weekday()) && cook.prefs.cooks_on(date.weekday()))
+            .filter(|cook| {
+                cook.prefs.avoided_in(&candidate.description, &candidate.tags).is_none() && !cook.prefs.too_long(candidate.minutes)
+            })
+            .collect();
+        cooks.sort_by_key(|cook| load(&cook.name));
+        cooks.into_iter().map(|cook| cook.name.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2023, 1, day).unwrap()
+    }
+
+    fn candidate(description: &str, tags: &[&str]) -> Candidate {
+        Candidate { description: description.to_string(), tags: tags.iter().map(|t| t.to_string()).collect(), minutes: None }
+    }
+
+    fn cook(name: &str) -> CookOption {
+        CookOption { name: name.to_string(), unavailable_days: Vec::new(), prefs: CookPrefs::default() }
+    }
+
+    fn rule(kind: RuleKind, soft: bool) -> PlanRule {
+        PlanRule { kind, soft }
+    }
+
+    /// Monday to Friday dinners with two red meat dishes among five
+    fn problem(rules: Vec<PlanRule>) -> Problem {
+        Problem {
+            slots: (2..=6).map(date).collect(),
+            planned: Vec::new(),
+            cook_counts: HashMap::new(),
+            candidates: vec![
+                candidate("Steak", &["red meat"]),
+                candidate("Burgers", &["red meat"]),
+                candidate("Salmon", &["fish"]),
+                candidate("Risotto", &[]),
+                candidate("Curry", &[]),
+            ],
+            cooks: vec![cook("Alice"), cook("Bob")],
+            rules,
+        }
+    }
+
+    #[test]
+    fn test_solve_keeps_hard_rules() {
+        let rules = vec![
+            rule(RuleKind::NotConsecutive { tag: "red meat".to_string() }, false),
+            rule(RuleKind::OnDay { tag: "fish".to_string(), day: Weekday::Fri }, false),
+            rule(RuleKind::MaxMeals { cook: "Alice".to_string(), count: 2 }, false),
+        ];
+        let solution = solve(&problem(rules)).unwrap();
+        assert!(solution.broken.is_empty());
+        let meals = &solution.meals;
+        assert_eq!(meals.len(), 5);
+        assert_eq!(meals[4].description, "Salmon");
+        for pair in meals.windows(2) {
+            assert!(!(pair[0].has_tag("red meat") && pair[1].has_tag("red meat")));
+        }
+        assert!(meals.iter().filter(|m| m.cook == "Alice").count() <= 2);
+    }
+
+    #[test]
+    fn test_solve_respects_cook_prefs() {
+        let mut problem = problem(Vec::new());
+        problem.candidates[3].minutes = Some(60);
+        // Bob won't cook fish or anything over 30 minutes, Alice only cooks late in the week
+        problem.cooks[1].prefs = CookPrefs { avoid: vec!["fish".to_string()], max_minutes: Some(30), days: Vec::new() };
+        problem.cooks[0].prefs = CookPrefs { days: vec![Weekday::Thu, Weekday::Fri], ..CookPrefs::default() };
+        let solution = solve(&problem).unwrap();
+        for meal in &solution.meals {
+            if meal.has_tag("fish") || meal.description == "Risotto" {
+                assert_eq!(meal.cook, "Alice");
+            }
+            if meal.cook == "Alice" {
+                assert!(matches!(meal.date.weekday(), Weekday::Thu | Weekday::Fri));
+            }
+        }
+
+        // Cooking on Fridays alone, Alice can't make both the fish and the risotto
+        problem.cooks[0].prefs.days = vec![Weekday::Fri];
+        assert!(solve(&problem).is_err());
+    }
+
+    #[test]
+    fn test_solve_counts_planned_meals() {
+        let mut problem = problem(vec![
+            rule(RuleKind::NotConsecutive { tag: "red meat".to_string() }, false),
+            rule(RuleKind::MaxMeals { cook: "bob".to_string(), count: 1 }, false),
+        ]);
+        // Steak on Tuesday is already planned and Bob has a lunch this week
+        problem.slots = vec![date(2), date(4)];
+        problem.planned = vec![Placed { date: date(3), description: "Steak".to_string(), cook: "Alice".to_string(), tags: vec!["red meat".to_string()] }];
+        problem.cook_counts.insert("bob".to_string(), 1);
+        let solution = solve(&problem).unwrap();
+        assert!(solution.meals.iter().all(|m| !m.has_tag("red meat") && m.cook == "Alice"));
+    }
+
+    #[test]
+    fn test_solve_breaks_soft_rules_when_needed() {
+        // Five dinners from five dishes can't avoid a second cook without breaking a rule
+        let rules = vec![rule(RuleKind::MaxMeals { cook: "Alice".to_string(), count: 3 }, true)];
+        let mut problem = problem(rules);
+        problem.cooks = vec![cook("Alice")];
+        let solution = solve(&problem).unwrap();
+        assert_eq!(solution.meals.len(), 5);
+        assert_eq!(solution.broken, problem.rules);
+        assert!(solution.broken[0].to_string().ends_with("(soft)"));
+    }
+
+    #[test]
+    fn test_unsatisfiable_reports_blocking_rules() {
+        let rules = vec![
+            rule(RuleKind::OnDay { tag: "fish".to_string(), day: Weekday::Mon }, false),
+            rule(RuleKind::OnDay { tag: "fish".to_string(), day: Weekday::Fri }, false),
+            rule(RuleKind::NotConsecutive { tag: "red meat".to_string() }, false),
+        ];
+        let error = solve(&problem(rules.clone())).unwrap_err();
+        assert!(error.exhausted);
+        // Only one fish dish: either fish rule could give way, but not the red meat rule
+        assert_eq!(error.blocking, rules[..2].to_vec());
+        assert_eq!(rules[1].to_string(), "a fish meal on Fridays");
+    }
+
+    #[test]
+    fn test_rule_serialization() {
+        let rules: Vec<PlanRule> = serde_json::from_str(
+            r#"[{"rule": "not_consecutive", "tag": "red meat"},
+                {"rule": "max_meals", "cook": "Alice", "count": 3, "soft": true},
+                {"rule": "on_day", "tag": "fish", "day": "Friday"}]"#,
+        )
+        .unwrap();
+        assert_eq!(rules[1], rule(RuleKind::MaxMeals { cook: "Alice".to_string(), count: 3 }, true));
+        assert_eq!(rules[2].kind, RuleKind::OnDay { tag: "fish".to_string(), day: Weekday::Fri });
+        assert!(serde_json::from_str::<PlanRule>(r#"{"rule": "never"}"#).is_err());
+    }
+}
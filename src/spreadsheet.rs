@@ -0,0 +1,155 @@
+use crate::models::{Meal, MealPlan, MealType};
+use chrono::{Duration, NaiveDate};
+use rust_xlsxwriter::{Color, Format, FormatBorder, Workbook, XlsxError};
+use std::path::Path;
+
+/// Meal types in the order they appear as rows of the weekly grid
+pub const GRID_MEAL_TYPES: [MealType; 4] = [MealType::Breakfast, MealType::Lunch, MealType::Snack, MealType::Dinner];
+
+/// Background colors given to cooks in turn, light enough to read text on
+const COOK_COLORS: [u32; 8] = [0xC6EFCE, 0xFFEB9C, 0xBDD7EE, 0xF8CBAD, 0xE4DFEC, 0xDDEBF7, 0xFCE4D6, 0xD9D9D9];
+
+/// Returns the plan's meals laid out by meal type (rows) and day of the week (columns).
+/// Meals on dates outside the plan's week are left out.
+pub fn week_grid(meal_plan: &MealPlan) -> Vec<Vec<Option<&Meal>>> {
+    let mut grid = vec![vec![None; 7]; GRID_MEAL_TYPES.len()];
+    for meal in meal_plan.meals() {
+        let offset = (meal_plan.date_of(&meal.day) - meal_plan.week_start_date).num_days();
+        let row = GRID_MEAL_TYPES.iter().position(|t| *t == meal.meal_type);
+        if let (Some(row), 0..=6) = (row, offset) {
+            grid[row][offset as usize] = Some(meal);
+        }
+    }
+    grid
+}
+
+/// Assigns each cook a color, following `roster` first and then the order cooks appear
+/// in the plans, so a cook keeps the same color on every sheet
+pub fn cook_colors(roster: &[String], meal_plans: &[MealPlan]) -> Vec<(String, u32)> {
+    let mut cooks: Vec<String> = Vec::new();
+    let planned = meal_plans.iter().flat_map(|plan| plan.meals().iter().map(|m| m.cook.trim().to_string()));
+    for cook in roster.iter().cloned().chain(planned) {
+        if !cook.is_empty() && !cooks.iter().any(|c| c.eq_ignore_ascii_case(&cook)) {
+            cooks.push(cook);
+        }
+    }
+    cooks
+        .into_iter()
+        .enumerate()
+        .map(|(n, cook)| (cook, COOK_COLORS[n % COOK_COLORS.len()]))
+        .collect()
+}
+
+/// Writes one sheet per week with the meals in a grid, colored by cook
+pub fn write_xlsx(meal_plans: &[MealPlan], roster: &[String], path: &Path) -> std::io::Result<()> {
+    build_workbook(meal_plans, roster)
+        .and_then(|mut workbook| workbook.save(path))
+        .map_err(std::io::Error::other)
+}
+
+fn build_workbook(meal_plans: &[MealPlan], roster: &[String]) -> Result<Workbook, XlsxError> {
+    let colors = cook_colors(roster, meal_plans);
+    let header = Format::new().set_bold().set_border(FormatBorder::Thin).set_background_color(Color::RGB(0xEDEDED));
+    let cell = Format::new().set_text_wrap().set_border(FormatBorder::Thin);
+
+    let mut workbook = Workbook::new();
+    for meal_plan in meal_plans {
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(format!("Week of {}", meal_plan.week_start_date.format("%Y-%m-%d")))?;
+        sheet.set_column_width(0, 12)?;
+
+        sheet.write_string_with_format(0, 0, "", &header)?;
+        for offset in 0..7u16 {
+            let date: NaiveDate = meal_plan.week_start_date + Duration::days(offset as i64);
+            sheet.write_string_with_format(0, offset + 1, date.format("%a %Y-%m-%d").to_string(), &header)?;
+            sheet.set_column_width(offset + 1, 22)?;
+        }
+        sheet.set_freeze_panes(1, 0)?;
+
+        for (row, meals) in week_grid(meal_plan).into_iter().enumerate() {
+            let row = row as u32 + 1;
+            sheet.write_string_with_format(row, 0, GRID_MEAL_TYPES[row as usize - 1].to_string(), &header)?;
+            for (col, meal) in meals.into_iter().enumerate() {
+                let col = col as u16 + 1;
+                let Some(meal) = meal else {
+                    sheet.write_string_with_format(row, col, "", &cell)?;
+                    continue;
+                };
+                let format = match colors.iter().find(|(cook, _)| cook.eq_ignore_ascii_case(meal.cook.trim())) {
+                    Some((_, color)) => cell.clone().set_background_color(Color::RGB(*color)),
+                    None => cell.clone(),
+                };
+                let description = meal_plan.render_description(meal);
+                let text = if meal.cook.trim().is_empty() {
+                    description
+                } else {
+                    format!("{}\n({})", description, meal.cook)
+                };
+                sheet.write_string_with_format(row, col, text, &format)?;
+            }
+        }
+
+        // Legend of cook colors below the grid
+        let legend_row = GRID_MEAL_TYPES.len() as u32 + 2;
+        sheet.write_string_with_format(legend_row, 0, "Cooks", &header)?;
+        for (n, (cook, color)) in colors.iter().enumerate() {
+            let format = cell.clone().set_background_color(Color::RGB(*color));
+            sheet.write_string_with_format(legend_row, n as u16 + 1, cook, &format)?;
+        }
+    }
+
+    if meal_plans.is_empty() {
+        workbook.add_worksheet();
+    }
+    Ok(workbook)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Day;
+    use chrono::Weekday;
+    use tempfile::tempdir;
+
+    fn plan() -> MealPlan {
+        // Starts on a Wednesday, so Monday is the sixth column
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 4).unwrap());
+        plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Bob".to_string(), "Pasta".to_string()));
+        plan.add_meal(Meal::new(MealType::Breakfast, Day::Weekday(Weekday::Wed), "Alice".to_string(), "Eggs".to_string()));
+        plan.add_meal(Meal::new(
+            MealType::Lunch,
+            Day::Date(NaiveDate::from_ymd_opt(2023, 2, 1).unwrap()),
+            "Alice".to_string(),
+            "Soup".to_string(),
+        ));
+        plan
+    }
+
+    #[test]
+    fn test_week_grid() {
+        let plan = plan();
+        let grid = week_grid(&plan);
+        assert_eq!(grid[0][0].unwrap().description, "Eggs");
+        assert_eq!(grid[3][5].unwrap().description, "Pasta");
+        // The lunch is outside the week
+        assert!(grid[1].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn test_cook_colors_follow_roster() {
+        let colors = cook_colors(&["Bob".to_string()], &[plan()]);
+        assert_eq!(colors, vec![("Bob".to_string(), COOK_COLORS[0]), ("Alice".to_string(), COOK_COLORS[1])]);
+    }
+
+    #[test]
+    fn test_write_xlsx() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("plan.xlsx");
+        let next_week = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 11).unwrap());
+        write_xlsx(&[plan(), next_week], &[], &path).unwrap();
+
+        // An xlsx file is a zip archive
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..2], b"PK");
+    }
+}
@@ -0,0 +1,270 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File in the storage directory recording the plan files' contents at the last sync
+pub const SYNC_STATE: &str = "sync_state.json";
+
+/// Directory under the storage path holding previous versions of saved files
+pub const BACKUP_DIR: &str = "backups";
+
+/// Number of previous versions kept when the configuration doesn't say
+pub const DEFAULT_BACKUP_COUNT: usize = 5;
+
+/// Writes the file only if its contents differ from what is already there, so unchanged
+/// files keep their modification time. Returns true if the file was written.
+pub fn write_if_changed<P: AsRef<Path>>(path: P, contents: &[u8]) -> std::io::Result<bool> {
+    let path = path.as_ref();
+    match std::fs::read(path) {
+        Ok(existing) if existing == contents => Ok(false),
+        _ => {
+            std::fs::write(path, contents)?;
+            Ok(true)
+        }
+    }
+}
+
+/// Hashes file contents with 64-bit FNV-1a, which is stable across builds and platforms
+pub fn content_hash(contents: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in contents {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Hashes a file's contents, or returns None if it doesn't exist
+pub fn file_hash<P: AsRef<Path>>(path: P) -> std::io::Result<Option<String>> {
+    match std::fs::read(path) {
+        Ok(contents) => Ok(Some(content_hash(&contents))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the file holding the checksum of a saved file
+pub fn checksum_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".checksum");
+    path.with_file_name(name)
+}
+
+/// Returns where the nth most recent backup of a file is kept, counting from 1
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.parent()
+        .unwrap_or(Path::new("."))
+        .join(BACKUP_DIR)
+        .join(format!("{}.{}", name, n))
+}
+
+/// Returns the backups of a file that exist, most recent first
+pub fn backups(path: &Path) -> Vec<PathBuf> {
+    (1..)
+        .map(|n| backup_path(path, n))
+        .take_while(|backup| backup.exists())
+        .collect()
+}
+
+/// Writes the file with a checksum next to it, first copying the previous version into
+/// the backups and keeping at most `keep` of them. Nothing happens if the contents are
+/// unchanged. Returns true if the file was written.
+///
+/// The new contents go to a temporary file in the same directory, synced to disk, and are
+/// then renamed over the file, so a crash leaves either the old file or the new one, never
+/// half of it.
+pub fn write_with_backups(path: &Path, contents: &[u8], keep: usize) -> std::io::Result<bool> {
+    if std::fs::read(path).is_ok_and(|existing| existing == contents) {
+        return Ok(false);
+    }
+
+    let temp = write_synced_temp(path, contents)?;
+    let checksum_temp = write_synced_temp(&checksum_path(path), content_hash(contents).as_bytes())?;
+
+    if keep > 0 && path.exists() {
+        let oldest = backup_path(path, keep);
+        for stale in [checksum_path(&oldest), oldest] {
+            if stale.exists() {
+                std::fs::remove_file(stale)?;
+            }
+        }
+        for n in (1..keep).rev() {
+            let from = backup_path(path, n);
+            if from.exists() {
+                let to = backup_path(path, n + 1);
+                std::fs::rename(&from, &to)?;
+                if checksum_path(&from).exists() {
+                    std::fs::rename(checksum_path(&from), checksum_path(&to))?;
+                }
+            }
+        }
+
+        let newest = backup_path(path, 1);
+        std::fs::create_dir_all(newest.parent().unwrap_or(Path::new(".")))?;
+        std::fs::copy(path, &newest)?;
+        if checksum_path(path).exists() {
+            std::fs::copy(checksum_path(path), checksum_path(&newest))?;
+        }
+    }
+
+    std::fs::rename(&temp, path)?;
+    std::fs::rename(&checksum_temp, checksum_path(path))?;
+    Ok(true)
+}
+
+/// Replaces a file's contents all at once, through a temporary file synced to disk first,
+/// so a crash never leaves it half written
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let temp = write_synced_temp(path, contents)?;
+    std::fs::rename(temp, path)
+}
+
+/// Writes contents to a temporary file beside `path` and syncs it to disk, returning its path
+fn write_synced_temp(path: &Path, contents: &[u8]) -> std::io::Result<PathBuf> {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".tmp");
+    let temp = path.with_file_name(name);
+    let mut file = std::fs::File::create(&temp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    Ok(temp)
+}
+
+/// Checks a file against its stored checksum. Returns None when no checksum was stored.
+pub fn verify_checksum(path: &Path) -> std::io::Result<Option<bool>> {
+    let expected = match std::fs::read_to_string(checksum_path(path)) {
+        Ok(checksum) => checksum,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    Ok(Some(content_hash(&std::fs::read(path)?) == expected.trim()))
+}
+
+/// Content hashes of the JSON and Markdown plan files when they were last known to match
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncState {
+    pub json_hash: Option<String>,
+    pub markdown_hash: Option<String>,
+}
+
+/// Which of the two plan files changed since they were last in sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Changes {
+    pub json: bool,
+    pub markdown: bool,
+}
+
+impl SyncState {
+    fn path(storage_path: &Path) -> PathBuf {
+        storage_path.join(SYNC_STATE)
+    }
+
+    /// Loads the recorded state, treating a missing or unreadable file as never synced
+    pub fn load(storage_path: &Path) -> Self {
+        std::fs::read_to_string(Self::path(storage_path))
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Records the current contents of both files as being in sync
+    pub fn record(storage_path: &Path, json_path: &Path, markdown_path: &Path) -> std::io::Result<()> {
+        let state = Self {
+            json_hash: file_hash(json_path)?,
+            markdown_hash: file_hash(markdown_path)?,
+        };
+        write_if_changed(Self::path(storage_path), serde_json::to_string_pretty(&state)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Compares the files' current contents with the recorded state
+    pub fn changes(&self, json_path: &Path, markdown_path: &Path) -> std::io::Result<Changes> {
+        Ok(Changes {
+            json: file_hash(json_path)? != self.json_hash,
+            markdown: file_hash(markdown_path)? != self.markdown_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_write_if_changed() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("meal_plan.md");
+
+        assert!(write_if_changed(&path, b"# Meal Plan").unwrap());
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(!write_if_changed(&path, b"# Meal Plan").unwrap());
+        assert_eq!(std::fs::metadata(&path).unwrap().modified().unwrap(), modified);
+
+        assert!(write_if_changed(&path, b"# Meal Plan\n").unwrap());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "# Meal Plan\n");
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        assert_eq!(content_hash(b""), "cbf29ce484222325");
+        assert_eq!(content_hash(b"a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn test_write_with_backups() {
+        let temp_dir = tempdir().unwrap();
+        let path = temp_dir.path().join("meal_plan.json");
+
+        for version in ["one", "two", "two", "three", "four"] {
+            write_with_backups(&path, version.as_bytes(), 2).unwrap();
+        }
+
+        // Unchanged writes don't rotate, and only two backups are kept
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "four");
+        let backups = backups(&path);
+        assert_eq!(backups.len(), 2);
+        assert_eq!(std::fs::read_to_string(&backups[0]).unwrap(), "three");
+        assert_eq!(std::fs::read_to_string(&backups[1]).unwrap(), "two");
+
+        assert_eq!(verify_checksum(&path).unwrap(), Some(true));
+        assert_eq!(verify_checksum(&backups[1]).unwrap(), Some(true));
+        std::fs::write(&path, "fouR").unwrap();
+        assert_eq!(verify_checksum(&path).unwrap(), Some(false));
+        assert_eq!(verify_checksum(&temp_dir.path().join("other.json")).unwrap(), None);
+
+        // No temporary files are left behind
+        let mut names: Vec<String> = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["backups", "meal_plan.json", "meal_plan.json.checksum"]);
+    }
+
+    #[test]
+    fn test_sync_state_changes() {
+        let temp_dir = tempdir().unwrap();
+        let json_path = temp_dir.path().join("meal_plan.json");
+        let markdown_path = temp_dir.path().join("meal_plan.md");
+        std::fs::write(&json_path, "{}").unwrap();
+        std::fs::write(&markdown_path, "# Meal Plan").unwrap();
+
+        // Nothing recorded yet: both count as changed
+        let state = SyncState::load(temp_dir.path());
+        assert_eq!(state.changes(&json_path, &markdown_path).unwrap(), Changes { json: true, markdown: true });
+
+        SyncState::record(temp_dir.path(), &json_path, &markdown_path).unwrap();
+        let state = SyncState::load(temp_dir.path());
+        assert_eq!(state.changes(&json_path, &markdown_path).unwrap(), Changes { json: false, markdown: false });
+
+        // Touching a file without changing it is not a change
+        std::fs::write(&json_path, "{}").unwrap();
+        std::fs::write(&markdown_path, "# Meal Plan\n- edited").unwrap();
+        assert_eq!(state.changes(&json_path, &markdown_path).unwrap(), Changes { json: false, markdown: true });
+    }
+}
@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    crate::storage,
+    std::path::PathBuf,
+};
+
+/// Somewhere plans are kept by name. The command line keeps them in a directory; a
+/// browser build keeps them in localStorage or IndexedDB.
+pub trait Storage {
+    /// Reads what is stored under `name`, or None if nothing is
+    fn read(&self, name: &str) -> std::io::Result<Option<String>>;
+
+    /// Stores `contents` under `name`, replacing what was there
+    fn write(&mut self, name: &str, contents: &str) -> std::io::Result<()>;
+}
+
+/// Storage held in memory, for a browser build to fill from and copy back to its own
+/// storage, and for tests
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemoryStorage {
+    entries: BTreeMap<String, String>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything stored, by name
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(name, contents)| (name.as_str(), contents.as_str()))
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read(&self, name: &str) -> std::io::Result<Option<String>> {
+        Ok(self.entries.get(name).cloned())
+    }
+
+    fn write(&mut self, name: &str, contents: &str) -> std::io::Result<()> {
+        self.entries.insert(name.to_string(), contents.to_string());
+        Ok(())
+    }
+}
+
+/// Files in a directory, each written with a checksum and previous versions kept in its
+/// backups, as the command line saves the plan
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct Directory {
+    path: PathBuf,
+    keep: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Directory {
+    pub fn new(path: impl Into<PathBuf>, keep: usize) -> Self {
+        Self { path: path.into(), keep }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Storage for Directory {
+    fn read(&self, name: &str) -> std::io::Result<Option<String>> {
+        match std::fs::read_to_string(self.path.join(name)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&mut self, name: &str, contents: &str) -> std::io::Result<()> {
+        storage::write_with_backups(&self.path.join(name), contents.as_bytes(), self.keep)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::{Day, Meal, MealPlan, MealType};
+    use chrono::{NaiveDate, Weekday};
+    use tempfile::tempdir;
+
+    fn plan() -> MealPlan {
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Mon), "Alice".to_string(), "Chili".to_string()));
+        plan
+    }
+
+    #[test]
+    fn test_plan_in_memory() {
+        let mut storage = MemoryStorage::new();
+        assert!(MealPlan::load_from(&storage, "meal_plan.json").unwrap().is_none());
+
+        plan().save_to(&mut storage, "meal_plan.json").unwrap();
+        let loaded = MealPlan::load_from(&storage, "meal_plan.json").unwrap().unwrap();
+        assert_eq!(loaded.find_meal(&MealType::Dinner, &Day::Weekday(Weekday::Mon)).unwrap().description, "Chili");
+        assert_eq!(storage.entries().count(), 1);
+    }
+
+    #[test]
+    fn test_plan_in_directory() {
+        let temp_dir = tempdir().unwrap();
+        let mut storage = Directory::new(temp_dir.path(), 2);
+        plan().save_to(&mut storage, "meal_plan.json").unwrap();
+
+        // Saved the same way as the command line saves it, checksum included
+        let path = temp_dir.path().join("meal_plan.json");
+        assert_eq!(storage::verify_checksum(&path).unwrap(), Some(true));
+        assert_eq!(MealPlan::load_from_json(&path).unwrap().meals().len(), 1);
+        assert_eq!(MealPlan::load_from(&storage, "meal_plan.json").unwrap().unwrap().meals().len(), 1);
+    }
+}
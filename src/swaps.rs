@@ -0,0 +1,170 @@
+use crate::models::{Day, MealPlan, MealType};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// File in the storage path with the swap requests waiting to be accepted
+pub const SWAPS_FILE: &str = "swaps.json";
+
+/// A cook asking another to take over one of their meals
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwapRequest {
+    pub meal_type: MealType,
+    pub day: Day,
+    /// The date the meal falls on, so a request left from another week isn't applied to this one
+    pub date: NaiveDate,
+    pub description: String,
+    /// The meal's cook when the request was made
+    pub from: String,
+    pub to: String,
+    pub requested_at: DateTime<Utc>,
+}
+
+impl SwapRequest {
+    /// The meal asked about, e.g. Dinner on Fri 2024-01-05 (Tacos)
+    pub fn meal(&self) -> String {
+        format!("{} on {} ({})", self.meal_type, self.date.format("%a %Y-%m-%d"), self.description)
+    }
+}
+
+/// Swap requests waiting for the other cook, kept in swaps.json in the storage path
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Swaps {
+    pub pending: Vec<SwapRequest>,
+}
+
+impl Swaps {
+    /// Asks for a planned meal to be cooked by `to` instead, replacing a request already
+    /// waiting for the same meal
+    pub fn request(&mut self, meal_plan: &MealPlan, meal_type: &MealType, day: &Day, to: &str, at: DateTime<Utc>) -> Result<SwapRequest, String> {
+        let meal = meal_plan.find_meal(meal_type, day)
+            .ok_or_else(|| format!("No {} meal found for {}.", meal_type, day))?;
+        if meal.cook.eq_ignore_ascii_case(to.trim()) {
+            return Err(format!("{} already cooks {} on {}.", meal.cook, meal_type, day));
+        }
+        let request = SwapRequest {
+            meal_type: meal_type.clone(),
+            day: day.clone(),
+            date: meal_plan.date_of(day),
+            description: meal.description.clone(),
+            from: meal.cook.clone(),
+            to: to.trim().to_string(),
+            requested_at: at,
+        };
+        self.remove(&request);
+        self.pending.push(request.clone());
+        self.pending.sort_by_key(|request| request.date);
+        Ok(request)
+    }
+
+    /// The requests waiting for `cook`, narrowed down to a meal type or date when given
+    pub fn waiting_for(&self, cook: &str, meal_type: Option<&MealType>, date: Option<NaiveDate>) -> Vec<&SwapRequest> {
+        self.pending.iter()
+            .filter(|request| request.to.eq_ignore_ascii_case(cook.trim()))
+            .filter(|request| meal_type.is_none_or(|t| *t == request.meal_type))
+            .filter(|request| date.is_none_or(|d| d == request.date))
+            .collect()
+    }
+
+    /// Takes out the request for the same meal, returning false if there was none
+    pub fn remove(&mut self, request: &SwapRequest) -> bool {
+        let before = self.pending.len();
+        self.pending.retain(|r| r.date != request.date || r.meal_type != request.meal_type);
+        self.pending.len() < before
+    }
+
+    /// Drops requests for meals before `today`, returning how many were dropped
+    pub fn expire(&mut self, today: NaiveDate) -> usize {
+        let before = self.pending.len();
+        self.pending.retain(|request| request.date >= today);
+        before - self.pending.len()
+    }
+
+    /// Saves the swap requests to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the swap requests from a JSON file, which is empty until the first request
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+/// Hands a meal over to the cook it was offered to, as long as the plan still has it with
+/// the cook who asked
+pub fn apply(meal_plan: &mut MealPlan, request: &SwapRequest, updated_by: &str) -> Result<(), String> {
+    if meal_plan.date_of(&request.day) != request.date {
+        return Err(format!("The request for {} was made for another week.", request.meal()));
+    }
+    let meal = meal_plan.find_meal(&request.meal_type, &request.day)
+        .ok_or_else(|| format!("{} has been removed from the plan.", request.meal()))?;
+    if meal.cook != request.from {
+        return Err(format!("{} is now cooked by {}, not {} who asked.", request.meal(), meal.cook, request.from));
+    }
+    meal_plan.change_meal(&request.meal_type, &request.day, |meal| {
+        meal.cook = request.to.clone();
+        meal.updated_by = Some(updated_by.to_string());
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Meal;
+    use chrono::Weekday;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_swaps() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(SWAPS_FILE);
+        let mut plan = MealPlan::new(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap());
+        let friday = Day::Weekday(Weekday::Fri);
+        plan.add_meal(Meal::new(MealType::Dinner, friday.clone(), "Alice".to_string(), "Tacos".to_string()));
+
+        let mut swaps = Swaps::load_from_json(&path).unwrap();
+        assert!(swaps.request(&plan, &MealType::Lunch, &friday, "Bob", Utc::now()).is_err());
+        assert!(swaps.request(&plan, &MealType::Dinner, &friday, "alice", Utc::now()).is_err());
+        let request = swaps.request(&plan, &MealType::Dinner, &friday, "Bob", Utc::now()).unwrap();
+        assert_eq!(request.meal(), "Dinner on Fri 2024-01-05 (Tacos)");
+        // Asking again replaces the request
+        swaps.request(&plan, &MealType::Dinner, &friday, "Carol", Utc::now()).unwrap();
+        assert_eq!(swaps.pending.len(), 1);
+        assert!(swaps.waiting_for("bob", None, None).is_empty());
+        assert_eq!(swaps.waiting_for("carol", Some(&MealType::Dinner), Some(plan.date_of(&friday))).len(), 1);
+        assert!(swaps.waiting_for("carol", Some(&MealType::Lunch), None).is_empty());
+
+        swaps.save_to_json(&path).unwrap();
+        let mut swaps = Swaps::load_from_json(&path).unwrap();
+        let request = swaps.pending[0].clone();
+        apply(&mut plan, &request, "carol").unwrap();
+        let meal = plan.find_meal(&MealType::Dinner, &friday).unwrap();
+        assert_eq!(meal.cook, "Carol");
+        assert_eq!(meal.updated_by.as_deref(), Some("carol"));
+        // The meal has changed hands since, so it can't be accepted again
+        assert!(apply(&mut plan, &request, "carol").unwrap_err().contains("now cooked by Carol"));
+
+        let mut next_week = MealPlan::new(NaiveDate::from_ymd_opt(2024, 1, 8).unwrap());
+        assert!(apply(&mut next_week, &request, "carol").unwrap_err().contains("another week"));
+        assert!(swaps.remove(&request));
+        assert!(!swaps.remove(&request));
+
+        swaps.request(&plan, &MealType::Dinner, &friday, "Bob", Utc::now()).unwrap();
+        assert_eq!(swaps.expire(NaiveDate::from_ymd_opt(2024, 1, 5).unwrap()), 0);
+        assert_eq!(swaps.expire(NaiveDate::from_ymd_opt(2024, 1, 6).unwrap()), 1);
+    }
+}
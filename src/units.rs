@@ -0,0 +1,262 @@
+use crate::ingredients;
+use std::fmt;
+
+/// Grams per millilitre of common ingredients, for combining weights with volumes. An
+/// ingredient matches when its name is the key or ends with it, so "unsalted butter"
+/// finds butter.
+const DENSITIES: [(&str, f64); 12] = [
+    ("butter", 0.911),
+    ("flour", 0.53),
+    ("sugar", 0.85),
+    ("brown sugar", 0.93),
+    ("powdered sugar", 0.56),
+    ("water", 1.0),
+    ("milk", 1.03),
+    ("cream", 1.01),
+    ("oil", 0.92),
+    ("honey", 1.42),
+    ("rice", 0.85),
+    ("salt", 1.2),
+];
+
+/// What a unit measures; amounts only convert within one, or between volume and mass
+/// for ingredients with a known density
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Volume,
+    Mass,
+}
+
+/// A unit of measure, with its size in millilitres or grams
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Unit {
+    pub symbol: &'static str,
+    pub dimension: Dimension,
+    pub base: f64,
+}
+
+const UNITS: [(Unit, &[&str]); 9] = [
+    (Unit { symbol: "tsp", dimension: Dimension::Volume, base: 4.928_92 }, &["tsp", "tsps", "teaspoon", "teaspoons"]),
+    (Unit { symbol: "tbsp", dimension: Dimension::Volume, base: 14.786_8 }, &["tbsp", "tbsps", "tbs", "tablespoon", "tablespoons"]),
+    (Unit { symbol: "cup", dimension: Dimension::Volume, base: 236.588 }, &["cup", "cups"]),
+    (Unit { symbol: "ml", dimension: Dimension::Volume, base: 1.0 }, &["ml", "milliliter", "milliliters", "millilitre", "millilitres"]),
+    (Unit { symbol: "l", dimension: Dimension::Volume, base: 1000.0 }, &["l", "liter", "liters", "litre", "litres"]),
+    (Unit { symbol: "g", dimension: Dimension::Mass, base: 1.0 }, &["g", "gram", "grams"]),
+    (Unit { symbol: "kg", dimension: Dimension::Mass, base: 1000.0 }, &["kg", "kilogram", "kilograms"]),
+    (Unit { symbol: "oz", dimension: Dimension::Mass, base: 28.349_5 }, &["oz", "ounce", "ounces"]),
+    (Unit { symbol: "lb", dimension: Dimension::Mass, base: 453.592 }, &["lb", "lbs", "pound", "pounds"]),
+];
+
+/// Units a simplified amount moves between, smallest first
+const LADDERS: [&[&str]; 4] = [&["tsp", "tbsp", "cup"], &["ml", "l"], &["g", "kg"], &["oz", "lb"]];
+
+impl Unit {
+    pub fn parse(text: &str) -> Option<Unit> {
+        let text = text.trim().trim_end_matches('.').to_lowercase();
+        UNITS.iter().find(|(_, names)| names.contains(&text.as_str())).map(|(unit, _)| *unit)
+    }
+
+    fn by_symbol(symbol: &str) -> Option<Unit> {
+        UNITS.iter().map(|(unit, _)| *unit).find(|unit| unit.symbol == symbol)
+    }
+}
+
+/// An amount of an ingredient, such as "2 tbsp butter" or "3 eggs"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    pub amount: f64,
+    /// None for things counted, like eggs
+    pub unit: Option<Unit>,
+    pub ingredient: String,
+}
+
+impl Quantity {
+    /// Parses an amount, as a number, a fraction or a mixed number like "1 1/2", then an
+    /// optional unit, then the ingredient. Returns None when there's no amount.
+    pub fn parse(text: &str) -> Option<Quantity> {
+        let mut words = text.split_whitespace().peekable();
+        let mut amount = parse_number(words.next()?)?;
+        if let Some(fraction) = words.peek().filter(|word| word.contains('/')).and_then(|word| parse_number(word)) {
+            amount += fraction;
+            words.next();
+        }
+        let unit = words.peek().and_then(|word| Unit::parse(word));
+        if unit.is_some() {
+            words.next();
+        }
+        if words.peek() == Some(&"of") {
+            words.next();
+        }
+        let ingredient = words.collect::<Vec<_>>().join(" ");
+        if ingredient.is_empty() {
+            return None;
+        }
+        Some(Quantity { amount, unit, ingredient })
+    }
+
+    /// Whether two quantities are of the same ingredient, ignoring case and a plural "s" and
+    /// going by the ingredient registry
+    pub fn same_ingredient(&self, other: &Quantity) -> bool {
+        ingredient_key(&self.ingredient) == ingredient_key(&other.ingredient)
+    }
+
+    /// The amount in another unit, converting between volume and mass by the ingredient's
+    /// density when the dimensions differ
+    pub fn amount_in(&self, unit: Unit) -> Option<f64> {
+        let from = self.unit?;
+        let base = from.base * self.amount;
+        let base = match (from.dimension, unit.dimension) {
+            (a, b) if a == b => base,
+            (Dimension::Volume, Dimension::Mass) => base * density(&self.ingredient)?,
+            (Dimension::Mass, Dimension::Volume) => base / density(&self.ingredient)?,
+            _ => return None,
+        };
+        Some(base / unit.base)
+    }
+
+    /// Adds another quantity of the same ingredient, in this quantity's unit. None when the
+    /// ingredients differ or the units can't be converted.
+    pub fn combine(&self, other: &Quantity) -> Option<Quantity> {
+        if !self.same_ingredient(other) {
+            return None;
+        }
+        let added = match (self.unit, other.unit) {
+            (None, None) => other.amount,
+            (Some(unit), Some(_)) => other.amount_in(unit)?,
+            _ => return None,
+        };
+        Some(Quantity { amount: self.amount + added, ..self.clone() }.simplified())
+    }
+
+    pub fn scaled(&self, factor: f64) -> Quantity {
+        Quantity { amount: self.amount * factor, ..self.clone() }.simplified()
+    }
+
+    /// Moves the amount to the largest unit of its kind it is at least one of, so 6 tsp
+    /// becomes 2 tbsp and 1500 g becomes 1.5 kg
+    pub fn simplified(&self) -> Quantity {
+        let Some(unit) = self.unit else {
+            return self.clone();
+        };
+        let Some(ladder) = LADDERS.iter().find(|ladder| ladder.contains(&unit.symbol)) else {
+            return self.clone();
+        };
+        let base = self.amount * unit.base;
+        let best = ladder
+            .iter()
+            .filter_map(|symbol| Unit::by_symbol(symbol))
+            .rfind(|candidate| base / candidate.base >= 1.0 - 1e-9)
+            .unwrap_or(unit);
+        Quantity { amount: base / best.base, unit: Some(best), ingredient: self.ingredient.clone() }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", format_amount(self.amount))?;
+        match self.unit {
+            Some(unit) if unit.symbol == "cup" && format_amount(self.amount) != "1" => write!(f, " cups")?,
+            Some(unit) => write!(f, " {}", unit.symbol)?,
+            None => {}
+        }
+        write!(f, " {}", self.ingredient)
+    }
+}
+
+/// Rounds an amount for display: two decimals for small amounts, one from 10 up, with
+/// trailing zeros dropped
+pub fn format_amount(amount: f64) -> String {
+    let text = if amount >= 10.0 { format!("{:.1}", amount) } else { format!("{:.2}", amount) };
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn parse_number(text: &str) -> Option<f64> {
+    let number = match text.split_once('/') {
+        Some((numerator, denominator)) => numerator.parse::<f64>().ok()? / denominator.parse::<f64>().ok()?,
+        None => text.parse().ok()?,
+    };
+    (number.is_finite() && number >= 0.0).then_some(number)
+}
+
+/// The ingredient a grocery or recipe line is about, without its amount, for matching
+/// lines up: "2 cups Onions" and "onion" both give "onion", and "green onions" gives
+/// "scallion", the name it is registered under
+pub fn ingredient_name(text: &str) -> String {
+    match Quantity::parse(text) {
+        Some(quantity) => ingredient_key(&quantity.ingredient),
+        None => ingredient_key(text),
+    }
+}
+
+fn ingredient_key(ingredient: &str) -> String {
+    ingredients::canonical(&fold_ingredient(ingredient))
+}
+
+/// An ingredient's name ignoring case, spacing and a plural "s"
+pub fn fold_ingredient(ingredient: &str) -> String {
+    let key = ingredient.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    key.strip_suffix('s').map(str::to_string).unwrap_or(key)
+}
+
+fn density(ingredient: &str) -> Option<f64> {
+    let name = ingredient.trim().to_lowercase();
+    DENSITIES
+        .iter()
+        .filter(|(key, _)| name == *key || name.ends_with(&format!(" {}", key)))
+        .max_by_key(|(key, _)| key.len())
+        .map(|(_, density)| *density)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantity(text: &str) -> Quantity {
+        Quantity::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_quantity() {
+        assert_eq!(quantity("2 tbsp butter").unit.map(|u| u.symbol), Some("tbsp"));
+        let mixed = quantity("1 1/2 cups of flour");
+        assert_eq!((mixed.amount, mixed.ingredient.as_str()), (1.5, "flour"));
+        let eggs = quantity("3 eggs");
+        assert_eq!((eggs.amount, eggs.unit), (3.0, None));
+        assert_eq!(quantity("0.5 L milk").unit.map(|u| u.symbol), Some("l"));
+        assert!(Quantity::parse("butter").is_none());
+        assert!(Quantity::parse("2 cups").is_none());
+        assert!(Quantity::parse("1/0 cup sugar").is_none());
+        assert!(Quantity::parse("-1/2 cup sugar").is_none());
+        assert!(Quantity::parse("inf/2 cup sugar").is_none());
+        assert!(Quantity::parse("1e308/1e-10 cup sugar").is_none());
+    }
+
+    #[test]
+    fn test_combine() {
+        // Volume and mass combine through the density of butter
+        let combined = quantity("30 g butter").combine(&quantity("2 tbsp Butter")).unwrap();
+        assert_eq!(combined.to_string(), "56.9 g butter");
+        assert_eq!(quantity("1 cup milk").combine(&quantity("250 ml milk")).unwrap().to_string(), "2.06 cups milk");
+        assert_eq!(quantity("2 eggs").combine(&quantity("1 egg")).unwrap().to_string(), "3 eggs");
+        assert_eq!(quantity("600 g rice").combine(&quantity("0.5 kg rice")).unwrap().to_string(), "1.1 kg rice");
+        // Without a density, weights and volumes stay apart
+        assert!(quantity("100 g spinach").combine(&quantity("1 cup spinach")).is_none());
+        assert!(quantity("2 eggs").combine(&quantity("100 g eggs")).is_none());
+        assert!(quantity("1 cup milk").combine(&quantity("1 cup cream")).is_none());
+    }
+
+    #[test]
+    fn test_ingredient_name() {
+        assert_eq!(ingredient_name("2 cups Onions"), "onion");
+        assert_eq!(ingredient_name(" onion "), "onion");
+    }
+
+    #[test]
+    fn test_scale_and_simplify() {
+        assert_eq!(quantity("2 tsp salt").scaled(3.0).to_string(), "2 tbsp salt");
+        assert_eq!(quantity("8 oz cheese").scaled(2.0).to_string(), "1 lb cheese");
+        assert_eq!(quantity("1 tbsp olive oil").scaled(0.5).to_string(), "1.5 tsp olive oil");
+        assert_eq!(quantity("1/3 cup sugar").scaled(1.0).to_string(), "5.33 tbsp sugar");
+        assert_eq!(quantity("2 onions").scaled(1.5).to_string(), "3 onions");
+    }
+}
@@ -0,0 +1,133 @@
+use crate::report::ReportPeriod;
+use crate::units;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Food that was thrown away
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WasteEntry {
+    pub date: NaiveDate,
+    /// What was thrown away, such as "half a bag of spinach"
+    pub item: String,
+    /// Recipe the food was bought or cooked for
+    #[serde(default)]
+    pub recipe: Option<String>,
+    /// Start of the planned week the food came from
+    #[serde(default)]
+    pub week: Option<NaiveDate>,
+}
+
+/// The household's food waste, kept in waste.json in the storage path
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WasteLog {
+    pub entries: Vec<WasteEntry>,
+}
+
+impl WasteLog {
+    /// Records an entry, keeping the log in date order
+    pub fn log(&mut self, entry: WasteEntry) {
+        let position = self.entries.partition_point(|e| e.date <= entry.date);
+        self.entries.insert(position, entry);
+    }
+
+    /// Saves the log to a JSON file
+    pub fn save_to_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let mut file = File::create(path)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Loads the log from a JSON file, which is empty until the first entry is logged
+    pub fn load_from_json<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
+
+/// What was thrown away over a month or year
+#[derive(Debug, Clone, PartialEq)]
+pub struct WasteReport<'a> {
+    pub period: ReportPeriod,
+    pub entries: Vec<&'a WasteEntry>,
+    /// Recipes behind the waste with how many entries name them, most first
+    pub recipes: Vec<(String, usize)>,
+    /// Ingredients thrown away more than once, with how often, most first
+    pub repeated: Vec<(String, usize)>,
+}
+
+impl<'a> WasteReport<'a> {
+    pub fn new(log: &'a WasteLog, period: ReportPeriod) -> Self {
+        let entries: Vec<&WasteEntry> = log.entries.iter().filter(|e| period.contains(e.date)).collect();
+        let recipes = ranked(entries.iter().filter_map(|e| e.recipe.clone()));
+        let repeated = ranked(entries.iter().map(|e| units::ingredient_name(&e.item)))
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .collect();
+        Self { period, entries, recipes, repeated }
+    }
+}
+
+/// Counts names case-insensitively, most common first, keeping the first spelling seen
+fn ranked(names: impl Iterator<Item = String>) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, (String, usize)> = HashMap::new();
+    for name in names {
+        counts.entry(name.trim().to_lowercase()).or_insert_with(|| (name.trim().to_string(), 0)).1 += 1;
+    }
+    let mut ranked: Vec<(String, usize)> = counts.into_values().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.to_lowercase().cmp(&b.0.to_lowercase())));
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entry(day: u32, item: &str, recipe: Option<&str>) -> WasteEntry {
+        WasteEntry {
+            date: NaiveDate::from_ymd_opt(2023, 1, day).unwrap(),
+            item: item.to_string(),
+            recipe: recipe.map(str::to_string),
+            week: None,
+        }
+    }
+
+    #[test]
+    fn test_waste_report() {
+        let mut log = WasteLog::default();
+        log.log(entry(20, "2 cups rice", Some("chili")));
+        log.log(entry(3, "half a bag of spinach", None));
+        log.log(entry(12, "1 cup Rice", Some("Chili")));
+        log.log(entry(31, "bread", Some("Sandwiches")));
+        log.log(WasteEntry { date: NaiveDate::from_ymd_opt(2023, 2, 1).unwrap(), ..entry(1, "milk", None) });
+        assert_eq!(log.entries[0].item, "half a bag of spinach");
+
+        let report = WasteReport::new(&log, ReportPeriod::parse_month("2023-01").unwrap());
+        assert_eq!(report.entries.len(), 4);
+        assert_eq!(report.recipes, vec![("Chili".to_string(), 2), ("Sandwiches".to_string(), 1)]);
+        assert_eq!(report.repeated, vec![("rice".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_waste_log_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("waste.json");
+        assert!(WasteLog::load_from_json(&path).unwrap().entries.is_empty());
+
+        let mut log = WasteLog::default();
+        log.log(WasteEntry { week: NaiveDate::from_ymd_opt(2023, 1, 2), ..entry(4, "spinach", Some("Salad")) });
+        log.save_to_json(&path).unwrap();
+        assert_eq!(WasteLog::load_from_json(&path).unwrap().entries, log.entries);
+    }
+}
@@ -0,0 +1,395 @@
+use crate::days::weekday_name;
+use crate::models::MealPlan;
+use crate::spreadsheet::GRID_MEAL_TYPES;
+use chrono::{Datelike, Duration};
+use serde_json::json;
+
+/// Content type the web app posts queued edits with. Browsers won't send it across sites
+/// without asking first, which the server never agrees to, so other pages can't post edits.
+pub const OPS_CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// The files making up the web app, by path: the content type and contents of each
+pub fn asset(path: &str) -> Option<(&'static str, String)> {
+    match path {
+        "/" | "/index.html" => Some(("text/html; charset=utf-8", INDEX_HTML.to_string())),
+        "/app.js" => Some(("text/javascript; charset=utf-8", APP_JS.to_string())),
+        "/sw.js" => Some(("text/javascript; charset=utf-8", service_worker())),
+        "/manifest.webmanifest" => Some(("application/manifest+json", MANIFEST.to_string())),
+        "/icon.svg" => Some(("image/svg+xml", ICON_SVG.to_string())),
+        _ => None,
+    }
+}
+
+/// The week as the web app shows it. Each meal has its date for showing it and its day as
+/// planned, weekday or date, for editing it; a new meal goes on the weekday. The description
+/// is as stored, placeholders and all, for editing, and the text is what to show.
+pub fn plan_json(meal_plan: &MealPlan) -> serde_json::Value {
+    let days: Vec<serde_json::Value> = (0..7)
+        .map(|offset| meal_plan.week_start_date + Duration::days(offset))
+        .map(|date| {
+            json!({
+                "date": date.format("%Y-%m-%d").to_string(),
+                "day": weekday_name(date.weekday()),
+                "name": date.format("%A, %b %-d").to_string(),
+            })
+        })
+        .collect();
+    let mut meals: Vec<_> = meal_plan.meals().iter().collect();
+    meals.sort_by_key(|meal| (meal_plan.date_of(&meal.day), GRID_MEAL_TYPES.iter().position(|t| *t == meal.meal_type)));
+    let meals: Vec<serde_json::Value> = meals
+        .into_iter()
+        .map(|meal| {
+            json!({
+                "meal_type": meal.meal_type.to_string(),
+                "date": meal_plan.date_of(&meal.day).format("%Y-%m-%d").to_string(),
+                "day": meal.day.to_string(),
+                "cook": meal.cook,
+                "description": meal.description,
+                "text": meal_plan.render_description(meal),
+            })
+        })
+        .collect();
+    json!({
+        "week_start_date": meal_plan.week_start_date.format("%Y-%m-%d").to_string(),
+        "meal_types": GRID_MEAL_TYPES.iter().map(|t| t.to_string()).collect::<Vec<_>>(),
+        "days": days,
+        "meals": meals,
+    })
+}
+
+/// The service worker, with a cache named after the version so an upgrade replaces the
+/// cached app
+fn service_worker() -> String {
+    SERVICE_WORKER_JS.replace("{version}", env!("CARGO_PKG_VERSION"))
+}
+
+const INDEX_HTML: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="theme-color" content="#2f6f4f">
+<title>Meal Plan</title>
+<link rel="manifest" href="manifest.webmanifest">
+<link rel="icon" href="icon.svg">
+<style>
+body { font-family: system-ui, sans-serif; margin: 0 auto; max-width: 40rem; padding: 1rem; }
+header { display: flex; justify-content: space-between; align-items: baseline; }
+#status { font-size: 0.85rem; color: #666; }
+#error { color: #a00; }
+h2 { font-size: 1rem; margin: 1.2rem 0 0.3rem; border-bottom: 1px solid #ddd; }
+ul { list-style: none; padding: 0; margin: 0; }
+li { display: flex; gap: 0.5rem; align-items: center; padding: 0.3rem 0; }
+li span { flex: 1; }
+li.pending { opacity: 0.6; }
+button { font-size: 1rem; }
+form { display: grid; gap: 0.5rem; margin-top: 1.5rem; padding-top: 1rem; border-top: 2px solid #2f6f4f; }
+input, select { font-size: 1rem; padding: 0.4rem; }
+</style>
+</head>
+<body>
+<header><h1 id="title">Meal Plan</h1><span id="status"></span></header>
+<p id="error" hidden></p>
+<main id="week"><p>Loading…</p></main>
+<form id="meal">
+<select id="day" aria-label="Day"></select>
+<select id="meal-type" aria-label="Meal"></select>
+<input id="description" placeholder="What's cooking" required>
+<input id="cook" placeholder="Cook, or auto">
+<button type="submit">Save</button>
+</form>
+<script src="app.js"></script>
+</body>
+</html>
+"##;
+
+const APP_JS: &str = r##"// Edits are queued in localStorage and sent one at a time, so they survive going offline.
+// Each queued entry is the operation to post and the date it is for.
+const QUEUE_KEY = "mealplan-queue";
+const PLAN_KEY = "mealplan-plan";
+const RETRY_MS = 30000;
+
+let plan = JSON.parse(localStorage.getItem(PLAN_KEY) || "null");
+let flushing = false;
+
+function queued() {
+  return JSON.parse(localStorage.getItem(QUEUE_KEY) || "[]");
+}
+
+function setQueued(ops) {
+  localStorage.setItem(QUEUE_KEY, JSON.stringify(ops));
+}
+
+// Shows a queued edit before the server has it
+function applyLocally(view, entry) {
+  const op = entry.op;
+  const others = view.meals.filter((meal) => !(meal.meal_type === op.meal_type && meal.date === entry.date));
+  if (op.op === "add") {
+    others.push({ meal_type: op.meal_type, date: entry.date, day: op.day, cook: op.cook, description: op.description, text: op.description, pending: true });
+  } else if (op.op === "edit") {
+    const meal = view.meals.find((meal) => meal.meal_type === op.meal_type && meal.date === entry.date);
+    if (meal) {
+      const description = op.description === undefined ? meal.description : op.description;
+      const text = description === meal.description ? meal.text : description;
+      others.push(Object.assign({}, meal, { cook: op.cook || meal.cook, description: description, text: text, pending: true }));
+    }
+  }
+  view.meals = others;
+}
+
+function slot(date, type) {
+  const view = { meals: plan.meals.slice() };
+  queued().forEach((entry) => applyLocally(view, entry));
+  return view.meals.find((meal) => meal.date === date && meal.meal_type === type);
+}
+
+function showError(message) {
+  const error = document.getElementById("error");
+  error.textContent = message;
+  error.hidden = !message;
+}
+
+function render() {
+  const pending = queued();
+  const status = navigator.onLine ? "" : "Offline. ";
+  document.getElementById("status").textContent =
+    status + (pending.length ? pending.length + " change(s) waiting to sync" : "");
+  if (!plan) {
+    document.getElementById("week").innerHTML = "<p>The plan hasn't been loaded yet.</p>";
+    return;
+  }
+
+  const view = { meals: plan.meals.slice() };
+  pending.forEach((entry) => applyLocally(view, entry));
+  document.getElementById("title").textContent = "Week of " + plan.week_start_date;
+
+  const week = document.getElementById("week");
+  week.replaceChildren();
+  for (const day of plan.days) {
+    const heading = document.createElement("h2");
+    heading.textContent = day.name;
+    const list = document.createElement("ul");
+    for (const type of plan.meal_types) {
+      const meal = view.meals.find((m) => m.date === day.date && m.meal_type === type);
+      if (!meal) continue;
+      const item = document.createElement("li");
+      item.className = meal.pending ? "pending" : "";
+      const text = document.createElement("span");
+      text.textContent = type + ": " + meal.text + (meal.cook ? " (" + meal.cook + ")" : "");
+      const edit = document.createElement("button");
+      edit.textContent = "Edit";
+      edit.onclick = () => fillForm(meal);
+      const remove = document.createElement("button");
+      remove.textContent = "Remove";
+      remove.onclick = () => queue({ op: "remove", meal_type: type, day: meal.day }, day.date);
+      item.append(text, edit, remove);
+      list.append(item);
+    }
+    if (!list.children.length) {
+      const item = document.createElement("li");
+      item.textContent = "Nothing planned";
+      list.append(item);
+    }
+    week.append(heading, list);
+  }
+
+  const days = document.getElementById("day");
+  if (!days.options.length || days.options[0].value !== plan.days[0].date) {
+    days.replaceChildren(...plan.days.map((day) => new Option(day.name, day.date)));
+  }
+  const types = document.getElementById("meal-type");
+  if (!types.options.length) {
+    plan.meal_types.forEach((type) => types.add(new Option(type, type)));
+  }
+}
+
+function fillForm(meal) {
+  document.getElementById("day").value = meal.date;
+  document.getElementById("meal-type").value = meal.meal_type;
+  document.getElementById("description").value = meal.description;
+  document.getElementById("cook").value = meal.cook;
+  document.getElementById("description").focus();
+}
+
+function queue(op, date) {
+  setQueued(queued().concat([{ op: op, date: date }]));
+  render();
+  flush();
+}
+
+// Loads the plan, returning whether the server could be reached
+async function refresh() {
+  let reached = false;
+  try {
+    const response = await fetch("api/plan", { cache: "no-store" });
+    reached = true;
+    if (response.ok) {
+      plan = await response.json();
+      localStorage.setItem(PLAN_KEY, JSON.stringify(plan));
+    }
+  } catch (e) {
+    // Offline; keep showing the last plan seen
+  }
+  render();
+  return reached;
+}
+
+// Sends the queued edits in order. One the server turns down, or for a week that has
+// since been rolled over, is dropped with its reason shown; when the server can't be
+// reached the rest wait for the next try.
+async function flush() {
+  if (flushing) return;
+  flushing = true;
+  try {
+    if (!(await refresh()) || !plan) return;
+    while (queued().length) {
+      const entry = queued()[0];
+      if (!plan.days.some((day) => day.date === entry.date)) {
+        showError("Dropped a change for " + entry.date + ", which is no longer in the plan's week.");
+        setQueued(queued().slice(1));
+        continue;
+      }
+      const op = entry.op;
+      let response;
+      try {
+        response = await fetch("api/ops", {
+          method: "POST",
+          headers: { "Content-Type": "application/x-ndjson" },
+          body: JSON.stringify(op) + "\n",
+        });
+      } catch (e) {
+        break;
+      }
+      if (response.status >= 500) break;
+      showError(response.ok ? "" : await response.text());
+      setQueued(queued().slice(1));
+    }
+    await refresh();
+  } finally {
+    flushing = false;
+  }
+}
+
+document.getElementById("meal").addEventListener("submit", (event) => {
+  event.preventDefault();
+  if (!plan) return;
+  const date = document.getElementById("day").value;
+  const type = document.getElementById("meal-type").value;
+  const cook = document.getElementById("cook").value.trim();
+  const description = document.getElementById("description").value.trim();
+  // A planned meal is edited in its own slot, which may be by weekday or by date, so it
+  // keeps its tags, recipe and the rest. An empty slot gets a new meal.
+  const planned = slot(date, type);
+  if (planned) {
+    const op = { op: "edit", meal_type: type, day: planned.day, description: description };
+    if (cook) op.cook = cook;
+    queue(op, date);
+  } else {
+    const day = plan.days.find((d) => d.date === date).day;
+    queue({ op: "add", meal_type: type, day: day, cook: cook, description: description, replace: true }, date);
+  }
+  document.getElementById("description").value = "";
+});
+
+window.addEventListener("online", flush);
+window.addEventListener("offline", render);
+setInterval(flush, RETRY_MS);
+if ("serviceWorker" in navigator) {
+  navigator.serviceWorker.register("sw.js");
+}
+render();
+flush();
+"##;
+
+const SERVICE_WORKER_JS: &str = r##"// Keeps the app and the last plan seen, so the app opens without the server
+const CACHE = "mealplan-{version}";
+const SHELL = ["./", "app.js", "manifest.webmanifest", "icon.svg"];
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(caches.open(CACHE).then((cache) => cache.addAll(SHELL)));
+  self.skipWaiting();
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches.keys()
+      .then((keys) => Promise.all(keys.filter((key) => key !== CACHE).map((key) => caches.delete(key))))
+      .then(() => self.clients.claim())
+  );
+});
+
+self.addEventListener("fetch", (event) => {
+  const request = event.request;
+  if (request.method !== "GET") return;
+  // The plan comes from the network when it can, and the app from the cache
+  if (new URL(request.url).pathname.endsWith("/api/plan")) {
+    event.respondWith(
+      fetch(request)
+        .then((response) => {
+          const copy = response.clone();
+          caches.open(CACHE).then((cache) => cache.put(request, copy));
+          return response;
+        })
+        .catch(() => caches.match(request))
+    );
+    return;
+  }
+  event.respondWith(caches.match(request).then((cached) => cached || fetch(request)));
+});
+"##;
+
+const MANIFEST: &str = r##"{
+  "name": "Meal Plan",
+  "short_name": "Meals",
+  "start_url": "./",
+  "scope": "./",
+  "display": "standalone",
+  "background_color": "#ffffff",
+  "theme_color": "#2f6f4f",
+  "icons": [{ "src": "icon.svg", "sizes": "any", "type": "image/svg+xml", "purpose": "any maskable" }]
+}
+"##;
+
+const ICON_SVG: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 512 512">
+<rect width="512" height="512" rx="96" fill="#2f6f4f"/>
+<circle cx="256" cy="272" r="140" fill="#fff"/>
+<circle cx="256" cy="272" r="96" fill="none" stroke="#2f6f4f" stroke-width="16"/>
+<rect x="88" y="120" width="20" height="290" rx="10" fill="#fff"/>
+<rect x="404" y="120" width="20" height="290" rx="10" fill="#fff"/>
+</svg>
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Day, Meal, MealType};
+    use chrono::{NaiveDate, Weekday};
+
+    #[test]
+    fn test_asset() {
+        let (content_type, body) = asset("/").unwrap();
+        assert_eq!(content_type, "text/html; charset=utf-8");
+        assert!(body.contains("<link rel=\"manifest\" href=\"manifest.webmanifest\">"));
+        let (_, worker) = asset("/sw.js").unwrap();
+        assert!(worker.contains(&format!("const CACHE = \"mealplan-{}\";", env!("CARGO_PKG_VERSION"))));
+        let (_, manifest) = asset("/manifest.webmanifest").unwrap();
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&manifest).unwrap()["display"], "standalone");
+        assert!(asset("/today").is_none());
+    }
+
+    #[test]
+    fn test_plan_json() {
+        let mut meal_plan = MealPlan::new(NaiveDate::from_ymd_opt(2023, 1, 2).unwrap());
+        meal_plan.add_meal(Meal::new(MealType::Dinner, Day::Weekday(Weekday::Tue), "Alice".to_string(), "Chili".to_string()));
+        meal_plan.add_meal(Meal::new(MealType::Breakfast, Day::Weekday(Weekday::Tue), String::new(), "Oatmeal".to_string()));
+
+        let json = plan_json(&meal_plan);
+        assert_eq!(json["days"].as_array().unwrap().len(), 7);
+        assert_eq!(json["days"][1], json!({ "date": "2023-01-03", "day": "Tuesday", "name": "Tuesday, Jan 3" }));
+        assert_eq!(json["meals"][0]["description"], "Oatmeal");
+        assert_eq!(
+            json["meals"][1],
+            json!({ "meal_type": "Dinner", "date": "2023-01-03", "day": "Tue", "cook": "Alice", "description": "Chili", "text": "Chili" })
+        );
+    }
+}